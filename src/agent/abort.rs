@@ -0,0 +1,108 @@
+//! Stopping a turn in progress
+//!
+//! The UI holds an `AbortHandle` shared with the agent. Each turn gets a
+//! fresh token; aborting cancels it, and the agent loop drops the provider
+//! request (closing the HTTP connection or killing the CLI process) and any
+//! running tools, keeping the reply streamed so far.
+//!
+//! Single tool calls can be cancelled too (`cancel_tool`): the call ends with
+//! an error the model sees, and the turn goes on.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Cancels the agent's current turn, or one of its tool calls, from any thread
+#[derive(Clone, Default)]
+pub struct AbortHandle {
+    token: Arc<Mutex<CancellationToken>>,
+    /// Tokens of running tool calls, by call ID
+    tools: Arc<Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl AbortHandle {
+    /// Stop the turn in progress; does nothing between turns
+    pub fn abort(&self) {
+        self.current().cancel();
+    }
+
+    /// Whether the current (or last) turn was stopped
+    pub fn is_aborted(&self) -> bool {
+        self.current().is_cancelled()
+    }
+
+    /// Token for a new turn, replacing the previous one
+    pub(crate) fn start_turn(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = token.clone();
+        token
+    }
+
+    fn current(&self) -> CancellationToken {
+        self.token.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Stop one running tool call; does nothing once it has finished
+    pub fn cancel_tool(&self, call_id: &str) {
+        if let Some(token) = self.tool_tokens().get(call_id) {
+            token.cancel();
+        }
+    }
+
+    /// Token for a tool call, cancelled with the turn or by `cancel_tool`
+    pub(crate) fn start_tool(&self, call_id: &str) -> CancellationToken {
+        let token = self.current().child_token();
+        self.tool_tokens()
+            .insert(call_id.to_string(), token.clone());
+        token
+    }
+
+    /// Forget a finished tool call's token
+    pub(crate) fn finish_tool(&self, call_id: &str) {
+        self.tool_tokens().remove(call_id);
+    }
+
+    fn tool_tokens(&self) -> std::sync::MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.tools.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_abort_stops_only_the_current_turn() {
+        let handle = AbortHandle::default();
+        let ui = handle.clone();
+
+        let first = handle.start_turn();
+        let pending = first.run_until_cancelled(std::future::pending::<()>());
+        ui.abort();
+        assert_eq!(pending.await, None);
+
+        // A stop that arrived earlier doesn't carry over
+        let second = handle.start_turn();
+        assert!(!second.is_cancelled());
+        ui.abort();
+        assert!(second.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_one_tool() {
+        let handle = AbortHandle::default();
+        let turn = handle.start_turn();
+        let first = handle.start_tool("call_1");
+        let second = handle.start_tool("call_2");
+
+        handle.cancel_tool("call_1");
+        assert!(first.is_cancelled());
+        assert!(!second.is_cancelled());
+        assert!(!turn.is_cancelled());
+
+        // Stopping the turn stops its tools
+        handle.finish_tool("call_1");
+        handle.abort();
+        assert!(second.is_cancelled());
+    }
+}
@@ -0,0 +1,29 @@
+//! Asking the user about tool calls during a streamed turn
+//!
+//! Frontends that stream turns with `chat_stream_with_tools` install a
+//! `ToolApprover`. Before a batch of tool calls runs, the calls that need
+//! approval (`Agent::requires_approval`) are handed to it with a preview of
+//! what they would change, and only the approved ones run; the others are
+//! answered with an error the model can see.
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+use super::providers::ToolCall;
+
+/// A tool call waiting for the user's decision
+#[derive(Debug, Clone)]
+pub struct PendingApproval {
+    pub call: ToolCall,
+    /// Short form of the key argument (path, command, URL)
+    pub detail: Option<String>,
+    /// What the call would change, e.g. a unified diff for file edits
+    pub preview: Option<String>,
+}
+
+/// Decides on pending calls, returning whether each one may run (in order)
+pub type ToolApprover =
+    Arc<dyn Fn(Vec<PendingApproval>) -> BoxFuture<'static, Vec<bool>> + Send + Sync>;
+
+/// Output given to the model for a call the user denied
+pub const DENIED_OUTPUT: &str = "Error: the user denied this tool call";
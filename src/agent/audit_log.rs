@@ -0,0 +1,418 @@
+//! Audit log of provider traffic (`[providers.audit]`)
+//!
+//! Every request a provider sends is written to
+//! `~/.localgpt/agents/<agent>/audit/audit.jsonl` as a `request` record,
+//! followed by a `response` record with the same id once the reply (or
+//! error) is complete. Configured API keys and anything that looks like a
+//! secret are redacted, and image data is replaced by its size. The file is
+//! rotated to `audit.1.jsonl`, `audit.2.jsonl`, ... at `max_file_mb`.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+use super::providers::{
+    GenerationParams, LLMProvider, LLMResponse, LLMResponseContent, Message, Role, StreamResult,
+    ToolCall, ToolSchema, Usage,
+};
+use super::session::{get_state_dir, DEFAULT_AGENT_ID};
+use super::share::redact_secrets;
+use crate::config::{AuditLogConfig, RetryConfig};
+
+/// Serializes writes and rotation across providers in this process
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Rotating JSONL file of provider requests and responses
+pub struct AuditLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    /// Values redacted besides the built-in secret patterns
+    secrets: Vec<String>,
+}
+
+impl AuditLog {
+    pub fn new(dir: PathBuf, config: &AuditLogConfig, secrets: Vec<String>) -> Self {
+        Self {
+            dir,
+            max_bytes: config.max_file_mb.max(1) * 1024 * 1024,
+            max_files: config.max_files,
+            secrets,
+        }
+    }
+
+    /// Log in the configured agent's directory
+    pub fn open(config: &AuditLogConfig, secrets: Vec<String>) -> Result<Self> {
+        let agent_id = config.agent_id.as_deref().unwrap_or(DEFAULT_AGENT_ID);
+        Ok(Self::new(audit_log_dir(agent_id)?, config, secrets))
+    }
+
+    /// Append one record, redacted; failures are only logged
+    pub fn write(&self, record: &Value) {
+        let line = redact_secrets(&record.to_string(), &self.secrets);
+        if let Err(e) = self.append(&line) {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+
+    fn append(&self, line: &str) -> Result<()> {
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(&self.dir)?;
+        let path = self.file(0);
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 >= self.max_bytes {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        Ok(())
+    }
+
+    /// Shift each file up one number, dropping the oldest
+    fn rotate(&self) -> Result<()> {
+        let _ = fs::remove_file(self.file(self.max_files));
+        for n in (0..self.max_files).rev() {
+            let from = self.file(n);
+            if from.exists() {
+                fs::rename(&from, self.file(n + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `audit.jsonl`, or the `n`th rotated file
+    fn file(&self, n: usize) -> PathBuf {
+        match n {
+            0 => self.dir.join("audit.jsonl"),
+            n => self.dir.join(format!("audit.{}.jsonl", n)),
+        }
+    }
+}
+
+/// `~/.localgpt/agents/<agent>/audit` (per profile)
+pub fn audit_log_dir(agent_id: &str) -> Result<PathBuf> {
+    Ok(get_state_dir()?.join("agents").join(agent_id).join("audit"))
+}
+
+/// Provider wrapper that writes every request and response to an `AuditLog`
+pub struct AuditedProvider {
+    inner: Box<dyn LLMProvider>,
+    model: String,
+    params: GenerationParams,
+    log: Arc<AuditLog>,
+}
+
+impl AuditedProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, model: &str, log: AuditLog) -> Self {
+        Self {
+            inner,
+            model: model.to_string(),
+            params: GenerationParams::default(),
+            log: Arc::new(log),
+        }
+    }
+
+    /// Log a request, returning its id
+    fn log_request(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+        stream: bool,
+    ) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.log.write(&json!({
+            "ts": chrono::Utc::now().to_rfc3339(),
+            "id": id,
+            "type": "request",
+            "model": self.model,
+            "stream": stream,
+            "params": self.params,
+            "messages": messages_json(messages),
+            "tools": tools,
+        }));
+        id
+    }
+}
+
+#[async_trait]
+impl LLMProvider for AuditedProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let id = self.log_request(messages, tools, false);
+        let started = Instant::now();
+        let result = self.inner.chat(messages, tools).await;
+        let reply = match &result {
+            Ok(response) => {
+                let (text, tool_calls) = match &response.content {
+                    LLMResponseContent::Text(text) => (text.as_str(), None),
+                    LLMResponseContent::ToolCalls(calls) => ("", Some(calls.as_slice())),
+                };
+                Reply {
+                    text,
+                    reasoning: "",
+                    tool_calls,
+                    usage: response.usage.as_ref(),
+                    error: None,
+                }
+            }
+            Err(e) => Reply {
+                error: Some(e.to_string()),
+                ..Reply::default()
+            },
+        };
+        self.log.write(&reply.record(&id, &self.model, started));
+        result
+    }
+
+    async fn chat_json(&self, prompt: &str, schema: &Value) -> Result<Value> {
+        let request = Message {
+            role: Role::User,
+            content: prompt.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let id = self.log_request(std::slice::from_ref(&request), None, false);
+        let started = Instant::now();
+        let result = self.inner.chat_json(prompt, schema).await;
+        let text = result.as_ref().map(|v| v.to_string()).unwrap_or_default();
+        let reply = Reply {
+            text: &text,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            ..Reply::default()
+        };
+        self.log.write(&reply.record(&id, &self.model, started));
+        result
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+        self.inner.set_generation_params(params);
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.inner.set_retry_policy(policy);
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        self.inner.health_check().await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let id = self.log_request(messages, tools, true);
+        let started = Instant::now();
+        let mut inner = match self.inner.chat_stream(messages, tools).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                let reply = Reply {
+                    error: Some(e.to_string()),
+                    ..Reply::default()
+                };
+                self.log.write(&reply.record(&id, &self.model, started));
+                return Err(e);
+            }
+        };
+
+        // The reply is logged before the final chunk is passed on, since the
+        // caller may drop the stream as soon as it has it
+        let log = Arc::clone(&self.log);
+        let model = self.model.clone();
+        Ok(Box::pin(async_stream::stream! {
+            let mut text = String::new();
+            let mut reasoning = String::new();
+            let mut logged = false;
+            while let Some(chunk) = inner.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        text.push_str(&chunk.delta);
+                        reasoning.push_str(&chunk.reasoning);
+                        if chunk.done {
+                            let reply = Reply {
+                                text: &text,
+                                reasoning: &reasoning,
+                                tool_calls: chunk.tool_calls.as_deref(),
+                                usage: chunk.usage.as_ref(),
+                                error: None,
+                            };
+                            log.write(&reply.record(&id, &model, started));
+                            logged = true;
+                        }
+                        yield Ok(chunk);
+                    }
+                    Err(e) => {
+                        let reply = Reply {
+                            text: &text,
+                            reasoning: &reasoning,
+                            error: Some(e.to_string()),
+                            ..Reply::default()
+                        };
+                        log.write(&reply.record(&id, &model, started));
+                        logged = true;
+                        yield Err(e);
+                    }
+                }
+            }
+            if !logged {
+                let reply = Reply {
+                    text: &text,
+                    reasoning: &reasoning,
+                    ..Reply::default()
+                };
+                log.write(&reply.record(&id, &model, started));
+            }
+        }))
+    }
+}
+
+/// What came back for one request
+#[derive(Default)]
+struct Reply<'a> {
+    text: &'a str,
+    reasoning: &'a str,
+    tool_calls: Option<&'a [ToolCall]>,
+    usage: Option<&'a Usage>,
+    error: Option<String>,
+}
+
+impl Reply<'_> {
+    fn record(&self, id: &str, model: &str, started: Instant) -> Value {
+        json!({
+            "ts": chrono::Utc::now().to_rfc3339(),
+            "id": id,
+            "type": "response",
+            "model": model,
+            "latency_ms": started.elapsed().as_millis() as u64,
+            "text": self.text,
+            "reasoning": (!self.reasoning.is_empty()).then_some(self.reasoning),
+            "tool_calls": self.tool_calls,
+            "usage": self.usage,
+            "error": self.error,
+        })
+    }
+}
+
+/// Messages as JSON, with image data replaced by its size
+fn messages_json(messages: &[Message]) -> Value {
+    let mut value = json!(messages);
+    for message in value.as_array_mut().into_iter().flatten() {
+        for image in message["images"].as_array_mut().into_iter().flatten() {
+            let size = image["data"].as_str().map(str::len).unwrap_or(0);
+            image["data"] = json!(format!("[{} base64 chars]", size));
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::providers::{ImageAttachment, Role, StreamChunk};
+
+    struct EchoProvider;
+
+    #[async_trait]
+    impl LLMProvider for EchoProvider {
+        async fn chat(
+            &self,
+            messages: &[Message],
+            _tools: Option<&[ToolSchema]>,
+        ) -> Result<LLMResponse> {
+            if messages[0].content == "fail" {
+                anyhow::bail!("server error");
+            }
+            Ok(LLMResponse::text(format!("echo {}", messages[0].content)))
+        }
+    }
+
+    fn user(content: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn read_records(dir: &std::path::Path, file: &str) -> Vec<Value> {
+        fs::read_to_string(dir.join(file))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_records_redacts_and_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(
+            dir.path().to_path_buf(),
+            &AuditLogConfig::default(),
+            vec!["my-configured-key".to_string()],
+        );
+        let provider = AuditedProvider::new(Box::new(EchoProvider), "openai/gpt-4o", log);
+
+        let mut message = user("key is my-configured-key, token sk-abcdefghijklmnopqrstuvwx");
+        message.images.push(ImageAttachment {
+            data: "aGVsbG8=".to_string(),
+            media_type: "image/png".to_string(),
+        });
+        provider.chat(&[message], None).await.unwrap();
+        assert!(provider.chat(&[user("fail")], None).await.is_err());
+
+        // Streams are logged before the final chunk reaches the caller
+        let mut stream = provider.chat_stream(&[user("hi")], None).await.unwrap();
+        let chunk: StreamChunk = stream.next().await.unwrap().unwrap();
+        assert!(chunk.done);
+
+        let records = read_records(dir.path(), "audit.jsonl");
+        assert_eq!(records.len(), 6);
+        let text = records[0].to_string();
+        assert!(!text.contains("my-configured-key"));
+        assert!(!text.contains("sk-abcdefghijklmnopqrstuvwx"));
+        assert!(!text.contains("aGVsbG8="));
+        assert_eq!(records[0]["type"], "request");
+        assert_eq!(records[1]["type"], "response");
+        assert_eq!(records[1]["id"], records[0]["id"]);
+        assert!(records[1]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("echo key is"));
+        assert_eq!(records[3]["error"], "server error");
+        assert_eq!(records[4]["stream"], true);
+        assert_eq!(records[5]["text"], "echo hi");
+
+        // Rotation keeps max_files old files
+        let small = AuditLog {
+            dir: dir.path().to_path_buf(),
+            max_bytes: 10,
+            max_files: 2,
+            secrets: Vec::new(),
+        };
+        for n in 0..4 {
+            small.write(&json!({ "n": n }));
+        }
+        assert_eq!(read_records(dir.path(), "audit.jsonl")[0]["n"], 3);
+        assert_eq!(read_records(dir.path(), "audit.1.jsonl")[0]["n"], 2);
+        assert_eq!(read_records(dir.path(), "audit.2.jsonl")[0]["n"], 1);
+        assert!(!dir.path().join("audit.3.jsonl").exists());
+    }
+}
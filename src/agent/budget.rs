@@ -0,0 +1,335 @@
+//! Spending limits per provider (`[budget]`)
+//!
+//! Every model request adds its tokens and estimated cost to the provider's
+//! total for the day in `~/.localgpt/spend.json`. Before a chat turn the
+//! agent checks the provider's daily and monthly limits: past `warn_at` it
+//! warns once per limit and period, and once a limit is reached it refuses
+//! the turn with `BudgetExceededError` until the user overrides it.
+//!
+//! Every agent (server sessions, heartbeat, sub-agents, the desktop) keeps
+//! its own store, so each record re-reads the file under a lock before
+//! adding to it, and each check re-reads it, to count the others' spend.
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use super::session::get_state_dir;
+use crate::config::{BudgetConfig, ProviderBudget};
+
+/// Days of history kept; enough for the current and previous month
+const KEEP_DAYS: i64 = 62;
+
+/// A provider's budget ran out
+///
+/// Returned inside `anyhow::Error` so callers can `downcast_ref` it and
+/// offer to continue anyway.
+#[derive(Debug, Clone)]
+pub struct BudgetExceededError {
+    /// Provider section the budget is for
+    pub provider: String,
+    /// Which limit, e.g. "daily spend $2.04 of $2.00"
+    pub message: String,
+}
+
+impl std::fmt::Display for BudgetExceededError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} budget exceeded: {}", self.provider, self.message)
+    }
+}
+
+impl std::error::Error for BudgetExceededError {}
+
+/// Tokens and estimated cost for one provider on one day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaySpend {
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl DaySpend {
+    fn add(&mut self, other: &DaySpend) {
+        self.tokens += other.tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SpendStore {
+    /// None keeps the records in memory only
+    path: Option<PathBuf>,
+    /// Provider -> day (YYYY-MM-DD) -> spend
+    days: BTreeMap<String, BTreeMap<NaiveDate, DaySpend>>,
+    /// Limits already warned about ("provider/limit/period")
+    warned: HashSet<String>,
+}
+
+impl SpendStore {
+    /// Load the spend file (missing or unreadable starts empty)
+    pub fn load() -> Self {
+        match get_state_dir() {
+            Ok(dir) => Self::at(dir.join("spend.json")),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn at(path: PathBuf) -> Self {
+        let mut store = Self {
+            path: Some(path),
+            ..Default::default()
+        };
+        store.reload();
+        store
+    }
+
+    /// Pick up what other agents recorded since the file was last read
+    fn reload(&mut self) {
+        if let Some(days) = self.path.as_deref().and_then(read_days) {
+            self.days = days;
+        }
+    }
+
+    /// Add one request's tokens and cost to `provider`'s total for `day`
+    pub fn record(&mut self, provider: &str, day: NaiveDate, tokens: u64, cost_usd: f64) {
+        if let Err(e) = self.record_locked(provider, day, &DaySpend { tokens, cost_usd }) {
+            debug!("Failed to save spend: {}", e);
+        }
+    }
+
+    /// Add to the totals as the file has them, holding its lock so records
+    /// from agents in other threads and processes aren't overwritten
+    fn record_locked(&mut self, provider: &str, day: NaiveDate, spend: &DaySpend) -> Result<()> {
+        let lock = match self.path {
+            Some(ref path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let lock = File::create(path.with_extension("lock"))?;
+                lock.lock_exclusive()?;
+                self.reload();
+                Some(lock)
+            }
+            None => None,
+        };
+
+        let days = self.days.entry(provider.to_string()).or_default();
+        days.entry(day).or_default().add(spend);
+        days.retain(|d, _| (day - *d).num_days() < KEEP_DAYS);
+        let saved = self.save();
+        if let Some(lock) = lock {
+            lock.unlock()?;
+        }
+        saved
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.days)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// `provider`'s spend on `day` and in its month
+    pub fn totals(&self, provider: &str, day: NaiveDate) -> (DaySpend, DaySpend) {
+        let mut today = DaySpend::default();
+        let mut month = DaySpend::default();
+        for (d, spend) in self.days.get(provider).into_iter().flatten() {
+            if d.year() == day.year() && d.month() == day.month() {
+                month.add(spend);
+                if *d == day {
+                    today.add(spend);
+                }
+            }
+        }
+        (today, month)
+    }
+
+    /// Check `provider` against its limits on `day`. Errors with
+    /// `BudgetExceededError` once a limit is reached; returns a warning the
+    /// first time a limit passes `warn_at`.
+    pub fn check(
+        &mut self,
+        provider: &str,
+        config: &BudgetConfig,
+        day: NaiveDate,
+    ) -> Result<Option<String>> {
+        let Some(budget) = config.providers.get(provider) else {
+            return Ok(None);
+        };
+        self.reload();
+        let (today, month) = self.totals(provider, day);
+        let mut warning = None;
+        for limit in limits(budget, &today, &month) {
+            if limit.used >= limit.max {
+                return Err(BudgetExceededError {
+                    provider: provider.to_string(),
+                    message: limit.describe(),
+                }
+                .into());
+            }
+            let period = match limit.period {
+                "daily" => day.to_string(),
+                _ => day.format("%Y-%m").to_string(),
+            };
+            let key = format!("{}/{}/{}", provider, limit.name, period);
+            if limit.used >= limit.max * config.warn_at && self.warned.insert(key) {
+                warning.get_or_insert_with(|| {
+                    format!(
+                        "{} has used {:.0}% of its budget: {}",
+                        provider,
+                        limit.used / limit.max * 100.0,
+                        limit.describe()
+                    )
+                });
+            }
+        }
+        Ok(warning)
+    }
+}
+
+/// The spend file's records, if it can be read
+fn read_days(path: &Path) -> Option<BTreeMap<String, BTreeMap<NaiveDate, DaySpend>>> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// One configured limit and how much of it is used
+struct Limit {
+    name: &'static str,
+    period: &'static str,
+    usd: bool,
+    used: f64,
+    max: f64,
+}
+
+impl Limit {
+    fn describe(&self) -> String {
+        if self.usd {
+            format!(
+                "{} spend ${:.2} of ${:.2}",
+                self.period, self.used, self.max
+            )
+        } else {
+            format!(
+                "{} tokens {} of {}",
+                self.period, self.used as u64, self.max as u64
+            )
+        }
+    }
+}
+
+fn limits(budget: &ProviderBudget, today: &DaySpend, month: &DaySpend) -> Vec<Limit> {
+    let usd = |name, period, spend: &DaySpend, max: Option<f64>| {
+        max.map(|max| Limit {
+            name,
+            period,
+            usd: true,
+            used: spend.cost_usd,
+            max,
+        })
+    };
+    let tokens = |name, period, spend: &DaySpend, max: Option<u64>| {
+        max.map(|max| Limit {
+            name,
+            period,
+            usd: false,
+            used: spend.tokens as f64,
+            max: max as f64,
+        })
+    };
+    [
+        usd("daily_usd", "daily", today, budget.daily_usd),
+        usd("monthly_usd", "monthly", month, budget.monthly_usd),
+        tokens("daily_tokens", "daily", today, budget.daily_tokens),
+        tokens("monthly_tokens", "monthly", month, budget.monthly_tokens),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_warns_then_refuses() {
+        let mut config = BudgetConfig::default();
+        config.providers.insert(
+            "openai".to_string(),
+            ProviderBudget {
+                daily_usd: Some(1.0),
+                monthly_tokens: Some(10_000),
+                ..Default::default()
+            },
+        );
+        let day = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        let mut store = SpendStore::default();
+
+        // Other providers and earlier months don't count
+        store.record("anthropic", day, 50_000, 5.0);
+        store.record("openai", day.with_month(2).unwrap(), 50_000, 5.0);
+        assert!(store.check("openai", &config, day).unwrap().is_none());
+        assert!(store.check("anthropic", &config, day).unwrap().is_none());
+
+        store.record("openai", day, 1_000, 0.85);
+        let warning = store.check("openai", &config, day).unwrap().unwrap();
+        assert!(warning.contains("85%"), "{}", warning);
+        // Warned once per limit and period
+        assert!(store.check("openai", &config, day).unwrap().is_none());
+
+        // A new day has a fresh daily budget but the same month
+        let tomorrow = day.succ_opt().unwrap();
+        store.record("openai", tomorrow, 8_500, 0.1);
+        let warning = store.check("openai", &config, tomorrow).unwrap().unwrap();
+        assert!(warning.contains("monthly tokens 9500 of 10000"));
+
+        store.record("openai", tomorrow, 500, 0.1);
+        let error = store.check("openai", &config, tomorrow).unwrap_err();
+        let exceeded = error.downcast_ref::<BudgetExceededError>().unwrap();
+        assert_eq!(exceeded.provider, "openai");
+        assert_eq!(exceeded.message, "monthly tokens 10000 of 10000");
+
+        let json = serde_json::to_string(&store.days).unwrap();
+        assert!(json.contains("\"2025-03-15\""));
+        let days: BTreeMap<String, BTreeMap<NaiveDate, DaySpend>> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(days["openai"][&tomorrow].tokens, 9_000);
+    }
+
+    #[test]
+    fn test_agents_share_the_spend_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("spend.json");
+        let mut config = BudgetConfig::default();
+        config.providers.insert(
+            "openai".to_string(),
+            ProviderBudget {
+                daily_usd: Some(1.0),
+                ..Default::default()
+            },
+        );
+        let day = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+
+        // Both loaded before either recorded anything
+        let mut parent = SpendStore::at(path.clone());
+        let mut child = SpendStore::at(path.clone());
+        child.record("openai", day, 1_000, 0.6);
+        parent.record("openai", day, 1_000, 0.5);
+
+        let days = read_days(&path).unwrap();
+        assert_eq!(days["openai"][&day].tokens, 2_000);
+        // Each sees the other's spend
+        assert!(parent.check("openai", &config, day).is_err());
+        assert!(child.check("openai", &config, day).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! `calendar` and `reminder` tools (`[calendar]`)
+//!
+//! The calendar tool reads and changes events through `crate::calendar`;
+//! adding and deleting show a preview for approval. Reminders go into the
+//! reminder file and are fired by the daemon's or desktop app's scheduler.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Duration, Local, Utc};
+use serde_json::{json, Value};
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+use crate::calendar::{parse_when, Calendar, Delivery, Event, EventTime, ReminderStore};
+use crate::config::Config;
+
+/// Days listed when no end is given
+const DEFAULT_LIST_DAYS: u64 = 7;
+
+/// Length of a timed event without an end
+const DEFAULT_EVENT_MINUTES: i64 = 60;
+
+/// Whether a `calendar` call adds or deletes events (anything but a
+/// list), which the user approves
+pub fn changes_calendar(arguments: &str) -> bool {
+    serde_json::from_str::<Value>(arguments)
+        .map_or(true, |args| args["action"].as_str() != Some("list"))
+}
+
+/// `09:30–10:00`, `2026-10-17 09:30 – 2026-10-18 10:00` or an all-day span
+fn event_span(event: &Event) -> String {
+    match (event.start, event.end) {
+        (EventTime::Date(start), end) => {
+            // DTEND of an all-day event is the day after the last one
+            let last = match end {
+                Some(EventTime::Date(end)) if end > start + Days::new(1) => {
+                    Some(end - Days::new(1))
+                }
+                _ => None,
+            };
+            match last {
+                Some(last) => format!("{} to {} (all day)", start, last),
+                None => format!("{} (all day)", start),
+            }
+        }
+        (EventTime::DateTime(start), _) => {
+            let end = event.end_instant();
+            if end == start {
+                start.format("%Y-%m-%d %H:%M").to_string()
+            } else if end.date_naive() == start.date_naive() {
+                format!("{}–{}", start.format("%Y-%m-%d %H:%M"), end.format("%H:%M"))
+            } else {
+                format!(
+                    "{} – {}",
+                    start.format("%Y-%m-%d %H:%M"),
+                    end.format("%Y-%m-%d %H:%M")
+                )
+            }
+        }
+    }
+}
+
+pub struct CalendarTool {
+    calendar: Calendar,
+    reminders: ReminderStore,
+}
+
+impl CalendarTool {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            calendar: Calendar::from_config(config)?,
+            reminders: ReminderStore::new()?,
+        })
+    }
+
+    /// The event described by `add` arguments
+    fn event(args: &Value) -> Result<Event> {
+        let title = args["title"]
+            .as_str()
+            .filter(|t| !t.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing title"))?;
+        let start = parse_when(
+            args["start"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Missing start"))?,
+        )?;
+        let end = match (args["end"].as_str(), start) {
+            (Some(end), _) => match (parse_when(end)?, start) {
+                // The last day of an all-day event, stored as the day after
+                (EventTime::Date(last), EventTime::Date(_)) => EventTime::Date(last + Days::new(1)),
+                (end, _) => end,
+            },
+            (None, EventTime::Date(date)) => EventTime::Date(date + Days::new(1)),
+            (None, EventTime::DateTime(dt)) => {
+                let minutes = args["duration_minutes"]
+                    .as_i64()
+                    .unwrap_or(DEFAULT_EVENT_MINUTES);
+                EventTime::DateTime(dt + Duration::minutes(minutes))
+            }
+        };
+        if end.instant() < start.instant() {
+            anyhow::bail!("The event ends before it starts");
+        }
+        Ok(Event {
+            uid: format!("{}@localgpt", uuid::Uuid::new_v4()),
+            summary: title.trim().to_string(),
+            start,
+            end: Some(end),
+            location: args["location"].as_str().map(str::to_string),
+            description: args["description"].as_str().map(str::to_string),
+            rrule: None,
+        })
+    }
+
+    async fn list(&self, args: &Value) -> Result<String> {
+        let from = match args["from"].as_str() {
+            Some(from) => parse_when(from)?.instant(),
+            None => EventTime::Date(Local::now().date_naive()).instant(),
+        };
+        let to = match args["to"].as_str() {
+            // Through the end of that day
+            Some(to) => match parse_when(to)? {
+                EventTime::Date(date) => EventTime::Date(date + Days::new(1)).instant(),
+                time => time.instant(),
+            },
+            None => from + Days::new(DEFAULT_LIST_DAYS),
+        };
+        let events = self.calendar.events(from, to).await?;
+        if events.is_empty() {
+            return Ok(format!(
+                "No events from {} to {}",
+                from.format("%Y-%m-%d %H:%M"),
+                to.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        let mut out = format!("{} events:\n", events.len());
+        for event in &events {
+            out.push_str(&format!("- {} · {}", event_span(event), event.summary));
+            if let Some(ref location) = event.location {
+                out.push_str(&format!(" @ {}", location));
+            }
+            out.push_str(&format!(" (uid {})\n", event.uid));
+            if let Some(ref description) = event.description {
+                for line in description.lines().filter(|l| !l.trim().is_empty()) {
+                    out.push_str(&format!("  {}\n", line.trim()));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    async fn add(&self, args: &Value) -> Result<String> {
+        let event = Self::event(args)?;
+        self.calendar.add(&event).await?;
+        let mut out = format!(
+            "Added \"{}\" on {} (uid {})",
+            event.summary,
+            event_span(&event),
+            event.uid
+        );
+        if let Some(minutes) = args["remind_minutes_before"].as_i64() {
+            let due = event.start.instant() - Duration::minutes(minutes);
+            let text = match event.start {
+                EventTime::Date(_) => format!("Today: {}", event.summary),
+                EventTime::DateTime(start) => {
+                    format!("{} at {}", event.summary, start.format("%H:%M"))
+                }
+            };
+            self.reminders
+                .add(&text, due.with_timezone(&Utc), Delivery::Notification)?;
+            out.push_str(&format!(
+                ", with a reminder at {}",
+                due.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        Ok(out)
+    }
+}
+
+#[async_trait]
+impl Tool for CalendarTool {
+    fn name(&self) -> &str {
+        "calendar"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "calendar".to_string(),
+            description: format!(
+                "List, add or delete calendar events ({}). Times are local: YYYY-MM-DD for all-day, YYYY-MM-DD HH:MM otherwise. Today is {}.",
+                self.calendar.describe(),
+                Local::now().format("%A %Y-%m-%d")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["list", "add", "delete"]
+                    },
+                    "from": {
+                        "type": "string",
+                        "description": "list: start of the range (default: today)"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "list: end of the range, a date includes that day (default: 7 days after from)"
+                    },
+                    "title": { "type": "string", "description": "add: event title" },
+                    "start": { "type": "string", "description": "add: start time or date" },
+                    "end": {
+                        "type": "string",
+                        "description": "add: end time, or last day of an all-day event"
+                    },
+                    "duration_minutes": {
+                        "type": "integer",
+                        "description": "add: length when no end is given (default 60)"
+                    },
+                    "location": { "type": "string" },
+                    "description": { "type": "string" },
+                    "remind_minutes_before": {
+                        "type": "integer",
+                        "description": "add: also set a reminder this many minutes before the start"
+                    },
+                    "uid": { "type": "string", "description": "delete: the event's uid from list" }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        match args["action"].as_str() {
+            Some("list") => self.list(&args).await,
+            Some("add") => self.add(&args).await,
+            Some("delete") => {
+                let uid = args["uid"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing uid"))?;
+                let event = self.calendar.delete(uid).await?;
+                Ok(format!(
+                    "Deleted \"{}\" on {}",
+                    event.summary,
+                    event_span(&event)
+                ))
+            }
+            Some(other) => anyhow::bail!("Unknown action '{}'. Use list, add or delete.", other),
+            None => anyhow::bail!("Missing action"),
+        }
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        match args["action"].as_str()? {
+            "add" => {
+                let event = Self::event(&args).ok()?;
+                let mut out = format!("Add event: {}\nWhen: {}", event.summary, event_span(&event));
+                if let Some(ref location) = event.location {
+                    out.push_str(&format!("\nWhere: {}", location));
+                }
+                if let Some(minutes) = args["remind_minutes_before"].as_i64() {
+                    out.push_str(&format!("\nReminder: {} minutes before", minutes));
+                }
+                Some(out)
+            }
+            "delete" => Some(format!("Delete event {}", args["uid"].as_str()?)),
+            _ => None,
+        }
+    }
+}
+
+pub struct ReminderTool {
+    store: ReminderStore,
+}
+
+impl ReminderTool {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            store: ReminderStore::new()?,
+        })
+    }
+
+    fn due(args: &Value) -> Result<DateTime<Local>> {
+        if let Some(minutes) = args["in_minutes"].as_i64() {
+            return Ok(Local::now() + Duration::minutes(minutes.max(0)));
+        }
+        let at = args["at"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Give at or in_minutes"))?;
+        let due = parse_when(at)?.instant();
+        if due < Local::now() - Duration::minutes(1) {
+            anyhow::bail!("{} is in the past", due.format("%Y-%m-%d %H:%M"));
+        }
+        Ok(due)
+    }
+}
+
+#[async_trait]
+impl Tool for ReminderTool {
+    fn name(&self) -> &str {
+        "reminder"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "reminder".to_string(),
+            description: format!(
+                "Set, list or cancel reminders. A reminder pops up as a desktop notification (or a chat message) at its time, even between conversations. The time now is {}.",
+                Local::now().format("%A %Y-%m-%d %H:%M")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["set", "list", "cancel"]
+                    },
+                    "text": { "type": "string", "description": "set: what to remind the user of" },
+                    "at": { "type": "string", "description": "set: local time, YYYY-MM-DD HH:MM" },
+                    "in_minutes": { "type": "integer", "description": "set: instead of at, minutes from now" },
+                    "via": {
+                        "type": "string",
+                        "enum": ["notification", "chat"],
+                        "description": "set: how it reaches the user (default: notification)"
+                    },
+                    "id": { "type": "string", "description": "cancel: the reminder's id from list" }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        match args["action"].as_str() {
+            Some("set") => {
+                let text = args["text"]
+                    .as_str()
+                    .filter(|t| !t.trim().is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("Missing text"))?;
+                let via = match args["via"].as_str() {
+                    Some(via) => Delivery::parse(via).ok_or_else(|| {
+                        anyhow::anyhow!("Unknown via '{}'. Use notification or chat.", via)
+                    })?,
+                    None => Delivery::Notification,
+                };
+                let due = Self::due(&args)?;
+                let reminder = self.store.add(text.trim(), due.with_timezone(&Utc), via)?;
+                Ok(format!(
+                    "Reminder {} set for {}",
+                    reminder.id,
+                    due.format("%Y-%m-%d %H:%M")
+                ))
+            }
+            Some("list") => {
+                let reminders = self.store.list()?;
+                if reminders.is_empty() {
+                    return Ok("No pending reminders".to_string());
+                }
+                Ok(reminders
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "- {} · {} · {}{}",
+                            r.id,
+                            r.due.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                            r.text,
+                            if r.via == Delivery::Chat {
+                                " (chat)"
+                            } else {
+                                ""
+                            }
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Some("cancel") => {
+                let id = args["id"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing id"))?;
+                match self.store.cancel(id)? {
+                    Some(reminder) => Ok(format!("Cancelled reminder: {}", reminder.text)),
+                    None => anyhow::bail!("No pending reminder with id '{}'", id),
+                }
+            }
+            Some(other) => anyhow::bail!("Unknown action '{}'. Use set, list or cancel.", other),
+            None => anyhow::bail!("Missing action"),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Crash checkpoints for in-progress turns
+//!
+//! While a turn streams, the text received so far and any tool calls issued
+//! are written to `<session-id>.partial.json` in the sessions directory every
+//! few seconds. The file is removed when the turn completes; if it is still
+//! there when the session is resumed, the turn was cut short and its partial
+//! response is recovered into the transcript.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::providers::ToolCall;
+
+/// State of a turn that has not finished yet
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialTurn {
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Assistant text streamed so far
+    pub content: String,
+    /// Tool calls issued during the turn
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl PartialTurn {
+    /// Transcript text for a recovered turn
+    pub fn recovered_text(&self) -> String {
+        let mut text = self.content.trim_end().to_string();
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str("[Response interrupted");
+        if !self.tool_calls.is_empty() {
+            let names: Vec<&str> = self.tool_calls.iter().map(|c| c.name.as_str()).collect();
+            text.push_str(&format!("; tool calls issued: {}", names.join(", ")));
+        }
+        text.push(']');
+        text
+    }
+}
+
+/// Path of a session's checkpoint file
+pub fn partial_turn_path(sessions_dir: &Path, session_id: &str) -> PathBuf {
+    sessions_dir.join(format!("{}.partial.json", session_id))
+}
+
+/// Read and remove a leftover checkpoint
+pub fn take_partial_turn(path: &Path) -> Option<PartialTurn> {
+    let json = fs::read_to_string(path).ok()?;
+    let _ = fs::remove_file(path);
+    serde_json::from_str(&json).ok()
+}
+
+/// Writes a turn's progress to disk, at most once per interval
+pub struct TurnCheckpoint {
+    path: PathBuf,
+    interval: Duration,
+    last_write: Instant,
+    dirty: bool,
+    finished: bool,
+    turn: PartialTurn,
+}
+
+impl TurnCheckpoint {
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        Self {
+            path,
+            interval,
+            last_write: Instant::now(),
+            dirty: false,
+            finished: false,
+            turn: PartialTurn::default(),
+        }
+    }
+
+    pub fn push_content(&mut self, delta: &str) {
+        if delta.is_empty() {
+            return;
+        }
+        self.turn.content.push_str(delta);
+        self.dirty = true;
+        if self.last_write.elapsed() >= self.interval {
+            self.flush();
+        }
+    }
+
+    /// Record tool calls; written immediately since tools have side effects
+    pub fn push_tool_calls(&mut self, calls: &[ToolCall]) {
+        self.turn.tool_calls.extend_from_slice(calls);
+        self.dirty = true;
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        if !self.dirty || self.finished {
+            return;
+        }
+        self.turn.updated_at = Some(Utc::now());
+        if let Err(e) = self.write() {
+            warn!("Failed to write turn checkpoint: {}", e);
+        }
+        self.dirty = false;
+        self.last_write = Instant::now();
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash mid-write can't leave a torn file
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&self.turn)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// The turn completed; the saved session supersedes the checkpoint
+    pub fn finish(&mut self) {
+        self.finished = true;
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let path = partial_turn_path(tmp.path(), "abc");
+
+        let mut checkpoint = TurnCheckpoint::new(path.clone(), Duration::from_secs(60));
+        checkpoint.push_content("Looking at the ");
+        assert!(
+            !path.exists(),
+            "content is batched until the interval passes"
+        );
+
+        checkpoint.push_tool_calls(&[ToolCall {
+            id: "1".into(),
+            name: "read_file".into(),
+            arguments: "{}".into(),
+        }]);
+        checkpoint.push_content("logs");
+        checkpoint.flush();
+
+        let turn = take_partial_turn(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(turn.content, "Looking at the logs");
+        assert_eq!(
+            turn.recovered_text(),
+            "Looking at the logs\n\n[Response interrupted; tool calls issued: read_file]"
+        );
+
+        // A finished turn leaves nothing behind
+        let mut checkpoint = TurnCheckpoint::new(path.clone(), Duration::ZERO);
+        checkpoint.push_content("done");
+        assert!(path.exists());
+        checkpoint.finish();
+        assert!(take_partial_turn(&path).is_none());
+    }
+}
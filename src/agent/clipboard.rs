@@ -0,0 +1,244 @@
+//! System clipboard access: the `clipboard` tool (`tools.clipboard`) and
+//! the `/paste` and `/copy` chat commands
+//!
+//! Like screen capture, this shells out to the platform's clipboard
+//! programs (pbpaste/pbcopy, wl-paste/wl-copy, xclip, xsel, PowerShell)
+//! instead of linking a windowing library, so headless builds are
+//! unaffected.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+
+/// Clipboard text kept for the model; the rest is cut
+const MAX_PASTE_CHARS: usize = 100_000;
+
+/// Programs that print the clipboard, in the order they are tried
+fn read_commands() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", vec![])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            vec!["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+        )]
+    } else {
+        let mut commands = vec![
+            ("xclip", vec!["-selection", "clipboard", "-o"]),
+            ("xsel", vec!["--clipboard", "--output"]),
+        ];
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, ("wl-paste", vec!["--no-newline"]));
+        }
+        commands
+    }
+}
+
+/// Programs that set the clipboard from stdin, in the order they are tried
+fn write_commands() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", vec![])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            vec![
+                "-NoProfile",
+                "-Command",
+                "Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+            ],
+        )]
+    } else {
+        let mut commands = vec![
+            ("xclip", vec!["-selection", "clipboard", "-i"]),
+            ("xsel", vec!["--clipboard", "--input"]),
+        ];
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, ("wl-copy", vec![]));
+        }
+        commands
+    }
+}
+
+/// Text on the system clipboard
+pub fn read_clipboard() -> Result<String> {
+    let mut errors = Vec::new();
+    for (program, args) in read_commands() {
+        match Command::new(program).args(&args).output() {
+            Ok(output) if output.status.success() => {
+                debug!("Read clipboard with {}", program);
+                return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+            }
+            Ok(output) => errors.push(format!(
+                "{}: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                errors.push(format!("{}: not installed", program))
+            }
+            Err(e) => errors.push(format!("{}: {}", program, e)),
+        }
+    }
+    anyhow::bail!("Could not read the clipboard ({})", errors.join("; "))
+}
+
+/// Put `text` on the system clipboard
+pub fn write_clipboard(text: &str) -> Result<()> {
+    let mut errors = Vec::new();
+    for (program, args) in write_commands() {
+        // xclip and wl-copy stay in the background to serve the clipboard,
+        // so their output is not waited on
+        let child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                errors.push(format!("{}: not installed", program));
+                continue;
+            }
+            Err(e) => {
+                errors.push(format!("{}: {}", program, e));
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        match child.wait() {
+            Ok(status) if status.success() => {
+                debug!("Wrote clipboard with {}", program);
+                return Ok(());
+            }
+            Ok(status) => errors.push(format!("{}: {}", program, status)),
+            Err(e) => errors.push(format!("{}: {}", program, e)),
+        }
+    }
+    anyhow::bail!("Could not write the clipboard ({})", errors.join("; "))
+}
+
+/// A user message with clipboard text under `instruction` (for `/paste`)
+pub fn paste_message(instruction: &str, text: &str) -> String {
+    let instruction = match instruction.trim() {
+        "" => "Here is text from my clipboard:",
+        instruction => instruction,
+    };
+    format!("{}\n\n{}", instruction, fenced(&clip(text)))
+}
+
+/// `text` in a code fence longer than any backtick run inside it
+fn fenced(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}\n{}\n{}", fence, text.trim_end_matches('\n'), fence)
+}
+
+fn clip(text: &str) -> String {
+    match text.char_indices().nth(MAX_PASTE_CHARS) {
+        Some((end, _)) => format!(
+            "{}\n[Clipboard truncated at {} characters]",
+            &text[..end],
+            MAX_PASTE_CHARS
+        ),
+        None => text.to_string(),
+    }
+}
+
+pub struct ClipboardTool;
+
+#[async_trait]
+impl Tool for ClipboardTool {
+    fn name(&self) -> &str {
+        "clipboard"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "clipboard".to_string(),
+            description: "Read text from the user's clipboard, or copy text (e.g. a finished rewrite) to it so the user can paste it elsewhere.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["read", "write"],
+                        "description": "read returns the clipboard text; write replaces it with text"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Text to copy (write only)"
+                    }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        match args["action"].as_str() {
+            Some("read") => {
+                let text = tokio::task::spawn_blocking(read_clipboard).await??;
+                if text.trim().is_empty() {
+                    Ok("(The clipboard has no text)".to_string())
+                } else {
+                    Ok(clip(&text))
+                }
+            }
+            Some("write") => {
+                let text = args["text"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing text"))?
+                    .to_string();
+                let chars = text.chars().count();
+                tokio::task::spawn_blocking(move || write_clipboard(&text)).await??;
+                Ok(format!("Copied {} characters to the clipboard", chars))
+            }
+            Some(other) => anyhow::bail!("Unknown action '{}'. Use read or write.", other),
+            None => anyhow::bail!("Missing action"),
+        }
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        if args["action"].as_str()? != "write" {
+            return None;
+        }
+        Some(format!(
+            "Copy to the clipboard:\n\n{}",
+            args["text"].as_str()?
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_message() {
+        assert_eq!(
+            paste_message("", "hello\n"),
+            "Here is text from my clipboard:\n\n```\nhello\n```"
+        );
+        // The fence outgrows backticks in the pasted text
+        assert_eq!(
+            paste_message(" Fix the grammar ", "see ```code``` and ````"),
+            "Fix the grammar\n\n`````\nsee ```code``` and ````\n`````"
+        );
+        let long = "é".repeat(MAX_PASTE_CHARS + 5);
+        assert!(paste_message("x", &long).ends_with(&format!(
+            "é\n[Clipboard truncated at {} characters]\n```",
+            MAX_PASTE_CHARS
+        )));
+    }
+}
@@ -0,0 +1,344 @@
+//! `run_code` tool (`[tools.code_interpreter]`)
+//!
+//! Runs a Python or JavaScript snippet in a child process and returns its
+//! output. Each run gets a fresh scratch directory (its working directory,
+//! HOME and TMPDIR, deleted afterwards), an environment with only PATH and
+//! locale, and on Unix its own process group with resource limits: CPU
+//! time, memory, written file size, no core dumps. The whole group is
+//! killed at the timeout. This contains runaway snippets; it does not stop
+//! them reading files or using the network.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+use crate::config::CodeInterpreterConfig;
+
+/// Output kept per stream; the rest is read and dropped
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Largest file a snippet may write
+#[cfg(unix)]
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Python,
+    JavaScript,
+}
+
+impl Language {
+    fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "python" | "python3" | "py" => Ok(Self::Python),
+            "javascript" | "js" | "node" => Ok(Self::JavaScript),
+            other => anyhow::bail!(
+                "Unsupported language '{}'. Use python or javascript.",
+                other
+            ),
+        }
+    }
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::Python => "main.py",
+            Self::JavaScript => "main.js",
+        }
+    }
+}
+
+pub struct CodeInterpreterTool {
+    python: String,
+    node: String,
+    timeout: Duration,
+    memory_mb: u64,
+}
+
+impl CodeInterpreterTool {
+    pub fn new(config: &CodeInterpreterConfig) -> Self {
+        Self {
+            python: config.python.clone(),
+            node: config.node.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            memory_mb: config.memory_mb,
+        }
+    }
+
+    fn command(&self, language: Language, scratch: &Path) -> tokio::process::Command {
+        let script = scratch.join(language.file_name());
+        let mut command = match language {
+            Language::Python => {
+                let mut command = tokio::process::Command::new(&self.python);
+                // Isolated mode: no user site-packages, PYTHON* variables ignored
+                command.arg("-I").arg(script);
+                command
+            }
+            Language::JavaScript => {
+                let mut command = tokio::process::Command::new(&self.node);
+                // V8 reserves far more address space than it uses, so node
+                // gets a heap limit instead of an address space limit
+                if self.memory_mb > 0 {
+                    command.arg(format!("--max-old-space-size={}", self.memory_mb));
+                }
+                command.arg(script);
+                command
+            }
+        };
+        command
+            .current_dir(scratch)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", scratch)
+            .env("TMPDIR", scratch)
+            .env("LANG", "C.UTF-8")
+            .env("PYTHONDONTWRITEBYTECODE", "1")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+
+        #[cfg(unix)]
+        {
+            let memory_bytes = (language == Language::Python && self.memory_mb > 0)
+                .then_some(self.memory_mb << 20);
+            limit_resources(&mut command, self.timeout.as_secs() + 1, memory_bytes);
+        }
+        command
+    }
+
+    async fn run(&self, language: Language, code: &str) -> Result<String> {
+        let scratch = std::env::temp_dir().join(format!("localgpt-code-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&scratch)?;
+        let result = self.run_in(language, code, &scratch).await;
+        if let Err(e) = std::fs::remove_dir_all(&scratch) {
+            debug!("Failed to remove {}: {}", scratch.display(), e);
+        }
+        result
+    }
+
+    async fn run_in(&self, language: Language, code: &str, scratch: &Path) -> Result<String> {
+        std::fs::write(scratch.join(language.file_name()), code)?;
+        let program = match language {
+            Language::Python => &self.python,
+            Language::JavaScript => &self.node,
+        };
+        let mut child = self
+            .command(language, scratch)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", program))?;
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let run = async { tokio::join!(read_capped(stdout), read_capped(stderr), child.wait()) };
+        let (stdout, stderr, status) = match tokio::time::timeout(self.timeout, run).await {
+            Ok(result) => result,
+            Err(_) => {
+                kill_group(&mut child).await;
+                anyhow::bail!("Code timed out after {}s", self.timeout.as_secs());
+            }
+        };
+        let status = status?;
+        Ok(format_output(&stdout, &stderr, status.code()))
+    }
+}
+
+#[async_trait]
+impl Tool for CodeInterpreterTool {
+    fn name(&self) -> &str {
+        "run_code"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "run_code".to_string(),
+            description: format!("Run a Python or JavaScript snippet and return what it prints. Use it for calculations and data wrangling; print results explicitly. Each run starts fresh in an empty scratch directory, with a {}s time limit.", self.timeout.as_secs()),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "code": {
+                        "type": "string",
+                        "description": "The program to run"
+                    },
+                    "language": {
+                        "type": "string",
+                        "enum": ["python", "javascript"],
+                        "description": "Language of the code (default: python)"
+                    }
+                },
+                "required": ["code"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let code = args["code"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing code"))?;
+        let language = Language::parse(args["language"].as_str().unwrap_or("python"))?;
+
+        debug!("Running {:?} snippet ({} bytes)", language, code.len());
+        self.run(language, code).await
+    }
+}
+
+/// Read a pipe to the end, keeping the first `MAX_OUTPUT_BYTES`
+async fn read_capped<R: AsyncRead + Unpin>(pipe: Option<R>) -> (Vec<u8>, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let Some(mut pipe) = pipe else {
+        return (kept, truncated);
+    };
+    let mut buf = [0u8; 8192];
+    loop {
+        match pipe.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = MAX_OUTPUT_BYTES - kept.len();
+                kept.extend_from_slice(&buf[..n.min(room)]);
+                truncated |= n > room;
+            }
+        }
+    }
+    (kept, truncated)
+}
+
+fn format_output(stdout: &(Vec<u8>, bool), stderr: &(Vec<u8>, bool), code: Option<i32>) -> String {
+    let text = |(bytes, truncated): &(Vec<u8>, bool)| {
+        let mut text = String::from_utf8_lossy(bytes).trim_end().to_string();
+        if *truncated {
+            text.push_str("\n[Output truncated]");
+        }
+        text
+    };
+    let stdout = text(stdout);
+    let stderr = text(stderr);
+
+    let mut result = stdout;
+    if !stderr.is_empty() {
+        if !result.is_empty() {
+            result.push_str("\n\n");
+        }
+        result.push_str("STDERR:\n");
+        result.push_str(&stderr);
+    }
+    match code {
+        Some(0) if result.is_empty() => "(No output; print results to see them)".to_string(),
+        Some(0) => result,
+        Some(code) => format!("{}\n\nExit code: {}", result, code)
+            .trim_start()
+            .to_string(),
+        None => format!("{}\n\nKilled (a resource limit was reached)", result)
+            .trim_start()
+            .to_string(),
+    }
+}
+
+/// Run the child in its own process group with CPU, memory and file size
+/// limits
+#[cfg(unix)]
+fn limit_resources(
+    command: &mut tokio::process::Command,
+    cpu_secs: u64,
+    memory_bytes: Option<u64>,
+) {
+    command.process_group(0);
+    // SAFETY: getrlimit and setrlimit are async-signal-safe, and the
+    // closure allocates nothing
+    unsafe {
+        command.pre_exec(move || {
+            let limit = |resource, value: u64| {
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(resource, &mut current) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // Lowering is always allowed; raising the hard limit isn't
+                let value = (value as libc::rlim_t).min(current.rlim_max);
+                let new = libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                };
+                if libc::setrlimit(resource, &new) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            };
+            limit(libc::RLIMIT_CPU, cpu_secs)?;
+            limit(libc::RLIMIT_FSIZE, MAX_FILE_BYTES)?;
+            limit(libc::RLIMIT_CORE, 0)?;
+            if let Some(bytes) = memory_bytes {
+                limit(libc::RLIMIT_AS, bytes)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Kill the child and anything it started
+async fn kill_group(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: signalling a process group we created
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_python() {
+        if std::process::Command::new("python3")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("python3 not found, skipping");
+            return;
+        }
+        let tool = CodeInterpreterTool::new(&CodeInterpreterConfig {
+            enabled: true,
+            timeout_secs: 2,
+            ..Default::default()
+        });
+
+        let out = tool
+            .execute(r#"{"code": "import os\nprint(sum(range(101)))\nprint(os.listdir('.'))"}"#)
+            .await
+            .unwrap();
+        assert_eq!(out, "5050\n['main.py']");
+
+        let out = tool
+            .execute(r#"{"code": "raise ValueError('bad')"}"#)
+            .await
+            .unwrap();
+        assert!(out.starts_with("STDERR:\nTraceback"), "{}", out);
+        assert!(out.ends_with("ValueError: bad\n\nExit code: 1"), "{}", out);
+
+        // Over the 512 MB default memory limit
+        let out = tool
+            .execute(r#"{"code": "x = bytearray(1 << 30)"}"#)
+            .await
+            .unwrap();
+        assert!(out.contains("MemoryError"), "{}", out);
+
+        let err = tool
+            .execute(r#"{"code": "while True: pass"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Code timed out after 2s");
+    }
+}
@@ -0,0 +1,492 @@
+//! `grep_workspace` and `find_files` tools
+//!
+//! Search the workspace the way ripgrep does (the `ignore` crate's walker
+//! and ripgrep's searcher): `.gitignore`, `.ignore` and hidden files are
+//! skipped, binary files are dropped at the first NUL byte, and results
+//! come back as `path:line:text` lines with paths relative to the
+//! workspace, so the agent can find code without reading whole files.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use grep_regex::RegexMatcherBuilder;
+use grep_searcher::{BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use ignore::overrides::OverrideBuilder;
+use ignore::{WalkBuilder, WalkState};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::sandbox::PathSandbox;
+use super::tools::Tool;
+
+/// Matches returned by default and at most
+const DEFAULT_MATCHES: usize = 50;
+const MAX_MATCHES: usize = 500;
+
+/// Paths returned by default and at most
+const DEFAULT_FILES: usize = 200;
+const MAX_FILES: usize = 2000;
+
+/// Lines of context around matches, at most
+const MAX_CONTEXT: usize = 10;
+
+/// Files larger than this are not searched
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Longest line shown in results
+const MAX_LINE_CHARS: usize = 300;
+
+/// Walker over `root` honoring ignore files, limited to files matching
+/// `glob` (gitignore syntax, e.g. `*.rs` or `src/**/*.ts`) when given
+fn walker(workspace: &Path, root: &Path, glob: Option<&str>) -> Result<WalkBuilder> {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .require_git(false)
+        .max_filesize(Some(MAX_FILE_BYTES));
+    if let Some(glob) = glob.filter(|g| !g.trim().is_empty()) {
+        let mut overrides = OverrideBuilder::new(workspace);
+        for pattern in glob.split(',') {
+            overrides.add(pattern.trim())?;
+        }
+        builder.overrides(overrides.build()?);
+    }
+    Ok(builder)
+}
+
+/// `path` relative to the workspace, with `/` separators
+pub(super) fn relative(workspace: &Path, path: &Path) -> String {
+    path.strip_prefix(workspace)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Optional `path` argument, resolved inside the sandbox
+fn search_root(sandbox: &PathSandbox, args: &Value) -> Result<PathBuf> {
+    let workspace = sandbox.workspace();
+    let root = match args["path"].as_str().filter(|p| !p.trim().is_empty()) {
+        Some(path) => sandbox.resolve(path)?,
+        None => workspace.to_path_buf(),
+    };
+    if !root.exists() {
+        anyhow::bail!("{} does not exist", relative(workspace, &root));
+    }
+    Ok(root)
+}
+
+fn limit(args: &Value, default: usize, max: usize) -> usize {
+    args["max_results"]
+        .as_u64()
+        .map_or(default, |n| n as usize)
+        .clamp(1, max)
+}
+
+pub struct GrepWorkspaceTool {
+    sandbox: PathSandbox,
+}
+
+impl GrepWorkspaceTool {
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for GrepWorkspaceTool {
+    fn name(&self) -> &str {
+        "grep_workspace"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "grep_workspace".to_string(),
+            description: "Search file contents in the workspace with a regular expression (ripgrep syntax), skipping gitignored, hidden and binary files. Returns path:line:text for each match; read_file the interesting parts afterwards.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression, e.g. \"fn\\s+load_config\""
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory or file to search (default: the whole workspace)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Only files matching these globs, comma-separated, e.g. \"*.rs\" or \"src/**/*.ts,!*.test.ts\""
+                    },
+                    "case_insensitive": {
+                        "type": "boolean",
+                        "description": "Ignore case (default: false)"
+                    },
+                    "fixed_strings": {
+                        "type": "boolean",
+                        "description": "Treat the pattern as literal text (default: false)"
+                    },
+                    "context": {
+                        "type": "integer",
+                        "description": format!("Lines of context around each match (default: 0, at most {})", MAX_CONTEXT)
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!("Most matches to return (default: {}, at most {})", DEFAULT_MATCHES, MAX_MATCHES)
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let pattern = args["pattern"]
+            .as_str()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing pattern"))?
+            .to_string();
+        let root = search_root(&self.sandbox, &args)?;
+        let workspace = self.sandbox.workspace().to_path_buf();
+
+        debug!("Searching {} for: {}", root.display(), pattern);
+
+        tokio::task::spawn_blocking(move || grep(&workspace, &root, &pattern, &args)).await?
+    }
+}
+
+/// Matches of one search, shared by the walker's threads
+#[derive(Default)]
+struct GrepResults {
+    /// Output lines per file
+    files: Vec<(String, Vec<Line>)>,
+    matches: usize,
+    truncated: bool,
+}
+
+fn grep(workspace: &Path, root: &Path, pattern: &str, args: &Value) -> Result<String> {
+    let matcher = RegexMatcherBuilder::new()
+        .case_insensitive(args["case_insensitive"].as_bool().unwrap_or(false))
+        .fixed_strings(args["fixed_strings"].as_bool().unwrap_or(false))
+        .build(pattern)
+        .map_err(|e| anyhow::anyhow!("Invalid pattern: {}", e))?;
+    let context = args["context"]
+        .as_u64()
+        .map_or(0, |n| n as usize)
+        .min(MAX_CONTEXT);
+    let max_matches = limit(args, DEFAULT_MATCHES, MAX_MATCHES);
+
+    let results = Arc::new(Mutex::new(GrepResults::default()));
+    walker(workspace, root, args["glob"].as_str())?
+        .build_parallel()
+        .run(|| {
+            let matcher = matcher.clone();
+            let results = Arc::clone(&results);
+            let mut searcher = SearcherBuilder::new()
+                .binary_detection(BinaryDetection::quit(0))
+                .line_number(true)
+                .before_context(context)
+                .after_context(context)
+                .build();
+            Box::new(move |entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if !entry.file_type().is_some_and(|t| t.is_file()) {
+                    return WalkState::Continue;
+                }
+                let mut sink = LineCollector {
+                    lines: Vec::new(),
+                    matches: 0,
+                    remaining: max_matches,
+                };
+                if searcher
+                    .search_path(&matcher, entry.path(), &mut sink)
+                    .is_err()
+                    || sink.matches == 0
+                {
+                    return WalkState::Continue;
+                }
+                let mut results = results.lock().unwrap();
+                results.matches += sink.matches;
+                results
+                    .files
+                    .push((relative(workspace, entry.path()), sink.lines));
+                // Enough to show; which files made it depends on timing
+                if results.matches > max_matches {
+                    WalkState::Quit
+                } else {
+                    WalkState::Continue
+                }
+            })
+        });
+
+    let mut results = Arc::try_unwrap(results)
+        .map_err(|_| anyhow::anyhow!("Search still running"))?
+        .into_inner()
+        .unwrap();
+    // Threads finish in any order; report files in path order, then cut
+    // to the limit
+    results.files.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut out = String::new();
+    let mut shown = 0;
+    let mut files = 0;
+    for (path, lines) in &results.files {
+        if shown >= max_matches {
+            results.truncated = true;
+            break;
+        }
+        if context > 0 && files > 0 {
+            out.push_str("--\n");
+        }
+        files += 1;
+        for line in lines {
+            match line {
+                Line::Match(n, text) if shown < max_matches => {
+                    out.push_str(&format!("{}:{}:{}\n", path, n, text));
+                    shown += 1;
+                }
+                Line::Match(..) => {
+                    results.truncated = true;
+                    break;
+                }
+                Line::Context(n, text) => out.push_str(&format!("{}-{}-{}\n", path, n, text)),
+                Line::Break => out.push_str("--\n"),
+            }
+        }
+    }
+
+    if shown == 0 {
+        return Ok(format!("No matches for \"{}\"", pattern));
+    }
+    out.push_str(&format!(
+        "\n{} {} in {} {}",
+        shown,
+        if shown == 1 { "match" } else { "matches" },
+        files,
+        if files == 1 { "file" } else { "files" }
+    ));
+    if results.truncated || results.matches > shown {
+        out.push_str(" (more not shown; narrow the pattern, path or glob)");
+    }
+    Ok(out)
+}
+
+enum Line {
+    Match(u64, String),
+    Context(u64, String),
+    Break,
+}
+
+impl Line {
+    fn text(bytes: &[u8]) -> String {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches(['\r', '\n']);
+        if text.chars().count() > MAX_LINE_CHARS {
+            format!(
+                "{}...",
+                text.chars().take(MAX_LINE_CHARS).collect::<String>()
+            )
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Collects one file's matching and context lines
+struct LineCollector {
+    lines: Vec<Line>,
+    matches: usize,
+    /// Matches after which the file's search stops
+    remaining: usize,
+}
+
+impl Sink for LineCollector {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, _: &Searcher, found: &SinkMatch<'_>) -> Result<bool, Self::Error> {
+        self.lines.push(Line::Match(
+            found.line_number().unwrap_or(0),
+            Line::text(found.bytes()),
+        ));
+        self.matches += 1;
+        Ok(self.matches < self.remaining)
+    }
+
+    fn context(&mut self, _: &Searcher, context: &SinkContext<'_>) -> Result<bool, Self::Error> {
+        self.lines.push(Line::Context(
+            context.line_number().unwrap_or(0),
+            Line::text(context.bytes()),
+        ));
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _: &Searcher) -> Result<bool, Self::Error> {
+        self.lines.push(Line::Break);
+        Ok(true)
+    }
+}
+
+pub struct FindFilesTool {
+    sandbox: PathSandbox,
+}
+
+impl FindFilesTool {
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for FindFilesTool {
+    fn name(&self) -> &str {
+        "find_files"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "find_files".to_string(),
+            description: "Find files in the workspace by name or path glob, skipping gitignored and hidden files. Returns workspace-relative paths.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Globs, comma-separated, e.g. \"*.rs\", \"*config*\" or \"src/**/mod.rs\""
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to search (default: the whole workspace)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!("Most paths to return (default: {}, at most {})", DEFAULT_FILES, MAX_FILES)
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let pattern = args["pattern"]
+            .as_str()
+            .filter(|p| !p.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing pattern"))?
+            .to_string();
+        let root = search_root(&self.sandbox, &args)?;
+        let workspace = self.sandbox.workspace().to_path_buf();
+        let max_files = limit(&args, DEFAULT_FILES, MAX_FILES);
+
+        tokio::task::spawn_blocking(move || find_files(&workspace, &root, &pattern, max_files))
+            .await?
+    }
+}
+
+fn find_files(workspace: &Path, root: &Path, pattern: &str, max_files: usize) -> Result<String> {
+    let mut paths = Vec::new();
+    let mut truncated = false;
+    let mut walker = walker(workspace, root, Some(pattern))?;
+    walker.sort_by_file_path(|a, b| a.cmp(b));
+    for entry in walker.build() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        if paths.len() == max_files {
+            truncated = true;
+            break;
+        }
+        paths.push(relative(workspace, entry.path()));
+    }
+
+    if paths.is_empty() {
+        return Ok(format!("No files match \"{}\"", pattern));
+    }
+    let mut out = paths.join("\n");
+    if truncated {
+        out.push_str(&format!(
+            "\n\n(first {} files; narrow the pattern or path)",
+            max_files
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grep_and_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::create_dir_all(ws.join("src/config")).unwrap();
+        std::fs::create_dir_all(ws.join("target")).unwrap();
+        std::fs::write(ws.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(
+            ws.join("src/main.rs"),
+            "use config::load;\n\nfn main() {\n    let c = load_config();\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            ws.join("src/config/mod.rs"),
+            "pub fn load_config() -> Config {\n    Config::default()\n}\n",
+        )
+        .unwrap();
+        std::fs::write(ws.join("notes.md"), "call load_config() first\n").unwrap();
+        std::fs::write(ws.join("target/out.rs"), "fn load_config() {}\n").unwrap();
+        std::fs::write(ws.join("data.bin"), b"load_config\0\x01\x02").unwrap();
+
+        let grep = GrepWorkspaceTool::new(ws.to_path_buf());
+        let out = grep
+            .execute(r#"{"pattern": "load_config\\(", "glob": "*.rs"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            "src/config/mod.rs:1:pub fn load_config() -> Config {\n\
+             src/main.rs:4:    let c = load_config();\n\
+             \n2 matches in 2 files"
+        );
+
+        let out = grep
+            .execute(r#"{"pattern": "LET", "case_insensitive": true, "context": 1, "path": "src"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            "src/main.rs-3-fn main() {\nsrc/main.rs:4:    let c = load_config();\nsrc/main.rs-5-}\n\
+             \n1 match in 1 file"
+        );
+
+        let out = grep
+            .execute(r#"{"pattern": "load_config", "max_results": 1}"#)
+            .await
+            .unwrap();
+        assert!(
+            out.ends_with("1 match in 1 file (more not shown; narrow the pattern, path or glob)")
+        );
+        assert!(grep
+            .execute(r#"{"pattern": "x", "path": "../elsewhere"}"#)
+            .await
+            .is_err());
+
+        let find = FindFilesTool::new(ws.to_path_buf());
+        let out = find.execute(r#"{"pattern": "*.rs"}"#).await.unwrap();
+        assert_eq!(out, "src/config/mod.rs\nsrc/main.rs");
+        let out = find
+            .execute(r#"{"pattern": "src/**/mod.rs"}"#)
+            .await
+            .unwrap();
+        assert_eq!(out, "src/config/mod.rs");
+    }
+}
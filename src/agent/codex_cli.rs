@@ -0,0 +1,579 @@
+//! OpenAI Codex CLI provider (`codex-cli/<model>`)
+//!
+//! Shells out to `codex exec --json` like `ClaudeCliProvider` does to
+//! `claude -p`. Codex keeps the conversation itself: the thread ID from its
+//! first run is stored in `SessionStore.cli_session_ids` under "codex-cli"
+//! and later turns run `codex exec resume <id>`. Codex has no system prompt
+//! flag, so the system prompt is sent ahead of the first prompt of a thread.
+//! Commands and file changes Codex makes are reported as provider tools.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use super::providers::{
+    build_prompt_from_messages, cli_version, extract_system_prompt, load_cli_session_from_store,
+    save_cli_session_to_store, LLMProvider, LLMResponse, Message, ProviderToolEvent, StreamChunk,
+    StreamResult, ToolSchema, Usage,
+};
+
+/// Provider name for CLI session storage
+const CODEX_CLI_PROVIDER: &str = "codex-cli";
+
+pub struct CodexCliProvider {
+    command: String,
+    /// Empty to use Codex's configured default
+    model: String,
+    /// `--sandbox` policy for commands Codex runs
+    sandbox: String,
+    /// Working directory for CLI execution
+    workspace: PathBuf,
+    /// Session key for the session store (e.g., "main")
+    session_key: String,
+    /// LocalGPT session ID (for session store tracking)
+    localgpt_session_id: String,
+    /// Codex thread ID for multi-turn conversations, shared with streams
+    thread_id: Arc<StdMutex<Option<String>>>,
+}
+
+impl CodexCliProvider {
+    pub fn new(command: &str, model: &str, sandbox: &str, workspace: PathBuf) -> Result<Self> {
+        let session_key = "main".to_string();
+        let existing = load_cli_session_from_store(&session_key, CODEX_CLI_PROVIDER);
+        if let Some(ref id) = existing {
+            debug!("Loaded existing Codex CLI thread: {}", id);
+        }
+
+        Ok(Self {
+            command: command.to_string(),
+            model: model.to_string(),
+            sandbox: sandbox.to_string(),
+            workspace,
+            session_key,
+            localgpt_session_id: uuid::Uuid::new_v4().to_string(),
+            thread_id: Arc::new(StdMutex::new(existing)),
+        })
+    }
+
+    fn current_thread(&self) -> Option<String> {
+        self.thread_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Arguments for one `codex exec` run, resuming `thread` if given
+    fn build_args(&self, messages: &[Message], thread: Option<&str>) -> Vec<String> {
+        let mut args = vec![
+            "exec".to_string(),
+            "--json".to_string(),
+            "--skip-git-repo-check".to_string(),
+            "--sandbox".to_string(),
+            self.sandbox.clone(),
+        ];
+        if thread.is_none() && !self.model.is_empty() {
+            args.push("--model".to_string());
+            args.push(self.model.clone());
+        }
+
+        let prompt = build_prompt_from_messages(messages);
+        match thread {
+            Some(id) => {
+                args.push("resume".to_string());
+                args.push(id.to_string());
+                args.push(prompt);
+            }
+            None => match extract_system_prompt(messages) {
+                Some(system) => args.push(format!("{}\n\n---\n\n{}", system, prompt)),
+                None => args.push(prompt),
+            },
+        }
+        args
+    }
+
+    async fn run(&self, args: &[String]) -> Result<std::process::Output> {
+        debug!(
+            "Codex CLI: {} {:?} (cwd: {:?})",
+            self.command, args, self.workspace
+        );
+        Command::new(&self.command)
+            .args(args)
+            .current_dir(&self.workspace)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to run Codex CLI '{}': {}", self.command, e))
+    }
+
+    /// Remember the thread for the next turn, here and in the session store
+    fn keep_thread(&self, id: &str) {
+        remember_thread(
+            &self.thread_id,
+            &self.session_key,
+            &self.localgpt_session_id,
+            id,
+        );
+    }
+}
+
+fn remember_thread(
+    thread_id: &StdMutex<Option<String>>,
+    session_key: &str,
+    localgpt_session_id: &str,
+    id: &str,
+) {
+    *thread_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+    if let Err(e) =
+        save_cli_session_to_store(session_key, localgpt_session_id, CODEX_CLI_PROVIDER, id)
+    {
+        debug!("Failed to persist Codex CLI thread: {}", e);
+    }
+}
+
+/// Whether Codex failed because the thread to resume is gone
+fn is_missing_thread(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    (stderr.contains("session") || stderr.contains("thread") || stderr.contains("rollout"))
+        && (stderr.contains("not found") || stderr.contains("no such"))
+}
+
+/// What one line of `codex exec --json` output means for the reply
+#[derive(Debug)]
+enum CodexEvent {
+    /// The thread ID, for resuming
+    Thread(String),
+    /// A complete assistant message
+    Text(String),
+    Reasoning(String),
+    Tools(Vec<ProviderToolEvent>),
+    /// The turn finished
+    Done(Option<Usage>),
+    Failed(String),
+    Other,
+}
+
+/// Interpret one event. `started` holds the items already reported as
+/// started, so items that only send `item.completed` still get a start.
+fn parse_codex_event(event: &Value, started: &mut HashSet<String>) -> CodexEvent {
+    match event["type"].as_str().unwrap_or_default() {
+        "thread.started" => match event["thread_id"].as_str() {
+            Some(id) => CodexEvent::Thread(id.to_string()),
+            None => CodexEvent::Other,
+        },
+        "item.started" | "item.updated" | "item.completed" => {
+            let item = &event["item"];
+            let id = item["id"].as_str().unwrap_or_default().to_string();
+            let completed = event["type"] == "item.completed";
+            match item["type"].as_str().unwrap_or_default() {
+                "agent_message" if completed => {
+                    CodexEvent::Text(item["text"].as_str().unwrap_or_default().to_string())
+                }
+                "reasoning" if completed => {
+                    CodexEvent::Reasoning(item["text"].as_str().unwrap_or_default().to_string())
+                }
+                kind @ ("command_execution" | "file_change" | "mcp_tool_call" | "web_search") => {
+                    let mut events = Vec::new();
+                    let (name, detail) = codex_tool(kind, item);
+                    if started.insert(id.clone()) {
+                        events.push(ProviderToolEvent::Started {
+                            id: id.clone(),
+                            name: name.clone(),
+                            detail,
+                        });
+                    }
+                    if completed {
+                        events.push(ProviderToolEvent::Finished {
+                            id,
+                            name,
+                            output: codex_tool_output(kind, item),
+                            is_error: item["status"] == "failed"
+                                || item["exit_code"].as_i64().is_some_and(|code| code != 0),
+                        });
+                    }
+                    if events.is_empty() {
+                        CodexEvent::Other
+                    } else {
+                        CodexEvent::Tools(events)
+                    }
+                }
+                _ => CodexEvent::Other,
+            }
+        }
+        "turn.completed" => {
+            let usage = event.get("usage").map(|u| Usage {
+                input_tokens: u["input_tokens"].as_u64().unwrap_or(0),
+                output_tokens: u["output_tokens"].as_u64().unwrap_or(0),
+                ..Default::default()
+            });
+            CodexEvent::Done(usage)
+        }
+        "turn.failed" => CodexEvent::Failed(
+            event["error"]["message"]
+                .as_str()
+                .unwrap_or("turn failed")
+                .to_string(),
+        ),
+        "error" => CodexEvent::Failed(
+            event["message"]
+                .as_str()
+                .unwrap_or("Unknown CLI error")
+                .to_string(),
+        ),
+        _ => CodexEvent::Other,
+    }
+}
+
+/// Tool name and the detail worth showing for a Codex item
+fn codex_tool(kind: &str, item: &Value) -> (String, Option<String>) {
+    let text = |field: &str| item[field].as_str().map(str::to_string);
+    match kind {
+        "command_execution" => ("Bash".to_string(), text("command")),
+        "file_change" => {
+            let paths: Vec<&str> = item["changes"]
+                .as_array()
+                .map(|changes| changes.iter().filter_map(|c| c["path"].as_str()).collect())
+                .unwrap_or_default();
+            (
+                "Edit".to_string(),
+                (!paths.is_empty()).then(|| paths.join(", ")),
+            )
+        }
+        "mcp_tool_call" => (
+            format!(
+                "{}.{}",
+                item["server"].as_str().unwrap_or("mcp"),
+                item["tool"].as_str().unwrap_or("tool")
+            ),
+            None,
+        ),
+        _ => ("WebSearch".to_string(), text("query")),
+    }
+}
+
+fn codex_tool_output(kind: &str, item: &Value) -> String {
+    match kind {
+        "command_execution" => item["aggregated_output"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        "file_change" => item["changes"]
+            .as_array()
+            .map(|changes| {
+                changes
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{} {}",
+                            c["kind"].as_str().unwrap_or("update"),
+                            c["path"].as_str().unwrap_or_default()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default(),
+        _ => item["status"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn chunk(delta: String, reasoning: String, provider_tools: Vec<ProviderToolEvent>) -> StreamChunk {
+    StreamChunk {
+        delta,
+        done: false,
+        tool_calls: None,
+        tool_call_deltas: Vec::new(),
+        provider_tools,
+        reasoning,
+        usage: None,
+    }
+}
+
+#[async_trait]
+impl LLMProvider for CodexCliProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>, // Ignored - Codex runs its own tools
+    ) -> Result<LLMResponse> {
+        let mut output = None;
+        if let Some(thread) = self.current_thread() {
+            let resumed = self.run(&self.build_args(messages, Some(&thread))).await?;
+            let stderr = String::from_utf8_lossy(&resumed.stderr);
+            if resumed.status.success() || !is_missing_thread(&stderr) {
+                output = Some(resumed);
+            } else {
+                info!("Codex CLI thread {} not found, starting a new one", thread);
+                *self.thread_id.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            }
+        }
+        let output = match output {
+            Some(output) => output,
+            None => self.run(&self.build_args(messages, None)).await?,
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut started = HashSet::new();
+        let mut texts = Vec::new();
+        let mut usage = None;
+        for line in stdout.lines() {
+            let Ok(event) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            match parse_codex_event(&event, &mut started) {
+                CodexEvent::Thread(id) => self.keep_thread(&id),
+                CodexEvent::Text(text) => texts.push(text),
+                CodexEvent::Done(reported) => usage = reported,
+                CodexEvent::Failed(message) => anyhow::bail!("Codex CLI error: {}", message),
+                _ => {}
+            }
+        }
+        if !output.status.success() && texts.is_empty() {
+            anyhow::bail!(
+                "Codex CLI failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let text = texts.join("\n\n");
+        Ok(match usage {
+            Some(usage) => LLMResponse::text_with_usage(text, usage),
+            None => LLMResponse::text(text),
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec![
+            "codex-cli/gpt-5-codex".to_string(),
+            "codex-cli/gpt-5".to_string(),
+        ])
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        cli_version(&self.command).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        // A stale thread shows up as a failed resume; the next turn starts fresh
+        let thread = self.current_thread();
+        let args = self.build_args(messages, thread.as_deref());
+        debug!(
+            "Codex CLI streaming: {} {:?} (cwd: {:?})",
+            self.command, args, self.workspace
+        );
+
+        let mut child = Command::new(&self.command)
+            .args(&args)
+            .current_dir(&self.workspace)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn Codex CLI: {}", e))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to capture stdout"))?;
+
+        let thread_id = Arc::clone(&self.thread_id);
+        let session_key = self.session_key.clone();
+        let localgpt_session_id = self.localgpt_session_id.clone();
+
+        let stream = async_stream::stream! {
+            let mut lines = BufReader::new(stdout).lines();
+            let mut started = HashSet::new();
+            let mut sent_text = false;
+            let mut finished = false;
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(event) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                match parse_codex_event(&event, &mut started) {
+                    CodexEvent::Thread(id) => {
+                        remember_thread(&thread_id, &session_key, &localgpt_session_id, &id);
+                    }
+                    CodexEvent::Text(text) => {
+                        // Separate messages the way `chat` joins them
+                        let delta = if sent_text { format!("\n\n{}", text) } else { text };
+                        sent_text = true;
+                        yield Ok(chunk(delta, String::new(), Vec::new()));
+                    }
+                    CodexEvent::Reasoning(text) => {
+                        yield Ok(chunk(String::new(), text, Vec::new()));
+                    }
+                    CodexEvent::Tools(events) => {
+                        yield Ok(chunk(String::new(), String::new(), events));
+                    }
+                    CodexEvent::Done(usage) => {
+                        finished = true;
+                        yield Ok(StreamChunk {
+                            done: true,
+                            usage,
+                            ..chunk(String::new(), String::new(), Vec::new())
+                        });
+                    }
+                    CodexEvent::Failed(message) => {
+                        yield Err(anyhow::anyhow!("Codex CLI error: {}", message));
+                    }
+                    CodexEvent::Other => {}
+                }
+            }
+
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    let mut error_buf = String::new();
+                    if let Some(mut stderr) = child.stderr.take() {
+                        use tokio::io::AsyncReadExt;
+                        let _ = stderr.read_to_string(&mut error_buf).await;
+                    }
+                    if is_missing_thread(&error_buf) {
+                        *thread_id.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                    }
+                    if !finished {
+                        yield Err(anyhow::anyhow!("Codex CLI failed: {}", error_buf.trim()));
+                    }
+                }
+                Err(e) => {
+                    yield Err(anyhow::anyhow!("Failed to wait for CLI process: {}", e));
+                }
+                _ => {}
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::providers::Role;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_codex_events() {
+        let mut started = HashSet::new();
+        assert!(matches!(
+            parse_codex_event(
+                &json!({"type": "thread.started", "thread_id": "0199a213"}),
+                &mut started
+            ),
+            CodexEvent::Thread(ref id) if id == "0199a213"
+        ));
+
+        let command = json!({"type": "item.started", "item": {
+            "id": "item_1", "type": "command_execution",
+            "command": "bash -lc ls", "aggregated_output": "", "status": "in_progress"
+        }});
+        assert!(matches!(
+            parse_codex_event(&command, &mut started),
+            CodexEvent::Tools(ref events) if matches!(
+                &events[..],
+                [ProviderToolEvent::Started { name, detail: Some(d), .. }]
+                    if name == "Bash" && d == "bash -lc ls"
+            )
+        ));
+        let done = json!({"type": "item.completed", "item": {
+            "id": "item_1", "type": "command_execution", "command": "bash -lc ls",
+            "aggregated_output": "src\n", "exit_code": 1, "status": "failed"
+        }});
+        match parse_codex_event(&done, &mut started) {
+            CodexEvent::Tools(events) => assert_eq!(
+                events,
+                vec![ProviderToolEvent::Finished {
+                    id: "item_1".to_string(),
+                    name: "Bash".to_string(),
+                    output: "src\n".to_string(),
+                    is_error: true,
+                }]
+            ),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // File changes only arrive completed, so they start and finish at once
+        let change = json!({"type": "item.completed", "item": {
+            "id": "item_2", "type": "file_change", "status": "completed",
+            "changes": [{"path": "src/main.rs", "kind": "update"}]
+        }});
+        match parse_codex_event(&change, &mut started) {
+            CodexEvent::Tools(events) => {
+                assert_eq!(events.len(), 2);
+                assert!(matches!(
+                    &events[1],
+                    ProviderToolEvent::Finished { output, is_error: false, .. }
+                        if output == "update src/main.rs"
+                ));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_codex_event(
+                &json!({"type": "item.completed", "item": {"id": "item_3", "type": "agent_message", "text": "Done."}}),
+                &mut started
+            ),
+            CodexEvent::Text(ref text) if text == "Done."
+        ));
+        match parse_codex_event(
+            &json!({"type": "turn.completed", "usage": {"input_tokens": 120, "cached_input_tokens": 0, "output_tokens": 30}}),
+            &mut started,
+        ) {
+            CodexEvent::Done(Some(usage)) => assert_eq!(usage.total(), 150),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            parse_codex_event(
+                &json!({"type": "turn.failed", "error": {"message": "usage limit"}}),
+                &mut started
+            ),
+            CodexEvent::Failed(ref message) if message == "usage limit"
+        ));
+    }
+
+    #[test]
+    fn test_codex_args_resume_thread() {
+        let provider = CodexCliProvider {
+            command: "codex".to_string(),
+            model: "gpt-5-codex".to_string(),
+            sandbox: "workspace-write".to_string(),
+            workspace: PathBuf::from("/tmp"),
+            session_key: "main".to_string(),
+            localgpt_session_id: "s".to_string(),
+            thread_id: Arc::new(StdMutex::new(None)),
+        };
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let messages = vec![
+            message(Role::System, "Be brief."),
+            message(Role::User, "hi"),
+        ];
+
+        let new = provider.build_args(&messages, None);
+        assert!(new.windows(2).any(|w| w == ["--model", "gpt-5-codex"]));
+        assert!(!new.contains(&"resume".to_string()));
+        assert!(new.last().unwrap().starts_with("Be brief."));
+
+        let resumed = provider.build_args(&messages, Some("0199a213"));
+        assert!(!resumed.contains(&"--model".to_string()));
+        assert_eq!(&resumed[resumed.len() - 3..], ["resume", "0199a213", "hi"]);
+
+        assert!(is_missing_thread("Error: thread/session not found"));
+        assert!(!is_missing_thread("rate limited"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Context inspector
+//!
+//! Shows the exact message array the next turn will send (after compaction,
+//! with memory context, pinned files, and session instructions already
+//! applied), split into segments with a token count for each, so users can
+//! see why the model is missing information.
+
+use super::providers::{Message, Role, ToolSchema};
+use super::tokens::TokenCounter;
+
+/// One message of the request, as it will be sent
+#[derive(Debug, Clone)]
+pub struct ContextSegment {
+    pub role: Role,
+    pub label: String,
+    pub tokens: usize,
+    pub content: String,
+}
+
+/// Everything the next request will contain
+#[derive(Debug, Clone, Default)]
+pub struct ContextReport {
+    pub model: String,
+    /// How the counts were made
+    pub tokenizer: &'static str,
+    pub segments: Vec<ContextSegment>,
+    /// Tool definitions sent alongside the messages
+    pub tool_count: usize,
+    pub tool_tokens: usize,
+    /// Context window minus the response reserve
+    pub usable_tokens: usize,
+}
+
+impl ContextReport {
+    pub fn build(
+        model: &str,
+        counter: TokenCounter,
+        messages: &[Message],
+        tools: &[ToolSchema],
+        usable: usize,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            tokenizer: counter.name(),
+            segments: messages.iter().map(|m| segment(m, counter)).collect(),
+            tool_count: tools.len(),
+            tool_tokens: counter.tools(tools),
+            usable_tokens: usable,
+        }
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.segments.iter().map(|s| s.tokens).sum::<usize>() + self.tool_tokens
+    }
+
+    /// Plain-text rendering with each segment's full content
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "Next request to {}: {} messages + {} tool definitions, ~{} of {} usable tokens ({})\n",
+            self.model,
+            self.segments.len(),
+            self.tool_count,
+            self.total_tokens(),
+            self.usable_tokens,
+            self.tokenizer
+        );
+        for (i, segment) in self.segments.iter().enumerate() {
+            out.push_str(&format!(
+                "\n--- [{}] {} (~{} tokens) ---\n{}\n",
+                i + 1,
+                segment.label,
+                segment.tokens,
+                segment.content
+            ));
+        }
+        out.push_str(&format!(
+            "\n--- Tool definitions: {} (~{} tokens) ---\n",
+            self.tool_count, self.tool_tokens
+        ));
+        out
+    }
+}
+
+fn segment(message: &Message, counter: TokenCounter) -> ContextSegment {
+    let mut content = message.content.clone();
+    let mut label = match message.role {
+        Role::System => "System".to_string(),
+        Role::User => "User".to_string(),
+        Role::Assistant => "Assistant".to_string(),
+        Role::Tool => match message.tool_call_id {
+            Some(ref id) => format!("Tool result ({})", id),
+            None => "Tool result".to_string(),
+        },
+    };
+
+    if let Some(ref calls) = message.tool_calls {
+        let names: Vec<&str> = calls.iter().map(|c| c.name.as_str()).collect();
+        label.push_str(&format!(" → {}", names.join(", ")));
+        for call in calls {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(&format!("{}({})", call.name, call.arguments));
+        }
+    }
+    if !message.images.is_empty() {
+        label.push_str(&format!(" + {} image(s)", message.images.len()));
+    }
+
+    ContextSegment {
+        role: message.role,
+        label,
+        tokens: counter.message(message),
+        content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ToolCall;
+
+    fn message(role: Role, content: &str) -> Message {
+        Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_report_segments_and_totals() {
+        let mut call = message(Role::Assistant, "");
+        call.tool_calls = Some(vec![ToolCall {
+            id: "c1".into(),
+            name: "read_file".into(),
+            arguments: r#"{"path":"a.md"}"#.into(),
+        }]);
+        let mut result = message(Role::Tool, "file body");
+        result.tool_call_id = Some("c1".into());
+
+        let messages = vec![
+            message(Role::System, "You are helpful."),
+            message(Role::User, "Read a.md"),
+            call,
+            result,
+        ];
+        let tools = vec![ToolSchema {
+            name: "read_file".into(),
+            description: "Read a file".into(),
+            parameters: serde_json::json!({}),
+        }];
+
+        let report =
+            ContextReport::build("gpt-4o", TokenCounter::default(), &messages, &tools, 1000);
+        assert_eq!(report.segments.len(), 4);
+        assert_eq!(report.segments[2].label, "Assistant → read_file");
+        assert_eq!(report.segments[2].content, r#"read_file({"path":"a.md"})"#);
+        assert_eq!(report.segments[3].label, "Tool result (c1)");
+        assert_eq!(
+            report.total_tokens(),
+            report.segments.iter().map(|s| s.tokens).sum::<usize>() + report.tool_tokens
+        );
+        assert!(report
+            .to_text()
+            .contains("--- [2] User (~3 tokens) ---\nRead a.md\n"));
+    }
+}
@@ -0,0 +1,507 @@
+//! Line diffs in unified format: producing them for previews of file
+//! changes, and applying the ones models write (`apply_patch`)
+
+use anyhow::Result;
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Largest changed region (old lines × new lines) diffed line by line;
+/// bigger ones are shown as all old lines removed and all new ones added
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Unified diff from `old` to `new`, with `path` in the header. Empty if
+/// the texts have the same lines.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old, &new);
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], Line::Same(_)))
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let mut group_start = 0;
+    for i in 1..=changes.len() {
+        // A hunk ends where the next change is too far away to share context
+        if i < changes.len() && changes[i] - changes[i - 1] <= 2 * CONTEXT_LINES {
+            continue;
+        }
+        let start = changes[group_start].saturating_sub(CONTEXT_LINES);
+        let end = (changes[i - 1] + CONTEXT_LINES + 1).min(lines.len());
+        write_hunk(&mut out, &lines, start, end);
+        group_start = i;
+    }
+    out
+}
+
+/// Append lines `start..end` as one hunk
+fn write_hunk(out: &mut String, lines: &[Line], start: usize, end: usize) {
+    let old_before = lines[..start]
+        .iter()
+        .filter(|l| !matches!(l, Line::Added(_)))
+        .count();
+    let new_before = lines[..start]
+        .iter()
+        .filter(|l| !matches!(l, Line::Removed(_)))
+        .count();
+    let hunk = &lines[start..end];
+    let old_len = hunk.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+    let new_len = hunk
+        .iter()
+        .filter(|l| !matches!(l, Line::Removed(_)))
+        .count();
+    // An empty side is numbered by the line before it
+    let first = |before: usize, len: usize| if len == 0 { before } else { before + 1 };
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        first(old_before, old_len),
+        old_len,
+        first(new_before, new_len),
+        new_len
+    ));
+    for line in hunk {
+        let (marker, text) = match line {
+            Line::Same(text) => (' ', text),
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+        out.push(marker);
+        out.push_str(text);
+        out.push('\n');
+    }
+}
+
+/// Every line of both texts, in order, marked as kept, removed or added
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|l| Line::Same(l)).collect();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        lines.extend(old_mid.iter().map(|l| Line::Removed(l)));
+        lines.extend(new_mid.iter().map(|l| Line::Added(l)));
+    } else {
+        lines.extend(lcs_diff(old_mid, new_mid));
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+    lines
+}
+
+/// Diff by longest common subsequence, removals before additions
+fn lcs_diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let width = new.len() + 1;
+    // common[i * width + j]: longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| Line::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| Line::Added(l)));
+    lines
+}
+
+/// Context lines at either end of a hunk that may be dropped to find a
+/// place for it, as `patch` does with its fuzz factor
+const MAX_FUZZ: usize = 2;
+
+/// One file's part of a unified diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    /// Path before the change; None for a new file (`/dev/null`)
+    pub old_path: Option<String>,
+    /// Path after the change; None for a deleted file
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// The `@@ ... @@` line
+    pub header: String,
+    /// Where the hunk starts in the old file (1-based; 0 when the header
+    /// has no line numbers)
+    old_start: usize,
+    /// Marker (' ', '-' or '+') and text of each line
+    lines: Vec<(char, String)>,
+}
+
+impl Hunk {
+    /// Lines the hunk expects in the file (context and removed)
+    fn old_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .filter(|(marker, _)| *marker != '+')
+            .map(|(_, text)| text.as_str())
+    }
+
+    /// Context lines before the first change and after the last
+    fn outer_context(&self) -> (usize, usize) {
+        let front = self.lines.iter().take_while(|(m, _)| *m == ' ').count();
+        let back = self
+            .lines
+            .iter()
+            .rev()
+            .take_while(|(m, _)| *m == ' ')
+            .count();
+        if front == self.lines.len() {
+            (front, 0)
+        } else {
+            (front, back)
+        }
+    }
+}
+
+/// A hunk that found no place in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    /// 1-based position of the hunk in its file's patch
+    pub hunk: usize,
+    pub header: String,
+    /// The lines the hunk expected to find
+    pub expected: Vec<String>,
+    /// Line the hunk was expected at (1-based, 0 when unknown)
+    pub near_line: usize,
+}
+
+/// A file's contents after applying hunks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patched {
+    pub content: String,
+    /// Hunks placed only by ignoring whitespace or outer context
+    pub fuzzy: usize,
+    pub rejected: Vec<Rejection>,
+}
+
+/// Split a unified diff (as from `git diff` or `diff -u`) into files and
+/// hunks. Line counts in hunk headers are ignored, since models often get
+/// them wrong: a hunk runs until the next hunk or file header.
+pub fn parse_patch(patch: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let is_file_header = |i: usize| {
+        lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+    };
+
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if is_file_header(i) {
+            files.push(FilePatch {
+                old_path: patch_path(&lines[i][4..]),
+                new_path: patch_path(&lines[i + 1][4..]),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+        if !lines[i].starts_with("@@") {
+            // `diff --git`, `index`, or commentary around the patch
+            i += 1;
+            continue;
+        }
+
+        let file = files.last_mut().ok_or_else(|| {
+            anyhow::anyhow!("Hunk without a file header (--- a/path, +++ b/path) before it")
+        })?;
+        let mut hunk = Hunk {
+            header: lines[i].to_string(),
+            old_start: hunk_old_start(lines[i]),
+            lines: Vec::new(),
+        };
+        let mut blank_tail = 0;
+        i += 1;
+        while i < lines.len()
+            && !lines[i].starts_with("@@")
+            && !lines[i].starts_with("diff ")
+            && !is_file_header(i)
+        {
+            let line = lines[i];
+            match line.chars().next() {
+                Some(marker @ (' ' | '-' | '+')) => {
+                    hunk.lines.push((marker, line[1..].to_string()));
+                    blank_tail = 0;
+                }
+                // An empty context line whose leading space was lost
+                None => {
+                    hunk.lines.push((' ', String::new()));
+                    blank_tail += 1;
+                }
+                // "\ No newline at end of file"
+                Some('\\') => {}
+                Some(_) => break,
+            }
+            i += 1;
+        }
+        // Blank lines after the last hunk are usually just the end of the message
+        hunk.lines.truncate(hunk.lines.len() - blank_tail);
+        file.hunks.push(hunk);
+    }
+
+    if files.is_empty() {
+        anyhow::bail!("No file headers (--- a/path, +++ b/path) found in the patch");
+    }
+    Ok(files)
+}
+
+/// Path from a `---`/`+++` line: timestamp dropped, `a/`/`b/` prefix
+/// removed, None for `/dev/null`
+fn patch_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Old start line from `@@ -12,5 +12,6 @@`, 0 if missing
+fn hunk_old_start(header: &str) -> usize {
+    header
+        .trim_start_matches('@')
+        .trim_start()
+        .strip_prefix('-')
+        .and_then(|rest| {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .unwrap_or(0)
+}
+
+/// How strictly a hunk's lines are compared with the file's
+#[derive(Clone, Copy, PartialEq)]
+enum Compare {
+    Exact,
+    /// Trailing whitespace ignored
+    TrimEnd,
+    /// Leading and trailing whitespace ignored
+    Trim,
+}
+
+impl Compare {
+    fn eq(self, a: &str, b: &str) -> bool {
+        match self {
+            Self::Exact => a == b,
+            Self::TrimEnd => a.trim_end() == b.trim_end(),
+            Self::Trim => a.trim() == b.trim(),
+        }
+    }
+}
+
+/// Apply `hunks` to `original` in order. Each hunk goes where its lines
+/// match nearest to where its header (shifted by earlier hunks) says,
+/// below the previous hunk; failing that, ignoring whitespace, then with
+/// up to `MAX_FUZZ` context lines dropped from each end. Hunks that fit
+/// nowhere are rejected and the rest still applied.
+pub fn apply_hunks(original: &str, hunks: &[Hunk]) -> Patched {
+    let mut lines: Vec<String> = original.lines().map(String::from).collect();
+    let mut patched = Patched {
+        content: String::new(),
+        fuzzy: 0,
+        rejected: Vec::new(),
+    };
+    // Later hunks go below earlier ones
+    let mut floor = 0;
+    let mut shift: isize = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected = if hunk.old_start == 0 {
+            floor
+        } else {
+            ((hunk.old_start as isize - 1 + shift).max(0) as usize).max(floor)
+        };
+        let Some((start, front, back, fuzzy)) = locate(&lines, hunk, floor, expected) else {
+            patched.rejected.push(Rejection {
+                hunk: index + 1,
+                header: hunk.header.clone(),
+                expected: hunk.old_lines().map(String::from).collect(),
+                near_line: hunk.old_start,
+            });
+            continue;
+        };
+
+        // Context lines keep the file's text, even when matched loosely
+        let mut cursor = start;
+        let mut replacement = Vec::new();
+        for (marker, text) in &hunk.lines[front..hunk.lines.len() - back] {
+            match marker {
+                ' ' => {
+                    replacement.push(lines[cursor].clone());
+                    cursor += 1;
+                }
+                '-' => cursor += 1,
+                _ => replacement.push(text.clone()),
+            }
+        }
+        shift += replacement.len() as isize - (cursor - start) as isize;
+        floor = start + replacement.len();
+        lines.splice(start..cursor, replacement);
+        if fuzzy {
+            patched.fuzzy += 1;
+        }
+    }
+
+    if !lines.is_empty() {
+        patched.content = lines.join("\n");
+        // Keep the original's final newline (new files get one)
+        if original.is_empty() || original.ends_with('\n') {
+            patched.content.push('\n');
+        }
+    }
+    patched
+}
+
+/// Where a hunk's old lines start in `lines`, with the outer context lines
+/// dropped from its front and back, and whether matching was loose
+fn locate(
+    lines: &[String],
+    hunk: &Hunk,
+    floor: usize,
+    expected: usize,
+) -> Option<(usize, usize, usize, bool)> {
+    let (front_context, back_context) = hunk.outer_context();
+    let old: Vec<&str> = hunk.old_lines().collect();
+    for fuzz in 0..=MAX_FUZZ {
+        let front = front_context.min(fuzz);
+        let back = back_context.min(fuzz);
+        if fuzz > 0 && front + back == 0 {
+            break;
+        }
+        let window = &old[front..old.len() - back];
+        if window.is_empty() {
+            // Only additions: trust the line number
+            return Some((expected.min(lines.len()), front, back, fuzz > 0));
+        }
+        for compare in [Compare::Exact, Compare::TrimEnd, Compare::Trim] {
+            if let Some(start) = find_window(lines, window, floor, expected + front, compare) {
+                let loose = fuzz > 0 || compare != Compare::Exact;
+                return Some((start, front, back, loose));
+            }
+        }
+    }
+    None
+}
+
+/// Start of `window` in `lines` at or after `floor`, nearest `near`
+fn find_window(
+    lines: &[String],
+    window: &[&str],
+    floor: usize,
+    near: usize,
+    compare: Compare,
+) -> Option<usize> {
+    if lines.len() < window.len() || floor > lines.len() - window.len() {
+        return None;
+    }
+    let last = lines.len() - window.len();
+    let near = near.clamp(floor, last);
+    let matches = |start: usize| {
+        window
+            .iter()
+            .enumerate()
+            .all(|(i, line)| compare.eq(&lines[start + i], line))
+    };
+    for distance in 0..=(last - floor) {
+        if near + distance <= last && matches(near + distance) {
+            return Some(near + distance);
+        }
+        if distance > 0 && near >= floor + distance && matches(near - distance) {
+            return Some(near - distance);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(unified_diff("a.txt", "same\n", "same\n"), "");
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            unified_diff("a.txt", old, new),
+            "--- a/a.txt\n+++ b/a.txt\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+
+        // A new file
+        assert_eq!(
+            unified_diff("new.txt", "", "a\nb\n"),
+            "--- a/new.txt\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_hunks() {
+        let old = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n\nfn c() {\n    3\n}\n";
+        // Wrong line numbers and counts, lost indentation, a changed outer
+        // context line, and a header without numbers
+        let patch = "Here is the fix:\n\
+                     diff --git a/lib.rs b/lib.rs\n--- a/lib.rs\n+++ b/lib.rs\n\
+                     @@ -1,3 +1,3 @@\n fn b() {\n-    2\n+    20\n }\n\
+                     @@ -20,9 +20,9 @@\n fn c() {\n-3\n+    30\n WRONG\n\
+                     @@ @@\n-fn missing() {}\n\n";
+        let files = parse_patch(patch).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].old_path.as_deref(), Some("lib.rs"));
+        assert_eq!(files[0].hunks.len(), 3);
+
+        let patched = apply_hunks(old, &files[0].hunks);
+        assert_eq!(
+            patched.content,
+            "fn a() {\n    1\n}\n\nfn b() {\n    20\n}\n\nfn c() {\n    30\n}\n"
+        );
+        assert_eq!(patched.fuzzy, 1);
+        assert_eq!(patched.rejected.len(), 1);
+        assert_eq!(patched.rejected[0].hunk, 3);
+        assert_eq!(patched.rejected[0].expected, vec!["fn missing() {}"]);
+
+        assert!(parse_patch("@@ -1 +1 @@\n-a\n+b\n").is_err());
+    }
+}
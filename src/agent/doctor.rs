@@ -0,0 +1,146 @@
+//! Provider diagnostics for `/doctor` and `localgpt doctor`
+//!
+//! Every configured provider is checked concurrently: HTTP providers are
+//! asked for their models (which also validates the API key), each Ollama
+//! host is probed, and the Claude and Codex CLIs are run with `--version`.
+
+use std::time::{Duration, Instant};
+
+use super::ollama_hosts::{probe_hosts, OllamaHostStatus};
+use super::providers::{create_provider, resolve_provider, ProviderAuthError};
+use crate::config::Config;
+
+/// Longest wait for one provider's check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Outcome of checking one provider
+#[derive(Debug, Clone)]
+pub struct ProviderHealth {
+    /// Config section, or `ollama@<host>` for each Ollama host
+    pub provider: String,
+    pub ok: bool,
+    /// Round trip of the check; None if it failed
+    pub latency_ms: Option<u64>,
+    /// What the provider reported, or why the check failed
+    pub detail: String,
+}
+
+/// Check every configured provider
+pub async fn run_health_checks(config: &Config) -> Vec<ProviderHealth> {
+    let providers = &config.providers;
+    // The CLI is the fallback provider, so check it whenever it's in use
+    let uses_claude_cli = providers.claude_cli.is_some()
+        || resolve_provider(&config.agent.default_model, config).0 == "claude-cli";
+    let mut sections: Vec<String> = [
+        ("openai", providers.openai.is_some()),
+        ("anthropic", providers.anthropic.is_some()),
+        ("gemini", providers.gemini.is_some()),
+        ("deepseek", providers.deepseek.is_some()),
+        ("claude-cli", uses_claude_cli),
+        ("codex-cli", providers.codex_cli.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, configured)| *configured)
+    .map(|(section, _)| section.to_string())
+    .collect();
+    sections.extend(providers.custom.iter().map(|c| c.name.clone()));
+
+    let checks = futures::future::join_all(
+        sections
+            .into_iter()
+            .map(|section| check_provider(section, config)),
+    );
+    let ollama = async {
+        match &providers.ollama {
+            Some(ollama) => probe_hosts(ollama).await,
+            None => Vec::new(),
+        }
+    };
+    let (mut results, hosts) = futures::join!(checks, ollama);
+    results.extend(hosts.into_iter().map(ollama_health));
+    results
+}
+
+async fn check_provider(section: String, config: &Config) -> ProviderHealth {
+    // The check doesn't use the model, so build the provider without one
+    let started = Instant::now();
+    let result = match create_provider(&format!("{}/", section), config) {
+        Ok(provider) => tokio::time::timeout(CHECK_TIMEOUT, provider.health_check())
+            .await
+            .unwrap_or_else(|_| anyhow::bail!("no answer within {:?}", CHECK_TIMEOUT)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(detail) => ProviderHealth {
+            provider: section,
+            ok: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            detail,
+        },
+        Err(e) => ProviderHealth {
+            provider: section,
+            ok: false,
+            latency_ms: None,
+            detail: match e.downcast_ref::<ProviderAuthError>() {
+                Some(_) => "API key rejected".to_string(),
+                None => e.to_string(),
+            },
+        },
+    }
+}
+
+fn ollama_health(status: OllamaHostStatus) -> ProviderHealth {
+    ProviderHealth {
+        provider: format!("ollama@{}", status.name),
+        ok: status.is_reachable(),
+        latency_ms: status.latency_ms,
+        detail: match status.error {
+            Some(error) => format!("{}: {}", status.endpoint, error),
+            None => format!("{} models installed", status.installed.len()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_health_checks_report_each_provider() {
+        // A server that rejects every key
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"error":{"code":"invalid_api_key"}}"#;
+            let reply = format!(
+                "HTTP/1.1 401 Unauthorized\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(reply.as_bytes()).await.unwrap();
+        });
+
+        let config: Config = toml::from_str(&format!(
+            "[agent]\ndefault_model = \"corp/m\"\n\n\
+             [[providers.custom]]\nname = \"corp\"\nbase_url = \"http://{}/v1\"\n\n\
+             [providers.claude_cli]\ncommand = \"/nonexistent/claude\"\n",
+            addr
+        ))
+        .unwrap();
+        let mut results = run_health_checks(&config).await;
+        results.sort_by(|a, b| a.provider.cmp(&b.provider));
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].provider, "claude-cli");
+        assert!(!results[0].ok);
+        assert!(results[0].detail.contains("not found"));
+        assert_eq!(results[1].provider, "corp");
+        assert!(!results[1].ok);
+        assert_eq!(results[1].detail, "API key rejected");
+        assert_eq!(results[1].latency_ms, None);
+    }
+}
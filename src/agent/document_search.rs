@@ -0,0 +1,142 @@
+//! `document_search` tool: passages from the documents added with
+//! `localgpt index <path>`
+//!
+//! Results are numbered `[n] Source: <file>, page <p>` (or `lines a-b`) so
+//! the model can cite them as [1], [2], ...; the desktop lists the cited
+//! sources under the reply (`parse_sources`).
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+use crate::memory::{citation, MemoryChunk, MemoryManager};
+
+/// Passages returned when the model doesn't ask for a number
+const DEFAULT_PASSAGES: usize = 5;
+
+/// Most passages one search may ask for
+const MAX_PASSAGES: usize = 10;
+
+static SOURCE_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\[(\d+)\] Source: (.+)$").unwrap());
+
+pub struct DocumentSearchTool {
+    memory: Arc<MemoryManager>,
+}
+
+impl DocumentSearchTool {
+    pub fn new(memory: Arc<MemoryManager>) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for DocumentSearchTool {
+    fn name(&self) -> &str {
+        "document_search"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "document_search".to_string(),
+            description: "Search the user's indexed documents (notes, PDFs, Word files) and return the most relevant passages with their source. Cite passages you use as [1], [2], ... matching the result numbers.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to look for, in natural language"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of passages (default: 5, max 10)"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let query = args["query"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing query"))?;
+        let limit = args["limit"]
+            .as_u64()
+            .map_or(DEFAULT_PASSAGES, |n| n as usize)
+            .clamp(1, MAX_PASSAGES);
+
+        debug!("Document search: {} (limit: {})", query, limit);
+
+        let passages = self.memory.search_documents(query, limit).await?;
+        Ok(format_passages(query, &passages))
+    }
+}
+
+/// Numbered passages for the model
+fn format_passages(query: &str, passages: &[MemoryChunk]) -> String {
+    if passages.is_empty() {
+        return format!("No passages found for \"{}\"", query);
+    }
+    let mut out = format!("Passages for \"{}\":\n", query);
+    for (i, passage) in passages.iter().enumerate() {
+        out.push_str(&format!(
+            "\n[{}] Source: {}\n{}\n",
+            i + 1,
+            citation(&passage.file, passage.line_start, passage.line_end),
+            passage.content.trim()
+        ));
+    }
+    out
+}
+
+/// The numbered sources in a `document_search` output
+pub fn parse_sources(output: &str) -> Vec<(usize, String)> {
+    SOURCE_LINE
+        .captures_iter(output)
+        .filter_map(|c| Some((c[1].parse().ok()?, c[2].trim().to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passages_round_trip_to_sources() {
+        let passages = vec![
+            MemoryChunk {
+                file: "/docs/report.pdf#page=3".to_string(),
+                line_start: 1,
+                line_end: 8,
+                content: "Revenue grew 12%.\n[1] See appendix".to_string(),
+                score: 0.9,
+            },
+            MemoryChunk {
+                file: "/docs/notes.md".to_string(),
+                line_start: 10,
+                line_end: 24,
+                content: "Q3 plan".to_string(),
+                score: 0.5,
+            },
+        ];
+        let output = format_passages("revenue", &passages);
+        assert_eq!(
+            parse_sources(&output),
+            vec![
+                (1, "/docs/report.pdf, page 3".to_string()),
+                (2, "/docs/notes.md, lines 10-24".to_string()),
+            ]
+        );
+        assert_eq!(format_passages("x", &[]), "No passages found for \"x\"");
+    }
+}
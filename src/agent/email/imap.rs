@@ -0,0 +1,398 @@
+//! The slice of IMAP4rev1 (RFC 3501) the email tools use: log in, open a
+//! folder read-only, search, fetch and append
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+static LITERAL: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\d+)\}\r?\n$").unwrap());
+static UID: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bUID (\d+)").unwrap());
+static FLAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bFLAGS \(([^)]*)\)").unwrap());
+
+/// Most bytes of a message fetched for reading; attachments past this are
+/// cut off, the text usually comes first
+const MAX_FETCH_BYTES: usize = 5_000_000;
+
+/// Largest literal accepted from the server
+const MAX_LITERAL_BYTES: usize = MAX_FETCH_BYTES + 1024;
+
+/// A piece of a command: text sent as is, or a string sent as a literal
+enum Piece {
+    Text(String),
+    Literal(Vec<u8>),
+}
+
+/// An IMAP string argument: quoted when possible, a literal otherwise
+fn string(s: &str) -> Piece {
+    if s.is_ascii() && !s.contains(['\r', '\n']) {
+        Piece::Text(format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        ))
+    } else {
+        Piece::Literal(s.as_bytes().to_vec())
+    }
+}
+
+fn text(s: impl Into<String>) -> Piece {
+    Piece::Text(s.into())
+}
+
+/// A server response line with the literals it carried
+#[derive(Debug, Default)]
+pub struct Line {
+    pub text: String,
+    pub literals: Vec<Vec<u8>>,
+}
+
+/// What `search` looks for; empty criteria match every message
+#[derive(Debug, Default)]
+pub struct Search {
+    pub text: Option<String>,
+    pub from: Option<String>,
+    pub unread_only: bool,
+    /// IMAP date, e.g. `17-Oct-2026`
+    pub since: Option<String>,
+}
+
+/// A message from a fetch
+#[derive(Debug, Default)]
+pub struct Fetched {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    /// Header fields or the whole message, depending on the fetch
+    pub data: Vec<u8>,
+}
+
+impl Fetched {
+    pub fn seen(&self) -> bool {
+        self.flags.iter().any(|f| f.eq_ignore_ascii_case("\\Seen"))
+    }
+}
+
+pub struct ImapSession<S> {
+    stream: BufReader<S>,
+    next_tag: u32,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> ImapSession<S> {
+    /// Start a session, reading the server greeting
+    pub async fn start(stream: S) -> Result<Self> {
+        let mut session = Self {
+            stream: BufReader::new(stream),
+            next_tag: 1,
+        };
+        let greeting = session.read_line().await?;
+        if !greeting.text.starts_with("* OK") && !greeting.text.starts_with("* PREAUTH") {
+            anyhow::bail!("Unexpected IMAP greeting: {}", greeting.text.trim());
+        }
+        Ok(session)
+    }
+
+    /// Continue a session on a stream upgraded by STARTTLS (no greeting)
+    pub fn start_after_tls(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            next_tag: 1,
+        }
+    }
+
+    /// The stream, for upgrading after STARTTLS
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    pub async fn starttls(&mut self) -> Result<()> {
+        self.run(vec![text("STARTTLS")]).await.map(|_| ())
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        self.run(vec![
+            text("LOGIN "),
+            string(username),
+            text(" "),
+            string(password),
+        ])
+        .await
+        .map_err(|e| anyhow::anyhow!("IMAP login failed: {}", e))?;
+        Ok(())
+    }
+
+    /// Open a folder read-only, so fetching doesn't mark mail as seen
+    pub async fn examine(&mut self, folder: &str) -> Result<()> {
+        self.run(vec![text("EXAMINE "), string(folder)])
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not open folder '{}': {}", folder, e))?;
+        Ok(())
+    }
+
+    /// UIDs of matching messages, oldest first
+    pub async fn search(&mut self, search: &Search) -> Result<Vec<u32>> {
+        let mut pieces = vec![text("UID SEARCH")];
+        let non_ascii = [&search.text, &search.from]
+            .iter()
+            .any(|s| s.as_deref().is_some_and(|s| !s.is_ascii()));
+        if non_ascii {
+            pieces.push(text(" CHARSET UTF-8"));
+        }
+        let mut criteria = 0;
+        if search.unread_only {
+            pieces.push(text(" UNSEEN"));
+            criteria += 1;
+        }
+        if let Some(ref since) = search.since {
+            pieces.push(text(format!(" SINCE {}", since)));
+            criteria += 1;
+        }
+        if let Some(ref from) = search.from {
+            pieces.push(text(" FROM "));
+            pieces.push(string(from));
+            criteria += 1;
+        }
+        if let Some(ref query) = search.text {
+            pieces.push(text(" TEXT "));
+            pieces.push(string(query));
+            criteria += 1;
+        }
+        if criteria == 0 {
+            pieces.push(text(" ALL"));
+        }
+
+        let mut uids: Vec<u32> = self
+            .run(pieces)
+            .await?
+            .iter()
+            .filter_map(|line| line.text.strip_prefix("* SEARCH"))
+            .flat_map(|rest| {
+                rest.split_whitespace()
+                    .filter_map(|n| n.parse().ok())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        uids.sort_unstable();
+        Ok(uids)
+    }
+
+    /// Flags and these header fields of each message
+    pub async fn fetch_headers(&mut self, uids: &[u32], fields: &[&str]) -> Result<Vec<Fetched>> {
+        let items = format!(
+            "(UID FLAGS BODY.PEEK[HEADER.FIELDS ({})])",
+            fields.join(" ")
+        );
+        self.fetch(uids, &items).await
+    }
+
+    /// Flags and the message itself (its first `MAX_FETCH_BYTES`)
+    pub async fn fetch_message(&mut self, uid: u32) -> Result<Option<Fetched>> {
+        let items = format!("(UID FLAGS BODY.PEEK[]<0.{}>)", MAX_FETCH_BYTES);
+        Ok(self.fetch(&[uid], &items).await?.into_iter().next())
+    }
+
+    async fn fetch(&mut self, uids: &[u32], items: &str) -> Result<Vec<Fetched>> {
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let set: Vec<String> = uids.iter().map(u32::to_string).collect();
+        let lines = self
+            .run(vec![text(format!("UID FETCH {} {}", set.join(","), items))])
+            .await?;
+        Ok(lines
+            .into_iter()
+            .filter(|line| line.text.starts_with("* ") && line.text.contains(" FETCH ("))
+            .filter_map(|line| {
+                let uid = UID.captures(&line.text)?[1].parse().ok()?;
+                // Only the uids asked for: servers may add unsolicited updates
+                if !uids.contains(&uid) {
+                    return None;
+                }
+                let flags = FLAGS
+                    .captures(&line.text)
+                    .map(|c| c[1].split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default();
+                Some(Fetched {
+                    uid,
+                    flags,
+                    data: line.literals.into_iter().next().unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    /// Store a message in a folder with these flags (e.g. `\Draft`)
+    pub async fn append(&mut self, folder: &str, flags: &str, message: &[u8]) -> Result<()> {
+        self.run(vec![
+            text("APPEND "),
+            string(folder),
+            text(format!(" ({}) ", flags)),
+            Piece::Literal(message.to_vec()),
+        ])
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not save to '{}': {}", folder, e))?;
+        Ok(())
+    }
+
+    pub async fn logout(&mut self) -> Result<()> {
+        self.run(vec![text("LOGOUT")]).await.map(|_| ())
+    }
+
+    /// Send a tagged command; returns the untagged responses, or the
+    /// server's reason when it answers NO or BAD
+    async fn run(&mut self, pieces: Vec<Piece>) -> Result<Vec<Line>> {
+        let tag = format!("a{}", self.next_tag);
+        self.next_tag += 1;
+        let mut untagged = Vec::new();
+
+        let mut pending = format!("{} ", tag);
+        for piece in pieces {
+            match piece {
+                Piece::Text(s) => pending.push_str(&s),
+                Piece::Literal(bytes) => {
+                    pending.push_str(&format!("{{{}}}\r\n", bytes.len()));
+                    self.write(pending.as_bytes()).await?;
+                    pending.clear();
+                    // Wait for the server to ask for the literal
+                    loop {
+                        let line = self.read_line().await?;
+                        if line.text.starts_with('+') {
+                            break;
+                        }
+                        if line.text.starts_with(&format!("{} ", tag)) {
+                            anyhow::bail!("{}", status_text(&line.text, &tag));
+                        }
+                        untagged.push(line);
+                    }
+                    self.stream.get_mut().write_all(&bytes).await?;
+                }
+            }
+        }
+        pending.push_str("\r\n");
+        self.write(pending.as_bytes()).await?;
+
+        loop {
+            let line = self.read_line().await?;
+            if let Some(status) = line.text.strip_prefix(&format!("{} ", tag)) {
+                if status.starts_with("OK") {
+                    return Ok(untagged);
+                }
+                anyhow::bail!("{}", status_text(&line.text, &tag));
+            }
+            untagged.push(line);
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(bytes).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// One response line, following any literals it announces
+    async fn read_line(&mut self) -> Result<Line> {
+        let mut line = Line::default();
+        loop {
+            let mut chunk = Vec::new();
+            if self.stream.read_until(b'\n', &mut chunk).await? == 0 {
+                anyhow::bail!("IMAP server closed the connection");
+            }
+            let chunk = String::from_utf8_lossy(&chunk).to_string();
+            let literal = LITERAL
+                .captures(&chunk)
+                .and_then(|c| c[1].parse::<usize>().ok());
+            line.text.push_str(chunk.trim_end_matches(['\r', '\n']));
+            let Some(len) = literal else {
+                return Ok(line);
+            };
+            if len > MAX_LITERAL_BYTES {
+                anyhow::bail!("IMAP server sent {} bytes at once", len);
+            }
+            let mut bytes = vec![0; len];
+            self.stream.read_exact(&mut bytes).await?;
+            line.literals.push(bytes);
+        }
+    }
+}
+
+/// The text after the tag of a failed command
+fn status_text(line: &str, tag: &str) -> String {
+    line.strip_prefix(tag).unwrap_or(line).trim().to_string()
+}
+
+/// An IMAP date (`17-Oct-2026`) for SEARCH SINCE
+pub fn imap_date(date: chrono::NaiveDate) -> String {
+    date.format("%-d-%b-%Y").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_imap_session() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut received = Vec::new();
+            server
+                .get_mut()
+                .write_all(b"* OK IMAP4rev1 ready\r\n")
+                .await
+                .unwrap();
+            let replies: Vec<&[u8]> = vec![
+                b"a1 OK logged in\r\n",
+                b"* 2 EXISTS\r\na2 OK [READ-ONLY] done\r\n",
+                b"* SEARCH 9 4\r\na3 OK done\r\n",
+                b"* 1 FETCH (UID 4 FLAGS (\\Seen) BODY[HEADER.FIELDS (SUBJECT)] {15}\r\nSubject: Hi\r\n\r\n)\r\n\
+                  * 2 FETCH (FLAGS () UID 9 BODY[HEADER.FIELDS (SUBJECT)] {16}\r\nSubject: Bye\r\n\r\n)\r\n\
+                  a4 OK done\r\n",
+            ];
+            for reply in replies {
+                let mut line = String::new();
+                server.read_line(&mut line).await.unwrap();
+                if line.ends_with("}\r\n") {
+                    // A literal: ask for it, then read it and the line end
+                    server.get_mut().write_all(b"+ go\r\n").await.unwrap();
+                    let mut rest = String::new();
+                    server.read_line(&mut rest).await.unwrap();
+                    line.push_str(&rest);
+                }
+                received.push(line);
+                server.get_mut().write_all(reply).await.unwrap();
+            }
+            received
+        });
+
+        let mut session = ImapSession::start(client).await.unwrap();
+        session.login("me", "pa\"ss").await.unwrap();
+        session.examine("INBOX").await.unwrap();
+        let uids = session
+            .search(&Search {
+                text: Some("café".to_string()),
+                unread_only: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(uids, vec![4, 9]);
+        let fetched = session.fetch_headers(&uids, &["SUBJECT"]).await.unwrap();
+        assert_eq!(fetched.len(), 2);
+        assert!(fetched[0].seen());
+        assert_eq!(fetched[1].uid, 9);
+        assert!(!fetched[1].seen());
+        assert_eq!(fetched[1].data, b"Subject: Bye\r\n\r\n");
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0], "a1 LOGIN \"me\" \"pa\\\"ss\"\r\n");
+        assert_eq!(received[1], "a2 EXAMINE \"INBOX\"\r\n");
+        assert_eq!(
+            received[2],
+            "a3 UID SEARCH CHARSET UTF-8 UNSEEN TEXT {5}\r\ncafé\r\n"
+        );
+        assert!(received[3].starts_with("a4 UID FETCH 4,9 (UID FLAGS BODY.PEEK"));
+        assert_eq!(
+            imap_date(chrono::NaiveDate::from_ymd_opt(2026, 3, 7).unwrap()),
+            "7-Mar-2026"
+        );
+    }
+}
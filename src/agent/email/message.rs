@@ -0,0 +1,569 @@
+//! Reading fetched messages (headers, MIME parts, transfer encodings,
+//! charsets) into plain text, and building outgoing ones
+
+use anyhow::Result;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine as _;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+
+use crate::agent::readability;
+
+static ENCODED_WORD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"=\?([^?\s]+)\?([BbQq])\?([^?\s]*)\?=").unwrap());
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+
+/// Nesting depth of multiparts followed before the rest is ignored
+const MAX_DEPTH: usize = 8;
+
+/// A fetched message as text
+#[derive(Debug, Default)]
+pub struct ParsedMessage {
+    /// Unfolded headers with encoded words decoded, in order
+    pub headers: Vec<(String, String)>,
+    /// The readable body: text/plain parts, or converted HTML where a
+    /// message has no plain text
+    pub text: String,
+    /// Attachments as "name (type)"
+    pub attachments: Vec<String>,
+}
+
+impl ParsedMessage {
+    /// First header with this name (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Parse a raw RFC 5322 message
+pub fn parse_message(raw: &[u8]) -> ParsedMessage {
+    let (headers, body) = split_entity(raw);
+    let mut message = ParsedMessage {
+        headers,
+        ..Default::default()
+    };
+    let mut texts = Vec::new();
+    walk(
+        &message.headers,
+        body,
+        0,
+        &mut texts,
+        &mut message.attachments,
+    );
+    message.text = texts.join("\n\n").trim().to_string();
+    message
+}
+
+/// Parse just a header block (e.g. from a header-only fetch)
+pub fn parse_headers(raw: &[u8]) -> Vec<(String, String)> {
+    split_entity(raw).0
+}
+
+/// Headers and body of a message or MIME part
+fn split_entity(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let (head, body) = match find(raw, b"\r\n\r\n") {
+        Some(i) => (&raw[..i], &raw[i + 4..]),
+        None => match find(raw, b"\n\n") {
+            Some(i) => (&raw[..i], &raw[i + 2..]),
+            None => (raw, &raw[raw.len()..]),
+        },
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    for (_, value) in &mut headers {
+        *value = decode_words(value);
+    }
+    (headers, body)
+}
+
+/// Collect the text and attachments of an entity
+fn walk(
+    headers: &[(String, String)],
+    body: &[u8],
+    depth: usize,
+    texts: &mut Vec<String>,
+    attachments: &mut Vec<String>,
+) {
+    let content_type = header(headers, "Content-Type").unwrap_or("text/plain");
+    let (mime, params) = parse_content_type(content_type);
+
+    if mime.starts_with("multipart/") {
+        let Some(boundary) = param(&params, "boundary") else {
+            return;
+        };
+        if depth >= MAX_DEPTH {
+            return;
+        }
+        let parts = split_multipart(body, &boundary);
+        if mime == "multipart/alternative" {
+            // One version of the same content: plain text when there is one
+            let mut best: Option<(bool, Vec<String>, Vec<String>)> = None;
+            for part in parts {
+                let (part_headers, part_body) = split_entity(part);
+                let plain = header(&part_headers, "Content-Type")
+                    .is_none_or(|t| parse_content_type(t).0 == "text/plain");
+                let mut part_texts = Vec::new();
+                let mut part_attachments = Vec::new();
+                walk(
+                    &part_headers,
+                    part_body,
+                    depth + 1,
+                    &mut part_texts,
+                    &mut part_attachments,
+                );
+                if part_texts.iter().all(|t| t.trim().is_empty()) {
+                    continue;
+                }
+                if best.as_ref().is_none_or(|(was_plain, _, _)| !was_plain) {
+                    best = Some((plain, part_texts, part_attachments));
+                }
+            }
+            if let Some((_, part_texts, part_attachments)) = best {
+                texts.extend(part_texts);
+                attachments.extend(part_attachments);
+            }
+        } else {
+            for part in parts {
+                let (part_headers, part_body) = split_entity(part);
+                walk(&part_headers, part_body, depth + 1, texts, attachments);
+            }
+        }
+        return;
+    }
+
+    let disposition = header(headers, "Content-Disposition").unwrap_or("");
+    let (disposition_kind, disposition_params) = parse_content_type(disposition);
+    let filename = param(&disposition_params, "filename").or_else(|| param(&params, "name"));
+    let is_text = mime == "text/plain" || mime == "text/html";
+    if !is_text || disposition_kind == "attachment" {
+        attachments.push(format!(
+            "{} ({})",
+            filename.unwrap_or_else(|| "unnamed".to_string()),
+            mime
+        ));
+        return;
+    }
+
+    let encoding = header(headers, "Content-Transfer-Encoding").unwrap_or("");
+    let bytes = decode_transfer(body, encoding);
+    let charset = param(&params, "charset").unwrap_or_else(|| "utf-8".to_string());
+    let text = decode_charset(&bytes, &charset).replace("\r\n", "\n");
+    texts.push(if mime == "text/html" {
+        html_to_text(&text)
+    } else {
+        text
+    });
+}
+
+/// `type/subtype` (lowercased) and its `key=value` parameters
+fn parse_content_type(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = value.split(';');
+    let mime = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.trim().to_ascii_lowercase(),
+                v.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    (mime, params)
+}
+
+fn param(params: &[(String, String)], name: &str) -> Option<String> {
+    params
+        .iter()
+        .find(|(k, _)| k == name || k.strip_suffix('*') == Some(name))
+        .map(|(k, v)| {
+            if k.ends_with('*') {
+                // RFC 2231: charset'language'percent-encoded
+                let encoded = v.splitn(3, '\'').last().unwrap_or(v);
+                String::from_utf8_lossy(&percent_decode(encoded)).to_string()
+            } else {
+                v.clone()
+            }
+        })
+}
+
+/// The parts between `--boundary` delimiter lines
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let end = find(&body[pos..], b"\n").map_or(body.len(), |i| pos + i + 1);
+        let line = trim_line_end(&body[pos..end]);
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            if let Some(s) = start {
+                // The line break before a delimiter belongs to the delimiter
+                let part_end = if body[..pos].ends_with(b"\r\n") {
+                    pos - 2
+                } else {
+                    pos.saturating_sub(1).max(s)
+                };
+                parts.push(&body[s..part_end]);
+            }
+            if rest.starts_with(b"--") {
+                return parts;
+            }
+            start = Some(end);
+        }
+        pos = end;
+    }
+    if let Some(s) = start {
+        parts.push(&body[s..]);
+    }
+    parts
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn decode_transfer(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "base64" => decode_base64(body).unwrap_or_else(|| body.to_vec()),
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+/// Base64 with line breaks, tolerating missing or damaged padding
+fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let mut compact: Vec<u8> = input
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    while compact.last() == Some(&b'=') {
+        compact.pop();
+    }
+    STANDARD_NO_PAD.decode(&compact).ok()
+}
+
+/// Quoted-printable (or, with `header`, the Q encoding where `_` is a space)
+fn decode_quoted_printable(input: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'=' if input[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if input[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < input.len() => {
+                match std::str::from_utf8(&input[i + 1..i + 3])
+                    .ok()
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&input[i + 1..i + 3], 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match encoding_rs::Encoding::for_label(charset.trim().as_bytes()) {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// Decode RFC 2047 encoded words; whitespace between two of them is dropped
+fn decode_words(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut last = 0;
+    let mut after_word = false;
+    for caps in ENCODED_WORD.captures_iter(value) {
+        let m = caps.get(0).unwrap();
+        let gap = &value[last..m.start()];
+        if !(after_word && gap.trim().is_empty()) {
+            out.push_str(gap);
+        }
+        let bytes = match &caps[2] {
+            "B" | "b" => decode_base64(caps[3].as_bytes()).unwrap_or_default(),
+            _ => decode_quoted_printable(caps[3].as_bytes(), true),
+        };
+        // A language may follow the charset: utf-8*en
+        let charset = caps[1].split('*').next().unwrap_or("utf-8");
+        out.push_str(&decode_charset(&bytes, charset));
+        last = m.end();
+        after_word = true;
+    }
+    out.push_str(&value[last..]);
+    out
+}
+
+fn html_to_text(html: &str) -> String {
+    let base = Url::parse("http://localhost/").expect("static url");
+    let article = readability::extract(html, &base);
+    if !article.markdown.trim().is_empty() {
+        return article.markdown;
+    }
+    TAG.replace_all(html, " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The bare address in `Name <user@host>` (or the whole value)
+pub fn bare_address(value: &str) -> &str {
+    match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value.trim(),
+    }
+}
+
+/// Refuse a recipient that isn't one plain `user@host` or
+/// `Name <user@host>`: line breaks or stray brackets would let it add
+/// headers or SMTP commands, and commas would add recipients
+pub fn check_address(value: &str) -> Result<()> {
+    let name = match value.find('<') {
+        Some(start) => &value[..start],
+        None => "",
+    };
+    let address = bare_address(value);
+    let valid = !value.chars().any(char::is_control)
+        && !value.contains(',')
+        && !name.contains(['<', '>', '"'])
+        && value.matches('<').count() <= 1
+        && value.matches('<').count() == value.matches('>').count()
+        && (!value.contains('<') || value.trim_end().ends_with('>'))
+        && address
+            .split_once('@')
+            .is_some_and(|(user, host)| !user.is_empty() && !host.is_empty())
+        && !address.contains(|c: char| c.is_whitespace() || "<>,;\"".contains(c));
+    if !valid {
+        anyhow::bail!("Invalid email address: {:?}", value);
+    }
+    Ok(())
+}
+
+/// Refuse a Message-ID (for In-Reply-To) that isn't `<id@host>` or `id@host`
+pub fn check_message_id(value: &str) -> Result<()> {
+    let id = value
+        .strip_prefix('<')
+        .and_then(|v| v.strip_suffix('>'))
+        .unwrap_or(value);
+    if id.is_empty()
+        || id.contains(|c: char| c.is_whitespace() || c.is_control() || "<>,".contains(c))
+    {
+        anyhow::bail!("Invalid message ID: {:?}", value);
+    }
+    Ok(())
+}
+
+/// What an outgoing message says
+pub struct Outgoing<'a> {
+    pub from: &'a str,
+    pub to: &'a [String],
+    pub cc: &'a [String],
+    pub subject: &'a str,
+    pub body: &'a str,
+    /// Message-ID of the message this replies to
+    pub in_reply_to: Option<&'a str>,
+}
+
+/// A plain-text UTF-8 message with CRLF line endings
+pub fn build_message(message: &Outgoing) -> String {
+    let domain = bare_address(message.from)
+        .rsplit_once('@')
+        .map_or("localhost", |(_, d)| d);
+    let mut out = String::new();
+    let mut push = |name: &str, value: &str| {
+        out.push_str(name);
+        out.push_str(": ");
+        out.push_str(value);
+        out.push_str("\r\n");
+    };
+    push("From", message.from);
+    push("To", &message.to.join(", "));
+    if !message.cc.is_empty() {
+        push("Cc", &message.cc.join(", "));
+    }
+    push("Subject", &encode_header(message.subject));
+    push("Date", &chrono::Local::now().to_rfc2822());
+    push(
+        "Message-ID",
+        &format!("<{}@{}>", uuid::Uuid::new_v4(), domain),
+    );
+    if let Some(id) = message.in_reply_to {
+        let id = if id.starts_with('<') {
+            id.to_string()
+        } else {
+            format!("<{}>", id)
+        };
+        push("In-Reply-To", &id);
+        push("References", &id);
+    }
+    push("MIME-Version", "1.0");
+    push("Content-Type", "text/plain; charset=utf-8");
+    push("Content-Transfer-Encoding", "base64");
+    out.push_str("\r\n");
+
+    let body = message.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    let encoded = STANDARD.encode(body.as_bytes());
+    for line in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// A header value, as encoded words when it isn't plain ASCII
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() && !value.contains(['\r', '\n']) {
+        return value.to_string();
+    }
+    // Encoded words are at most 75 characters: 45 bytes of text each
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars().filter(|c| *c != '\r' && *c != '\n') {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(c);
+    }
+    words.push(chunk);
+    words
+        .iter()
+        .map(|w| format!("=?UTF-8?B?{}?=", STANDARD.encode(w.as_bytes())))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart_message() {
+        let raw = "From: =?UTF-8?Q?Ren=C3=A9e?= <renee@example.com>\r\n\
+Subject: =?UTF-8?B?UmXDp3U=?= =?UTF-8?B?IG1lcmNp?=\r\n\
+\x20and more\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+\r\n\
+<p>HTML version</p>\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=iso-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Caf=E9 at 10, soft=\r\n\
+\x20wrapped\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: application/pdf; name=\"agenda.pdf\"\r\n\
+Content-Disposition: attachment; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0=\r\n\
+--outer--\r\n";
+
+        let message = parse_message(raw.as_bytes());
+        assert_eq!(message.header("from"), Some("Renée <renee@example.com>"));
+        assert_eq!(message.header("Subject"), Some("Reçu merci and more"));
+        assert_eq!(message.text, "Café at 10, soft wrapped");
+        assert_eq!(message.attachments, vec!["résumé.pdf (application/pdf)"]);
+
+        let outgoing = build_message(&Outgoing {
+            from: "Me <me@example.com>",
+            to: &["a@example.com".to_string()],
+            cc: &[],
+            subject: "Déjà vu",
+            body: "line one\nline two",
+            in_reply_to: Some("abc@example.com"),
+        });
+        assert!(outgoing.contains("Subject: =?UTF-8?B?RMOpasOgIHZ1?=\r\n"));
+        assert!(outgoing.contains("In-Reply-To: <abc@example.com>\r\n"));
+        let sent = parse_message(outgoing.as_bytes());
+        assert_eq!(sent.header("Subject"), Some("Déjà vu"));
+        assert_eq!(sent.text, "line one\nline two");
+    }
+
+    #[test]
+    fn test_header_injection_is_refused() {
+        assert!(check_address("a@example.com").is_ok());
+        assert!(check_address("Renée Roe <renee@example.com>").is_ok());
+        for bad in [
+            "a@b.c>\r\nRCPT TO:<x@evil.com",
+            "a@example.com\r\nBcc: x@evil.com",
+            "a@example.com, x@evil.com",
+            "Name <a@example.com> <x@evil.com>",
+            "a@example.com>",
+            "<a@example.com",
+            "nobody",
+        ] {
+            assert!(check_address(bad).is_err(), "{:?}", bad);
+        }
+
+        assert!(check_message_id("<abc@example.com>").is_ok());
+        assert!(check_message_id("abc@example.com").is_ok());
+        assert!(check_message_id("abc@example.com>\r\nBcc: x@evil.com").is_err());
+        assert!(check_message_id("").is_err());
+    }
+}
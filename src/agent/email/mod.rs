@@ -0,0 +1,562 @@
+//! Email tools (`[[tools.email]]`): `email_search` and `email_read` over
+//! IMAP, `email_send` over SMTP
+//!
+//! Folders are opened read-only (EXAMINE, BODY.PEEK), so the agent reading
+//! mail never marks it as seen. `email_send` is in `ALWAYS_REQUIRE_APPROVAL`:
+//! every message, sent or saved as a draft, is shown to the user first.
+
+mod imap;
+mod message;
+mod smtp;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsStream};
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+use crate::config::{expand_env, Config, EmailAccountConfig};
+use imap::{imap_date, ImapSession, Search};
+use message::{
+    bare_address, build_message, check_address, check_message_id, parse_headers, parse_message,
+    Outgoing,
+};
+use smtp::SmtpSession;
+
+/// Messages listed by email_search unless it asks for fewer
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 50;
+
+/// Body text returned by email_read; the rest is cut
+const MAX_BODY_CHARS: usize = 20_000;
+
+struct Account {
+    name: String,
+    address: String,
+    username: String,
+    password: String,
+    imap_host: String,
+    imap_port: u16,
+    smtp: Option<(String, u16)>,
+    drafts_folder: String,
+    timeout: Duration,
+}
+
+impl Account {
+    fn from_config(config: &EmailAccountConfig) -> Result<Self> {
+        let password = expand_env(&config.password).map_err(|var| {
+            anyhow::anyhow!(
+                "Email account '{}' password uses ${{{}}}, which is not set",
+                config.name,
+                var
+            )
+        })?;
+        Ok(Self {
+            name: config.name.clone(),
+            address: config.address.clone(),
+            username: config
+                .username
+                .clone()
+                .unwrap_or_else(|| bare_address(&config.address).to_string()),
+            password,
+            imap_host: config.imap_host.clone(),
+            imap_port: config.imap_port,
+            smtp: config
+                .smtp_host
+                .clone()
+                .map(|host| (host, config.smtp_port)),
+            drafts_folder: config.drafts_folder.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        })
+    }
+
+    /// A logged-in IMAP session
+    async fn imap(&self) -> Result<ImapSession<TlsStream<TcpStream>>> {
+        let tcp = TcpStream::connect((self.imap_host.as_str(), self.imap_port)).await?;
+        let mut session = if self.imap_port == 993 {
+            ImapSession::start(tls(&self.imap_host, tcp).await?).await?
+        } else {
+            let mut plain = ImapSession::start(tcp).await?;
+            plain.starttls().await?;
+            ImapSession::start_after_tls(tls(&self.imap_host, plain.into_inner()).await?)
+        };
+        session.login(&self.username, &self.password).await?;
+        debug!("Logged in to IMAP for '{}'", self.name);
+        Ok(session)
+    }
+
+    async fn send(&self, recipients: &[&str], message: &str) -> Result<()> {
+        let Some((ref host, port)) = self.smtp else {
+            anyhow::bail!("Email account '{}' has no smtp_host", self.name);
+        };
+        let domain = bare_address(&self.address)
+            .rsplit_once('@')
+            .map_or("localhost", |(_, d)| d);
+        let tcp = TcpStream::connect((host.as_str(), port)).await?;
+        let mut session = if port == 465 {
+            SmtpSession::start(tls(host, tcp).await?).await?
+        } else {
+            let mut plain = SmtpSession::start(tcp).await?;
+            plain.ehlo(domain).await?;
+            plain.starttls().await?;
+            SmtpSession::start_after_tls(tls(host, plain.into_inner()).await?)
+        };
+        session.ehlo(domain).await?;
+        session.login(&self.username, &self.password).await?;
+        session
+            .send(bare_address(&self.address), recipients, message)
+            .await?;
+        let _ = session.quit().await;
+        Ok(())
+    }
+
+    /// Run a mail server conversation under the account's timeout
+    async fn timed<T>(&self, work: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        tokio::time::timeout(self.timeout, work)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Mail server for '{}' timed out after {}s",
+                    self.name,
+                    self.timeout.as_secs()
+                )
+            })?
+    }
+}
+
+async fn tls(host: &str, tcp: TcpStream) -> Result<TlsStream<TcpStream>> {
+    let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+    Ok(connector.connect(host, tcp).await?)
+}
+
+/// The configured accounts, shared by the three email tools
+#[derive(Clone)]
+pub struct EmailAccounts(Arc<Vec<Account>>);
+
+impl EmailAccounts {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let accounts = config
+            .tools
+            .email
+            .iter()
+            .map(Account::from_config)
+            .collect::<Result<_>>()?;
+        Ok(Self(Arc::new(accounts)))
+    }
+
+    fn find(&self, name: Option<&str>) -> Result<&Account> {
+        let names = || {
+            self.0
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match name {
+            Some(name) => self.0.iter().find(|a| a.name == name).ok_or_else(|| {
+                anyhow::anyhow!("Unknown email account '{}'. Use one of: {}", name, names())
+            }),
+            None if self.0.len() == 1 => Ok(&self.0[0]),
+            None => anyhow::bail!("Missing account (one of: {})", names()),
+        }
+    }
+
+    /// For tool descriptions: "personal (me@example.com), work (...)"
+    fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|a| format!("{} ({})", a.name, a.address))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A short local date for a Date header, or the header as is
+fn short_date(date: &str) -> String {
+    chrono::DateTime::parse_from_rfc2822(date)
+        .map(|d| {
+            d.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| date.to_string())
+}
+
+fn clip(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!(
+            "{}\n\n[Message truncated at {} characters]",
+            &text[..end],
+            max
+        ),
+        None => text.to_string(),
+    }
+}
+
+pub struct EmailSearchTool {
+    accounts: EmailAccounts,
+}
+
+impl EmailSearchTool {
+    pub fn new(accounts: EmailAccounts) -> Self {
+        Self { accounts }
+    }
+}
+
+#[async_trait]
+impl Tool for EmailSearchTool {
+    fn name(&self) -> &str {
+        "email_search"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "email_search".to_string(),
+            description: format!(
+                "List recent email (newest first) with uid, date, sender and subject, optionally filtered. Read one with email_read. Accounts: {}.",
+                self.accounts.describe()
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "account": {
+                        "type": "string",
+                        "description": "Account name (optional when only one is configured)"
+                    },
+                    "folder": {
+                        "type": "string",
+                        "description": "Mail folder (default: INBOX)"
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "Text to find in headers or body"
+                    },
+                    "from": {
+                        "type": "string",
+                        "description": "Sender name or address contains this"
+                    },
+                    "unread_only": {
+                        "type": "boolean",
+                        "description": "Only messages not yet read"
+                    },
+                    "since_days": {
+                        "type": "integer",
+                        "description": "Only messages from the last N days (0 = today)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most messages to list (default 20, max 50)"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let account = self.accounts.find(args["account"].as_str())?;
+        let folder = args["folder"].as_str().unwrap_or("INBOX");
+        let limit = args["limit"]
+            .as_u64()
+            .map_or(DEFAULT_SEARCH_LIMIT, |n| n as usize)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        let search = Search {
+            text: args["query"].as_str().map(str::to_string),
+            from: args["from"].as_str().map(str::to_string),
+            unread_only: args["unread_only"].as_bool().unwrap_or(false),
+            since: args["since_days"]
+                .as_u64()
+                .map(|days| imap_date(chrono::Local::now().date_naive() - chrono::Days::new(days))),
+        };
+
+        let (total, mut messages) = account
+            .timed(async {
+                let mut session = account.imap().await?;
+                session.examine(folder).await?;
+                let uids = session.search(&search).await?;
+                let recent = &uids[uids.len().saturating_sub(limit)..];
+                let fetched = session
+                    .fetch_headers(recent, &["DATE", "FROM", "SUBJECT"])
+                    .await?;
+                let _ = session.logout().await;
+                Ok((uids.len(), fetched))
+            })
+            .await?;
+
+        if messages.is_empty() {
+            return Ok(format!("No messages found in {}/{}", account.name, folder));
+        }
+        messages.sort_by_key(|m| std::cmp::Reverse(m.uid));
+        let mut out = if total > messages.len() {
+            format!(
+                "{} of {} messages in {}/{} (newest first):\n",
+                messages.len(),
+                total,
+                account.name,
+                folder
+            )
+        } else {
+            format!(
+                "{} messages in {}/{} (newest first):\n",
+                messages.len(),
+                account.name,
+                folder
+            )
+        };
+        for message in &messages {
+            let headers = parse_headers(&message.data);
+            let field = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map_or("", |(_, v)| v.as_str())
+            };
+            out.push_str(&format!(
+                "- uid {} · {} · {} · {}{}\n",
+                message.uid,
+                short_date(field("Date")),
+                field("From"),
+                match field("Subject") {
+                    "" => "(no subject)",
+                    subject => subject,
+                },
+                if message.seen() { "" } else { " [unread]" }
+            ));
+        }
+        Ok(out)
+    }
+}
+
+pub struct EmailReadTool {
+    accounts: EmailAccounts,
+}
+
+impl EmailReadTool {
+    pub fn new(accounts: EmailAccounts) -> Self {
+        Self { accounts }
+    }
+}
+
+#[async_trait]
+impl Tool for EmailReadTool {
+    fn name(&self) -> &str {
+        "email_read"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "email_read".to_string(),
+            description: "Read one email by the uid email_search listed: headers, text body and attachment names. Does not mark it as read.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "account": {
+                        "type": "string",
+                        "description": "Account name (optional when only one is configured)"
+                    },
+                    "folder": {
+                        "type": "string",
+                        "description": "Mail folder (default: INBOX)"
+                    },
+                    "uid": {
+                        "type": "integer",
+                        "description": "Message uid from email_search"
+                    }
+                },
+                "required": ["uid"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let account = self.accounts.find(args["account"].as_str())?;
+        let folder = args["folder"].as_str().unwrap_or("INBOX");
+        let uid = args["uid"]
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(|| anyhow::anyhow!("Missing uid"))?;
+
+        let fetched = account
+            .timed(async {
+                let mut session = account.imap().await?;
+                session.examine(folder).await?;
+                let fetched = session.fetch_message(uid).await?;
+                let _ = session.logout().await;
+                Ok(fetched)
+            })
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No message with uid {} in {}", uid, folder))?;
+
+        let message = parse_message(&fetched.data);
+        let mut out = String::new();
+        for name in ["From", "To", "Cc", "Date", "Subject", "Message-ID"] {
+            if let Some(value) = message.header(name) {
+                out.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+        if !message.attachments.is_empty() {
+            out.push_str(&format!(
+                "Attachments: {}\n",
+                message.attachments.join(", ")
+            ));
+        }
+        out.push('\n');
+        out.push_str(&match message.text.as_str() {
+            "" => "(No text body)".to_string(),
+            text => clip(text, MAX_BODY_CHARS),
+        });
+        Ok(out)
+    }
+}
+
+pub struct EmailSendTool {
+    accounts: EmailAccounts,
+}
+
+impl EmailSendTool {
+    pub fn new(accounts: EmailAccounts) -> Self {
+        Self { accounts }
+    }
+
+    /// The message and its account, from the tool arguments
+    fn compose(&self, args: &Value) -> Result<(&Account, String, Vec<String>)> {
+        let account = self.accounts.find(args["account"].as_str())?;
+        let list = |key: &str| -> Vec<String> {
+            args[key]
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let to = list("to");
+        let cc = list("cc");
+        if to.is_empty() {
+            anyhow::bail!("Missing to");
+        }
+        for address in to.iter().chain(&cc) {
+            check_address(address)?;
+        }
+        let in_reply_to = args["in_reply_to"].as_str();
+        if let Some(id) = in_reply_to {
+            check_message_id(id)?;
+        }
+        let subject = args["subject"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing subject"))?;
+        let body = args["body"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing body"))?;
+        let message = build_message(&Outgoing {
+            from: &account.address,
+            to: &to,
+            cc: &cc,
+            subject,
+            body,
+            in_reply_to,
+        });
+        let recipients = to
+            .iter()
+            .chain(&cc)
+            .map(|r| bare_address(r).to_string())
+            .collect();
+        Ok((account, message, recipients))
+    }
+}
+
+#[async_trait]
+impl Tool for EmailSendTool {
+    fn name(&self) -> &str {
+        "email_send"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "email_send".to_string(),
+            description: "Send a plain-text email, or save it as a draft. The user approves every message before it goes out.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "account": {
+                        "type": "string",
+                        "description": "Account to send from (optional when only one is configured)"
+                    },
+                    "to": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Recipients, e.g. [\"Ann <ann@example.com>\"]"
+                    },
+                    "cc": {
+                        "type": "array",
+                        "items": { "type": "string" }
+                    },
+                    "subject": { "type": "string" },
+                    "body": { "type": "string" },
+                    "in_reply_to": {
+                        "type": "string",
+                        "description": "Message-ID of the email being answered (from email_read)"
+                    },
+                    "draft": {
+                        "type": "boolean",
+                        "description": "Save to the drafts folder instead of sending"
+                    }
+                },
+                "required": ["to", "subject", "body"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let (account, message, recipients) = self.compose(&args)?;
+
+        if args["draft"].as_bool().unwrap_or(false) {
+            account
+                .timed(async {
+                    let mut session = account.imap().await?;
+                    session
+                        .append(&account.drafts_folder, "\\Draft \\Seen", message.as_bytes())
+                        .await?;
+                    let _ = session.logout().await;
+                    Ok(())
+                })
+                .await?;
+            return Ok(format!(
+                "Draft saved to {}/{}",
+                account.name, account.drafts_folder
+            ));
+        }
+
+        let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+        account.timed(account.send(&recipients, &message)).await?;
+        Ok(format!("Sent to {}", recipients.join(", ")))
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        let (account, message, _) = self.compose(&args).ok()?;
+        let parsed = parse_message(message.as_bytes());
+        let mut out = String::new();
+        if args["draft"].as_bool().unwrap_or(false) {
+            out.push_str(&format!("Save as a draft in {}\n\n", account.drafts_folder));
+        }
+        for name in ["From", "To", "Cc", "Subject", "In-Reply-To"] {
+            if let Some(value) = parsed.header(name) {
+                out.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+        out.push('\n');
+        out.push_str(&parsed.text);
+        Some(out)
+    }
+}
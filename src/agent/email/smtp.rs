@@ -0,0 +1,226 @@
+//! The slice of SMTP (RFC 5321) needed to submit a message: EHLO,
+//! STARTTLS, AUTH PLAIN/LOGIN, MAIL/RCPT/DATA
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+pub struct SmtpSession<S> {
+    stream: BufReader<S>,
+    /// Extensions from the last EHLO reply, uppercased
+    extensions: Vec<String>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpSession<S> {
+    /// Start a session, reading the server greeting
+    pub async fn start(stream: S) -> Result<Self> {
+        let mut session = Self {
+            stream: BufReader::new(stream),
+            extensions: Vec::new(),
+        };
+        session.expect(220).await?;
+        Ok(session)
+    }
+
+    /// Continue a session on a stream upgraded by STARTTLS (no greeting)
+    pub fn start_after_tls(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            extensions: Vec::new(),
+        }
+    }
+
+    /// The stream, for upgrading after STARTTLS
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    pub async fn ehlo(&mut self, domain: &str) -> Result<()> {
+        let reply = self.command(&format!("EHLO {}", domain), 250).await?;
+        self.extensions = reply
+            .lines()
+            .skip(1)
+            .map(|l| l.trim().to_ascii_uppercase())
+            .collect();
+        Ok(())
+    }
+
+    pub fn supports(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|e| e.split_whitespace().next() == Some(extension))
+    }
+
+    pub async fn starttls(&mut self) -> Result<()> {
+        if !self.supports("STARTTLS") {
+            anyhow::bail!("SMTP server does not offer STARTTLS");
+        }
+        self.command("STARTTLS", 220).await.map(|_| ())
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        let mechanisms: Vec<String> = self
+            .extensions
+            .iter()
+            .find_map(|e| e.strip_prefix("AUTH "))
+            .map(|m| m.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let result = if mechanisms.iter().any(|m| m == "PLAIN") || mechanisms.is_empty() {
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+            self.command(&format!("AUTH PLAIN {}", token), 235).await
+        } else if mechanisms.iter().any(|m| m == "LOGIN") {
+            self.command("AUTH LOGIN", 334).await?;
+            self.command(&STANDARD.encode(username), 334).await?;
+            self.command(&STANDARD.encode(password), 235).await
+        } else {
+            anyhow::bail!(
+                "SMTP server offers no supported login ({})",
+                mechanisms.join(", ")
+            );
+        };
+        result.map_err(|e| anyhow::anyhow!("SMTP login failed: {}", e))?;
+        Ok(())
+    }
+
+    /// Submit `message` (CRLF line endings) from `from` to `recipients`
+    pub async fn send(&mut self, from: &str, recipients: &[&str], message: &str) -> Result<()> {
+        // Anything that could end the command early is refused outright
+        if let Some(bad) = std::iter::once(&from)
+            .chain(recipients)
+            .find(|a| a.contains(['\r', '\n', '<', '>']))
+        {
+            anyhow::bail!("Invalid address for SMTP: {:?}", bad);
+        }
+        self.command(&format!("MAIL FROM:<{}>", from), 250).await?;
+        for recipient in recipients {
+            self.command(&format!("RCPT TO:<{}>", recipient), 250)
+                .await
+                .map_err(|e| anyhow::anyhow!("Recipient {} refused: {}", recipient, e))?;
+        }
+        self.command("DATA", 354).await?;
+
+        let mut data = String::with_capacity(message.len() + 64);
+        for line in message.trim_end_matches("\r\n").split("\r\n") {
+            // Dot-stuffing: a leading dot is doubled
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        self.write(&data).await?;
+        self.expect(250).await?;
+        Ok(())
+    }
+
+    pub async fn quit(&mut self) -> Result<()> {
+        self.command("QUIT", 221).await.map(|_| ())
+    }
+
+    /// Send a command line and check the reply code; returns the reply text
+    async fn command(&mut self, line: &str, code: u16) -> Result<String> {
+        self.write(&format!("{}\r\n", line)).await?;
+        self.expect(code).await
+    }
+
+    async fn write(&mut self, data: &str) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(data.as_bytes()).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// Read a (possibly multi-line) reply and check its code
+    async fn expect(&mut self, code: u16) -> Result<String> {
+        let mut text = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                anyhow::bail!("SMTP server closed the connection");
+            }
+            let line = line.trim_end();
+            let got: u16 = line
+                .get(..3)
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("Unexpected SMTP reply: {}", line))?;
+            text.push(line.get(4..).unwrap_or("").to_string());
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            if got != code {
+                anyhow::bail!("{} {}", got, text.join(" "));
+            }
+            return Ok(text.join("\n"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_smtp_session() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut received = String::new();
+            server
+                .get_mut()
+                .write_all(b"220 mail.example.com ready\r\n")
+                .await
+                .unwrap();
+            let replies: Vec<&[u8]> = vec![
+                b"250-mail.example.com\r\n250-AUTH LOGIN\r\n250 SIZE 1000\r\n",
+                b"334 VXNlcm5hbWU6\r\n",
+                b"334 UGFzc3dvcmQ6\r\n",
+                b"235 ok\r\n",
+                b"250 ok\r\n",
+                b"250 ok\r\n",
+                b"354 go ahead\r\n",
+            ];
+            for reply in replies {
+                server.read_line(&mut received).await.unwrap();
+                server.get_mut().write_all(reply).await.unwrap();
+            }
+            // The message, up to the lone dot
+            loop {
+                let mut line = String::new();
+                server.read_line(&mut line).await.unwrap();
+                received.push_str(&line);
+                if line == ".\r\n" {
+                    break;
+                }
+            }
+            server.get_mut().write_all(b"250 queued\r\n").await.unwrap();
+            received
+        });
+
+        let mut session = SmtpSession::start(client).await.unwrap();
+        session.ehlo("example.com").await.unwrap();
+        assert!(session.supports("AUTH"));
+        assert!(!session.supports("STARTTLS"));
+        session.login("me", "secret").await.unwrap();
+        session
+            .send(
+                "me@example.com",
+                &["you@example.com"],
+                "Subject: x\r\n\r\n.hidden\r\nend\r\n",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            server.await.unwrap(),
+            "EHLO example.com\r\n\
+             AUTH LOGIN\r\n\
+             bWU=\r\n\
+             c2VjcmV0\r\n\
+             MAIL FROM:<me@example.com>\r\n\
+             RCPT TO:<you@example.com>\r\n\
+             DATA\r\n\
+             Subject: x\r\n\r\n..hidden\r\nend\r\n.\r\n"
+        );
+    }
+}
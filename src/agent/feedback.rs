@@ -0,0 +1,223 @@
+//! Thumbs-up/down ratings on assistant replies
+//!
+//! Ratings are stored on the message in the session transcript. Rated
+//! prompt/reply pairs can be exported as JSONL, and summarized into a short
+//! list of preferences that is added to the system prompt when
+//! `agent.feedback_summary` is on.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::providers::{create_provider, LLMProvider};
+use super::session::{get_state_dir, list_sessions, Session};
+use crate::config::Config;
+
+/// Most recent rated pairs sent to the model when summarizing
+const MAX_SUMMARY_PAIRS: usize = 40;
+
+/// Longest excerpt of a prompt or reply in the summary input
+const MAX_SUMMARY_EXCERPT_CHARS: usize = 600;
+
+pub const FEEDBACK_SUMMARY_PROMPT: &str = "Below are replies an assistant gave, each rated \
+     by the user as good (up) or bad (down), sometimes with a note. Describe what the user \
+     prefers as at most 6 short bullet points (e.g. \"Prefers concise answers with code \
+     first\"). Only state preferences the ratings clearly support; cover style, length, \
+     format and approach, not topics. Output only the bullet points.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+impl Rating {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "up" | "good" | "+" | "+1" | "👍" => Some(Self::Up),
+            "down" | "bad" | "-" | "-1" | "👎" => Some(Self::Down),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+}
+
+/// A rating given to one assistant reply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Feedback {
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl Feedback {
+    pub fn new(rating: Rating, note: Option<&str>) -> Self {
+        Self {
+            rating,
+            note: note
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string),
+            at: Utc::now(),
+        }
+    }
+}
+
+/// A rated reply with the user message that prompted it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatedPair {
+    pub session_id: String,
+    pub prompt: String,
+    pub response: String,
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Rated pairs from every saved session, oldest first
+pub fn collect_rated_pairs() -> Result<Vec<RatedPair>> {
+    let mut pairs = Vec::new();
+    for info in list_sessions()? {
+        match Session::load(&info.id) {
+            Ok(session) => pairs.extend(session.rated_pairs()),
+            Err(e) => tracing::debug!("Skipping session {}: {}", info.id, e),
+        }
+    }
+    pairs.sort_by_key(|p| p.at);
+    Ok(pairs)
+}
+
+/// Write pairs as JSONL, one per line
+pub fn export_rated_pairs(pairs: &[RatedPair], path: &Path) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    for pair in pairs {
+        writeln!(file, "{}", serde_json::to_string(pair)?)?;
+    }
+    Ok(())
+}
+
+/// Where the preference summary is kept between sessions
+pub fn feedback_summary_path() -> Result<PathBuf> {
+    Ok(get_state_dir()?.join("feedback_summary.md"))
+}
+
+/// The saved preference summary, if any
+pub fn load_feedback_summary() -> Option<String> {
+    let summary = fs::read_to_string(feedback_summary_path().ok()?).ok()?;
+    let summary = summary.trim();
+    (!summary.is_empty()).then(|| summary.to_string())
+}
+
+/// Summarize the user's preferences from rated pairs and save the summary
+/// (removing it when there are no ratings)
+pub async fn update_feedback_summary(
+    provider: &dyn LLMProvider,
+    pairs: &[RatedPair],
+) -> Result<Option<String>> {
+    let path = feedback_summary_path()?;
+    if pairs.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(None);
+    }
+
+    let summary = provider
+        .summarize_with(FEEDBACK_SUMMARY_PROMPT, &summary_input(pairs))
+        .await?;
+    let summary = summary.trim().to_string();
+    fs::write(&path, format!("{}\n", summary))?;
+    Ok(Some(summary))
+}
+
+/// `update_feedback_summary` over every saved session, using
+/// `agent.auxiliary_model` (or the default model)
+pub async fn summarize_feedback(config: &Config) -> Result<Option<String>> {
+    let model = config
+        .agent
+        .auxiliary_model
+        .as_deref()
+        .unwrap_or(&config.agent.default_model);
+    let provider = create_provider(model, config)?;
+    update_feedback_summary(provider.as_ref(), &collect_rated_pairs()?).await
+}
+
+/// The most recent pairs as text for the summarizer
+fn summary_input(pairs: &[RatedPair]) -> String {
+    let start = pairs.len().saturating_sub(MAX_SUMMARY_PAIRS);
+    pairs[start..]
+        .iter()
+        .map(|pair| {
+            let mut entry = format!(
+                "Rating: {}\nUser: {}\nAssistant: {}",
+                pair.rating.as_str(),
+                excerpt(&pair.prompt),
+                excerpt(&pair.response)
+            );
+            if let Some(ref note) = pair.note {
+                entry.push_str(&format!("\nNote: {}", note));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n")
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_SUMMARY_EXCERPT_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX_SUMMARY_EXCERPT_CHARS).collect();
+    format!("{}...", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_input() {
+        let pair = |rating, response: &str| RatedPair {
+            session_id: "s".to_string(),
+            prompt: "How do I reverse a list?".to_string(),
+            response: response.to_string(),
+            rating,
+            note: (rating == Rating::Down).then(|| "too long".to_string()),
+            model: None,
+            at: Utc::now(),
+        };
+        let long = "x".repeat(2 * MAX_SUMMARY_EXCERPT_CHARS);
+        let mut pairs = vec![pair(Rating::Down, &long)];
+        pairs.extend((0..MAX_SUMMARY_PAIRS).map(|_| pair(Rating::Up, "`list.reverse()`")));
+
+        // Only the most recent pairs are sent
+        let input = summary_input(&pairs);
+        assert!(!input.contains("too long"));
+        assert_eq!(input.matches("Rating: up").count(), MAX_SUMMARY_PAIRS);
+
+        let input = summary_input(&pairs[..2]);
+        assert!(input.contains("Rating: down") && input.contains("Note: too long"));
+        assert!(input.len() < long.len());
+
+        assert_eq!(Rating::parse("Good"), Some(Rating::Up));
+        assert_eq!(Rating::parse("-1"), Some(Rating::Down));
+        assert_eq!(Rating::parse("meh"), None);
+    }
+}
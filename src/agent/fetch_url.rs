@@ -0,0 +1,362 @@
+//! `fetch_url` tool (`[tools.fetch_url]`)
+//!
+//! Downloads a page, keeps the main content (see `readability`), and
+//! returns it as Markdown cut to a token budget. Unlike `web_fetch`, which
+//! returns the raw body, the result is small enough to read in full.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{redirect, Url};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::providers::{http_client_builder, ToolSchema};
+use super::readability;
+use super::tokens::TokenCounter;
+use super::tools::{host_in_domains, Tool};
+use crate::config::Config;
+
+/// Browser-like agent; some sites refuse requests without one
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; LocalGPT/0.1)";
+
+/// Redirects followed before giving up, as reqwest's default policy
+const MAX_REDIRECTS: usize = 10;
+
+pub struct FetchUrlTool {
+    client: reqwest::Client,
+    counter: TokenCounter,
+    max_tokens: usize,
+    max_bytes: usize,
+    domains: DomainRules,
+}
+
+/// `[tools.fetch_url]` domain lists
+#[derive(Clone)]
+struct DomainRules {
+    allowed: Vec<String>,
+    denied: Vec<String>,
+}
+
+impl DomainRules {
+    /// Refuse URLs that aren't http(s) or whose domain the config excludes
+    fn check_url(&self, url: &Url) -> Result<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("Only http and https URLs can be fetched");
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("URL has no host: {}", url))?;
+        if host_in_domains(host, &self.denied) {
+            anyhow::bail!("{} is in tools.fetch_url.denied_domains", host);
+        }
+        if !self.allowed.is_empty() && !host_in_domains(host, &self.allowed) {
+            anyhow::bail!("{} is not in tools.fetch_url.allowed_domains", host);
+        }
+        Ok(())
+    }
+
+    /// Follow a redirect only to a URL `check_url` accepts, so a page can't
+    /// pass a request on to a denied host
+    fn redirect_policy(&self) -> redirect::Policy {
+        let rules = self.clone();
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match rules.check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        })
+    }
+}
+
+impl FetchUrlTool {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let fetch = &config.tools.fetch_url;
+        let domains = DomainRules {
+            allowed: fetch.allowed_domains.clone(),
+            denied: fetch.denied_domains.clone(),
+        };
+        Ok(Self {
+            client: http_client_builder(&fetch.timeouts, &fetch.network)?
+                .redirect(domains.redirect_policy())
+                .build()?,
+            counter: TokenCounter::for_model(&config.agent.default_model, config),
+            max_tokens: fetch.max_tokens.max(1),
+            max_bytes: fetch.max_bytes.max(1),
+            domains,
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for FetchUrlTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "fetch_url".to_string(),
+            description: format!("Read a web page as clean Markdown: navigation, ads and other boilerplate are removed, and the text is cut to about {} tokens.", self.max_tokens),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The page to read (http or https)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let url = args["url"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing url"))?;
+        let url = Url::parse(url.trim()).with_context(|| format!("Invalid URL: {}", url))?;
+        self.domains.check_url(&url)?;
+
+        debug!("Fetching page: {}", url);
+
+        let mut response = self
+            .client
+            .get(url.clone())
+            .header("User-Agent", USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| match redirect_refusal(&e) {
+                Some(reason) => anyhow::anyhow!("Refused a redirect from {}: {}", url, reason),
+                None => anyhow::Error::new(e).context(format!("Failed to fetch {}", url)),
+            })?;
+
+        // Every hop was checked by the redirect policy
+        let final_url = response.url().clone();
+
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("{} returned {}", final_url, status);
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("text/html")
+            .to_lowercase();
+        let (body, cut) = read_capped(&mut response, self.max_bytes).await?;
+
+        let (title, text) = if content_type.contains("html") {
+            let article = readability::extract(&body, &final_url);
+            (article.title, article.markdown)
+        } else if content_type.starts_with("text/")
+            || content_type.contains("json")
+            || content_type.contains("xml")
+        {
+            (None, body)
+        } else {
+            anyhow::bail!("{} is not a web page ({})", final_url, content_type);
+        };
+
+        let mut page = format_page(
+            title.as_deref(),
+            &final_url,
+            &text,
+            &self.counter,
+            self.max_tokens,
+        );
+        if cut {
+            page.push_str(&format!(
+                "\n\n[Only the first {} bytes of the page were read]",
+                self.max_bytes
+            ));
+        }
+        Ok(page)
+    }
+}
+
+/// Why the redirect policy stopped a request, if it did
+fn redirect_refusal(error: &reqwest::Error) -> Option<String> {
+    if !error.is_redirect() {
+        return None;
+    }
+    std::error::Error::source(error).map(|source| source.to_string())
+}
+
+/// The response body as text, reading no more than `max_bytes`, and
+/// whether it was cut there
+async fn read_capped(response: &mut reqwest::Response, max_bytes: usize) -> Result<(String, bool)> {
+    let mut body = Vec::new();
+    let mut cut = false;
+    while let Some(chunk) = response.chunk().await? {
+        let room = max_bytes - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            cut = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((String::from_utf8_lossy(&body).into_owned(), cut))
+}
+
+/// The page for the model, cut at a paragraph (or line) boundary to fit
+/// `max_tokens`
+fn format_page(
+    title: Option<&str>,
+    url: &Url,
+    text: &str,
+    counter: &TokenCounter,
+    max_tokens: usize,
+) -> String {
+    let mut out = String::new();
+    if let Some(title) = title {
+        out.push_str(&format!("# {}\n\n", title));
+    }
+    out.push_str(&format!("Source: {}\n\n", url));
+
+    let text = text.trim();
+    if text.is_empty() {
+        out.push_str("(No readable text on this page)");
+        return out;
+    }
+    let total = counter.count(text);
+    if total <= max_tokens {
+        out.push_str(text);
+        return out;
+    }
+
+    let mut used = 0;
+    let mut kept = String::new();
+    'paragraphs: for paragraph in text.split("\n\n") {
+        let tokens = counter.count(paragraph) + 1;
+        if used + tokens <= max_tokens {
+            kept.push_str(paragraph);
+            kept.push_str("\n\n");
+            used += tokens;
+            continue;
+        }
+        // A paragraph too long to fit is cut by lines
+        for line in paragraph.lines() {
+            let tokens = counter.count(line) + 1;
+            if used + tokens > max_tokens {
+                break 'paragraphs;
+            }
+            kept.push_str(line);
+            kept.push('\n');
+            used += tokens;
+        }
+        break;
+    }
+    out.push_str(kept.trim_end());
+    out.push_str(&format!(
+        "\n\n[Truncated: showing about {} of {} tokens]",
+        used, total
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_domains_and_truncation() {
+        let domains = DomainRules {
+            allowed: vec!["example.com".to_string(), "*.rust-lang.org".to_string()],
+            denied: vec!["private.example.com".to_string()],
+        };
+        let check = |url: &str| domains.check_url(&Url::parse(url).unwrap()).is_ok();
+        assert!(check("https://example.com/page"));
+        assert!(check("https://doc.rust-lang.org/std/"));
+        assert!(!check("https://api.private.example.com/"));
+        assert!(!check("https://example.org/"));
+        assert!(!check("file:///etc/passwd"));
+
+        let url = Url::parse("https://example.com/").unwrap();
+        // The second paragraph's first line still fits; the rest doesn't
+        let text = "aaaa aaaa aa\n\nbbbb bbbb\nbbbb bbbb bbbb bbbb\n\ncccc";
+        assert_eq!(
+            format_page(Some("Page"), &url, text, &TokenCounter::Estimate, 10),
+            "# Page\n\nSource: https://example.com/\n\n\
+             aaaa aaaa aa\n\nbbbb bbbb\n\n\
+             [Truncated: showing about 8 of 13 tokens]"
+        );
+        assert_eq!(
+            format_page(None, &url, "short", &TokenCounter::Estimate, 10),
+            "Source: https://example.com/\n\nshort"
+        );
+    }
+
+    /// A local server answering each request with the next of `replies`
+    async fn serve(replies: Vec<String>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for reply in replies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 8192];
+                let _ = socket.read(&mut buf).await.unwrap();
+                socket.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    fn tool(max_bytes: usize, denied: &[&str]) -> FetchUrlTool {
+        let domains = DomainRules {
+            allowed: Vec::new(),
+            denied: denied.iter().map(|d| d.to_string()).collect(),
+        };
+        FetchUrlTool {
+            client: reqwest::Client::builder()
+                .redirect(domains.redirect_policy())
+                .build()
+                .unwrap(),
+            counter: TokenCounter::Estimate,
+            max_tokens: 1000,
+            max_bytes,
+            domains,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_denied_domain_refused() {
+        let addr = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: http://private.example.com/\r\nContent-Length: 0\r\n\r\n"
+                .to_string(),
+        ])
+        .await;
+        let args = json!({ "url": format!("http://{}/", addr) }).to_string();
+        let err = tool(1000, &["private.example.com"])
+            .execute(&args)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Refused a redirect"), "{}", err);
+        assert!(err.contains("denied_domains"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_body_read_up_to_max_bytes() {
+        let body = "x".repeat(100);
+        let addr = serve(vec![format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )])
+        .await;
+        let args = json!({ "url": format!("http://{}/", addr) }).to_string();
+        let page = tool(10, &[]).execute(&args).await.unwrap();
+        assert!(
+            page.contains(&format!("\n\n{}\n\n", "x".repeat(10))),
+            "{}",
+            page
+        );
+        assert!(page.ends_with("[Only the first 10 bytes of the page were read]"));
+    }
+}
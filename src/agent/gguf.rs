@@ -0,0 +1,309 @@
+//! In-process GGUF inference with llama.cpp (`--features gguf`)
+//!
+//! Runs `gguf/<model>` models inside the localgpt process, so chat works
+//! fully offline without an Ollama daemon. Models are loaded on first use
+//! and shared between providers for the life of the process.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
+use tracing::{debug, info};
+
+use super::providers::{
+    GenerationParams, LLMProvider, LLMResponse, Message, Role, StreamChunk, StreamResult,
+    ThinkTagSplitter, ToolSchema, Usage,
+};
+use crate::config::GgufConfig;
+
+/// llama.cpp may only be initialized once per process
+static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+
+/// Loaded models by file, so switching back to a model doesn't reload it
+static MODELS: OnceLock<StdMutex<HashMap<PathBuf, Arc<LlamaModel>>>> = OnceLock::new();
+
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+fn backend() -> Result<&'static LlamaBackend> {
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+    let backend = LlamaBackend::init()?;
+    Ok(BACKEND.get_or_init(|| backend))
+}
+
+fn load_model(path: &Path, gpu_layers: u32) -> Result<Arc<LlamaModel>> {
+    let models = MODELS.get_or_init(Default::default);
+    let mut models = models.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(model) = models.get(path) {
+        return Ok(Arc::clone(model));
+    }
+
+    info!("Loading GGUF model {}", path.display());
+    let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+    let model = Arc::new(LlamaModel::load_from_file(backend()?, path, &params)?);
+    models.insert(path.to_path_buf(), Arc::clone(&model));
+    Ok(model)
+}
+
+pub struct GgufProvider {
+    path: PathBuf,
+    config: GgufConfig,
+    max_tokens: usize,
+    params: GenerationParams,
+}
+
+impl GgufProvider {
+    /// Provider for `model` (a path or a file in `config.model_dir`). The
+    /// model is loaded on the first request.
+    pub fn new(model: &str, config: &GgufConfig, max_tokens: usize) -> Result<Self> {
+        Ok(Self {
+            path: config.resolve_model(model)?,
+            config: config.clone(),
+            max_tokens,
+            params: GenerationParams::default(),
+        })
+    }
+
+    fn job(&self, messages: &[Message]) -> Job {
+        Job {
+            path: self.path.clone(),
+            config: self.config.clone(),
+            messages: messages.to_vec(),
+            max_tokens: self.params.max_tokens.unwrap_or(self.max_tokens),
+            temperature: self.params.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+        }
+    }
+}
+
+/// One generation request, moved onto a blocking thread
+struct Job {
+    path: PathBuf,
+    config: GgufConfig,
+    messages: Vec<Message>,
+    max_tokens: usize,
+    temperature: f32,
+}
+
+impl Job {
+    /// Generate a reply, passing each piece of text to `on_text` until it
+    /// returns false
+    fn run(&self, mut on_text: impl FnMut(&str) -> bool) -> Result<Usage> {
+        let model = load_model(&self.path, self.config.gpu_layers)?;
+
+        let mut ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(self.config.context_size))
+            .with_n_batch(self.config.context_size);
+        if let Some(threads) = self.config.threads {
+            ctx_params = ctx_params
+                .with_n_threads(threads)
+                .with_n_threads_batch(threads);
+        }
+        let mut ctx = model.new_context(backend()?, ctx_params)?;
+
+        let prompt = chat_prompt(&model, &self.messages);
+        let tokens = model.str_to_token(&prompt, AddBos::Always)?;
+        let n_ctx = ctx.n_ctx() as usize;
+        if tokens.len() >= n_ctx {
+            anyhow::bail!(
+                "Prompt is {} tokens but the GGUF context is {}. Raise providers.gguf.context_size or start a new session.",
+                tokens.len(),
+                n_ctx
+            );
+        }
+
+        let mut batch = LlamaBatch::new(n_ctx, 1);
+        let last = tokens.len() - 1;
+        for (i, token) in tokens.iter().enumerate() {
+            batch.add(*token, i as i32, &[0], i == last)?;
+        }
+        ctx.decode(&mut batch)?;
+
+        let mut sampler = if self.temperature <= 0.0 {
+            LlamaSampler::greedy()
+        } else {
+            LlamaSampler::chain_simple([
+                LlamaSampler::temp(self.temperature),
+                LlamaSampler::dist(seed()),
+            ])
+        };
+
+        let mut n_cur = tokens.len();
+        let mut generated = 0usize;
+        let mut pending: Vec<u8> = Vec::new();
+        while generated < self.max_tokens && n_cur < n_ctx {
+            let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if model.is_eog_token(token) {
+                break;
+            }
+            generated += 1;
+
+            // A token can end partway through a UTF-8 character
+            pending.extend(model.token_to_bytes(token, Special::Plaintext)?);
+            let text = take_utf8(&mut pending);
+            if !text.is_empty() && !on_text(&text) {
+                break;
+            }
+
+            batch.clear();
+            batch.add(token, n_cur as i32, &[0], true)?;
+            ctx.decode(&mut batch)?;
+            n_cur += 1;
+        }
+        if !pending.is_empty() {
+            on_text(&String::from_utf8_lossy(&pending));
+        }
+
+        debug!(
+            "GGUF generated {} tokens from a {} token prompt",
+            generated,
+            tokens.len()
+        );
+        Ok(Usage {
+            input_tokens: tokens.len() as u64,
+            output_tokens: generated as u64,
+            reasoning_tokens: 0,
+        })
+    }
+}
+
+fn seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+}
+
+/// Remove and return the complete UTF-8 text at the start of `bytes`
+fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            let text = text.to_string();
+            bytes.clear();
+            text
+        }
+        // Invalid rather than incomplete: don't hold it back forever
+        Err(e) if e.error_len().is_some() => {
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            bytes.clear();
+            text
+        }
+        Err(e) => {
+            let rest = bytes.split_off(e.valid_up_to());
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            *bytes = rest;
+            text
+        }
+    }
+}
+
+/// Format messages with the model's own chat template, falling back to ChatML
+fn chat_prompt(model: &LlamaModel, messages: &[Message]) -> String {
+    let turns: Vec<(&str, String)> = messages
+        .iter()
+        .map(|m| match m.role {
+            Role::System => ("system", m.content.clone()),
+            Role::User => ("user", m.content.clone()),
+            Role::Assistant => ("assistant", m.content.clone()),
+            // No tool support: results go back as user text, as with Ollama
+            Role::Tool => ("user", format!("Tool result:\n{}", m.content)),
+        })
+        .collect();
+
+    let templated = model.chat_template(None).ok().and_then(|template| {
+        let chat = turns
+            .iter()
+            .map(|(role, content)| LlamaChatMessage::new(role.to_string(), content.clone()))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        model.apply_chat_template(&template, &chat, true).ok()
+    });
+
+    templated.unwrap_or_else(|| {
+        debug!("GGUF model has no usable chat template, using ChatML");
+        let mut prompt = String::new();
+        for (role, content) in &turns {
+            prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, content));
+        }
+        prompt.push_str("<|im_start|>assistant\n");
+        prompt
+    })
+}
+
+fn chunk(delta: String, reasoning: String, usage: Option<Usage>) -> StreamChunk {
+    StreamChunk {
+        delta,
+        done: usage.is_some(),
+        tool_calls: None,
+        tool_call_deltas: Vec::new(),
+        provider_tools: Vec::new(),
+        reasoning,
+        usage,
+    }
+}
+
+#[async_trait]
+impl LLMProvider for GgufProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let job = self.job(messages);
+        let (text, usage) = tokio::task::spawn_blocking(move || {
+            let mut text = String::new();
+            let usage = job.run(|piece| {
+                text.push_str(piece);
+                true
+            })?;
+            Ok::<_, anyhow::Error>((text, usage))
+        })
+        .await??;
+
+        // Inline reasoning is not part of the answer
+        let mut think_tags = ThinkTagSplitter::default();
+        let (mut content, _) = think_tags.push(&text);
+        content.push_str(&think_tags.finish().0);
+        Ok(LLMResponse::text_with_usage(content, usage))
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let job = self.job(messages);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Result<StreamChunk>>();
+
+        // Generation stops once the stream is dropped and sends start failing
+        tokio::task::spawn_blocking(move || {
+            let mut think_tags = ThinkTagSplitter::default();
+            let result = job.run(|piece| {
+                let (answer, reasoning) = think_tags.push(piece);
+                tx.send(Ok(chunk(answer, reasoning, None))).is_ok()
+            });
+            let last = result.map(|usage| {
+                let (answer, reasoning) = think_tags.finish();
+                chunk(answer, reasoning, Some(usage))
+            });
+            let _ = tx.send(last);
+        });
+
+        Ok(Box::pin(futures::stream::poll_fn(move |cx| {
+            rx.poll_recv(cx)
+        })))
+    }
+}
@@ -0,0 +1,930 @@
+//! Model Context Protocol client (`[[tools.mcp_servers]]`)
+//!
+//! A server is either launched as a child process speaking JSON-RPC 2.0
+//! over stdin/stdout, one message per line (`command`), or reached over
+//! HTTP (`url`): streamable HTTP, where each request is POSTed and answered
+//! with JSON or an SSE stream, falling back to the older HTTP+SSE transport
+//! for servers that refuse the POST. After the `initialize` handshake the
+//! server's tools are listed and offered to the model as `<server>__<tool>`,
+//! and calls are forwarded with `tools/call`.
+//!
+//! Requests to one server go one at a time. A server that exits, drops the
+//! connection or forgets its HTTP session is reconnected on the next
+//! request (retrying that request if it was never delivered), and its tools
+//! are listed again, as they are when it sends `tools/list_changed`. The
+//! stderr of a child process is appended to `~/.localgpt/logs/mcp-<server>.log`.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tracing::{debug, info, warn};
+
+use super::providers::{http_client, ImageAttachment, ToolSchema};
+use super::tools::Tool;
+use crate::config::{expand_env, Config, McpServerConfig, TimeoutConfig};
+
+/// Protocol revision sent in `initialize`
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Longest tool name providers accept
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// Header carrying a streamable HTTP session
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// The connection to a server was lost; `delivered` is whether the request
+/// reached it first (only undelivered requests are retried)
+#[derive(Debug)]
+pub struct McpDisconnected {
+    pub server: String,
+    pub delivered: bool,
+}
+
+impl std::fmt::Display for McpDisconnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lost the connection to MCP server '{}'", self.server)
+    }
+}
+
+impl std::error::Error for McpDisconnected {}
+
+/// An MCP server, connected on demand
+pub struct McpClient {
+    config: McpServerConfig,
+    timeout: Duration,
+    next_id: AtomicU64,
+    /// None until connected, and again after the connection is lost
+    transport: tokio::sync::Mutex<Option<Transport>>,
+    /// Set when the server's tools may have changed since they were listed
+    tools_changed: AtomicBool,
+}
+
+enum Transport {
+    Stdio {
+        stdin: ChildStdin,
+        stdout: Lines<BufReader<ChildStdout>>,
+        /// Killed when the transport is dropped
+        child: Child,
+    },
+    Http(HttpTransport),
+}
+
+/// A tool as the server describes it
+#[derive(Debug, Clone)]
+pub struct McpToolInfo {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+/// Result of `tools/call`
+#[derive(Debug, Default)]
+pub struct McpCallResult {
+    pub text: String,
+    pub images: Vec<ImageAttachment>,
+    /// The tool ran but reported a failure
+    pub is_error: bool,
+}
+
+impl McpClient {
+    /// Connect to the server and complete the `initialize` handshake
+    pub async fn start(config: &McpServerConfig) -> Result<Self> {
+        let client = Self {
+            config: config.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            next_id: AtomicU64::new(1),
+            transport: tokio::sync::Mutex::new(None),
+            tools_changed: AtomicBool::new(false),
+        };
+        *client.transport.lock().await = Some(client.connect().await?);
+        Ok(client)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Whether the tools should be listed again (reconnected, or the server
+    /// said they changed). Clears the flag.
+    pub fn take_tools_changed(&self) -> bool {
+        self.tools_changed.swap(false, Ordering::Relaxed)
+    }
+
+    /// Every tool the server offers, following pagination
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match cursor {
+                Some(ref cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                let Some(name) = tool["name"].as_str() else {
+                    continue;
+                };
+                let input_schema = match tool["inputSchema"] {
+                    Value::Object(_) => tool["inputSchema"].clone(),
+                    _ => json!({ "type": "object", "properties": {} }),
+                };
+                tools.push(McpToolInfo {
+                    name: name.to_string(),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema,
+                });
+            }
+            cursor = result["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// The server's tools, proxied
+    pub async fn tools(self: &Arc<Self>) -> Result<Vec<Box<dyn Tool>>> {
+        Ok(self
+            .list_tools()
+            .await?
+            .into_iter()
+            .map(|info| {
+                Box::new(McpTool {
+                    client: Arc::clone(self),
+                    name: tool_name(self.name(), &info.name),
+                    info,
+                }) as Box<dyn Tool>
+            })
+            .collect())
+    }
+
+    /// Whether `tool_name` is one of this server's tools
+    pub fn owns_tool(&self, tool_name: &str) -> bool {
+        tool_name.starts_with(&format!("{}__", sanitize_name(self.name())))
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<McpCallResult> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        Ok(parse_call_result(&result))
+    }
+
+    /// Send a request and wait for its response, reconnecting first if the
+    /// connection was lost
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let mut transport = self.transport.lock().await;
+        let mut retried = false;
+        loop {
+            if transport.is_none() {
+                info!("Reconnecting to MCP server '{}'", self.name());
+                *transport = Some(self.connect().await?);
+                // It may have been restarted with other tools
+                self.tools_changed.store(true, Ordering::Relaxed);
+            }
+            let Some(connected) = transport.as_mut() else {
+                unreachable!("connected above");
+            };
+            let result =
+                tokio::time::timeout(self.timeout, self.exchange(connected, method, &params))
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "MCP server '{}' did not answer {} within {}s",
+                            self.name(),
+                            method,
+                            self.timeout.as_secs()
+                        )
+                    })?;
+            let Err(e) = result else {
+                return result;
+            };
+            let Some(lost) = e.downcast_ref::<McpDisconnected>() else {
+                return Err(e);
+            };
+            *transport = None;
+            if lost.delivered || retried {
+                return Err(e);
+            }
+            retried = true;
+        }
+    }
+
+    /// Send one request on `transport` and read until its response,
+    /// answering anything the server asks in the meantime
+    async fn exchange(
+        &self,
+        transport: &mut Transport,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        transport.send(self.name(), &message).await?;
+        loop {
+            let reply = transport.receive(self.name()).await?;
+            if let Some(method) = reply.get("method") {
+                if method == "notifications/tools/list_changed" {
+                    self.tools_changed.store(true, Ordering::Relaxed);
+                }
+                if let Some(request_id) = reply.get("id") {
+                    transport
+                        .send(self.name(), &answer_server_request(request_id, method))
+                        .await?;
+                }
+                continue;
+            }
+            // Responses to requests that timed out earlier
+            if reply["id"].as_u64() != Some(id) {
+                continue;
+            }
+            if let Some(error) = reply.get("error") {
+                anyhow::bail!(
+                    "MCP server '{}': {}",
+                    self.name(),
+                    error["message"].as_str().unwrap_or("request failed")
+                );
+            }
+            return Ok(reply["result"].clone());
+        }
+    }
+
+    /// Open the transport and complete the `initialize` handshake on it
+    async fn connect(&self) -> Result<Transport> {
+        let mut transport = match (&self.config.url, &self.config.command) {
+            (Some(url), _) => Transport::Http(HttpTransport::new(&self.config, url)?),
+            (None, Some(command)) => self.spawn(command)?,
+            (None, None) => anyhow::bail!("MCP server '{}' needs a command or a url", self.name()),
+        };
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {
+                "name": "localgpt",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        let init = tokio::time::timeout(
+            self.timeout,
+            self.exchange(&mut transport, "initialize", &params),
+        )
+        .await
+        .with_context(|| format!("MCP server '{}' did not initialize", self.name()))??;
+        debug!(
+            "MCP server '{}' initialized: {}",
+            self.name(),
+            init["serverInfo"]
+        );
+        transport
+            .send(
+                self.name(),
+                &json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            )
+            .await?;
+        Ok(transport)
+    }
+
+    fn spawn(&self, program: &str) -> Result<Transport> {
+        let config = &self.config;
+        let mut command = Command::new(program);
+        command
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr_log(&config.name))
+            .kill_on_drop(true);
+        for (key, value) in &config.env {
+            command.env(key, expand_setting(&config.name, "env", key, value)?);
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start MCP server '{}'", config.name))?;
+        let stdin = child.stdin.take().context("MCP server stdin")?;
+        let stdout = child.stdout.take().context("MCP server stdout")?;
+        Ok(Transport::Stdio {
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            child,
+        })
+    }
+}
+
+/// Expand `${VAR}` in an env or header value
+fn expand_setting(server: &str, table: &str, key: &str, value: &str) -> Result<String> {
+    expand_env(value).map_err(|var| {
+        anyhow::anyhow!(
+            "MCP server '{}' uses ${{{}}} in {}.{}, which is not set",
+            server,
+            var,
+            table,
+            key
+        )
+    })
+}
+
+impl Transport {
+    async fn send(&mut self, server: &str, message: &Value) -> Result<()> {
+        let lost = |delivered| McpDisconnected {
+            server: server.to_string(),
+            delivered,
+        };
+        match self {
+            Self::Stdio { stdin, child, .. } => {
+                if !matches!(child.try_wait(), Ok(None)) {
+                    return Err(lost(false).into());
+                }
+                let mut line = message.to_string();
+                line.push('\n');
+                // A closed pipe means the server exited before reading this
+                stdin
+                    .write_all(line.as_bytes())
+                    .await
+                    .map_err(|_| lost(false))?;
+                stdin.flush().await.map_err(|_| lost(false))?;
+                Ok(())
+            }
+            Self::Http(http) => http.send(server, message).await,
+        }
+    }
+
+    async fn receive(&mut self, server: &str) -> Result<Value> {
+        match self {
+            Self::Stdio { stdout, .. } => loop {
+                let line = stdout.next_line().await?.ok_or_else(|| McpDisconnected {
+                    server: server.to_string(),
+                    delivered: true,
+                })?;
+                match serde_json::from_str(&line) {
+                    Ok(message) => return Ok(message),
+                    Err(_) => debug!("MCP server '{}' wrote non-JSON: {}", server, line),
+                }
+            },
+            Self::Http(http) => http.receive(server).await,
+        }
+    }
+}
+
+/// Streamable HTTP, or HTTP+SSE once the server refuses a POST to `url`
+struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    session_id: Option<String>,
+    /// HTTP+SSE: where messages are POSTed; replies arrive on `events`
+    post_url: Option<reqwest::Url>,
+    /// SSE being read: the last POST's reply, or the HTTP+SSE GET stream
+    events: Option<SseReader>,
+    /// Messages from a JSON reply, not yet read
+    queued: VecDeque<Value>,
+}
+
+impl HttpTransport {
+    fn new(config: &McpServerConfig, url: &str) -> Result<Self> {
+        let timeouts = TimeoutConfig {
+            timeout_secs: config.timeout_secs.max(1),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        for (key, value) in &config.headers {
+            let value = expand_setting(&config.name, "headers", key, value)?;
+            headers.insert(
+                HeaderName::from_bytes(key.as_bytes())
+                    .with_context(|| format!("Invalid header name '{}'", key))?,
+                HeaderValue::from_str(&value)
+                    .with_context(|| format!("Invalid value for header '{}'", key))?,
+            );
+        }
+        Ok(Self {
+            client: http_client(&timeouts, &config.network)?,
+            url: url.to_string(),
+            headers,
+            session_id: None,
+            post_url: None,
+            events: None,
+            queued: VecDeque::new(),
+        })
+    }
+
+    async fn send(&mut self, server: &str, message: &Value) -> Result<()> {
+        let lost = |delivered| McpDisconnected {
+            server: server.to_string(),
+            delivered,
+        };
+        let url = match self.post_url {
+            Some(ref url) => url.to_string(),
+            None => self.url.clone(),
+        };
+        let mut request = self
+            .client
+            .post(&url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        if let Some(ref session) = self.session_id {
+            request = request.header(SESSION_HEADER, session);
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) if e.is_connect() => return Err(lost(false).into()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && self.session_id.is_some() {
+            // The server no longer knows the session
+            return Err(lost(false).into());
+        }
+        if self.post_url.is_none()
+            && self.session_id.is_none()
+            && matches!(status.as_u16(), 400 | 404 | 405)
+            && message["method"] == "initialize"
+        {
+            // Not streamable HTTP: open the SSE stream and POST there instead
+            self.open_sse(server).await?;
+            return Box::pin(self.send(server, message)).await;
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("MCP server '{}' returned {}: {}", server, status, body);
+        }
+        if let Some(session) = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            self.session_id = Some(session.to_string());
+        }
+        // HTTP+SSE replies come on the GET stream; notifications get 202
+        if self.post_url.is_some() || status == reqwest::StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let is_sse = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|t| t.starts_with("text/event-stream"));
+        if is_sse {
+            self.events = Some(SseReader::new(response));
+        } else {
+            match response.json::<Value>().await? {
+                Value::Array(batch) => self.queued.extend(batch),
+                Value::Null => {}
+                reply => self.queued.push_back(reply),
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self, server: &str) -> Result<Value> {
+        if let Some(message) = self.queued.pop_front() {
+            return Ok(message);
+        }
+        let lost = McpDisconnected {
+            server: server.to_string(),
+            delivered: true,
+        };
+        let Some(ref mut events) = self.events else {
+            return Err(lost.into());
+        };
+        loop {
+            match events.next_event().await {
+                Ok(Some((event, data))) if event == "message" => {
+                    match serde_json::from_str(&data) {
+                        Ok(message) => return Ok(message),
+                        Err(_) => debug!("MCP server '{}' sent non-JSON: {}", server, data),
+                    }
+                }
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => {
+                    self.events = None;
+                    return Err(lost.into());
+                }
+            }
+        }
+    }
+
+    /// Switch to HTTP+SSE: GET the event stream and wait for the endpoint
+    /// messages are to be POSTed to
+    async fn open_sse(&mut self, server: &str) -> Result<()> {
+        let response = self
+            .client
+            .get(&self.url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "MCP server '{}' refused both streamable HTTP and SSE ({})",
+                server,
+                response.status()
+            );
+        }
+        let base = response.url().clone();
+        let mut events = SseReader::new(response);
+        while let Some((event, data)) = events.next_event().await? {
+            if event == "endpoint" {
+                self.post_url = Some(base.join(data.trim())?);
+                self.events = Some(events);
+                return Ok(());
+            }
+        }
+        anyhow::bail!(
+            "MCP server '{}' closed the SSE stream before naming an endpoint",
+            server
+        )
+    }
+}
+
+/// Server-sent events from a response body
+struct SseReader {
+    body: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    buffer: String,
+}
+
+impl SseReader {
+    fn new(response: reqwest::Response) -> Self {
+        Self {
+            body: response
+                .bytes_stream()
+                .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+                .boxed(),
+            buffer: String::new(),
+        }
+    }
+
+    /// The next event's type ("message" if unnamed) and data, or None at
+    /// the end of the stream
+    async fn next_event(&mut self) -> Result<Option<(String, String)>> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                if let Some(event) = parse_sse_event(&block) {
+                    return Ok(Some(event));
+                }
+                continue;
+            }
+            match self.body.next().await {
+                Some(chunk) => {
+                    let text = String::from_utf8_lossy(&chunk?).replace("\r\n", "\n");
+                    self.buffer.push_str(&text);
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Event type and data of one SSE block; None for comments and keep-alives
+fn parse_sse_event(block: &str) -> Option<(String, String)> {
+    let mut event = "message".to_string();
+    let mut data = Vec::new();
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            event = value.trim().to_string();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    (!data.is_empty()).then(|| (event, data.join("\n")))
+}
+
+/// Reply to a request from the server: LocalGPT offers no client features
+/// (sampling, roots), so only `ping` succeeds
+fn answer_server_request(id: &Value, method: &Value) -> Value {
+    if method == "ping" {
+        return json!({ "jsonrpc": "2.0", "id": id, "result": {} });
+    }
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+    })
+}
+
+/// Text, images and embedded text resources of a `tools/call` result
+fn parse_call_result(result: &Value) -> McpCallResult {
+    let mut parsed = McpCallResult {
+        is_error: result["isError"].as_bool().unwrap_or(false),
+        ..Default::default()
+    };
+    let mut texts = Vec::new();
+    for item in result["content"].as_array().into_iter().flatten() {
+        match item["type"].as_str() {
+            Some("text") => texts.push(item["text"].as_str().unwrap_or_default().to_string()),
+            Some("image") => {
+                if let (Some(data), Some(media_type)) =
+                    (item["data"].as_str(), item["mimeType"].as_str())
+                {
+                    parsed.images.push(ImageAttachment {
+                        data: data.to_string(),
+                        media_type: media_type.to_string(),
+                    });
+                }
+            }
+            Some("resource") => {
+                let resource = &item["resource"];
+                match resource["text"].as_str() {
+                    Some(text) => texts.push(text.to_string()),
+                    None => texts.push(format!(
+                        "[binary resource {}]",
+                        resource["uri"].as_str().unwrap_or_default()
+                    )),
+                }
+            }
+            _ => {}
+        }
+    }
+    // Some servers return only structured output
+    if texts.is_empty() && !result["structuredContent"].is_null() {
+        texts.push(result["structuredContent"].to_string());
+    }
+    parsed.text = texts.join("\n");
+    parsed
+}
+
+/// Where a server's stderr goes; discarded if the log can't be opened
+fn stderr_log(server: &str) -> Stdio {
+    let file = super::get_state_dir().and_then(|dir| {
+        let dir = dir.join("logs");
+        std::fs::create_dir_all(&dir)?;
+        Ok(OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("mcp-{}.log", sanitize_name(server))))?)
+    });
+    match file {
+        Ok(file) => Stdio::from(file),
+        Err(_) => Stdio::null(),
+    }
+}
+
+/// Letters, digits, `_` and `-` only, as tool names must be
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Name a server's tool is offered under
+fn tool_name(server: &str, tool: &str) -> String {
+    let mut name = format!("{}__{}", sanitize_name(server), sanitize_name(tool));
+    name.truncate(MAX_TOOL_NAME_LEN);
+    name
+}
+
+/// One of a server's tools, proxied
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    info: McpToolInfo,
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name.clone(),
+            description: format!(
+                "{} (MCP server '{}')",
+                self.info.description,
+                self.client.name()
+            ),
+            parameters: self.info.input_schema.clone(),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        Ok(self.execute_with_images(arguments).await?.0)
+    }
+
+    async fn execute_with_images(&self, arguments: &str) -> Result<(String, Vec<ImageAttachment>)> {
+        let arguments = match arguments.trim() {
+            "" => json!({}),
+            text => serde_json::from_str(text)?,
+        };
+        let result = self.client.call_tool(&self.info.name, arguments).await?;
+        if result.is_error {
+            anyhow::bail!("{}", result.text);
+        }
+        Ok((result.text, result.images))
+    }
+
+    /// Whatever a server returns comes from outside the user's control
+    fn untrusted_output(&self) -> bool {
+        true
+    }
+}
+
+/// Start the MCP servers configured for `agent_id` (every agent, if a server
+/// names none). A server that fails to start is logged and skipped.
+pub async fn start_mcp_servers(config: &Config, agent_id: &str) -> Vec<Arc<McpClient>> {
+    let mut clients = Vec::new();
+    for server in &config.tools.mcp_servers {
+        if !server.agents.is_empty() && !server.agents.iter().any(|a| a == agent_id) {
+            continue;
+        }
+        match McpClient::start(server).await {
+            Ok(client) => clients.push(Arc::new(client)),
+            Err(e) => warn!("Skipping MCP server '{}': {:#}", server.name, e),
+        }
+    }
+    clients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap as RequestHeaders, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_parse_call_result() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "first" },
+                { "type": "image", "data": "aGk=", "mimeType": "image/png" },
+                { "type": "resource", "resource": { "uri": "file:///a.txt", "text": "second" } },
+            ],
+            "isError": true,
+        });
+        let parsed = parse_call_result(&result);
+        assert_eq!(parsed.text, "first\nsecond");
+        assert_eq!(parsed.images.len(), 1);
+        assert!(parsed.is_error);
+
+        assert_eq!(
+            tool_name("git hub", "create.issue"),
+            "git_hub__create_issue"
+        );
+        assert_eq!(tool_name(&"s".repeat(40), &"t".repeat(40)).len(), 64);
+    }
+
+    #[test]
+    fn test_parse_sse_event() {
+        assert_eq!(
+            parse_sse_event("data: {\"a\":1}\n\n"),
+            Some(("message".to_string(), "{\"a\":1}".to_string()))
+        );
+        assert_eq!(
+            parse_sse_event("event: endpoint\ndata: /messages?id=1\n\n"),
+            Some(("endpoint".to_string(), "/messages?id=1".to_string()))
+        );
+        assert_eq!(
+            parse_sse_event("data:one\ndata: two\n\n").map(|e| e.1),
+            Some("one\ntwo".to_string())
+        );
+        assert_eq!(parse_sse_event(": keep-alive\n\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_stdio_server() {
+        // A minimal server in shell: answers initialize, tools/list and one
+        // call, then exits
+        let script = r#"
+reply() { read line; id=$(echo "$line" | sed 's/.*"id":\([0-9]*\).*/\1/'); echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$1}"; }
+reply '{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"sh"}}'
+read line
+reply '{"tools":[{"name":"hello","description":"Say hi","inputSchema":{"type":"object"}}]}'
+echo '{"jsonrpc":"2.0","method":"notifications/message","params":{}}'
+reply '{"content":[{"type":"text","text":"hi there"}]}'
+"#;
+        let config = McpServerConfig {
+            name: "sh".to_string(),
+            command: Some("sh".to_string()),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout_secs: 5,
+            ..Default::default()
+        };
+        let client = Arc::new(McpClient::start(&config).await.unwrap());
+        let tools = client.tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].schema().name, "sh__hello");
+        assert!(client.owns_tool("sh__hello"));
+        assert_eq!(tools[0].execute("{}").await.unwrap(), "hi there");
+        assert!(!client.take_tools_changed());
+
+        // Once the server has exited, it is started again for the next request
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(client.list_tools().await.unwrap().len(), 1);
+        assert!(client.take_tools_changed());
+    }
+
+    #[derive(Default)]
+    struct TestServer {
+        sessions: u32,
+        /// Sessions the server has forgotten
+        expired: Vec<String>,
+        authorized: bool,
+    }
+
+    async fn handle_post(
+        State(server): State<Arc<Mutex<TestServer>>>,
+        headers: RequestHeaders,
+        axum::Json(message): axum::Json<Value>,
+    ) -> Response {
+        let mut server = server.lock().unwrap();
+        server.authorized = headers
+            .get("authorization")
+            .is_some_and(|v| v == "Bearer secret");
+        let session = headers
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "initialize" {
+            server.sessions += 1;
+            let reply = json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "result": { "protocolVersion": PROTOCOL_VERSION, "capabilities": {} },
+            });
+            return (
+                [(SESSION_HEADER, format!("s{}", server.sessions))],
+                axum::Json(reply),
+            )
+                .into_response();
+        }
+        if session.is_none() || server.expired.contains(session.as_ref().unwrap()) {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        if message.get("id").is_none() {
+            return StatusCode::ACCEPTED.into_response();
+        }
+        // Answered as an event stream, after a notification
+        let result =
+            json!({ "tools": [{ "name": "search", "inputSchema": { "type": "object" } }] });
+        let body = format!(
+            "event: message\ndata: {}\n\ndata: {}\n\n",
+            json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }),
+            json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+        );
+        (
+            [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
+            body,
+        )
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_server() {
+        let state = Arc::new(Mutex::new(TestServer::default()));
+        let app = axum::Router::new()
+            .route("/mcp", axum::routing::post(handle_post))
+            .with_state(Arc::clone(&state));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        std::env::set_var("LOCALGPT_TEST_MCP_TOKEN", "secret");
+        let config = McpServerConfig {
+            name: "remote".to_string(),
+            url: Some(format!("http://{}/mcp", addr)),
+            headers: [(
+                "Authorization".to_string(),
+                "Bearer ${LOCALGPT_TEST_MCP_TOKEN}".to_string(),
+            )]
+            .into(),
+            timeout_secs: 5,
+            ..Default::default()
+        };
+        let client = McpClient::start(&config).await.unwrap();
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "search");
+        assert!(state.lock().unwrap().authorized);
+        assert!(client.take_tools_changed());
+
+        // The server forgets the session: a new one is opened and the
+        // request sent again
+        state.lock().unwrap().expired.push("s1".to_string());
+        assert_eq!(client.list_tools().await.unwrap().len(), 1);
+        assert_eq!(state.lock().unwrap().sessions, 2);
+        assert!(client.take_tools_changed());
+    }
+}
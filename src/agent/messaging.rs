@@ -0,0 +1,127 @@
+//! Agent-to-agent messaging
+//!
+//! The message_agent tool sends a message or task to another agent ID (e.g. a
+//! "coder" agent delegating research to "researcher"). The recipient answers
+//! in its most recent session, or a new one, which is saved afterwards, so the
+//! exchange appears in both transcripts: as a tool call in the sender's and as
+//! a user turn in the recipient's. The recipient runs with its own settings
+//! (`agents/<id>/agent.toml`) but without message_agent, so calls cannot
+//! chain or loop.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::providers::ToolSchema;
+use super::session::get_last_session_id_for_agent;
+use super::tools::Tool;
+use super::{Agent, AgentConfig};
+use crate::config::Config;
+use crate::memory::MemoryManager;
+
+pub struct MessageAgentTool {
+    config: Config,
+    /// Agent ID of the sender
+    from: String,
+}
+
+impl MessageAgentTool {
+    pub fn new(config: &Config, from: &str) -> Self {
+        Self {
+            config: config.clone(),
+            from: from.to_string(),
+        }
+    }
+
+    fn recipients(&self) -> Vec<&str> {
+        self.config
+            .tools
+            .agent_messaging
+            .iter()
+            .map(|a| a.as_str())
+            .filter(|a| *a != self.from)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Tool for MessageAgentTool {
+    fn name(&self) -> &str {
+        "message_agent"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let recipients = self.recipients();
+        ToolSchema {
+            name: "message_agent".to_string(),
+            description: format!(
+                "Send a message or task to another agent and wait for its reply. \
+                 The agent has its own memory and session history. Available agents: {}",
+                recipients.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "agent": {
+                        "type": "string",
+                        "enum": recipients,
+                        "description": "ID of the agent to message"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "The message or task, with any context the agent needs"
+                    }
+                },
+                "required": ["agent", "message"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let to = args["agent"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing agent"))?;
+        let message = args["message"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing message"))?;
+
+        if !self.recipients().contains(&to) {
+            anyhow::bail!(
+                "Agent '{}' is not available (allowed: {})",
+                to,
+                self.recipients().join(", ")
+            );
+        }
+
+        info!("Agent '{}' messaging agent '{}'", self.from, to);
+        let reply = deliver(&self.from, to, message).await?;
+        Ok(format!("Reply from agent '{}':\n\n{}", to, reply))
+    }
+}
+
+/// Run one turn of the recipient agent in its latest session and save it
+async fn deliver(from: &str, to: &str, message: &str) -> Result<String> {
+    // The recipient's own settings (agents/<to>/agent.toml), not the sender's
+    let mut config = Config::load_for_agent(to)?;
+    config.tools.agent_messaging.clear();
+
+    let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), to)?;
+    let agent_config = AgentConfig {
+        model: config.agent.default_model.clone(),
+        context_window: config.agent.context_window_or_default(),
+        reserve_tokens: config.agent.reserve_tokens,
+    };
+    let mut agent = Agent::new(agent_config, &config, memory).await?;
+    match get_last_session_id_for_agent(to)? {
+        Some(id) => agent.resume_session_for_agent(to, &id).await?,
+        None => agent.new_session().await?,
+    }
+
+    let reply = agent
+        .chat(&format!("[Message from agent '{}']\n\n{}", from, message))
+        .await?;
+    agent.save_session_for_agent(to).await?;
+    Ok(reply)
+}
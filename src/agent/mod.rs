@@ -1,15 +1,108 @@
+mod abort;
+mod approval;
+mod approval_policy;
+mod audit_log;
+mod budget;
+mod calendar_tools;
+mod checkpoint;
+mod clipboard;
+mod code_interpreter;
+mod code_search;
+mod codex_cli;
+mod context;
+mod diff;
+mod doctor;
+mod document_search;
+mod email;
+mod feedback;
+mod fetch_url;
+#[cfg(feature = "gguf")]
+mod gguf;
+mod mcp;
+mod messaging;
+mod model_usage;
+mod moderation;
+mod notify;
+mod ocr;
+mod ollama_hosts;
+mod ollama_models;
+mod plan;
 mod providers;
+mod readability;
+mod replay;
+mod repo_map;
+mod response_cache;
+mod retry;
+mod sandbox;
 mod sanitize;
 mod session;
 mod session_store;
+mod share;
 mod skills;
+mod snapshot;
+mod sql;
+mod stats;
+mod subagent;
 mod system_prompt;
+mod test_runner;
+mod tokens;
+mod tool_output;
+mod tool_registry;
 mod tools;
+mod turn_limits;
+mod web_search;
 
+pub use abort::AbortHandle;
+pub use approval::{PendingApproval, ToolApprover, DENIED_OUTPUT};
+pub use approval_policy::{
+    ApprovalPolicies, ApprovalPolicy, ApprovalRule, ApprovalScope, ApprovalStore, APPROVALS_FILE,
+};
+pub use budget::{BudgetExceededError, DaySpend, SpendStore};
+pub use calendar_tools::{changes_calendar, CalendarTool, ReminderTool};
+pub use checkpoint::{partial_turn_path, take_partial_turn, PartialTurn, TurnCheckpoint};
+pub use clipboard::{paste_message, read_clipboard, write_clipboard, ClipboardTool};
+pub use code_interpreter::CodeInterpreterTool;
+pub use code_search::{FindFilesTool, GrepWorkspaceTool};
+pub use context::{ContextReport, ContextSegment};
+pub use diff::unified_diff;
+pub use doctor::{run_health_checks, ProviderHealth};
+pub use document_search::{parse_sources, DocumentSearchTool};
+pub use email::{EmailAccounts, EmailReadTool, EmailSearchTool, EmailSendTool};
+pub use feedback::{
+    collect_rated_pairs, export_rated_pairs, load_feedback_summary, summarize_feedback,
+    update_feedback_summary, Feedback, RatedPair, Rating,
+};
+pub use fetch_url::FetchUrlTool;
+pub use mcp::{start_mcp_servers, McpCallResult, McpClient, McpDisconnected, McpTool, McpToolInfo};
+pub use messaging::MessageAgentTool;
+pub use model_usage::{
+    ModelSuggestion, ModelUsageReport, ModelUsageStore, ModelUsageSummary, TaskUsage, UsageTask,
+};
+pub use moderation::{
+    KeywordModerator, ModerationAction, ModerationDirection, ModerationHook, ModerationResult,
+    Moderator, OpenAIModerator,
+};
+pub use notify::{send_notification, NotifyTool, Urgency};
+pub use ocr::{Ocr, OcrEngine};
+pub use ollama_hosts::{
+    find_host, prepare_local_model, probe_hosts, LocalModelState, LocalModelStatus,
+    OllamaHostStatus,
+};
+pub use ollama_models::{
+    delete_ollama_model, list_ollama_models, ollama_model_host, pull_ollama_model, OllamaModelInfo,
+    OllamaPullProgress,
+};
+pub use plan::{Plan, PlanStep, StepStatus, MAX_PLAN_STEPS};
 pub use providers::{
-    ImageAttachment, LLMProvider, LLMResponse, LLMResponseContent, Message, Role, StreamChunk,
-    StreamEvent, StreamResult, ToolCall, ToolSchema, Usage,
+    check_api_key, list_available_models, GenerationParams, ImageAttachment, LLMProvider,
+    LLMResponse, LLMResponseContent, Message, ProviderAuthError, ProviderModels,
+    ProviderTimeoutError, ProviderToolEvent, RegenerateStyle, Role, StreamChunk, StreamEvent,
+    StreamResult, ToolCall, ToolCallAccumulator, ToolCallDelta, ToolSchema, Usage,
+    DEFAULT_COMPACTION_PROMPT, DEFAULT_TITLE_PROMPT, FACT_EXTRACTION_PROMPT,
+    TOOL_OUTPUT_SUMMARY_PROMPT,
 };
+pub use response_cache::{clear_response_cache, response_cache_dir, response_cache_len};
+pub use sandbox::PathSandbox;
 pub use sanitize::{
     wrap_external_content, wrap_memory_content, wrap_tool_output, MemorySource, SanitizeResult,
     EXTERNAL_CONTENT_END, EXTERNAL_CONTENT_START, MEMORY_CONTENT_END, MEMORY_CONTENT_START,
@@ -17,29 +110,111 @@ pub use sanitize::{
 };
 pub use session::{
     get_last_session_id, get_last_session_id_for_agent, get_sessions_dir_for_agent, get_state_dir,
-    list_sessions, list_sessions_for_agent, search_sessions, search_sessions_for_agent, Session,
-    SessionInfo, SessionMessage, SessionSearchResult, SessionStatus, DEFAULT_AGENT_ID,
+    list_sessions, list_sessions_for_agent, search_sessions, search_sessions_for_agent,
+    CompactionEvent, CompactionPlan, Session, SessionInfo, SessionMessage, SessionSearchResult,
+    SessionStatus, DEFAULT_AGENT_ID,
 };
 pub use session_store::{SessionEntry, SessionStore};
+pub use share::{default_share_path, redact_secrets, ShareOptions};
 pub use skills::{get_skills_summary, load_skills, parse_skill_command, Skill, SkillInvocation};
+pub use snapshot::{SnapshotStore, UndoSummary, SNAPSHOT_TOOLS};
+pub use sql::SqlTool;
+pub use stats::{sparkline, SessionStats};
+pub use subagent::SpawnSubagentTool;
 pub use system_prompt::{
-    build_heartbeat_prompt, is_heartbeat_ok, is_silent_reply, HEARTBEAT_OK_TOKEN,
-    SILENT_REPLY_TOKEN,
+    build_heartbeat_prompt, is_heartbeat_ok, is_silent_reply, language_instruction,
+    HEARTBEAT_OK_TOKEN, SILENT_REPLY_TOKEN,
+};
+pub use test_runner::RunTestsTool;
+pub use tokens::TokenCounter;
+pub use tool_registry::{register_tool, tool_registry, ToolContext, ToolFactory, ToolRegistry};
+pub use tools::{
+    extract_tool_detail, workspace_path, ShellEvent, Tool, ToolResult, ALWAYS_REQUIRE_APPROVAL,
+    PARALLEL_SAFE_TOOLS, UNTRUSTED_OUTPUT_TOOLS,
 };
-pub use tools::{extract_tool_detail, Tool, ToolResult};
+pub use web_search::{SearchBackend, SearchResult, WebSearchTool};
 
 use anyhow::Result;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tracing::{debug, info};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
-use crate::config::Config;
+use crate::config::{CompactionStrategy, Config, ToolsConfig, SUMMARIZER_ALIAS};
 use crate::memory::{MemoryChunk, MemoryManager};
+use model_usage::estimated_cost;
+use repo_map::RepoMap;
+use turn_limits::TurnLimits;
 
 /// Soft threshold buffer before compaction (tokens)
 /// Memory flush runs when within this buffer of the hard limit
 const MEMORY_FLUSH_SOFT_THRESHOLD: usize = 4000;
 
+/// Longest excerpt of a tool output sent for summarization
+const MAX_TOOL_SUMMARY_INPUT_CHARS: usize = 20_000;
+
+/// Most facts one session's extraction pass may add to USER.md
+const MAX_EXTRACTED_FACTS: usize = 10;
+
+/// Longer "facts" from extraction are dropped
+const MAX_FACT_CHARS: usize = 200;
+
+/// Latest conversation messages the planning request sees
+const MAX_PLAN_CONTEXT_MESSAGES: usize = 6;
+
+/// How long a listing of the providers' models is trusted for `@model`
+const MODEL_LIST_TTL: Duration = Duration::from_secs(300);
+
+/// Shown in place of a model response blocked by content moderation
+const MODERATION_BLOCKED_RESPONSE: &str = "[Response withheld by content moderation]";
+
+/// A model response, or its replacement if moderation blocks it
+async fn moderated_output(hook: &ModerationHook, response: String) -> String {
+    match hook.review(&response, ModerationDirection::Output).await {
+        Some(_) => MODERATION_BLOCKED_RESPONSE.to_string(),
+        None => response,
+    }
+}
+
+/// A reply stream whose text is withheld until the end, then moderated and
+/// sent in the final chunk. Other chunk contents pass through as they come.
+fn hold_for_moderation(
+    mut stream: StreamResult,
+    hook: Arc<ModerationHook>,
+) -> impl futures::Stream<Item = Result<StreamChunk>> + Send {
+    use futures::StreamExt;
+
+    async_stream::stream! {
+        let mut text = String::new();
+        let mut finished = false;
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(mut chunk) => {
+                    text.push_str(&std::mem::take(&mut chunk.delta));
+                    if chunk.done {
+                        chunk.delta = moderated_output(&hook, std::mem::take(&mut text)).await;
+                        finished = true;
+                    }
+                    yield Ok(chunk);
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+        if !finished && !text.is_empty() {
+            yield Ok(StreamChunk {
+                delta: moderated_output(&hook, text).await,
+                done: true,
+                tool_calls: None,
+                tool_call_deltas: Vec::new(),
+                provider_tools: Vec::new(),
+                reasoning: String::new(),
+                usage: None,
+            });
+        }
+    }
+}
+
 /// Generate a URL-safe slug from text (first 3-5 words, lowercased, hyphenated)
 fn generate_slug(text: &str) -> String {
     text.split_whitespace()
@@ -58,9 +233,85 @@ fn generate_slug(text: &str) -> String {
         .collect()
 }
 
+/// Parse a per-turn model override prefix: `@model rest of message`.
+/// Returns the model name and the message with the prefix removed. Whether
+/// the name is a model is up to the caller (see `Agent::turn_model_override`).
+pub fn parse_model_override(message: &str) -> Option<(&str, &str)> {
+    let rest = message.trim_start().strip_prefix('@')?;
+    let (model, prompt) = rest.split_once(char::is_whitespace)?;
+    let prompt = prompt.trim_start();
+    if model.is_empty() || prompt.is_empty() {
+        return None;
+    }
+    Some((model, prompt))
+}
+
+/// `agent.summarize_model`, else `summarizer` if `[models.aliases]` defines it
+fn summarize_model(config: &Config) -> Option<&str> {
+    config.agent.summarize_model.as_deref().or_else(|| {
+        config
+            .models
+            .aliases
+            .contains_key(SUMMARIZER_ALIAS)
+            .then_some(SUMMARIZER_ALIAS)
+    })
+}
+
+/// Provider for an optional secondary model; falls back to the main model
+/// (None) if it is unset or cannot be created
+fn optional_provider(model: Option<&str>, config: &Config) -> Option<Box<dyn LLMProvider>> {
+    match providers::create_provider(model?, config) {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            warn!("Using the main model instead of '{}': {}", model?, e);
+            None
+        }
+    }
+}
+
+/// Indexes of tool calls in batches that run one after another. Calls in a
+/// batch may run together; only `PARALLEL_SAFE_TOOLS` share a batch, any
+/// other call is a batch of its own.
+fn tool_call_batches(calls: &[ToolCall]) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut reads = Vec::new();
+    for (index, call) in calls.iter().enumerate() {
+        if PARALLEL_SAFE_TOOLS.contains(&call.name.as_str()) {
+            reads.push(index);
+        } else {
+            if !reads.is_empty() {
+                batches.push(std::mem::take(&mut reads));
+            }
+            batches.push(vec![index]);
+        }
+    }
+    if !reads.is_empty() {
+        batches.push(reads);
+    }
+    batches
+}
+
+/// Add a server's tools, except ones the config leaves out and names already taken
+fn add_mcp_tools(tools: &mut Vec<Box<dyn Tool>>, found: Vec<Box<dyn Tool>>, config: &ToolsConfig) {
+    for tool in found {
+        if !config.offers(tool.name()) {
+            continue;
+        }
+        if tools.iter().any(|t| t.name() == tool.name()) {
+            warn!(
+                "Skipping MCP tool {}: a tool with that name exists",
+                tool.name()
+            );
+            continue;
+        }
+        tools.push(tool);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentConfig {
     pub model: String,
+    /// Context window for models without a known or configured limit
     pub context_window: usize,
     pub reserve_tokens: usize,
 }
@@ -74,6 +325,77 @@ pub struct Agent {
     tools: Vec<Box<dyn Tool>>,
     /// Cumulative token usage for this session
     cumulative_usage: Usage,
+    /// Provider for the current turn only (set by an `@model` prefix)
+    turn_override: Option<(String, Box<dyn LLMProvider>)>,
+    /// Model the next turn goes to (`set_turn_model`), whatever its message
+    next_turn_model: Option<String>,
+    /// Models the providers offered, for `@name` prefixes that aren't
+    /// recognizably models
+    listed_models: Vec<String>,
+    /// When every provider last answered a listing (None if one failed)
+    models_listed_at: Option<Instant>,
+    /// Optional content moderation for user messages and responses
+    moderation: Option<Arc<ModerationHook>>,
+    /// Files whose current contents are included in every request
+    pinned_files: Vec<PathBuf>,
+    /// Whether the last batch of tool calls brought untrusted content into context
+    untrusted_in_context: bool,
+    /// Live output of bash tool executions
+    shell_events: tokio::sync::broadcast::Sender<ShellEvent>,
+    /// Copies of files taken before tools change them, for `/undo`
+    /// (shared with the blocking threads that take them)
+    snapshots: Option<Arc<Mutex<SnapshotStore>>>,
+    /// Whether in-progress turns are checkpointed to disk
+    checkpoints_enabled: bool,
+    /// Checkpoint for the turn in progress (shared with the response stream)
+    checkpoint: Option<Arc<Mutex<TurnCheckpoint>>>,
+    /// Model for tool output summaries (`tools.tool_output_summary_model`)
+    summary_provider: Option<Box<dyn LLMProvider>>,
+    /// Model for background work (`agent.auxiliary_model`)
+    auxiliary_provider: Option<Box<dyn LLMProvider>>,
+    /// Model for compaction and titles (`agent.summarize_model`)
+    summarizer_provider: Option<Box<dyn LLMProvider>>,
+    /// Per-model latency, failures and tokens, for model suggestions
+    model_usage: Mutex<ModelUsageStore>,
+    /// Whether this turn used memory tools (its replies count as memory queries)
+    turn_used_memory: bool,
+    /// Model and task of the last reply, charged if it is regenerated
+    last_reply: Option<(String, UsageTask)>,
+    /// Start of a streamed request the caller is still consuming
+    reply_started: Option<Instant>,
+    /// Token counts the provider reported for that streamed request
+    streamed_usage: Arc<Mutex<Option<Usage>>>,
+    /// Stops the streamed turn in progress
+    abort: AbortHandle,
+    /// Tokens and cost per provider and day, for `[budget]`
+    spend: Mutex<SpendStore>,
+    /// Providers the user chose to keep using past their budget
+    budget_overrides: HashSet<String>,
+    /// Warning about a budget running low, for the frontend to show
+    budget_warning: Option<String>,
+    /// The main model's context window
+    context_window: usize,
+    /// Compaction summarizing on its own thread
+    compaction: Option<BackgroundCompaction>,
+    /// MCP servers this agent's tools come from
+    mcp_clients: Vec<Arc<McpClient>>,
+    /// Asks the user about tool calls in streamed turns
+    approver: Option<ToolApprover>,
+    /// Per-tool approval policies (`agents/<id>/approvals.toml`, plus
+    /// choices remembered for the session)
+    approvals: ApprovalStore,
+    /// No one can be asked (sub-agents): calls that need approval are refused
+    unattended: bool,
+    /// Outline of the workspace's source code (`agent.repo_map_tokens`)
+    repo_map: Option<Mutex<RepoMap>>,
+}
+
+/// A compaction whose summary is being written on another thread
+struct BackgroundCompaction {
+    plan: CompactionPlan,
+    model: String,
+    started: Instant,
+    summary: tokio::sync::oneshot::Receiver<Result<Option<String>>>,
 }
 
 impl Agent {
@@ -82,50 +404,728 @@ impl Agent {
         app_config: &Config,
         memory: MemoryManager,
     ) -> Result<Self> {
+        // Providers log to this agent's audit directory
+        let mut app_config = app_config.clone();
+        app_config.providers.audit.agent_id = Some(memory.agent_id().to_string());
+        let app_config = &app_config;
         let provider = providers::create_provider(&config.model, app_config)?;
 
         // Wrap memory in Arc so tools can share it
         let memory = Arc::new(memory);
-        let tools = tools::create_default_tools(app_config, Some(Arc::clone(&memory)))?;
+        let (shell_events, _) = tokio::sync::broadcast::channel(1024);
+        let approvals = ApprovalStore::load(memory.agent_id())?;
+        let mut tools = tool_registry().build(&ToolContext {
+            config: app_config,
+            agent_id: memory.agent_id(),
+            memory: Some(Arc::clone(&memory)),
+            approvals: approvals.clone(),
+            shell_events: shell_events.clone(),
+        })?;
+        let mcp_clients = mcp::start_mcp_servers(app_config, memory.agent_id()).await;
+        for client in &mcp_clients {
+            match client.tools().await {
+                Ok(found) => {
+                    info!("MCP server '{}': {} tools", client.name(), found.len());
+                    add_mcp_tools(&mut tools, found, &app_config.tools);
+                }
+                Err(e) => warn!(
+                    "Could not list tools of MCP server '{}': {:#}",
+                    client.name(),
+                    e
+                ),
+            }
+        }
+        let snapshots = if app_config.tools.workspace_snapshots {
+            Some(Arc::new(Mutex::new(SnapshotStore::new(
+                get_state_dir()?.join("snapshots"),
+                PathSandbox::from_config(app_config),
+            ))))
+        } else {
+            None
+        };
+
+        let summary_provider = optional_provider(
+            app_config.tools.tool_output_summary_model.as_deref(),
+            app_config,
+        );
+        let auxiliary_provider =
+            optional_provider(app_config.agent.auxiliary_model.as_deref(), app_config);
+        let summarizer_provider = optional_provider(summarize_model(app_config), app_config);
+        let mut session = Session::new();
+        session.set_token_counter(TokenCounter::for_model(&config.model, app_config));
+        let context_window =
+            tokens::context_window(&config.model, app_config, config.context_window);
+        let repo_map = (app_config.agent.repo_map_tokens > 0).then(|| {
+            Mutex::new(RepoMap::new(
+                memory.workspace(),
+                app_config.agent.repo_map_tokens,
+                session.token_counter(),
+            ))
+        });
 
         Ok(Self {
             config,
             app_config: app_config.clone(),
             provider,
-            session: Session::new(),
+            session,
             memory,
             tools,
             cumulative_usage: Usage::default(),
+            turn_override: None,
+            next_turn_model: None,
+            listed_models: Vec::new(),
+            models_listed_at: None,
+            moderation: ModerationHook::from_config(app_config)?.map(Arc::new),
+            pinned_files: Vec::new(),
+            untrusted_in_context: false,
+            shell_events,
+            snapshots,
+            checkpoints_enabled: false,
+            checkpoint: None,
+            summary_provider,
+            auxiliary_provider,
+            summarizer_provider,
+            model_usage: Mutex::new(ModelUsageStore::load()),
+            turn_used_memory: false,
+            last_reply: None,
+            reply_started: None,
+            streamed_usage: Arc::new(Mutex::new(None)),
+            abort: AbortHandle::default(),
+            spend: Mutex::new(SpendStore::load()),
+            budget_overrides: HashSet::new(),
+            budget_warning: None,
+            context_window,
+            compaction: None,
+            mcp_clients,
+            approver: None,
+            approvals,
+            unattended: false,
+            repo_map,
         })
     }
 
+    /// Provider for background work such as titles and summaries
+    fn auxiliary(&self) -> &dyn LLMProvider {
+        match self.auxiliary_provider {
+            Some(ref provider) => provider.as_ref(),
+            None => self.provider.as_ref(),
+        }
+    }
+
+    /// Model name behind `auxiliary()`
+    fn auxiliary_model(&self) -> &str {
+        match (
+            &self.auxiliary_provider,
+            &self.app_config.agent.auxiliary_model,
+        ) {
+            (Some(_), Some(model)) => model,
+            _ => &self.config.model,
+        }
+    }
+
+    /// Provider for compaction summaries and session titles
+    fn summarizer(&self) -> &dyn LLMProvider {
+        match self.summarizer_provider {
+            Some(ref provider) => provider.as_ref(),
+            None => self.auxiliary(),
+        }
+    }
+
+    /// Model name behind `summarizer()`
+    fn summarizer_model(&self) -> &str {
+        match (&self.summarizer_provider, summarize_model(&self.app_config)) {
+            (Some(_), Some(model)) => self.app_config.models.resolve(model),
+            _ => self.auxiliary_model(),
+        }
+    }
+
+    /// Switch the model for background work (None uses the main model)
+    pub fn set_auxiliary_model(&mut self, model: Option<&str>) -> Result<()> {
+        self.auxiliary_provider = match model {
+            Some(model) => Some(providers::create_provider(model, &self.app_config)?),
+            None => None,
+        };
+        self.app_config.agent.auxiliary_model = model.map(str::to_string);
+        Ok(())
+    }
+
     pub fn model(&self) -> &str {
         &self.config.model
     }
 
+    /// Model answering the current turn (the `@model` override, if any)
+    pub fn active_model(&self) -> &str {
+        match self.turn_override {
+            Some((ref model, _)) => model,
+            None => &self.config.model,
+        }
+    }
+
+    fn active_provider(&self) -> &dyn LLMProvider {
+        match self.turn_override {
+            Some((_, ref provider)) => &**provider,
+            None => &*self.provider,
+        }
+    }
+
+    /// Start a new user turn, routing it to the model set with
+    /// `set_turn_model`, or to another model if the message starts with
+    /// `@model`. Returns the message with the prefix stripped.
+    async fn begin_turn<'a>(&mut self, message: &'a str) -> Result<&'a str> {
+        if self.next_turn_model.is_none() {
+            self.list_models_for_override(message).await;
+        }
+        let check = self.check_budget(message);
+        let forced = self.next_turn_model.take();
+        check?;
+        self.reset_turn_state();
+
+        let (model, prompt) = match forced {
+            Some(model) => (model, message),
+            None => match self.turn_model_override(message) {
+                Some(routed) => routed,
+                None => return Ok(message),
+            },
+        };
+        let mut provider = providers::create_provider(&model, &self.app_config)
+            .map_err(|e| anyhow::anyhow!("Cannot use {} for this turn: {}", model, e))?;
+        provider.set_generation_params(self.session.generation_params());
+        info!("Routing this turn to model: {}", model);
+        self.turn_override = Some((model, provider));
+        Ok(prompt)
+    }
+
+    /// Send the next turn to `model` (`/ask`), whatever its message starts
+    /// with. Fails if `model` isn't a model name or one a provider lists.
+    pub async fn set_turn_model(&mut self, model: &str) -> Result<()> {
+        if self.resolve_model(model).is_none() {
+            self.list_models(true).await;
+        }
+        let resolved = self
+            .resolve_model(model)
+            .ok_or_else(|| anyhow::anyhow!("Unknown model '{}'", model))?;
+        self.next_turn_model = Some(resolved);
+        Ok(())
+    }
+
+    /// Refuse a turn for `message` when its model's provider is over budget
+    /// (`[budget]`) and hasn't been overridden. A warning that a budget is
+    /// running low is kept for `take_budget_warning`.
+    pub fn check_budget(&mut self, message: &str) -> Result<()> {
+        let model = match self.next_turn_model.clone() {
+            Some(model) => model,
+            None => self
+                .turn_model_override(message)
+                .map_or_else(|| self.config.model.clone(), |(model, _)| model),
+        };
+        self.check_model_budget(&model)
+    }
+
+    /// The model named by a message's `@name` prefix and the rest of the
+    /// message, if the prefix names one (see `resolve_model`).
+    /// "@alice can you review this" is an ordinary message.
+    fn turn_model_override<'a>(&self, message: &'a str) -> Option<(String, &'a str)> {
+        let (name, prompt) = parse_model_override(message)?;
+        Some((self.resolve_model(name)?, prompt))
+    }
+
+    /// The model `name` refers to: itself if it is recognizably a model
+    /// (`providers::is_model_name`), else a model a provider listed under
+    /// that name or id, a bare Ollama name meaning its `:latest` tag
+    fn resolve_model(&self, name: &str) -> Option<String> {
+        if providers::is_model_name(name, &self.app_config) {
+            return Some(name.to_string());
+        }
+        self.listed_models
+            .iter()
+            .find(|listed| {
+                let id = listed.split_once('/').map_or(listed.as_str(), |(_, id)| id);
+                listed.as_str() == name || id == name || id.strip_suffix(":latest") == Some(name)
+            })
+            .cloned()
+    }
+
+    /// List the providers' models when a message starts with an `@name`
+    /// that isn't recognizably a model, e.g. a bare Ollama name
+    async fn list_models_for_override(&mut self, message: &str) {
+        let Some((model, _)) = parse_model_override(message) else {
+            return;
+        };
+        if !providers::is_model_name(model, &self.app_config) {
+            self.list_models(false).await;
+        }
+    }
+
+    /// Ask the providers for their models, unless (without `force`) every
+    /// provider answered within `MODEL_LIST_TTL`. After a provider fails to
+    /// answer, the next `@name` lists again.
+    async fn list_models(&mut self, force: bool) {
+        if !force
+            && self
+                .models_listed_at
+                .is_some_and(|listed_at| listed_at.elapsed() < MODEL_LIST_TTL)
+        {
+            return;
+        }
+        let listings = list_available_models(&self.app_config).await;
+        self.models_listed_at = listings
+            .iter()
+            .all(|l| l.error.is_none())
+            .then(Instant::now);
+        self.listed_models = listings.into_iter().flat_map(|l| l.models).collect();
+    }
+
+    fn check_model_budget(&mut self, model: &str) -> Result<()> {
+        let (provider, _) = providers::resolve_provider(model, &self.app_config);
+        if self.budget_overrides.contains(&provider) {
+            return Ok(());
+        }
+        let warning = self.spend.lock().unwrap_or_else(|e| e.into_inner()).check(
+            &provider,
+            &self.app_config.budget,
+            chrono::Local::now().date_naive(),
+        )?;
+        if let Some(warning) = warning {
+            warn!("{}", warning);
+            self.budget_warning = Some(warning);
+        }
+        Ok(())
+    }
+
+    /// Keep using `provider` past its budget until LocalGPT exits
+    pub fn override_budget(&mut self, provider: &str) {
+        info!("Budget for {} overridden", provider);
+        self.budget_overrides.insert(provider.to_string());
+    }
+
+    /// Warning from the last budget check, if a budget is running low
+    pub fn take_budget_warning(&mut self) -> Option<String> {
+        self.budget_warning.take()
+    }
+
+    /// Clear per-turn routing and tracking
+    fn reset_turn_state(&mut self) {
+        self.turn_override = None;
+        self.untrusted_in_context = false;
+        self.turn_used_memory = false;
+        if let Some(ref snapshots) = self.snapshots {
+            snapshots
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .begin_turn();
+        }
+    }
+
+    /// Reject a user message flagged by moderation (when the action is "block")
+    async fn moderate_input(&self, message: &str) -> Result<()> {
+        if let Some(ref hook) = self.moderation {
+            if let Some(reason) = hook.review(message, ModerationDirection::Input).await {
+                anyhow::bail!("Message blocked by content moderation ({})", reason);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace a model response flagged by moderation (when the action is "block")
+    async fn moderate_output(&self, response: String) -> String {
+        match self.moderation {
+            Some(ref hook) => moderated_output(hook, response).await,
+            None => response,
+        }
+    }
+
     /// Check if a tool requires user approval before execution
-    pub fn requires_approval(&self, tool_name: &str) -> bool {
-        self.app_config
-            .tools
-            .require_approval
+    pub fn requires_approval(&self, call: &ToolCall) -> bool {
+        let policy = self.approval_policy(call);
+        if policy == Some(ApprovalPolicy::Never) {
+            // Refused without asking
+            return false;
+        }
+        self.needs_explicit_approval(call)
+            || self.untrusted_gate_active()
+            || (policy.is_none() && self.app_config.tools.require_approval.contains(&call.name))
+    }
+
+    /// This agent's approval policy for a call, if one covers it
+    fn approval_policy(&self, call: &ToolCall) -> Option<ApprovalPolicy> {
+        self.approvals.decide(call, self.memory.workspace())
+    }
+
+    /// This agent's approval policies, shared with approval dialogs so they
+    /// can remember the user's choices
+    pub fn approval_store(&self) -> ApprovalStore {
+        self.approvals.clone()
+    }
+
+    /// Run with no one to ask, as a sub-agent does: every call that would
+    /// need approval under `approvals` (the delegating agent's) is refused
+    pub fn set_unattended(&mut self, approvals: ApprovalStore) {
+        self.approvals = approvals;
+        self.unattended = true;
+    }
+
+    /// Whether tool calls need approval because untrusted content just entered
+    /// the context (`tools.approve_after_untrusted`)
+    fn untrusted_gate_active(&self) -> bool {
+        self.app_config.tools.approve_after_untrusted && self.untrusted_in_context
+    }
+
+    /// Record whether a batch of executed tool calls returned untrusted content
+    fn note_tool_batch(&mut self, calls: &[ToolCall]) {
+        self.untrusted_in_context = calls.iter().any(|c| self.untrusted_output(&c.name));
+        self.turn_used_memory |= calls.iter().any(|c| c.name.starts_with("memory_"));
+    }
+
+    /// Whether this tool's output is wrapped as untrusted external content
+    fn untrusted_output(&self, tool_name: &str) -> bool {
+        self.tools
+            .iter()
+            .find(|t| t.name() == tool_name)
+            .map_or(UNTRUSTED_OUTPUT_TOOLS.contains(&tool_name), |t| {
+                t.untrusted_output()
+            })
+    }
+
+    /// Tools refused unless the user approved this specific call. An
+    /// "allow" policy lifts this for all but `ALWAYS_REQUIRE_APPROVAL` tools;
+    /// "ask" imposes it.
+    fn needs_explicit_approval(&self, call: &ToolCall) -> bool {
+        let tool_name = call.name.as_str();
+        if ALWAYS_REQUIRE_APPROVAL.contains(&tool_name) {
+            return true;
+        }
+        match self.approval_policy(call) {
+            Some(ApprovalPolicy::Ask) => return true,
+            Some(ApprovalPolicy::Allow) => return false,
+            _ => {}
+        }
+        (tool_name == "message_agent" && self.app_config.tools.agent_messaging_approval)
+            || (tool_name == "sql" && self.app_config.tools.databases.iter().any(|d| !d.read_only))
+            || (tool_name == "calendar" && changes_calendar(&call.arguments))
+    }
+
+    /// Ask the user (through `set_tool_approver`) before streamed turns run
+    /// tool calls that require approval
+    pub fn set_tool_approver(&mut self, approver: Option<ToolApprover>) {
+        self.approver = approver;
+    }
+
+    /// What a tool call would change, e.g. a diff for file edits
+    pub fn tool_preview(&self, call: &ToolCall) -> Option<String> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == call.name)?
+            .preview(&call.arguments)
+    }
+
+    /// The approver's decision on each call: None for calls that need no
+    /// approval (or when no approver is set)
+    async fn ask_approval(&self, calls: &[ToolCall]) -> Vec<Option<bool>> {
+        let mut decisions = vec![None; calls.len()];
+        let Some(ref approver) = self.approver else {
+            return decisions;
+        };
+        let asked: Vec<usize> = (0..calls.len())
+            .filter(|&i| self.requires_approval(&calls[i]))
+            .collect();
+        if asked.is_empty() {
+            return decisions;
+        }
+        let pending = asked
             .iter()
-            .any(|t| t == tool_name)
+            .map(|&i| PendingApproval {
+                call: calls[i].clone(),
+                detail: extract_tool_detail(&calls[i].name, &calls[i].arguments),
+                preview: self.tool_preview(&calls[i]),
+            })
+            .collect();
+        let answers = approver(pending).await;
+        for (n, &i) in asked.iter().enumerate() {
+            // A missing answer counts as a denial
+            decisions[i] = Some(answers.get(n).copied().unwrap_or(false));
+        }
+        decisions
     }
 
     /// Get the list of tools that require approval
-    pub fn approval_required_tools(&self) -> &[String] {
-        &self.app_config.tools.require_approval
+    pub fn approval_required_tools(&self) -> Vec<String> {
+        let mut tools = self.app_config.tools.require_approval.clone();
+        for name in ALWAYS_REQUIRE_APPROVAL {
+            if !tools.iter().any(|t| t == name) {
+                tools.push(name.to_string());
+            }
+        }
+        if self.app_config.tools.agent_messaging_approval
+            && !tools.iter().any(|t| t == "message_agent")
+        {
+            tools.push("message_agent".to_string());
+        }
+        tools
     }
 
     /// Switch to a different model
     pub fn set_model(&mut self, model: &str) -> Result<()> {
-        let provider = providers::create_provider(model, &self.app_config)?;
+        let mut provider = providers::create_provider(model, &self.app_config)?;
+        provider.set_generation_params(self.session.generation_params());
         self.config.model = model.to_string();
         self.provider = provider;
+        self.session
+            .set_token_counter(TokenCounter::for_model(model, &self.app_config));
+        self.context_window =
+            tokens::context_window(model, &self.app_config, self.config.context_window);
         info!("Switched to model: {}", model);
         Ok(())
     }
 
+    /// Check a replacement API key for `provider` against `model`, then
+    /// rebuild every provider with it
+    pub async fn replace_api_key(&mut self, provider: &str, model: &str, key: &str) -> Result<()> {
+        let mut app_config = self.app_config.clone();
+        app_config.set_api_key(provider, key)?;
+        providers::check_api_key(model, &app_config).await?;
+
+        self.app_config = app_config;
+        let main_model = self.config.model.clone();
+        self.set_model(&main_model)?;
+        self.summary_provider = optional_provider(
+            self.app_config.tools.tool_output_summary_model.as_deref(),
+            &self.app_config,
+        );
+        self.auxiliary_provider = optional_provider(
+            self.app_config.agent.auxiliary_model.as_deref(),
+            &self.app_config,
+        );
+        self.summarizer_provider =
+            optional_provider(summarize_model(&self.app_config), &self.app_config);
+        info!("Replaced API key for {}", provider);
+        Ok(())
+    }
+
+    /// Drop the user message of a turn that failed before any reply, so it
+    /// can be sent again. Returns its text.
+    pub fn take_failed_turn(&mut self) -> Option<String> {
+        self.session
+            .pop_unanswered_user_message()
+            .map(|message| message.content)
+    }
+
+    pub fn generation_params(&self) -> &GenerationParams {
+        self.session.generation_params()
+    }
+
+    /// Override a generation parameter for this session (`/set key=value`)
+    pub fn set_generation_param(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut params = self.session.generation_params().clone();
+        params.set(key, value)?;
+        self.session.set_generation_params(params);
+        self.apply_generation_params();
+        Ok(())
+    }
+
+    /// Push the session's generation overrides to the provider, and have
+    /// the session count tokens the way the model does
+    fn apply_generation_params(&mut self) {
+        self.provider
+            .set_generation_params(self.session.generation_params());
+        self.session.set_token_counter(TokenCounter::for_model(
+            &self.config.model,
+            &self.app_config,
+        ));
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.session.system_prompt()
+    }
+
+    /// Set (or clear) extra system instructions for this session (`/system`)
+    pub fn set_system_prompt(&mut self, prompt: Option<String>) {
+        self.session.set_system_prompt(prompt);
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.session.language()
+    }
+
+    /// Set (or clear) the reply language for this session (`/lang`)
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.session.set_language(language);
+    }
+
+    /// How this session is compacted: its own strategy, else the config's
+    pub fn compaction_strategy(&self) -> CompactionStrategy {
+        self.session
+            .compaction_strategy()
+            .unwrap_or(self.app_config.agent.compaction_strategy)
+    }
+
+    /// Set (or clear) this session's compaction strategy
+    /// (`/compact strategy`)
+    pub fn set_compaction_strategy(&mut self, strategy: Option<CompactionStrategy>) {
+        self.session.set_compaction_strategy(strategy);
+    }
+
+    /// Prompt for background work (titles, summaries, compaction), asking
+    /// for output in the session's language
+    fn localized_prompt(&self, prompt: &str) -> String {
+        match self.session.language() {
+            Some(language) => format!("{}\n\n{}", prompt, language_instruction(language)),
+            None => prompt.to_string(),
+        }
+    }
+
+    /// Restore files changed by the agent's last turn that used write_file,
+    /// edit_file, or bash. Calling again goes back another turn.
+    pub fn undo_agent_changes(&mut self) -> Result<UndoSummary> {
+        match self.snapshots {
+            Some(ref snapshots) => snapshots
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .undo_last(),
+            None => anyhow::bail!("Workspace snapshots are disabled (tools.workspace_snapshots)"),
+        }
+    }
+
+    /// Copy aside whatever a file-changing tool call is about to modify
+    async fn snapshot_before(&self, call: &ToolCall) {
+        if !SNAPSHOT_TOOLS.contains(&call.name.as_str()) {
+            return;
+        }
+        let (name, arguments) = (call.name.clone(), call.arguments.clone());
+        let result = self
+            .with_snapshots(move |store| store.capture_for_tool(&name, &arguments))
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to snapshot files before {}: {}", call.name, e);
+        }
+    }
+
+    /// Record what a file-changing tool call created
+    async fn snapshot_after(&self, call: &ToolCall) {
+        let name = call.name.clone();
+        if let Err(e) = self
+            .with_snapshots(move |store| store.after_tool(&name))
+            .await
+        {
+            warn!("Failed to record files created by {}: {}", call.name, e);
+        }
+    }
+
+    /// Run `f` on the snapshot store on a blocking thread; copying a
+    /// workspace can take a while
+    async fn with_snapshots(
+        &self,
+        f: impl FnOnce(&mut SnapshotStore) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let Some(ref snapshots) = self.snapshots else {
+            return Ok(());
+        };
+        let snapshots = Arc::clone(snapshots);
+        tokio::task::spawn_blocking(move || {
+            f(&mut snapshots.lock().unwrap_or_else(|e| e.into_inner()))
+        })
+        .await?
+    }
+
+    /// Workspace directory the agent operates in
+    pub fn workspace(&self) -> &Path {
+        self.memory.workspace()
+    }
+
+    /// Pin a file so its current contents are sent with every request
+    pub fn pin_file(&mut self, path: &Path) -> Result<()> {
+        if !path.is_file() {
+            anyhow::bail!("Not a file: {}", path.display());
+        }
+        if !self.pinned_files.iter().any(|p| p == path) {
+            self.pinned_files.push(path.to_path_buf());
+            info!("Pinned file into context: {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Unpin a file. Returns false if it wasn't pinned.
+    pub fn unpin_file(&mut self, path: &Path) -> bool {
+        let before = self.pinned_files.len();
+        self.pinned_files.retain(|p| p != path);
+        self.pinned_files.len() != before
+    }
+
+    pub fn pinned_files(&self) -> &[PathBuf] {
+        &self.pinned_files
+    }
+
+    /// Pinned file contents, read fresh so edits are picked up on the next request
+    fn pinned_context(&self) -> Option<String> {
+        if self.pinned_files.is_empty() {
+            return None;
+        }
+
+        let max_chars = match self.app_config.tools.tool_output_max_chars {
+            0 => None,
+            n => Some(n),
+        };
+        let mut context = String::from("# Pinned Files\n\nThe user pinned these files into context. They reflect the current contents on disk.\n");
+
+        for path in &self.pinned_files {
+            let display = path
+                .strip_prefix(self.workspace())
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            let content = std::fs::read_to_string(path)
+                .unwrap_or_else(|e| format!("[Could not read file: {}]", e));
+
+            context.push('\n');
+            if self.app_config.tools.use_content_delimiters {
+                context.push_str(&wrap_external_content(&display, &content, max_chars).content);
+            } else {
+                context.push_str(&format!("## {}\n\n```\n{}\n```", display, content));
+            }
+            context.push('\n');
+        }
+
+        Some(context)
+    }
+
+    /// Repository map of the workspace, rescanned so changed files show up
+    fn repo_map_context(&self) -> Option<String> {
+        self.repo_map
+            .as_ref()?
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .context(self.session.token_counter())
+    }
+
+    /// Session messages plus the repository map and pinned file context
+    /// (appended to the system prompt)
+    fn messages_for_llm(&self) -> Vec<Message> {
+        let mut messages = self.session.messages_for_llm();
+
+        for section in [self.repo_map_context(), self.pinned_context()]
+            .into_iter()
+            .flatten()
+        {
+            match messages.first_mut() {
+                Some(first) if first.role == Role::System => {
+                    first.content.push_str("\n\n---\n\n");
+                    first.content.push_str(&section);
+                }
+                _ => messages.insert(
+                    0,
+                    Message {
+                        role: Role::System,
+                        content: section,
+                        tool_calls: None,
+                        tool_call_id: None,
+                        images: Vec::new(),
+                    },
+                ),
+            }
+        }
+
+        messages
+    }
+
     pub fn memory_chunk_count(&self) -> usize {
         self.memory.chunk_count().unwrap_or(0)
     }
@@ -134,9 +1134,14 @@ impl Agent {
         self.memory.has_embeddings()
     }
 
-    /// Get context window configuration
+    /// The agent's memory, for background indexing
+    pub fn memory(&self) -> Arc<MemoryManager> {
+        Arc::clone(&self.memory)
+    }
+
+    /// The current model's context window
     pub fn context_window(&self) -> usize {
-        self.config.context_window
+        self.context_window
     }
 
     /// Get reserve tokens configuration
@@ -144,15 +1149,30 @@ impl Agent {
         self.config.reserve_tokens
     }
 
-    /// Get current context usage info
+    /// Get current context usage info: the session plus tool definitions,
+    /// counted with the model's tokenizer
     pub fn context_usage(&self) -> (usize, usize, usize) {
-        let used = self.session.token_count();
-        let available = self.config.context_window;
-        let reserve = self.config.reserve_tokens;
+        let counter = self.session.token_counter();
+        let used = self.session.token_count() + counter.tools(&self.tool_schemas());
+        let available = self.context_window;
+        // Small windows still leave most of their room for the conversation
+        let reserve = self.config.reserve_tokens.min(available / 4);
         let usable = available.saturating_sub(reserve);
         (used, usable, available)
     }
 
+    /// The exact messages and tools the next turn would send, with token estimates
+    pub fn inspect_context(&self) -> ContextReport {
+        let (_, usable, _) = self.context_usage();
+        ContextReport::build(
+            &self.config.model,
+            self.session.token_counter(),
+            &self.messages_for_llm(),
+            &self.tool_schemas(),
+            usable,
+        )
+    }
+
     /// Export session messages as markdown
     pub fn export_markdown(&self) -> String {
         let mut output = String::new();
@@ -174,21 +1194,136 @@ impl Agent {
         output
     }
 
+    /// Export the session as a self-contained, redacted HTML file for sharing.
+    /// Configured API keys are always redacted.
+    pub fn export_html(&self, options: &ShareOptions) -> String {
+        let mut options = options.clone();
+        options
+            .extra_secrets
+            .extend(self.app_config.providers.api_keys());
+        share::render_html(&self.session.messages(), &self.config.model, &options)
+    }
+
     /// Get cumulative token usage for this session
     pub fn usage(&self) -> &Usage {
         &self.cumulative_usage
     }
 
-    /// Add usage from an API response to cumulative totals
+    /// Add a model request to the usage records behind model suggestions
+    fn record_model_use(
+        &self,
+        model: &str,
+        task: UsageTask,
+        started: Instant,
+        usage: Option<&Usage>,
+        ok: bool,
+    ) {
+        self.model_usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(model, task, started.elapsed(), usage, ok);
+        if let Some(usage) = usage {
+            let (provider, _) = providers::resolve_provider(model, &self.app_config);
+            let cost = estimated_cost(model, usage.input_tokens, usage.output_tokens);
+            self.spend.lock().unwrap_or_else(|e| e.into_inner()).record(
+                &provider,
+                chrono::Local::now().date_naive(),
+                usage.input_tokens + usage.output_tokens,
+                cost.unwrap_or(0.0),
+            );
+        }
+    }
+
+    /// What the replies of this turn count as in the usage records
+    fn reply_task(&self) -> UsageTask {
+        if self.turn_used_memory {
+            UsageTask::Memory
+        } else {
+            UsageTask::Chat
+        }
+    }
+
+    /// Estimate token counts for streaming responses whose provider didn't report them
+    fn estimated_usage(&self, output: &str) -> Usage {
+        Usage {
+            input_tokens: self.session.token_count() as u64,
+            output_tokens: self.session.token_counter().count(output) as u64,
+            reasoning_tokens: 0,
+        }
+    }
+
+    /// Send a request to the turn's model, recording how it went
+    async fn timed_chat(&self, messages: &[Message], tools: &[ToolSchema]) -> Result<LLMResponse> {
+        let started = Instant::now();
+        let response = self.active_provider().chat(messages, Some(tools)).await;
+        self.record_model_use(
+            self.active_model(),
+            self.reply_task(),
+            started,
+            response.as_ref().ok().and_then(|r| r.usage.as_ref()),
+            response.is_ok(),
+        );
+        response
+    }
+
+    /// Record the end of a streamed request the caller consumed
+    fn finish_streamed_request(&mut self, output: &str) {
+        if let Some(started) = self.reply_started.take() {
+            let reported = self
+                .streamed_usage
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            let usage = reported
+                .clone()
+                .unwrap_or_else(|| self.estimated_usage(output));
+            self.record_model_use(
+                self.active_model(),
+                self.reply_task(),
+                started,
+                Some(&usage),
+                true,
+            );
+            self.add_usage(reported);
+        }
+    }
+
+    /// Remember which model wrote the reply that ends this turn
+    fn note_reply(&mut self) {
+        self.last_reply = Some((self.active_model().to_string(), self.reply_task()));
+    }
+
+    /// Per-model usage and suggested model changes
+    pub fn model_usage_report(&self) -> ModelUsageReport {
+        self.model_usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .report(
+                &self.config.model,
+                self.app_config.agent.auxiliary_model.as_deref(),
+            )
+    }
+
+    /// Add usage from an API response to cumulative totals and to the
+    /// session's entry in sessions.json
     fn add_usage(&mut self, usage: Option<Usage>) {
         if let Some(u) = usage {
             self.cumulative_usage.input_tokens += u.input_tokens;
             self.cumulative_usage.output_tokens += u.output_tokens;
+            self.cumulative_usage.reasoning_tokens += u.reasoning_tokens;
+
+            let recorded = SessionStore::load_for_agent(self.memory.agent_id())
+                .and_then(|mut store| store.add_usage(self.session.id(), &u));
+            if let Err(e) = recorded {
+                debug!("Failed to record session token usage: {}", e);
+            }
         }
     }
 
     pub async fn new_session(&mut self) -> Result<()> {
         self.session = Session::new();
+        self.approvals.clear_session();
+        self.apply_generation_params();
 
         // Load skills from workspace
         let workspace_skills = skills::load_skills(self.memory.workspace()).unwrap_or_default();
@@ -201,7 +1336,14 @@ impl Agent {
             system_prompt::SystemPromptParams::new(self.memory.workspace(), &self.config.model)
                 .with_tools(tool_names)
                 .with_skills_prompt(skills_prompt);
-        let system_prompt = system_prompt::build_system_prompt(system_prompt_params);
+        let mut system_prompt = system_prompt::build_system_prompt(system_prompt_params);
+        if let Some(ref instructions) = self.app_config.agent.system_prompt {
+            system_prompt = format!(
+                "{}\n\n## Agent Instructions\n\n{}",
+                system_prompt,
+                instructions.trim()
+            );
+        }
 
         // Load memory context (SOUL.md, MEMORY.md, daily logs, HEARTBEAT.md)
         let memory_context = self.build_memory_context().await?;
@@ -224,10 +1366,76 @@ impl Agent {
 
     pub async fn resume_session(&mut self, session_id: &str) -> Result<()> {
         self.session = Session::load(session_id)?;
+        self.apply_generation_params();
+        self.approvals.clear_session();
         info!("Resumed session: {}", session_id);
+
+        // A leftover checkpoint means the last turn was cut short
+        let sessions_dir = get_sessions_dir_for_agent(DEFAULT_AGENT_ID)?;
+        if let Some(turn) = take_partial_turn(&partial_turn_path(&sessions_dir, session_id)) {
+            info!("Recovered interrupted turn in session {}", session_id);
+            self.add_assistant_message(&turn.recovered_text());
+            self.session.save()?;
+        }
+        Ok(())
+    }
+
+    /// Resume a session saved under an agent ID (see `save_session_for_agent`)
+    pub async fn resume_session_for_agent(
+        &mut self,
+        agent_id: &str,
+        session_id: &str,
+    ) -> Result<()> {
+        self.session = Session::load_for_agent(agent_id, session_id)?;
+        self.apply_generation_params();
+        self.approvals.clear_session();
+        info!("Resumed session {} for agent {}", session_id, agent_id);
         Ok(())
     }
 
+    /// Checkpoint in-progress turns to disk (every `agent.checkpoint_interval_secs`)
+    /// so a crash mid-turn loses seconds of work; `resume_session` recovers them
+    pub fn enable_turn_checkpoints(&mut self) {
+        self.checkpoints_enabled = self.app_config.agent.checkpoint_interval_secs > 0;
+    }
+
+    /// Save the session (with the new user message) and start checkpointing the turn
+    fn start_checkpoint(&mut self) {
+        self.finish_checkpoint();
+        if !self.checkpoints_enabled {
+            return;
+        }
+
+        let dir = match self
+            .session
+            .save()
+            .and_then(|_| get_sessions_dir_for_agent(DEFAULT_AGENT_ID))
+        {
+            Ok(dir) => dir,
+            Err(e) => {
+                warn!("Turn checkpoints unavailable: {}", e);
+                return;
+            }
+        };
+        let interval = Duration::from_secs(self.app_config.agent.checkpoint_interval_secs);
+        self.checkpoint = Some(Arc::new(Mutex::new(TurnCheckpoint::new(
+            partial_turn_path(&dir, self.session.id()),
+            interval,
+        ))));
+    }
+
+    fn with_checkpoint(&self, f: impl FnOnce(&mut TurnCheckpoint)) {
+        if let Some(ref checkpoint) = self.checkpoint {
+            f(&mut checkpoint.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+    }
+
+    /// The turn completed, so its checkpoint is no longer needed
+    fn finish_checkpoint(&mut self) {
+        self.with_checkpoint(|c| c.finish());
+        self.checkpoint = None;
+    }
+
     pub async fn chat(&mut self, message: &str) -> Result<String> {
         self.chat_with_images(message, Vec::new()).await
     }
@@ -237,6 +1445,9 @@ impl Agent {
         message: &str,
         images: Vec<ImageAttachment>,
     ) -> Result<String> {
+        let message = self.begin_turn(message).await?;
+        self.moderate_input(message).await?;
+
         // Add user message with images
         self.session.add_message(Message {
             role: Role::User,
@@ -252,25 +1463,24 @@ impl Agent {
             self.memory_flush().await?;
         }
 
-        // Check if we need to compact (hard limit)
-        if self.should_compact() {
-            self.compact_session().await?;
-        }
+        // Tool schemas count toward the window, so update them first
+        self.refresh_mcp_tools().await;
+
+        // Compact (or truncate) if the request would overflow the window
+        self.fit_context(false).await;
 
         // Build messages for LLM
-        let messages = self.session.messages_for_llm();
+        let messages = self.messages_for_llm();
 
         // Get available tools
         let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
 
         // Invoke LLM
-        let response = self
-            .provider
-            .chat(&messages, Some(tool_schemas.as_slice()))
-            .await?;
+        let response = self.timed_chat(&messages, &tool_schemas).await?;
 
         // Handle tool calls if any
         let final_response = self.handle_response(response).await?;
+        let final_response = self.moderate_output(final_response).await;
 
         // Add assistant response
         self.session.add_message(Message {
@@ -280,71 +1490,170 @@ impl Agent {
             tool_call_id: None,
             images: Vec::new(),
         });
+        self.note_reply();
 
         Ok(final_response)
     }
 
-    async fn handle_response(&mut self, response: LLMResponse) -> Result<String> {
-        // Track usage
-        self.add_usage(response.usage);
+    /// Run the tool calls a response asks for, and those of the responses
+    /// after it, until the model replies with text or a turn limit is hit
+    async fn handle_response(&mut self, mut response: LLMResponse) -> Result<String> {
+        let mut limits = TurnLimits::new(&self.app_config.agent);
+        loop {
+            // Track usage
+            self.add_usage(response.usage);
+
+            let calls = match response.content {
+                LLMResponseContent::Text(text) => return Ok(text),
+                LLMResponseContent::ToolCalls(calls) => calls,
+            };
+            self.with_checkpoint(|c| c.push_tool_calls(&calls));
 
-        match response.content {
-            LLMResponseContent::Text(text) => Ok(text),
-            LLMResponseContent::ToolCalls(calls) => {
-                // Execute tool calls
-                let mut results = Vec::new();
+            // Execute tool calls
+            let (results, tool_images) = self.execute_tool_calls(&calls, false).await;
+            self.note_tool_batch(&calls);
 
-                for call in &calls {
-                    debug!(
-                        "Executing tool: {} with args: {}",
-                        call.name, call.arguments
-                    );
-
-                    let result = self.execute_tool(call).await;
-                    results.push(ToolResult {
-                        call_id: call.id.clone(),
-                        output: result.unwrap_or_else(|e| format!("Error: {}", e)),
-                    });
-                }
+            // Add tool call message
+            self.session.add_message(Message {
+                role: Role::Assistant,
+                content: String::new(),
+                tool_calls: Some(calls),
+                tool_call_id: None,
+                images: Vec::new(),
+            });
 
-                // Add tool call message
+            // Add tool results
+            for result in &results {
                 self.session.add_message(Message {
-                    role: Role::Assistant,
-                    content: String::new(),
-                    tool_calls: Some(calls),
-                    tool_call_id: None,
+                    role: Role::Tool,
+                    content: result.output.clone(),
+                    tool_calls: None,
+                    tool_call_id: Some(result.call_id.clone()),
                     images: Vec::new(),
                 });
+            }
+            for (name, images) in tool_images {
+                self.add_tool_images(&name, images);
+            }
 
-                // Add tool results
-                for result in &results {
-                    self.session.add_message(Message {
-                        role: Role::Tool,
-                        content: result.output.clone(),
-                        tool_calls: None,
-                        tool_call_id: Some(result.call_id.clone()),
-                        images: Vec::new(),
-                    });
-                }
+            if let Some(hit) = limits.after_tool_round(results.iter().map(|r| r.output.as_str())) {
+                info!("Ending turn: {:?}", hit);
+                return Ok(hit.notice());
+            }
 
-                // Continue conversation with tool results
-                let messages = self.session.messages_for_llm();
-                let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
-                let next_response = self
-                    .provider
-                    .chat(&messages, Some(tool_schemas.as_slice()))
-                    .await?;
+            // Continue conversation with tool results
+            self.fit_context(true).await;
+            let messages = self.messages_for_llm();
+            let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
+            response = self.timed_chat(&messages, &tool_schemas).await?;
+        }
+    }
+
+    /// One-paragraph summary of a tool output longer than
+    /// `tools.tool_output_summary_chars`, for display above the raw output
+    async fn summarize_tool_output(&self, output: &str) -> Option<String> {
+        let threshold = self.app_config.tools.tool_output_summary_chars;
+        if threshold == 0 || output.chars().count() <= threshold {
+            return None;
+        }
 
-                // Recursively handle (in case of more tool calls)
-                Box::pin(self.handle_response(next_response)).await
+        let excerpt: String = output.chars().take(MAX_TOOL_SUMMARY_INPUT_CHARS).collect();
+        let (provider, model) = match (&self.summary_provider, &self.auxiliary_provider) {
+            (Some(provider), _) => (
+                provider.as_ref(),
+                self.app_config
+                    .tools
+                    .tool_output_summary_model
+                    .as_deref()
+                    .unwrap_or_default(),
+            ),
+            (None, Some(provider)) => (provider.as_ref(), self.auxiliary_model()),
+            (None, None) => (self.active_provider(), self.active_model()),
+        };
+        let prompt = self.localized_prompt(TOOL_OUTPUT_SUMMARY_PROMPT);
+        let started = Instant::now();
+        let summary = provider.summarize_with(&prompt, &excerpt).await;
+        self.record_model_use(model, UsageTask::Background, started, None, summary.is_ok());
+        match summary {
+            Ok(summary) => Some(summary.trim().to_string()).filter(|s| !s.is_empty()),
+            Err(e) => {
+                warn!("Failed to summarize tool output: {}", e);
+                None
             }
         }
     }
 
-    async fn execute_tool(&self, call: &ToolCall) -> Result<String> {
+    /// The copy of a tool output that goes into the context, if the output is
+    /// longer than `tools.tool_output_context_tokens` (see `tool_output`).
+    /// The full output is still what the user sees.
+    async fn condense_tool_output(&self, name: &str, output: &str) -> Option<String> {
+        let request = tool_output::CondenseRequest {
+            name,
+            output,
+            untrusted: self.untrusted_output(name),
+            counter: self.session.token_counter(),
+        };
+        let started = Instant::now();
+        let condensed =
+            tool_output::condense(&request, &self.app_config.tools, self.summarizer()).await?;
+        if let Some(ok) = condensed.summarizer_ok {
+            self.record_model_use(
+                self.summarizer_model(),
+                UsageTask::Background,
+                started,
+                None,
+                ok,
+            );
+        }
+        Some(condensed.text)
+    }
+
+    /// Execute a tool call, returning its (possibly sanitized) output and any images.
+    /// Tools in `ALWAYS_REQUIRE_APPROVAL`, message_agent (unless
+    /// `tools.agent_messaging_approval` is off), writable sql, calendar
+    /// changes, any call gated by untrusted content, and in unattended
+    /// agents any call needing approval are refused unless `approved` is set.
+    /// Calls with a "never" approval policy are refused.
+    async fn execute_tool(
+        &self,
+        call: &ToolCall,
+        approved: bool,
+    ) -> Result<(String, Vec<ImageAttachment>)> {
+        if self.approval_policy(call) == Some(ApprovalPolicy::Never) {
+            anyhow::bail!(
+                "{} is blocked by this agent's approval policy ({})",
+                call.name,
+                APPROVALS_FILE
+            );
+        }
+        if !approved && self.needs_explicit_approval(call) {
+            anyhow::bail!(
+                "{} requires explicit user approval and cannot run here",
+                call.name
+            );
+        }
+        if !approved && self.unattended && self.requires_approval(call) {
+            anyhow::bail!(
+                "{} requires user approval, which a sub-agent can't ask for",
+                call.name
+            );
+        }
+        if !approved && self.untrusted_gate_active() {
+            anyhow::bail!(
+                "{} was requested right after untrusted content entered the context \
+                 and requires user approval",
+                call.name
+            );
+        }
+
+        self.snapshot_before(call).await;
+
         for tool in &self.tools {
             if tool.name() == call.name {
-                let raw_output = tool.execute(&call.arguments).await?;
+                // A bash call that timed out may still have created files
+                let result = self.run_tool(tool.as_ref(), call).await;
+                self.snapshot_after(call).await;
+                let (raw_output, images) = result?;
 
                 // Apply sanitization if configured
                 if self.app_config.tools.use_content_delimiters {
@@ -353,7 +1662,15 @@ impl Agent {
                     } else {
                         None
                     };
-                    let result = sanitize::wrap_tool_output(&call.name, &raw_output, max_chars);
+                    let result = if tool.untrusted_output() {
+                        let source = match extract_tool_detail(&call.name, &call.arguments) {
+                            Some(detail) => format!("{} {}", call.name, detail),
+                            None => call.name.clone(),
+                        };
+                        sanitize::wrap_external_content(&source, &raw_output, max_chars)
+                    } else {
+                        sanitize::wrap_tool_output(&call.name, &raw_output, max_chars)
+                    };
 
                     // Log warnings for suspicious patterns
                     if self.app_config.tools.log_injection_warnings && !result.warnings.is_empty() {
@@ -364,15 +1681,122 @@ impl Agent {
                         );
                     }
 
-                    return Ok(result.content);
+                    return Ok((result.content, images));
                 }
 
-                return Ok(raw_output);
+                return Ok((raw_output, images));
             }
         }
         anyhow::bail!("Unknown tool: {}", call.name)
     }
 
+    /// Run a tool call, failing it when it outlasts its timeout
+    /// (`tools.tool_timeout_secs`) or the user cancels it (`AbortHandle::cancel_tool`)
+    async fn run_tool(
+        &self,
+        tool: &dyn Tool,
+        call: &ToolCall,
+    ) -> Result<(String, Vec<ImageAttachment>)> {
+        let cancel = self.abort.start_tool(&call.id);
+        let run = tool.execute_with_images(&call.arguments);
+        let result = match self.app_config.tools.tool_timeout(&call.name) {
+            Some(limit) => cancel
+                .run_until_cancelled(tokio::time::timeout(limit, run))
+                .await
+                .map(|result| {
+                    result.unwrap_or_else(|_| {
+                        Err(anyhow::anyhow!(
+                            "{} timed out after {} seconds (tools.tool_timeout_secs)",
+                            call.name,
+                            limit.as_secs()
+                        ))
+                    })
+                }),
+            None => cancel.run_until_cancelled(run).await,
+        };
+        self.abort.finish_tool(&call.id);
+        result.unwrap_or_else(|| Err(anyhow::anyhow!("{} was cancelled by the user", call.name)))
+    }
+
+    /// Run a turn's tool calls concurrently, up to `tools.max_parallel_tools`
+    /// at a time, yielding each call's index and result as it finishes.
+    /// A call that may have side effects (any tool outside
+    /// `PARALLEL_SAFE_TOOLS`) waits for the calls before it and runs alone,
+    /// so later calls see its changes.
+    fn run_tool_calls<'a>(
+        &'a self,
+        calls: &'a [ToolCall],
+        approved: &'a [bool],
+    ) -> impl futures::Stream<Item = (usize, Result<(String, Vec<ImageAttachment>)>)> + 'a {
+        use futures::StreamExt;
+
+        let limit = self.app_config.tools.max_parallel_tools.max(1);
+        futures::stream::iter(tool_call_batches(calls)).flat_map(move |batch| {
+            futures::stream::iter(batch)
+                .map(move |index| {
+                    let call = &calls[index];
+                    async move {
+                        debug!(
+                            "Executing tool: {} with args: {}",
+                            call.name, call.arguments
+                        );
+                        (index, self.execute_tool(call, approved[index]).await)
+                    }
+                })
+                .buffer_unordered(limit)
+        })
+    }
+
+    /// Run a turn's tool calls (see `run_tool_calls`) and return their
+    /// results in call order. Failed calls become "Error: ..." outputs.
+    async fn execute_tool_calls(
+        &self,
+        calls: &[ToolCall],
+        approved: bool,
+    ) -> (Vec<ToolResult>, Vec<(String, Vec<ImageAttachment>)>) {
+        use futures::StreamExt;
+
+        let approved = vec![approved; calls.len()];
+        let mut finished: Vec<_> = self.run_tool_calls(calls, &approved).collect().await;
+        finished.sort_by_key(|(index, _)| *index);
+
+        let mut results = Vec::new();
+        let mut tool_images = Vec::new();
+        for (call, (_, result)) in calls.iter().zip(finished) {
+            let output = match result {
+                Ok((output, images)) => {
+                    tool_images.push((call.name.clone(), images));
+                    output
+                }
+                Err(e) => format!("Error: {}", e),
+            };
+            let output = self
+                .condense_tool_output(&call.name, &output)
+                .await
+                .unwrap_or(output);
+            results.push(ToolResult {
+                call_id: call.id.clone(),
+                output,
+            });
+        }
+        (results, tool_images)
+    }
+
+    /// Attach images produced by a tool as a user message, since tool result
+    /// messages can't carry images for most providers
+    fn add_tool_images(&mut self, tool_name: &str, images: Vec<ImageAttachment>) {
+        if images.is_empty() {
+            return;
+        }
+        self.session.add_message(Message {
+            role: Role::User,
+            content: format!("[Image output from {}]", tool_name),
+            tool_calls: None,
+            tool_call_id: None,
+            images,
+        });
+    }
+
     async fn build_memory_context(&self) -> Result<String> {
         let mut context = String::new();
         let use_delimiters = self.app_config.tools.use_content_delimiters;
@@ -502,6 +1926,23 @@ impl Agent {
             }
         }
 
+        // Preferences learned from rated replies
+        if self.app_config.agent.feedback_summary {
+            if let Some(summary) = load_feedback_summary() {
+                if use_delimiters {
+                    context.push_str(&sanitize::wrap_memory_content(
+                        "feedback_summary.md",
+                        &summary,
+                        sanitize::MemorySource::Feedback,
+                    ));
+                } else {
+                    context.push_str("# User Preferences (from rated replies)\n\n");
+                    context.push_str(&summary);
+                }
+                context.push_str("\n\n");
+            }
+        }
+
         // Load HEARTBEAT.md if it exists
         if let Ok(heartbeat) = self.memory.read_heartbeat_file() {
             if !heartbeat.is_empty() {
@@ -519,36 +1960,220 @@ impl Agent {
             }
         }
 
-        Ok(context)
+        Ok(context)
+    }
+
+    fn should_compact(&self) -> bool {
+        let (used, usable, _) = self.context_usage();
+        used > usable
+    }
+
+    /// Check if we should run pre-compaction memory flush (soft threshold)
+    fn should_memory_flush(&self) -> bool {
+        let (used, hard_limit, _) = self.context_usage();
+        let soft_limit = hard_limit.saturating_sub(MEMORY_FLUSH_SOFT_THRESHOLD);
+
+        used > soft_limit && self.session.should_memory_flush()
+    }
+
+    pub async fn compact_session(&mut self) -> Result<(usize, usize)> {
+        // Trigger memory flush before compacting (if not already done)
+        if self.session.should_memory_flush() {
+            self.memory_flush().await?;
+        }
+        self.compact_history().await
+    }
+
+    fn compaction_prompt(&self) -> String {
+        let prompt = self
+            .app_config
+            .agent
+            .compaction_prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_COMPACTION_PROMPT);
+        self.localized_prompt(prompt)
+    }
+
+    /// Summarize the older history, without a memory flush
+    async fn compact_history(&mut self) -> Result<(usize, usize)> {
+        let before = self.session.token_count();
+        let prompt = self.compaction_prompt();
+        let model = self.summarizer_model().to_string();
+        // Fields rather than summarizer(), which would borrow the session too
+        let provider = match (&self.summarizer_provider, &self.auxiliary_provider) {
+            (Some(provider), _) | (None, Some(provider)) => provider.as_ref(),
+            (None, None) => self.provider.as_ref(),
+        };
+        let strategy = self.compaction_strategy();
+        let keep_recent = self.app_config.agent.compaction_keep_recent.max(1);
+        let started = Instant::now();
+        let result = self
+            .session
+            .compact(provider, &prompt, strategy, keep_recent)
+            .await;
+        if strategy != CompactionStrategy::DropOldest {
+            self.record_model_use(&model, UsageTask::Background, started, None, result.is_ok());
+        }
+        result?;
+
+        let after = self.session.token_count();
+        info!(
+            "Session compacted ({}): {} -> {} tokens",
+            strategy, before, after
+        );
+
+        Ok((before, after))
+    }
+
+    /// Start compacting the history on a background thread. The caller
+    /// keeps using the session; the summary replaces the older messages once
+    /// `poll_compaction` (or the next request) finds it ready. Returns false
+    /// if there is nothing to compact or a compaction is already running.
+    pub fn start_compaction(&mut self) -> bool {
+        if self.compaction.is_some() {
+            return false;
+        }
+        let strategy = self.compaction_strategy();
+        let keep_recent = self.app_config.agent.compaction_keep_recent.max(1);
+        let Some(plan) = self.session.plan_compaction(strategy, keep_recent) else {
+            return false;
+        };
+
+        let model = self.summarizer_model().to_string();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        match plan.text.clone() {
+            None => {
+                let _ = tx.send(Ok(None));
+            }
+            Some(text) => {
+                let prompt = self.compaction_prompt();
+                let config = self.app_config.clone();
+                let model = model.clone();
+                // A thread with its own runtime: the desktop worker's runtime
+                // is blocked on UI messages between turns
+                std::thread::spawn(move || {
+                    let summary = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|rt| {
+                            rt.block_on(async {
+                                let provider = providers::create_provider(&model, &config)?;
+                                provider.summarize_with(&prompt, &text).await
+                            })
+                        });
+                    let _ = tx.send(summary.map(Some));
+                });
+            }
+        }
+        info!("Compacting session ({}) in the background", strategy);
+        self.compaction = Some(BackgroundCompaction {
+            plan,
+            model,
+            started: Instant::now(),
+            summary: rx,
+        });
+        true
     }
 
-    fn should_compact(&self) -> bool {
-        self.session.token_count() > (self.config.context_window - self.config.reserve_tokens)
+    /// Whether a background compaction is still to be swapped in
+    pub fn compaction_running(&self) -> bool {
+        self.compaction.is_some()
     }
 
-    /// Check if we should run pre-compaction memory flush (soft threshold)
-    fn should_memory_flush(&self) -> bool {
-        let hard_limit = self.config.context_window - self.config.reserve_tokens;
-        let soft_limit = hard_limit.saturating_sub(MEMORY_FLUSH_SOFT_THRESHOLD);
+    /// Swap in a finished background compaction, returning the token counts
+    /// before and after. None while it is still running, or if none is.
+    pub fn poll_compaction(&mut self) -> Option<Result<(usize, usize)>> {
+        use tokio::sync::oneshot::error::TryRecvError;
+
+        let mut running = self.compaction.take()?;
+        let summary = match running.summary.try_recv() {
+            Ok(summary) => summary,
+            Err(TryRecvError::Empty) => {
+                self.compaction = Some(running);
+                return None;
+            }
+            Err(TryRecvError::Closed) => Err(anyhow::anyhow!("compaction thread stopped")),
+        };
+        Some(self.apply_background_compaction(running, summary))
+    }
 
-        self.session.token_count() > soft_limit && self.session.should_memory_flush()
+    /// Wait for the background compaction, if any, and swap it in
+    async fn finish_compaction(&mut self) -> Option<Result<(usize, usize)>> {
+        let mut running = self.compaction.take()?;
+        let summary = (&mut running.summary)
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("compaction thread stopped")));
+        Some(self.apply_background_compaction(running, summary))
     }
 
-    pub async fn compact_session(&mut self) -> Result<(usize, usize)> {
+    fn apply_background_compaction(
+        &mut self,
+        running: BackgroundCompaction,
+        summary: Result<Option<String>>,
+    ) -> Result<(usize, usize)> {
+        let strategy = running.plan.strategy();
+        if strategy != CompactionStrategy::DropOldest {
+            self.record_model_use(
+                &running.model,
+                UsageTask::Background,
+                running.started,
+                None,
+                summary.is_ok(),
+            );
+        }
         let before = self.session.token_count();
+        self.session.apply_compaction(running.plan, summary?)?;
+        let after = self.session.token_count();
+        info!(
+            "Session compacted in the background ({}): {} -> {} tokens",
+            strategy, before, after
+        );
+        Ok((before, after))
+    }
 
-        // Trigger memory flush before compacting (if not already done)
-        if self.session.should_memory_flush() {
-            self.memory_flush().await?;
+    /// Make sure the next request fits the model's context window: compact
+    /// the history, and if that fails or isn't enough, drop the oldest
+    /// messages. Between tool calls (`in_turn`) there is no memory flush,
+    /// which would put a user message in the middle of the turn.
+    ///
+    /// With `agent.background_compaction`, compaction starts in the
+    /// background once the context reaches the memory flush threshold, so
+    /// it is usually swapped in before the window is actually full.
+    async fn fit_context(&mut self, in_turn: bool) {
+        if let Some(Err(e)) = self.poll_compaction() {
+            warn!("Background compaction failed: {}", e);
+        }
+        if !self.should_compact() {
+            let (used, usable, _) = self.context_usage();
+            if self.app_config.agent.background_compaction
+                && used > usable.saturating_sub(MEMORY_FLUSH_SOFT_THRESHOLD)
+            {
+                self.start_compaction();
+            }
+            return;
+        }
+        let compacted = match self.finish_compaction().await {
+            // Already summarizing: waiting beats starting over
+            Some(result) => result,
+            None if in_turn => self.compact_history().await,
+            // Boxed: the memory flush runs a tool loop, which calls back here
+            None => Box::pin(self.compact_session()).await,
+        };
+        if let Err(e) = compacted {
+            warn!("Compaction failed, dropping old messages instead: {}", e);
         }
 
-        // Compact the session
-        self.session.compact(&*self.provider).await?;
-
-        let after = self.session.token_count();
-        info!("Session compacted: {} -> {} tokens", before, after);
-
-        Ok((before, after))
+        let (used, usable, _) = self.context_usage();
+        if used > usable {
+            // Tool definitions take their share of the window too
+            let limit = usable.saturating_sub(used - self.session.token_count());
+            let dropped = self.session.truncate_to_fit(limit);
+            warn!(
+                "Context over the {} token window: dropped {} oldest messages",
+                self.context_window, dropped
+            );
+        }
     }
 
     /// Pre-compaction memory flush - prompts agent to save important info
@@ -578,7 +2203,7 @@ impl Agent {
 
         // Get tool schemas so agent can write files
         let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
-        let messages = self.session.messages_for_llm();
+        let messages = self.messages_for_llm();
 
         let response = self.provider.chat(&messages, Some(&tool_schemas)).await?;
 
@@ -603,6 +2228,45 @@ impl Agent {
 
     /// Save current session to memory file (called on /new command)
     /// Creates memory/YYYY-MM-DD-slug.md with session transcript
+    /// Model-written title for a conversation, if `agent.llm_session_titles` is on
+    async fn session_title(&self, messages: &[Message]) -> Option<String> {
+        if !self.app_config.agent.llm_session_titles {
+            return None;
+        }
+        let prompt = self
+            .app_config
+            .agent
+            .title_prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_TITLE_PROMPT);
+        let prompt = self.localized_prompt(prompt);
+        let text: String = messages
+            .iter()
+            .map(|m| format!("{:?}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let started = Instant::now();
+        let title = self.summarizer().summarize_with(&prompt, &text).await;
+        self.record_model_use(
+            self.summarizer_model(),
+            UsageTask::Background,
+            started,
+            None,
+            title.is_ok(),
+        );
+        match title {
+            Ok(title) => {
+                let title = title.trim().trim_matches(['"', '\'']).trim();
+                (!title.is_empty()).then(|| title.to_string())
+            }
+            Err(e) => {
+                warn!("Failed to generate session title: {}", e);
+                None
+            }
+        }
+    }
+
     pub async fn save_session_to_memory(&self) -> Result<Option<PathBuf>> {
         let messages = self.session.user_assistant_messages();
 
@@ -629,25 +2293,33 @@ impl Agent {
             messages
         };
 
-        // Generate slug from first user message
-        let slug = messages
-            .iter()
-            .find(|m| m.role == Role::User)
-            .map(|m| generate_slug(&m.content))
-            .unwrap_or_else(|| "session".to_string());
+        // Generate slug from the title, or else the first user message
+        let title = self.session_title(&messages).await;
+        let slug = match title {
+            Some(ref title) => generate_slug(title),
+            None => messages
+                .iter()
+                .find(|m| m.role == Role::User)
+                .map(|m| generate_slug(&m.content))
+                .unwrap_or_else(|| "session".to_string()),
+        };
 
         let now = chrono::Local::now();
         let date_str = now.format("%Y-%m-%d").to_string();
         let time_str = now.format("%H:%M:%S").to_string();
 
         // Build memory file content
+        let title_line = title
+            .map(|t| format!("- **Title**: {}\n", t))
+            .unwrap_or_default();
         let mut content = format!(
             "# Session: {} {}\n\n\
-             - **Session ID**: {}\n\n\
+             - **Session ID**: {}\n{}\n\
              ## Conversation\n\n",
             date_str,
             time_str,
-            self.session.id()
+            self.session.id(),
+            title_line
         );
 
         for msg in &messages {
@@ -687,12 +2359,124 @@ impl Agent {
         Ok(Some(path))
     }
 
+    /// Have the model pick durable facts about the user out of this
+    /// session (`memory.extract_facts`) and add the new ones to USER.md,
+    /// which is loaded into future system prompts. Returns the facts added.
+    pub async fn extract_session_facts(&self) -> Result<Vec<String>> {
+        if !self.app_config.memory.extract_facts {
+            return Ok(Vec::new());
+        }
+        let messages = self.session.user_assistant_messages();
+        if !messages.iter().any(|m| m.role == Role::User) {
+            return Ok(Vec::new());
+        }
+
+        let transcript: String = messages
+            .iter()
+            .map(|m| format!("{:?}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let known = self.memory.read_user_file().unwrap_or_default();
+        let prompt = format!(
+            "{}\n\nAlready known:\n{}\n\nConversation:\n{}",
+            self.localized_prompt(FACT_EXTRACTION_PROMPT),
+            if known.trim().is_empty() {
+                "(nothing)"
+            } else {
+                known.trim()
+            },
+            transcript
+        );
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "facts": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["facts"],
+            "additionalProperties": false
+        });
+
+        let started = Instant::now();
+        let reply = self.auxiliary().chat_json(&prompt, &schema).await;
+        self.record_model_use(
+            self.auxiliary_model(),
+            UsageTask::Background,
+            started,
+            None,
+            reply.is_ok(),
+        );
+        let facts: Vec<String> = reply?["facts"]
+            .as_array()
+            .map(|facts| {
+                facts
+                    .iter()
+                    .filter_map(|f| f.as_str())
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty() && f.chars().count() <= MAX_FACT_CHARS)
+                    .take(MAX_EXTRACTED_FACTS)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let added = self.memory.add_user_facts(&facts).await?;
+        if !added.is_empty() {
+            info!("Remembered {} new fact(s) about the user", added.len());
+        }
+        Ok(added)
+    }
+
+    /// Ask the model for a step list for `task` (plan-then-execute mode).
+    /// Nothing runs yet: frontends show the plan for review, then send each
+    /// step's `Plan::step_message` as a turn.
+    pub async fn make_plan(&self, task: &str) -> Result<Plan> {
+        let tools: Vec<&str> = self.tools.iter().map(|t| t.name()).collect();
+        let recent: Vec<String> = self
+            .session
+            .user_assistant_messages()
+            .iter()
+            .rev()
+            .take(MAX_PLAN_CONTEXT_MESSAGES)
+            .rev()
+            .map(|m| {
+                let content: String = m.content.chars().take(2000).collect();
+                format!("{:?}: {}", m.role, content)
+            })
+            .collect();
+        let prompt = format!(
+            "{}\n\nWorkspace: {}\nTools: {}\n\nRecent conversation:\n{}\n\nTask: {}",
+            self.localized_prompt(plan::PLAN_PROMPT),
+            self.memory.workspace().display(),
+            tools.join(", "),
+            if recent.is_empty() {
+                "(none)".to_string()
+            } else {
+                recent.join("\n\n")
+            },
+            task
+        );
+
+        let started = Instant::now();
+        let reply = self
+            .active_provider()
+            .chat_json(&prompt, &Plan::schema())
+            .await;
+        self.record_model_use(
+            self.active_model(),
+            UsageTask::Chat,
+            started,
+            None,
+            reply.is_ok(),
+        );
+        Plan::from_reply(task, &reply?)
+    }
+
     pub fn clear_session(&mut self) {
         self.session = Session::new();
+        self.apply_generation_params();
     }
 
     pub async fn search_memory(&self, query: &str) -> Result<Vec<MemoryChunk>> {
-        self.memory.search(query, 10)
+        self.memory.search(query, 10).await
     }
 
     pub async fn reindex_memory(&self) -> Result<(usize, usize, usize)> {
@@ -708,22 +2492,118 @@ impl Agent {
         self.session.save()
     }
 
+    /// Rate an assistant reply (the latest one if `reply` is None) and save
+    /// the session. `None` clears the rating.
+    pub async fn rate_reply(
+        &mut self,
+        reply: Option<&str>,
+        rating: Option<Rating>,
+        note: Option<&str>,
+    ) -> Result<()> {
+        let feedback = rating.map(|rating| Feedback::new(rating, note));
+        if !self.session.rate_reply(reply, feedback) {
+            anyhow::bail!("No assistant reply to rate");
+        }
+        self.session.save()?;
+
+        if self.app_config.agent.feedback_summary {
+            if let Err(e) = self.refresh_feedback_summary().await {
+                warn!("Failed to update feedback summary: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pin (or unpin) a message so compaction keeps it verbatim: the latest
+    /// with this text, else the latest containing it, else (None) the last
+    /// reply. Saves the session and returns the message's text.
+    pub fn set_pinned(&mut self, text: Option<&str>, pinned: bool) -> Result<String> {
+        let content = match self.session.set_pinned(text, pinned) {
+            Some(message) => message.content.clone(),
+            None => anyhow::bail!(
+                "No matching message to {}",
+                if pinned { "pin" } else { "unpin" }
+            ),
+        };
+        self.session.save()?;
+        Ok(content)
+    }
+
+    /// Text of the latest assistant reply (for `/copy`)
+    pub fn last_reply(&self) -> Option<String> {
+        self.session
+            .user_assistant_messages()
+            .into_iter()
+            .rev()
+            .find(|m| m.role == Role::Assistant && !m.content.trim().is_empty())
+            .map(|m| m.content)
+    }
+
+    /// Text of the pinned messages, oldest first
+    pub fn pinned_messages(&self) -> Vec<String> {
+        self.session
+            .pinned_messages()
+            .into_iter()
+            .map(|m| m.content.clone())
+            .collect()
+    }
+
+    /// Re-summarize all rated replies into the preferences added to new
+    /// sessions' system prompt
+    pub async fn refresh_feedback_summary(&self) -> Result<Option<String>> {
+        let pairs = collect_rated_pairs()?;
+        let started = Instant::now();
+        let summary = update_feedback_summary(self.auxiliary(), &pairs).await;
+        if !pairs.is_empty() {
+            self.record_model_use(
+                self.auxiliary_model(),
+                UsageTask::Background,
+                started,
+                None,
+                summary.is_ok(),
+            );
+        }
+        summary
+    }
+
     /// Save session for a specific agent ID (used by HTTP server)
     pub async fn save_session_for_agent(&self, agent_id: &str) -> Result<PathBuf> {
         self.session.save_for_agent(agent_id)
     }
 
+    /// Message, tool, and token statistics for the current session
+    pub fn session_stats(&self) -> SessionStats {
+        SessionStats::from_session(&self.session)
+    }
+
+    /// Models each configured provider offers, for the `/model` picker
+    pub async fn available_models(&self) -> Vec<ProviderModels> {
+        list_available_models(&self.app_config).await
+    }
+
+    /// Check each configured provider for `/doctor`
+    pub async fn health_checks(&self) -> Vec<ProviderHealth> {
+        run_health_checks(&self.app_config).await
+    }
+
+    /// Probe the configured Ollama hosts (empty if Ollama is not configured)
+    pub async fn ollama_hosts(&self) -> Vec<OllamaHostStatus> {
+        match self.app_config.providers.ollama {
+            Some(ref ollama) => probe_hosts(ollama).await,
+            None => Vec::new(),
+        }
+    }
+
     pub fn session_status(&self) -> SessionStatus {
-        self.session.status_with_usage(
-            self.cumulative_usage.input_tokens,
-            self.cumulative_usage.output_tokens,
-        )
+        let mut status = self.session.status_with_usage(&self.cumulative_usage);
+        (status.token_count, status.usable_tokens, _) = self.context_usage();
+        status
     }
 
     /// Stream chat response - returns a stream of chunks
-    /// After consuming the stream, call `finish_chat_stream` with the full response
-    /// Note: Tool calls during streaming are not yet supported - the model will know
-    /// about tools but any tool_use blocks won't be executed automatically.
+    /// After consuming the stream, call `finish_chat_stream` with the full response,
+    /// or `execute_streaming_tool_calls` if the final chunk carries tool calls.
+    /// With content moderation on, all the text comes in the final chunk.
     pub async fn chat_stream(&mut self, message: &str) -> Result<StreamResult> {
         self.chat_stream_with_images(message, Vec::new()).await
     }
@@ -733,6 +2613,9 @@ impl Agent {
         message: &str,
         images: Vec<ImageAttachment>,
     ) -> Result<StreamResult> {
+        let message = self.begin_turn(message).await?;
+        self.moderate_input(message).await?;
+
         // Add user message with images
         self.session.add_message(Message {
             role: Role::User,
@@ -748,25 +2631,82 @@ impl Agent {
             self.memory_flush().await?;
         }
 
-        // Check if we need to compact (hard limit)
-        if self.should_compact() {
-            self.compact_session().await?;
-        }
+        // Tool schemas count toward the window, so update them first
+        self.refresh_mcp_tools().await;
+
+        // Compact (or truncate) if the request would overflow the window
+        self.fit_context(false).await;
 
         // Build messages for LLM
-        let messages = self.session.messages_for_llm();
+        let messages = self.messages_for_llm();
 
         // Get tool schemas so the model knows the correct tool call format
         let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
 
         // Get stream from provider with tools
-        self.provider
+        self.start_checkpoint();
+        let started = Instant::now();
+        let stream = match self
+            .active_provider()
             .chat_stream(&messages, Some(&tool_schemas))
             .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.record_model_use(self.active_model(), self.reply_task(), started, None, false);
+                return Err(e);
+            }
+        };
+        self.reply_started = Some(started);
+
+        // Keep the token counts the provider reports at the end
+        use futures::StreamExt;
+        let reported = Arc::clone(&self.streamed_usage);
+        *reported.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        let stream: StreamResult = Box::pin(stream.inspect(move |chunk| {
+            if let Ok(StreamChunk {
+                usage: Some(usage), ..
+            }) = chunk
+            {
+                *reported.lock().unwrap_or_else(|e| e.into_inner()) = Some(usage.clone());
+            }
+        }));
+
+        // With moderation on, the reply is held back until it has been
+        // checked, then sent whole in the final chunk
+        let stream: StreamResult = match self.moderation.clone() {
+            Some(hook) => Box::pin(hold_for_moderation(stream, hook)),
+            None => stream,
+        };
+
+        // Checkpoint the response as it streams
+        let stream: StreamResult = if self.app_config.agent.keep_reasoning {
+            stream
+        } else {
+            Box::pin(stream.map(|chunk| {
+                chunk.map(|mut chunk| {
+                    chunk.reasoning.clear();
+                    chunk
+                })
+            }))
+        };
+        Ok(match self.checkpoint.clone() {
+            Some(checkpoint) => Box::pin(stream.inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    let mut checkpoint = checkpoint.lock().unwrap_or_else(|e| e.into_inner());
+                    checkpoint.push_content(&chunk.delta);
+                    if let Some(ref calls) = chunk.tool_calls {
+                        checkpoint.push_tool_calls(calls);
+                    }
+                }
+            })),
+            None => stream,
+        })
     }
 
     /// Complete a streaming chat by adding the assistant response to the session
     pub fn finish_chat_stream(&mut self, response: &str) {
+        self.finish_streamed_request(response);
         self.session.add_message(Message {
             role: Role::Assistant,
             content: response.to_string(),
@@ -774,6 +2714,8 @@ impl Agent {
             tool_call_id: None,
             images: Vec::new(),
         });
+        self.note_reply();
+        self.finish_checkpoint();
     }
 
     /// Execute tool calls that were accumulated during streaming
@@ -783,6 +2725,8 @@ impl Agent {
         text_response: &str,
         tool_calls: Vec<ToolCall>,
     ) -> Result<String> {
+        self.finish_streamed_request(text_response);
+
         // Add assistant message with tool calls
         self.session.add_message(Message {
             role: Role::Assistant,
@@ -792,20 +2736,9 @@ impl Agent {
             images: Vec::new(),
         });
 
-        // Execute each tool and collect results
-        let mut results = Vec::new();
-        for call in &tool_calls {
-            debug!(
-                "Executing tool: {} with args: {}",
-                call.name, call.arguments
-            );
-
-            let result = self.execute_tool(call).await;
-            results.push(ToolResult {
-                call_id: call.id.clone(),
-                output: result.unwrap_or_else(|e| format!("Error: {}", e)),
-            });
-        }
+        // Execute the tools and collect results (calls here were approved by the caller)
+        let (results, tool_images) = self.execute_tool_calls(&tool_calls, true).await;
+        self.note_tool_batch(&tool_calls);
 
         // Add tool results to session
         for result in &results {
@@ -817,17 +2750,18 @@ impl Agent {
                 images: Vec::new(),
             });
         }
+        for (name, images) in tool_images {
+            self.add_tool_images(&name, images);
+        }
 
         // Get follow-up response from LLM
-        let messages = self.session.messages_for_llm();
+        let messages = self.messages_for_llm();
         let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
-        let response = self
-            .provider
-            .chat(&messages, Some(tool_schemas.as_slice()))
-            .await?;
+        let response = self.timed_chat(&messages, &tool_schemas).await?;
 
         // Handle the response (may have more tool calls)
         let final_response = self.handle_response(response).await?;
+        let final_response = self.moderate_output(final_response).await;
 
         // Add final response to session
         self.session.add_message(Message {
@@ -837,18 +2771,20 @@ impl Agent {
             tool_call_id: None,
             images: Vec::new(),
         });
+        self.note_reply();
+        self.finish_checkpoint();
 
         Ok(final_response)
     }
 
     /// Get a reference to the LLM provider for streaming
     pub fn provider(&self) -> &dyn LLMProvider {
-        &*self.provider
+        self.active_provider()
     }
 
     /// Get messages for the LLM (for streaming)
     pub fn session_messages(&self) -> Vec<Message> {
-        self.session.messages_for_llm()
+        self.messages_for_llm()
     }
 
     /// Get raw session messages with metadata (for API responses)
@@ -885,14 +2821,66 @@ impl Agent {
         &mut self,
         message: &str,
     ) -> Result<impl futures::Stream<Item = Result<StreamEvent>> + '_> {
-        // Add user message
-        self.session.add_message(Message {
+        let message = self.begin_turn(message).await?;
+        self.moderate_input(message).await?;
+
+        self.add_user_turn(Message {
             role: Role::User,
             content: message.to_string(),
             tool_calls: None,
             tool_call_id: None,
             images: Vec::new(),
-        });
+        })
+        .await?;
+
+        self.start_checkpoint();
+        Ok(self.stream_with_tool_loop())
+    }
+
+    /// Replace the last reply by running its user message again, with
+    /// the sampling preset applied to this turn only
+    pub async fn regenerate_stream(
+        &mut self,
+        style: RegenerateStyle,
+    ) -> Result<impl futures::Stream<Item = Result<StreamEvent>> + '_> {
+        let model = self.config.model.clone();
+        self.check_model_budget(&model)?;
+        let message = self
+            .session
+            .rewind_last_turn()
+            .ok_or_else(|| anyhow::anyhow!("No previous message to regenerate"))?;
+        if let Some((model, task)) = self.last_reply.take() {
+            self.model_usage
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .record_rejected(&model, task);
+        }
+        self.reset_turn_state();
+
+        if let Some(temperature) = style.temperature() {
+            let model = self.config.model.clone();
+            let mut params = self.session.generation_params().clone();
+            params.temperature = Some(temperature);
+            let mut provider = providers::create_provider(&model, &self.app_config)?;
+            provider.set_generation_params(&params);
+            info!("Regenerating with temperature {}", temperature);
+            self.turn_override = Some((model, provider));
+        }
+
+        self.add_user_turn(message).await?;
+        self.start_checkpoint();
+        Ok(self.stream_with_tool_loop())
+    }
+
+    /// Show a different version of the last reply to the model from now on
+    pub fn use_reply_variant(&mut self, content: &str) -> bool {
+        self.session.replace_last_reply(content)
+    }
+
+    /// Add the user message for a turn, flushing memory and compacting first
+    /// if the context is getting full
+    async fn add_user_turn(&mut self, message: Message) -> Result<()> {
+        self.session.add_message(message);
 
         // Check if we should run pre-compaction memory flush (soft threshold)
         if self.should_memory_flush() {
@@ -900,111 +2888,354 @@ impl Agent {
             self.memory_flush().await?;
         }
 
-        // Check if we need to compact (hard limit)
-        if self.should_compact() {
-            self.compact_session().await?;
-        }
+        // Tool schemas count toward the window, so update them first
+        self.refresh_mcp_tools().await;
 
-        Ok(self.stream_with_tool_loop())
+        // Compact (or truncate) if the request would overflow the window
+        self.fit_context(false).await;
+        Ok(())
     }
 
     fn stream_with_tool_loop(&mut self) -> impl futures::Stream<Item = Result<StreamEvent>> + '_ {
+        use futures::StreamExt;
+
         async_stream::stream! {
-            let max_tool_iterations = 10;
+            let mut limits = TurnLimits::new(&self.app_config.agent);
             let mut iteration = 0;
+            let cancel = self.abort.start_turn();
 
             loop {
                 iteration += 1;
-                if iteration > max_tool_iterations {
-                    yield Err(anyhow::anyhow!("Max tool iterations exceeded"));
-                    break;
+
+                // Tool results may have filled the window since the turn began
+                if iteration > 1 {
+                    self.fit_context(true).await;
                 }
 
                 // Get tool schemas
                 let tool_schemas: Vec<ToolSchema> = self.tools.iter().map(|t| t.schema()).collect();
 
                 // Build messages for LLM
-                let messages = self.session.messages_for_llm();
-
-                // Try streaming first (without tools since most providers don't support tool streaming)
-                // Then check for tool calls in the response
-                let response = self
-                    .provider
-                    .chat(&messages, Some(tool_schemas.as_slice()))
-                    .await;
-
-                match response {
-                    Ok(resp) => {
-                        // Track usage
-                        self.add_usage(resp.usage);
-
-                        match resp.content {
-                            LLMResponseContent::Text(text) => {
-                                // No tool calls - yield the text and we're done
-                                yield Ok(StreamEvent::Content(text.clone()));
-                                yield Ok(StreamEvent::Done);
-
-                                // Add to session
-                                self.session.add_message(Message {
-                                    role: Role::Assistant,
-                                    content: text,
-                                    tool_calls: None,
-                                    tool_call_id: None,
-                                    images: Vec::new(),
-                                });
+                let messages = self.messages_for_llm();
+
+                let started = Instant::now();
+                let request = self
+                    .active_provider()
+                    .chat_stream(&messages, Some(tool_schemas.as_slice()));
+                let mut stream = match cancel.run_until_cancelled(request).await {
+                    Some(Ok(stream)) => stream,
+                    Some(Err(e)) => {
+                        self.record_model_use(self.active_model(), self.reply_task(), started, None, false);
+                        yield Err(e);
+                        break;
+                    }
+                    None => {
+                        info!("Turn stopped before the model replied");
+                        yield Ok(StreamEvent::Done);
+                        self.finish_checkpoint();
+                        break;
+                    }
+                };
+
+                // Moderation needs the whole response, so text is held back
+                // until the stream ends when it is enabled
+                let hold_text = self.moderation.is_some();
+                let mut text = String::new();
+                let mut calls: Vec<ToolCall> = Vec::new();
+                let mut reported: Option<Usage> = None;
+                let mut failed = false;
+                let mut stopped = false;
+                loop {
+                    let chunk = match cancel.run_until_cancelled(stream.next()).await {
+                        Some(Some(chunk)) => chunk,
+                        Some(None) => break,
+                        None => {
+                            stopped = true;
+                            break;
+                        }
+                    };
+                    match chunk {
+                        Ok(chunk) => {
+                            if !chunk.reasoning.is_empty() && self.app_config.agent.keep_reasoning {
+                                yield Ok(StreamEvent::Reasoning(chunk.reasoning));
+                            }
+                            for event in chunk.provider_tools {
+                                yield Ok(StreamEvent::ProviderTool(event));
+                            }
+                            if !chunk.delta.is_empty() {
+                                text.push_str(&chunk.delta);
+                                if !hold_text {
+                                    self.with_checkpoint(|c| c.push_content(&chunk.delta));
+                                    yield Ok(StreamEvent::Content(chunk.delta));
+                                }
+                            }
+                            if let Some(tool_calls) = chunk.tool_calls {
+                                calls = tool_calls;
+                            }
+                            if chunk.usage.is_some() {
+                                reported = chunk.usage;
+                            }
+                            if chunk.done {
                                 break;
                             }
-                            LLMResponseContent::ToolCalls(calls) => {
-                        // Notify about tool calls
-                        for call in &calls {
-                            yield Ok(StreamEvent::ToolCallStart {
-                                name: call.name.clone(),
-                                id: call.id.clone(),
-                                arguments: call.arguments.clone(),
-                            });
-
-                            // Execute tool
-                            let result = self.execute_tool(call).await;
-                            let output = result.unwrap_or_else(|e| format!("Error: {}", e));
+                        }
+                        Err(e) => {
+                            yield Err(e);
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                // Dropping the stream closes the connection or kills the CLI
+                drop(stream);
+                if failed {
+                    self.record_model_use(self.active_model(), self.reply_task(), started, None, false);
+                    break;
+                }
+                let usage = reported.clone().unwrap_or_else(|| self.estimated_usage(&text));
+                self.record_model_use(self.active_model(), self.reply_task(), started, Some(&usage), true);
+                self.add_usage(reported);
+
+                if stopped {
+                    info!("Turn stopped during the reply");
+                    // Keep what was shown; held text was never shown
+                    calls.clear();
+                    if hold_text {
+                        text.clear();
+                    }
+                }
 
-                            yield Ok(StreamEvent::ToolCallEnd {
-                                name: call.name.clone(),
-                                id: call.id.clone(),
-                                output: output.clone(),
-                            });
+                if hold_text && !text.is_empty() {
+                    text = self.moderate_output(text).await;
+                    self.with_checkpoint(|c| c.push_content(&text));
+                    yield Ok(StreamEvent::Content(text.clone()));
+                }
 
-                            // Add tool result to session
-                            self.session.add_message(Message {
-                                role: Role::Tool,
-                                content: output,
-                                tool_calls: None,
-                                tool_call_id: Some(call.id.clone()),
-                                images: Vec::new(),
-                            });
-                        }
+                if calls.is_empty() {
+                    // No tool calls - we're done
+                    yield Ok(StreamEvent::Done);
 
-                        // Add tool call message to session
+                    // Add to session (a reply stopped before any text has nothing to add)
+                    if !(stopped && text.is_empty()) {
                         self.session.add_message(Message {
                             role: Role::Assistant,
-                            content: String::new(),
-                            tool_calls: Some(calls),
+                            content: text,
+                            tool_calls: None,
                             tool_call_id: None,
                             images: Vec::new(),
                         });
+                        self.note_reply();
+                    }
+                    self.finish_checkpoint();
+                    break;
+                }
 
-                        // Continue loop to get next response
-                            }
+                let mut tool_images = Vec::new();
+                self.with_checkpoint(|c| c.push_tool_calls(&calls));
+
+                // Add tool call message (with any text streamed before it) to
+                // session; the results must follow it
+                self.session.add_message(Message {
+                    role: Role::Assistant,
+                    content: text,
+                    tool_calls: Some(calls.clone()),
+                    tool_call_id: None,
+                    images: Vec::new(),
+                });
+
+                // Notify about tool calls
+                for call in &calls {
+                    yield Ok(StreamEvent::ToolCallStart {
+                        name: call.name.clone(),
+                        id: call.id.clone(),
+                        arguments: call.arguments.clone(),
+                    });
+                }
+
+                // Calls the user denies (or that were waiting when the turn
+                // was stopped) don't run
+                let decisions = cancel
+                    .run_until_cancelled(self.ask_approval(&calls))
+                    .await
+                    .unwrap_or_else(|| vec![Some(false); calls.len()]);
+                let mut outputs: Vec<Option<String>> = vec![None; calls.len()];
+                if !cancel.is_cancelled() {
+                    for (index, call) in calls.iter().enumerate() {
+                        if decisions[index] != Some(false) {
+                            continue;
                         }
+                        yield Ok(StreamEvent::ToolCallEnd {
+                            name: call.name.clone(),
+                            id: call.id.clone(),
+                            output: DENIED_OUTPUT.to_string(),
+                            summary: None,
+                        });
+                        outputs[index] = Some(DENIED_OUTPUT.to_string());
                     }
-                    Err(e) => {
-                        yield Err(e);
-                        break;
+                }
+                let runnable: Vec<usize> =
+                    (0..calls.len()).filter(|&i| decisions[i] != Some(false)).collect();
+
+                // Execute tools, reporting each as it finishes. Tools still
+                // running when the turn is stopped are dropped.
+                {
+                    let run: Vec<ToolCall> = runnable.iter().map(|&i| calls[i].clone()).collect();
+                    let approved: Vec<bool> =
+                        runnable.iter().map(|&i| decisions[i] == Some(true)).collect();
+                    let mut finished = std::pin::pin!(self.run_tool_calls(&run, &approved));
+                    loop {
+                        let Some(Some((index, result))) =
+                            cancel.run_until_cancelled(finished.next()).await
+                        else {
+                            break;
+                        };
+                        let index = runnable[index];
+                        let call = &calls[index];
+                        let output = match result {
+                            Ok((output, images)) => {
+                                tool_images.push((call.name.clone(), images));
+                                output
+                            }
+                            Err(e) => format!("Error: {}", e),
+                        };
+
+                        // The UI gets the full output; the context may get
+                        // a shorter copy
+                        let condensed = cancel
+                            .run_until_cancelled(self.condense_tool_output(&call.name, &output))
+                            .await
+                            .flatten();
+                        let summary = cancel
+                            .run_until_cancelled(self.summarize_tool_output(&output))
+                            .await
+                            .flatten()
+                            .or_else(|| {
+                                condensed.as_ref().map(|condensed| {
+                                    format!(
+                                        "Long output ({} chars), shortened to {} chars for the model.",
+                                        output.chars().count(),
+                                        condensed.chars().count()
+                                    )
+                                })
+                            });
+                        yield Ok(StreamEvent::ToolCallEnd {
+                            name: call.name.clone(),
+                            id: call.id.clone(),
+                            output: output.clone(),
+                            summary,
+                        });
+                        outputs[index] = Some(condensed.unwrap_or(output));
                     }
                 }
+
+                // Add tool results to session, in call order; every call
+                // needs one, even if it never finished
+                let outputs: Vec<String> = outputs
+                    .into_iter()
+                    .map(|output| output.unwrap_or_else(|| "Error: stopped by the user".to_string()))
+                    .collect();
+                for (call, output) in calls.iter().zip(&outputs) {
+                    self.session.add_message(Message {
+                        role: Role::Tool,
+                        content: output.clone(),
+                        tool_calls: None,
+                        tool_call_id: Some(call.id.clone()),
+                        images: Vec::new(),
+                    });
+                }
+
+                self.note_tool_batch(&calls);
+                for (name, images) in tool_images {
+                    self.add_tool_images(&name, images);
+                }
+
+                if cancel.is_cancelled() {
+                    info!("Turn stopped while running tools");
+                    yield Ok(StreamEvent::Done);
+                    self.finish_checkpoint();
+                    break;
+                }
+
+                // End with a note on which limit was hit, keeping the results
+                if let Some(hit) = limits.after_tool_round(outputs.iter().map(String::as_str)) {
+                    info!("Ending turn: {:?}", hit);
+                    let notice = hit.notice();
+                    self.with_checkpoint(|c| c.push_content(&notice));
+                    yield Ok(StreamEvent::Content(notice.clone()));
+                    yield Ok(StreamEvent::Done);
+                    self.session.add_message(Message {
+                        role: Role::Assistant,
+                        content: notice,
+                        tool_calls: None,
+                        tool_call_id: None,
+                        images: Vec::new(),
+                    });
+                    self.finish_checkpoint();
+                    break;
+                }
+
+                // Continue loop to get next response
+            }
+        }
+    }
+
+    /// Share an abort handle, so another thread (e.g. the UI) can stop
+    /// streamed turns
+    pub fn set_abort_handle(&mut self, abort: AbortHandle) {
+        self.abort = abort;
+    }
+
+    /// Subscribe to live stdout/stderr of bash tool executions
+    pub fn subscribe_shell_events(&self) -> tokio::sync::broadcast::Receiver<ShellEvent> {
+        self.shell_events.subscribe()
+    }
+
+    /// Run a user-typed command through the agent's bash tool (same shell and timeout
+    /// as agent commands). Output is mirrored to shell event subscribers and is not
+    /// added to the conversation.
+    pub async fn run_shell_command(&self, command: &str) -> Result<String> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.name() == "bash")
+            .ok_or_else(|| anyhow::anyhow!("bash tool not available"))?;
+        tool.execute(&serde_json::json!({ "command": command }).to_string())
+            .await
+    }
+
+    /// List the tools of MCP servers that reconnected or said their tools
+    /// changed, replacing the ones offered before
+    async fn refresh_mcp_tools(&mut self) {
+        for client in self.mcp_clients.clone() {
+            if !client.take_tools_changed() {
+                continue;
+            }
+            match client.tools().await {
+                Ok(found) => {
+                    debug!(
+                        "MCP server '{}' now has {} tools",
+                        client.name(),
+                        found.len()
+                    );
+                    self.tools.retain(|t| !client.owns_tool(t.name()));
+                    add_mcp_tools(&mut self.tools, found, &self.app_config.tools);
+                }
+                Err(e) => warn!(
+                    "Could not list tools of MCP server '{}': {:#}",
+                    client.name(),
+                    e
+                ),
             }
         }
     }
 
+    /// Give this agent one more tool (`register_tool` adds one to every
+    /// agent). Replaces a tool with the same name.
+    pub fn add_tool(&mut self, tool: Box<dyn Tool>) {
+        self.tools.retain(|t| t.name() != tool.name());
+        self.tools.push(tool);
+    }
+
     /// Get tool schemas for external use
     pub fn tool_schemas(&self) -> Vec<ToolSchema> {
         self.tools.iter().map(|t| t.schema()).collect()
@@ -1034,3 +3265,137 @@ This is your first session. I've set up a fresh workspace for you.
 What's your name? What kind of projects do you work on? Any preferences for how I should communicate?
 
 I'll save what I learn to MEMORY.md so I remember it next time."#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_override() {
+        assert_eq!(
+            parse_model_override("@gpt-4o explain this"),
+            Some(("gpt-4o", "explain this"))
+        );
+        assert_eq!(
+            parse_model_override("  @ollama/llama3   hi\nthere"),
+            Some(("ollama/llama3", "hi\nthere"))
+        );
+        assert_eq!(parse_model_override("@opus"), None);
+        assert_eq!(parse_model_override("@ hello"), None);
+        assert_eq!(parse_model_override("email me@example.com"), None);
+    }
+
+    #[test]
+    fn test_model_names() {
+        let mut config = Config::default();
+        config
+            .models
+            .aliases
+            .insert("fast".to_string(), "openai/gpt-4o-mini".to_string());
+        for name in [
+            "fast",
+            "opus",
+            "gpt-4o",
+            "ollama/llama3",
+            "llama3.2:3b",
+            "model.gguf",
+        ] {
+            assert!(providers::is_model_name(name, &config), "{}", name);
+        }
+        for name in ["alice", "team", "someone/else"] {
+            assert!(!providers::is_model_name(name, &config), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamed_reply_held_for_moderation() {
+        use futures::StreamExt;
+
+        let mut config = Config::default();
+        config.moderation.enabled = true;
+        config.moderation.provider = "keywords".to_string();
+        config.moderation.action = "block".to_string();
+        config.moderation.blocked_patterns = vec!["secret plan".to_string()];
+        let hook = Arc::new(ModerationHook::from_config(&config).unwrap().unwrap());
+
+        let chunk = |delta: &str, done: bool| {
+            Ok(StreamChunk {
+                delta: delta.to_string(),
+                done,
+                tool_calls: None,
+                tool_call_deltas: Vec::new(),
+                provider_tools: Vec::new(),
+                reasoning: String::new(),
+                usage: None,
+            })
+        };
+        let reply = |chunks: Vec<Result<StreamChunk>>| {
+            let stream: StreamResult = Box::pin(futures::stream::iter(chunks));
+            let hook = Arc::clone(&hook);
+            async move {
+                hold_for_moderation(stream, hook)
+                    .map(|chunk| chunk.unwrap().delta)
+                    .collect::<Vec<_>>()
+                    .await
+            }
+        };
+
+        assert_eq!(
+            reply(vec![chunk("the secret ", false), chunk("plan", true)]).await,
+            vec!["".to_string(), MODERATION_BLOCKED_RESPONSE.to_string()]
+        );
+        assert_eq!(
+            reply(vec![chunk("all ", false), chunk("good", false)]).await,
+            vec!["", "", "all good"]
+        );
+    }
+
+    #[test]
+    fn test_tool_call_batches() {
+        let calls: Vec<ToolCall> = [
+            "read_file",
+            "grep_workspace",
+            "edit_file",
+            "run_tests",
+            "bash",
+            "web_search",
+            "fetch_url",
+            "mcp_lookup",
+            "find_files",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| ToolCall {
+            id: i.to_string(),
+            name: name.to_string(),
+            arguments: "{}".to_string(),
+        })
+        .collect();
+        assert_eq!(
+            tool_call_batches(&calls),
+            vec![
+                vec![0, 1],
+                vec![2],
+                vec![3],
+                vec![4],
+                vec![5, 6],
+                vec![7],
+                vec![8]
+            ]
+        );
+        assert!(tool_call_batches(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_summarize_model() {
+        let mut config = Config::default();
+        assert_eq!(summarize_model(&config), None);
+        config
+            .models
+            .aliases
+            .insert(SUMMARIZER_ALIAS.to_string(), "ollama/llama3".to_string());
+        assert_eq!(summarize_model(&config), Some(SUMMARIZER_ALIAS));
+        config.agent.summarize_model = Some("openai/gpt-4o-mini".to_string());
+        assert_eq!(summarize_model(&config), Some("openai/gpt-4o-mini"));
+    }
+}
@@ -0,0 +1,450 @@
+//! Per-model usage tracking and model suggestions
+//!
+//! Every model request records its latency, whether it failed, and its token
+//! counts in `~/.localgpt/model_usage.json`, split by the kind of work (chat,
+//! answers drawing on memory, background titles and summaries). A
+//! regenerated reply counts against the model that wrote it. The desktop
+//! Status panel shows the totals and suggests cheaper or more reliable
+//! models for `agent.auxiliary_model` and `agent.default_model`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+
+use super::providers::Usage;
+use super::session::get_state_dir;
+
+/// Requests needed before a model's record is trusted for suggestions
+const MIN_REQUESTS: u64 = 10;
+
+/// Share of good replies for a model to count as doing a task fine
+const GOOD_RATE: f64 = 0.9;
+
+/// Failure rate at which switching the default model is suggested
+const UNRELIABLE_RATE: f64 = 0.25;
+
+/// USD per million input and output tokens. More specific names come first
+/// since the first substring match wins.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("o1", 15.0, 60.0),
+    ("opus", 5.0, 25.0),
+    ("sonnet", 3.0, 15.0),
+    ("haiku", 1.0, 5.0),
+    ("gemini-2.5-flash", 0.3, 2.5),
+    ("gemini-flash", 0.3, 2.5),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini", 1.25, 10.0),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("deepseek-r1", 0.55, 2.19),
+    ("deepseek", 0.27, 1.1),
+    ("gpt", 2.5, 10.0),
+];
+
+/// What a model request was for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageTask {
+    /// A reply in the conversation
+    Chat,
+    /// A reply that used memory_search or memory_get
+    Memory,
+    /// Titles, compaction and tool output summaries
+    Background,
+}
+
+impl UsageTask {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UsageTask::Chat => "chat",
+            UsageTask::Memory => "memory",
+            UsageTask::Background => "background",
+        }
+    }
+}
+
+/// Totals for one model and task
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskUsage {
+    pub requests: u64,
+    pub failures: u64,
+    /// Replies the user regenerated
+    pub rejected: u64,
+    pub latency_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TaskUsage {
+    fn add(&mut self, other: &TaskUsage) {
+        self.requests += other.requests;
+        self.failures += other.failures;
+        self.rejected += other.rejected;
+        self.latency_ms += other.latency_ms;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+
+    /// Share of requests that succeeded and were not regenerated
+    pub fn good_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        let good = self.requests.saturating_sub(self.failures + self.rejected);
+        good as f64 / self.requests as f64
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.failures as f64 / self.requests as f64
+    }
+
+    pub fn avg_latency_ms(&self) -> u64 {
+        let answered = self.requests.saturating_sub(self.failures);
+        self.latency_ms.checked_div(answered).unwrap_or(0)
+    }
+}
+
+/// One row of the Status panel usage table
+#[derive(Debug, Clone)]
+pub struct ModelUsageSummary {
+    pub model: String,
+    pub usage: TaskUsage,
+    /// Estimated spend in USD; None for models without a known price
+    pub cost: Option<f64>,
+}
+
+/// A config change the usage data argues for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSuggestion {
+    pub message: String,
+    /// Config key to change (`agent.auxiliary_model` or `agent.default_model`)
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelUsageReport {
+    pub models: Vec<ModelUsageSummary>,
+    pub suggestions: Vec<ModelSuggestion>,
+}
+
+#[derive(Debug, Default)]
+pub struct ModelUsageStore {
+    /// None keeps the records in memory only
+    path: Option<PathBuf>,
+    models: BTreeMap<String, BTreeMap<UsageTask, TaskUsage>>,
+}
+
+impl ModelUsageStore {
+    /// Load the usage file (missing or unreadable starts empty)
+    pub fn load() -> Self {
+        let path = get_state_dir().ok().map(|dir| dir.join("model_usage.json"));
+        let models = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, models }
+    }
+
+    /// Record one request to `model`
+    pub fn record(
+        &mut self,
+        model: &str,
+        task: UsageTask,
+        latency: Duration,
+        usage: Option<&Usage>,
+        ok: bool,
+    ) {
+        let entry = self.entry(model, task);
+        entry.requests += 1;
+        if ok {
+            entry.latency_ms += latency.as_millis() as u64;
+        } else {
+            entry.failures += 1;
+        }
+        if let Some(usage) = usage {
+            entry.input_tokens += usage.input_tokens;
+            entry.output_tokens += usage.output_tokens;
+        }
+        self.persist();
+    }
+
+    /// Count a reply from `model` that the user regenerated
+    pub fn record_rejected(&mut self, model: &str, task: UsageTask) {
+        self.entry(model, task).rejected += 1;
+        self.persist();
+    }
+
+    fn entry(&mut self, model: &str, task: UsageTask) -> &mut TaskUsage {
+        self.models
+            .entry(model.to_string())
+            .or_default()
+            .entry(task)
+            .or_default()
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            debug!("Failed to save model usage: {}", e);
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temp file then atomically rename
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.models)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// All tasks of a model added together
+    fn total(&self, model: &str) -> TaskUsage {
+        let mut total = TaskUsage::default();
+        for usage in self.models.get(model).into_iter().flat_map(|t| t.values()) {
+            total.add(usage);
+        }
+        total
+    }
+
+    /// Per-model totals, most used first
+    pub fn summaries(&self) -> Vec<ModelUsageSummary> {
+        let mut summaries: Vec<ModelUsageSummary> = self
+            .models
+            .keys()
+            .map(|model| {
+                let usage = self.total(model);
+                let cost = estimated_cost(model, usage.input_tokens, usage.output_tokens);
+                ModelUsageSummary {
+                    model: model.clone(),
+                    usage,
+                    cost,
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.usage
+                .requests
+                .cmp(&a.usage.requests)
+                .then_with(|| a.model.cmp(&b.model))
+        });
+        summaries
+    }
+
+    /// Suggested changes to the default and auxiliary models
+    pub fn suggestions(
+        &self,
+        default_model: &str,
+        auxiliary_model: Option<&str>,
+    ) -> Vec<ModelSuggestion> {
+        let mut suggestions = Vec::new();
+
+        // A cheaper model that handles some task fine can take over
+        // background work
+        let auxiliary = auxiliary_model.unwrap_or(default_model);
+        if let Some(current_price) = price(auxiliary).map(|(i, o)| i + o) {
+            let cheaper = self
+                .models
+                .iter()
+                .filter(|(model, _)| *model != auxiliary)
+                .filter_map(|(model, tasks)| {
+                    let model_price = price(model).map(|(i, o)| i + o)?;
+                    let (task, usage) = tasks
+                        .iter()
+                        .filter(|(_, u)| u.requests >= MIN_REQUESTS && u.good_rate() >= GOOD_RATE)
+                        .max_by_key(|(_, u)| u.requests)?;
+                    (model_price < current_price).then_some((model, model_price, task, usage))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((model, _, task, usage)) = cheaper {
+                suggestions.push(ModelSuggestion {
+                    message: format!(
+                        "{} answered {:.0}% of your {} queries fine — consider making it \
+                        the auxiliary model",
+                        model,
+                        usage.good_rate() * 100.0,
+                        task.label()
+                    ),
+                    key: "agent.auxiliary_model".to_string(),
+                    value: model.clone(),
+                });
+            }
+        }
+
+        // Conversation replies only; background work says little about how a
+        // model does as the main model
+        let replies = |model: &str| {
+            let mut total = TaskUsage::default();
+            for (task, usage) in self.models.get(model).into_iter().flatten() {
+                if *task != UsageTask::Background {
+                    total.add(usage);
+                }
+            }
+            total
+        };
+        let current = replies(default_model);
+        if current.requests < MIN_REQUESTS {
+            return suggestions;
+        }
+        let alternatives: Vec<(&String, TaskUsage)> = self
+            .models
+            .keys()
+            .filter(|model| *model != default_model)
+            .map(|model| (model, replies(model)))
+            .filter(|(_, usage)| usage.requests >= MIN_REQUESTS)
+            .collect();
+
+        if current.failure_rate() >= UNRELIABLE_RATE {
+            let better = alternatives
+                .iter()
+                .filter(|(_, usage)| usage.good_rate() >= GOOD_RATE)
+                .max_by(|a, b| a.1.good_rate().total_cmp(&b.1.good_rate()));
+            if let Some((model, usage)) = better {
+                suggestions.push(ModelSuggestion {
+                    message: format!(
+                        "{} failed {:.0}% of requests while {} answered {:.0}% fine — \
+                        consider making it the default model",
+                        default_model,
+                        current.failure_rate() * 100.0,
+                        model,
+                        usage.good_rate() * 100.0
+                    ),
+                    key: "agent.default_model".to_string(),
+                    value: (*model).clone(),
+                });
+                return suggestions;
+            }
+        }
+
+        let faster = alternatives
+            .iter()
+            .filter(|(_, usage)| {
+                usage.good_rate() >= current.good_rate()
+                    && usage.avg_latency_ms() > 0
+                    && usage.avg_latency_ms() * 2 <= current.avg_latency_ms()
+            })
+            .min_by_key(|(_, usage)| usage.avg_latency_ms());
+        if let Some((model, usage)) = faster {
+            suggestions.push(ModelSuggestion {
+                message: format!(
+                    "{} replies in {:.1}s on average vs {:.1}s for {} with as many good \
+                    answers — consider making it the default model",
+                    model,
+                    usage.avg_latency_ms() as f64 / 1000.0,
+                    current.avg_latency_ms() as f64 / 1000.0,
+                    default_model
+                ),
+                key: "agent.default_model".to_string(),
+                value: (*model).clone(),
+            });
+        }
+        suggestions
+    }
+
+    pub fn report(&self, default_model: &str, auxiliary_model: Option<&str>) -> ModelUsageReport {
+        ModelUsageReport {
+            models: self.summaries(),
+            suggestions: self.suggestions(default_model, auxiliary_model),
+        }
+    }
+}
+
+/// Estimated USD for a model's tokens; None without a known price
+pub(super) fn estimated_cost(model: &str, input_tokens: u64, output_tokens: u64) -> Option<f64> {
+    price(model).map(|(input, output)| {
+        (input_tokens as f64 * input + output_tokens as f64 * output) / 1_000_000.0
+    })
+}
+
+/// USD per million (input, output) tokens; local models are free
+fn price(model: &str) -> Option<(f64, f64)> {
+    let model = model.to_lowercase();
+    if model.starts_with("ollama/")
+        || model.starts_with("claude-cli/")
+        || model.starts_with("codex-cli/")
+    {
+        return Some((0.0, 0.0));
+    }
+    PRICES
+        .iter()
+        .find(|(name, _, _)| model.contains(name))
+        .map(|&(_, input, output)| (input, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(store: &mut ModelUsageStore, model: &str, task: UsageTask, ms: u64, ok: &[bool]) {
+        for &ok in ok {
+            store.record(model, task, Duration::from_millis(ms), None, ok);
+        }
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut store = ModelUsageStore::default();
+        record(&mut store, "gpt-4o", UsageTask::Chat, 4000, &[true; 12]);
+        assert!(store.suggestions("gpt-4o", None).is_empty());
+
+        // Cheaper model doing memory queries fine
+        record(
+            &mut store,
+            "gpt-4o-mini",
+            UsageTask::Memory,
+            1000,
+            &[true; 10],
+        );
+        store.record_rejected("gpt-4o-mini", UsageTask::Memory);
+        let suggestions = store.suggestions("gpt-4o", None);
+        assert_eq!(suggestions[0].key, "agent.auxiliary_model");
+        assert_eq!(suggestions[0].value, "gpt-4o-mini");
+        assert!(suggestions[0]
+            .message
+            .contains("90% of your memory queries"));
+        assert!(store.suggestions("gpt-4o", Some("gpt-4o-mini")).is_empty());
+
+        // Much faster model with as many good replies
+        record(
+            &mut store,
+            "ollama/llama3",
+            UsageTask::Chat,
+            1000,
+            &[true; 10],
+        );
+        let suggestions = store.suggestions("gpt-4o", Some("ollama/llama3"));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].key, "agent.default_model");
+        assert_eq!(suggestions[0].value, "ollama/llama3");
+
+        // Unreliable default model
+        record(&mut store, "opus", UsageTask::Chat, 500, &[false; 5]);
+        record(&mut store, "opus", UsageTask::Chat, 500, &[true; 5]);
+        let suggestions = store.suggestions("opus", Some("ollama/llama3"));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].value, "ollama/llama3");
+        assert!(suggestions[0].message.contains("failed 50%"));
+
+        let summaries = store.summaries();
+        assert_eq!(summaries[0].model, "gpt-4o");
+        assert_eq!(summaries[0].usage.avg_latency_ms(), 4000);
+    }
+}
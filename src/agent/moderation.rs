@@ -0,0 +1,298 @@
+//! Content moderation for inbound user messages and outbound model responses
+//!
+//! Providers:
+//! - "openai": OpenAI moderation endpoint (uses `providers.openai` credentials)
+//! - "keywords": local regex classifier driven by `moderation.blocked_patterns`
+
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::config::{Config, ModerationConfig};
+
+/// Outcome of a moderation check
+#[derive(Debug, Clone, Default)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Categories (or matched patterns) that triggered the flag
+    pub categories: Vec<String>,
+}
+
+/// What to do with flagged content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModerationAction {
+    /// Log a warning and let the content through
+    Warn,
+    /// Reject the message or withhold the response
+    Block,
+    /// Record at info level only
+    Log,
+}
+
+impl ModerationAction {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "warn" => Ok(Self::Warn),
+            "block" => Ok(Self::Block),
+            "log" => Ok(Self::Log),
+            other => anyhow::bail!(
+                "Unknown moderation action '{}'. Use warn, block, or log.",
+                other
+            ),
+        }
+    }
+}
+
+/// Which side of the conversation is being checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModerationDirection {
+    Input,
+    Output,
+}
+
+impl std::fmt::Display for ModerationDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input => write!(f, "input"),
+            Self::Output => write!(f, "output"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn check(&self, text: &str) -> Result<ModerationResult>;
+}
+
+/// Moderation settings plus the provider used to run checks
+pub struct ModerationHook {
+    moderator: Box<dyn Moderator>,
+    action: ModerationAction,
+    check_input: bool,
+    check_output: bool,
+}
+
+impl ModerationHook {
+    /// Build the hook from config. Returns None when moderation is disabled.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let mc: &ModerationConfig = &config.moderation;
+        if !mc.enabled {
+            return Ok(None);
+        }
+
+        let moderator: Box<dyn Moderator> = match mc.provider.as_str() {
+            "openai" => {
+                let openai = config.providers.openai.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "OpenAI moderation requires [providers.openai] in ~/.localgpt/config.toml"
+                    )
+                })?;
+                Box::new(OpenAIModerator::new(&openai.api_key, &openai.base_url))
+            }
+            "keywords" => Box::new(KeywordModerator::new(&mc.blocked_patterns)?),
+            other => anyhow::bail!(
+                "Unknown moderation provider '{}'. Use openai or keywords.",
+                other
+            ),
+        };
+
+        Ok(Some(Self {
+            moderator,
+            action: ModerationAction::parse(&mc.action)?,
+            check_input: mc.check_input,
+            check_output: mc.check_output,
+        }))
+    }
+
+    /// Check text and apply the configured action.
+    /// Returns `Some(reason)` when the content should be blocked.
+    /// Moderation failures are logged and let the content through.
+    pub async fn review(&self, text: &str, direction: ModerationDirection) -> Option<String> {
+        let enabled = match direction {
+            ModerationDirection::Input => self.check_input,
+            ModerationDirection::Output => self.check_output,
+        };
+        if !enabled || text.trim().is_empty() {
+            return None;
+        }
+
+        let result = match self.moderator.check(text).await {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("Moderation check failed ({}): {}", direction, e);
+                return None;
+            }
+        };
+
+        if !result.flagged {
+            return None;
+        }
+
+        let reason = if result.categories.is_empty() {
+            "flagged".to_string()
+        } else {
+            result.categories.join(", ")
+        };
+
+        match self.action {
+            ModerationAction::Log => {
+                info!("Moderation flagged {} ({})", direction, reason);
+                None
+            }
+            ModerationAction::Warn => {
+                warn!("Moderation flagged {} ({})", direction, reason);
+                None
+            }
+            ModerationAction::Block => {
+                warn!("Moderation blocked {} ({})", direction, reason);
+                Some(reason)
+            }
+        }
+    }
+}
+
+/// OpenAI moderation endpoint
+pub struct OpenAIModerator {
+    client: reqwest::Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl OpenAIModerator {
+    pub fn new(api_key: &str, base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: api_key.to_string(),
+            base_url: base_url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Moderator for OpenAIModerator {
+    async fn check(&self, text: &str) -> Result<ModerationResult> {
+        let response = self
+            .client
+            .post(format!("{}/moderations", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&json!({
+                "model": "omni-moderation-latest",
+                "input": text,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("OpenAI moderation error {}: {}", status, body);
+        }
+
+        let body: Value = response.json().await?;
+        let result = &body["results"][0];
+
+        let categories = result["categories"]
+            .as_object()
+            .map(|cats| {
+                cats.iter()
+                    .filter(|(_, v)| v.as_bool() == Some(true))
+                    .map(|(k, _)| k.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ModerationResult {
+            flagged: result["flagged"].as_bool().unwrap_or(false),
+            categories,
+        })
+    }
+}
+
+/// Local classifier matching case-insensitive regex patterns
+pub struct KeywordModerator {
+    patterns: Vec<Regex>,
+}
+
+impl KeywordModerator {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                RegexBuilder::new(p)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("Invalid moderation pattern '{}': {}", p, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { patterns })
+    }
+}
+
+#[async_trait]
+impl Moderator for KeywordModerator {
+    async fn check(&self, text: &str) -> Result<ModerationResult> {
+        let categories: Vec<String> = self
+            .patterns
+            .iter()
+            .filter(|re| re.is_match(text))
+            .map(|re| re.as_str().to_string())
+            .collect();
+
+        Ok(ModerationResult {
+            flagged: !categories.is_empty(),
+            categories,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword_hook(action: ModerationAction) -> ModerationHook {
+        ModerationHook {
+            moderator: Box::new(KeywordModerator::new(&["forbidden\\s+word".to_string()]).unwrap()),
+            action,
+            check_input: true,
+            check_output: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keyword_block() {
+        let hook = keyword_hook(ModerationAction::Block);
+        assert!(hook
+            .review("this has a FORBIDDEN  word", ModerationDirection::Input)
+            .await
+            .is_some());
+        assert!(hook
+            .review("all good here", ModerationDirection::Input)
+            .await
+            .is_none());
+        // Output checks disabled
+        assert!(hook
+            .review("forbidden word", ModerationDirection::Output)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_warn_does_not_block() {
+        let hook = keyword_hook(ModerationAction::Warn);
+        assert!(hook
+            .review("forbidden word", ModerationDirection::Input)
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(
+            ModerationAction::parse("Block").unwrap(),
+            ModerationAction::Block
+        );
+        assert!(ModerationAction::parse("delete").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Native desktop notifications: the `notify` tool (`tools.notifications`)
+//!
+//! Lets the agent tell the user a long task finished or needs attention,
+//! mostly from heartbeat and other background runs nobody is watching.
+//! Like the clipboard, this shells out to the platform's own programs
+//! (notify-send, osascript, PowerShell) rather than linking a library.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+
+/// Longest notification body sent; desktops cut long ones off anyway
+const MAX_BODY_CHARS: usize = 500;
+
+/// How insistent a notification is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Urgency {
+    Low,
+    Normal,
+    /// Stays on screen until dismissed where the desktop supports it
+    Critical,
+}
+
+impl Urgency {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Self::Low),
+            "normal" => Some(Self::Normal),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Commands that show a notification, in the order they are tried.
+/// Title and body are passed as arguments or environment variables, never
+/// spliced into a script.
+fn notify_commands(title: &str, body: &str, urgency: Urgency) -> Vec<NotifyCommand> {
+    if cfg!(target_os = "macos") {
+        vec![NotifyCommand {
+            program: "osascript",
+            args: vec![
+                "-e".into(),
+                "on run argv".into(),
+                "-e".into(),
+                "display notification (item 2 of argv) with title (item 1 of argv)".into(),
+                "-e".into(),
+                "end run".into(),
+                title.into(),
+                body.into(),
+            ],
+            env: vec![],
+        }]
+    } else if cfg!(windows) {
+        vec![NotifyCommand {
+            program: "powershell",
+            args: vec![
+                "-NoProfile".into(),
+                "-Command".into(),
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 $n = New-Object System.Windows.Forms.NotifyIcon; \
+                 $n.Icon = [System.Drawing.SystemIcons]::Information; \
+                 $n.Visible = $true; \
+                 $n.ShowBalloonTip(10000, $env:LOCALGPT_NOTIFY_TITLE, $env:LOCALGPT_NOTIFY_BODY, 'Info'); \
+                 Start-Sleep -Seconds 10; $n.Dispose()"
+                    .into(),
+            ],
+            env: vec![
+                ("LOCALGPT_NOTIFY_TITLE", title.into()),
+                ("LOCALGPT_NOTIFY_BODY", body.into()),
+            ],
+        }]
+    } else {
+        vec![NotifyCommand {
+            program: "notify-send",
+            args: vec![
+                "--app-name=LocalGPT".into(),
+                format!("--urgency={}", urgency.as_str()),
+                "--".into(),
+                title.into(),
+                body.into(),
+            ],
+            env: vec![],
+        }]
+    }
+}
+
+struct NotifyCommand {
+    program: &'static str,
+    args: Vec<String>,
+    env: Vec<(&'static str, String)>,
+}
+
+/// Show a native notification
+pub async fn send_notification(title: &str, body: &str, urgency: Urgency) -> Result<()> {
+    let body = match body.char_indices().nth(MAX_BODY_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    };
+
+    let mut errors = Vec::new();
+    for command in notify_commands(title, &body, urgency) {
+        let mut child = tokio::process::Command::new(command.program);
+        child.args(&command.args).envs(command.env.iter().cloned());
+        // The Windows balloon stays up for a while; don't wait on it
+        let result = if cfg!(windows) {
+            child.spawn().map(|_| None)
+        } else {
+            child.output().await.map(Some)
+        };
+        match result {
+            Ok(None) => return Ok(()),
+            Ok(Some(output)) if output.status.success() => {
+                debug!("Sent notification with {}", command.program);
+                return Ok(());
+            }
+            Ok(Some(output)) => errors.push(format!(
+                "{}: {}",
+                command.program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                errors.push(format!("{}: not installed", command.program))
+            }
+            Err(e) => errors.push(format!("{}: {}", command.program, e)),
+        }
+    }
+    anyhow::bail!("Could not show a notification ({})", errors.join("; "))
+}
+
+pub struct NotifyTool;
+
+#[async_trait]
+impl Tool for NotifyTool {
+    fn name(&self) -> &str {
+        "notify"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "notify".to_string(),
+            description: "Show a desktop notification to the user. Use it when a long-running or background task finishes, fails, or needs the user's attention; not for routine replies.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "title": {
+                        "type": "string",
+                        "description": "Short headline, e.g. 'Backup finished'"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "One or two sentences of detail"
+                    },
+                    "urgency": {
+                        "type": "string",
+                        "enum": ["low", "normal", "critical"],
+                        "description": "critical stays on screen until dismissed (default: normal)"
+                    }
+                },
+                "required": ["title", "message"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let title = args["title"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing title"))?;
+        let message = args["message"].as_str().unwrap_or("");
+        let urgency = match args["urgency"].as_str() {
+            None => Urgency::Normal,
+            Some(s) => Urgency::parse(s).ok_or_else(|| {
+                anyhow::anyhow!("Unknown urgency '{}'. Use low, normal or critical.", s)
+            })?,
+        };
+
+        send_notification(title, message, urgency).await?;
+        Ok(format!("Notification shown: {}", title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_commands_pass_text_as_arguments() {
+        let title = "Done \"quoted\" $(rm -rf ~)";
+        let commands = notify_commands(title, "body", Urgency::Critical);
+        let command = &commands[0];
+        // Text only reaches the program as whole arguments or variables
+        let passed: Vec<&str> = command
+            .args
+            .iter()
+            .map(String::as_str)
+            .chain(command.env.iter().map(|(_, v)| v.as_str()))
+            .collect();
+        assert!(passed.contains(&title));
+        assert!(passed.contains(&"body"));
+        if cfg!(target_os = "linux") {
+            assert_eq!(command.program, "notify-send");
+            assert!(passed.contains(&"--urgency=critical"));
+        }
+        assert_eq!(Urgency::parse("urgent"), None);
+    }
+}
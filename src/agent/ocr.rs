@@ -0,0 +1,292 @@
+//! OCR for images and scanned PDFs
+//!
+//! Engines:
+//! - "tesseract": the `tesseract` CLI (PDF pages are rasterized with poppler's `pdftoppm`)
+//! - "ollama": a local vision model served by Ollama (e.g. llava, minicpm-v)
+//!
+//! PDFs with a text layer are read with `pdftotext` first; OCR only runs on
+//! scans. Either way each page starts with a `--- Page n ---` marker. Extracted text can be saved as markdown under `workspace/ocr/` so
+//! the memory index picks it up.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::memory::paged_text;
+
+/// Image extensions OCR can read
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// Scanned PDFs longer than this are truncated
+const MAX_PDF_PAGES: usize = 50;
+
+/// Below this many non-whitespace characters per page, a PDF text layer is treated as missing
+const MIN_CHARS_PER_PAGE: usize = 20;
+
+const VISION_PROMPT: &str = "Transcribe all text in this image exactly as written, preserving line breaks. Output only the text. If there is no text, output nothing.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OcrEngine {
+    Tesseract,
+    Ollama,
+}
+
+impl OcrEngine {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tesseract" => Ok(Self::Tesseract),
+            "ollama" => Ok(Self::Ollama),
+            other => anyhow::bail!("Unknown OCR engine '{}'. Use tesseract or ollama.", other),
+        }
+    }
+}
+
+pub struct Ocr {
+    engine: OcrEngine,
+    language: String,
+    vision_model: String,
+    ollama_endpoint: String,
+    /// Where extracted text is saved for memory search (None disables saving)
+    save_dir: Option<PathBuf>,
+    client: reqwest::Client,
+}
+
+impl Ocr {
+    /// Build from config; None when OCR is disabled
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let ocr = &config.ocr;
+        if !ocr.enabled {
+            return Ok(None);
+        }
+
+        let ollama_endpoint = config
+            .providers
+            .ollama
+            .as_ref()
+            .map(|o| o.endpoint.clone())
+            .unwrap_or_else(|| "http://localhost:11434".to_string());
+
+        Ok(Some(Self {
+            engine: OcrEngine::parse(&ocr.engine)?,
+            language: ocr.language.clone(),
+            vision_model: ocr.vision_model.clone(),
+            ollama_endpoint: ollama_endpoint.trim_end_matches('/').to_string(),
+            save_dir: ocr
+                .save_to_memory
+                .then(|| config.workspace_path().join("ocr")),
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    /// Whether the file is an image or PDF that OCR can handle
+    pub fn supports(path: &Path) -> bool {
+        extension(path).is_some_and(|ext| ext == "pdf" || IMAGE_EXTENSIONS.contains(&ext.as_str()))
+    }
+
+    /// Extract text from an image or PDF
+    pub async fn extract(&self, path: &Path) -> Result<String> {
+        if extension(path).as_deref() == Some("pdf") {
+            self.extract_pdf(path).await
+        } else {
+            self.extract_image(path).await
+        }
+    }
+
+    /// Extract text and, if enabled, save it for memory search.
+    /// Returns the text and the saved note's path.
+    pub async fn extract_and_save(&self, path: &Path) -> Result<(String, Option<PathBuf>)> {
+        let text = self.extract(path).await?;
+        let saved = match self.save_dir {
+            Some(ref dir) if !text.trim().is_empty() => Some(save_note(dir, path, &text)?),
+            _ => None,
+        };
+        Ok((text, saved))
+    }
+
+    async fn extract_pdf(&self, path: &Path) -> Result<String> {
+        let pages = pdf_page_count(path).await.unwrap_or(1);
+
+        // Prefer the embedded text layer when there is one
+        if let Ok(text) = run_tool("pdftotext", &[arg(path), "-".into()]).await {
+            if !is_sparse(&text, pages) {
+                debug!("Using PDF text layer for {}", path.display());
+                return Ok(paged_text(&text));
+            }
+        }
+
+        debug!("OCR on scanned PDF {} ({} pages)", path.display(), pages);
+        let tmp = std::env::temp_dir().join(format!("localgpt-ocr-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&tmp)?;
+        let text = self.ocr_pdf_pages(path, &tmp, pages).await;
+        let _ = fs::remove_dir_all(&tmp);
+        text
+    }
+
+    async fn ocr_pdf_pages(&self, path: &Path, tmp: &Path, pages: usize) -> Result<String> {
+        let prefix = tmp.join("page");
+        run_tool(
+            "pdftoppm",
+            &[
+                "-r".into(),
+                "200".into(),
+                "-png".into(),
+                "-l".into(),
+                MAX_PDF_PAGES.to_string(),
+                arg(path),
+                arg(&prefix),
+            ],
+        )
+        .await?;
+
+        let mut images: Vec<PathBuf> = fs::read_dir(tmp)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+        images.sort();
+
+        let mut text = String::new();
+        for (i, image) in images.iter().enumerate() {
+            text.push_str(&format!("\n--- Page {} ---\n", i + 1));
+            text.push_str(&self.extract_image(image).await?);
+        }
+        if pages > MAX_PDF_PAGES {
+            text.push_str(&format!(
+                "\n[OCR stopped after {} of {} pages]\n",
+                MAX_PDF_PAGES, pages
+            ));
+        }
+        Ok(text)
+    }
+
+    async fn extract_image(&self, path: &Path) -> Result<String> {
+        match self.engine {
+            OcrEngine::Tesseract => {
+                run_tool(
+                    "tesseract",
+                    &[
+                        arg(path),
+                        "stdout".into(),
+                        "-l".into(),
+                        self.language.clone(),
+                    ],
+                )
+                .await
+            }
+            OcrEngine::Ollama => {
+                use base64::{engine::general_purpose::STANDARD, Engine as _};
+                let image = STANDARD.encode(fs::read(path)?);
+
+                let response = self
+                    .client
+                    .post(format!("{}/api/generate", self.ollama_endpoint))
+                    .json(&json!({
+                        "model": self.vision_model,
+                        "prompt": VISION_PROMPT,
+                        "images": [image],
+                        "stream": false,
+                    }))
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    anyhow::bail!("Ollama OCR error {}: {}", status, body);
+                }
+                let result: Value = response.json().await?;
+                Ok(result["response"].as_str().unwrap_or_default().to_string())
+            }
+        }
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+fn arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+async fn run_tool(program: &str, args: &[String]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run {} (is it installed?): {}", program, e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+async fn pdf_page_count(path: &Path) -> Option<usize> {
+    let info = run_tool("pdfinfo", &[arg(path)]).await.ok()?;
+    info.lines()
+        .find_map(|l| l.strip_prefix("Pages:"))
+        .and_then(|n| n.trim().parse().ok())
+}
+
+/// True when a PDF text layer is too thin to be the real content (i.e. a scan)
+fn is_sparse(text: &str, pages: usize) -> bool {
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    chars < MIN_CHARS_PER_PAGE * pages.max(1)
+}
+
+/// Write extracted text as a markdown note so the memory index picks it up
+fn save_note(dir: &Path, source: &Path, text: &str) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "document".to_string());
+    let path = dir.join(format!("{}.md", stem));
+    fs::write(&path, format_note(source, text))?;
+    Ok(path)
+}
+
+fn format_note(source: &Path, text: &str) -> String {
+    let name = source
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!(
+        "# OCR: {}\n\nSource: {}\nExtracted: {}\n\n{}\n",
+        name,
+        source.display(),
+        chrono::Local::now().format("%Y-%m-%d %H:%M"),
+        text.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_files_and_sparse_text() {
+        assert!(Ocr::supports(Path::new("scan.PDF")));
+        assert!(Ocr::supports(Path::new("shot.png")));
+        assert!(!Ocr::supports(Path::new("notes.md")));
+
+        // Page-number-only text layer is a scan; real text is not
+        assert!(is_sparse("\u{c}1\u{c}2\u{c}3", 3));
+        assert!(!is_sparse(&"Quarterly report ".repeat(10), 3));
+    }
+
+    #[test]
+    fn test_note_is_markdown() {
+        let note = format_note(Path::new("/tmp/receipt.jpg"), "  TOTAL 12.50\n");
+        assert!(note.starts_with("# OCR: receipt.jpg\n"));
+        assert!(note.contains("Source: /tmp/receipt.jpg"));
+        assert!(note.ends_with("TOTAL 12.50\n"));
+    }
+}
@@ -0,0 +1,321 @@
+//! Multiple Ollama hosts
+//!
+//! `providers.ollama.endpoint` plus any `[[providers.ollama.hosts]]` entries
+//! form a pool. A model named `llama3@gpu` always goes to the host called
+//! `gpu`; a plain `llama3` goes to the reachable host with the lowest
+//! latency among those that list (or report having) the model.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use reqwest::Client;
+use serde_json::Value;
+
+use super::providers::resolve_provider;
+use crate::config::{Config, OllamaConfig, OllamaHostConfig};
+
+/// How long to wait for a host to answer a probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Large models can take minutes to load from disk
+const WARM_UP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Result of probing one Ollama host
+#[derive(Debug, Clone)]
+pub struct OllamaHostStatus {
+    pub name: String,
+    pub endpoint: String,
+    /// Round trip of the model list request; None if unreachable
+    pub latency_ms: Option<u64>,
+    /// Models routed to this host (configured list, else installed models)
+    pub models: Vec<String>,
+    /// Models the host reports having installed
+    pub installed: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl OllamaHostStatus {
+    pub fn is_reachable(&self) -> bool {
+        self.latency_ms.is_some()
+    }
+
+    /// Whether `model` is routed to this host. An untagged name matches
+    /// any tag (`llama3` matches `llama3:latest` and `llama3:70b`).
+    pub fn serves(&self, model: &str) -> bool {
+        self.models.iter().any(|m| same_model(m, model))
+    }
+
+    /// Whether the host reported having `model` installed
+    pub fn has_installed(&self, model: &str) -> bool {
+        self.installed.iter().any(|m| same_model(m, model))
+    }
+}
+
+/// Model names match if equal, or if one has no tag and the base names match
+fn same_model(a: &str, b: &str) -> bool {
+    a == b
+        || (!b.contains(':') && a.split(':').next() == Some(b))
+        || (!a.contains(':') && b.split(':').next() == Some(a))
+}
+
+/// Readiness of the default local model, shown in the desktop toolbar
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalModelState {
+    /// No host answered
+    Unreachable(String),
+    /// The host is up but does not have the model (`ollama pull` needed)
+    NotInstalled,
+    /// Loading the model into memory
+    Warming,
+    /// Model is available; `loaded` if it was preloaded
+    Ready { loaded: bool },
+    /// Preloading failed
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalModelStatus {
+    pub model: String,
+    /// Host the model will be served from
+    pub host: String,
+    pub endpoint: String,
+    pub state: LocalModelState,
+}
+
+/// Split `model@host` into the model and host name
+pub fn split_model_host(model: &str) -> (&str, Option<&str>) {
+    match model.rsplit_once('@') {
+        Some((model, host)) if !model.is_empty() && !host.is_empty() => (model, Some(host)),
+        _ => (model, None),
+    }
+}
+
+/// Find a configured host by name
+pub fn find_host(config: &OllamaConfig, name: &str) -> Result<OllamaHostConfig> {
+    let hosts = config.all_hosts();
+    hosts
+        .iter()
+        .find(|host| host.name.eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown Ollama host '{}'. Configured hosts: {}",
+                name,
+                hosts
+                    .iter()
+                    .map(|host| host.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Ask a host for its installed models, timing the request
+pub async fn probe_host(client: &Client, host: &OllamaHostConfig) -> OllamaHostStatus {
+    let endpoint = host.endpoint.trim_end_matches('/').to_string();
+    let mut status = OllamaHostStatus {
+        name: host.name.clone(),
+        endpoint: endpoint.clone(),
+        latency_ms: None,
+        models: host.models.clone(),
+        installed: Vec::new(),
+        error: None,
+    };
+
+    let started = Instant::now();
+    let response = client
+        .get(format!("{}/api/tags", endpoint))
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    let body: Value = match response {
+        Ok(response) => match response.json().await {
+            Ok(body) => body,
+            Err(e) => {
+                status.error = Some(e.to_string());
+                return status;
+            }
+        },
+        Err(e) => {
+            status.error = Some(e.to_string());
+            return status;
+        }
+    };
+    status.latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    status.installed = body["models"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["name"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    if status.models.is_empty() {
+        status.models = status.installed.clone();
+    }
+    status
+}
+
+/// Probe every configured host concurrently
+pub async fn probe_hosts(config: &OllamaConfig) -> Vec<OllamaHostStatus> {
+    let client = Client::new();
+    let hosts = config.all_hosts();
+    futures::future::join_all(hosts.iter().map(|host| probe_host(&client, host))).await
+}
+
+/// Find the host for an Ollama model (`llama3` or `llama3@gpu`) and check
+/// that it is up and has the model
+pub async fn probe_local_model(config: &OllamaConfig, model: &str) -> LocalModelStatus {
+    let (model, pinned) = split_model_host(model);
+    let hosts = match pinned {
+        Some(name) => match find_host(config, name) {
+            Ok(host) => vec![host],
+            Err(e) => {
+                return LocalModelStatus {
+                    model: model.to_string(),
+                    host: name.to_string(),
+                    endpoint: String::new(),
+                    state: LocalModelState::Unreachable(e.to_string()),
+                }
+            }
+        },
+        None => config.all_hosts(),
+    };
+
+    let client = Client::new();
+    let statuses =
+        futures::future::join_all(hosts.iter().map(|host| probe_host(&client, host))).await;
+    let chosen = pick_host(&statuses, model)
+        .or_else(|| statuses.iter().find(|status| status.is_reachable()));
+
+    match chosen {
+        Some(status) => LocalModelStatus {
+            model: model.to_string(),
+            host: status.name.clone(),
+            endpoint: status.endpoint.clone(),
+            state: if status.has_installed(model) {
+                LocalModelState::Ready { loaded: false }
+            } else {
+                LocalModelState::NotInstalled
+            },
+        },
+        None => {
+            let first = &statuses[0];
+            LocalModelStatus {
+                model: model.to_string(),
+                host: first.name.clone(),
+                endpoint: first.endpoint.clone(),
+                state: LocalModelState::Unreachable(
+                    first
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| "no response".to_string()),
+                ),
+            }
+        }
+    }
+}
+
+/// Load the model into memory so the first message doesn't wait for it.
+/// An empty generate request makes Ollama load the model and keep it for
+/// `keep_alive`.
+pub async fn warm_up(status: &LocalModelStatus, keep_alive: &str) -> LocalModelState {
+    let result = Client::new()
+        .post(format!("{}/api/generate", status.endpoint))
+        .json(&serde_json::json!({
+            "model": status.model,
+            "keep_alive": keep_alive,
+        }))
+        .timeout(WARM_UP_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    match result {
+        Ok(_) => LocalModelState::Ready { loaded: true },
+        Err(e) => LocalModelState::Failed(e.to_string()),
+    }
+}
+
+/// Startup check for a model served by Ollama: find its host and, with
+/// `providers.ollama.warm_up`, load it into memory. Each step is passed to
+/// `report`. Does nothing for other providers.
+pub async fn prepare_local_model(
+    config: &Config,
+    model: &str,
+    mut report: impl FnMut(LocalModelStatus),
+) {
+    let Some(ref ollama) = config.providers.ollama else {
+        return;
+    };
+    let (provider, model) = resolve_provider(model, config);
+    if provider != "ollama" {
+        return;
+    }
+
+    let mut status = probe_local_model(ollama, &model).await;
+    report(status.clone());
+    if ollama.warm_up && status.state == (LocalModelState::Ready { loaded: false }) {
+        status.state = LocalModelState::Warming;
+        report(status.clone());
+        status.state = warm_up(&status, &ollama.keep_alive).await;
+        report(status);
+    }
+}
+
+/// Fastest reachable host serving `model`
+pub fn pick_host<'a>(
+    statuses: &'a [OllamaHostStatus],
+    model: &str,
+) -> Option<&'a OllamaHostStatus> {
+    statuses
+        .iter()
+        .filter(|status| status.is_reachable() && status.serves(model))
+        .min_by_key(|status| status.latency_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(name: &str, latency_ms: Option<u64>, models: &[&str]) -> OllamaHostStatus {
+        OllamaHostStatus {
+            name: name.to_string(),
+            endpoint: format!("http://{}:11434", name),
+            latency_ms,
+            models: models.iter().map(|m| m.to_string()).collect(),
+            installed: Vec::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_host_routing() {
+        assert_eq!(split_model_host("llama3@gpu"), ("llama3", Some("gpu")));
+        assert_eq!(split_model_host("llama3:70b"), ("llama3:70b", None));
+        assert_eq!(split_model_host("llama3@"), ("llama3@", None));
+
+        let statuses = vec![
+            status("default", Some(40), &["llama3:latest"]),
+            status("gpu", Some(5), &["llama3:70b", "qwen2.5-coder"]),
+            status("homelab", None, &["llama3:latest"]),
+        ];
+        assert_eq!(pick_host(&statuses, "llama3").unwrap().name, "gpu");
+        assert_eq!(
+            pick_host(&statuses, "llama3:latest").unwrap().name,
+            "default"
+        );
+        assert_eq!(
+            pick_host(&statuses, "qwen2.5-coder:latest").unwrap().name,
+            "gpu"
+        );
+        assert!(pick_host(&statuses, "mistral").is_none());
+
+        let mut gpu = statuses[1].clone();
+        gpu.installed = vec!["llama3:70b".to_string()];
+        assert!(gpu.has_installed("llama3"));
+        assert!(!gpu.has_installed("qwen2.5-coder"));
+    }
+}
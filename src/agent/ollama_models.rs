@@ -0,0 +1,215 @@
+//! Ollama model management: list, pull and delete models on a host
+//!
+//! Models are addressed like chat models: `llama3` uses the default host
+//! (`providers.ollama.endpoint`), `llama3@gpu` the host called `gpu`.
+
+use anyhow::Result;
+use futures::StreamExt;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::ollama_hosts::{find_host, split_model_host};
+use crate::config::{OllamaConfig, OllamaHostConfig, DEFAULT_OLLAMA_HOST};
+
+/// An installed model as reported by `/api/tags`
+#[derive(Debug, Clone)]
+pub struct OllamaModelInfo {
+    pub name: String,
+    /// Size on disk in bytes
+    pub size: u64,
+    pub modified_at: String,
+    /// e.g. "8.0B"
+    pub parameter_size: Option<String>,
+    /// e.g. "Q4_K_M"
+    pub quantization: Option<String>,
+}
+
+/// One progress update from `/api/pull`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OllamaPullProgress {
+    pub model: String,
+    pub host: String,
+    /// Ollama's step description ("pulling manifest", "pulling 6a0746a1ec1a", "success")
+    pub status: String,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+}
+
+impl OllamaPullProgress {
+    /// Fraction of the current layer downloaded, if known
+    pub fn fraction(&self) -> Option<f32> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => {
+                Some((completed as f32 / total as f32).min(1.0))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Host for `model` or `model@host`, and the bare model name
+pub fn ollama_model_host<'a>(
+    config: &OllamaConfig,
+    model: &'a str,
+) -> Result<(OllamaHostConfig, &'a str)> {
+    let (model, host) = split_model_host(model);
+    let host = find_host(config, host.unwrap_or(DEFAULT_OLLAMA_HOST))?;
+    Ok((host, model))
+}
+
+/// Models installed on a host, largest first
+pub async fn list_ollama_models(host: &OllamaHostConfig) -> Result<Vec<OllamaModelInfo>> {
+    let body: Value = Client::new()
+        .get(format!("{}/api/tags", endpoint(host)))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut models = parse_tags(&body);
+    models.sort_by_key(|m| std::cmp::Reverse(m.size));
+    Ok(models)
+}
+
+/// Download a model, passing each progress update to `report`
+pub async fn pull_ollama_model(
+    host: &OllamaHostConfig,
+    model: &str,
+    mut report: impl FnMut(OllamaPullProgress),
+) -> Result<()> {
+    let response = Client::new()
+        .post(format!("{}/api/pull", endpoint(host)))
+        .json(&json!({ "model": model, "stream": true }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        anyhow::bail!(
+            "Ollama pull failed ({}): {}",
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+
+    // Progress arrives as newline-delimited JSON
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    while let Some(chunk) = stream.next().await {
+        buffer.push_str(&String::from_utf8_lossy(&chunk?));
+        while let Some(pos) = buffer.find('\n') {
+            let line: String = buffer.drain(..=pos).collect();
+            let Ok(update) = serde_json::from_str::<Value>(line.trim()) else {
+                continue;
+            };
+            if let Some(error) = update["error"].as_str() {
+                anyhow::bail!("Ollama pull failed: {}", error);
+            }
+            report(OllamaPullProgress {
+                model: model.to_string(),
+                host: host.name.clone(),
+                status: update["status"].as_str().unwrap_or_default().to_string(),
+                completed: update["completed"].as_u64(),
+                total: update["total"].as_u64(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Remove a model from a host
+pub async fn delete_ollama_model(host: &OllamaHostConfig, model: &str) -> Result<()> {
+    let response = Client::new()
+        .delete(format!("{}/api/delete", endpoint(host)))
+        .json(&json!({ "model": model }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        anyhow::bail!(
+            "Failed to delete {} from {} ({}): {}",
+            model,
+            host.name,
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn endpoint(host: &OllamaHostConfig) -> &str {
+    host.endpoint.trim_end_matches('/')
+}
+
+fn parse_tags(body: &Value) -> Vec<OllamaModelInfo> {
+    body["models"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| {
+                    Some(OllamaModelInfo {
+                        name: m["name"].as_str()?.to_string(),
+                        size: m["size"].as_u64().unwrap_or(0),
+                        modified_at: m["modified_at"].as_str().unwrap_or_default().to_string(),
+                        parameter_size: m["details"]["parameter_size"].as_str().map(str::to_string),
+                        quantization: m["details"]["quantization_level"]
+                            .as_str()
+                            .map(str::to_string),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags_and_progress() {
+        let body = json!({
+            "models": [{
+                "name": "llama3.2:3b",
+                "size": 2019393189u64,
+                "modified_at": "2025-01-10T12:00:00Z",
+                "details": { "parameter_size": "3.2B", "quantization_level": "Q4_K_M" }
+            }, { "size": 1 }]
+        });
+        let models = parse_tags(&body);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "llama3.2:3b");
+        assert_eq!(models[0].quantization.as_deref(), Some("Q4_K_M"));
+
+        let mut progress = OllamaPullProgress {
+            model: "llama3.2:3b".to_string(),
+            host: "default".to_string(),
+            status: "pulling manifest".to_string(),
+            completed: None,
+            total: None,
+        };
+        assert_eq!(progress.fraction(), None);
+        progress.completed = Some(50);
+        progress.total = Some(200);
+        assert_eq!(progress.fraction(), Some(0.25));
+
+        let config = OllamaConfig {
+            endpoint: "http://localhost:11434".to_string(),
+            model: "llama3".to_string(),
+            hosts: vec![OllamaHostConfig {
+                name: "gpu".to_string(),
+                endpoint: "http://gpu:11434".to_string(),
+                models: Vec::new(),
+            }],
+            warm_up: false,
+            keep_alive: "30m".to_string(),
+            timeouts: Default::default(),
+            network: Default::default(),
+        };
+        let (host, model) = ollama_model_host(&config, "qwen2.5:7b@gpu").unwrap();
+        assert_eq!((host.name.as_str(), model), ("gpu", "qwen2.5:7b"));
+        let (host, _) = ollama_model_host(&config, "qwen2.5:7b").unwrap();
+        assert_eq!(host.name, DEFAULT_OLLAMA_HOST);
+        assert!(ollama_model_host(&config, "qwen2.5:7b@cpu").is_err());
+    }
+}
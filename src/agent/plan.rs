@@ -0,0 +1,202 @@
+//! Plan-then-execute mode
+//!
+//! `/plan <task>` asks the model for a list of steps instead of starting on
+//! the work. The user reviews the list (editing, dropping or adding steps)
+//! and runs it; each step is then an ordinary turn of the session that is
+//! shown the whole plan and told which step it is on. Frontends update the
+//! step statuses as turns finish. A failed or stopped step ends the run and
+//! leaves the remaining steps skipped.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+/// Longer plans are cut to this many steps
+pub const MAX_PLAN_STEPS: usize = 20;
+
+/// Instruction for the planning request
+pub(crate) const PLAN_PROMPT: &str = "Break the task below into the steps you will carry out, \
+one at a time, with your tools. Each step is one concrete action with a checkable result \
+(e.g. \"Read src/config.rs and list where timeouts are set\"). Don't start on the work. \
+Use as few steps as the task needs, at most 20.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Skipped,
+}
+
+impl StepStatus {
+    /// Checkbox-style marker for plan listings
+    pub fn marker(&self) -> &'static str {
+        match self {
+            StepStatus::Pending => "[ ]",
+            StepStatus::Running => "[>]",
+            StepStatus::Done => "[x]",
+            StepStatus::Failed => "[!]",
+            StepStatus::Skipped => "[-]",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStep {
+    pub description: String,
+    pub status: StepStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub task: String,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// A plan of pending steps; blank steps are dropped and the list is
+    /// cut to `MAX_PLAN_STEPS`
+    pub fn new(task: &str, steps: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            task: task.trim().to_string(),
+            steps: steps
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .take(MAX_PLAN_STEPS)
+                .map(|description| PlanStep {
+                    description,
+                    status: StepStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    /// JSON schema of the planning reply
+    pub(crate) fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "steps": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["steps"],
+            "additionalProperties": false
+        })
+    }
+
+    /// The plan in a planning reply (`{"steps": [...]}`)
+    pub(crate) fn from_reply(task: &str, reply: &Value) -> Result<Self> {
+        let steps = reply["steps"]
+            .as_array()
+            .map(|steps| {
+                steps
+                    .iter()
+                    .filter_map(|s| s.as_str().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let plan = Self::new(task, steps);
+        if plan.steps.is_empty() {
+            anyhow::bail!("The model returned no steps for this task");
+        }
+        Ok(plan)
+    }
+
+    /// Drop the steps left empty by editing
+    pub fn remove_blank_steps(&mut self) {
+        self.steps.retain(|s| !s.description.trim().is_empty());
+    }
+
+    /// First step still to run
+    pub fn next_pending(&self) -> Option<usize> {
+        self.steps
+            .iter()
+            .position(|s| s.status == StepStatus::Pending)
+    }
+
+    /// Mark every step not yet run as skipped (after a failure or stop)
+    pub fn skip_remaining(&mut self) {
+        for step in &mut self.steps {
+            if step.status == StepStatus::Pending {
+                step.status = StepStatus::Skipped;
+            }
+        }
+    }
+
+    /// Steps finished successfully
+    pub fn done_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|s| s.status == StepStatus::Done)
+            .count()
+    }
+
+    /// The steps as a numbered checklist
+    pub fn checklist(&self) -> String {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{} {}. {}", s.status.marker(), i + 1, s.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The user turn that carries out step `index`
+    pub fn step_message(&self, index: usize) -> String {
+        let mut plan = self.clone();
+        if let Some(step) = plan.steps.get_mut(index) {
+            step.status = StepStatus::Running;
+        }
+        format!(
+            "[Plan step {} of {}]\n\nTask: {}\n\nPlan:\n{}\n\nDo step {} now, and only that step: {}\n\
+             Then reply with a short summary of what you did and anything later steps need to know.",
+            index + 1,
+            self.steps.len(),
+            self.task,
+            plan.checklist(),
+            index + 1,
+            self.steps
+                .get(index)
+                .map(|s| s.description.as_str())
+                .unwrap_or_default()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_from_reply() {
+        let reply = json!({"steps": ["  Read the config ", "", "Change the timeout"]});
+        let plan = Plan::from_reply("Raise the timeout", &reply).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].description, "Read the config");
+        assert_eq!(plan.next_pending(), Some(0));
+
+        assert!(Plan::from_reply("Task", &json!({"steps": []})).is_err());
+        let long: Vec<String> = (0..30).map(|i| format!("Step {}", i)).collect();
+        assert_eq!(Plan::new("Task", long).steps.len(), MAX_PLAN_STEPS);
+    }
+
+    #[test]
+    fn test_step_progress() {
+        let mut plan = Plan::new(
+            "Rename the crate",
+            ["Find uses", "Rename", "Run the tests"].map(str::to_string),
+        );
+        plan.steps[0].status = StepStatus::Done;
+
+        let message = plan.step_message(1);
+        assert!(message.starts_with("[Plan step 2 of 3]"));
+        assert!(message.contains("[x] 1. Find uses\n[>] 2. Rename\n[ ] 3. Run the tests"));
+        assert!(message.contains("Do step 2 now, and only that step: Rename"));
+
+        plan.steps[1].status = StepStatus::Failed;
+        plan.skip_remaining();
+        assert_eq!(plan.steps[2].status, StepStatus::Skipped);
+        assert_eq!(plan.next_pending(), None);
+        assert_eq!(plan.done_count(), 1);
+    }
+}
@@ -2,16 +2,23 @@ use anyhow::Result;
 use async_trait::async_trait;
 use futures::Stream;
 use futures::StreamExt;
-use reqwest::Client;
+use reqwest::{Certificate, Client, NoProxy};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::process::Stdio;
-use std::sync::Mutex as StdMutex;
+use std::sync::{Arc, Mutex as StdMutex};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing::{debug, info};
 
-use crate::config::Config;
+use super::audit_log::{AuditLog, AuditedProvider};
+use super::codex_cli::CodexCliProvider;
+use super::ollama_hosts::{find_host, pick_host, probe_host, split_model_host};
+use super::replay::{MockProvider, RecordingProvider};
+use super::response_cache::{CachedProvider, ResponseCache};
+use super::retry::send_with_retry;
+use crate::config::{Config, NetworkConfig, OllamaHostConfig, RetryConfig, TimeoutConfig};
 
 /// Image attachment for multimodal messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,7 +51,7 @@ pub enum Role {
     Tool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
@@ -63,6 +70,10 @@ pub struct ToolSchema {
 pub struct Usage {
     pub input_tokens: u64,
     pub output_tokens: u64,
+    /// Output tokens the model spent reasoning before answering (already
+    /// counted in `output_tokens`; reported by OpenAI reasoning models)
+    #[serde(default)]
+    pub reasoning_tokens: u64,
 }
 
 impl Usage {
@@ -71,6 +82,90 @@ impl Usage {
     }
 }
 
+/// Per-session generation overrides (unset fields keep the provider defaults)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+}
+
+impl GenerationParams {
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none() && self.max_tokens.is_none()
+    }
+
+    /// Set a parameter from `/set key=value`; "default" clears it
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        let clear = value.eq_ignore_ascii_case("default");
+        match key.trim() {
+            "temperature" => {
+                self.temperature = if clear {
+                    None
+                } else {
+                    let t: f32 = value
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("temperature must be a number"))?;
+                    if !(0.0..=2.0).contains(&t) {
+                        anyhow::bail!("temperature must be between 0 and 2");
+                    }
+                    Some(t)
+                };
+            }
+            "max_tokens" => {
+                self.max_tokens = if clear {
+                    None
+                } else {
+                    match value.parse::<usize>() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => anyhow::bail!("max_tokens must be a positive integer"),
+                    }
+                };
+            }
+            other => anyhow::bail!(
+                "Unknown setting '{}'. Available: temperature, max_tokens",
+                other
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Sampling preset for regenerating a reply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegenerateStyle {
+    /// Same settings as the original turn
+    Same,
+    /// Higher temperature for more varied wording and ideas
+    Creative,
+    /// Low temperature for a focused, literal answer
+    Precise,
+}
+
+impl RegenerateStyle {
+    /// Temperature for the preset; None keeps the session setting
+    pub fn temperature(self) -> Option<f32> {
+        match self {
+            Self::Same => None,
+            Self::Creative => Some(1.1),
+            Self::Precise => Some(0.2),
+        }
+    }
+
+    /// Parse the argument of `/regenerate`
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.trim().to_lowercase().as_str() {
+            "" | "same" => Some(Self::Same),
+            "creative" => Some(Self::Creative),
+            "precise" => Some(Self::Precise),
+            _ => None,
+        }
+    }
+}
+
 pub struct LLMResponse {
     pub content: LLMResponseContent,
     pub usage: Option<Usage>,
@@ -117,6 +212,149 @@ pub struct StreamChunk {
     pub done: bool,
     /// Tool calls accumulated during streaming (only set when done=true)
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Pieces of tool calls received in this chunk, for providers that
+    /// stream them
+    pub tool_call_deltas: Vec<ToolCallDelta>,
+    /// Tools the provider ran itself, reported for display only
+    pub provider_tools: Vec<ProviderToolEvent>,
+    /// Model reasoning ("thinking") received in this chunk, kept apart from
+    /// the answer in `delta`
+    pub reasoning: String,
+    /// Token counts for the whole request, on the final chunk when the
+    /// provider reports them
+    pub usage: Option<Usage>,
+}
+
+/// A tool run by the provider rather than by LocalGPT (the Claude CLI's
+/// file edits, bash commands, ...). Its result never goes back to the model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderToolEvent {
+    Started {
+        id: String,
+        name: String,
+        detail: Option<String>,
+    },
+    Finished {
+        id: String,
+        name: String,
+        output: String,
+        is_error: bool,
+    },
+}
+
+/// Splits `<think>...</think>` reasoning out of streamed text, for models
+/// (DeepSeek R1, Qwen3, ...) that inline it in the answer
+#[derive(Debug, Default)]
+pub struct ThinkTagSplitter {
+    in_think: bool,
+    /// Text held back because it may be the start of a tag
+    pending: String,
+}
+
+impl ThinkTagSplitter {
+    const OPEN: &'static str = "<think>";
+    const CLOSE: &'static str = "</think>";
+
+    /// Returns (answer, reasoning) text from the next piece of the stream
+    pub fn push(&mut self, text: &str) -> (String, String) {
+        let mut input = std::mem::take(&mut self.pending);
+        input.push_str(text);
+        let (mut answer, mut reasoning) = (String::new(), String::new());
+
+        let mut rest = input.as_str();
+        loop {
+            let tag = if self.in_think {
+                Self::CLOSE
+            } else {
+                Self::OPEN
+            };
+            let out = if self.in_think {
+                &mut reasoning
+            } else {
+                &mut answer
+            };
+            if let Some(pos) = rest.find(tag) {
+                out.push_str(&rest[..pos]);
+                rest = &rest[pos + tag.len()..];
+                self.in_think = !self.in_think;
+                continue;
+            }
+            // Hold back a trailing prefix of the tag until more text arrives
+            let keep = (1..tag.len())
+                .rev()
+                .find(|&n| rest.ends_with(&tag[..n]))
+                .unwrap_or(0);
+            out.push_str(&rest[..rest.len() - keep]);
+            self.pending = rest[rest.len() - keep..].to_string();
+            break;
+        }
+        (answer, reasoning)
+    }
+
+    /// Text still held back at the end of the stream
+    pub fn finish(&mut self) -> (String, String) {
+        let pending = std::mem::take(&mut self.pending);
+        if self.in_think {
+            (String::new(), pending)
+        } else {
+            (pending, String::new())
+        }
+    }
+}
+
+/// Part of a tool call as it streams in. Deltas with the same `index` belong
+/// to one call; the id and name usually come with the first one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    /// Fragment of the JSON arguments
+    pub arguments: String,
+}
+
+/// Assembles streamed tool call deltas into complete calls
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: Vec<(usize, ToolCall)>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, delta: &ToolCallDelta) {
+        let pos = match self.calls.iter().position(|(i, _)| *i == delta.index) {
+            Some(pos) => pos,
+            None => {
+                self.calls.push((delta.index, ToolCall::default()));
+                self.calls.len() - 1
+            }
+        };
+        let call = &mut self.calls[pos].1;
+        if let Some(ref id) = delta.id {
+            call.id.clone_from(id);
+        }
+        if let Some(ref name) = delta.name {
+            call.name.push_str(name);
+        }
+        call.arguments.push_str(&delta.arguments);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Completed calls in stream order; empty arguments become `{}`
+    pub fn calls(&self) -> Vec<ToolCall> {
+        self.calls
+            .iter()
+            .map(|(_, call)| {
+                let mut call = call.clone();
+                if call.arguments.trim().is_empty() {
+                    call.arguments = "{}".to_string();
+                }
+                call
+            })
+            .collect()
+    }
 }
 
 /// Events emitted during streaming with tools
@@ -124,6 +362,8 @@ pub struct StreamChunk {
 pub enum StreamEvent {
     /// Text content chunk
     Content(String),
+    /// Reasoning ("thinking") chunk, shown apart from the answer
+    Reasoning(String),
     /// Tool call started
     ToolCallStart {
         name: String,
@@ -135,11 +375,35 @@ pub enum StreamEvent {
         name: String,
         id: String,
         output: String,
+        /// Short readable summary, for outputs over `tools.tool_output_summary_chars`
+        summary: Option<String>,
     },
+    /// A tool the provider ran itself started or finished
+    ProviderTool(ProviderToolEvent),
     /// Stream completed
     Done,
 }
 
+/// Default instruction for compaction summaries (`agent.compaction_prompt`)
+pub const DEFAULT_COMPACTION_PROMPT: &str =
+    "Summarize the following conversation concisely, preserving key information and context:";
+
+/// Instruction for pulling durable user facts out of a finished session
+/// (`memory.extract_facts`)
+pub const FACT_EXTRACTION_PROMPT: &str = "From the conversation below, list durable facts about the user worth remembering in future conversations: who they are, their preferences, projects, tools and recurring needs. Each fact is one short standalone sentence. Skip one-off requests, anything about the assistant, and facts already known. Return an empty list if there is nothing new.";
+
+/// Default instruction for session titles (`agent.title_prompt`)
+pub const DEFAULT_TITLE_PROMPT: &str =
+    "Write a short title (at most six words) for the following conversation. Reply with the title only, without quotes:";
+
+/// Instruction for summarizing long tool outputs for display
+pub const TOOL_OUTPUT_SUMMARY_PROMPT: &str =
+    "Summarize this tool output for the user in one short paragraph of plain prose: what it shows and anything notable such as errors, counts, or names. No lists or code blocks:";
+
+/// Instruction for condensing tool outputs too long for the context
+pub const TOOL_OUTPUT_CONDENSE_PROMPT: &str =
+    "Condense this tool output for an assistant that needs it to continue its task. Keep exact values it may need, such as file paths, line numbers, error messages, identifiers and numbers, and drop repetition. Reply with the condensed output only:";
+
 pub type StreamResult = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
 
 #[async_trait]
@@ -147,7 +411,53 @@ pub trait LLMProvider: Send + Sync {
     async fn chat(&self, messages: &[Message], tools: Option<&[ToolSchema]>)
         -> Result<LLMResponse>;
 
-    async fn summarize(&self, text: &str) -> Result<String>;
+    /// Summarize a conversation for compaction, using the default prompt
+    async fn summarize(&self, text: &str) -> Result<String> {
+        self.summarize_with(DEFAULT_COMPACTION_PROMPT, text).await
+    }
+
+    /// Run a summarization instruction (compaction, titling, ...) over text
+    async fn summarize_with(&self, prompt: &str, text: &str) -> Result<String> {
+        let messages = vec![Message {
+            role: Role::User,
+            content: format!("{}\n\n{}", prompt.trim_end(), text),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }];
+
+        match self.chat(&messages, None).await?.content {
+            LLMResponseContent::Text(summary) => Ok(summary),
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    /// Answer `prompt` with JSON matching `schema` (structured output).
+    /// By default the schema is given in the prompt and the reply parsed;
+    /// providers with native JSON-schema output override this.
+    async fn chat_json(&self, prompt: &str, schema: &Value) -> Result<Value> {
+        prompted_json(self, prompt, schema).await
+    }
+
+    /// Apply per-session generation overrides (ignored by providers without sampling controls)
+    fn set_generation_params(&mut self, _params: &GenerationParams) {}
+
+    /// Set how transient HTTP failures are retried (ignored by providers
+    /// that don't make HTTP requests)
+    fn set_retry_policy(&mut self, _policy: &RetryConfig) {}
+
+    /// Models this provider can serve, named as `/model` accepts them
+    /// (e.g. `openai/gpt-4o`). Empty when the provider can't tell.
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Check that the provider answers and accepts its credentials,
+    /// returning a short note on what it reported (default: list models)
+    async fn health_check(&self) -> Result<String> {
+        let models = self.list_models().await?;
+        Ok(format!("{} models", models.len()))
+    }
 
     /// Stream chat response (default: falls back to non-streaming)
     async fn chat_stream(
@@ -157,22 +467,29 @@ pub trait LLMProvider: Send + Sync {
     ) -> Result<StreamResult> {
         // Default implementation: single chunk with full response
         let resp = self.chat(messages, tools).await?;
+        let usage = resp.usage;
         match resp.content {
-            LLMResponseContent::Text(text) => {
-                Ok(Box::pin(futures::stream::once(async move {
-                    Ok(StreamChunk {
-                        delta: text,
-                        done: true,
-                        tool_calls: None,
-                    })
-                })))
-            }
+            LLMResponseContent::Text(text) => Ok(Box::pin(futures::stream::once(async move {
+                Ok(StreamChunk {
+                    delta: text,
+                    done: true,
+                    tool_calls: None,
+                    tool_call_deltas: Vec::new(),
+                    provider_tools: Vec::new(),
+                    reasoning: String::new(),
+                    usage,
+                })
+            }))),
             LLMResponseContent::ToolCalls(calls) => {
                 Ok(Box::pin(futures::stream::once(async move {
                     Ok(StreamChunk {
                         delta: String::new(),
                         done: true,
                         tool_calls: Some(calls),
+                        tool_call_deltas: Vec::new(),
+                        provider_tools: Vec::new(),
+                        reasoning: String::new(),
+                        usage,
                     })
                 })))
             }
@@ -180,6 +497,48 @@ pub trait LLMProvider: Send + Sync {
     }
 }
 
+/// `chat_json` for providers without native structured output: ask for
+/// JSON matching the schema and parse it out of the reply
+async fn prompted_json<P: LLMProvider + ?Sized>(
+    provider: &P,
+    prompt: &str,
+    schema: &Value,
+) -> Result<Value> {
+    let instruction = format!(
+        "Reply with only a JSON value matching this JSON Schema, without any other text:\n{}",
+        schema
+    );
+    let reply = provider.summarize_with(prompt, &instruction).await?;
+    parse_json_reply(&reply)
+}
+
+/// The single user message of a `chat_json` request
+fn json_request_message(prompt: &str) -> Message {
+    Message {
+        role: Role::User,
+        content: prompt.to_string(),
+        tool_calls: None,
+        tool_call_id: None,
+        images: Vec::new(),
+    }
+}
+
+/// The JSON value in a model reply, allowing for code fences or prose
+/// around it
+pub(crate) fn parse_json_reply(reply: &str) -> Result<Value> {
+    let reply = reply.trim();
+    if let Ok(value) = serde_json::from_str(reply) {
+        return Ok(value);
+    }
+    let start = reply.find(['{', '[']);
+    let end = reply.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&reply[start..=end])
+            .map_err(|e| anyhow::anyhow!("Reply is not valid JSON: {}", e)),
+        _ => anyhow::bail!("Reply contains no JSON"),
+    }
+}
+
 /// Resolve model alias to provider/model format (OpenClaw-compatible)
 fn resolve_model_alias(model: &str) -> String {
     // OpenClaw-compatible aliases
@@ -189,6 +548,10 @@ fn resolve_model_alias(model: &str) -> String {
         "sonnet" => "anthropic/claude-sonnet-4-5".to_string(),
         "gpt" => "openai/gpt-4o".to_string(),
         "gpt-mini" => "openai/gpt-4o-mini".to_string(),
+        "gemini" => "gemini/gemini-2.5-pro".to_string(),
+        "gemini-flash" => "gemini/gemini-2.5-flash".to_string(),
+        "deepseek" => "deepseek/deepseek-chat".to_string(),
+        "deepseek-r1" => "deepseek/deepseek-reasoner".to_string(),
         _ => model.to_string(),
     }
 }
@@ -210,30 +573,365 @@ fn normalize_model_id(provider: &str, model_id: &str) -> String {
     }
 }
 
-pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvider>> {
-    let workspace = config.workspace_path();
+/// A provider rejected its API key (missing, invalid, expired or revoked)
+///
+/// Returned inside `anyhow::Error` so callers can `downcast_ref` it and offer
+/// to replace the key instead of just showing the error text.
+#[derive(Debug, Clone)]
+pub struct ProviderAuthError {
+    /// Config section of the provider (`openai`, `anthropic`, `gemini`, `deepseek`)
+    pub provider: String,
+    /// Model the failed request was for, used to test a replacement key
+    pub model: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ProviderAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rejected the API key: {}",
+            self.provider, self.message
+        )
+    }
+}
+
+impl std::error::Error for ProviderAuthError {}
+
+/// A provider didn't connect or answer within its configured timeout
+///
+/// Returned inside `anyhow::Error` so callers can tell a hung or unreachable
+/// server apart from other failures.
+#[derive(Debug, Clone)]
+pub struct ProviderTimeoutError {
+    /// Config section of the provider (`openai`, `ollama`, a custom name, ...)
+    pub provider: String,
+    /// Whether the connection itself timed out (vs. waiting for a reply)
+    pub connect: bool,
+    /// The timeout that was exceeded
+    pub secs: u64,
+}
+
+impl std::fmt::Display for ProviderTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.connect {
+            write!(
+                f,
+                "could not connect to {} within {}s",
+                self.provider, self.secs
+            )
+        } else {
+            write!(f, "{} did not respond within {}s", self.provider, self.secs)
+        }
+    }
+}
+
+impl std::error::Error for ProviderTimeoutError {}
+
+/// HTTP client with a provider's timeouts, proxy and extra CA certificates
+pub(crate) fn http_client(timeouts: &TimeoutConfig, network: &NetworkConfig) -> Result<Client> {
+    Ok(http_client_builder(timeouts, network)?.build()?)
+}
+
+/// `http_client`'s builder, for callers that need more settings
+pub(crate) fn http_client_builder(
+    timeouts: &TimeoutConfig,
+    network: &NetworkConfig,
+) -> Result<reqwest::ClientBuilder> {
+    let mut builder = Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(
+            timeouts.connect_timeout_secs,
+        ))
+        .read_timeout(std::time::Duration::from_secs(timeouts.timeout_secs));
+
+    if let Some(url) = network.proxy.as_deref().filter(|u| !u.is_empty()) {
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy '{}': {}", url, e))?
+            .no_proxy(network.no_proxy.as_deref().and_then(NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = network.ca_cert.as_deref().filter(|p| !p.is_empty()) {
+        for cert in load_ca_certs(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    Ok(builder)
+}
+
+/// Certificates from a PEM file, which may hold a whole bundle
+fn load_ca_certs(path: &str) -> Result<Vec<Certificate>> {
+    let expanded = shellexpand::tilde(path).to_string();
+    let pem = std::fs::read(&expanded)
+        .map_err(|e| anyhow::anyhow!("Failed to read ca_cert {}: {}", expanded, e))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| anyhow::anyhow!("Invalid ca_cert {}: {}", expanded, e))?;
+    if certs.is_empty() {
+        anyhow::bail!("No PEM certificates found in ca_cert {}", expanded);
+    }
+    Ok(certs)
+}
+
+/// Turn a request timeout into `ProviderTimeoutError`, passing other errors through
+fn check_timeout(
+    provider: &str,
+    timeouts: &TimeoutConfig,
+    error: impl Into<anyhow::Error>,
+) -> anyhow::Error {
+    let error = error.into();
+    let timed_out = error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .find(|e| e.is_timeout());
+    match timed_out {
+        Some(e) => ProviderTimeoutError {
+            provider: provider.to_string(),
+            connect: e.is_connect(),
+            secs: if e.is_connect() {
+                timeouts.connect_timeout_secs
+            } else {
+                timeouts.timeout_secs
+            },
+        }
+        .into(),
+        None => error,
+    }
+}
+
+/// Error for a failed read partway through a streamed reply
+fn stream_error(provider: &str, timeouts: &TimeoutConfig, error: reqwest::Error) -> anyhow::Error {
+    if error.is_timeout() {
+        check_timeout(provider, timeouts, error)
+    } else {
+        anyhow::anyhow!("Stream error: {}", error)
+    }
+}
+
+/// Fail with `ProviderAuthError` if a response looks like an authentication error
+fn check_auth(provider: &str, model: &str, status: reqwest::StatusCode, body: &str) -> Result<()> {
+    const AUTH_MARKERS: [&str; 3] = ["invalid_api_key", "authentication_error", "API_KEY_INVALID"];
+    let rejected = status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+        || AUTH_MARKERS.iter().any(|marker| body.contains(marker));
+    if rejected {
+        return Err(ProviderAuthError {
+            provider: provider.to_string(),
+            model: format!("{}/{}", provider, model),
+            message: body.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Send a minimal request to check that the configured key for `model` works
+pub async fn check_api_key(model: &str, config: &Config) -> Result<()> {
+    let provider = create_provider(model, config)?;
+    let ping = Message {
+        role: Role::User,
+        content: "ping".to_string(),
+        tool_calls: None,
+        tool_call_id: None,
+        images: Vec::new(),
+    };
+    provider.chat(&[ping], None).await?;
+    Ok(())
+}
+
+/// Provider name and provider-side model ID for a model string, after
+/// resolving aliases (`"opus"` → `("anthropic", "claude-opus-4-5")`)
+pub fn resolve_provider(model: &str, config: &Config) -> (String, String) {
+    // User aliases ([models.aliases]) come before everything else
+    let model = config.models.resolve(model);
+
+    // Explicitly configured servers come before any guessing:
+    // "lmstudio/qwen2.5-7b", or a bare name listed in an entry's models
+    if let Some((name, model_id)) = model.split_once('/') {
+        if let Some(custom) = config.providers.find_custom(name) {
+            return (custom.name.clone(), model_id.to_string());
+        }
+    }
+    if let Some(custom) = config
+        .providers
+        .custom
+        .iter()
+        .find(|c| c.models.iter().any(|m| m == model))
+    {
+        return (custom.name.clone(), model.to_string());
+    }
 
     // Resolve aliases first (e.g., "opus" → "anthropic/claude-opus-4-5")
     let model = resolve_model_alias(model);
 
     // Parse provider/model format (OpenClaw-compatible)
-    let (provider, model_id) = if let Some(pos) = model.find('/') {
+    if model.ends_with(".gguf") && !model.starts_with("gguf/") {
+        // A bare model file, possibly given as a path
+        ("gguf".to_string(), model)
+    } else if let Some(pos) = model.find('/') {
         let (p, m) = model.split_at(pos);
         (p.to_lowercase(), m[1..].to_string()) // Skip the '/'
     } else if model.starts_with("gpt-") || model.starts_with("o1") {
-        ("openai".to_string(), model.clone())
+        ("openai".to_string(), model)
     } else if model.starts_with("claude-") {
-        ("anthropic".to_string(), model.clone())
+        ("anthropic".to_string(), model)
+    } else if model.starts_with("gemini-") {
+        ("gemini".to_string(), model)
+    } else if model.starts_with("deepseek-") {
+        ("deepseek".to_string(), model)
     } else {
         // Default to anthropic for unknown models, or ollama if configured
         if config.providers.ollama.is_some() {
-            ("ollama".to_string(), model.clone())
+            ("ollama".to_string(), model)
         } else if config.providers.anthropic.is_some() {
-            ("anthropic".to_string(), model.clone())
+            ("anthropic".to_string(), model)
         } else {
-            ("unknown".to_string(), model.clone())
+            ("unknown".to_string(), model)
+        }
+    }
+}
+
+/// Provider names `<provider>/<model>` accepts, besides custom servers
+const PROVIDER_PREFIXES: &[&str] = &[
+    "anthropic",
+    "openai",
+    "gemini",
+    "google",
+    "deepseek",
+    "ollama",
+    "gguf",
+    "mock",
+    "claude-cli",
+    "codex-cli",
+];
+
+/// Whether `name` clearly names a model, without asking any server: an
+/// alias, `<provider>/<model>` for a known or configured provider, a model
+/// file, an Ollama `name:tag`, a custom server's model, or a name in a
+/// provider's family (`gpt-*`, `claude-*`, ...)
+pub fn is_model_name(name: &str, config: &Config) -> bool {
+    if config.models.aliases.contains_key(name) || resolve_model_alias(name) != name {
+        return true;
+    }
+    if let Some((provider, _)) = name.split_once('/') {
+        return PROVIDER_PREFIXES.contains(&provider.to_lowercase().as_str())
+            || config.providers.find_custom(provider).is_some();
+    }
+    if config
+        .providers
+        .custom
+        .iter()
+        .any(|c| c.models.iter().any(|m| m == name))
+    {
+        return true;
+    }
+    name.ends_with(".gguf")
+        || name.contains(':')
+        || ["gpt-", "o1", "o3", "o4", "claude-", "gemini-", "deepseek-"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvider>> {
+    let model = config.models.resolve(model);
+    let replay = &config.providers.replay;
+    if let Some(ref path) = replay.replay {
+        return Ok(Box::new(MockProvider::replay(path, model)?));
+    }
+    let mut provider = build_provider(model, config)?;
+    provider.set_retry_policy(&config.providers.retry);
+    if let Some(ref path) = replay.record {
+        provider = Box::new(RecordingProvider::new(provider, model, path));
+    }
+    // Audit inside the cache so only real requests are logged
+    if config.providers.audit.enabled {
+        let log = AuditLog::open(&config.providers.audit, config.providers.api_keys())?;
+        provider = Box::new(AuditedProvider::new(provider, model, log));
+    }
+    if config.providers.cache.enabled {
+        let cache = ResponseCache::open(&config.providers.cache)?;
+        provider = Box::new(CachedProvider::new(provider, model, cache));
+    }
+    Ok(provider)
+}
+
+/// Models offered by one configured provider, for the `/model` picker
+#[derive(Debug, Clone)]
+pub struct ProviderModels {
+    /// Config section (`openai`, `ollama`, a custom server's name, ...)
+    pub provider: String,
+    /// Names `/model` accepts, e.g. `openai/gpt-4o`
+    pub models: Vec<String>,
+    /// Why the provider couldn't list its models
+    pub error: Option<String>,
+}
+
+/// Longest wait for one provider's model list
+const LIST_MODELS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Ask every configured provider which models it offers
+pub async fn list_available_models(config: &Config) -> Vec<ProviderModels> {
+    let providers = &config.providers;
+    let mut sections: Vec<String> = [
+        ("openai", providers.openai.is_some()),
+        ("anthropic", providers.anthropic.is_some()),
+        ("gemini", providers.gemini.is_some()),
+        ("deepseek", providers.deepseek.is_some()),
+        ("ollama", providers.ollama.is_some()),
+        ("claude-cli", providers.claude_cli.is_some()),
+        ("codex-cli", providers.codex_cli.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, configured)| *configured)
+    .map(|(section, _)| section.to_string())
+    .collect();
+    sections.extend(providers.custom.iter().map(|c| c.name.clone()));
+
+    futures::future::join_all(sections.into_iter().map(|section| async move {
+        // Listing doesn't use the model, so build the provider without one
+        let result = match create_provider(&format!("{}/", section), config) {
+            Ok(provider) => tokio::time::timeout(LIST_MODELS_TIMEOUT, provider.list_models())
+                .await
+                .unwrap_or_else(|_| anyhow::bail!("no answer within {:?}", LIST_MODELS_TIMEOUT)),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(models) => ProviderModels {
+                provider: section,
+                models,
+                error: None,
+            },
+            Err(e) => ProviderModels {
+                provider: section,
+                models: Vec::new(),
+                error: Some(e.to_string()),
+            },
         }
+    }))
+    .await
+}
+
+fn build_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvider>> {
+    let workspace = config.workspace_path();
+    let (provider, model_id) = resolve_provider(model, config);
+
+    // An unset ${VAR} or missing keyring entry behind the key
+    let section = if provider == "google" {
+        "gemini"
+    } else {
+        &provider
     };
+    if let Some(error) = config.api_key_error(section) {
+        anyhow::bail!("{}", error);
+    }
+
+    if let Some(custom) = config.providers.find_custom(&provider) {
+        return Ok(Box::new(
+            OpenAIProvider::custom(&custom.name, &custom.api_key, &custom.base_url, &model_id)?
+                .with_timeouts(&custom.timeouts)?
+                .with_network(&custom.network)?,
+        ));
+    }
 
     match provider.as_str() {
         "anthropic" => {
@@ -247,12 +945,17 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
             })?;
 
             let full_model = normalize_model_id("anthropic", &model_id);
-            Ok(Box::new(AnthropicProvider::new(
-                &anthropic_config.api_key,
-                &anthropic_config.base_url,
-                &full_model,
-                config.agent.max_tokens,
-            )?))
+            Ok(Box::new(
+                AnthropicProvider::new(
+                    &anthropic_config.api_key,
+                    &anthropic_config.base_url,
+                    &full_model,
+                    config.agent.max_tokens,
+                )?
+                .with_timeouts(&anthropic_config.timeouts)?
+                .with_network(&anthropic_config.network)?
+                .with_thinking(anthropic_config.thinking_budget_tokens),
+            ))
         }
 
         "openai" => {
@@ -265,13 +968,72 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
                 )
             })?;
 
-            Ok(Box::new(OpenAIProvider::new(
-                &openai_config.api_key,
-                &openai_config.base_url,
+            Ok(Box::new(
+                OpenAIProvider::new(&openai_config.api_key, &openai_config.base_url, &model_id)?
+                    .with_timeouts(&openai_config.timeouts)?
+                    .with_network(&openai_config.network)?
+                    .with_reasoning_effort(openai_config.reasoning_effort.as_deref()),
+            ))
+        }
+
+        "gemini" | "google" => {
+            let gemini_config = config.providers.gemini.as_ref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Gemini provider not configured.\n\
+                    Add to ~/.localgpt/config.toml:\n\n\
+                    [providers.gemini]\n\
+                    api_key = \"${{GEMINI_API_KEY}}\""
+                )
+            })?;
+
+            Ok(Box::new(
+                GeminiProvider::new(&gemini_config.api_key, &gemini_config.base_url, &model_id)?
+                    .with_timeouts(&gemini_config.timeouts)?
+                    .with_network(&gemini_config.network)?,
+            ))
+        }
+
+        "deepseek" => {
+            let deepseek_config = config.providers.deepseek.as_ref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "DeepSeek provider not configured.\n\
+                    Add to ~/.localgpt/config.toml:\n\n\
+                    [providers.deepseek]\n\
+                    api_key = \"${{DEEPSEEK_API_KEY}}\""
+                )
+            })?;
+
+            Ok(Box::new(
+                OpenAIProvider::deepseek(
+                    &deepseek_config.api_key,
+                    &deepseek_config.base_url,
+                    &model_id,
+                )?
+                .with_timeouts(&deepseek_config.timeouts)?
+                .with_network(&deepseek_config.network)?,
+            ))
+        }
+
+        #[cfg(feature = "gguf")]
+        "gguf" => {
+            let gguf_config = config.providers.gguf.clone().unwrap_or_default();
+            Ok(Box::new(super::gguf::GgufProvider::new(
                 &model_id,
+                &gguf_config,
+                config.agent.max_tokens,
             )?))
         }
 
+        #[cfg(not(feature = "gguf"))]
+        "gguf" => anyhow::bail!(
+            "GGUF model '{}' needs in-process llama.cpp support, which this build doesn't include.\n\
+            Rebuild with: cargo install localgpt --features gguf\n\
+            Or serve the model with Ollama and use ollama/<model>.",
+            model_id
+        ),
+
+        "mock" => Ok(Box::new(MockProvider::open(&model_id)?)),
+
         "claude-cli" => {
             let cli_config = config.providers.claude_cli.as_ref();
             let command = cli_config.map(|c| c.command.as_str()).unwrap_or("claude");
@@ -280,6 +1042,21 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
             )?))
         }
 
+        "codex-cli" => {
+            let cli_config = config.providers.codex_cli.clone().unwrap_or_default();
+            let model = if model_id.is_empty() {
+                &cli_config.model
+            } else {
+                &model_id
+            };
+            Ok(Box::new(CodexCliProvider::new(
+                &cli_config.command,
+                model,
+                &cli_config.sandbox,
+                workspace,
+            )?))
+        }
+
         "ollama" => {
             let ollama_config = config.providers.ollama.as_ref().ok_or_else(|| {
                 anyhow::anyhow!(
@@ -290,10 +1067,20 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
                 )
             })?;
 
-            Ok(Box::new(OllamaProvider::new(
-                &ollama_config.endpoint,
-                &model_id,
-            )?))
+            let provider = match split_model_host(&model_id) {
+                (model_id, Some(host)) => {
+                    let host = find_host(ollama_config, host)?;
+                    OllamaProvider::new(&host.endpoint, model_id)?
+                }
+                (model_id, None) => {
+                    OllamaProvider::with_hosts(ollama_config.all_hosts(), model_id)?
+                }
+            };
+            Ok(Box::new(
+                provider
+                    .with_timeouts(&ollama_config.timeouts)?
+                    .with_network(&ollama_config.network)?,
+            ))
         }
 
         _ => {
@@ -311,9 +1098,15 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
                 Supported formats (OpenClaw-compatible):\n  \
                 - anthropic/claude-opus-4-5, anthropic/claude-sonnet-4-5\n  \
                 - openai/gpt-4o, openai/gpt-4o-mini\n  \
+                - gemini/gemini-2.5-pro, gemini/gemini-2.5-flash\n  \
+                - deepseek/deepseek-chat, deepseek/deepseek-reasoner\n  \
+                - <name>/<model> for a [[providers.custom]] server\n  \
                 - claude-cli/opus, claude-cli/sonnet\n  \
-                - ollama/llama3, ollama/mistral\n\n\
-                Or use aliases: opus, sonnet, haiku, gpt, gpt-mini",
+                - codex-cli/gpt-5-codex (OpenAI Codex CLI)\n  \
+                - ollama/llama3, ollama/mistral\n  \
+                - gguf/<file>.gguf (in-process llama.cpp, --features gguf)\n  \
+                - mock/<fixture>.jsonl (canned replies for tests and demos)\n\n\
+                Or use aliases: opus, sonnet, haiku, gpt, gpt-mini, gemini, gemini-flash, deepseek, deepseek-r1",
                 provider,
                 model
             )
@@ -324,21 +1117,95 @@ pub fn create_provider(model: &str, config: &Config) -> Result<Box<dyn LLMProvid
 // OpenAI Provider
 pub struct OpenAIProvider {
     client: Client,
+    /// Config section the key comes from (`openai`, `deepseek`, or the name
+    /// of a `[[providers.custom]]` entry)
+    provider: String,
+    /// Name used in error messages
+    label: String,
     api_key: String,
     base_url: String,
     model: String,
+    params: GenerationParams,
+    /// `reasoning_effort` sent to reasoning models
+    reasoning_effort: Option<String>,
+    retry: RetryConfig,
+    timeouts: TimeoutConfig,
+    network: NetworkConfig,
 }
 
 impl OpenAIProvider {
     pub fn new(api_key: &str, base_url: &str, model: &str) -> Result<Self> {
+        let timeouts = TimeoutConfig::default();
         Ok(Self {
-            client: Client::new(),
+            client: http_client(&timeouts, &NetworkConfig::default())?,
+            provider: "openai".to_string(),
+            label: "OpenAI".to_string(),
             api_key: api_key.to_string(),
             base_url: base_url.to_string(),
             model: model.to_string(),
+            params: GenerationParams::default(),
+            reasoning_effort: None,
+            retry: RetryConfig::default(),
+            timeouts,
+            network: NetworkConfig::default(),
+        })
+    }
+
+    /// Use `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Result<Self> {
+        self.client = http_client(timeouts, &self.network)?;
+        self.timeouts = timeouts.clone();
+        Ok(self)
+    }
+
+    /// Connect through `network`'s proxy and trust its CA certificates
+    pub fn with_network(mut self, network: &NetworkConfig) -> Result<Self> {
+        self.client = http_client(&self.timeouts, network)?;
+        self.network = network.clone();
+        Ok(self)
+    }
+
+    /// How hard reasoning models think ("low", "medium", "high"); unset
+    /// leaves the API default. Ignored by other models.
+    pub fn with_reasoning_effort(mut self, effort: Option<&str>) -> Self {
+        self.reasoning_effort = effort.map(str::to_string);
+        self
+    }
+
+    /// DeepSeek speaks the OpenAI API. deepseek-reasoner streams its chain
+    /// of thought as `reasoning_content`, which lands in
+    /// `StreamChunk::reasoning` rather than the answer.
+    pub fn deepseek(api_key: &str, base_url: &str, model: &str) -> Result<Self> {
+        Ok(Self {
+            provider: "deepseek".to_string(),
+            label: "DeepSeek".to_string(),
+            ..Self::new(api_key, base_url, model)?
+        })
+    }
+
+    /// A server from `[[providers.custom]]` (LM Studio, vLLM, LiteLLM,
+    /// llama.cpp server, ...). An empty key sends no Authorization header.
+    pub fn custom(name: &str, api_key: &str, base_url: &str, model: &str) -> Result<Self> {
+        Ok(Self {
+            provider: name.to_string(),
+            label: name.to_string(),
+            ..Self::new(api_key, base_url.trim_end_matches('/'), model)?
         })
     }
 
+    /// POST to the chat completions endpoint
+    fn post_chat(&self) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Content-Type", "application/json");
+        if self.api_key.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("Bearer {}", self.api_key))
+        }
+    }
+
     fn format_tools(&self, tools: &[ToolSchema]) -> Vec<Value> {
         tools
             .iter()
@@ -356,10 +1223,13 @@ impl OpenAIProvider {
     }
 
     fn format_messages(&self, messages: &[Message]) -> Vec<Value> {
+        let reasoning = is_openai_reasoning_model(&self.model);
         messages
             .iter()
             .map(|m| {
                 let role = match m.role {
+                    // Reasoning models take instructions as developer messages
+                    Role::System if reasoning => "developer",
                     Role::System => "system",
                     Role::User => "user",
                     Role::Assistant => "assistant",
@@ -422,38 +1292,105 @@ impl OpenAIProvider {
             })
             .collect()
     }
-}
 
-#[async_trait]
-impl LLMProvider for OpenAIProvider {
-    async fn chat(
-        &self,
-        messages: &[Message],
-        tools: Option<&[ToolSchema]>,
-    ) -> Result<LLMResponse> {
+    fn request_body(&self, messages: &[Message], tools: Option<&[ToolSchema]>) -> Value {
         let mut body = json!({
             "model": self.model,
             "messages": self.format_messages(messages)
         });
+        if is_openai_reasoning_model(&self.model) {
+            // No sampling parameters, and max_tokens is replaced by a limit
+            // that also covers reasoning tokens
+            if let Some(max_tokens) = self.params.max_tokens {
+                body["max_completion_tokens"] = json!(max_tokens);
+            }
+            if let Some(ref effort) = self.reasoning_effort {
+                body["reasoning_effort"] = json!(effort);
+            }
+        } else {
+            if let Some(temperature) = self.params.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            if let Some(max_tokens) = self.params.max_tokens {
+                body["max_tokens"] = json!(max_tokens);
+            }
+        }
 
         if let Some(tools) = tools {
             if !tools.is_empty() {
                 body["tools"] = json!(self.format_tools(tools));
             }
         }
+        body
+    }
+}
 
-        debug!("OpenAI request: {}", serde_json::to_string_pretty(&body)?);
+/// OpenAI reasoning models (o1, o3-mini, o4-mini, ...), which reject
+/// `system` messages, `max_tokens` and sampling parameters
+fn is_openai_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+/// Token counts from an OpenAI `usage` object
+fn openai_usage(usage: &Value) -> Option<Usage> {
+    usage.is_object().then(|| Usage {
+        input_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+        output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        reasoning_tokens: usage["completion_tokens_details"]["reasoning_tokens"]
+            .as_u64()
+            .unwrap_or(0),
+    })
+}
+
+/// Tool call fragments in an OpenAI streaming chunk
+/// (`choices[0].delta.tool_calls`)
+fn openai_tool_call_deltas(delta: &Value) -> Vec<ToolCallDelta> {
+    delta["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|tc| ToolCallDelta {
+                    index: tc["index"].as_u64().unwrap_or(0) as usize,
+                    id: tc["id"].as_str().map(|s| s.to_string()),
+                    name: tc["function"]["name"].as_str().map(|s| s.to_string()),
+                    arguments: tc["function"]["arguments"]
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let body = self.request_body(messages, tools);
+
+        debug!("OpenAI request: {}", serde_json::to_string_pretty(&body)?);
 
-        let response_body: Value = response.json().await?;
+        let response = send_with_retry(&self.retry, &self.label, self.post_chat().json(&body))
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+
+        let status = response.status();
+        let response_body: Value = response
+            .json()
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+        check_auth(
+            &self.provider,
+            &self.model,
+            status,
+            &response_body.to_string(),
+        )?;
         debug!(
             "OpenAI response: {}",
             serde_json::to_string_pretty(&response_body)?
@@ -461,7 +1398,7 @@ impl LLMProvider for OpenAIProvider {
 
         // Check for errors
         if let Some(error) = response_body.get("error") {
-            anyhow::bail!("OpenAI API error: {}", error);
+            anyhow::bail!("{} API error: {}", self.label, error);
         }
 
         let choice = response_body["choices"]
@@ -471,10 +1408,7 @@ impl LLMProvider for OpenAIProvider {
         let message = &choice["message"];
 
         // Parse usage
-        let usage = response_body.get("usage").map(|u| Usage {
-            input_tokens: u["prompt_tokens"].as_u64().unwrap_or(0),
-            output_tokens: u["completion_tokens"].as_u64().unwrap_or(0),
-        });
+        let usage = openai_usage(&response_body["usage"]);
 
         // Check for tool calls
         if let Some(tool_calls) = message.get("tool_calls") {
@@ -508,22 +1442,194 @@ impl LLMProvider for OpenAIProvider {
         })
     }
 
-    async fn summarize(&self, text: &str) -> Result<String> {
-        let messages = vec![Message {
-            role: Role::User,
-            content: format!(
-                "Summarize the following conversation concisely, preserving key information and context:\n\n{}",
-                text
-            ),
-            tool_calls: None,
-            tool_call_id: None,
-            images: Vec::new(),
-        }];
+    async fn chat_json(&self, prompt: &str, schema: &Value) -> Result<Value> {
+        let mut body = self.request_body(&[json_request_message(prompt)], None);
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema }
+        });
 
-        match self.chat(&messages, None).await?.content {
-            LLMResponseContent::Text(summary) => Ok(summary),
-            _ => anyhow::bail!("Unexpected response type"),
+        let response = send_with_retry(&self.retry, &self.label, self.post_chat().json(&body))
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+        let response_body: Value = response
+            .json()
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+
+        match response_body["choices"][0]["message"]["content"].as_str() {
+            Some(content) if response_body.get("error").is_none() => parse_json_reply(content),
+            // OpenAI-compatible servers without json_schema support
+            _ => {
+                debug!(
+                    "{} structured output failed ({}), asking in the prompt",
+                    self.label, response_body["error"]
+                );
+                prompted_json(self, prompt, schema).await
+            }
+        }
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.retry = policy.clone();
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let mut request = self.client.get(format!("{}/models", self.base_url));
+        if !self.api_key.is_empty() {
+            request = request.header("Authorization", format!("Bearer {}", self.api_key));
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_default();
+            check_auth(&self.provider, &self.model, status, &error_body)?;
+            anyhow::bail!(
+                "{} model list failed ({}): {}",
+                self.label,
+                status,
+                error_body
+            );
         }
+        let body: Value = response.json().await?;
+        Ok(openai_model_names(&self.provider, &body))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let mut body = self.request_body(messages, tools);
+        body["stream"] = json!(true);
+        // Usage comes in a last chunk only when asked for. Custom servers
+        // may reject the option, but many send usage anyway.
+        if matches!(self.provider.as_str(), "openai" | "deepseek") {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        debug!(
+            "OpenAI streaming request: {}",
+            serde_json::to_string_pretty(&body)?
+        );
+
+        let response = send_with_retry(&self.retry, &self.label, self.post_chat().json(&body))
+            .await
+            .map_err(|e| check_timeout(&self.provider, &self.timeouts, e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = response.text().await?;
+            check_auth(&self.provider, &self.model, status, &error_body)?;
+            anyhow::bail!("{} API error: {}", self.label, error_body);
+        }
+
+        // Server-Sent Events; tool call arguments arrive in fragments
+        let label = self.label.clone();
+        let provider = self.provider.clone();
+        let timeouts = self.timeouts.clone();
+        let stream = async_stream::stream! {
+            let mut byte_stream = response.bytes_stream();
+            let mut buffer = String::new();
+            let mut pending_tool_calls = ToolCallAccumulator::default();
+            let mut usage = None;
+            let mut finished = false;
+
+            while let Some(chunk) = byte_stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+                        while let Some(pos) = buffer.find('\n') {
+                            let line = buffer[..pos].trim().to_string();
+                            buffer = buffer[pos + 1..].to_string();
+
+                            let Some(data) = line.strip_prefix("data:") else {
+                                continue;
+                            };
+                            let data = data.trim();
+                            if data == "[DONE]" {
+                                if !finished {
+                                    finished = true;
+                                    yield Ok(StreamChunk {
+                                        delta: String::new(),
+                                        done: true,
+                                        tool_calls: (!pending_tool_calls.is_empty())
+                                            .then(|| pending_tool_calls.calls()),
+                                        tool_call_deltas: Vec::new(),
+                                        provider_tools: Vec::new(),
+                                        reasoning: String::new(),
+                                        usage: usage.take(),
+                                    });
+                                }
+                                continue;
+                            }
+
+                            let Ok(json) = serde_json::from_str::<Value>(data) else {
+                                continue;
+                            };
+                            if let Some(error) = json.get("error") {
+                                yield Err(anyhow::anyhow!("{} API error: {}", label, error));
+                                continue;
+                            }
+                            if let Some(u) = openai_usage(&json["usage"]) {
+                                usage = Some(u);
+                            }
+
+                            let delta = &json["choices"][0]["delta"];
+                            let tool_call_deltas = openai_tool_call_deltas(delta);
+                            for d in &tool_call_deltas {
+                                pending_tool_calls.push(d);
+                            }
+                            let content = delta["content"].as_str().unwrap_or("").to_string();
+                            // DeepSeek uses reasoning_content; vLLM and OpenRouter use reasoning
+                            let reasoning = delta["reasoning_content"]
+                                .as_str()
+                                .or_else(|| delta["reasoning"].as_str())
+                                .unwrap_or("")
+                                .to_string();
+                            if !content.is_empty() || !reasoning.is_empty() || !tool_call_deltas.is_empty() {
+                                yield Ok(StreamChunk {
+                                    delta: content,
+                                    done: false,
+                                    tool_calls: None,
+                                    tool_call_deltas,
+                                    provider_tools: Vec::new(),
+                                    reasoning,
+                                    usage: None,
+                                });
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(stream_error(&provider, &timeouts, e));
+                        break;
+                    }
+                }
+            }
+
+            // Some compatible servers close the stream without [DONE]
+            if !finished {
+                yield Ok(StreamChunk {
+                    delta: String::new(),
+                    done: true,
+                    tool_calls: (!pending_tool_calls.is_empty()).then(|| pending_tool_calls.calls()),
+                    tool_call_deltas: Vec::new(),
+                    provider_tools: Vec::new(),
+                    reasoning: String::new(),
+                    usage,
+                });
+            }
+        };
+
+        Ok(Box::pin(stream))
     }
 }
 
@@ -534,19 +1640,74 @@ pub struct AnthropicProvider {
     base_url: String,
     model: String,
     max_tokens: usize,
+    params: GenerationParams,
+    /// Extended thinking budget in tokens (0 = off)
+    thinking_budget: usize,
+    /// Thinking blocks of recent tool-use turns, keyed by the turn's first
+    /// tool call ID. The API wants them back when the turn continues.
+    thinking_blocks: Arc<StdMutex<HashMap<String, Vec<Value>>>>,
+    retry: RetryConfig,
+    timeouts: TimeoutConfig,
+    network: NetworkConfig,
 }
 
 impl AnthropicProvider {
     pub fn new(api_key: &str, base_url: &str, model: &str, max_tokens: usize) -> Result<Self> {
+        let timeouts = TimeoutConfig::default();
         Ok(Self {
-            client: Client::new(),
+            client: http_client(&timeouts, &NetworkConfig::default())?,
             api_key: api_key.to_string(),
             base_url: base_url.to_string(),
             model: model.to_string(),
             max_tokens,
+            params: GenerationParams::default(),
+            thinking_budget: 0,
+            thinking_blocks: Arc::default(),
+            retry: RetryConfig::default(),
+            timeouts,
+            network: NetworkConfig::default(),
         })
     }
 
+    /// Enable extended thinking with a token budget (0 = off). The API's
+    /// minimum budget is 1024 tokens.
+    pub fn with_thinking(mut self, budget_tokens: usize) -> Self {
+        self.thinking_budget = if budget_tokens == 0 {
+            0
+        } else {
+            budget_tokens.max(MIN_THINKING_BUDGET)
+        };
+        self
+    }
+
+    /// Use `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Result<Self> {
+        self.client = http_client(timeouts, &self.network)?;
+        self.timeouts = timeouts.clone();
+        Ok(self)
+    }
+
+    /// Connect through `network`'s proxy and trust its CA certificates
+    pub fn with_network(mut self, network: &NetworkConfig) -> Result<Self> {
+        self.client = http_client(&self.timeouts, network)?;
+        self.network = network.clone();
+        Ok(self)
+    }
+
+    /// Send a request body to the Messages API
+    async fn post_messages(&self, body: &Value) -> Result<reqwest::Response> {
+        let request = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("Content-Type", "application/json")
+            .json(body);
+        send_with_retry(&self.retry, "Anthropic", request)
+            .await
+            .map_err(|e| check_timeout("anthropic", &self.timeouts, e))
+    }
+
     fn format_tools(&self, tools: &[ToolSchema]) -> Vec<Value> {
         tools
             .iter()
@@ -560,7 +1721,70 @@ impl AnthropicProvider {
             .collect()
     }
 
-    fn format_messages(&self, messages: &[Message]) -> (Option<String>, Vec<Value>) {
+    /// Whether this request asks for thinking. A tool-use turn can only
+    /// continue with thinking on if its thinking blocks were kept (they
+    /// aren't after switching models or resuming a session), so thinking
+    /// pauses until the next user turn in that case.
+    fn thinking_enabled(&self, messages: &[Message]) -> bool {
+        if self.thinking_budget == 0 {
+            return false;
+        }
+        let last_reply = messages.iter().rev().find(|m| m.role == Role::Assistant);
+        match last_reply.and_then(|m| m.tool_calls.as_ref()?.first()) {
+            Some(call) => self
+                .thinking_blocks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains_key(&call.id),
+            None => true,
+        }
+    }
+
+    /// Messages API request body
+    fn request_body(&self, messages: &[Message], tools: Option<&[ToolSchema]>) -> Value {
+        let thinking = self.thinking_enabled(messages);
+        let (system_prompt, formatted_messages) = self.format_messages(messages, thinking);
+        let max_tokens = self.params.max_tokens.unwrap_or(self.max_tokens);
+
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": formatted_messages
+        });
+        if thinking {
+            // Thinking comes out of max_tokens, so the budget is added on
+            // top. Temperature can't be changed while thinking.
+            body["max_tokens"] = json!(max_tokens + self.thinking_budget);
+            body["thinking"] = json!({
+                "type": "enabled",
+                "budget_tokens": self.thinking_budget
+            });
+        } else if let Some(temperature) = self.params.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        if let Some(system) = system_prompt {
+            body["system"] = json!(system);
+        }
+
+        // Include tools so the model uses native tool_use instead of XML
+        if let Some(tools) = tools {
+            if !tools.is_empty() {
+                body["tools"] = json!(self.format_tools(tools));
+            }
+        }
+        body
+    }
+
+    fn format_messages(
+        &self,
+        messages: &[Message],
+        with_thinking: bool,
+    ) -> (Option<String>, Vec<Value>) {
+        let thinking_blocks = self
+            .thinking_blocks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
         let mut system_prompt = None;
         let mut formatted = Vec::new();
 
@@ -608,17 +1832,24 @@ impl AnthropicProvider {
                 }
                 Role::Assistant => {
                     if let Some(ref tool_calls) = m.tool_calls {
-                        let tool_use: Vec<Value> = tool_calls.iter().map(|tc| {
+                        // Thinking blocks must precede the tool calls they led to
+                        let mut content: Vec<Value> = tool_calls
+                            .first()
+                            .filter(|_| with_thinking)
+                            .and_then(|tc| thinking_blocks.get(&tc.id))
+                            .cloned()
+                            .unwrap_or_default();
+                        content.extend(tool_calls.iter().map(|tc| {
                             json!({
                                 "type": "tool_use",
                                 "id": tc.id,
                                 "name": tc.name,
                                 "input": serde_json::from_str::<Value>(&tc.arguments).unwrap_or(json!({}))
                             })
-                        }).collect();
+                        }));
                         formatted.push(json!({
                             "role": "assistant",
-                            "content": tool_use
+                            "content": content
                         }));
                     } else {
                         formatted.push(json!({
@@ -653,40 +1884,21 @@ impl LLMProvider for AnthropicProvider {
         messages: &[Message],
         tools: Option<&[ToolSchema]>,
     ) -> Result<LLMResponse> {
-        let (system_prompt, formatted_messages) = self.format_messages(messages);
-
-        let mut body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": formatted_messages
-        });
-
-        if let Some(system) = system_prompt {
-            body["system"] = json!(system);
-        }
-
-        if let Some(tools) = tools {
-            if !tools.is_empty() {
-                body["tools"] = json!(self.format_tools(tools));
-            }
-        }
+        let body = self.request_body(messages, tools);
 
         debug!(
             "Anthropic request: {}",
             serde_json::to_string_pretty(&body)?
         );
 
-        let response = self
-            .client
-            .post(format!("{}/v1/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+        let response = self.post_messages(&body).await?;
 
-        let response_body: Value = response.json().await?;
+        let status = response.status();
+        let response_body: Value = response
+            .json()
+            .await
+            .map_err(|e| check_timeout("anthropic", &self.timeouts, e))?;
+        check_auth("anthropic", &self.model, status, &response_body.to_string())?;
         debug!(
             "Anthropic response: {}",
             serde_json::to_string_pretty(&response_body)?
@@ -705,6 +1917,7 @@ impl LLMProvider for AnthropicProvider {
         let usage = response_body.get("usage").map(|u| Usage {
             input_tokens: u["input_tokens"].as_u64().unwrap_or(0),
             output_tokens: u["output_tokens"].as_u64().unwrap_or(0),
+            reasoning_tokens: 0,
         });
 
         // Check for tool use
@@ -719,6 +1932,12 @@ impl LLMProvider for AnthropicProvider {
             .collect();
 
         if !tool_calls.is_empty() {
+            let thinking = content
+                .iter()
+                .filter(|c| c["type"] == "thinking" || c["type"] == "redacted_thinking")
+                .cloned()
+                .collect();
+            keep_thinking_blocks(&self.thinking_blocks, &tool_calls, thinking);
             return Ok(LLMResponse {
                 content: LLMResponseContent::ToolCalls(tool_calls),
                 usage,
@@ -739,22 +1958,39 @@ impl LLMProvider for AnthropicProvider {
         })
     }
 
-    async fn summarize(&self, text: &str) -> Result<String> {
-        let messages = vec![Message {
-            role: Role::User,
-            content: format!(
-                "Summarize the following conversation concisely, preserving key information and context:\n\n{}",
-                text
-            ),
-            tool_calls: None,
-            tool_call_id: None,
-            images: Vec::new(),
-        }];
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+    }
 
-        match self.chat(&messages, None).await?.content {
-            LLMResponseContent::Text(summary) => Ok(summary),
-            _ => anyhow::bail!("Unexpected response type"),
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.retry = policy.clone();
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        // Only these resolve to a model ID (see normalize_model_id)
+        Ok(vec![
+            "anthropic/claude-opus-4-5".to_string(),
+            "anthropic/claude-sonnet-4-5".to_string(),
+        ])
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        // The model list above is fixed, so ask the API to check the key
+        let response = self
+            .client
+            .get(format!("{}/v1/models?limit=1", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .send()
+            .await
+            .map_err(|e| check_timeout("anthropic", &self.timeouts, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = response.text().await.unwrap_or_default();
+            check_auth("anthropic", &self.model, status, &error_body)?;
+            anyhow::bail!("Anthropic API error ({}): {}", status, error_body);
         }
+        Ok("key accepted".to_string())
     }
 
     async fn chat_stream(
@@ -762,58 +1998,38 @@ impl LLMProvider for AnthropicProvider {
         messages: &[Message],
         tools: Option<&[ToolSchema]>,
     ) -> Result<StreamResult> {
-        let (system_prompt, formatted_messages) = self.format_messages(messages);
-
-        let mut body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": formatted_messages,
-            "stream": true
-        });
-
-        if let Some(system) = system_prompt {
-            body["system"] = json!(system);
-        }
-
-        // Include tools so the model uses native tool_use instead of XML
-        if let Some(tools) = tools {
-            if !tools.is_empty() {
-                body["tools"] = json!(self.format_tools(tools));
-            }
-        }
+        let mut body = self.request_body(messages, tools);
+        body["stream"] = json!(true);
 
         debug!(
             "Anthropic streaming request: {}",
             serde_json::to_string_pretty(&body)?
         );
 
-        let response = self
-            .client
-            .post(format!("{}/v1/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+        let response = self.post_messages(&body).await?;
 
         // Check for error status
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_body = response.text().await?;
+            check_auth("anthropic", &self.model, status, &error_body)?;
             anyhow::bail!("Anthropic API error: {}", error_body);
         }
 
         // Anthropic streams Server-Sent Events (SSE)
         // We need to track tool_use blocks and accumulate their JSON input
+        let timeouts = self.timeouts.clone();
+        let thinking_blocks = Arc::clone(&self.thinking_blocks);
         let stream = async_stream::stream! {
             let mut byte_stream = response.bytes_stream();
             let mut buffer = String::new();
 
-            // Track tool calls being accumulated
-            let mut pending_tool_calls: Vec<ToolCall> = Vec::new();
-            let mut current_tool_id: Option<String> = None;
-            let mut current_tool_name: Option<String> = None;
-            let mut current_tool_input: String = String::new();
+            // Tool calls being accumulated, keyed by content block index
+            let mut pending_tool_calls = ToolCallAccumulator::default();
+            // Thinking blocks rebuilt from their deltas, kept for tool-use turns
+            let mut thinking: Vec<Value> = Vec::new();
+            // Input tokens come with message_start, output tokens with message_delta
+            let mut usage = Usage::default();
 
             while let Some(chunk) = byte_stream.next().await {
                 match chunk {
@@ -833,12 +2049,18 @@ impl LLMProvider for AnthropicProvider {
                                         let tool_calls = if pending_tool_calls.is_empty() {
                                             None
                                         } else {
-                                            Some(pending_tool_calls.clone())
+                                            let calls = pending_tool_calls.calls();
+                                            keep_thinking_blocks(&thinking_blocks, &calls, std::mem::take(&mut thinking));
+                                            Some(calls)
                                         };
                                         yield Ok(StreamChunk {
                                             delta: String::new(),
                                             done: true,
                                             tool_calls,
+                                            tool_call_deltas: Vec::new(),
+                                            provider_tools: Vec::new(),
+                                            reasoning: String::new(),
+                                            usage: Some(usage.clone()),
                                         });
                                         continue;
                                     }
@@ -855,33 +2077,91 @@ impl LLMProvider for AnthropicProvider {
                                                         delta: delta.to_string(),
                                                         done: false,
                                                         tool_calls: None,
+                                                        tool_call_deltas: Vec::new(),
+                                                        provider_tools: Vec::new(),
+                                                        reasoning: String::new(),
+                                                        usage: None,
+                                                    });
+                                                } else if let Some(text) = json["delta"]["thinking"].as_str() {
+                                                    if let Some(block) = thinking.last_mut() {
+                                                        let so_far = block["thinking"].as_str().unwrap_or_default();
+                                                        block["thinking"] = json!(format!("{}{}", so_far, text));
+                                                    }
+                                                    yield Ok(StreamChunk {
+                                                        delta: String::new(),
+                                                        done: false,
+                                                        tool_calls: None,
+                                                        tool_call_deltas: Vec::new(),
+                                                        provider_tools: Vec::new(),
+                                                        reasoning: text.to_string(),
+                                                        usage: None,
                                                     });
+                                                } else if let Some(signature) = json["delta"]["signature"].as_str() {
+                                                    if let Some(block) = thinking.last_mut() {
+                                                        block["signature"] = json!(signature);
+                                                    }
                                                 } else if let Some(input_delta) = json["delta"]["partial_json"].as_str() {
-                                                    // Accumulate tool input JSON
-                                                    current_tool_input.push_str(input_delta);
+                                                    // Tool input JSON fragment
+                                                    let delta = ToolCallDelta {
+                                                        index: json["index"].as_u64().unwrap_or(0) as usize,
+                                                        arguments: input_delta.to_string(),
+                                                        ..Default::default()
+                                                    };
+                                                    pending_tool_calls.push(&delta);
+                                                    yield Ok(StreamChunk {
+                                                        delta: String::new(),
+                                                        done: false,
+                                                        tool_calls: None,
+                                                        tool_call_deltas: vec![delta],
+                                                        provider_tools: Vec::new(),
+                                                        reasoning: String::new(),
+                                                        usage: None,
+                                                    });
                                                 }
                                             }
 
                                             // Tool use block started
                                             "content_block_start" => {
                                                 if let Some(content_block) = json.get("content_block") {
-                                                    if content_block["type"] == "tool_use" {
-                                                        current_tool_id = content_block["id"].as_str().map(|s| s.to_string());
-                                                        current_tool_name = content_block["name"].as_str().map(|s| s.to_string());
-                                                        current_tool_input.clear();
+                                                    if content_block["type"] == "thinking" {
+                                                        thinking.push(json!({
+                                                            "type": "thinking",
+                                                            "thinking": "",
+                                                            "signature": ""
+                                                        }));
+                                                    } else if content_block["type"] == "redacted_thinking" {
+                                                        thinking.push(content_block.clone());
+                                                    } else if content_block["type"] == "tool_use" {
+                                                        let delta = ToolCallDelta {
+                                                            index: json["index"].as_u64().unwrap_or(0) as usize,
+                                                            id: content_block["id"].as_str().map(|s| s.to_string()),
+                                                            name: content_block["name"].as_str().map(|s| s.to_string()),
+                                                            arguments: String::new(),
+                                                        };
+                                                        pending_tool_calls.push(&delta);
+                                                        yield Ok(StreamChunk {
+                                                            delta: String::new(),
+                                                            done: false,
+                                                            tool_calls: None,
+                                                            tool_call_deltas: vec![delta],
+                                                            provider_tools: Vec::new(),
+                                                            reasoning: String::new(),
+                                                            usage: None,
+                                                        });
                                                     }
                                                 }
                                             }
 
-                                            // Content block finished
-                                            "content_block_stop" => {
-                                                // If we were accumulating a tool call, finalize it
-                                                if let (Some(id), Some(name)) = (current_tool_id.take(), current_tool_name.take()) {
-                                                    pending_tool_calls.push(ToolCall {
-                                                        id,
-                                                        name,
-                                                        arguments: std::mem::take(&mut current_tool_input),
-                                                    });
+                                            "message_start" => {
+                                                let u = &json["message"]["usage"];
+                                                usage.input_tokens = u["input_tokens"].as_u64().unwrap_or(0);
+                                                usage.output_tokens = u["output_tokens"].as_u64().unwrap_or(0);
+                                            }
+
+                                            // Output token count so far
+                                            "message_delta" => {
+                                                if let Some(output) = json["usage"]["output_tokens"].as_u64() {
+                                                    usage.output_tokens = output;
                                                 }
                                             }
 
@@ -890,12 +2170,18 @@ impl LLMProvider for AnthropicProvider {
                                                 let tool_calls = if pending_tool_calls.is_empty() {
                                                     None
                                                 } else {
-                                                    Some(pending_tool_calls.clone())
+                                                    let calls = pending_tool_calls.calls();
+                                                    keep_thinking_blocks(&thinking_blocks, &calls, std::mem::take(&mut thinking));
+                                                    Some(calls)
                                                 };
                                                 yield Ok(StreamChunk {
                                                     delta: String::new(),
                                                     done: true,
                                                     tool_calls,
+                                                    tool_call_deltas: Vec::new(),
+                                                    provider_tools: Vec::new(),
+                                                    reasoning: String::new(),
+                                                    usage: Some(usage.clone()),
                                                 });
                                             }
 
@@ -915,7 +2201,7 @@ impl LLMProvider for AnthropicProvider {
                         }
                     }
                     Err(e) => {
-                        yield Err(anyhow::anyhow!("Stream error: {}", e));
+                        yield Err(stream_error("anthropic", &timeouts, e));
                         break;
                     }
                 }
@@ -926,82 +2212,318 @@ impl LLMProvider for AnthropicProvider {
     }
 }
 
+/// Smallest thinking budget the Messages API accepts
+const MIN_THINKING_BUDGET: usize = 1024;
+
+/// Remember the thinking blocks that led to a turn's tool calls
+fn keep_thinking_blocks(
+    store: &StdMutex<HashMap<String, Vec<Value>>>,
+    calls: &[ToolCall],
+    blocks: Vec<Value>,
+) {
+    if let (Some(call), false) = (calls.first(), blocks.is_empty()) {
+        store
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(call.id.clone(), blocks);
+    }
+}
+
 // Ollama Provider (for local models)
 pub struct OllamaProvider {
     client: Client,
     endpoint: String,
     model: String,
+    params: GenerationParams,
+    /// Hosts to route between; empty when the endpoint is fixed
+    hosts: Vec<OllamaHostConfig>,
+    /// Endpoint picked on first use
+    routed: StdMutex<Option<String>>,
+    retry: RetryConfig,
+    timeouts: TimeoutConfig,
+    network: NetworkConfig,
 }
 
 impl OllamaProvider {
     pub fn new(endpoint: &str, model: &str) -> Result<Self> {
+        let timeouts = TimeoutConfig::default();
         Ok(Self {
-            client: Client::new(),
-            endpoint: endpoint.to_string(),
+            client: http_client(&timeouts, &NetworkConfig::default())?,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
             model: model.to_string(),
+            params: GenerationParams::default(),
+            hosts: Vec::new(),
+            routed: StdMutex::new(None),
+            retry: RetryConfig::default(),
+            timeouts,
+            network: NetworkConfig::default(),
         })
     }
-}
 
-#[async_trait]
-impl LLMProvider for OllamaProvider {
-    async fn chat(
-        &self,
-        messages: &[Message],
-        _tools: Option<&[ToolSchema]>,
-    ) -> Result<LLMResponse> {
-        // Note: Ollama tool support is limited, so we format as plain chat
-        let formatted_messages: Vec<Value> = messages
+    /// Use `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Result<Self> {
+        self.client = http_client(timeouts, &self.network)?;
+        self.timeouts = timeouts.clone();
+        Ok(self)
+    }
+
+    /// Connect through `network`'s proxy and trust its CA certificates
+    pub fn with_network(mut self, network: &NetworkConfig) -> Result<Self> {
+        self.client = http_client(&self.timeouts, network)?;
+        self.network = network.clone();
+        Ok(self)
+    }
+
+    /// Route to the fastest of `hosts` that serves the model, falling back
+    /// to the first host
+    pub fn with_hosts(hosts: Vec<OllamaHostConfig>, model: &str) -> Result<Self> {
+        let first = hosts
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("No Ollama hosts configured"))?;
+        let mut provider = Self::new(&first.endpoint, model)?;
+        if hosts.len() > 1 {
+            provider.hosts = hosts;
+        }
+        Ok(provider)
+    }
+
+    /// Endpoint for requests, probing the hosts on first use
+    async fn endpoint(&self) -> String {
+        if self.hosts.is_empty() {
+            return self.endpoint.clone();
+        }
+        if let Some(ref endpoint) = *self.routed.lock().unwrap_or_else(|e| e.into_inner()) {
+            return endpoint.clone();
+        }
+
+        let statuses =
+            futures::future::join_all(self.hosts.iter().map(|host| probe_host(&self.client, host)))
+                .await;
+        let endpoint = match pick_host(&statuses, &self.model) {
+            Some(status) => {
+                info!(
+                    "Routing Ollama model {} to host {} ({} ms)",
+                    self.model,
+                    status.name,
+                    status.latency_ms.unwrap_or(0)
+                );
+                status.endpoint.clone()
+            }
+            None => {
+                debug!(
+                    "No reachable Ollama host lists {}, using {}",
+                    self.model, self.endpoint
+                );
+                self.endpoint.clone()
+            }
+        };
+        *self.routed.lock().unwrap_or_else(|e| e.into_inner()) = Some(endpoint.clone());
+        endpoint
+    }
+
+    /// Sampling options in Ollama's `options` format
+    fn options(&self) -> Value {
+        let mut options = json!({});
+        if let Some(temperature) = self.params.temperature {
+            options["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.params.max_tokens {
+            options["num_predict"] = json!(max_tokens);
+        }
+        options
+    }
+
+    /// Tools in Ollama's `/api/chat` format (same shape as OpenAI's)
+    fn format_tools(&self, tools: &[ToolSchema]) -> Vec<Value> {
+        tools
             .iter()
-            .map(|m| {
+            .map(|t| {
                 json!({
-                    "role": match m.role {
-                        Role::System => "system",
-                        Role::User => "user",
-                        Role::Assistant => "assistant",
-                        Role::Tool => "user", // Treat tool results as user messages
-                    },
-                    "content": m.content
+                    "type": "function",
+                    "function": {
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": t.parameters
+                    }
                 })
             })
+            .collect()
+    }
+
+    /// Ollama takes tool call arguments as objects, and matches tool results
+    /// to calls by `tool_name` (it has no call IDs)
+    fn format_messages(&self, messages: &[Message]) -> Vec<Value> {
+        let call_names: std::collections::HashMap<&str, &str> = messages
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().flatten())
+            .map(|tc| (tc.id.as_str(), tc.name.as_str()))
             .collect();
 
-        let body = json!({
+        messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    Role::System => "system",
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    Role::Tool => "tool",
+                };
+                let mut msg = json!({
+                    "role": role,
+                    "content": m.content
+                });
+
+                if let Some(ref tool_calls) = m.tool_calls {
+                    msg["tool_calls"] = json!(tool_calls
+                        .iter()
+                        .map(|tc| {
+                            json!({
+                                "function": {
+                                    "name": tc.name,
+                                    "arguments": serde_json::from_str::<Value>(&tc.arguments)
+                                        .unwrap_or(json!({}))
+                                }
+                            })
+                        })
+                        .collect::<Vec<_>>());
+                }
+
+                if let Some(name) = m.tool_call_id.as_deref().and_then(|id| call_names.get(id)) {
+                    msg["tool_name"] = json!(name);
+                }
+
+                msg
+            })
+            .collect()
+    }
+
+    fn request_body(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+        stream: bool,
+    ) -> Value {
+        let mut body = json!({
             "model": self.model,
-            "messages": formatted_messages,
-            "stream": false
+            "messages": self.format_messages(messages),
+            "stream": stream,
+            "options": self.options()
         });
+        if let Some(tools) = tools {
+            if !tools.is_empty() {
+                body["tools"] = json!(self.format_tools(tools));
+            }
+        }
+        body
+    }
 
-        debug!("Ollama request: {}", serde_json::to_string_pretty(&body)?);
+    /// POST to `/api/chat`. Models without tool support reject requests that
+    /// carry tools, so those are retried as plain chat.
+    async fn post_chat(&self, mut body: Value) -> Result<reqwest::Response> {
+        let url = format!("{}/api/chat", self.endpoint().await);
+        loop {
+            debug!("Ollama request: {}", serde_json::to_string_pretty(&body)?);
+            let request = self
+                .client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .json(&body);
+            let response = send_with_retry(&self.retry, "Ollama", request)
+                .await
+                .map_err(|e| check_timeout("ollama", &self.timeouts, e))?;
+            if response.status().is_success() {
+                return Ok(response);
+            }
 
-        let response = self
-            .client
-            .post(format!("{}/api/chat", self.endpoint))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            if text.contains("does not support tools") {
+                if let Some(obj) = body.as_object_mut() {
+                    if obj.remove("tools").is_some() {
+                        debug!(
+                            "Ollama model {} has no tool support, retrying without tools",
+                            self.model
+                        );
+                        continue;
+                    }
+                }
+            }
+            anyhow::bail!("Ollama API error ({}): {}", status, text);
+        }
+    }
+}
+
+/// Token counts from Ollama's `prompt_eval_count` and `eval_count` (final
+/// response or last streamed chunk)
+fn ollama_usage(response: &Value) -> Option<Usage> {
+    response.get("prompt_eval_count").map(|input| Usage {
+        input_tokens: input.as_u64().unwrap_or(0),
+        output_tokens: response["eval_count"].as_u64().unwrap_or(0),
+        reasoning_tokens: 0,
+    })
+}
+
+/// Tool calls in an Ollama response message. Ollama sends each call whole,
+/// without an ID, so one is made up for matching the result.
+fn ollama_tool_calls(message: &Value) -> Vec<ToolCall> {
+    message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|call| ToolCall {
+                    id: call["id"]
+                        .as_str()
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| {
+                            format!("call_{}", &uuid::Uuid::new_v4().simple().to_string()[..12])
+                        }),
+                    name: call["function"]["name"].as_str().unwrap_or("").to_string(),
+                    arguments: match &call["function"]["arguments"] {
+                        Value::String(s) => s.clone(),
+                        Value::Null => "{}".to_string(),
+                        args => args.to_string(),
+                    },
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-        let response_body: Value = response.json().await?;
+#[async_trait]
+impl LLMProvider for OllamaProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let body = self.request_body(messages, tools, false);
+        let response_body: Value = self
+            .post_chat(body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| check_timeout("ollama", &self.timeouts, e))?;
         debug!(
             "Ollama response: {}",
             serde_json::to_string_pretty(&response_body)?
         );
 
-        let content = response_body["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
+        let usage = ollama_usage(&response_body);
 
-        // Ollama returns token counts in prompt_eval_count and eval_count
-        let usage = if response_body.get("prompt_eval_count").is_some() {
-            Some(Usage {
-                input_tokens: response_body["prompt_eval_count"].as_u64().unwrap_or(0),
-                output_tokens: response_body["eval_count"].as_u64().unwrap_or(0),
-            })
-        } else {
-            None
-        };
+        let tool_calls = ollama_tool_calls(&response_body["message"]);
+        if !tool_calls.is_empty() {
+            return Ok(LLMResponse {
+                content: LLMResponseContent::ToolCalls(tool_calls),
+                usage,
+            });
+        }
+
+        // Inline reasoning is not part of the answer
+        let mut think_tags = ThinkTagSplitter::default();
+        let (mut content, _) =
+            think_tags.push(response_body["message"]["content"].as_str().unwrap_or(""));
+        content.push_str(&think_tags.finish().0);
 
         Ok(LLMResponse {
             content: LLMResponseContent::Text(content),
@@ -1009,103 +2531,620 @@ impl LLMProvider for OllamaProvider {
         })
     }
 
-    async fn summarize(&self, text: &str) -> Result<String> {
-        let messages = vec![Message {
-            role: Role::User,
-            content: format!(
-                "Summarize the following conversation concisely, preserving key information and context:\n\n{}",
-                text
-            ),
-            tool_calls: None,
-            tool_call_id: None,
-            images: Vec::new(),
-        }];
+    async fn chat_json(&self, prompt: &str, schema: &Value) -> Result<Value> {
+        let mut body = self.request_body(&[json_request_message(prompt)], None, false);
+        body["format"] = schema.clone();
+
+        // Ollama before 0.5 rejects a schema as the format
+        let response_body: Value = match self.post_chat(body).await {
+            Ok(response) => response
+                .json()
+                .await
+                .map_err(|e| check_timeout("ollama", &self.timeouts, e))?,
+            Err(e) => {
+                debug!(
+                    "Ollama structured output failed ({}), asking in the prompt",
+                    e
+                );
+                return prompted_json(self, prompt, schema).await;
+            }
+        };
 
-        match self.chat(&messages, None).await?.content {
-            LLMResponseContent::Text(summary) => Ok(summary),
-            _ => anyhow::bail!("Unexpected response type"),
+        let mut think_tags = ThinkTagSplitter::default();
+        let (mut content, _) =
+            think_tags.push(response_body["message"]["content"].as_str().unwrap_or(""));
+        content.push_str(&think_tags.finish().0);
+        parse_json_reply(&content)
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.retry = policy.clone();
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let hosts = if self.hosts.is_empty() {
+            vec![OllamaHostConfig {
+                name: "default".to_string(),
+                endpoint: self.endpoint.clone(),
+                models: Vec::new(),
+            }]
+        } else {
+            self.hosts.clone()
+        };
+        let statuses =
+            futures::future::join_all(hosts.iter().map(|host| probe_host(&self.client, host)))
+                .await;
+        if !statuses.iter().any(|s| s.is_reachable()) {
+            if let Some(error) = statuses.iter().find_map(|s| s.error.clone()) {
+                anyhow::bail!("{}", error);
+            }
         }
+
+        // Unqualified names are routed to whichever host has the model
+        let mut models: Vec<String> = statuses
+            .into_iter()
+            .flat_map(|s| s.installed)
+            .map(|name| format!("ollama/{}", name))
+            .collect();
+        models.sort();
+        models.dedup();
+        Ok(models)
     }
 
     async fn chat_stream(
         &self,
         messages: &[Message],
-        _tools: Option<&[ToolSchema]>,
+        tools: Option<&[ToolSchema]>,
     ) -> Result<StreamResult> {
-        let formatted_messages: Vec<Value> = messages
+        let body = self.request_body(messages, tools, true);
+        let response = self.post_chat(body).await?;
+
+        // Ollama streams newline-delimited JSON
+        let timeouts = self.timeouts.clone();
+        let stream = async_stream::stream! {
+            let mut byte_stream = response.bytes_stream();
+            let mut buffer = String::new();
+            let mut think_tags = ThinkTagSplitter::default();
+            // Tool calls arrive whole, usually in a chunk before the last
+            let mut tool_calls: Vec<ToolCall> = Vec::new();
+
+            while let Some(chunk) = byte_stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+                        // Process complete lines
+                        while let Some(pos) = buffer.find('\n') {
+                            let line = buffer[..pos].to_string();
+                            buffer = buffer[pos + 1..].to_string();
+
+                            if line.is_empty() {
+                                continue;
+                            }
+
+                            if let Ok(json) = serde_json::from_str::<Value>(&line) {
+                                if let Some(error) = json["error"].as_str() {
+                                    yield Err(anyhow::anyhow!("Ollama API error: {}", error));
+                                    return;
+                                }
+                                let done = json["done"].as_bool().unwrap_or(false);
+                                tool_calls.extend(ollama_tool_calls(&json["message"]));
+                                let (mut content, mut reasoning) =
+                                    think_tags.push(json["message"]["content"].as_str().unwrap_or(""));
+                                // Reasoning models return it separately when thinking is enabled
+                                if let Some(thinking) = json["message"]["thinking"].as_str() {
+                                    reasoning.insert_str(0, thinking);
+                                }
+                                if done {
+                                    let (rest, rest_reasoning) = think_tags.finish();
+                                    content.push_str(&rest);
+                                    reasoning.push_str(&rest_reasoning);
+                                }
+
+                                yield Ok(StreamChunk {
+                                    delta: content,
+                                    done,
+                                    tool_calls: (done && !tool_calls.is_empty())
+                                        .then(|| std::mem::take(&mut tool_calls)),
+                                    tool_call_deltas: Vec::new(),
+                                    provider_tools: Vec::new(),
+                                    reasoning,
+                                    usage: if done { ollama_usage(&json) } else { None },
+                                });
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(stream_error("ollama", &timeouts, e));
+                        break;
+                    }
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
+
+// Google Gemini Provider (Generative Language API)
+
+/// Schema keywords the Gemini API rejects in function declarations
+const GEMINI_UNSUPPORTED_SCHEMA_KEYS: &[&str] = &["$schema", "additionalProperties"];
+
+pub struct GeminiProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+    params: GenerationParams,
+    retry: RetryConfig,
+    timeouts: TimeoutConfig,
+    network: NetworkConfig,
+}
+
+impl GeminiProvider {
+    pub fn new(api_key: &str, base_url: &str, model: &str) -> Result<Self> {
+        let timeouts = TimeoutConfig::default();
+        Ok(Self {
+            client: http_client(&timeouts, &NetworkConfig::default())?,
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            params: GenerationParams::default(),
+            retry: RetryConfig::default(),
+            timeouts,
+            network: NetworkConfig::default(),
+        })
+    }
+
+    /// Use `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Result<Self> {
+        self.client = http_client(timeouts, &self.network)?;
+        self.timeouts = timeouts.clone();
+        Ok(self)
+    }
+
+    /// Connect through `network`'s proxy and trust its CA certificates
+    pub fn with_network(mut self, network: &NetworkConfig) -> Result<Self> {
+        self.client = http_client(&self.timeouts, network)?;
+        self.network = network.clone();
+        Ok(self)
+    }
+
+    fn format_tools(&self, tools: &[ToolSchema]) -> Value {
+        let declarations: Vec<Value> = tools
             .iter()
-            .map(|m| {
+            .map(|t| {
                 json!({
-                    "role": match m.role {
-                        Role::System => "system",
-                        Role::User => "user",
-                        Role::Assistant => "assistant",
-                        Role::Tool => "user",
-                    },
-                    "content": m.content
+                    "name": t.name,
+                    "description": t.description,
+                    "parameters": gemini_schema(&t.parameters)
                 })
             })
             .collect();
+        json!([{ "functionDeclarations": declarations }])
+    }
 
-        let body = json!({
-            "model": self.model,
-            "messages": formatted_messages,
-            "stream": true
-        });
+    /// Returns the system instruction and `contents`. Gemini has no tool
+    /// role: results are `functionResponse` parts in a user turn, matched to
+    /// the call by function name, and results of parallel calls share a turn.
+    fn format_messages(&self, messages: &[Message]) -> (Option<String>, Vec<Value>) {
+        let mut system_prompt = None;
+        let mut contents: Vec<Value> = Vec::new();
+
+        let call_names: std::collections::HashMap<&str, &str> = messages
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().flatten())
+            .map(|tc| (tc.id.as_str(), tc.name.as_str()))
+            .collect();
+
+        for m in messages {
+            match m.role {
+                Role::System => {
+                    system_prompt = Some(m.content.clone());
+                }
+                Role::User => {
+                    let mut parts: Vec<Value> = m
+                        .images
+                        .iter()
+                        .map(|img| {
+                            json!({
+                                "inlineData": {
+                                    "mimeType": img.media_type,
+                                    "data": img.data
+                                }
+                            })
+                        })
+                        .collect();
+                    if !m.content.is_empty() {
+                        parts.push(json!({ "text": m.content }));
+                    }
+                    contents.push(json!({ "role": "user", "parts": parts }));
+                }
+                Role::Assistant => {
+                    let mut parts = Vec::new();
+                    if !m.content.is_empty() {
+                        parts.push(json!({ "text": m.content }));
+                    }
+                    for tc in m.tool_calls.iter().flatten() {
+                        parts.push(json!({
+                            "functionCall": {
+                                "name": tc.name,
+                                "args": serde_json::from_str::<Value>(&tc.arguments).unwrap_or(json!({}))
+                            }
+                        }));
+                    }
+                    if !parts.is_empty() {
+                        contents.push(json!({ "role": "model", "parts": parts }));
+                    }
+                }
+                Role::Tool => {
+                    let name = m
+                        .tool_call_id
+                        .as_deref()
+                        .and_then(|id| call_names.get(id))
+                        .copied()
+                        .unwrap_or("tool");
+                    let part = json!({
+                        "functionResponse": {
+                            "name": name,
+                            "response": { "content": m.content }
+                        }
+                    });
+
+                    let previous_is_result = contents.last().is_some_and(|c| {
+                        c["role"] == "user" && c["parts"][0].get("functionResponse").is_some()
+                    });
+                    match contents.last_mut() {
+                        Some(last) if previous_is_result => {
+                            if let Some(parts) = last["parts"].as_array_mut() {
+                                parts.push(part);
+                            }
+                        }
+                        _ => contents.push(json!({ "role": "user", "parts": [part] })),
+                    }
+                }
+            }
+        }
+
+        (system_prompt, contents)
+    }
+
+    fn request_body(&self, messages: &[Message], tools: Option<&[ToolSchema]>) -> Value {
+        let (system_prompt, contents) = self.format_messages(messages);
+        let mut body = json!({ "contents": contents });
+
+        if let Some(system) = system_prompt {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if let Some(tools) = tools {
+            if !tools.is_empty() {
+                body["tools"] = self.format_tools(tools);
+            }
+        }
+
+        let mut generation_config = json!({});
+        if let Some(temperature) = self.params.temperature {
+            generation_config["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.params.max_tokens {
+            generation_config["maxOutputTokens"] = json!(max_tokens);
+        }
+        if generation_config != json!({}) {
+            body["generationConfig"] = generation_config;
+        }
+        body
+    }
+
+    fn endpoint(&self, method: &str) -> String {
+        format!("{}/models/{}:{}", self.base_url, self.model, method)
+    }
+}
+
+/// Chat model names from an OpenAI-style `/models` response. OpenAI's own
+/// list also has embedding, audio and image models, which are left out.
+fn openai_model_names(provider: &str, body: &Value) -> Vec<String> {
+    const CHAT_PREFIXES: &[&str] = &["gpt-", "chatgpt-", "o1", "o3", "o4"];
+    const NOT_CHAT: &[&str] = &["audio", "realtime", "transcribe", "tts", "image", "search"];
+
+    let mut models: Vec<String> = body["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m["id"].as_str())
+        .filter(|id| {
+            provider != "openai"
+                || (CHAT_PREFIXES.iter().any(|p| id.starts_with(p))
+                    && !NOT_CHAT.iter().any(|n| id.contains(n)))
+        })
+        .map(|id| format!("{}/{}", provider, id))
+        .collect();
+    models.sort();
+    models
+}
+
+/// Models from Gemini's `/models` response that support `generateContent`
+fn gemini_model_names(body: &Value) -> Vec<String> {
+    let mut models: Vec<String> = body["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|m| {
+            m["supportedGenerationMethods"]
+                .as_array()
+                .is_some_and(|methods| methods.iter().any(|x| x == "generateContent"))
+        })
+        .filter_map(|m| m["name"].as_str())
+        .map(|name| format!("gemini/{}", name.trim_start_matches("models/")))
+        .collect();
+    models.sort();
+    models
+}
+
+/// Copy of a JSON schema without keywords Gemini rejects
+fn gemini_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| !GEMINI_UNSUPPORTED_SCHEMA_KEYS.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), gemini_schema(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(gemini_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Text, thought summaries, and function calls from one
+/// `GenerateContentResponse`
+fn gemini_response_parts(response: &Value) -> (String, String, Vec<ToolCall>) {
+    let mut text = String::new();
+    let mut thoughts = String::new();
+    let mut calls = Vec::new();
+
+    let parts = response["candidates"][0]["content"]["parts"].as_array();
+    for part in parts.into_iter().flatten() {
+        if let Some(t) = part["text"].as_str() {
+            if part["thought"].as_bool() == Some(true) {
+                thoughts.push_str(t);
+            } else {
+                text.push_str(t);
+            }
+        }
+        if let Some(call) = part.get("functionCall") {
+            calls.push(ToolCall {
+                // Older models don't return call IDs
+                id: call["id"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| {
+                        format!("call_{}", &uuid::Uuid::new_v4().simple().to_string()[..12])
+                    }),
+                name: call["name"].as_str().unwrap_or("").to_string(),
+                arguments: call
+                    .get("args")
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| "{}".to_string()),
+            });
+        }
+    }
+
+    (text, thoughts, calls)
+}
+
+/// Token counts from `usageMetadata` (cumulative in streamed chunks)
+fn gemini_usage(response: &Value) -> Option<Usage> {
+    response.get("usageMetadata").map(|u| Usage {
+        input_tokens: u["promptTokenCount"].as_u64().unwrap_or(0),
+        output_tokens: u["candidatesTokenCount"].as_u64().unwrap_or(0),
+        reasoning_tokens: 0,
+    })
+}
 
+fn gemini_error(response: &Value) -> Option<String> {
+    if let Some(error) = response.get("error") {
+        return Some(
+            error["message"]
+                .as_str()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| error.to_string()),
+        );
+    }
+    response["promptFeedback"]["blockReason"]
+        .as_str()
+        .map(|reason| format!("prompt blocked ({})", reason))
+}
+
+#[async_trait]
+impl LLMProvider for GeminiProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let body = self.request_body(messages, tools);
+        debug!("Gemini request: {}", serde_json::to_string_pretty(&body)?);
+
+        let request = self
+            .client
+            .post(self.endpoint("generateContent"))
+            .header("x-goog-api-key", &self.api_key)
+            .header("Content-Type", "application/json")
+            .json(&body);
+        let response = send_with_retry(&self.retry, "Gemini", request)
+            .await
+            .map_err(|e| check_timeout("gemini", &self.timeouts, e))?;
+
+        let status = response.status();
+        let response_body: Value = response
+            .json()
+            .await
+            .map_err(|e| check_timeout("gemini", &self.timeouts, e))?;
+        check_auth("gemini", &self.model, status, &response_body.to_string())?;
+        debug!(
+            "Gemini response: {}",
+            serde_json::to_string_pretty(&response_body)?
+        );
+
+        if let Some(error) = gemini_error(&response_body) {
+            anyhow::bail!("Gemini API error: {}", error);
+        }
+
+        let usage = gemini_usage(&response_body);
+
+        let (text, _, calls) = gemini_response_parts(&response_body);
+        if !calls.is_empty() {
+            return Ok(LLMResponse {
+                content: LLMResponseContent::ToolCalls(calls),
+                usage,
+            });
+        }
+
+        Ok(LLMResponse {
+            content: LLMResponseContent::Text(text),
+            usage,
+        })
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.retry = policy.clone();
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self
+            .client
+            .get(format!("{}/models?pageSize=1000", self.base_url))
+            .header("x-goog-api-key", &self.api_key)
+            .send()
+            .await
+            .map_err(|e| check_timeout("gemini", &self.timeouts, e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_default();
+            check_auth("gemini", &self.model, status, &error_body)?;
+            anyhow::bail!("Gemini model list failed ({}): {}", status, error_body);
+        }
+        let body: Value = response.json().await?;
+        Ok(gemini_model_names(&body))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let body = self.request_body(messages, tools);
         debug!(
-            "Ollama streaming request: {}",
+            "Gemini streaming request: {}",
             serde_json::to_string_pretty(&body)?
         );
 
-        let response = self
+        let request = self
             .client
-            .post(format!("{}/api/chat", self.endpoint))
+            .post(format!(
+                "{}?alt=sse",
+                self.endpoint("streamGenerateContent")
+            ))
+            .header("x-goog-api-key", &self.api_key)
             .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+            .json(&body);
+        let response = send_with_retry(&self.retry, "Gemini", request)
+            .await
+            .map_err(|e| check_timeout("gemini", &self.timeouts, e))?;
 
-        // Ollama streams newline-delimited JSON
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = response.text().await?;
+            check_auth("gemini", &self.model, status, &error_body)?;
+            anyhow::bail!("Gemini API error: {}", error_body);
+        }
+
+        // Server-Sent Events, each a partial GenerateContentResponse. Function
+        // calls arrive whole, so each becomes a single delta.
+        let timeouts = self.timeouts.clone();
         let stream = async_stream::stream! {
             let mut byte_stream = response.bytes_stream();
             let mut buffer = String::new();
+            let mut pending_tool_calls = ToolCallAccumulator::default();
+            let mut next_index = 0;
+            let mut usage = None;
 
             while let Some(chunk) = byte_stream.next().await {
                 match chunk {
                     Ok(bytes) => {
                         buffer.push_str(&String::from_utf8_lossy(&bytes));
 
-                        // Process complete lines
                         while let Some(pos) = buffer.find('\n') {
-                            let line = buffer[..pos].to_string();
+                            let line = buffer[..pos].trim().to_string();
                             buffer = buffer[pos + 1..].to_string();
 
-                            if line.is_empty() {
+                            let Some(data) = line.strip_prefix("data:") else {
+                                continue;
+                            };
+                            let Ok(json) = serde_json::from_str::<Value>(data.trim()) else {
+                                continue;
+                            };
+                            if let Some(error) = gemini_error(&json) {
+                                yield Err(anyhow::anyhow!("Gemini API error: {}", error));
                                 continue;
                             }
+                            if let Some(u) = gemini_usage(&json) {
+                                usage = Some(u);
+                            }
 
-                            if let Ok(json) = serde_json::from_str::<Value>(&line) {
-                                let content = json["message"]["content"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string();
-                                let done = json["done"].as_bool().unwrap_or(false);
-
+                            let (text, reasoning, calls) = gemini_response_parts(&json);
+                            let tool_call_deltas: Vec<ToolCallDelta> = calls
+                                .into_iter()
+                                .map(|call| {
+                                    next_index += 1;
+                                    ToolCallDelta {
+                                        index: next_index - 1,
+                                        id: Some(call.id),
+                                        name: Some(call.name),
+                                        arguments: call.arguments,
+                                    }
+                                })
+                                .collect();
+                            for delta in &tool_call_deltas {
+                                pending_tool_calls.push(delta);
+                            }
+                            if !text.is_empty() || !reasoning.is_empty() || !tool_call_deltas.is_empty() {
                                 yield Ok(StreamChunk {
-                                    delta: content,
-                                    done,
+                                    delta: text,
+                                    done: false,
                                     tool_calls: None,
+                                    tool_call_deltas,
+                                    provider_tools: Vec::new(),
+                                    reasoning,
+                                    usage: None,
                                 });
                             }
                         }
                     }
                     Err(e) => {
-                        yield Err(anyhow::anyhow!("Stream error: {}", e));
+                        yield Err(stream_error("gemini", &timeouts, e));
                         break;
                     }
                 }
             }
+
+            yield Ok(StreamChunk {
+                delta: String::new(),
+                done: true,
+                tool_calls: (!pending_tool_calls.is_empty()).then(|| pending_tool_calls.calls()),
+                tool_call_deltas: Vec::new(),
+                provider_tools: Vec::new(),
+                reasoning: String::new(),
+                usage,
+            });
         };
 
         Ok(Box::pin(stream))
@@ -1158,7 +3197,8 @@ impl ClaudeCliProvider {
         system_prompt: Option<&str>,
         existing_session: Option<&str>,
     ) -> Result<(std::process::Output, bool)> {
-        use std::process::Command;
+        // Killed if the request is dropped, e.g. when a turn is stopped
+        use tokio::process::Command;
 
         // First attempt: try with existing session if available
         if let Some(cli_sid) = existing_session {
@@ -1169,18 +3209,12 @@ impl ClaudeCliProvider {
                 self.command, args, self.workspace
             );
 
-            let output = tokio::task::spawn_blocking({
-                let command = self.command.clone();
-                let args = args.clone();
-                let workspace = self.workspace.clone();
-                move || {
-                    Command::new(&command)
-                        .args(&args)
-                        .current_dir(&workspace)
-                        .output()
-                }
-            })
-            .await??;
+            let output = Command::new(&self.command)
+                .args(&args)
+                .current_dir(&self.workspace)
+                .kill_on_drop(true)
+                .output()
+                .await?;
 
             if output.status.success() {
                 return Ok((output, false));
@@ -1214,18 +3248,12 @@ impl ClaudeCliProvider {
             self.command, args, self.workspace
         );
 
-        let output = tokio::task::spawn_blocking({
-            let command = self.command.clone();
-            let args = args.clone();
-            let workspace = self.workspace.clone();
-            move || {
-                Command::new(&command)
-                    .args(&args)
-                    .current_dir(&workspace)
-                    .output()
-            }
-        })
-        .await??;
+        let output = Command::new(&self.command)
+            .args(&args)
+            .current_dir(&self.workspace)
+            .kill_on_drop(true)
+            .output()
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -1308,8 +3336,28 @@ impl ClaudeCliProvider {
     }
 }
 
+/// `<command> --version`, or why the CLI couldn't be run
+pub(super) async fn cli_version(command: &str) -> Result<String> {
+    let output = tokio::process::Command::new(command)
+        .arg("--version")
+        .output()
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow::anyhow!("'{}' not found on PATH", command),
+            _ => anyhow::anyhow!("Failed to run '{}': {}", command, e),
+        })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "'{} --version' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 /// Load CLI session ID from session store
-fn load_cli_session_from_store(session_key: &str, provider: &str) -> Option<String> {
+pub(super) fn load_cli_session_from_store(session_key: &str, provider: &str) -> Option<String> {
     use super::session_store::SessionStore;
 
     let store = SessionStore::load().ok()?;
@@ -1317,7 +3365,7 @@ fn load_cli_session_from_store(session_key: &str, provider: &str) -> Option<Stri
 }
 
 /// Save CLI session ID to session store
-fn save_cli_session_to_store(
+pub(super) fn save_cli_session_to_store(
     session_key: &str,
     session_id: &str,
     provider: &str,
@@ -1340,7 +3388,7 @@ fn normalize_claude_model(model: &str) -> String {
     .to_string()
 }
 
-fn build_prompt_from_messages(messages: &[Message]) -> String {
+pub(super) fn build_prompt_from_messages(messages: &[Message]) -> String {
     // Get the last user message as the prompt
     messages
         .iter()
@@ -1350,7 +3398,7 @@ fn build_prompt_from_messages(messages: &[Message]) -> String {
         .unwrap_or_default()
 }
 
-fn extract_system_prompt(messages: &[Message]) -> Option<String> {
+pub(super) fn extract_system_prompt(messages: &[Message]) -> Option<String> {
     messages
         .iter()
         .find(|m| m.role == Role::System)
@@ -1386,6 +3434,78 @@ fn parse_claude_cli_output(stdout: &str) -> Result<(String, Option<String>)> {
     Ok((stdout.trim().to_string(), None))
 }
 
+/// Tool activity in one Claude CLI `stream-json` event: `tool_use` blocks in
+/// assistant messages start a tool, `tool_result` blocks in user messages
+/// finish one. `names` maps call IDs to tool names between the two, and
+/// also drops the repeats that partial messages bring.
+fn claude_cli_tool_events(
+    event: &Value,
+    names: &mut HashMap<String, String>,
+) -> Vec<ProviderToolEvent> {
+    let Some(blocks) = event["message"]["content"].as_array() else {
+        return Vec::new();
+    };
+    let mut events = Vec::new();
+    for block in blocks {
+        let id = block["id"]
+            .as_str()
+            .or_else(|| block["tool_use_id"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        match block["type"].as_str() {
+            Some("tool_use") if !names.contains_key(&id) => {
+                let name = block["name"].as_str().unwrap_or("unknown").to_string();
+                names.insert(id.clone(), name.clone());
+                events.push(ProviderToolEvent::Started {
+                    detail: claude_cli_tool_detail(&name, &block["input"]),
+                    id,
+                    name,
+                });
+            }
+            Some("tool_result") => {
+                let output = match &block["content"] {
+                    Value::String(text) => text.clone(),
+                    Value::Array(parts) => parts
+                        .iter()
+                        .filter_map(|part| part["text"].as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => String::new(),
+                };
+                events.push(ProviderToolEvent::Finished {
+                    name: names.get(&id).cloned().unwrap_or_default(),
+                    id,
+                    output,
+                    is_error: block["is_error"].as_bool().unwrap_or(false),
+                });
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// The argument worth showing for a Claude CLI tool (command, path, pattern, ...)
+fn claude_cli_tool_detail(name: &str, input: &Value) -> Option<String> {
+    let field = match name {
+        "Bash" => "command",
+        "Read" | "Edit" | "MultiEdit" | "Write" => "file_path",
+        "Grep" | "Glob" => "pattern",
+        "WebFetch" => "url",
+        "WebSearch" => "query",
+        "Task" => "description",
+        _ => return None,
+    };
+    let value = input[field].as_str()?;
+    Some(match name {
+        "Bash" if value.chars().count() > 60 => {
+            format!("{}...", value.chars().take(57).collect::<String>())
+        }
+        "Grep" | "Glob" => format!("\"{}\"", value),
+        _ => value.to_string(),
+    })
+}
+
 #[async_trait]
 impl LLMProvider for ClaudeCliProvider {
     async fn chat(
@@ -1444,22 +3564,15 @@ impl LLMProvider for ClaudeCliProvider {
         Ok(LLMResponse::text(response))
     }
 
-    async fn summarize(&self, text: &str) -> Result<String> {
-        let messages = vec![Message {
-            role: Role::User,
-            content: format!(
-                "Summarize the following conversation concisely:\n\n{}",
-                text
-            ),
-            tool_calls: None,
-            tool_call_id: None,
-            images: Vec::new(),
-        }];
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec![
+            "claude-cli/opus".to_string(),
+            "claude-cli/sonnet".to_string(),
+        ])
+    }
 
-        match self.chat(&messages, None).await?.content {
-            LLMResponseContent::Text(summary) => Ok(summary),
-            _ => anyhow::bail!("Unexpected response type"),
-        }
+    async fn health_check(&self) -> Result<String> {
+        cli_version(&self.command).await
     }
 
     async fn chat_stream(
@@ -1499,12 +3612,14 @@ impl LLMProvider for ClaudeCliProvider {
             self.command, args, self.workspace
         );
 
-        // Spawn the CLI process with piped stdout
+        // Spawn the CLI process with piped stdout; dropping the stream
+        // (a stopped turn) kills it
         let mut child = tokio::process::Command::new(&self.command)
             .args(&args)
             .current_dir(&self.workspace)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| anyhow::anyhow!("Failed to spawn Claude CLI: {}", e))?;
 
@@ -1526,8 +3641,7 @@ impl LLMProvider for ClaudeCliProvider {
             let mut accumulated_text = String::new();
             let mut session_id_captured: Option<String> = None;
             let mut last_text_len = 0;
-            let mut shown_tool_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
-            let mut pending_tools: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+            let mut tool_names: HashMap<String, String> = HashMap::new();
 
             while let Ok(Some(line)) = lines.next_line().await {
                 if line.is_empty() {
@@ -1551,6 +3665,10 @@ impl LLMProvider for ClaudeCliProvider {
                                         delta: format!("[Model: {} | Tools: {}]\n", model, tools_count),
                                         done: false,
                                         tool_calls: None,
+                                        tool_call_deltas: Vec::new(),
+                                        provider_tools: Vec::new(),
+                                        reasoning: String::new(),
+                                        usage: None,
                                     });
                                 }
                             }
@@ -1558,60 +3676,27 @@ impl LLMProvider for ClaudeCliProvider {
 
                         // Assistant message with content (streaming updates)
                         "assistant" => {
-                            // Extract text and tool_use from message.content array
+                            // Tools the CLI starts run in the workspace on
+                            // their own; report them for display
+                            let provider_tools = claude_cli_tool_events(&json, &mut tool_names);
+                            if !provider_tools.is_empty() {
+                                yield Ok(StreamChunk {
+                                    delta: String::new(),
+                                    done: false,
+                                    tool_calls: None,
+                                    tool_call_deltas: Vec::new(),
+                                    provider_tools,
+                                    reasoning: String::new(),
+                                    usage: None,
+                                });
+                            }
+
                             if let Some(content_array) = json["message"]["content"].as_array() {
                                 for block in content_array {
                                     if block["type"] == "text" {
                                         if let Some(text) = block["text"].as_str() {
                                             accumulated_text = text.to_string();
                                         }
-                                    } else if block["type"] == "tool_use" {
-                                        // Show tool call as it happens
-                                        let tool_id = block["id"].as_str().unwrap_or("").to_string();
-                                        let tool_name = block["name"].as_str().unwrap_or("unknown");
-
-                                        // Only show each tool call once
-                                        if !shown_tool_ids.contains(&tool_id) {
-                                            shown_tool_ids.insert(tool_id.clone());
-                                            pending_tools.insert(tool_id, tool_name.to_string());
-
-                                            // Format tool details
-                                            let detail = if let Some(input) = block.get("input") {
-                                                match tool_name {
-                                                    "Bash" => input.get("command")
-                                                        .and_then(|v| v.as_str())
-                                                        .map(|s| if s.len() > 60 { format!("{}...", &s[..57]) } else { s.to_string() }),
-                                                    "Read" | "Edit" | "Write" => input.get("file_path")
-                                                        .or_else(|| input.get("path"))
-                                                        .and_then(|v| v.as_str())
-                                                        .map(|s| s.to_string()),
-                                                    "Grep" | "Glob" => input.get("pattern")
-                                                        .and_then(|v| v.as_str())
-                                                        .map(|s| format!("\"{}\"", s)),
-                                                    "WebFetch" => input.get("url")
-                                                        .and_then(|v| v.as_str())
-                                                        .map(|s| s.to_string()),
-                                                    "Task" => input.get("description")
-                                                        .and_then(|v| v.as_str())
-                                                        .map(|s| s.to_string()),
-                                                    _ => None,
-                                                }
-                                            } else {
-                                                None
-                                            };
-
-                                            let tool_msg = if let Some(d) = detail {
-                                                format!("\n[{}: {}]", tool_name, d)
-                                            } else {
-                                                format!("\n[{}]", tool_name)
-                                            };
-
-                                            yield Ok(StreamChunk {
-                                                delta: tool_msg,
-                                                done: false,
-                                                tool_calls: None,
-                                            });
-                                        }
                                     }
                                 }
                             }
@@ -1624,31 +3709,27 @@ impl LLMProvider for ClaudeCliProvider {
                                     delta,
                                     done: false,
                                     tool_calls: None,
+                                    tool_call_deltas: Vec::new(),
+                                    provider_tools: Vec::new(),
+                                    reasoning: String::new(),
+                                    usage: None,
                                 });
                             }
                         }
 
-                        // Tool result - show completion
+                        // Tool results - report completion
                         "user" => {
-                            if let Some(content_array) = json["message"]["content"].as_array() {
-                                for block in content_array {
-                                    if block["type"] == "tool_result" {
-                                        let tool_id = block["tool_use_id"].as_str().unwrap_or("");
-                                        let is_error = block.get("is_error")
-                                            .and_then(|v| v.as_bool())
-                                            .unwrap_or(false);
-
-                                        // Get tool name from pending_tools
-                                        let _tool_name = pending_tools.remove(tool_id);
-
-                                        let status = if is_error { "failed" } else { "done" };
-                                        yield Ok(StreamChunk {
-                                            delta: format!(" [{}]\n", status),
-                                            done: false,
-                                            tool_calls: None,
-                                        });
-                                    }
-                                }
+                            let provider_tools = claude_cli_tool_events(&json, &mut tool_names);
+                            if !provider_tools.is_empty() {
+                                yield Ok(StreamChunk {
+                                    delta: String::new(),
+                                    done: false,
+                                    tool_calls: None,
+                                    tool_call_deltas: Vec::new(),
+                                    provider_tools,
+                                    reasoning: String::new(),
+                                    usage: None,
+                                });
                             }
                         }
 
@@ -1686,6 +3767,10 @@ impl LLMProvider for ClaudeCliProvider {
                                             delta,
                                             done: false,
                                             tool_calls: None,
+                                            tool_call_deltas: Vec::new(),
+                                            provider_tools: Vec::new(),
+                                            reasoning: String::new(),
+                                            usage: None,
                                         });
                                     }
                                 }
@@ -1696,6 +3781,10 @@ impl LLMProvider for ClaudeCliProvider {
                                 delta: String::new(),
                                 done: true,
                                 tool_calls: None,
+                                tool_call_deltas: Vec::new(),
+                                provider_tools: Vec::new(),
+                                reasoning: String::new(),
+                                usage: None,
                             });
                         }
 
@@ -1753,11 +3842,25 @@ mod providers_test;
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_json_reply() {
+        assert_eq!(
+            parse_json_reply(r#"{"facts": ["Uses Rust"]}"#).unwrap(),
+            json!({"facts": ["Uses Rust"]})
+        );
+        assert_eq!(
+            parse_json_reply("Sure:\n```json\n{\"facts\": []}\n```").unwrap(),
+            json!({"facts": []})
+        );
+        assert!(parse_json_reply("Nothing new.").is_err());
+    }
+
     #[test]
     fn test_usage_total() {
         let usage = Usage {
             input_tokens: 100,
             output_tokens: 50,
+            ..Default::default()
         };
         assert_eq!(usage.total(), 150);
     }
@@ -1781,6 +3884,7 @@ mod tests {
         let usage = Usage {
             input_tokens: 10,
             output_tokens: 5,
+            ..Default::default()
         };
         let resp = LLMResponse::text_with_usage("hello".to_string(), usage);
         assert!(matches!(resp.content, LLMResponseContent::Text(_)));
@@ -1810,4 +3914,659 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_user_model_aliases() {
+        let config: Config = toml::from_str(
+            "[models.aliases]\nfast = \"gpt-4o-mini\"\nsmart = \"opus\"\n\
+             summarizer = \"fast\"\nloop-a = \"loop-b\"\nloop-b = \"loop-a\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            resolve_provider("fast", &config),
+            ("openai".to_string(), "gpt-4o-mini".to_string())
+        );
+        // Built-in aliases still apply to the target
+        assert_eq!(
+            resolve_provider("smart", &config),
+            ("anthropic".to_string(), "claude-opus-4-5".to_string())
+        );
+        assert_eq!(config.models.resolve("summarizer"), "gpt-4o-mini");
+        assert_eq!(config.models.resolve("gpt-4o"), "gpt-4o");
+        // A loop gives up instead of hanging
+        let looped = config.models.resolve("loop-a");
+        assert!(looped == "loop-a" || looped == "loop-b");
+    }
+
+    /// Replies with the prompt it was sent
+    struct EchoProvider;
+
+    #[async_trait]
+    impl LLMProvider for EchoProvider {
+        async fn chat(
+            &self,
+            messages: &[Message],
+            _tools: Option<&[ToolSchema]>,
+        ) -> Result<LLMResponse> {
+            Ok(LLMResponse::text(messages[0].content.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_summarize_with_returns_chat_text() {
+        let reply = EchoProvider
+            .summarize_with(DEFAULT_TITLE_PROMPT, "User: why does cargo fail?")
+            .await
+            .expect("summarize_with should succeed");
+
+        assert_eq!(
+            reply,
+            format!("{}\n\nUser: why does cargo fail?", DEFAULT_TITLE_PROMPT)
+        );
+    }
+
+    #[test]
+    fn test_tool_call_deltas_assemble_into_calls() {
+        // Two calls interleaved the way OpenAI streams them
+        let chunks = [
+            serde_json::json!({"tool_calls": [
+                {"index": 0, "id": "call_a", "function": {"name": "read_file", "arguments": ""}}
+            ]}),
+            serde_json::json!({"tool_calls": [
+                {"index": 0, "function": {"arguments": "{\"path\":"}},
+                {"index": 1, "id": "call_b", "function": {"name": "bash"}}
+            ]}),
+            serde_json::json!({"tool_calls": [
+                {"index": 0, "function": {"arguments": "\"a.md\"}"}}
+            ]}),
+        ];
+
+        let mut accumulator = ToolCallAccumulator::default();
+        for chunk in &chunks {
+            for delta in openai_tool_call_deltas(chunk) {
+                accumulator.push(&delta);
+            }
+        }
+
+        let calls = accumulator.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_a");
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].arguments, r#"{"path":"a.md"}"#);
+        assert_eq!(calls[1].name, "bash");
+        assert_eq!(
+            calls[1].arguments, "{}",
+            "missing arguments become an empty object"
+        );
+    }
+
+    #[test]
+    fn test_gemini_format_groups_function_responses() {
+        let provider =
+            GeminiProvider::new("key", "https://example.com/v1beta/", "gemini-2.5-flash")
+                .expect("provider");
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+
+        let mut call = message(Role::Assistant, "");
+        call.tool_calls = Some(vec![
+            ToolCall {
+                id: "a".into(),
+                name: "read_file".into(),
+                arguments: r#"{"path":"a.md"}"#.into(),
+            },
+            ToolCall {
+                id: "b".into(),
+                name: "bash".into(),
+                arguments: r#"{"command":"ls"}"#.into(),
+            },
+        ]);
+        let mut result_a = message(Role::Tool, "contents");
+        result_a.tool_call_id = Some("a".into());
+        let mut result_b = message(Role::Tool, "listing");
+        result_b.tool_call_id = Some("b".into());
+
+        let (system, contents) = provider.format_messages(&[
+            message(Role::System, "Be brief."),
+            message(Role::User, "Look around"),
+            call,
+            result_a,
+            result_b,
+        ]);
+
+        assert_eq!(system.as_deref(), Some("Be brief."));
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(
+            contents[1]["parts"][0]["functionCall"]["args"]["path"],
+            "a.md"
+        );
+        let responses = contents[2]["parts"].as_array().expect("parts");
+        assert_eq!(responses.len(), 2, "parallel results share one turn");
+        assert_eq!(responses[1]["functionResponse"]["name"], "bash");
+        assert_eq!(
+            provider.endpoint("generateContent"),
+            "https://example.com/v1beta/models/gemini-2.5-flash:generateContent"
+        );
+
+        let schema = gemini_schema(&serde_json::json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": { "path": { "type": "string" } }
+        }));
+        assert!(schema.get("additionalProperties").is_none());
+        assert_eq!(schema["properties"]["path"]["type"], "string");
+    }
+
+    #[test]
+    fn test_think_tags_split_across_chunks() {
+        let mut splitter = ThinkTagSplitter::default();
+        let mut answer = String::new();
+        let mut reasoning = String::new();
+        for piece in [
+            "<thi",
+            "nk>Check the ",
+            "docs</th",
+            "ink>\n\nUse `cargo ",
+            "build`<",
+        ] {
+            let (a, r) = splitter.push(piece);
+            answer.push_str(&a);
+            reasoning.push_str(&r);
+        }
+        let (a, r) = splitter.finish();
+        answer.push_str(&a);
+        reasoning.push_str(&r);
+
+        assert_eq!(reasoning, "Check the docs");
+        assert_eq!(answer, "\n\nUse `cargo build`<");
+    }
+
+    #[test]
+    fn test_check_auth_detects_rejected_keys() {
+        let err = check_auth(
+            "openai",
+            "gpt-4o",
+            reqwest::StatusCode::UNAUTHORIZED,
+            r#"{"error":{"code":"invalid_api_key"}}"#,
+        )
+        .unwrap_err();
+        let auth = err.downcast_ref::<ProviderAuthError>().unwrap();
+        assert_eq!(auth.provider, "openai");
+        assert_eq!(auth.model, "openai/gpt-4o");
+
+        // Gemini reports bad keys as 400
+        assert!(check_auth(
+            "gemini",
+            "gemini-2.5-pro",
+            reqwest::StatusCode::BAD_REQUEST,
+            r#"{"error":{"details":[{"reason":"API_KEY_INVALID"}]}}"#,
+        )
+        .is_err());
+        assert!(check_auth(
+            "anthropic",
+            "claude-opus-4-5",
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            r#"{"error":{"type":"rate_limit_error"}}"#,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_deepseek_routing() {
+        let config = Config::default();
+        assert_eq!(
+            resolve_provider("deepseek-reasoner", &config),
+            ("deepseek".to_string(), "deepseek-reasoner".to_string())
+        );
+        assert_eq!(
+            resolve_provider("deepseek-r1", &config),
+            ("deepseek".to_string(), "deepseek-reasoner".to_string())
+        );
+        assert!(create_provider("deepseek/deepseek-chat", &config).is_err());
+    }
+
+    #[test]
+    fn test_custom_provider_routing() {
+        let mut config = Config::default();
+        config
+            .providers
+            .custom
+            .push(crate::config::CustomProviderConfig {
+                name: "lmstudio".to_string(),
+                base_url: "http://localhost:1234/v1".to_string(),
+                api_key: String::new(),
+                models: vec!["qwen2.5-7b-instruct".to_string()],
+                timeouts: Default::default(),
+                network: Default::default(),
+            });
+
+        assert_eq!(
+            resolve_provider("lmstudio/llama-3.2-3b", &config),
+            ("lmstudio".to_string(), "llama-3.2-3b".to_string())
+        );
+        // Listed models need no prefix; others still go through the usual guessing
+        assert_eq!(
+            resolve_provider("qwen2.5-7b-instruct", &config),
+            ("lmstudio".to_string(), "qwen2.5-7b-instruct".to_string())
+        );
+        assert_eq!(resolve_provider("gpt-4o", &config).0, "openai");
+        assert!(create_provider("lmstudio/llama-3.2-3b", &config).is_ok());
+    }
+
+    #[test]
+    fn test_gguf_routing() {
+        let config = Config::default();
+        assert_eq!(
+            resolve_provider("gguf/qwen2.5-7b-instruct-q4_k_m", &config),
+            ("gguf".to_string(), "qwen2.5-7b-instruct-q4_k_m".to_string())
+        );
+        assert_eq!(
+            resolve_provider("~/models/llama-3.2-3b.gguf", &config),
+            ("gguf".to_string(), "~/models/llama-3.2-3b.gguf".to_string())
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("tiny.gguf"), b"GGUF").unwrap();
+        let gguf = crate::config::GgufConfig {
+            model_dir: dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            gguf.resolve_model("tiny").unwrap(),
+            dir.path().join("tiny.gguf")
+        );
+        assert!(gguf.resolve_model("missing").is_err());
+        // Missing model file, or a build without the gguf feature
+        assert!(create_provider("gguf/missing", &config).is_err());
+    }
+
+    #[test]
+    fn test_ollama_tool_calls_round_trip() {
+        let response = json!({
+            "role": "assistant",
+            "content": "",
+            "tool_calls": [
+                { "function": { "name": "read_file", "arguments": { "path": "notes.md" } } }
+            ]
+        });
+        let calls = ollama_tool_calls(&response);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "read_file");
+        assert!(calls[0].id.starts_with("call_"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&calls[0].arguments).unwrap(),
+            json!({ "path": "notes.md" })
+        );
+
+        let provider = OllamaProvider::new("http://localhost:11434", "qwen2.5").unwrap();
+        let messages = vec![
+            Message {
+                role: Role::Assistant,
+                content: String::new(),
+                tool_calls: Some(calls.clone()),
+                tool_call_id: None,
+                images: Vec::new(),
+            },
+            Message {
+                role: Role::Tool,
+                content: "# Notes".to_string(),
+                tool_calls: None,
+                tool_call_id: Some(calls[0].id.clone()),
+                images: Vec::new(),
+            },
+        ];
+        let tools = [ToolSchema {
+            name: "read_file".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({ "type": "object" }),
+        }];
+        let body = provider.request_body(&messages, Some(&tools), false);
+
+        // Arguments go back as objects, and results name their tool
+        assert_eq!(
+            body["messages"][0]["tool_calls"][0]["function"]["arguments"],
+            json!({ "path": "notes.md" })
+        );
+        assert_eq!(body["messages"][1]["role"], "tool");
+        assert_eq!(body["messages"][1]["tool_name"], "read_file");
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+    }
+
+    #[tokio::test]
+    async fn test_hung_server_times_out() {
+        // Accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        });
+
+        let timeouts = TimeoutConfig {
+            timeout_secs: 1,
+            connect_timeout_secs: 1,
+        };
+        let mut provider =
+            OpenAIProvider::custom("lmstudio", "", &format!("http://{}/v1", addr), "m")
+                .unwrap()
+                .with_timeouts(&timeouts)
+                .unwrap();
+        provider.set_retry_policy(&RetryConfig {
+            max_attempts: 1,
+            ..Default::default()
+        });
+
+        let messages = vec![Message {
+            role: Role::User,
+            content: "hi".to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }];
+        let Err(err) = provider.chat(&messages, None).await else {
+            panic!("expected a timeout");
+        };
+        let timeout = err.downcast_ref::<ProviderTimeoutError>().unwrap();
+        assert_eq!(timeout.provider, "lmstudio");
+        assert!(!timeout.connect);
+        assert_eq!(timeout.secs, 1);
+
+        // Timeouts are set per provider section, flattened into it
+        let config: crate::config::Config = toml::from_str(
+            "[providers.ollama]\nendpoint = \"http://localhost:11434\"\ntimeout_secs = 900\n",
+        )
+        .unwrap();
+        let ollama = config.providers.ollama.unwrap();
+        assert_eq!(ollama.timeouts.timeout_secs, 900);
+        assert_eq!(ollama.timeouts.connect_timeout_secs, 10);
+    }
+
+    #[test]
+    fn test_usage_parsing() {
+        let openai = openai_usage(&json!({"prompt_tokens": 12, "completion_tokens": 3})).unwrap();
+        assert_eq!((openai.input_tokens, openai.output_tokens), (12, 3));
+        // Streamed chunks without usage carry `"usage": null`
+        assert!(openai_usage(&Value::Null).is_none());
+
+        let ollama =
+            ollama_usage(&json!({"done": true, "prompt_eval_count": 30, "eval_count": 7})).unwrap();
+        assert_eq!((ollama.input_tokens, ollama.output_tokens), (30, 7));
+        assert!(ollama_usage(&json!({"done": false})).is_none());
+
+        let gemini = gemini_usage(&json!({
+            "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 9}
+        }))
+        .unwrap();
+        assert_eq!((gemini.input_tokens, gemini.output_tokens), (5, 9));
+    }
+
+    #[test]
+    fn test_model_list_parsing() {
+        let body = json!({
+            "data": [
+                {"id": "gpt-4o"},
+                {"id": "text-embedding-3-small"},
+                {"id": "gpt-4o-realtime-preview"},
+                {"id": "o3-mini"},
+                {"id": "dall-e-3"}
+            ]
+        });
+        assert_eq!(
+            openai_model_names("openai", &body),
+            vec!["openai/gpt-4o", "openai/o3-mini"]
+        );
+        // Other OpenAI-compatible servers are listed as-is under their name
+        assert_eq!(openai_model_names("lmstudio", &body).len(), 5);
+        assert!(openai_model_names("lmstudio", &body).contains(&"lmstudio/dall-e-3".to_string()));
+
+        let body = json!({
+            "models": [
+                {"name": "models/gemini-2.5-pro", "supportedGenerationMethods": ["generateContent", "countTokens"]},
+                {"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]}
+            ]
+        });
+        assert_eq!(gemini_model_names(&body), vec!["gemini/gemini-2.5-pro"]);
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_configured_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A forward proxy sees the absolute target URL in the request line
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let n = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"choices":[{"message":{"content":"via proxy"}}]}"#;
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let config: crate::config::Config = toml::from_str(&format!(
+            "[[providers.custom]]\nname = \"corp\"\nbase_url = \"http://llm.corp.invalid/v1\"\n\
+             proxy = \"http://{}\"\n",
+            addr
+        ))
+        .unwrap();
+        let custom = &config.providers.custom[0];
+        assert_eq!(custom.network.no_proxy, None);
+        let provider = OpenAIProvider::custom("corp", "", &custom.base_url, "m")
+            .unwrap()
+            .with_network(&custom.network)
+            .unwrap();
+
+        let messages = vec![Message {
+            role: Role::User,
+            content: "hi".to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }];
+        let response = provider.chat(&messages, None).await.unwrap();
+        assert!(matches!(response.content, LLMResponseContent::Text(ref t) if t == "via proxy"));
+        let request = proxy.await.unwrap();
+        assert!(request.starts_with("POST http://llm.corp.invalid/v1/chat/completions"));
+
+        // Bad settings fail when the provider is built, not on first use
+        let bad_proxy = NetworkConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(http_client(&TimeoutConfig::default(), &bad_proxy).is_err());
+        let missing_ca = NetworkConfig {
+            ca_cert: Some("/nonexistent/corp-ca.pem".to_string()),
+            ..Default::default()
+        };
+        let err = http_client(&TimeoutConfig::default(), &missing_ca).unwrap_err();
+        assert!(err.to_string().contains("ca_cert"));
+    }
+
+    #[test]
+    fn test_openai_reasoning_model_requests() {
+        let messages = vec![
+            Message {
+                role: Role::System,
+                content: "Be brief".to_string(),
+                tool_calls: None,
+                tool_call_id: None,
+                images: Vec::new(),
+            },
+            Message {
+                role: Role::User,
+                content: "hi".to_string(),
+                tool_calls: None,
+                tool_call_id: None,
+                images: Vec::new(),
+            },
+        ];
+        let params = GenerationParams {
+            temperature: Some(0.2),
+            max_tokens: Some(500),
+        };
+
+        let mut provider = OpenAIProvider::new("sk-test", "https://api.openai.com/v1", "o3-mini")
+            .unwrap()
+            .with_reasoning_effort(Some("high"));
+        provider.set_generation_params(&params);
+        let body = provider.request_body(&messages, None);
+        assert_eq!(body["messages"][0]["role"], "developer");
+        assert_eq!(body["max_completion_tokens"], 500);
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("max_tokens").is_none());
+
+        // Other models keep the classic parameters and ignore the effort
+        let mut provider = OpenAIProvider::new("sk-test", "https://api.openai.com/v1", "gpt-4o")
+            .unwrap()
+            .with_reasoning_effort(Some("high"));
+        provider.set_generation_params(&params);
+        let body = provider.request_body(&messages, None);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["max_tokens"], 500);
+        assert!(body.get("reasoning_effort").is_none());
+
+        let usage = openai_usage(&json!({
+            "prompt_tokens": 20,
+            "completion_tokens": 300,
+            "completion_tokens_details": { "reasoning_tokens": 256 }
+        }))
+        .unwrap();
+        assert_eq!((usage.output_tokens, usage.reasoning_tokens), (300, 256));
+    }
+
+    #[test]
+    fn test_anthropic_thinking_requests() {
+        let user = Message {
+            role: Role::User,
+            content: "List the notes".to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let call = ToolCall {
+            id: "toolu_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: r#"{"path":"notes.md"}"#.to_string(),
+        };
+        let tool_turn = vec![
+            user.clone(),
+            Message {
+                role: Role::Assistant,
+                content: String::new(),
+                tool_calls: Some(vec![call.clone()]),
+                tool_call_id: None,
+                images: Vec::new(),
+            },
+            Message {
+                role: Role::Tool,
+                content: "# Notes".to_string(),
+                tool_calls: None,
+                tool_call_id: Some("toolu_1".to_string()),
+                images: Vec::new(),
+            },
+        ];
+
+        let mut provider =
+            AnthropicProvider::new("sk-ant-test", "https://api.anthropic.com", "claude", 4096)
+                .unwrap()
+                .with_thinking(500);
+        provider.set_generation_params(&GenerationParams {
+            temperature: Some(0.2),
+            max_tokens: None,
+        });
+        let body = provider.request_body(std::slice::from_ref(&user), None);
+        assert_eq!(body["thinking"]["budget_tokens"], 1024);
+        assert_eq!(body["max_tokens"], 4096 + 1024);
+        assert!(body.get("temperature").is_none());
+
+        // A tool turn without its thinking blocks continues without thinking
+        let body = provider.request_body(&tool_turn, None);
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["temperature"], json!(0.2f32));
+
+        // With them, the blocks go back ahead of the tool calls
+        let block = json!({ "type": "thinking", "thinking": "Read it", "signature": "sig" });
+        keep_thinking_blocks(
+            &provider.thinking_blocks,
+            std::slice::from_ref(&call),
+            vec![block.clone()],
+        );
+        let body = provider.request_body(&tool_turn, None);
+        assert_eq!(body["thinking"]["type"], "enabled");
+        assert_eq!(body["messages"][1]["content"][0], block);
+        assert_eq!(body["messages"][1]["content"][1]["type"], "tool_use");
+    }
+
+    #[test]
+    fn test_claude_cli_tool_events() {
+        let mut names = HashMap::new();
+        let started = json!({
+            "type": "assistant",
+            "message": {"content": [
+                {"type": "text", "text": "Checking"},
+                {"type": "tool_use", "id": "toolu_1", "name": "Bash",
+                 "input": {"command": "cargo test"}},
+                {"type": "tool_use", "id": "toolu_2", "name": "Edit",
+                 "input": {"file_path": "src/lib.rs", "old_string": "a", "new_string": "b"}}
+            ]}
+        });
+        let events = claude_cli_tool_events(&started, &mut names);
+        assert_eq!(
+            events,
+            vec![
+                ProviderToolEvent::Started {
+                    id: "toolu_1".to_string(),
+                    name: "Bash".to_string(),
+                    detail: Some("cargo test".to_string()),
+                },
+                ProviderToolEvent::Started {
+                    id: "toolu_2".to_string(),
+                    name: "Edit".to_string(),
+                    detail: Some("src/lib.rs".to_string()),
+                },
+            ]
+        );
+        // Partial messages repeat blocks already reported
+        assert!(claude_cli_tool_events(&started, &mut names).is_empty());
+
+        let finished = json!({
+            "type": "user",
+            "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "is_error": true,
+                 "content": [{"type": "text", "text": "error: 1 test failed"}]},
+                {"type": "tool_result", "tool_use_id": "toolu_2", "content": "Edited src/lib.rs"}
+            ]}
+        });
+        let events = claude_cli_tool_events(&finished, &mut names);
+        assert_eq!(
+            events[0],
+            ProviderToolEvent::Finished {
+                id: "toolu_1".to_string(),
+                name: "Bash".to_string(),
+                output: "error: 1 test failed".to_string(),
+                is_error: true,
+            }
+        );
+        assert!(matches!(
+            &events[1],
+            ProviderToolEvent::Finished { name, is_error: false, .. } if name == "Edit"
+        ));
+
+        let long = "x".repeat(80);
+        let detail = claude_cli_tool_detail("Bash", &json!({ "command": long })).unwrap();
+        assert_eq!(detail.chars().count(), 60);
+        assert_eq!(claude_cli_tool_detail("TodoWrite", &json!({})), None);
+    }
 }
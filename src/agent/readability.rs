@@ -0,0 +1,810 @@
+//! Main-content extraction from web pages, as Markdown
+//!
+//! A forgiving HTML parser builds a small element tree, dropping scripts,
+//! styles and comments. As in Mozilla's Readability, each block of text
+//! scores its parent (in full) and grandparent (by half) by length and
+//! commas; navigation, sidebars, footers and other boilerplate (by tag,
+//! role, class or id) score nothing, and link-heavy candidates lose score.
+//! The best candidate, plus siblings that score close to it, is converted
+//! to Markdown with links made absolute.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+
+/// Elements whose raw content is skipped up to their closing tag
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "svg", "textarea"];
+
+/// Elements that never have content
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements left out of the article along with everything inside them
+const BOILERPLATE_ELEMENTS: &[&str] = &[
+    "head", "nav", "footer", "aside", "form", "button", "select", "menu", "dialog", "iframe",
+    "object",
+];
+
+/// Elements that end an open paragraph
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Text blocks shorter than this don't score their ancestors
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([A-Za-z_:][-A-Za-z0-9_:.]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#)
+        .unwrap()
+});
+static UNLIKELY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)banner|breadcrumb|combx|comment|community|cookie|disqus|extra|foot|header|legends|menu|modal|nav|newsletter|pager|pagination|popup|promo|related|remark|replies|rss|share|shoutbox|sidebar|skyscraper|social|sponsor|subscribe|tags|tool|widget|ad-|advert").unwrap()
+});
+static LIKELY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)and|article|body|column|content|main|shadow|story|entry|post|text|blog")
+        .unwrap()
+});
+static POSITIVE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)article|body|content|entry|hentry|h-entry|main|page|post|text|blog|story")
+        .unwrap()
+});
+static NEGATIVE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)hidden|banner|combx|comment|com-|contact|foot|footer|footnote|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|tool|widget").unwrap()
+});
+
+/// The readable part of a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Article {
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+/// Extract the main content of `html`, resolving links against `base`
+pub fn extract(html: &str, base: &Url) -> Article {
+    let doc = Document::parse(html);
+    let title = TITLE
+        .captures(html)
+        .map(|c| collapse_whitespace(&decode_entities(&c[1])))
+        .filter(|t| !t.is_empty());
+
+    let roots = doc.article_roots();
+    let mut out = Markdown::new(base);
+    for root in roots {
+        doc.render(root, &mut out);
+        out.block();
+    }
+    Article {
+        title,
+        markdown: out.finish(),
+    }
+}
+
+enum Node {
+    Element(usize),
+    Text(String),
+}
+
+struct Element {
+    tag: String,
+    /// class and id, for boilerplate detection
+    class_id: String,
+    role: String,
+    href: Option<String>,
+    src: Option<String>,
+    alt: Option<String>,
+    parent: usize,
+    children: Vec<Node>,
+}
+
+struct Document {
+    /// Element 0 is the root
+    elements: Vec<Element>,
+}
+
+impl Document {
+    fn parse(html: &str) -> Self {
+        let mut doc = Self {
+            elements: vec![Element::new("#root", 0)],
+        };
+        let mut stack = vec![0];
+        let mut rest = html;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('<') else {
+                doc.add_text(&stack, rest);
+                break;
+            };
+            doc.add_text(&stack, &rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let next = rest[1..].chars().next().unwrap_or(' ');
+            if next == '!' || next == '?' {
+                rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+                continue;
+            }
+            if !(next.is_ascii_alphabetic() || next == '/') {
+                // A literal '<'
+                doc.add_text(&stack, "<");
+                rest = &rest[1..];
+                continue;
+            }
+            let Some(end) = tag_end(rest) else {
+                break;
+            };
+            let source = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            if let Some(name) = source.strip_prefix('/') {
+                let name = name.trim().to_ascii_lowercase();
+                if let Some(open) = stack.iter().rposition(|&e| doc.elements[e].tag == name) {
+                    stack.truncate(open.max(1));
+                }
+                continue;
+            }
+
+            let name_len = source
+                .find(|c: char| c.is_whitespace() || c == '/')
+                .unwrap_or(source.len());
+            let tag = source[..name_len].to_ascii_lowercase();
+            if RAW_TEXT_ELEMENTS.contains(&tag.as_str()) {
+                let close = format!("</{}", tag);
+                rest = find_ignore_case(rest, &close).map_or("", |at| {
+                    rest[at..].find('>').map_or("", |e| &rest[at + e + 1..])
+                });
+                continue;
+            }
+            doc.close_implied(&mut stack, &tag);
+
+            let parent = *stack.last().unwrap_or(&0);
+            let mut element = Element::new(&tag, parent);
+            element.set_attributes(&source[name_len..]);
+            let index = doc.elements.len();
+            doc.elements.push(element);
+            doc.elements[parent].children.push(Node::Element(index));
+            if !VOID_ELEMENTS.contains(&tag.as_str()) && !source.ends_with('/') {
+                stack.push(index);
+            }
+        }
+        doc
+    }
+
+    fn add_text(&mut self, stack: &[usize], text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let parent = *stack.last().unwrap_or(&0);
+        self.elements[parent]
+            .children
+            .push(Node::Text(decode_entities(text)));
+    }
+
+    /// Close elements that a new `tag` ends without a closing tag, like a
+    /// paragraph followed by a block, or a list item by the next item
+    fn close_implied(&self, stack: &mut Vec<usize>, tag: &str) {
+        let top = |stack: &[usize]| self.elements[*stack.last().unwrap_or(&0)].tag.clone();
+        if BLOCK_ELEMENTS.contains(&tag) && top(stack) == "p" {
+            stack.pop();
+        }
+        let (same, scope): (&[&str], &[&str]) = match tag {
+            "li" => (&["li"], &["ul", "ol"]),
+            "dt" | "dd" => (&["dt", "dd"], &["dl"]),
+            "tr" => (&["tr"], &["table", "tbody", "thead", "tfoot"]),
+            "td" | "th" => (&["td", "th"], &["tr", "table"]),
+            "option" => (&["option"], &["select"]),
+            _ => return,
+        };
+        for i in (1..stack.len()).rev() {
+            let open = self.elements[stack[i]].tag.as_str();
+            if scope.contains(&open) {
+                return;
+            }
+            if same.contains(&open) {
+                stack.truncate(i);
+                return;
+            }
+        }
+    }
+
+    /// Text of an element and everything inside it, whitespace collapsed
+    fn text(&self, index: usize) -> String {
+        let mut text = String::new();
+        self.collect_text(index, &mut text);
+        collapse_whitespace(&text)
+    }
+
+    fn collect_text(&self, index: usize, text: &mut String) {
+        for child in &self.elements[index].children {
+            match child {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(e) => {
+                    text.push(' ');
+                    self.collect_text(*e, text);
+                }
+            }
+        }
+    }
+
+    /// Characters of text inside links, relative to all text
+    fn link_density(&self, index: usize) -> f64 {
+        let total = self.text(index).chars().count();
+        if total == 0 {
+            return 0.0;
+        }
+        let mut links = 0;
+        let mut stack = vec![index];
+        while let Some(e) = stack.pop() {
+            for child in &self.elements[e].children {
+                if let Node::Element(c) = child {
+                    if self.elements[*c].tag == "a" {
+                        links += self.text(*c).chars().count();
+                    } else {
+                        stack.push(*c);
+                    }
+                }
+            }
+        }
+        links as f64 / total as f64
+    }
+
+    fn is_boilerplate(&self, index: usize) -> bool {
+        let element = &self.elements[index];
+        if BOILERPLATE_ELEMENTS.contains(&element.tag.as_str()) {
+            return true;
+        }
+        if matches!(
+            element.role.as_str(),
+            "navigation" | "banner" | "contentinfo" | "complementary" | "menu" | "dialog"
+        ) {
+            return true;
+        }
+        element.tag != "body"
+            && element.tag != "article"
+            && element.tag != "main"
+            && UNLIKELY.is_match(&element.class_id)
+            && !LIKELY.is_match(&element.class_id)
+    }
+
+    /// Whether an element holds text directly rather than other blocks
+    fn is_paragraph(&self, index: usize) -> bool {
+        let element = &self.elements[index];
+        match element.tag.as_str() {
+            "p" | "pre" | "td" | "blockquote" => true,
+            "div" | "section" => !element.children.iter().any(|c| {
+                matches!(c, Node::Element(e) if BLOCK_ELEMENTS.contains(&self.elements[*e].tag.as_str()))
+            }),
+            _ => false,
+        }
+    }
+
+    /// Starting score of a candidate, by tag and class/id
+    fn base_score(&self, index: usize) -> f64 {
+        let element = &self.elements[index];
+        let tag = match element.tag.as_str() {
+            "article" | "main" => 10.0,
+            "div" | "section" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+        let mut class = 0.0;
+        if POSITIVE.is_match(&element.class_id) {
+            class += 25.0;
+        }
+        if NEGATIVE.is_match(&element.class_id) {
+            class -= 25.0;
+        }
+        tag + class
+    }
+
+    /// The element(s) holding the article, in document order
+    fn article_roots(&self) -> Vec<usize> {
+        // Elements outside boilerplate, found from the root down
+        let mut kept = Vec::new();
+        let mut stack = vec![0];
+        while let Some(e) = stack.pop() {
+            kept.push(e);
+            for child in self.elements[e].children.iter().rev() {
+                if let Node::Element(c) = child {
+                    if !self.is_boilerplate(*c) {
+                        stack.push(*c);
+                    }
+                }
+            }
+        }
+
+        let mut scores: Vec<Option<f64>> = vec![None; self.elements.len()];
+        for &e in &kept {
+            if e == 0 || !self.is_paragraph(e) {
+                continue;
+            }
+            let text = self.text(e);
+            let len = text.chars().count();
+            if len < MIN_PARAGRAPH_CHARS {
+                continue;
+            }
+            let score = 1.0 + text.matches(',').count() as f64 + (len as f64 / 100.0).min(3.0);
+            let parent = self.elements[e].parent;
+            let grandparent = self.elements[parent].parent;
+            for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+                if ancestor == 0 {
+                    continue;
+                }
+                let entry = scores[ancestor].get_or_insert_with(|| self.base_score(ancestor));
+                *entry += score * share;
+            }
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for (e, score) in scores.iter_mut().enumerate() {
+            if let Some(score) = score {
+                *score *= 1.0 - self.link_density(e);
+                if best.is_none_or(|(_, top)| *score > top) {
+                    best = Some((e, *score));
+                }
+            }
+        }
+        let Some((top, top_score)) = best else {
+            // Nothing scored: everything that isn't boilerplate
+            return vec![0];
+        };
+
+        // Siblings that look like part of the same article
+        let parent = self.elements[top].parent;
+        let threshold = (top_score * 0.2).max(10.0);
+        let top_class = &self.elements[top].class_id;
+        self.elements[parent]
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Element(e) if !self.is_boilerplate(*e) => Some(*e),
+                _ => None,
+            })
+            .filter(|&e| {
+                if e == top {
+                    return true;
+                }
+                let mut score = scores[e].unwrap_or(0.0);
+                if !top_class.is_empty() && self.elements[e].class_id == *top_class {
+                    score += top_score * 0.2;
+                }
+                if score >= threshold {
+                    return true;
+                }
+                if self.elements[e].tag != "p" {
+                    return false;
+                }
+                let text = self.text(e);
+                let density = self.link_density(e);
+                let len = text.chars().count();
+                (len > 80 && density < 0.25) || (len > 0 && density == 0.0 && text.ends_with('.'))
+            })
+            .collect()
+    }
+
+    fn render(&self, index: usize, out: &mut Markdown) {
+        let element = &self.elements[index];
+        match element.tag.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = element.tag[1..].parse().unwrap_or(1);
+                let text = self.render_inline(index, out);
+                if !text.is_empty() {
+                    out.block();
+                    out.raw(&format!("{} {}", "#".repeat(level), text));
+                    out.block();
+                }
+            }
+            "p" | "div" | "section" | "article" | "main" | "header" | "figure" | "dl" => {
+                if out.lists.is_empty() {
+                    out.block();
+                }
+                self.render_children(index, out);
+                if out.lists.is_empty() {
+                    out.block();
+                }
+            }
+            "br" => out.raw("\n"),
+            "hr" => {
+                out.block();
+                out.raw("---");
+                out.block();
+            }
+            "pre" => {
+                let mut code = String::new();
+                self.collect_text(index, &mut code);
+                out.block();
+                out.raw(&format!("```\n{}\n```", code.trim_matches('\n')));
+                out.block();
+            }
+            "blockquote" => {
+                let mut inner = out.nested();
+                self.render_children(index, &mut inner);
+                let quoted: Vec<String> = inner
+                    .finish()
+                    .lines()
+                    .map(|line| format!("> {}", line).trim_end().to_string())
+                    .collect();
+                out.block();
+                out.raw(&quoted.join("\n"));
+                out.block();
+            }
+            "ul" | "ol" => {
+                if out.lists.is_empty() {
+                    out.block();
+                }
+                out.lists.push((element.tag == "ol").then_some(1));
+                self.render_children(index, out);
+                out.lists.pop();
+                if out.lists.is_empty() {
+                    out.block();
+                } else {
+                    out.line();
+                }
+            }
+            "li" => {
+                out.line();
+                let depth = out.lists.len().max(1);
+                let marker = match out.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => "-".to_string(),
+                };
+                out.raw(&format!("{}{} ", "  ".repeat(depth - 1), marker));
+                self.render_children(index, out);
+            }
+            "tr" => {
+                let cells: Vec<String> = element
+                    .children
+                    .iter()
+                    .filter_map(|c| match c {
+                        Node::Element(e)
+                            if matches!(self.elements[*e].tag.as_str(), "td" | "th") =>
+                        {
+                            Some(self.render_inline(*e, out).replace('|', "\\|"))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                if !cells.is_empty() {
+                    out.line();
+                    out.raw(&format!("| {} |", cells.join(" | ")));
+                    if !out.table_started {
+                        out.raw(&format!("\n|{}", "---|".repeat(cells.len())));
+                        out.table_started = true;
+                    }
+                    out.line();
+                }
+            }
+            "table" => {
+                out.block();
+                out.table_started = false;
+                self.render_children(index, out);
+                out.block();
+            }
+            "a" => {
+                let text = self.render_inline(index, out);
+                match element.href.as_deref().and_then(|h| out.absolute(h)) {
+                    Some(url) if !text.is_empty() => out.text(&format!("[{}]({})", text, url)),
+                    _ => out.text(&text),
+                }
+            }
+            "img" => {
+                if let Some(url) = element.src.as_deref().and_then(|s| out.absolute(s)) {
+                    let alt = element.alt.as_deref().unwrap_or("").trim();
+                    out.text(&format!("![{}]({})", alt, url));
+                }
+            }
+            "strong" | "b" => self.render_wrapped(index, out, "**"),
+            "em" | "i" => self.render_wrapped(index, out, "*"),
+            "code" | "kbd" | "samp" => self.render_wrapped(index, out, "`"),
+            _ => self.render_children(index, out),
+        }
+    }
+
+    fn render_children(&self, index: usize, out: &mut Markdown) {
+        for child in &self.elements[index].children {
+            match child {
+                Node::Text(text) => out.text(text),
+                Node::Element(e) if !self.is_boilerplate(*e) => self.render(*e, out),
+                Node::Element(_) => {}
+            }
+        }
+    }
+
+    /// An element's content as one line of Markdown
+    fn render_inline(&self, index: usize, out: &Markdown) -> String {
+        let mut inner = out.nested();
+        self.render_children(index, &mut inner);
+        collapse_whitespace(&inner.finish())
+    }
+
+    fn render_wrapped(&self, index: usize, out: &mut Markdown, marker: &str) {
+        let text = self.render_inline(index, out);
+        if !text.is_empty() {
+            out.text(&format!("{}{}{}", marker, text, marker));
+        }
+    }
+}
+
+impl Element {
+    fn new(tag: &str, parent: usize) -> Self {
+        Self {
+            tag: tag.to_string(),
+            class_id: String::new(),
+            role: String::new(),
+            href: None,
+            src: None,
+            alt: None,
+            parent,
+            children: Vec::new(),
+        }
+    }
+
+    fn set_attributes(&mut self, source: &str) {
+        for attribute in ATTRIBUTE.captures_iter(source) {
+            let value = attribute
+                .get(2)
+                .or_else(|| attribute.get(3))
+                .or_else(|| attribute.get(4))
+                .map(|v| decode_entities(v.as_str()))
+                .unwrap_or_default();
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "class" | "id" => {
+                    self.class_id.push(' ');
+                    self.class_id.push_str(&value);
+                }
+                "role" => self.role = value.to_ascii_lowercase(),
+                "href" => self.href = Some(value),
+                "src" => self.src = Some(value),
+                "alt" => self.alt = Some(value),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Markdown being written, with the state of open lists and tables
+struct Markdown<'a> {
+    base: &'a Url,
+    text: String,
+    /// Open lists, innermost last: None for bullets, else the next number
+    lists: Vec<Option<usize>>,
+    table_started: bool,
+}
+
+impl<'a> Markdown<'a> {
+    fn new(base: &'a Url) -> Self {
+        Self {
+            base,
+            text: String::new(),
+            lists: Vec::new(),
+            table_started: false,
+        }
+    }
+
+    /// Writer for content that is wrapped afterwards (links, quotes)
+    fn nested(&self) -> Self {
+        Self::new(self.base)
+    }
+
+    /// Start a new paragraph
+    fn block(&mut self) {
+        self.trim_end_spaces();
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") {
+            self.text.push_str(if self.text.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            });
+        }
+    }
+
+    /// Start a new line
+    fn line(&mut self) {
+        self.trim_end_spaces();
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn trim_end_spaces(&mut self) {
+        let len = self.text.trim_end_matches(' ').len();
+        self.text.truncate(len);
+    }
+
+    /// Markup written as is
+    fn raw(&mut self, markdown: &str) {
+        self.text.push_str(markdown);
+    }
+
+    /// Running text, whitespace collapsed into single spaces
+    fn text(&mut self, text: &str) {
+        let words = collapse_whitespace(text);
+        let at_line_start = self.text.is_empty() || self.text.ends_with('\n');
+        if text.starts_with(char::is_whitespace) && !at_line_start && !self.text.ends_with(' ') {
+            self.text.push(' ');
+        }
+        self.text.push_str(&words);
+        if text.ends_with(char::is_whitespace) && !words.is_empty() {
+            self.text.push(' ');
+        }
+    }
+
+    /// An http(s) URL for a link or image
+    fn absolute(&self, url: &str) -> Option<String> {
+        let url = self.base.join(url.trim()).ok()?;
+        matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        let mut blank = 0;
+        for line in self.text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank += 1;
+                continue;
+            }
+            if !out.is_empty() {
+                out.push_str(if blank > 0 { "\n\n" } else { "\n" });
+            }
+            blank = 0;
+            out.push_str(line);
+        }
+        out
+    }
+}
+
+/// End of the tag starting at `html[0]` ('>' outside quotes)
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decode character references and the common named entities
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..end + 1];
+                let c = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    "hellip" => Some('…'),
+                    "lsquo" => Some('‘'),
+                    "rsquo" => Some('’'),
+                    "ldquo" => Some('“'),
+                    "rdquo" => Some('”'),
+                    "copy" => Some('©'),
+                    "deg" => Some('°'),
+                    _ => {
+                        let code = match name.strip_prefix('#') {
+                            Some(hex) if hex.starts_with(['x', 'X']) => {
+                                u32::from_str_radix(&hex[1..], 16).ok()
+                            }
+                            Some(decimal) => decimal.parse().ok(),
+                            None => None,
+                        };
+                        code.and_then(char::from_u32)
+                    }
+                };
+                c.map(|c| (c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_article() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Baking bread &amp; more</title>
+<style>body { color: red }</style><script>var x = "<p>not text</p>";</script></head>
+<body>
+<nav><ul><li><a href="/">Home</a></li><li><a href="/about">About</a></li></ul></nav>
+<div class="sidebar"><p>Subscribe to our newsletter, it is great, really, truly great.</p></div>
+<div id="content" class="post">
+  <h1>How to bake bread</h1>
+  <p>Bread needs flour, water, salt and yeast. Mix them, knead the dough, and let it rise for an hour or two.
+  <p>Bake at 230&deg;C until the crust is <b>deep brown</b>, then let it cool. See <a href="/tips">our tips</a>, too.</p>
+  <ul><li>Use a hot oven<li>Add steam</ul>
+  <pre>flour = 500g
+water = 350g</pre>
+</div>
+<footer><p>Copyright 2024, all rights reserved, every single one of them.</p></footer>
+</body></html>"#;
+        let base = Url::parse("https://example.com/recipes/bread").unwrap();
+        let article = extract(html, &base);
+        assert_eq!(article.title.as_deref(), Some("Baking bread & more"));
+        assert_eq!(
+            article.markdown,
+            "# How to bake bread\n\n\
+             Bread needs flour, water, salt and yeast. Mix them, knead the dough, and let it rise for an hour or two.\n\n\
+             Bake at 230°C until the crust is **deep brown**, then let it cool. See [our tips](https://example.com/tips), too.\n\n\
+             - Use a hot oven\n\
+             - Add steam\n\n\
+             ```\nflour = 500g\nwater = 350g\n```"
+        );
+    }
+}
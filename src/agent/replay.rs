@@ -0,0 +1,467 @@
+//! Canned replies for tests and demos
+//!
+//! A fixture is a JSONL file with one reply per line:
+//!
+//! ```text
+//! {"text": "Hello! How can I help?"}
+//! {"match": "weather", "tool_calls": [{"id": "1", "name": "web_fetch", "arguments": "{}"}]}
+//! {"text": "Slow reply for a demo", "delay_ms": 50}
+//! {"error": "rate limited"}
+//! ```
+//!
+//! `mock/<fixture>` serves the replies in order, skipping ones whose `match`
+//! isn't in the last user message. `[providers.replay] record = "<file>"`
+//! appends every real reply to a fixture, and `replay = "<file>"` answers
+//! every model from it without touching the network. Replies are shared
+//! process-wide per file, so the main, summary and title providers together
+//! replay the recorded order.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+
+use super::providers::{
+    GenerationParams, LLMProvider, LLMResponse, LLMResponseContent, Message, Role, StreamChunk,
+    StreamResult, ToolCall, ToolSchema, Usage,
+};
+use crate::config::RetryConfig;
+
+/// One line of a fixture
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FixtureReply {
+    /// Model that gave the reply; replay only serves it to that model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Only answer when the last user message contains this
+    #[serde(default, rename = "match", skip_serializing_if = "Option::is_none")]
+    pub matches: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Fail the request with this message instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Pause between streamed words
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+}
+
+/// A loaded fixture and which replies have been served
+struct Fixture {
+    path: PathBuf,
+    replies: Vec<FixtureReply>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl Fixture {
+    fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fixture {}", path.display()))?;
+        let replies = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("{} line {}", path.display(), n + 1))
+            })
+            .collect::<Result<Vec<FixtureReply>>>()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            used: Mutex::new(vec![false; replies.len()]),
+            replies,
+        })
+    }
+
+    /// Take the first unserved reply for `model` that matches `prompt`
+    fn next(&self, model: Option<&str>, prompt: &str) -> Result<FixtureReply> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let found = self.replies.iter().enumerate().find(|(i, reply)| {
+            !used[*i]
+                && match (model, reply.model.as_deref()) {
+                    (Some(model), Some(recorded)) => model == recorded,
+                    _ => true,
+                }
+                && reply
+                    .matches
+                    .as_deref()
+                    .is_none_or(|needle| prompt.contains(needle))
+        });
+        let Some((i, reply)) = found else {
+            anyhow::bail!(
+                "Fixture {} has no reply left for: {}",
+                self.path.display(),
+                prompt.chars().take(80).collect::<String>()
+            );
+        };
+        used[i] = true;
+        Ok(reply.clone())
+    }
+}
+
+/// Fixtures loaded so far, so providers on the same file share one order
+fn fixture(path: &str) -> Result<Arc<Fixture>> {
+    static FIXTURES: OnceLock<Mutex<HashMap<PathBuf, Arc<Fixture>>>> = OnceLock::new();
+    let path = PathBuf::from(shellexpand::tilde(path).to_string());
+    let mut fixtures = FIXTURES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(fixture) = fixtures.get(&path) {
+        return Ok(Arc::clone(fixture));
+    }
+    let fixture = Arc::new(Fixture::load(&path)?);
+    fixtures.insert(path, Arc::clone(&fixture));
+    Ok(fixture)
+}
+
+/// Text of the last user message, for `match`
+fn last_prompt(messages: &[Message]) -> &str {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == Role::User)
+        .map(|m| m.content.as_str())
+        .unwrap_or_default()
+}
+
+/// Provider that answers from a fixture file
+pub struct MockProvider {
+    fixture: Arc<Fixture>,
+    /// Only serve replies recorded for this model (replay)
+    model: Option<String>,
+}
+
+impl MockProvider {
+    /// `mock/<fixture>`: every reply in the file, in order
+    pub fn open(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            anyhow::bail!("Mock model needs a fixture file: mock/<path to .jsonl>");
+        }
+        Ok(Self {
+            fixture: fixture(path)?,
+            model: None,
+        })
+    }
+
+    /// `[providers.replay] replay`: the replies recorded for `model`
+    pub fn replay(path: &str, model: &str) -> Result<Self> {
+        Ok(Self {
+            fixture: fixture(path)?,
+            model: Some(model.to_string()),
+        })
+    }
+
+    fn next(&self, messages: &[Message]) -> Result<FixtureReply> {
+        let reply = self
+            .fixture
+            .next(self.model.as_deref(), last_prompt(messages))?;
+        if let Some(ref error) = reply.error {
+            anyhow::bail!("{}", error);
+        }
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let reply = self.next(messages)?;
+        let content = match reply.tool_calls {
+            Some(calls) if !calls.is_empty() => LLMResponseContent::ToolCalls(calls),
+            _ => LLMResponseContent::Text(reply.text.unwrap_or_default()),
+        };
+        Ok(LLMResponse {
+            content,
+            usage: reply.usage,
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        _tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let reply = self.next(messages)?;
+        let delay = Duration::from_millis(reply.delay_ms.unwrap_or(0));
+        let text = reply.text.unwrap_or_default();
+        let words: Vec<String> = text.split_inclusive(' ').map(str::to_string).collect();
+        let done = StreamChunk {
+            delta: String::new(),
+            done: true,
+            tool_calls: reply.tool_calls,
+            tool_call_deltas: Vec::new(),
+            provider_tools: Vec::new(),
+            reasoning: String::new(),
+            usage: reply.usage,
+        };
+        Ok(Box::pin(async_stream::stream! {
+            for word in words {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                yield Ok(StreamChunk {
+                    delta: word,
+                    done: false,
+                    tool_calls: None,
+                    tool_call_deltas: Vec::new(),
+                    provider_tools: Vec::new(),
+                    reasoning: String::new(),
+                    usage: None,
+                });
+            }
+            yield Ok(done);
+        }))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        Ok(format!(
+            "{} replies in {}",
+            self.fixture.replies.len(),
+            self.fixture.path.display()
+        ))
+    }
+}
+
+/// Serializes appends to fixture files
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Provider wrapper that appends every reply to a fixture file
+pub struct RecordingProvider {
+    inner: Box<dyn LLMProvider>,
+    model: String,
+    path: PathBuf,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, model: &str, path: &str) -> Self {
+        Self {
+            inner,
+            model: model.to_string(),
+            path: PathBuf::from(shellexpand::tilde(path).to_string()),
+        }
+    }
+
+    fn record(&self, reply: FixtureReply) {
+        let reply = FixtureReply {
+            model: Some(self.model.clone()),
+            ..reply
+        };
+        if let Err(e) = append(&self.path, &reply) {
+            warn!("Failed to record reply to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn append(path: &Path, reply: &FixtureReply) -> Result<()> {
+    let _guard = RECORD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(reply)?)?;
+    Ok(())
+}
+
+#[async_trait]
+impl LLMProvider for RecordingProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let result = self.inner.chat(messages, tools).await;
+        let reply = match &result {
+            Ok(response) => {
+                let (text, tool_calls) = match &response.content {
+                    LLMResponseContent::Text(text) => (Some(text.clone()), None),
+                    LLMResponseContent::ToolCalls(calls) => (None, Some(calls.clone())),
+                };
+                FixtureReply {
+                    text,
+                    tool_calls,
+                    usage: response.usage.clone(),
+                    ..Default::default()
+                }
+            }
+            Err(e) => FixtureReply {
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+        };
+        self.record(reply);
+        result
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.inner.set_generation_params(params);
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.inner.set_retry_policy(policy);
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        self.inner.health_check().await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        let mut inner = match self.inner.chat_stream(messages, tools).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.record(FixtureReply {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                });
+                return Err(e);
+            }
+        };
+
+        // Recorded before the final chunk is passed on, since the caller
+        // may drop the stream as soon as it has it
+        let path = self.path.clone();
+        let model = self.model.clone();
+        Ok(Box::pin(async_stream::stream! {
+            let mut text = String::new();
+            while let Some(chunk) = inner.next().await {
+                let reply = match chunk {
+                    Ok(ref chunk) => {
+                        text.push_str(&chunk.delta);
+                        chunk.done.then(|| FixtureReply {
+                            text: (!text.is_empty()).then(|| text.clone()),
+                            tool_calls: chunk.tool_calls.clone(),
+                            usage: chunk.usage.clone(),
+                            ..Default::default()
+                        })
+                    }
+                    Err(ref e) => Some(FixtureReply {
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    }),
+                };
+                if let Some(reply) = reply {
+                    let reply = FixtureReply {
+                        model: Some(model.clone()),
+                        ..reply
+                    };
+                    if let Err(e) = append(&path, &reply) {
+                        warn!("Failed to record reply to {}: {}", path.display(), e);
+                    }
+                }
+                yield chunk;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(content: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn text(response: LLMResponse) -> String {
+        match response.content {
+            LLMResponseContent::Text(text) => text,
+            LLMResponseContent::ToolCalls(_) => panic!("expected text"),
+        }
+    }
+
+    fn error(result: Result<LLMResponse>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let canned = dir.path().join("canned.jsonl");
+        fs::write(
+            &canned,
+            "{\"text\": \"first\"}\n\n\
+             {\"match\": \"weather\", \"tool_calls\": [{\"id\": \"1\", \"name\": \"web_fetch\", \"arguments\": \"{}\"}]}\n\
+             {\"text\": \"hello there\"}\n\
+             {\"error\": \"rate limited\"}\n",
+        )
+        .unwrap();
+        let mock = MockProvider::open(canned.to_str().unwrap()).unwrap();
+
+        // Record a session against the canned provider
+        let recorded = dir.path().join("recorded.jsonl");
+        let recorder =
+            RecordingProvider::new(Box::new(mock), "openai/gpt-4o", recorded.to_str().unwrap());
+        assert_eq!(
+            text(recorder.chat(&[user("hi")], None).await.unwrap()),
+            "first"
+        );
+        // "weather" isn't asked about, so the next reply without a match
+        let mut stream = recorder.chat_stream(&[user("hi")], None).await.unwrap();
+        let mut streamed = String::new();
+        while let Some(chunk) = stream.next().await {
+            streamed.push_str(&chunk.unwrap().delta);
+        }
+        assert_eq!(streamed, "hello there");
+        let response = recorder.chat(&[user("the weather?")], None).await.unwrap();
+        assert!(matches!(response.content, LLMResponseContent::ToolCalls(_)));
+        let result = recorder.chat(&[user("hi")], None).await;
+        assert_eq!(error(result), "rate limited");
+        assert!(recorder.chat(&[user("hi")], None).await.is_err());
+
+        // Replay gives the same answers, only to the recorded model
+        let path = recorded.to_str().unwrap();
+        let other = MockProvider::replay(path, "anthropic/claude-sonnet-4-5").unwrap();
+        assert!(other.chat(&[user("hi")], None).await.is_err());
+        let replay = MockProvider::replay(path, "openai/gpt-4o").unwrap();
+        assert_eq!(
+            text(replay.chat(&[user("hi")], None).await.unwrap()),
+            "first"
+        );
+        let again = MockProvider::replay(path, "openai/gpt-4o").unwrap();
+        assert_eq!(
+            text(again.chat(&[user("anything")], None).await.unwrap()),
+            "hello there"
+        );
+        assert!(matches!(
+            replay.chat(&[user("x")], None).await.unwrap().content,
+            LLMResponseContent::ToolCalls(_)
+        ));
+        assert_eq!(error(replay.chat(&[user("x")], None).await), "rate limited");
+    }
+}
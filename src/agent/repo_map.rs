@@ -0,0 +1,499 @@
+//! Repository map (`agent.repo_map_tokens`)
+//!
+//! A compact outline of the workspace's source files: each file with the
+//! signatures of the functions, types and classes it defines, most
+//! referenced files first, cut to a token budget. It is added to the system
+//! prompt so the model knows where things live without reading every file.
+//! Symbols come from per-language line patterns (Rust, Python,
+//! JavaScript/TypeScript, Go, Ruby) and are cached by file size and
+//! modification time, so refreshing before each request only re-reads files
+//! that changed. Workspaces without source files get no map.
+
+use ignore::WalkBuilder;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::debug;
+
+use super::code_search::relative;
+use super::tokens::TokenCounter;
+
+/// Source files indexed at most; larger workspaces are mapped partially
+const MAX_FILES: usize = 5000;
+
+/// Larger files are probably generated and are skipped
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// Lines joined to complete a signature split across lines
+const MAX_SIGNATURE_LINES: usize = 6;
+
+const MAX_SIGNATURE_CHARS: usize = 160;
+
+static IDENTIFIER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]{2,}").expect("valid regex"));
+
+static RUST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern\s+"\w+")\s+)*(?:fn|struct|enum|trait|type|union|mod)\s+(\w+)|^\s*(?:unsafe\s+)?impl\b(?:<[^>]*>)?\s*(?:[\w:<>, ]+\s+for\s+)?(\w+)|^\s*macro_rules!\s*(\w+)"#,
+    )
+    .expect("valid regex")
+});
+
+static PYTHON: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:async\s+)?(?:def|class)\s+(\w+)").expect("valid regex"));
+
+static JAVASCRIPT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\*?|class|interface|enum|type|namespace)\s+(\w+)|^\s*export\s+(?:const|let|var)\s+(\w+)",
+    )
+    .expect("valid regex")
+});
+
+static GO: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^func\s+(?:\([^)]*\)\s*)?(\w+)|^type\s+(\w+)").expect("valid regex"));
+
+static RUBY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:def\s+(?:self\.)?([\w?!=]+)|class\s+(\w+)|module\s+(\w+))")
+        .expect("valid regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    Ruby,
+}
+
+impl Language {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "py" => Some(Self::Python),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" => Some(Self::JavaScript),
+            "go" => Some(Self::Go),
+            "rb" => Some(Self::Ruby),
+            _ => None,
+        }
+    }
+
+    fn pattern(&self) -> &'static Regex {
+        match self {
+            Self::Rust => &RUST,
+            Self::Python => &PYTHON,
+            Self::JavaScript => &JAVASCRIPT,
+            Self::Go => &GO,
+            Self::Ruby => &RUBY,
+        }
+    }
+
+    /// Whether a signature is complete once `line` is added
+    fn ends_signature(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        match self {
+            Self::Python => line.ends_with(':'),
+            Self::Ruby => true,
+            _ => {
+                line.contains('{')
+                    || line.ends_with(';')
+                    || line.ends_with('=')
+                    || line.ends_with("=>")
+            }
+        }
+    }
+}
+
+/// A definition and the source lines that declare it
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: String,
+    signature: String,
+    /// The enclosing definition (impl, class), by index
+    parent: Option<usize>,
+}
+
+/// What the map knows about one file
+#[derive(Debug)]
+struct FileEntry {
+    modified: Option<SystemTime>,
+    len: u64,
+    symbols: Vec<Symbol>,
+    /// Identifiers the file mentions, for ranking
+    identifiers: HashSet<String>,
+}
+
+pub struct RepoMap {
+    root: PathBuf,
+    max_tokens: usize,
+    counter: TokenCounter,
+    files: HashMap<PathBuf, FileEntry>,
+    /// The map as of the last scan (None: rebuild; Some(None): nothing to show)
+    rendered: Option<Option<String>>,
+}
+
+impl RepoMap {
+    pub fn new(root: &Path, max_tokens: usize, counter: TokenCounter) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            max_tokens,
+            counter,
+            files: HashMap::new(),
+            rendered: None,
+        }
+    }
+
+    /// The map for the system prompt, after picking up changed files.
+    /// None when the workspace has no source files.
+    pub fn context(&mut self, counter: TokenCounter) -> Option<String> {
+        if counter != self.counter {
+            self.counter = counter;
+            self.rendered = None;
+        }
+        if self.scan() {
+            self.rendered = None;
+        }
+        if self.rendered.is_none() {
+            self.rendered = Some(self.render());
+        }
+        self.rendered.clone().flatten()
+    }
+
+    /// Re-read files that were added or changed and forget deleted ones.
+    /// Returns whether anything changed.
+    fn scan(&mut self) -> bool {
+        let mut changed = false;
+        let mut seen = HashSet::new();
+
+        let walker = WalkBuilder::new(&self.root)
+            .require_git(false)
+            .max_filesize(Some(MAX_FILE_BYTES))
+            .sort_by_file_path(|a, b| a.cmp(b))
+            .build();
+        for entry in walker.flatten() {
+            if seen.len() >= MAX_FILES {
+                debug!("Repo map limited to {} files", MAX_FILES);
+                break;
+            }
+            let path = entry.path();
+            let Some(language) = Language::from_path(path) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().ok();
+            let len = metadata.len();
+            seen.insert(path.to_path_buf());
+
+            let current = self.files.get(path);
+            if current.is_some_and(|f| f.modified == modified && f.len == len) {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(path) else {
+                continue;
+            };
+            self.files.insert(
+                path.to_path_buf(),
+                FileEntry {
+                    modified,
+                    len,
+                    symbols: extract_symbols(language, &content),
+                    identifiers: IDENTIFIER
+                        .find_iter(&content)
+                        .map(|m| m.as_str().to_string())
+                        .collect(),
+                },
+            );
+            changed = true;
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| seen.contains(path));
+        changed || self.files.len() != before
+    }
+
+    /// Symbols ranked by how many other files mention them, taken until
+    /// the budget runs out and listed by file
+    fn render(&self) -> Option<String> {
+        // Files mentioning each identifier, and files defining each symbol
+        let mut mentions: HashMap<&str, usize> = HashMap::new();
+        let mut definitions: HashMap<&str, usize> = HashMap::new();
+        for file in self.files.values() {
+            for identifier in &file.identifiers {
+                *mentions.entry(identifier).or_default() += 1;
+            }
+            let names: HashSet<&str> = file.symbols.iter().map(|s| s.name.as_str()).collect();
+            for name in names {
+                *definitions.entry(name).or_default() += 1;
+            }
+        }
+        // A name defined in many files (`new`, `run`) says little about
+        // any one of them, and a plain word (`push`, `text`) is mostly
+        // mentioned as something else
+        let score = |name: &str| {
+            let others = mentions.get(name).copied().unwrap_or(1).saturating_sub(1);
+            let score = others as f64 / definitions.get(name).copied().unwrap_or(1) as f64;
+            if is_distinctive(name) {
+                score * 10.0
+            } else {
+                score
+            }
+        };
+
+        let mut files: Vec<(String, &FileEntry)> = self
+            .files
+            .iter()
+            .filter(|(_, file)| !file.symbols.is_empty())
+            .map(|(path, file)| (relative(&self.root, path), file))
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut ranked: Vec<(f64, usize, usize)> = files
+            .iter()
+            .enumerate()
+            .flat_map(|(f, (_, file))| {
+                let score = &score;
+                file.symbols
+                    .iter()
+                    .enumerate()
+                    .map(move |(s, symbol)| (score(&symbol.name), f, s))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+        let mut out = String::from(
+            "# Repository Map\n\nSource files in the workspace and what they define, most \
+             referenced first. Read a file for the full code.\n",
+        );
+        let mut used = self.counter.count(&out);
+        // Chosen symbols per file, files in the order they were reached
+        let mut chosen: Vec<(usize, Vec<usize>)> = Vec::new();
+        for (_, f, s) in ranked {
+            let (path, file) = &files[f];
+            let existing = chosen.iter().position(|(file, _)| *file == f);
+            let shown: &[usize] = existing.map(|i| chosen[i].1.as_slice()).unwrap_or(&[]);
+            // A method is shown inside its impl or class
+            let mut needed = Vec::new();
+            let mut next = Some(s);
+            while let Some(i) = next.filter(|i| !shown.contains(i)) {
+                needed.push(i);
+                next = file.symbols[i].parent;
+            }
+            let mut tokens: usize = needed
+                .iter()
+                .map(|&i| {
+                    self.counter
+                        .count(&format!("  {}\n", file.symbols[i].signature))
+                })
+                .sum();
+            if existing.is_none() {
+                tokens += self.counter.count(&format!("\n{}:\n", path));
+            }
+            if needed.is_empty() || used + tokens > self.max_tokens {
+                continue;
+            }
+            used += tokens;
+            match existing {
+                Some(i) => chosen[i].1.extend(needed),
+                None => chosen.push((f, needed)),
+            }
+        }
+        if chosen.is_empty() {
+            return None;
+        }
+
+        for (f, symbols) in &mut chosen {
+            let (path, file) = &files[*f];
+            symbols.sort_unstable();
+            out.push_str(&format!("\n{}:\n", path));
+            for &s in symbols.iter() {
+                out.push_str(&format!("  {}\n", file.symbols[s].signature));
+            }
+        }
+        let omitted = files.len() - chosen.len();
+        if omitted > 0 {
+            out.push_str(&format!("\n({} more files not shown)\n", omitted));
+        }
+        Some(out)
+    }
+}
+
+/// Definitions in `content`, with their signatures as written
+fn extract_symbols(language: Language, content: &str) -> Vec<Symbol> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut symbols = Vec::new();
+    // Indentation of a test module or function whose body is skipped
+    let mut skipping: Option<usize> = None;
+    // Definitions enclosing the current line: indentation and index
+    let mut enclosing: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(depth) = skipping {
+            if line.trim().is_empty() || indent(line).len() > depth {
+                continue;
+            }
+            skipping = None;
+            // The closing brace of the skipped block
+            if line.trim_start().starts_with('}') {
+                continue;
+            }
+        }
+        let Some(captures) = language.pattern().captures(line) else {
+            continue;
+        };
+        let Some(name) = captures.iter().skip(1).flatten().next() else {
+            continue;
+        };
+        let name = name.as_str();
+        if name.starts_with("test_") || name == "tests" {
+            skipping = Some(indent(line).len());
+            continue;
+        }
+        if is_noise(language, name, line) {
+            continue;
+        }
+
+        let mut signature = String::new();
+        for line in lines.iter().skip(i).take(MAX_SIGNATURE_LINES) {
+            if !signature.is_empty() {
+                signature.push(' ');
+            }
+            signature.push_str(line.trim());
+            if language.ends_signature(line) {
+                break;
+            }
+        }
+        let depth = indent(line).len();
+        while enclosing.last().is_some_and(|(d, _)| *d >= depth) {
+            enclosing.pop();
+        }
+        enclosing.push((depth, symbols.len()));
+        symbols.push(Symbol {
+            name: name.to_string(),
+            signature: format!("{}{}", indent(line), tidy_signature(&signature)),
+            parent: enclosing.iter().rev().nth(1).map(|(_, i)| *i),
+        });
+    }
+    symbols
+}
+
+/// Private helpers and module declarations crowd out the interesting
+/// definitions
+fn is_noise(language: Language, name: &str, line: &str) -> bool {
+    match language {
+        Language::Python => name.starts_with('_') && name != "__init__",
+        // `mod foo;` only points at another file
+        Language::Rust => line.trim_end().ends_with(';') && line.contains("mod "),
+        _ => false,
+    }
+}
+
+/// CamelCase and snake_case names rarely collide with ordinary words
+fn is_distinctive(name: &str) -> bool {
+    let name = name.trim_matches('_');
+    name.len() >= 4
+        && (name.contains('_')
+            || name.chars().skip(1).any(|c| c.is_ascii_uppercase())
+            || name.starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+/// Leading whitespace of `line`, tabs as four spaces
+fn indent(line: &str) -> String {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { "    " } else { " " })
+        .collect()
+}
+
+/// Drop the body opener and squeeze whitespace left by joined lines
+fn tidy_signature(signature: &str) -> String {
+    let signature = signature
+        .split_once(" {")
+        .map(|(head, _)| head)
+        .unwrap_or(signature);
+    let signature = signature.trim_end_matches(['{', ':', ';']).trim_end();
+    let signature = signature
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace(" )", ")");
+    if signature.chars().count() > MAX_SIGNATURE_CHARS {
+        let cut: String = signature.chars().take(MAX_SIGNATURE_CHARS).collect();
+        format!("{}…", cut)
+    } else {
+        signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_map_ranks_and_refreshes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/store.rs"),
+            "/// A store\npub struct Store {\n    items: Vec<String>,\n}\n\n\
+             impl Store {\n    pub fn insert(\n        &mut self,\n        item: String,\n    ) -> bool {\n        true\n    }\n}\n\n\
+             #[cfg(test)]\nmod tests {\n    fn test_insert() {}\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "mod store;\n\nfn main() {\n    let mut s = store::Store::default();\n    s.insert(String::new());\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tool.py"),
+            "class Runner:\n    def __init__(self):\n        pass\n\n    def _helper(self):\n        pass\n\nasync def run(path: str,\n              limit: int = 3) -> None:\n    pass\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "# Notes\n").unwrap();
+
+        let mut map = RepoMap::new(dir.path(), 1000, TokenCounter::Estimate);
+        let context = map.context(TokenCounter::Estimate).unwrap();
+        // store.rs is used by main.rs, so it comes first
+        assert!(context.ends_with(
+            "\nsrc/store.rs:\n  pub struct Store\n  impl Store\n      pub fn insert(&mut self, item: String) -> bool\n\n\
+             src/main.rs:\n  fn main()\n\n\
+             tool.py:\n  class Runner\n      def __init__(self)\n  async def run(path: str, limit: int = 3) -> None\n"
+        ), "{}", context);
+
+        // Unchanged files aren't re-read; changed ones are
+        assert!(!map.scan());
+        std::fs::write(dir.path().join("src/main.rs"), "fn start() {}\n").unwrap();
+        let context = map.context(TokenCounter::Estimate).unwrap();
+        assert!(
+            context.contains("src/main.rs:\n  fn start()\n"),
+            "{}",
+            context
+        );
+
+        // Files that don't fit the budget are left out
+        let mut small = RepoMap::new(dir.path(), 45, TokenCounter::Estimate);
+        let context = small.context(TokenCounter::Estimate).unwrap();
+        assert!(
+            context.ends_with("\nsrc/main.rs:\n  fn start()\n\n(2 more files not shown)\n"),
+            "{}",
+            context
+        );
+
+        let empty = tempfile::tempdir().unwrap();
+        std::fs::write(empty.path().join("MEMORY.md"), "# Memory\n").unwrap();
+        assert!(RepoMap::new(empty.path(), 1000, TokenCounter::Estimate)
+            .context(TokenCounter::Estimate)
+            .is_none());
+    }
+}
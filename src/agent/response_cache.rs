@@ -0,0 +1,312 @@
+//! On-disk cache of non-streaming chat replies (`[providers.cache]`)
+//!
+//! Replies are stored as JSON under `~/.localgpt/cache/responses`, one file
+//! per request, named by a SHA-256 of the model, generation parameters,
+//! messages and tools. Only `chat` goes through the cache, so compaction
+//! summaries and titles are reused while streamed replies stay live.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use super::providers::{
+    GenerationParams, LLMProvider, LLMResponse, LLMResponseContent, Message, StreamResult,
+    ToolCall, ToolSchema,
+};
+use crate::config::{ResponseCacheConfig, RetryConfig};
+
+/// A stored reply. Usage isn't kept: a cache hit costs no tokens.
+#[derive(Serialize, Deserialize)]
+struct CachedReply {
+    created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// Directory of cached replies
+pub struct ResponseCache {
+    dir: PathBuf,
+    /// Seconds an entry stays valid; 0 = until cleared
+    ttl_secs: u64,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, config: &ResponseCacheConfig) -> Self {
+        Self {
+            dir,
+            ttl_secs: config.ttl_secs,
+        }
+    }
+
+    /// Cache in the default directory
+    pub fn open(config: &ResponseCacheConfig) -> Result<Self> {
+        Ok(Self::new(response_cache_dir()?, config))
+    }
+
+    /// Stored reply for `key`, unless missing or expired
+    pub fn get(&self, key: &str) -> Option<LLMResponse> {
+        let path = self.path(key);
+        let data = std::fs::read_to_string(&path).ok()?;
+        let Ok(reply) = serde_json::from_str::<CachedReply>(&data) else {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        };
+        let age = chrono::Utc::now().timestamp() - reply.created_at;
+        if self.ttl_secs > 0 && age > self.ttl_secs as i64 {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        let content = match (reply.tool_calls, reply.text) {
+            (Some(calls), _) => LLMResponseContent::ToolCalls(calls),
+            (None, Some(text)) => LLMResponseContent::Text(text),
+            (None, None) => return None,
+        };
+        Some(LLMResponse {
+            content,
+            usage: None,
+        })
+    }
+
+    /// Store a reply under `key`
+    pub fn put(&self, key: &str, response: &LLMResponse) -> Result<()> {
+        let (text, tool_calls) = match &response.content {
+            LLMResponseContent::Text(text) => (Some(text.clone()), None),
+            LLMResponseContent::ToolCalls(calls) => (None, Some(calls.clone())),
+        };
+        let reply = CachedReply {
+            created_at: chrono::Utc::now().timestamp(),
+            text,
+            tool_calls,
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        // Write then rename so a concurrent reader never sees half a file
+        let tmp = self.dir.join(format!("{}.tmp-{}", key, std::process::id()));
+        std::fs::write(&tmp, serde_json::to_vec(&reply)?)?;
+        std::fs::rename(&tmp, self.path(key))?;
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+/// `~/.localgpt/cache/responses` (per profile)
+pub fn response_cache_dir() -> Result<PathBuf> {
+    Ok(crate::config::localgpt_home()?
+        .join("cache")
+        .join("responses"))
+}
+
+/// Number of cached replies in the default directory
+pub fn response_cache_len() -> Result<usize> {
+    let dir = response_cache_dir()?;
+    if !dir.exists() {
+        return Ok(0);
+    }
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .count())
+}
+
+/// Delete every cached reply, returning how many were removed
+pub fn clear_response_cache() -> Result<usize> {
+    let dir = response_cache_dir()?;
+    let count = response_cache_len()?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    Ok(count)
+}
+
+/// Cache key for one request
+pub fn response_cache_key(
+    model: &str,
+    params: &GenerationParams,
+    messages: &[Message],
+    tools: Option<&[ToolSchema]>,
+) -> String {
+    let request = json!({
+        "model": model,
+        "params": params,
+        "messages": messages,
+        "tools": tools,
+    });
+    let mut hasher = Sha256::new();
+    hasher.update(request.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Provider wrapper that answers repeated `chat` requests from the cache
+pub struct CachedProvider {
+    inner: Box<dyn LLMProvider>,
+    model: String,
+    params: GenerationParams,
+    cache: ResponseCache,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, model: &str, cache: ResponseCache) -> Self {
+        Self {
+            inner,
+            model: model.to_string(),
+            params: GenerationParams::default(),
+            cache,
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for CachedProvider {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<LLMResponse> {
+        let key = response_cache_key(&self.model, &self.params, messages, tools);
+        if let Some(response) = self.cache.get(&key) {
+            debug!("Response cache hit for {}", self.model);
+            return Ok(response);
+        }
+        let response = self.inner.chat(messages, tools).await?;
+        if let Err(e) = self.cache.put(&key, &response) {
+            warn!("Failed to cache response: {}", e);
+        }
+        Ok(response)
+    }
+
+    async fn chat_json(&self, prompt: &str, schema: &Value) -> Result<Value> {
+        self.inner.chat_json(prompt, schema).await
+    }
+
+    fn set_generation_params(&mut self, params: &GenerationParams) {
+        self.params = params.clone();
+        self.inner.set_generation_params(params);
+    }
+
+    fn set_retry_policy(&mut self, policy: &RetryConfig) {
+        self.inner.set_retry_policy(policy);
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+
+    async fn health_check(&self) -> Result<String> {
+        self.inner.health_check().await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        tools: Option<&[ToolSchema]>,
+    ) -> Result<StreamResult> {
+        self.inner.chat_stream(messages, tools).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::providers::Role;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for CountingProvider {
+        async fn chat(
+            &self,
+            messages: &[Message],
+            _tools: Option<&[ToolSchema]>,
+        ) -> Result<LLMResponse> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(LLMResponse::text(format!(
+                "reply {} to {}",
+                n, messages[0].content
+            )))
+        }
+    }
+
+    fn user(content: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn text(response: LLMResponse) -> String {
+        match response.content {
+            LLMResponseContent::Text(text) => text,
+            LLMResponseContent::ToolCalls(_) => panic!("expected text"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_provider_reuses_replies() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut provider = CachedProvider::new(
+            Box::new(CountingProvider {
+                calls: calls.clone(),
+            }),
+            "openai/gpt-4o",
+            ResponseCache::new(dir.path().to_path_buf(), &ResponseCacheConfig::default()),
+        );
+
+        let first = text(provider.chat(&[user("hi")], None).await.unwrap());
+        let again = provider.chat(&[user("hi")], None).await.unwrap();
+        assert!(again.usage.is_none());
+        assert_eq!(text(again), first);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Different messages or sampling settings are different requests
+        provider.chat(&[user("bye")], None).await.unwrap();
+        provider.set_generation_params(&GenerationParams {
+            temperature: Some(0.0),
+            max_tokens: None,
+        });
+        provider.chat(&[user("hi")], None).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Expired entries are dropped
+        let key = response_cache_key(
+            "openai/gpt-4o",
+            &GenerationParams::default(),
+            &[user("hi")],
+            None,
+        );
+        let stale = CachedReply {
+            created_at: chrono::Utc::now().timestamp() - 100,
+            text: Some("old".to_string()),
+            tool_calls: None,
+        };
+        std::fs::write(
+            dir.path().join(format!("{}.json", key)),
+            serde_json::to_vec(&stale).unwrap(),
+        )
+        .unwrap();
+        let short = ResponseCache::new(
+            dir.path().to_path_buf(),
+            &ResponseCacheConfig {
+                enabled: true,
+                ttl_secs: 60,
+            },
+        );
+        assert!(short.get(&key).is_none());
+        assert!(!dir.path().join(format!("{}.json", key)).exists());
+    }
+}
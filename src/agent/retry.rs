@@ -0,0 +1,156 @@
+//! Retries for provider HTTP requests
+//!
+//! Rate limits (429), server errors (5xx, Anthropic's 529 "overloaded") and
+//! dropped connections are retried with exponential backoff, honoring the
+//! server's `Retry-After` when it sends one. Other responses are returned
+//! as-is for the provider to report.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::RetryConfig;
+
+/// Whether a response status is worth another attempt
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+/// Send `request`, retrying transient failures according to `policy`.
+/// `label` names the provider in log messages.
+pub async fn send_with_retry(
+    policy: &RetryConfig,
+    label: &str,
+    request: RequestBuilder,
+) -> Result<Response> {
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        // Streaming bodies can't be cloned; those get a single attempt
+        let Some(this_try) = request.try_clone().filter(|_| attempt < max_attempts) else {
+            return Ok(request.send().await?);
+        };
+
+        let delay = match this_try.send().await {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let status = response.status();
+                match retry_after(&response) {
+                    Some(wait) if wait > Duration::from_millis(policy.max_backoff_ms) => {
+                        warn!(
+                            "{} returned {} with Retry-After {}s, not waiting",
+                            label,
+                            status,
+                            wait.as_secs()
+                        );
+                        return Ok(response);
+                    }
+                    Some(wait) => {
+                        warn!("{} returned {}, retrying in {:?}", label, status, wait);
+                        wait
+                    }
+                    None => {
+                        let wait = backoff(policy, attempt);
+                        warn!("{} returned {}, retrying in {:?}", label, status, wait);
+                        wait
+                    }
+                }
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => {
+                let wait = backoff(policy, attempt);
+                warn!("{} request failed ({}), retrying in {:?}", label, e, wait);
+                wait
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Wait before retry number `attempt` (1-based): doubling from the initial
+/// backoff, capped, and with jitter scaled into its upper half
+pub fn backoff(policy: &RetryConfig, attempt: u32) -> Duration {
+    let exp = policy
+        .initial_backoff_ms
+        .saturating_mul(1u64 << (attempt.saturating_sub(1)).min(20));
+    let ms = exp.min(policy.max_backoff_ms);
+    let ms = if policy.jitter {
+        ms / 2 + (ms as f64 / 2.0 * jitter_fraction()) as u64
+    } else {
+        ms
+    };
+    Duration::from_millis(ms)
+}
+
+fn jitter_fraction() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as f64 / 1e9)
+        .unwrap_or(0.5)
+}
+
+/// `Retry-After` as seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    parse_retry_after(value.to_str().ok()?, Utc::now())
+}
+
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs >= 0.0).then(|| Duration::from_secs_f64(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_retry_after() {
+        let policy = RetryConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 3000,
+            jitter: false,
+        };
+        let waits: Vec<u64> = (1..=5)
+            .map(|a| backoff(&policy, a).as_millis() as u64)
+            .collect();
+        assert_eq!(waits, vec![500, 1000, 2000, 3000, 3000]);
+
+        let jittered = backoff(
+            &RetryConfig {
+                jitter: true,
+                ..policy
+            },
+            2,
+        );
+        assert!(jittered >= Duration::from_millis(500) && jittered <= Duration::from_millis(1000));
+
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_retry_after("7", now), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::from_u16(529).unwrap()));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+}
@@ -0,0 +1,184 @@
+//! Path sandbox for file tools
+//!
+//! File tools resolve their path arguments through a `PathSandbox`: `~` is
+//! expanded, relative paths are taken from the workspace, and the result must
+//! lie in an allowed directory (the workspace plus `tools.allowed_dirs`)
+//! after symlinks are resolved, so neither `..` nor a link pointing elsewhere
+//! gets out. Paths that don't exist yet (a file about to be written) are
+//! checked through their nearest existing ancestor. A violation is an error,
+//! which the model sees as the tool's output.
+//!
+//! bash and the code interpreter aren't sandboxed; gate them with approval.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::tools::normalize_path;
+use crate::config::Config;
+
+#[derive(Debug, Clone)]
+pub struct PathSandbox {
+    workspace: PathBuf,
+    /// Directories allowed besides the workspace
+    allowed: Vec<PathBuf>,
+}
+
+impl PathSandbox {
+    /// A sandbox allowing only the workspace
+    pub fn new(workspace: PathBuf) -> Self {
+        Self {
+            workspace,
+            allowed: Vec::new(),
+        }
+    }
+
+    /// The workspace plus `tools.allowed_dirs`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.workspace_path()).with_allowed(
+            config
+                .tools
+                .allowed_dirs
+                .iter()
+                .map(|dir| PathBuf::from(shellexpand::tilde(dir).to_string())),
+        )
+    }
+
+    /// Also allow these directories
+    pub fn with_allowed(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.allowed.extend(dirs);
+        self
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Resolve a tool's path argument, refusing anything outside the allowed
+    /// directories. Returns the path without symlinks resolved, so it still
+    /// reads relative to the workspace.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let expanded = PathBuf::from(shellexpand::tilde(path).to_string());
+        let resolved = normalize_path(&self.workspace.join(expanded));
+        let roots: Vec<PathBuf> = std::iter::once(&self.workspace)
+            .chain(&self.allowed)
+            .map(|root| normalize_path(root))
+            .collect();
+
+        if !roots.iter().any(|root| resolved.starts_with(root)) {
+            if self.allowed.is_empty() {
+                anyhow::bail!(
+                    "{} is outside the workspace ({})",
+                    resolved.display(),
+                    self.workspace.display()
+                );
+            }
+            anyhow::bail!(
+                "{} is outside the workspace ({}) and tools.allowed_dirs",
+                resolved.display(),
+                self.workspace.display()
+            );
+        }
+
+        let Some(real) = real_path(&resolved) else {
+            anyhow::bail!(
+                "{} is a broken symbolic link; file tools don't follow those",
+                resolved.display()
+            );
+        };
+        let inside = roots
+            .iter()
+            .any(|root| real.starts_with(real_path(root).unwrap_or_else(|| root.clone())));
+        if !inside {
+            anyhow::bail!(
+                "{} leads to {} through a symbolic link, outside the allowed directories",
+                resolved.display(),
+                real.display()
+            );
+        }
+        Ok(resolved)
+    }
+}
+
+impl From<PathBuf> for PathSandbox {
+    fn from(workspace: PathBuf) -> Self {
+        Self::new(workspace)
+    }
+}
+
+/// `path` with symlinks resolved: its nearest existing ancestor made
+/// canonical, with the rest appended. None if the path, or an ancestor, is a
+/// symlink whose target is missing (writing through it would land wherever
+/// the link points).
+fn real_path(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match fs::canonicalize(existing) {
+            Ok(mut real) => {
+                real.extend(rest.iter().rev());
+                return Some(real);
+            }
+            Err(_) if fs::symlink_metadata(existing).is_ok() => return None,
+            Err(_) => {}
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return Some(path.to_path_buf()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traversal_is_refused() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let workspace = temp.path().join("workspace");
+        fs::create_dir_all(workspace.join("notes"))?;
+        let sandbox = PathSandbox::new(workspace.clone());
+
+        assert_eq!(
+            sandbox.resolve("notes/new.md")?,
+            workspace.join("notes/new.md")
+        );
+        assert!(sandbox.resolve("../outside.md").is_err());
+        assert!(sandbox.resolve("notes/../../outside.md").is_err());
+        assert!(sandbox.resolve("/etc/passwd").is_err());
+
+        let shared = temp.path().join("shared");
+        fs::create_dir_all(&shared)?;
+        let sandbox = sandbox.with_allowed([shared.clone()]);
+        assert_eq!(sandbox.resolve("../shared/a.txt")?, shared.join("a.txt"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escapes_are_refused() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let workspace = temp.path().join("workspace");
+        let outside = temp.path().join("outside");
+        fs::create_dir_all(&workspace)?;
+        fs::create_dir_all(&outside)?;
+        fs::write(outside.join("secret.txt"), "secret")?;
+        fs::write(workspace.join("real.txt"), "fine")?;
+        std::os::unix::fs::symlink(&outside, workspace.join("link"))?;
+        std::os::unix::fs::symlink(workspace.join("real.txt"), workspace.join("alias.txt"))?;
+        std::os::unix::fs::symlink(outside.join("missing.txt"), workspace.join("dangling"))?;
+        let sandbox = PathSandbox::new(workspace.clone());
+
+        let error = sandbox.resolve("link/secret.txt").unwrap_err();
+        assert!(error.to_string().contains("symbolic link"));
+        assert!(sandbox.resolve("link/new.txt").is_err());
+        assert!(sandbox.resolve("dangling").is_err());
+        // Links that stay inside are fine
+        assert_eq!(sandbox.resolve("alias.txt")?, workspace.join("alias.txt"));
+        Ok(())
+    }
+}
@@ -13,7 +13,11 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 use uuid::Uuid;
 
-use super::providers::{LLMProvider, Message, Role, ToolCall, Usage};
+use super::feedback::{Feedback, RatedPair};
+use super::providers::{GenerationParams, LLMProvider, Message, Role, ToolCall, Usage};
+use super::system_prompt::language_instruction;
+use super::tokens::TokenCounter;
+use crate::config::CompactionStrategy;
 
 /// Current session format version (matches Pi)
 pub const CURRENT_SESSION_VERSION: u32 = 1;
@@ -29,8 +33,56 @@ pub struct Session {
     token_count: usize,
     compaction_count: u32,
     memory_flush_compaction_count: u32,
+    /// Generation overrides set with `/set`
+    generation: GenerationParams,
+    /// Extra system instructions set with `/system`
+    system_prompt: Option<String>,
+    /// Reply language set with `/lang`
+    language: Option<String>,
+    /// When the session was compacted, and by how much
+    compactions: Vec<CompactionEvent>,
+    /// Compaction strategy set with `/compact strategy` (None: the config's)
+    compaction_strategy: Option<CompactionStrategy>,
+    /// How `token_count` is counted; follows the agent's model
+    tokens: TokenCounter,
 }
 
+/// One compaction of the session history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionEvent {
+    pub at: DateTime<Utc>,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<CompactionStrategy>,
+    /// The summary that replaced the older messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+/// Messages picked for compaction by `Session::plan_compaction`
+#[derive(Debug, Clone)]
+pub struct CompactionPlan {
+    strategy: CompactionStrategy,
+    /// Timestamp, role and content of each message to replace, to check
+    /// they are still there when the summary is ready
+    replaced: Vec<(u64, Role, String)>,
+    /// Indices of the pinned ones, which are kept
+    pinned: Vec<usize>,
+    /// Transcript to summarize (None for drop-oldest)
+    pub text: Option<String>,
+}
+
+impl CompactionPlan {
+    pub fn strategy(&self) -> CompactionStrategy {
+        self.strategy
+    }
+}
+
+/// Start of the system message a summary is stored in
+const SUMMARY_PREFIX: &str = "Previous conversation summary:\n\n";
+
 /// Message with metadata for persistence
 #[derive(Debug, Clone)]
 pub struct SessionMessage {
@@ -41,6 +93,10 @@ pub struct SessionMessage {
     pub usage: Option<MessageUsage>,
     pub stop_reason: Option<String>,
     pub timestamp: u64,
+    /// The user's rating of an assistant reply
+    pub feedback: Option<Feedback>,
+    /// Kept verbatim by compaction (`/pin`)
+    pub pinned: bool,
 }
 
 /// Per-message usage tracking (Pi-compatible)
@@ -90,6 +146,8 @@ impl SessionMessage {
             usage: None,
             stop_reason: None,
             timestamp: Utc::now().timestamp_millis() as u64,
+            feedback: None,
+            pinned: false,
         }
     }
 
@@ -108,6 +166,8 @@ impl SessionMessage {
             usage: usage.map(MessageUsage::from),
             stop_reason: stop_reason.map(|s| s.to_string()),
             timestamp: Utc::now().timestamp_millis() as u64,
+            feedback: None,
+            pinned: false,
         }
     }
 }
@@ -116,10 +176,18 @@ impl SessionMessage {
 pub struct SessionStatus {
     pub id: String,
     pub message_count: usize,
+    /// Tokens the next request starts with, including tool definitions
     pub token_count: usize,
+    /// Context window minus the response reserve (set by the agent)
+    pub usable_tokens: usize,
     pub compaction_count: u32,
     pub api_input_tokens: u64,
     pub api_output_tokens: u64,
+    /// Part of `api_output_tokens` spent on reasoning
+    pub api_reasoning_tokens: u64,
+    pub generation: GenerationParams,
+    pub system_prompt: Option<String>,
+    pub language: Option<String>,
 }
 
 impl Session {
@@ -141,6 +209,12 @@ impl Session {
             token_count: 0,
             compaction_count: 0,
             memory_flush_compaction_count: 0,
+            generation: GenerationParams::default(),
+            system_prompt: None,
+            language: None,
+            compaction_strategy: None,
+            compactions: Vec::new(),
+            tokens: TokenCounter::default(),
         }
     }
 
@@ -152,10 +226,26 @@ impl Session {
         self.token_count
     }
 
+    pub fn token_counter(&self) -> TokenCounter {
+        self.tokens
+    }
+
+    /// Count tokens the way `model` will, recounting the history
+    pub fn set_token_counter(&mut self, counter: TokenCounter) {
+        if self.tokens != counter {
+            self.tokens = counter;
+            self.recalculate_tokens();
+        }
+    }
+
     pub fn compaction_count(&self) -> u32 {
         self.compaction_count
     }
 
+    pub fn compactions(&self) -> &[CompactionEvent] {
+        &self.compactions
+    }
+
     pub fn should_memory_flush(&self) -> bool {
         self.memory_flush_compaction_count <= self.compaction_count
     }
@@ -169,9 +259,58 @@ impl Session {
         self.recalculate_tokens();
     }
 
+    pub fn generation_params(&self) -> &GenerationParams {
+        &self.generation
+    }
+
+    pub fn set_generation_params(&mut self, params: GenerationParams) {
+        self.generation = params;
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// Set (or clear) extra system instructions for this session
+    pub fn set_system_prompt(&mut self, prompt: Option<String>) {
+        self.system_prompt = prompt.filter(|p| !p.trim().is_empty());
+        self.recalculate_tokens();
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn compaction_strategy(&self) -> Option<CompactionStrategy> {
+        self.compaction_strategy
+    }
+
+    /// Set (or clear) how this session is compacted
+    pub fn set_compaction_strategy(&mut self, strategy: Option<CompactionStrategy>) {
+        self.compaction_strategy = strategy;
+    }
+
+    /// Set (or clear) the language replies should be written in
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.language = language
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty());
+        self.recalculate_tokens();
+    }
+
+    /// `/system` instructions plus the language instruction, if any
+    fn session_instructions(&self) -> Option<String> {
+        let language = self.language.as_deref().map(language_instruction);
+        match (&self.system_prompt, language) {
+            (Some(prompt), Some(language)) => Some(format!("{}\n\n{}", prompt, language)),
+            (Some(prompt), None) => Some(prompt.clone()),
+            (None, language) => language,
+        }
+    }
+
     /// Add a message without metadata
     pub fn add_message(&mut self, message: Message) {
-        let tokens = estimate_tokens(&message.content);
+        let tokens = self.tokens.message(&message);
         self.token_count += tokens;
         self.messages.push(SessionMessage::new(message));
     }
@@ -185,7 +324,7 @@ impl Session {
         usage: Option<&Usage>,
         stop_reason: Option<&str>,
     ) {
-        let tokens = estimate_tokens(&message.content);
+        let tokens = self.tokens.message(&message);
         self.token_count += tokens;
         self.messages.push(SessionMessage::with_metadata(
             message,
@@ -196,13 +335,142 @@ impl Session {
         ));
     }
 
+    /// Remove the last message if it is a user message that never got a reply
+    pub fn pop_unanswered_user_message(&mut self) -> Option<Message> {
+        if self.messages.last()?.message.role != Role::User {
+            return None;
+        }
+        let message = self.messages.pop()?.message;
+        self.token_count = self
+            .token_count
+            .saturating_sub(self.tokens.message(&message));
+        Some(message)
+    }
+
+    /// Remove the last user message and everything after it, returning
+    /// that message
+    pub fn rewind_last_turn(&mut self) -> Option<Message> {
+        let index = self
+            .messages
+            .iter()
+            .rposition(|sm| sm.message.role == Role::User)?;
+        let message = self.messages.drain(index..).next()?.message;
+        self.recalculate_tokens();
+        Some(message)
+    }
+
+    /// Replace the text of the final assistant reply
+    pub fn replace_last_reply(&mut self, content: &str) -> bool {
+        match self.messages.last_mut() {
+            Some(sm) if sm.message.role == Role::Assistant => {
+                sm.message.content = content.to_string();
+                // A rating was for the reply being replaced
+                sm.feedback = None;
+                self.recalculate_tokens();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Rate the latest assistant reply with this text (the latest reply
+    /// if None); `None` feedback clears a rating
+    pub fn rate_reply(&mut self, reply: Option<&str>, feedback: Option<Feedback>) -> bool {
+        let found = self.messages.iter_mut().rev().find(|sm| {
+            sm.message.role == Role::Assistant
+                && !sm.message.content.trim().is_empty()
+                && reply.is_none_or(|r| sm.message.content.trim() == r.trim())
+        });
+        match found {
+            Some(sm) => {
+                sm.feedback = feedback;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pin (or unpin) the latest user message or reply with this text, or
+    /// failing that the latest one containing it (the latest reply if
+    /// None). Returns the message, or None if nothing matched.
+    pub fn set_pinned(&mut self, text: Option<&str>, pinned: bool) -> Option<&Message> {
+        // Tool calls and their results must stay together, so only plain
+        // messages can be pinned
+        let pinnable = |sm: &SessionMessage| {
+            matches!(sm.message.role, Role::User | Role::Assistant)
+                && sm.message.tool_calls.is_none()
+                && !sm.message.content.trim().is_empty()
+        };
+        let found = match text.map(str::trim) {
+            None => self
+                .messages
+                .iter()
+                .rposition(|sm| pinnable(sm) && sm.message.role == Role::Assistant),
+            Some(text) => self
+                .messages
+                .iter()
+                .rposition(|sm| pinnable(sm) && sm.message.content.trim() == text)
+                .or_else(|| {
+                    self.messages
+                        .iter()
+                        .rposition(|sm| pinnable(sm) && sm.message.content.contains(text))
+                }),
+        }?;
+        self.messages[found].pinned = pinned;
+        Some(&self.messages[found].message)
+    }
+
+    /// Messages compaction keeps verbatim
+    pub fn pinned_messages(&self) -> Vec<&Message> {
+        self.messages
+            .iter()
+            .filter(|sm| sm.pinned)
+            .map(|sm| &sm.message)
+            .collect()
+    }
+
+    /// Rated replies, each with the user message before it
+    pub fn rated_pairs(&self) -> Vec<RatedPair> {
+        let mut pairs = Vec::new();
+        let mut prompt = "";
+        for sm in &self.messages {
+            match sm.message.role {
+                Role::User => prompt = &sm.message.content,
+                Role::Assistant => {
+                    if let Some(ref feedback) = sm.feedback {
+                        pairs.push(RatedPair {
+                            session_id: self.id.clone(),
+                            prompt: prompt.to_string(),
+                            response: sm.message.content.clone(),
+                            rating: feedback.rating,
+                            note: feedback.note.clone(),
+                            model: sm.model.clone(),
+                            at: feedback.at,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        pairs
+    }
+
     pub fn messages_for_llm(&self) -> Vec<Message> {
         let mut messages = Vec::new();
 
-        if let Some(ref context) = self.system_context {
+        let context = match (&self.system_context, self.session_instructions()) {
+            (Some(context), Some(prompt)) => Some(format!(
+                "{}\n\n---\n\n# Session Instructions\n\n{}",
+                context, prompt
+            )),
+            (Some(context), None) => Some(context.clone()),
+            (None, prompt) => prompt,
+        };
+
+        if let Some(context) = context {
             messages.push(Message {
                 role: Role::System,
-                content: context.clone(),
+                content: context,
                 tool_calls: None,
                 tool_call_id: None,
                 images: Vec::new(),
@@ -230,48 +498,205 @@ impl Session {
             .collect()
     }
 
-    pub async fn compact(&mut self, provider: &dyn LLMProvider) -> Result<()> {
-        if self.messages.len() < 4 {
+    /// Replace older messages following `strategy`: with a summary written
+    /// by the provider following `prompt`, or (drop-oldest) with nothing.
+    /// `keep_recent` is the number of latest messages left as they are.
+    pub async fn compact(
+        &mut self,
+        provider: &dyn LLMProvider,
+        prompt: &str,
+        strategy: CompactionStrategy,
+        keep_recent: usize,
+    ) -> Result<()> {
+        let Some(plan) = self.plan_compaction(strategy, keep_recent) else {
             return Ok(());
-        }
+        };
+        let summary = match plan.text {
+            Some(ref text) => Some(provider.summarize_with(prompt, text).await?),
+            None => None,
+        };
+        self.apply_compaction(plan, summary)
+    }
 
-        let keep_count = 4;
-        let to_summarize = &self.messages[..self.messages.len() - keep_count];
+    /// Pick the messages `compact` would replace, without touching the
+    /// session, so the summary can be written elsewhere (e.g. on a
+    /// background thread). None if there is nothing to compact.
+    pub fn plan_compaction(
+        &self,
+        strategy: CompactionStrategy,
+        keep_recent: usize,
+    ) -> Option<CompactionPlan> {
+        let mut split = match strategy {
+            // The current turn starts at the last user message
+            CompactionStrategy::SummarizeAll => self
+                .messages
+                .iter()
+                .rposition(|sm| sm.message.role == Role::User)
+                .unwrap_or(self.messages.len()),
+            _ => self.messages.len().saturating_sub(keep_recent),
+        };
+        // Keep tool results with the call that asked for them
+        while split > 0
+            && split < self.messages.len()
+            && self.messages[split].message.role == Role::Tool
+        {
+            split -= 1;
+        }
+        // Nothing but pinned messages to replace
+        if self.messages[..split].iter().all(|sm| sm.pinned) {
+            return None;
+        }
 
-        let text: String = to_summarize
-            .iter()
-            .map(|sm| format!("{:?}: {}", sm.message.role, sm.message.content))
-            .collect::<Vec<_>>()
-            .join("\n\n");
+        let text = match strategy {
+            CompactionStrategy::DropOldest => None,
+            CompactionStrategy::RollingSummary => {
+                // Only the messages since the last summary are read again
+                let (previous, start) = match self.messages[0]
+                    .message
+                    .content
+                    .strip_prefix(SUMMARY_PREFIX)
+                {
+                    Some(previous) if self.messages[0].message.role == Role::System => {
+                        (Some(previous), 1)
+                    }
+                    _ => (None, 0),
+                };
+                let mut text = transcript(&self.messages[start..split]);
+                if let Some(previous) = previous {
+                    text = format!(
+                        "Summary so far:\n\n{}\n\nNew messages to fold into it:\n\n{}",
+                        previous, text
+                    );
+                }
+                Some(text)
+            }
+            _ => Some(transcript(&self.messages[..split])),
+        };
 
-        let summary = provider.summarize(&text).await?;
+        Some(CompactionPlan {
+            strategy,
+            replaced: self.messages[..split]
+                .iter()
+                .map(|sm| (sm.timestamp, sm.message.role, sm.message.content.clone()))
+                .collect(),
+            pinned: (0..split).filter(|&i| self.messages[i].pinned).collect(),
+            text,
+        })
+    }
 
-        let mut new_messages = vec![SessionMessage::new(Message {
-            role: Role::System,
-            content: format!("Previous conversation summary:\n\n{}", summary),
-            tool_calls: None,
-            tool_call_id: None,
-            images: Vec::new(),
-        })];
+    /// Replace the messages picked by `plan` with `summary`, followed by the
+    /// pinned ones among them. Messages added since the plan was made are
+    /// kept; fails if the planned ones were changed in the meantime
+    /// (cleared, rewound, truncated).
+    pub fn apply_compaction(
+        &mut self,
+        plan: CompactionPlan,
+        summary: Option<String>,
+    ) -> Result<()> {
+        let split = plan.replaced.len();
+        let unchanged = self.messages.len() >= split
+            && self.messages[..split].iter().zip(&plan.replaced).all(
+                |(sm, (timestamp, role, content))| {
+                    sm.timestamp == *timestamp
+                        && sm.message.role == *role
+                        && sm.message.content == *content
+                },
+            );
+        if !unchanged {
+            anyhow::bail!("the history changed while it was being compacted");
+        }
 
-        new_messages.extend(self.messages[self.messages.len() - keep_count..].to_vec());
+        let tokens_before = self.token_count;
+        let mut new_messages = Vec::new();
+        if let Some(ref summary) = summary {
+            new_messages.push(SessionMessage::new(Message {
+                role: Role::System,
+                content: format!("{}{}", SUMMARY_PREFIX, summary),
+                tool_calls: None,
+                tool_call_id: None,
+                images: Vec::new(),
+            }));
+        }
+        // Pinned when the plan was made (left out of the summary) or since
+        new_messages.extend(
+            self.messages
+                .drain(..)
+                .enumerate()
+                .filter(|(i, sm)| *i >= split || sm.pinned || plan.pinned.contains(i))
+                .map(|(_, sm)| sm),
+        );
 
         self.messages = new_messages;
         self.compaction_count += 1;
         self.recalculate_tokens();
+        self.compactions.push(CompactionEvent {
+            at: Utc::now(),
+            tokens_before,
+            tokens_after: self.token_count,
+            strategy: Some(plan.strategy),
+            summary,
+        });
 
         Ok(())
     }
 
+    /// Drop the oldest unpinned messages until the history fits in `limit`
+    /// tokens, shortening the last one if it is too long on its own. Returns how
+    /// many messages were dropped.
+    pub fn truncate_to_fit(&mut self, limit: usize) -> usize {
+        let mut dropped = 0;
+        while self.token_count > limit {
+            // Pinned messages stay, and so does the last one
+            let oldest = match self.messages.iter().position(|sm| !sm.pinned) {
+                Some(i) if i + 1 < self.messages.len() => i,
+                _ => break,
+            };
+            let removed = self.messages.remove(oldest);
+            self.token_count = self
+                .token_count
+                .saturating_sub(self.tokens.message(&removed.message));
+            dropped += 1;
+            // Tool results can't come before the call that asked for them
+            while oldest + 1 < self.messages.len()
+                && self.messages[oldest].message.role == Role::Tool
+            {
+                let removed = self.messages.remove(oldest);
+                self.token_count = self
+                    .token_count
+                    .saturating_sub(self.tokens.message(&removed.message));
+                dropped += 1;
+            }
+        }
+
+        if self.token_count > limit {
+            let marker = "\n\n[... truncated to fit the context window]";
+            let over = self.token_count - limit + self.tokens.count(marker);
+            if let Some(last) = self.messages.last_mut() {
+                let content = &last.message.content;
+                let tokens = self.tokens.count(content).max(1);
+                let mut keep = content.len() * tokens.saturating_sub(over) / tokens;
+                while !content.is_char_boundary(keep) {
+                    keep -= 1;
+                }
+                last.message.content = format!("{}{}", &content[..keep], marker);
+            }
+        }
+        self.recalculate_tokens();
+        dropped
+    }
+
     fn recalculate_tokens(&mut self) {
         self.token_count = 0;
 
         if let Some(ref context) = self.system_context {
-            self.token_count += estimate_tokens(context);
+            self.token_count += self.tokens.count(context);
+        }
+        if let Some(ref instructions) = self.session_instructions() {
+            self.token_count += self.tokens.count(instructions);
         }
 
         for sm in &self.messages {
-            self.token_count += estimate_tokens(&sm.message.content);
+            self.token_count += self.tokens.message(&sm.message);
         }
     }
 
@@ -298,7 +723,7 @@ impl Session {
         let mut file = File::create(path)?;
 
         // Write Pi-compatible header
-        let header = json!({
+        let mut header = json!({
             "type": "session",
             "version": CURRENT_SESSION_VERSION,
             "id": self.id,
@@ -308,6 +733,21 @@ impl Session {
             "compactionCount": self.compaction_count,
             "memoryFlushCompactionCount": self.memory_flush_compaction_count
         });
+        if !self.generation.is_empty() {
+            header["generation"] = serde_json::to_value(&self.generation)?;
+        }
+        if let Some(ref prompt) = self.system_prompt {
+            header["systemPrompt"] = json!(prompt);
+        }
+        if let Some(ref language) = self.language {
+            header["language"] = json!(language);
+        }
+        if let Some(strategy) = self.compaction_strategy {
+            header["compactionStrategy"] = json!(strategy);
+        }
+        if !self.compactions.is_empty() {
+            header["compactions"] = serde_json::to_value(&self.compactions)?;
+        }
         writeln!(file, "{}", serde_json::to_string(&header)?)?;
 
         // Write system context as a system message
@@ -403,6 +843,12 @@ impl Session {
         if let Some(ref reason) = sm.stop_reason {
             message["stopReason"] = json!(reason);
         }
+        if let Some(ref feedback) = sm.feedback {
+            message["feedback"] = serde_json::to_value(feedback).unwrap_or(json!(null));
+        }
+        if sm.pinned {
+            message["pinned"] = json!(true);
+        }
         message["timestamp"] = json!(sm.timestamp);
 
         json!({
@@ -423,6 +869,17 @@ impl Session {
         Self::load_from_path(&path, session_id)
     }
 
+    /// Load a session saved with `save_for_agent`
+    pub fn load_for_agent(agent_id: &str, session_id: &str) -> Result<Self> {
+        let path = get_sessions_dir_for_agent(agent_id)?.join(format!("{}.jsonl", session_id));
+
+        if !path.exists() {
+            anyhow::bail!("Session not found: {}", session_id);
+        }
+
+        Self::load_from_path(&path, session_id)
+    }
+
     fn load_from_path(path: &PathBuf, session_id: &str) -> Result<Self> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
@@ -436,6 +893,12 @@ impl Session {
             token_count: 0,
             compaction_count: 0,
             memory_flush_compaction_count: 0,
+            generation: GenerationParams::default(),
+            system_prompt: None,
+            language: None,
+            compaction_strategy: None,
+            compactions: Vec::new(),
+            tokens: TokenCounter::default(),
         };
 
         for line in reader.lines() {
@@ -466,6 +929,16 @@ impl Session {
                     if let Some(count) = entry["memoryFlushCompactionCount"].as_u64() {
                         session.memory_flush_compaction_count = count as u32;
                     }
+                    if let Ok(generation) = serde_json::from_value(entry["generation"].clone()) {
+                        session.generation = generation;
+                    }
+                    session.system_prompt = entry["systemPrompt"].as_str().map(|s| s.to_string());
+                    session.language = entry["language"].as_str().map(|s| s.to_string());
+                    session.compaction_strategy =
+                        serde_json::from_value(entry["compactionStrategy"].clone()).unwrap_or(None);
+                    if let Ok(compactions) = serde_json::from_value(entry["compactions"].clone()) {
+                        session.compactions = compactions;
+                    }
                 }
                 // Pi format message
                 Some("message") => {
@@ -548,6 +1021,8 @@ impl Session {
             usage,
             stop_reason: msg["stopReason"].as_str().map(|s| s.to_string()),
             timestamp: msg["timestamp"].as_u64().unwrap_or(0),
+            feedback: serde_json::from_value(msg["feedback"].clone()).ok(),
+            pinned: msg["pinned"].as_bool().unwrap_or(false),
         })
     }
 
@@ -556,20 +1031,30 @@ impl Session {
             id: self.id.clone(),
             message_count: self.messages.len(),
             token_count: self.token_count,
+            usable_tokens: 0,
             compaction_count: self.compaction_count,
             api_input_tokens: 0,
             api_output_tokens: 0,
+            api_reasoning_tokens: 0,
+            generation: self.generation.clone(),
+            system_prompt: self.system_prompt.clone(),
+            language: self.language.clone(),
         }
     }
 
-    pub fn status_with_usage(&self, input_tokens: u64, output_tokens: u64) -> SessionStatus {
+    pub fn status_with_usage(&self, usage: &Usage) -> SessionStatus {
         SessionStatus {
             id: self.id.clone(),
             message_count: self.messages.len(),
             token_count: self.token_count,
+            usable_tokens: 0,
             compaction_count: self.compaction_count,
-            api_input_tokens: input_tokens,
-            api_output_tokens: output_tokens,
+            api_input_tokens: usage.input_tokens,
+            api_output_tokens: usage.output_tokens,
+            api_reasoning_tokens: usage.reasoning_tokens,
+            generation: self.generation.clone(),
+            system_prompt: self.system_prompt.clone(),
+            language: self.language.clone(),
         }
     }
 
@@ -596,26 +1081,26 @@ fn get_sessions_dir() -> Result<PathBuf> {
 }
 
 pub fn get_sessions_dir_for_agent(agent_id: &str) -> Result<PathBuf> {
-    let base = directories::BaseDirs::new()
-        .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
-
-    Ok(base
-        .home_dir()
-        .join(".localgpt")
+    Ok(get_state_dir()?
         .join("agents")
         .join(agent_id)
         .join("sessions"))
 }
 
-pub fn get_state_dir() -> Result<PathBuf> {
-    let base = directories::BaseDirs::new()
-        .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
-
-    Ok(base.home_dir().join(".localgpt"))
+/// Messages as plain text for a summary
+/// Messages as text for a summary, leaving out pinned ones (kept as they are)
+fn transcript(messages: &[SessionMessage]) -> String {
+    messages
+        .iter()
+        .filter(|sm| !sm.pinned)
+        .map(|sm| format!("{:?}: {}", sm.message.role, sm.message.content))
+        .collect::<Vec<_>>()
+        .join("\n\n")
 }
 
-fn estimate_tokens(text: &str) -> usize {
-    text.len() / 4
+/// LocalGPT state directory (`~/.localgpt`, or the active user profile's home)
+pub fn get_state_dir() -> Result<PathBuf> {
+    crate::config::localgpt_home()
 }
 
 #[derive(Debug, Clone)]
@@ -684,7 +1169,7 @@ pub fn list_sessions_for_agent(agent_id: &str) -> Result<Vec<SessionInfo>> {
         }
     }
 
-    sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
     Ok(sessions)
 }
 
@@ -749,7 +1234,7 @@ pub fn search_sessions_for_agent(agent_id: &str, query: &str) -> Result<Vec<Sess
         }
     }
 
-    results.sort_by(|a, b| b.match_count.cmp(&a.match_count));
+    results.sort_by_key(|r| std::cmp::Reverse(r.match_count));
     Ok(results)
 }
 
@@ -794,10 +1279,327 @@ mod tests {
         let usage = Usage {
             input_tokens: 100,
             output_tokens: 50,
+            ..Default::default()
         };
         let msg_usage = MessageUsage::from(&usage);
         assert_eq!(msg_usage.input, 100);
         assert_eq!(msg_usage.output, 50);
         assert_eq!(msg_usage.total_tokens, 150);
     }
+
+    #[test]
+    fn test_overrides_persist_in_header() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.jsonl");
+
+        let mut session = Session::new();
+        session.set_system_context("You are LocalGPT.".to_string());
+        session.set_system_prompt(Some("Answer in French.".to_string()));
+        let mut params = GenerationParams::default();
+        params.set("temperature", "0.2").unwrap();
+        params.set("max_tokens", "2000").unwrap();
+        assert!(params.set("temperature", "hot").is_err());
+        session.set_generation_params(params.clone());
+        session.save_to_path(&path).unwrap();
+
+        let loaded = Session::load_from_path(&path, session.id()).unwrap();
+        assert_eq!(loaded.generation_params(), &params);
+        assert_eq!(loaded.system_prompt(), Some("Answer in French."));
+        let system = &loaded.messages_for_llm()[0].content;
+        assert!(system.starts_with("You are LocalGPT."));
+        assert!(system.ends_with("Answer in French."));
+    }
+    #[test]
+    fn test_language_persists_and_is_injected() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.jsonl");
+
+        let mut session = Session::new();
+        session.set_language(Some(" German ".to_string()));
+        session.save_to_path(&path).unwrap();
+
+        let mut loaded = Session::load_from_path(&path, session.id()).unwrap();
+        assert_eq!(loaded.language(), Some("German"));
+        assert!(loaded.messages_for_llm()[0].content.contains("in German"));
+
+        loaded.set_language(None);
+        assert!(loaded.messages_for_llm().is_empty());
+    }
+
+    /// Summarizes by quoting what it was given
+    struct EchoSummarizer;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for EchoSummarizer {
+        async fn chat(
+            &self,
+            messages: &[Message],
+            _tools: Option<&[super::super::providers::ToolSchema]>,
+        ) -> Result<super::super::providers::LLMResponse> {
+            Ok(super::super::providers::LLMResponse::text(
+                messages.last().unwrap().content.clone(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compaction_strategies() {
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let turns = |session: &mut Session, n: usize| {
+            for i in 0..n {
+                session.add_message(message(Role::User, &format!("q{}", i)));
+                session.add_message(message(Role::Assistant, &format!("a{}", i)));
+            }
+        };
+
+        let mut session = Session::new();
+        turns(&mut session, 3);
+        session
+            .compact(
+                &EchoSummarizer,
+                "Sum:",
+                CompactionStrategy::RollingSummary,
+                2,
+            )
+            .await
+            .unwrap();
+        assert_eq!(session.messages().len(), 3);
+        turns(&mut session, 2);
+        session
+            .compact(
+                &EchoSummarizer,
+                "Sum:",
+                CompactionStrategy::RollingSummary,
+                2,
+            )
+            .await
+            .unwrap();
+        // The earlier summary is folded in rather than quoted as a message
+        let summary = session.compactions()[1].summary.clone().unwrap();
+        assert!(summary.contains("Summary so far:"));
+        assert!(summary.contains("New messages to fold into it:\n\nUser: q2"));
+        assert!(!summary.contains("System: Previous conversation summary"));
+
+        session
+            .compact(&EchoSummarizer, "Sum:", CompactionStrategy::DropOldest, 1)
+            .await
+            .unwrap();
+        assert_eq!(session.messages().len(), 1);
+        assert_eq!(session.compactions()[2].summary, None);
+
+        let mut session = Session::new();
+        turns(&mut session, 2);
+        session.add_message(message(Role::User, "current"));
+        session
+            .compact(&EchoSummarizer, "Sum:", CompactionStrategy::SummarizeAll, 4)
+            .await
+            .unwrap();
+        let contents: Vec<String> = session
+            .messages()
+            .into_iter()
+            .map(|m| m.content.clone())
+            .collect();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[1], "current");
+
+        // The session's own strategy and the summaries are saved with it
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.jsonl");
+        session.set_compaction_strategy(Some(CompactionStrategy::DropOldest));
+        session.save_to_path(&path).unwrap();
+        let loaded = Session::load_from_path(&path, session.id()).unwrap();
+        assert_eq!(
+            loaded.compaction_strategy(),
+            Some(CompactionStrategy::DropOldest)
+        );
+        assert_eq!(
+            loaded.compactions()[0].strategy,
+            Some(CompactionStrategy::SummarizeAll)
+        );
+        assert!(loaded.compactions()[0].summary.is_some());
+    }
+
+    #[test]
+    fn test_apply_compaction_plan() {
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let mut session = Session::new();
+        for i in 0..3 {
+            session.add_message(message(Role::User, &format!("q{}", i)));
+            session.add_message(message(Role::Assistant, &format!("a{}", i)));
+        }
+        let plan = session
+            .plan_compaction(CompactionStrategy::KeepRecent, 2)
+            .unwrap();
+        assert!(plan.text.as_deref().unwrap().contains("User: q1"));
+
+        // Messages added while the summary was being written are kept
+        session.add_message(message(Role::User, "q3"));
+        session
+            .apply_compaction(plan.clone(), Some("sum".into()))
+            .unwrap();
+        let contents: Vec<&str> = session
+            .messages()
+            .into_iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            ["Previous conversation summary:\n\nsum", "q2", "a2", "q3"]
+        );
+
+        // The planned messages are gone now, so the plan no longer applies
+        assert!(session.apply_compaction(plan, Some("sum".into())).is_err());
+        assert_eq!(session.compactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_messages_survive_compaction() {
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let mut session = Session::new();
+        session.add_message(message(Role::User, "Always answer in French."));
+        session.add_message(message(Role::Assistant, "D'accord."));
+        for i in 0..3 {
+            session.add_message(message(Role::User, &format!("q{}", i)));
+            session.add_message(message(Role::Assistant, &format!("a{}", i)));
+        }
+        assert!(session.set_pinned(Some("in French"), true).is_some());
+        assert!(session.set_pinned(Some("no such message"), true).is_none());
+
+        session
+            .compact(&EchoSummarizer, "Sum:", CompactionStrategy::KeepRecent, 2)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = session
+            .messages()
+            .into_iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents[1..], ["Always answer in French.", "q2", "a2"]);
+        // Kept verbatim rather than summarized
+        let summary = session.compactions()[0].summary.clone().unwrap();
+        assert!(!summary.contains("French"));
+
+        // Truncation drops the summary before the pinned message
+        session.truncate_to_fit(session.token_count() - 1);
+        assert_eq!(session.messages()[0].content, "Always answer in French.");
+        assert_eq!(session.pinned_messages().len(), 1);
+    }
+
+    #[test]
+    fn test_rewind_last_turn() {
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let mut session = Session::new();
+        session.add_message(message(Role::User, "first"));
+        session.add_message(message(Role::Assistant, "one"));
+        session.add_message(message(Role::User, "second"));
+        session.add_message(message(Role::Tool, "output"));
+        session.add_message(message(Role::Assistant, "two"));
+
+        assert!(session.replace_last_reply("two, again"));
+        assert_eq!(session.messages()[4].content, "two, again");
+
+        let rewound = session.rewind_last_turn().unwrap();
+        assert_eq!(rewound.content, "second");
+        assert_eq!(session.messages().len(), 2);
+        assert_eq!(
+            session.token_count(),
+            TokenCounter::default().count("first") + TokenCounter::default().count("one")
+        );
+    }
+
+    #[test]
+    fn test_truncate_to_fit() {
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let mut session = Session::new();
+        session.add_message(message(Role::User, &"a".repeat(400)));
+        session.add_message(message(Role::Assistant, &"b".repeat(400)));
+        session.add_message(message(Role::Tool, &"c".repeat(400)));
+        session.add_message(message(Role::User, &"d".repeat(400)));
+        assert_eq!(session.token_count(), 400);
+
+        // The orphaned tool result goes with its call
+        assert_eq!(session.truncate_to_fit(250), 3);
+        assert_eq!(session.messages().len(), 1);
+        assert_eq!(session.token_count(), 100);
+
+        // A message too long on its own is shortened
+        assert_eq!(session.truncate_to_fit(50), 0);
+        assert!(session.token_count() <= 50);
+        assert!(session.messages()[0]
+            .content
+            .ends_with("[... truncated to fit the context window]"));
+    }
+
+    #[test]
+    fn test_feedback_persists() {
+        use super::super::feedback::Rating;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("s.jsonl");
+        let message = |role, content: &str| Message {
+            role,
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Vec::new(),
+        };
+        let mut session = Session::new();
+        session.add_message(message(Role::User, "first"));
+        session.add_message(message(Role::Assistant, "one"));
+        session.add_message(message(Role::User, "second"));
+        session.add_message(message(Role::Assistant, "two"));
+
+        let down = Feedback::new(Rating::Down, Some("too terse"));
+        assert!(session.rate_reply(Some("one"), Some(down.clone())));
+        assert!(session.rate_reply(None, Some(Feedback::new(Rating::Up, None))));
+        assert!(!session.rate_reply(Some("three"), None));
+        session.save_to_path(&path).unwrap();
+
+        let mut loaded = Session::load_from_path(&path, session.id()).unwrap();
+        let pairs = loaded.rated_pairs();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(
+            (pairs[0].prompt.as_str(), pairs[0].rating),
+            ("first", Rating::Down)
+        );
+        assert_eq!(pairs[0].note.as_deref(), Some("too terse"));
+        assert_eq!(
+            (pairs[1].prompt.as_str(), pairs[1].rating),
+            ("second", Rating::Up)
+        );
+
+        // Regenerating drops the old reply's rating
+        loaded.replace_last_reply("two, again");
+        assert_eq!(loaded.rated_pairs().len(), 1);
+    }
 }
@@ -10,6 +10,7 @@ use std::fs;
 use std::path::PathBuf;
 use tracing::debug;
 
+use super::providers::Usage;
 use super::session::{get_sessions_dir_for_agent, DEFAULT_AGENT_ID};
 
 /// Session entry in sessions.json (matches OpenClaw's SessionEntry)
@@ -100,6 +101,15 @@ impl SessionEntry {
         self.updated_at = chrono::Utc::now().timestamp_millis() as u64;
     }
 
+    /// Add the tokens of one model request to the running totals
+    pub fn add_usage(&mut self, usage: &Usage) {
+        let input = self.input_tokens.unwrap_or(0) + usage.input_tokens;
+        let output = self.output_tokens.unwrap_or(0) + usage.output_tokens;
+        self.input_tokens = Some(input);
+        self.output_tokens = Some(output);
+        self.total_tokens = Some(input + output);
+    }
+
     /// Check if a heartbeat would be a duplicate (same text within 24 hours)
     pub fn is_duplicate_heartbeat(&self, text: &str) -> bool {
         const DEDUP_WINDOW_MS: u64 = 24 * 60 * 60 * 1000; // 24 hours
@@ -224,6 +234,11 @@ impl SessionStore {
         self.save()
     }
 
+    /// Add token usage to the entry for a LocalGPT session (keyed by its ID)
+    pub fn add_usage(&mut self, session_id: &str, usage: &Usage) -> Result<()> {
+        self.load_and_update(session_id, session_id, |entry| entry.add_usage(usage))
+    }
+
     /// Get CLI session ID for a session and provider
     pub fn get_cli_session_id(&self, session_key: &str, provider: &str) -> Option<String> {
         self.get(session_key)
@@ -285,6 +300,17 @@ mod tests {
         assert_eq!(deserialized.input_tokens, Some(100));
         assert_eq!(deserialized.output_tokens, Some(50));
         assert_eq!(deserialized.total_tokens, Some(150));
+
+        // Usage from later turns accumulates
+        let mut entry = deserialized;
+        entry.add_usage(&Usage {
+            input_tokens: 20,
+            output_tokens: 5,
+            ..Default::default()
+        });
+        assert_eq!(entry.input_tokens, Some(120));
+        assert_eq!(entry.output_tokens, Some(55));
+        assert_eq!(entry.total_tokens, Some(175));
     }
 
     #[test]
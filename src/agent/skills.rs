@@ -213,7 +213,9 @@ pub fn load_skills(workspace: &Path) -> Result<Vec<Skill>> {
 
 /// Get the managed skills directory (~/.localgpt/skills/)
 fn get_managed_skills_dir() -> Option<PathBuf> {
-    directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".localgpt").join("skills"))
+    crate::config::localgpt_home()
+        .ok()
+        .map(|home| home.join("skills"))
 }
 
 /// Load skills from a single directory
@@ -489,16 +491,15 @@ pub fn build_skills_prompt(skills: &[Skill]) -> String {
         return String::new();
     }
 
-    let mut lines = Vec::new();
-    lines.push("## Skills".to_string());
-    lines.push(String::new());
-    lines.push(
+    let mut lines = vec![
+        "## Skills".to_string(),
+        String::new(),
         "Before replying: scan available skills below. If one clearly applies, \
          read its SKILL.md with read_file, then follow it."
             .to_string(),
-    );
-    lines.push(String::new());
-    lines.push("<available_skills>".to_string());
+        String::new(),
+        "<available_skills>".to_string(),
+    ];
 
     for skill in &prompt_skills {
         let emoji_prefix = skill
@@ -0,0 +1,494 @@
+//! Workspace snapshots for undoing agent file changes
+//!
+//! Before a write_file or edit_file call, the target file's current contents
+//! are copied aside; before a bash call, every file in the workspace is (the
+//! command could touch anything). One snapshot covers one user turn, so undo
+//! puts files back the way they were before the agent's last turn that
+//! changed them.
+//!
+//! File tools' paths are resolved through the agent's `PathSandbox`, so
+//! changes in `tools.allowed_dirs` are captured too; bash captures only the
+//! workspace.
+//!
+//! Files a bash command creates are found by listing the workspace before
+//! and after it runs, and are removed on undo; files created any other way
+//! (by the user, say) are left alone. Workspace listings skip hidden and
+//! gitignored paths (build output, dependencies), which undo leaves as is.
+//!
+//! Snapshots live under `~/.localgpt/snapshots/<workspace>/<id>/` as a
+//! manifest plus copies of the original files, so agents with their own
+//! workspaces never undo each other's changes. Only the most recent few are
+//! kept per workspace.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use super::diff::parse_patch;
+use super::sandbox::PathSandbox;
+
+/// Snapshots kept on disk; older ones are deleted
+const MAX_SNAPSHOTS: usize = 20;
+
+/// Larger files are left out of whole-workspace captures
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Whole-workspace captures stop copying after this many bytes
+const MAX_WORKSPACE_BYTES: u64 = 200 * 1024 * 1024;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Tools whose calls are snapshotted before they run
+pub const SNAPSHOT_TOOLS: &[&str] = &["write_file", "edit_file", "apply_patch", "bash"];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    created_at: DateTime<Utc>,
+    /// Workspace the snapshot was taken in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workspace: Option<PathBuf>,
+    files: Vec<FileEntry>,
+    /// Every workspace file present when the workspace was captured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workspace_files: Option<Vec<PathBuf>>,
+    /// Files bash commands created during the turn; removed on undo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    created: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    path: PathBuf,
+    /// Copy of the original contents; None if the file did not exist
+    blob: Option<String>,
+}
+
+/// What an undo changed
+#[derive(Debug, Default, PartialEq)]
+pub struct UndoSummary {
+    pub restored: usize,
+    pub removed: usize,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for UndoSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Undid agent changes: {} file(s) restored, {} removed",
+            self.restored, self.removed
+        )?;
+        if let Some(at) = self.created_at {
+            write!(
+                f,
+                " (snapshot from {})",
+                at.with_timezone(&chrono::Local).format("%H:%M:%S")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot being recorded for the current turn
+struct Pending {
+    dir: PathBuf,
+    manifest: Manifest,
+    captured: HashSet<PathBuf>,
+    /// Workspace listing from just before the running bash call
+    bash_before: Option<HashSet<PathBuf>>,
+}
+
+pub struct SnapshotStore {
+    root: PathBuf,
+    workspace: PathBuf,
+    /// Resolves file tools' paths the way the tools do
+    sandbox: PathSandbox,
+    /// Snapshot for the current turn, created on the first capture
+    pending: Option<Pending>,
+}
+
+impl SnapshotStore {
+    /// Snapshots of the sandbox's workspace, kept in their own directory
+    /// under `root`
+    pub fn new(root: PathBuf, sandbox: impl Into<PathSandbox>) -> Self {
+        let sandbox = sandbox.into();
+        let workspace = sandbox.workspace().to_path_buf();
+        let key = format!(
+            "{:x}",
+            Sha256::digest(workspace.to_string_lossy().as_bytes())
+        );
+        Self {
+            root: root.join(&key[..16]),
+            workspace,
+            sandbox,
+            pending: None,
+        }
+    }
+
+    /// Start a new turn; the next capture opens a new snapshot
+    pub fn begin_turn(&mut self) {
+        self.pending = None;
+    }
+
+    /// Record the state of files a tool call is about to change
+    pub fn capture_for_tool(&mut self, tool: &str, arguments: &str) -> Result<()> {
+        match tool {
+            "write_file" | "edit_file" => {
+                let args: serde_json::Value = serde_json::from_str(arguments)?;
+                // A path outside the sandbox is refused by the tool itself
+                if let Some(path) = args["path"].as_str() {
+                    if let Ok(path) = self.sandbox.resolve(path) {
+                        self.capture_file(&path)?;
+                    }
+                }
+                Ok(())
+            }
+            "apply_patch" => {
+                let args: serde_json::Value = serde_json::from_str(arguments)?;
+                let Some(patch) = args["patch"].as_str() else {
+                    return Ok(());
+                };
+                // A patch the tool can't parse changes nothing
+                for file in parse_patch(patch).unwrap_or_default() {
+                    for path in [file.old_path, file.new_path].into_iter().flatten() {
+                        if let Ok(path) = self.sandbox.resolve(&path) {
+                            self.capture_file(&path)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            "bash" => {
+                self.capture_workspace()?;
+                let before = list_files(&self.workspace).into_iter().collect();
+                self.pending()?.bash_before = Some(before);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Note the files a bash call created, once it has finished
+    pub fn after_tool(&mut self, tool: &str) -> Result<()> {
+        if tool != "bash" {
+            return Ok(());
+        }
+        let workspace = self.workspace.clone();
+        let Some(pending) = self.pending.as_mut() else {
+            return Ok(());
+        };
+        let Some(before) = pending.bash_before.take() else {
+            return Ok(());
+        };
+        for path in list_files(&workspace) {
+            if !before.contains(&path) && !pending.manifest.created.contains(&path) {
+                pending.manifest.created.push(path);
+            }
+        }
+        write_manifest(pending)
+    }
+
+    /// Record a file's current contents (or absence), once per turn
+    pub fn capture_file(&mut self, path: &Path) -> Result<()> {
+        let pending = self.pending()?;
+        if pending.captured.contains(path) {
+            return Ok(());
+        }
+        save_file(pending, path)?;
+        write_manifest(pending)
+    }
+
+    /// Record every file in the workspace, once per turn
+    pub fn capture_workspace(&mut self) -> Result<()> {
+        let workspace = self.workspace.clone();
+        let pending = self.pending()?;
+        if pending.manifest.workspace_files.is_some() {
+            return Ok(());
+        }
+
+        let files = list_files(&workspace);
+        let mut copied = 0u64;
+        for path in &files {
+            if pending.captured.contains(path) {
+                continue;
+            }
+            let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if size > MAX_FILE_BYTES || copied + size > MAX_WORKSPACE_BYTES {
+                warn!("Not snapshotting {} (too large)", path.display());
+                continue;
+            }
+            save_file(pending, path)?;
+            copied += size;
+        }
+        debug!(
+            "Snapshotted {} workspace files ({} bytes)",
+            files.len(),
+            copied
+        );
+
+        pending.manifest.workspace_files = Some(files);
+        write_manifest(pending)
+    }
+
+    /// When the most recent snapshot was taken, if there is one
+    pub fn latest(&self) -> Option<DateTime<Utc>> {
+        let dir = self.snapshot_dirs().pop()?;
+        read_manifest(&dir).ok().map(|m| m.created_at)
+    }
+
+    /// Restore the files recorded in the most recent snapshot and delete it.
+    /// Repeating goes back one more turn.
+    pub fn undo_last(&mut self) -> Result<UndoSummary> {
+        self.pending = None;
+        let Some(dir) = self.snapshot_dirs().pop() else {
+            anyhow::bail!("No agent changes to undo");
+        };
+        let manifest = read_manifest(&dir)?;
+        if let Some(ref workspace) = manifest.workspace {
+            if *workspace != self.workspace {
+                anyhow::bail!(
+                    "The latest snapshot was taken in {}, not this workspace ({})",
+                    workspace.display(),
+                    self.workspace.display()
+                );
+            }
+        }
+        let mut summary = UndoSummary {
+            created_at: Some(manifest.created_at),
+            ..Default::default()
+        };
+
+        for entry in &manifest.files {
+            match entry.blob {
+                Some(ref blob) => {
+                    let original = fs::read(dir.join(blob))?;
+                    if fs::read(&entry.path).ok().as_deref() != Some(original.as_slice()) {
+                        if let Some(parent) = entry.path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::write(&entry.path, original)?;
+                        summary.restored += 1;
+                    }
+                }
+                None => {
+                    if entry.path.exists() {
+                        fs::remove_file(&entry.path)?;
+                        summary.removed += 1;
+                    }
+                }
+            }
+        }
+
+        // Files bash commands created
+        for path in &manifest.created {
+            if path.is_file() && manifest.files.iter().all(|f| f.path != *path) {
+                fs::remove_file(path)?;
+                summary.removed += 1;
+            }
+        }
+
+        fs::remove_dir_all(&dir)?;
+        Ok(summary)
+    }
+
+    fn pending(&mut self) -> Result<&mut Pending> {
+        if self.pending.is_none() {
+            let now = Utc::now();
+            let id = format!(
+                "{}-{}",
+                now.format("%Y%m%d-%H%M%S%.3f"),
+                &uuid::Uuid::new_v4().to_string()[..8]
+            );
+            let dir = self.root.join(id);
+            fs::create_dir_all(dir.join("files"))?;
+            self.pending = Some(Pending {
+                dir,
+                manifest: Manifest {
+                    created_at: now,
+                    workspace: Some(self.workspace.clone()),
+                    files: Vec::new(),
+                    workspace_files: None,
+                    created: Vec::new(),
+                },
+                captured: HashSet::new(),
+                bash_before: None,
+            });
+            self.prune();
+        }
+        Ok(self.pending.as_mut().expect("pending snapshot"))
+    }
+
+    /// Snapshot directories, oldest first
+    fn snapshot_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.join(MANIFEST_FILE).exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+        dirs.sort();
+        dirs
+    }
+
+    fn prune(&self) {
+        let dirs = self.snapshot_dirs();
+        if dirs.len() > MAX_SNAPSHOTS {
+            for dir in &dirs[..dirs.len() - MAX_SNAPSHOTS] {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+fn save_file(pending: &mut Pending, path: &Path) -> Result<()> {
+    let blob = if path.is_file() {
+        let name = format!("files/{}", pending.manifest.files.len());
+        fs::copy(path, pending.dir.join(&name))?;
+        Some(name)
+    } else {
+        None
+    };
+    pending.manifest.files.push(FileEntry {
+        path: path.to_path_buf(),
+        blob,
+    });
+    pending.captured.insert(path.to_path_buf());
+    Ok(())
+}
+
+fn write_manifest(pending: &Pending) -> Result<()> {
+    let json = serde_json::to_string_pretty(&pending.manifest)?;
+    fs::write(pending.dir.join(MANIFEST_FILE), json)?;
+    Ok(())
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest> {
+    Ok(serde_json::from_str(&fs::read_to_string(
+        dir.join(MANIFEST_FILE),
+    )?)?)
+}
+
+/// Regular files under the workspace, skipping hidden and gitignored paths
+fn list_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_undo_restores_edits_and_removes_new_files() {
+        let tmp = TempDir::new().unwrap();
+        let workspace = tmp.path().join("workspace");
+        fs::create_dir_all(workspace.join("memory")).unwrap();
+        let notes = workspace.join("memory/notes.md");
+        fs::write(&notes, "original").unwrap();
+
+        let mut store = SnapshotStore::new(tmp.path().join("snapshots"), workspace.clone());
+        store.begin_turn();
+
+        // edit_file, then a bash command that creates and deletes files
+        let args = serde_json::json!({ "path": notes }).to_string();
+        store.capture_for_tool("edit_file", &args).unwrap();
+        fs::write(&notes, "edited").unwrap();
+        store.capture_for_tool("bash", "{}").unwrap();
+        fs::write(&notes, "edited twice").unwrap();
+        fs::write(workspace.join("junk.txt"), "junk").unwrap();
+        store.after_tool("bash").unwrap();
+        // Created by the user, not the agent
+        fs::write(workspace.join("mine.txt"), "keep").unwrap();
+
+        let summary = store.undo_last().unwrap();
+        assert_eq!(summary.restored, 1);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "original");
+        assert!(!workspace.join("junk.txt").exists());
+        assert!(workspace.join("mine.txt").exists());
+
+        assert!(store.latest().is_none());
+        assert!(store.undo_last().is_err());
+    }
+
+    #[test]
+    fn test_workspaces_keep_separate_snapshots() {
+        let tmp = TempDir::new().unwrap();
+        let first = tmp.path().join("first");
+        let second = tmp.path().join("second");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(second.join("keep.txt"), "keep").unwrap();
+
+        let root = tmp.path().join("snapshots");
+        let mut a = SnapshotStore::new(root.clone(), first.clone());
+        let mut b = SnapshotStore::new(root, second.clone());
+        a.begin_turn();
+        a.capture_for_tool("bash", "{}").unwrap();
+        a.after_tool("bash").unwrap();
+
+        // The second workspace has nothing to undo, and its files stay
+        assert!(b.latest().is_none());
+        assert!(b.undo_last().is_err());
+        assert!(second.join("keep.txt").exists());
+        assert!(a.undo_last().is_ok());
+    }
+
+    #[test]
+    fn test_edits_in_allowed_dirs_are_captured() {
+        let tmp = TempDir::new().unwrap();
+        let workspace = tmp.path().join("workspace");
+        let notes_dir = tmp.path().join("notes");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&notes_dir).unwrap();
+        let todo = notes_dir.join("todo.md");
+        fs::write(&todo, "original").unwrap();
+
+        let sandbox = PathSandbox::new(workspace).with_allowed([notes_dir]);
+        let mut store = SnapshotStore::new(tmp.path().join("snapshots"), sandbox);
+        store.begin_turn();
+        let args = serde_json::json!({ "path": todo }).to_string();
+        store.capture_for_tool("write_file", &args).unwrap();
+        fs::write(&todo, "rewritten").unwrap();
+
+        assert_eq!(store.undo_last().unwrap().restored, 1);
+        assert_eq!(fs::read_to_string(&todo).unwrap(), "original");
+    }
+
+    #[test]
+    fn test_gitignored_files_are_skipped() {
+        let tmp = TempDir::new().unwrap();
+        let workspace = tmp.path().join("workspace");
+        fs::create_dir_all(workspace.join("target")).unwrap();
+        fs::write(workspace.join(".gitignore"), "target/\n").unwrap();
+        fs::write(workspace.join("target/old.o"), "old").unwrap();
+
+        let mut store = SnapshotStore::new(tmp.path().join("snapshots"), workspace.clone());
+        store.begin_turn();
+        store.capture_for_tool("bash", "{}").unwrap();
+        fs::write(workspace.join("target/new.o"), "new").unwrap();
+        fs::write(workspace.join("notes.txt"), "new").unwrap();
+        store.after_tool("bash").unwrap();
+
+        assert_eq!(store.undo_last().unwrap().removed, 1);
+        assert!(!workspace.join("notes.txt").exists());
+        assert!(workspace.join("target/new.o").exists());
+    }
+}
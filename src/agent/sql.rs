@@ -0,0 +1,796 @@
+//! `sql` tool (`[[tools.databases]]`)
+//!
+//! Runs one statement per call against a configured SQLite, PostgreSQL
+//! (`--features postgres`) or MySQL (`--features mysql`) database and
+//! returns the rows as a Markdown table. Databases are read-only unless
+//! configured with `read_only = false`: only statements that read are
+//! accepted, SQLite files are opened read-only, and on servers the
+//! statement runs in a read-only transaction that is rolled back. Server
+//! statements are always prepared, which refuses a batch of several.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+use super::providers::ToolSchema;
+use super::tools::Tool;
+use crate::config::{expand_env, Config, DatabaseConfig};
+
+/// Longest cell shown in a result table
+const MAX_CELL_CHARS: usize = 200;
+
+/// First keywords of statements allowed on read-only databases
+const READ_STATEMENTS: &[&str] = &[
+    "select", "with", "show", "explain", "describe", "desc", "values", "table", "pragma",
+];
+
+/// Where a database lives, from its `url`
+#[derive(Debug, Clone, PartialEq)]
+enum DatabaseKind {
+    Sqlite(PathBuf),
+    Postgres(String),
+    Mysql(String),
+}
+
+impl DatabaseKind {
+    fn parse(url: &str, workspace: &Path) -> Self {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Self::Postgres(url.to_string());
+        }
+        if url.starts_with("mysql://") {
+            return Self::Mysql(url.to_string());
+        }
+        let path = url.strip_prefix("sqlite:").unwrap_or(url);
+        let path = path.strip_prefix("//").unwrap_or(path);
+        Self::Sqlite(workspace.join(shellexpand::tilde(path).as_ref()))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sqlite(_) => "SQLite",
+            Self::Postgres(_) => "PostgreSQL",
+            Self::Mysql(_) => "MySQL",
+        }
+    }
+}
+
+struct Database {
+    name: String,
+    kind: DatabaseKind,
+    read_only: bool,
+    max_rows: usize,
+    timeout: Duration,
+}
+
+/// Rows from a query, or the count of rows a change affected
+#[derive(Debug, Default, PartialEq)]
+struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// More rows than `max_rows` were returned
+    truncated: bool,
+    affected: Option<u64>,
+}
+
+pub struct SqlTool {
+    databases: Vec<Database>,
+}
+
+impl SqlTool {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let workspace = config.workspace_path();
+        let databases = config
+            .tools
+            .databases
+            .iter()
+            .map(|db| Self::database(db, &workspace))
+            .collect::<Result<_>>()?;
+        Ok(Self { databases })
+    }
+
+    fn database(config: &DatabaseConfig, workspace: &Path) -> Result<Database> {
+        let url = expand_env(&config.url).map_err(|var| {
+            anyhow::anyhow!(
+                "Database '{}' url uses ${{{}}}, which is not set",
+                config.name,
+                var
+            )
+        })?;
+        Ok(Database {
+            name: config.name.clone(),
+            kind: DatabaseKind::parse(&url, workspace),
+            read_only: config.read_only,
+            max_rows: config.max_rows.max(1),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        })
+    }
+
+    fn find(&self, name: Option<&str>) -> Result<&Database> {
+        let names = || {
+            self.databases
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match name {
+            Some(name) => self
+                .databases
+                .iter()
+                .find(|d| d.name == name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Unknown database '{}'. Use one of: {}", name, names())
+                }),
+            None if self.databases.len() == 1 => Ok(&self.databases[0]),
+            None => anyhow::bail!("Missing database (one of: {})", names()),
+        }
+    }
+
+    async fn run(&self, db: &Database, statement: &str) -> Result<QueryResult> {
+        let statement = single_statement(statement)?;
+        if db.read_only && !is_read_statement(statement) {
+            anyhow::bail!(
+                "Database '{}' is read-only; only SELECT, WITH, SHOW, EXPLAIN and similar statements can run",
+                db.name
+            );
+        }
+        debug!("Running SQL on {}: {}", db.name, statement);
+
+        match db.kind {
+            DatabaseKind::Sqlite(ref path) => query_sqlite(path, statement, db).await,
+            #[cfg(feature = "postgres")]
+            DatabaseKind::Postgres(ref url) => {
+                tokio::time::timeout(db.timeout, query_postgres(url, statement, db))
+                    .await
+                    .map_err(|_| timed_out(db))?
+            }
+            #[cfg(feature = "mysql")]
+            DatabaseKind::Mysql(ref url) => {
+                tokio::time::timeout(db.timeout, query_mysql(url, statement, db))
+                    .await
+                    .map_err(|_| timed_out(db))?
+            }
+            #[allow(unreachable_patterns)]
+            ref other => anyhow::bail!(
+                "{} databases need a build with --features {}",
+                other.name(),
+                if matches!(other, DatabaseKind::Mysql(_)) {
+                    "mysql"
+                } else {
+                    "postgres"
+                }
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for SqlTool {
+    fn name(&self) -> &str {
+        "sql"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let databases: Vec<String> = self
+            .databases
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} ({}{})",
+                    d.name,
+                    d.kind.name(),
+                    if d.read_only { ", read-only" } else { "" }
+                )
+            })
+            .collect();
+        ToolSchema {
+            name: "sql".to_string(),
+            description: format!(
+                "Run one SQL statement on a database and get the rows as a Markdown table. Databases: {}.",
+                databases.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "database": {
+                        "type": "string",
+                        "description": "Database name (optional when only one is configured)"
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "A single SQL statement"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let query = args["query"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing query"))?;
+        let db = self.find(args["database"].as_str())?;
+        let result = self.run(db, query).await?;
+        Ok(format_result(&result, db.max_rows))
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        let db = self.find(args["database"].as_str()).ok()?;
+        Some(format!(
+            "Database: {}\n\n{}",
+            db.name,
+            args["query"].as_str()?.trim()
+        ))
+    }
+}
+
+fn timed_out(db: &Database) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Query on '{}' timed out after {}s",
+        db.name,
+        db.timeout.as_secs()
+    )
+}
+
+/// The statement without a trailing `;`, refusing more than one statement
+fn single_statement(sql: &str) -> Result<&str> {
+    // PostgreSQL dollar quotes and MySQL backslash escapes move where a
+    // string ends, so a `;` only ends the statement if it's outside strings
+    // either way the input is read
+    let end = match (statement_end(sql, false)?, statement_end(sql, true)?) {
+        (Some(plain), Some(dialects)) => Some(plain.min(dialects)),
+        (plain, dialects) => plain.or(dialects),
+    };
+    let statement = sql[..end.unwrap_or(sql.len())].trim();
+    if statement.is_empty() {
+        anyhow::bail!("Empty query");
+    }
+    Ok(statement)
+}
+
+/// Offset of the `;` ending the first statement, failing if another
+/// statement follows. `dialects` reads `$tag$` quotes and `\` escapes.
+fn statement_end(sql: &str, dialects: bool) -> Result<Option<usize>> {
+    let bytes = sql.as_bytes();
+    let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$';
+    let mut end = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if dialects && bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'$' if dialects && (i == 0 || !is_word(bytes[i - 1])) => {
+                let tag_len = bytes[i + 1..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_alphanumeric() || c == b'_'))
+                    .unwrap_or(bytes.len() - i - 1);
+                let tag_end = i + 1 + tag_len;
+                if bytes.get(tag_end) == Some(&b'$') && !bytes[i + 1].is_ascii_digit() {
+                    let tag = &sql[i..=tag_end];
+                    i = sql[tag_end + 1..]
+                        .find(tag)
+                        .map_or(bytes.len(), |close| tag_end + close + tag.len());
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' => {
+                end.get_or_insert(i);
+            }
+            c if !c.is_ascii_whitespace() && end.is_some() => {
+                anyhow::bail!("Run one statement at a time");
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Ok(end)
+}
+
+/// Whether a statement's first keyword is one that only reads
+fn is_read_statement(statement: &str) -> bool {
+    let mut rest = statement.trim_start();
+    // Skip leading comments and parentheses
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else if let Some(inner) = rest.strip_prefix('(') {
+            rest = inner;
+        } else {
+            break;
+        }
+        rest = rest.trim_start();
+    }
+    let keyword: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+    READ_STATEMENTS.contains(&keyword.as_str())
+}
+
+async fn query_sqlite(path: &Path, statement: &str, db: &Database) -> Result<QueryResult> {
+    use rusqlite::{Connection, OpenFlags};
+
+    // Opening read-write never creates the file, so a typo doesn't leave
+    // an empty database behind
+    let flags = if db.read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    } | OpenFlags::SQLITE_OPEN_NO_MUTEX
+        | OpenFlags::SQLITE_OPEN_URI;
+    let conn = Connection::open_with_flags(path, flags)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if db.read_only {
+        conn.pragma_update(None, "query_only", true)?;
+    }
+    let interrupt = conn.get_interrupt_handle();
+    let statement = statement.to_string();
+    let max_rows = db.max_rows;
+    let query = tokio::task::spawn_blocking(move || sqlite_rows(&conn, &statement, max_rows));
+    match tokio::time::timeout(db.timeout, query).await {
+        Ok(result) => result?,
+        Err(_) => {
+            interrupt.interrupt();
+            Err(timed_out(db))
+        }
+    }
+}
+
+fn sqlite_rows(
+    conn: &rusqlite::Connection,
+    statement: &str,
+    max_rows: usize,
+) -> Result<QueryResult> {
+    use rusqlite::types::ValueRef;
+
+    let mut stmt = conn.prepare(statement)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    if columns.is_empty() {
+        let affected = stmt.execute([])?;
+        return Ok(QueryResult {
+            affected: Some(affected as u64),
+            ..Default::default()
+        });
+    }
+
+    let mut result = QueryResult {
+        columns,
+        ..Default::default()
+    };
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if result.rows.len() == max_rows {
+            result.truncated = true;
+            break;
+        }
+        let cells = (0..result.columns.len())
+            .map(|i| {
+                Ok(match row.get_ref(i)? {
+                    ValueRef::Null => "NULL".to_string(),
+                    ValueRef::Integer(n) => n.to_string(),
+                    // Debug keeps the ".0" on whole numbers, as SQLite prints them
+                    ValueRef::Real(n) => format!("{:?}", n),
+                    ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                    ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+                })
+            })
+            .collect::<Result<_>>()?;
+        result.rows.push(cells);
+    }
+    Ok(result)
+}
+
+#[cfg(feature = "postgres")]
+async fn query_postgres(url: &str, statement: &str, db: &Database) -> Result<QueryResult> {
+    use futures::StreamExt;
+
+    let tls = postgres_native_tls::MakeTlsConnector::new(native_tls::TlsConnector::new()?);
+    let (client, connection) = tokio_postgres::connect(url, tls)
+        .await
+        .with_context(|| format!("Failed to connect to database '{}'", db.name))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("PostgreSQL connection closed: {}", e);
+        }
+    });
+
+    client
+        .batch_execute(&format!(
+            "SET statement_timeout = {}",
+            db.timeout.as_millis()
+        ))
+        .await?;
+    if db.read_only {
+        client
+            .batch_execute("SET default_transaction_read_only = on; BEGIN READ ONLY")
+            .await?;
+    }
+
+    // The extended protocol runs exactly one statement, unlike a simple
+    // query, which runs every statement in the string
+    let statement = client.prepare(statement).await?;
+    let mut result = QueryResult {
+        columns: statement
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect(),
+        ..Default::default()
+    };
+    if result.columns.is_empty() {
+        result.affected = Some(client.execute(&statement, &[]).await?);
+    } else {
+        let mut rows = Box::pin(
+            client
+                .query_raw(&statement, std::iter::empty::<&str>())
+                .await?,
+        );
+        while let Some(row) = rows.next().await {
+            let row = row?;
+            if result.rows.len() == db.max_rows {
+                result.truncated = true;
+                break;
+            }
+            result.rows.push(
+                (0..row.len())
+                    .map(|i| Ok(row.try_get::<_, PostgresCell>(i)?.0))
+                    .collect::<Result<_>>()?,
+            );
+        }
+    }
+    if db.read_only {
+        client.batch_execute("ROLLBACK").await.ok();
+    }
+    Ok(result)
+}
+
+/// A PostgreSQL value of any type as result-table text
+#[cfg(feature = "postgres")]
+struct PostgresCell(String);
+
+#[cfg(feature = "postgres")]
+impl<'a> tokio_postgres::types::FromSql<'a> for PostgresCell {
+    fn from_sql(
+        ty: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+        use tokio_postgres::types::{Kind, Type};
+
+        let text = match *ty {
+            Type::BOOL => bool::from_sql(ty, raw)?.to_string(),
+            Type::CHAR => i8::from_sql(ty, raw)?.to_string(),
+            Type::INT2 => i16::from_sql(ty, raw)?.to_string(),
+            Type::INT4 => i32::from_sql(ty, raw)?.to_string(),
+            Type::INT8 => i64::from_sql(ty, raw)?.to_string(),
+            Type::OID => u32::from_sql(ty, raw)?.to_string(),
+            Type::FLOAT4 => f32::from_sql(ty, raw)?.to_string(),
+            Type::FLOAT8 => f64::from_sql(ty, raw)?.to_string(),
+            Type::NUMERIC => numeric_text(raw).ok_or("invalid numeric value")?,
+            Type::DATE => NaiveDate::from_sql(ty, raw)?.to_string(),
+            Type::TIME => NaiveTime::from_sql(ty, raw)?.to_string(),
+            Type::TIMESTAMP => NaiveDateTime::from_sql(ty, raw)?.to_string(),
+            Type::TIMESTAMPTZ => DateTime::<Utc>::from_sql(ty, raw)?.to_string(),
+            Type::UUID => uuid::Uuid::from_sql(ty, raw)?.to_string(),
+            Type::JSON | Type::JSONB => serde_json::Value::from_sql(ty, raw)?.to_string(),
+            Type::BYTEA => format!("<{} bytes>", raw.len()),
+            _ if <&str>::accepts(ty) || matches!(ty.kind(), Kind::Enum(_)) => {
+                String::from_utf8_lossy(raw).into_owned()
+            }
+            _ => format!("<{}>", ty.name()),
+        };
+        Ok(Self(text))
+    }
+
+    fn from_sql_null(
+        _: &tokio_postgres::types::Type,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(Self("NULL".to_string()))
+    }
+
+    fn accepts(_: &tokio_postgres::types::Type) -> bool {
+        true
+    }
+}
+
+/// A binary NUMERIC value as decimal text
+#[cfg(feature = "postgres")]
+fn numeric_text(raw: &[u8]) -> Option<String> {
+    let word = |i: usize| Some(u16::from_be_bytes([*raw.get(i)?, *raw.get(i + 1)?]));
+    let ndigits = word(0)? as usize;
+    let weight = word(2)? as i16 as isize;
+    let sign = word(4)?;
+    let scale = word(6)? as usize;
+    match sign {
+        0xC000 => return Some("NaN".to_string()),
+        0xD000 => return Some("Infinity".to_string()),
+        0xF000 => return Some("-Infinity".to_string()),
+        _ => {}
+    }
+    let digits = (0..ndigits)
+        .map(|i| word(8 + 2 * i))
+        .collect::<Option<Vec<_>>>()?;
+    // digits[i] is worth 10000^(weight - i)
+    let digit = |i: isize| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| digits.get(i).copied())
+            .unwrap_or(0)
+    };
+
+    let mut text = String::new();
+    if sign == 0x4000 {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    }
+    for i in 0..=weight {
+        if i == 0 {
+            text.push_str(&digit(i).to_string());
+        } else {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if scale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < scale {
+            fraction.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        fraction.truncate(scale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Some(text)
+}
+
+#[cfg(feature = "mysql")]
+async fn query_mysql(url: &str, statement: &str, db: &Database) -> Result<QueryResult> {
+    use mysql_async::prelude::Queryable;
+    use mysql_async::Value as MysqlValue;
+
+    let mut conn = mysql_async::Conn::from_url(url)
+        .await
+        .with_context(|| format!("Failed to connect to database '{}'", db.name))?;
+    if db.read_only {
+        conn.query_drop("SET SESSION TRANSACTION READ ONLY").await?;
+        conn.query_drop("START TRANSACTION READ ONLY").await?;
+    }
+
+    // A prepared statement is always a single one, whereas a text query
+    // runs every statement in a batch
+    let mut result = QueryResult::default();
+    {
+        let mut rows = conn.exec_iter(statement, ()).await?;
+        result.columns = rows
+            .columns_ref()
+            .iter()
+            .map(|c| c.name_str().into_owned())
+            .collect();
+        if result.columns.is_empty() {
+            result.affected = Some(rows.affected_rows());
+        }
+        // Rows past the limit are read and dropped so the connection can
+        // roll back afterwards
+        while let Some(row) = rows.next().await? {
+            if result.rows.len() == db.max_rows {
+                result.truncated = true;
+                continue;
+            }
+            let cells = row
+                .unwrap()
+                .into_iter()
+                .map(|value| match value {
+                    MysqlValue::NULL => "NULL".to_string(),
+                    MysqlValue::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                    MysqlValue::Int(n) => n.to_string(),
+                    MysqlValue::UInt(n) => n.to_string(),
+                    MysqlValue::Float(n) => n.to_string(),
+                    MysqlValue::Double(n) => n.to_string(),
+                    MysqlValue::Date(y, mo, d, h, mi, s, _) => {
+                        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, mo, d, h, mi, s)
+                    }
+                    MysqlValue::Time(negative, days, h, mi, s, _) => format!(
+                        "{}{:02}:{:02}:{:02}",
+                        if negative { "-" } else { "" },
+                        days * 24 + u32::from(h),
+                        mi,
+                        s
+                    ),
+                })
+                .collect();
+            result.rows.push(cells);
+        }
+    }
+    if db.read_only {
+        conn.query_drop("ROLLBACK").await.ok();
+    }
+    conn.disconnect().await.ok();
+    Ok(result)
+}
+
+/// Rows as a Markdown table, or the count of affected rows
+fn format_result(result: &QueryResult, max_rows: usize) -> String {
+    if result.columns.is_empty() {
+        return match result.affected {
+            Some(1) => "1 row affected".to_string(),
+            Some(n) => format!("{} rows affected", n),
+            None => "Statement ran".to_string(),
+        };
+    }
+
+    let cell = |text: &str| {
+        let mut text = text.replace('|', "\\|").replace(['\r', '\n'], " ");
+        if text.chars().count() > MAX_CELL_CHARS {
+            text = format!(
+                "{}...",
+                text.chars().take(MAX_CELL_CHARS).collect::<String>()
+            );
+        }
+        text
+    };
+    let row = |cells: &[String]| {
+        let cells: Vec<String> = cells.iter().map(|c| cell(c)).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut out = row(&result.columns);
+    out.push_str(&format!("|{}\n", "---|".repeat(result.columns.len())));
+    for cells in &result.rows {
+        out.push_str(&row(cells));
+    }
+    out.push('\n');
+    out.push_str(&match (result.rows.len(), result.truncated) {
+        (_, true) => format!("(first {} rows; add a LIMIT or narrow the query)", max_rows),
+        (1, false) => "(1 row)".to_string(),
+        (n, false) => format!("({} rows)", n),
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(path: &Path, read_only: bool) -> SqlTool {
+        SqlTool {
+            databases: vec![Database {
+                name: "shop".to_string(),
+                kind: DatabaseKind::Sqlite(path.to_path_buf()),
+                read_only,
+                max_rows: 2,
+                timeout: Duration::from_secs(5),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_statement_checks() {
+        assert_eq!(single_statement(" SELECT 1; ").unwrap(), "SELECT 1");
+        assert_eq!(
+            single_statement("SELECT ';' -- ; \n").unwrap(),
+            "SELECT ';' -- ;"
+        );
+        assert!(single_statement("SELECT 1; DROP TABLE users").is_err());
+        assert!(single_statement(";").is_err());
+
+        assert!(is_read_statement("/* report */ (select 1)"));
+        assert!(is_read_statement("WITH t AS (SELECT 1) SELECT * FROM t"));
+        assert!(!is_read_statement("DELETE FROM users"));
+        assert!(!is_read_statement("selectx"));
+
+        // A quote hidden in a dollar-quoted string or behind a backslash
+        // must not hide the statements after it
+        assert!(single_statement(
+            "SELECT $$'$$; COMMIT; SET default_transaction_read_only=off; DELETE FROM users; --"
+        )
+        .is_err());
+        assert!(single_statement(
+            "SELECT 'a\\''; COMMIT; SET SESSION TRANSACTION READ WRITE; DELETE FROM users; -- '"
+        )
+        .is_err());
+        assert_eq!(
+            single_statement("SELECT $tag$it's$tag$, 'it''s', 'a\\b';").unwrap(),
+            "SELECT $tag$it's$tag$, 'it''s', 'a\\b'"
+        );
+        assert_eq!(
+            single_statement("SELECT $1, a$b;").unwrap(),
+            "SELECT $1, a$b"
+        );
+
+        let workspace = Path::new("/ws");
+        assert_eq!(
+            DatabaseKind::parse("sqlite:data/shop.db", workspace),
+            DatabaseKind::Sqlite(PathBuf::from("/ws/data/shop.db"))
+        );
+        assert_eq!(
+            DatabaseKind::parse("postgres://localhost/db", workspace),
+            DatabaseKind::Postgres("postgres://localhost/db".to_string())
+        );
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_numeric_text() {
+        let numeric = |weight: i16, sign: u16, scale: u16, digits: &[u16]| {
+            let mut raw = Vec::new();
+            for word in [digits.len() as u16, weight as u16, sign, scale]
+                .iter()
+                .chain(digits)
+            {
+                raw.extend_from_slice(&word.to_be_bytes());
+            }
+            numeric_text(&raw).unwrap()
+        };
+        assert_eq!(numeric(1, 0, 2, &[12, 3456, 7800]), "123456.78");
+        assert_eq!(numeric(-2, 0x4000, 5, &[1000]), "-0.00001");
+        assert_eq!(numeric(2, 0, 0, &[1]), "100000000");
+        assert_eq!(numeric(0, 0xC000, 0, &[]), "NaN");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shop.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (name TEXT, price REAL, note TEXT);
+             INSERT INTO items VALUES ('pen', 1.5, NULL), ('a|b', 2.0, 'x'), ('ink', 3.25, 'y');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let read_only = tool(&path, true);
+        let out = read_only
+            .execute(r#"{"query": "SELECT name, price, note FROM items ORDER BY price"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            "| name | price | note |\n|---|---|---|\n| pen | 1.5 | NULL |\n| a\\|b | 2.0 | x |\n\n\
+             (first 2 rows; add a LIMIT or narrow the query)"
+        );
+        let denied = read_only
+            .execute(r#"{"query": "DELETE FROM items"}"#)
+            .await
+            .unwrap_err();
+        assert!(denied.to_string().contains("read-only"));
+
+        let writable = tool(&path, false);
+        let out = writable
+            .execute(r#"{"database": "shop", "query": "DELETE FROM items WHERE price >= 2"}"#)
+            .await
+            .unwrap();
+        assert_eq!(out, "2 rows affected");
+        assert!(writable
+            .execute(r#"{"database": "other", "query": "SELECT 1"}"#)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Conversation statistics
+//!
+//! Summarizes the current session's transcript for `/stats`: message counts
+//! by role, how often each tool was called, the web searches made,
+//! estimated tokens per turn, and when the history was compacted.
+
+use std::collections::HashMap;
+
+use super::providers::Role;
+use super::session::{CompactionEvent, Session};
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    pub session_id: String,
+    pub user_messages: usize,
+    pub assistant_messages: usize,
+    pub tool_results: usize,
+    pub system_messages: usize,
+    /// Calls per tool, most used first
+    pub tool_calls: Vec<(String, usize)>,
+    /// Queries of the web_search calls, in order
+    pub web_searches: Vec<String>,
+    /// Estimated tokens of each user turn (the user message plus every
+    /// assistant message and tool result that followed it)
+    pub turn_tokens: Vec<usize>,
+    pub compaction_count: u32,
+    /// Compactions with recorded details (older sessions only have the count)
+    pub compactions: Vec<CompactionEvent>,
+}
+
+impl SessionStats {
+    pub fn from_session(session: &Session) -> Self {
+        let mut stats = Self {
+            session_id: session.id().to_string(),
+            compaction_count: session.compaction_count(),
+            compactions: session.compactions().to_vec(),
+            ..Default::default()
+        };
+        let mut tool_counts: HashMap<&str, usize> = HashMap::new();
+
+        for sm in session.raw_messages() {
+            let message = &sm.message;
+            match message.role {
+                Role::User => {
+                    stats.user_messages += 1;
+                    stats.turn_tokens.push(0);
+                }
+                Role::Assistant => stats.assistant_messages += 1,
+                Role::Tool => stats.tool_results += 1,
+                Role::System => stats.system_messages += 1,
+            }
+
+            for call in message.tool_calls.iter().flatten() {
+                *tool_counts.entry(call.name.as_str()).or_default() += 1;
+                if call.name == "web_search" {
+                    let args: serde_json::Value =
+                        serde_json::from_str(&call.arguments).unwrap_or_default();
+                    if let Some(query) = args["query"].as_str() {
+                        stats.web_searches.push(query.to_string());
+                    }
+                }
+            }
+            let tokens = session.token_counter().message(message);
+            // Messages before the first user turn (e.g. a compaction summary)
+            // belong to no turn
+            if let Some(turn) = stats.turn_tokens.last_mut() {
+                *turn += tokens;
+            }
+        }
+
+        stats.tool_calls = tool_counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        stats
+            .tool_calls
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        stats
+    }
+
+    pub fn total_tool_calls(&self) -> usize {
+        self.tool_calls.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "Session {}\n\nMessages: {} user, {} assistant, {} tool results, {} system\n",
+            self.session_id,
+            self.user_messages,
+            self.assistant_messages,
+            self.tool_results,
+            self.system_messages
+        );
+
+        out.push_str(&format!("\nTool calls: {}\n", self.total_tool_calls()));
+        for (name, count) in &self.tool_calls {
+            out.push_str(&format!("  {:<20} {}\n", name, count));
+        }
+
+        if !self.web_searches.is_empty() {
+            out.push_str(&format!("\nWeb searches: {}\n", self.web_searches.len()));
+            for query in &self.web_searches {
+                out.push_str(&format!("  \"{}\"\n", query));
+            }
+        }
+
+        if !self.turn_tokens.is_empty() {
+            let total: usize = self.turn_tokens.iter().sum();
+            let max = self.turn_tokens.iter().max().copied().unwrap_or(0);
+            out.push_str(&format!(
+                "\nTokens per turn (~{} over {} turns, avg ~{}, max ~{}):\n  {}\n",
+                total,
+                self.turn_tokens.len(),
+                total / self.turn_tokens.len(),
+                max,
+                sparkline(&self.turn_tokens)
+            ));
+        }
+
+        out.push_str(&format!("\nCompactions: {}\n", self.compaction_count));
+        for event in &self.compactions {
+            out.push_str(&format!(
+                "  {}  ~{} -> ~{} tokens",
+                event
+                    .at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                event.tokens_before,
+                event.tokens_after
+            ));
+            if let Some(strategy) = event.strategy {
+                out.push_str(&format!(" ({})", strategy));
+            }
+            out.push('\n');
+            if let Some(ref summary) = event.summary {
+                for line in summary.lines() {
+                    out.push_str(&format!("      {}\n", line));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// One block character per value, scaled to the largest
+pub fn sparkline(values: &[usize]) -> String {
+    let max = values.iter().max().copied().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| SPARK_CHARS[(v * (SPARK_CHARS.len() - 1)).div_ceil(max)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Message, ToolCall};
+
+    fn message(role: Role, content: &str, tools: &[&str]) -> Message {
+        Message {
+            role,
+            content: content.to_string(),
+            tool_calls: (!tools.is_empty()).then(|| {
+                tools
+                    .iter()
+                    .map(|name| ToolCall {
+                        id: format!("id-{}", name),
+                        name: name.to_string(),
+                        arguments: "{}".to_string(),
+                    })
+                    .collect()
+            }),
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_stats_from_session() {
+        let mut session = Session::new();
+        session.add_message(message(Role::User, "Find the bug", &[]));
+        session.add_message(message(Role::Assistant, "", &["bash", "read_file"]));
+        session.add_message(message(Role::Tool, "output", &[]));
+        session.add_message(message(Role::Tool, "contents", &[]));
+        session.add_message(message(Role::Assistant, "", &["bash"]));
+        session.add_message(message(Role::Tool, "output", &[]));
+        session.add_message(message(Role::Assistant, "Fixed it", &[]));
+        session.add_message(message(Role::User, "Thanks", &[]));
+        let mut search = message(Role::Assistant, "", &["web_search"]);
+        search.tool_calls.as_mut().unwrap()[0].arguments = r#"{"query": "rust lifetimes"}"#.into();
+        session.add_message(search);
+
+        let stats = SessionStats::from_session(&session);
+        assert_eq!(stats.user_messages, 2);
+        assert_eq!(stats.assistant_messages, 4);
+        assert_eq!(stats.tool_results, 3);
+        assert_eq!(
+            stats.tool_calls,
+            vec![
+                ("bash".to_string(), 2),
+                ("read_file".to_string(), 1),
+                ("web_search".to_string(), 1)
+            ]
+        );
+        assert_eq!(stats.web_searches, vec!["rust lifetimes".to_string()]);
+        assert_eq!(stats.turn_tokens.len(), 2);
+        assert!(stats.turn_tokens[0] > stats.turn_tokens[1]);
+        assert!(stats.to_text().contains("Tool calls: 4\n"));
+        assert!(stats
+            .to_text()
+            .contains("Web searches: 1\n  \"rust lifetimes\"\n"));
+
+        assert_eq!(sparkline(&[0, 4, 8]), "▁▅█");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,303 @@
+//! Sub-agent delegation
+//!
+//! The spawn_subagent tool hands a scoped task to a child agent: a fresh
+//! session with its own model and a subset of the tools, in the same
+//! workspace and memory. Only the child's final answer comes back, so a
+//! multi-step job (reading a dozen files, a round of web searches) costs the
+//! parent one tool result instead of every intermediate output. The child's
+//! session isn't saved.
+//!
+//! Children can't spawn children or message other agents and don't start MCP
+//! servers. They have no one to ask for approval, so they aren't offered
+//! tools the parent would ask about (`ALWAYS_REQUIRE_APPROVAL`,
+//! `tools.require_approval`, "ask" policies) and refuse any other call that
+//! needs approval under the parent's policies.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::info;
+
+use super::approval_policy::{ApprovalPolicy, ApprovalStore};
+use super::providers::ToolSchema;
+use super::tool_registry::tool_registry;
+use super::tools::{Tool, ALWAYS_REQUIRE_APPROVAL};
+use super::{Agent, AgentConfig};
+use crate::config::Config;
+use crate::memory::MemoryManager;
+
+/// Tools children never get
+const PARENT_ONLY_TOOLS: &[&str] = &["spawn_subagent", "message_agent"];
+
+/// Put before the task in the child's first message
+const SUBAGENT_TASK_PROMPT: &str = "[Task delegated by another agent. Use your tools as needed, \
+then reply with only the result the task asks for: your reply is all the other agent will see.]";
+
+pub struct SpawnSubagentTool {
+    config: Config,
+    agent_id: String,
+    memory: Option<Arc<MemoryManager>>,
+    /// The parent's approval policies, which its children are held to
+    approvals: ApprovalStore,
+}
+
+impl SpawnSubagentTool {
+    pub fn new(
+        config: &Config,
+        agent_id: &str,
+        memory: Option<Arc<MemoryManager>>,
+        approvals: ApprovalStore,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            agent_id: agent_id.to_string(),
+            memory,
+            approvals,
+        }
+    }
+
+    /// Models a call may pick, the default first
+    fn models(&self) -> Vec<String> {
+        let subagent = &self.config.tools.subagent;
+        let default = subagent
+            .model
+            .clone()
+            .unwrap_or_else(|| self.config.agent.default_model.clone());
+        let mut models = vec![default];
+        for model in &subagent.models {
+            if !models.contains(model) {
+                models.push(model.clone());
+            }
+        }
+        models
+    }
+
+    /// Whether the parent would ask before running the tool
+    fn asks_first(&self, name: &str) -> bool {
+        if ALWAYS_REQUIRE_APPROVAL.contains(&name) {
+            return true;
+        }
+        match self.approvals.tool_policy(name) {
+            Some(policy) => policy != ApprovalPolicy::Allow,
+            None => self.config.tools.require_approval.iter().any(|t| t == name),
+        }
+    }
+
+    /// Tools a call may give its child
+    fn available_tools(&self) -> Vec<String> {
+        let subagent = &self.config.tools.subagent;
+        let names: Vec<String> = if subagent.tools.is_empty() {
+            tool_registry()
+                .names()
+                .into_iter()
+                .filter(|name| self.config.tools.offers(name))
+                .map(str::to_string)
+                .collect()
+        } else {
+            subagent.tools.clone()
+        };
+        names
+            .into_iter()
+            .filter(|name| !PARENT_ONLY_TOOLS.contains(&name.as_str()) && !self.asks_first(name))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Tool for SpawnSubagentTool {
+    fn name(&self) -> &str {
+        "spawn_subagent"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let models = self.models();
+        let tools = self.available_tools();
+        ToolSchema {
+            name: "spawn_subagent".to_string(),
+            description: "Delegate a self-contained task to a sub-agent that starts with an \
+                 empty conversation, works through it with its own tool calls, and returns \
+                 only its final answer. Use it for multi-step jobs whose intermediate output \
+                 you don't need, like reading many files or researching a question. Describe \
+                 the task fully: the sub-agent can't see this conversation."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "string",
+                        "description": "The task, with all the context needed and what the answer should contain"
+                    },
+                    "model": {
+                        "type": "string",
+                        "enum": models,
+                        "description": format!("Model for the sub-agent (default {})", models[0])
+                    },
+                    "tools": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": tools },
+                        "description": "Tools the sub-agent may use (default: all of these)"
+                    }
+                },
+                "required": ["task"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let task = args["task"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing task"))?;
+
+        let models = self.models();
+        let model = match args["model"].as_str() {
+            Some(model) if !models.iter().any(|m| m == model) => anyhow::bail!(
+                "Model '{}' is not available for sub-agents (allowed: {})",
+                model,
+                models.join(", ")
+            ),
+            Some(model) => model.to_string(),
+            None => models[0].clone(),
+        };
+        let requested: Vec<String> = args["tools"]
+            .as_array()
+            .map(|tools| {
+                tools
+                    .iter()
+                    .filter_map(|t| t.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let registered = tool_registry();
+        let config = child_config(
+            &self.config,
+            &registered.names(),
+            &self.available_tools(),
+            &requested,
+        )?;
+
+        info!(
+            "Agent '{}' spawning a sub-agent on {} ({} tools)",
+            self.agent_id,
+            model,
+            if requested.is_empty() {
+                "all".to_string()
+            } else {
+                requested.len().to_string()
+            }
+        );
+        let memory = match self.memory {
+            Some(ref memory) => (**memory).clone(),
+            None => {
+                MemoryManager::new_with_full_config(&config.memory, Some(&config), &self.agent_id)?
+            }
+        };
+        let agent_config = AgentConfig {
+            model: model.clone(),
+            context_window: config.agent.context_window_or_default(),
+            reserve_tokens: config.agent.reserve_tokens,
+        };
+        let mut agent = Agent::new(agent_config, &config, memory).await?;
+        agent.set_unattended(self.approvals.clone());
+        agent.new_session().await?;
+        let reply = agent
+            .chat(&format!("{}\n\n{}", SUBAGENT_TASK_PROMPT, task))
+            .await?;
+
+        let usage = agent.usage();
+        Ok(format!(
+            "Result from sub-agent ({}, {} tokens):\n\n{}",
+            model,
+            usage.input_tokens + usage.output_tokens,
+            reply
+        ))
+    }
+}
+
+/// The config a child runs with: every registered tool outside its subset
+/// disabled (`requested`, or all of `available` when empty), and no MCP
+/// servers, agent messaging or further sub-agents
+fn child_config(
+    config: &Config,
+    registered: &[&str],
+    available: &[String],
+    requested: &[String],
+) -> Result<Config> {
+    if let Some(tool) = requested.iter().find(|t| !available.contains(t)) {
+        anyhow::bail!(
+            "Tool '{}' is not available to sub-agents (allowed: {})",
+            tool,
+            available.join(", ")
+        );
+    }
+    let allowed = if requested.is_empty() {
+        available
+    } else {
+        requested
+    };
+
+    let mut config = config.clone();
+    for name in registered {
+        let name = name.to_string();
+        if !allowed.contains(&name) && !config.tools.disabled.contains(&name) {
+            config.tools.disabled.push(name);
+        }
+    }
+    config.tools.mcp_servers.clear();
+    config.tools.agent_messaging.clear();
+    config.tools.subagent.enabled = false;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::approval_policy::ApprovalScope;
+
+    #[test]
+    fn test_child_config() {
+        let config = Config::default();
+        let registered = ["bash", "read_file", "web_search", "spawn_subagent"];
+        let available = vec!["read_file".to_string(), "web_search".to_string()];
+
+        let child = child_config(&config, &registered, &available, &[]).unwrap();
+        assert_eq!(child.tools.disabled, vec!["bash", "spawn_subagent"]);
+        assert!(!child.tools.subagent.enabled);
+
+        let child =
+            child_config(&config, &registered, &available, &["read_file".to_string()]).unwrap();
+        assert_eq!(
+            child.tools.disabled,
+            vec!["bash", "web_search", "spawn_subagent"]
+        );
+
+        assert!(child_config(&config, &registered, &available, &["bash".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_children_skip_tools_needing_approval() {
+        let mut config = Config::default();
+        config.tools.subagent.tools = [
+            "bash",
+            "write_file",
+            "read_file",
+            "web_search",
+            "email_send",
+        ]
+        .map(String::from)
+        .to_vec();
+        config.tools.require_approval = vec!["bash".to_string(), "write_file".to_string()];
+        let approvals = ApprovalStore::default();
+        let remember = |tool, policy| {
+            approvals
+                .remember(tool, Some(policy), ApprovalScope::Session)
+                .unwrap()
+        };
+        remember("write_file", ApprovalPolicy::Allow);
+        remember("web_search", ApprovalPolicy::Ask);
+
+        let tool = SpawnSubagentTool::new(&config, "main", None, approvals);
+        assert_eq!(tool.available_tools(), vec!["write_file", "read_file"]);
+    }
+}
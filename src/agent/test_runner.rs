@@ -0,0 +1,627 @@
+//! `run_tests` tool (`[tools.run_tests]`)
+//!
+//! Finds the project a path belongs to (Cargo, npm or pytest), runs its
+//! test command through the bash tool's runner, so the desktop terminal
+//! panel shows it live, and returns a summary instead of the raw log: the
+//! result counts, each failing test with its output, or the build errors
+//! that stopped the run.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use super::code_search::relative;
+use super::providers::ToolSchema;
+use super::tools::{workspace_path, BashTool, ShellEvent, Tool};
+use crate::config::RunTestsConfig;
+
+/// Lines kept per failing test or build error
+const MAX_DETAIL_LINES: usize = 40;
+
+/// Lines of raw output shown when the failures can't be picked out
+const TAIL_LINES: usize = 60;
+
+/// `package.json`'s placeholder test script from `npm init`
+const NPM_PLACEHOLDER: &str = "no test specified";
+
+static PYTEST_SUMMARY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^=*\s*((?:\d+ \w+(?:, )?)+) in [\d.]+s").expect("valid regex"));
+
+static PYTEST_SECTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^=+ (.+?) =+$").expect("valid regex"));
+
+static PYTEST_TEST: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^_{3,} (.+?) _{3,}$").expect("valid regex"));
+
+static NPM_SUMMARY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:Tests:|Test Suites:|Test Files|# (?:pass|fail|tests)\b|\d+ (?:passing|failing|pending)\b)")
+        .expect("valid regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framework {
+    Cargo,
+    Npm,
+    Pytest,
+}
+
+impl Framework {
+    fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "cargo" | "rust" => Ok(Self::Cargo),
+            "npm" | "node" | "javascript" => Ok(Self::Npm),
+            "pytest" | "python" => Ok(Self::Pytest),
+            other => anyhow::bail!(
+                "Unsupported framework '{}'. Use cargo, npm or pytest.",
+                other
+            ),
+        }
+    }
+
+    /// The framework whose project files are in `dir`, checked in this order
+    fn detect(dir: &Path) -> Option<Self> {
+        [Self::Cargo, Self::Npm, Self::Pytest]
+            .into_iter()
+            .find(|framework| framework.is_project(dir))
+    }
+
+    fn is_project(&self, dir: &Path) -> bool {
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+        match self {
+            Self::Cargo => dir.join("Cargo.toml").is_file(),
+            Self::Npm => serde_json::from_str::<Value>(&read("package.json"))
+                .ok()
+                .and_then(|package| package["scripts"]["test"].as_str().map(str::to_string))
+                .is_some_and(|script| !script.contains(NPM_PLACEHOLDER)),
+            Self::Pytest => {
+                dir.join("pytest.ini").is_file()
+                    || dir.join("conftest.py").is_file()
+                    || read("pyproject.toml").contains("[tool.pytest")
+                    || read("setup.cfg").contains("[tool:pytest]")
+                    || read("tox.ini").contains("[pytest]")
+                    || (dir.join("tests").is_dir()
+                        && ["pyproject.toml", "setup.py", "requirements.txt"]
+                            .iter()
+                            .any(|f| dir.join(f).is_file()))
+            }
+        }
+    }
+
+    /// Project kind, for messages
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Cargo => "Cargo",
+            Self::Npm => "npm",
+            Self::Pytest => "pytest",
+        }
+    }
+}
+
+pub struct RunTestsTool {
+    workspace: PathBuf,
+    shell: BashTool,
+    timeout_secs: u64,
+    python: String,
+    max_failures: usize,
+}
+
+impl RunTestsTool {
+    pub fn new(workspace: PathBuf, config: &RunTestsConfig) -> Self {
+        Self {
+            workspace,
+            shell: BashTool::new(config.timeout_secs.max(1) * 1000),
+            timeout_secs: config.timeout_secs.max(1),
+            python: config.python.clone(),
+            max_failures: config.max_failures.max(1),
+        }
+    }
+
+    /// Mirror test output to subscribers while the tests run
+    pub fn with_events(mut self, events: broadcast::Sender<ShellEvent>) -> Self {
+        self.shell = self.shell.with_events(events);
+        self
+    }
+
+    /// `path` as shown to the model
+    fn location(&self, path: &Path) -> String {
+        match relative(&self.workspace, path) {
+            path if path.is_empty() => "the workspace".to_string(),
+            path => path,
+        }
+    }
+
+    /// The nearest directory from `start` up to the workspace holding a
+    /// project (of `framework`, when given)
+    fn find_project(
+        &self,
+        start: &Path,
+        framework: Option<Framework>,
+    ) -> Option<(PathBuf, Framework)> {
+        let mut dir = if start.is_file() {
+            start.parent()?
+        } else {
+            start
+        };
+        loop {
+            let found = match framework {
+                Some(framework) => framework.is_project(dir).then_some(framework),
+                None => Framework::detect(dir),
+            };
+            if let Some(found) = found {
+                return Some((dir.to_path_buf(), found));
+            }
+            if dir == self.workspace {
+                return None;
+            }
+            dir = dir.parent().filter(|d| d.starts_with(&self.workspace))?;
+        }
+    }
+
+    fn command(&self, framework: Framework, dir: &Path, filter: Option<&str>) -> String {
+        let filter = filter.map(shell_quote);
+        match framework {
+            Framework::Cargo => {
+                let mut command = "cargo test --color never".to_string();
+                if std::fs::read_to_string(dir.join("Cargo.toml"))
+                    .is_ok_and(|manifest| manifest.contains("[workspace]"))
+                {
+                    command.push_str(" --workspace");
+                }
+                if let Some(filter) = filter {
+                    command.push(' ');
+                    command.push_str(&filter);
+                }
+                command
+            }
+            Framework::Npm => {
+                let mut command = "CI=true npm test --silent".to_string();
+                if let Some(filter) = filter {
+                    command.push_str(" -- ");
+                    command.push_str(&filter);
+                }
+                command
+            }
+            Framework::Pytest => {
+                let python = [".venv/bin/python", "venv/bin/python"]
+                    .iter()
+                    .find(|p| dir.join(p).is_file())
+                    .map(|p| p.to_string())
+                    .unwrap_or_else(|| shell_quote(&self.python));
+                let mut command = format!("{} -m pytest -q -rfE --tb=short --color=no", python);
+                if let Some(filter) = filter {
+                    command.push_str(" -k ");
+                    command.push_str(&filter);
+                }
+                command
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for RunTestsTool {
+    fn name(&self) -> &str {
+        "run_tests"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "run_tests".to_string(),
+            description: format!("Run the project's tests (cargo test, npm test or pytest, detected from the project files) and get a summary: counts, failing tests with their output, or build errors. Runs for at most {}s.", self.timeout_secs),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory in the project to test (default: the workspace)"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only run tests matching this (cargo test name filter, pytest -k expression, or an argument to the npm test script)"
+                    },
+                    "framework": {
+                        "type": "string",
+                        "enum": ["cargo", "npm", "pytest"],
+                        "description": "Test runner to use when a directory has several projects"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let start = match args["path"].as_str().filter(|p| !p.trim().is_empty()) {
+            Some(path) => workspace_path(&self.workspace, path)?,
+            None => self.workspace.clone(),
+        };
+        let framework = args["framework"]
+            .as_str()
+            .filter(|f| !f.is_empty())
+            .map(Framework::parse)
+            .transpose()?;
+        let filter = args["filter"].as_str().filter(|f| !f.trim().is_empty());
+
+        let (dir, framework) = self.find_project(&start, framework).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No {} project found at {} or above it in the workspace",
+                framework.map_or("Cargo, npm or pytest", |f| f.label()),
+                self.location(&start)
+            )
+        })?;
+        let command = self.command(framework, &dir, filter);
+        debug!("Running tests in {}: {}", dir.display(), command);
+
+        let (stdout, stderr, exit_code) = self
+            .shell
+            .run(&command, Some(&dir), self.timeout_secs * 1000)
+            .await
+            .map_err(|e| anyhow::anyhow!("{} ({})", e, command))?;
+
+        let mut output = String::from_utf8_lossy(&stdout).to_string();
+        let stderr = String::from_utf8_lossy(&stderr);
+        if !stderr.trim().is_empty() {
+            output.push('\n');
+            output.push_str(&stderr);
+        }
+        Ok(format_report(
+            &command,
+            &self.location(&dir),
+            exit_code,
+            &summarize(framework, &output),
+            &output,
+            self.max_failures,
+        ))
+    }
+}
+
+/// What a test run's output says
+#[derive(Debug, Default)]
+struct Summary {
+    /// Result counts, e.g. "2 failed, 10 passed"
+    counts: Option<String>,
+    /// Failing tests and their output
+    failures: Vec<(String, String)>,
+    /// Errors that stopped the tests from running (compile errors,
+    /// collection errors)
+    errors: Vec<String>,
+}
+
+fn summarize(framework: Framework, output: &str) -> Summary {
+    match framework {
+        Framework::Cargo => summarize_cargo(output),
+        Framework::Pytest => summarize_pytest(output),
+        Framework::Npm => Summary {
+            counts: Some(
+                output
+                    .lines()
+                    .filter(|l| NPM_SUMMARY.is_match(l))
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            )
+            .filter(|c| !c.is_empty()),
+            ..Default::default()
+        },
+    }
+}
+
+fn summarize_cargo(output: &str) -> Summary {
+    let mut summary = Summary::default();
+    let (mut passed, mut failed, mut ignored, mut results) = (0, 0, 0, 0);
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut error: Option<Vec<&str>> = None;
+
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("test result: ") {
+            results += 1;
+            for part in rest.split(['.', ';']) {
+                let mut words = part.split_whitespace();
+                if let (Some(n), Some(what)) = (words.next(), words.next()) {
+                    let n: usize = n.parse().unwrap_or(0);
+                    match what {
+                        "passed" => passed += n,
+                        "failed" => failed += n,
+                        "ignored" => ignored += n,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // Output of a failing test: `---- name stdout ----` up to the next
+        // test or the list of failures
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"))
+        {
+            if let Some((name, lines)) = current.take() {
+                summary.failures.push((name, lines.join("\n")));
+            }
+            current = Some((name.to_string(), Vec::new()));
+            continue;
+        }
+        if let Some((_, ref mut lines)) = current {
+            if line.starts_with("note: run with `RUST_BACKTRACE") {
+                continue;
+            }
+            if line == "failures:" || line.starts_with("test result: ") {
+                let (name, lines) = current.take().expect("current failure");
+                summary.failures.push((name, lines.join("\n")));
+            } else {
+                lines.push(line);
+                continue;
+            }
+        }
+
+        // Compile errors: `error[E0308]: ...` up to the next blank line
+        if line.starts_with("error[")
+            || (line.starts_with("error:") && !line.contains("test failed"))
+        {
+            if let Some(lines) = error.take() {
+                summary.errors.push(lines.join("\n"));
+            }
+            error = Some(vec![line]);
+        } else if let Some(ref mut lines) = error {
+            if line.trim().is_empty() {
+                summary.errors.push(lines.join("\n"));
+                error = None;
+            } else {
+                lines.push(line);
+            }
+        }
+    }
+    if let Some((name, lines)) = current {
+        summary.failures.push((name, lines.join("\n")));
+    }
+    if let Some(lines) = error {
+        summary.errors.push(lines.join("\n"));
+    }
+    // The "could not compile" line repeats the errors above it
+    summary
+        .errors
+        .retain(|e| !e.starts_with("error: could not compile") || results > 0);
+
+    if results > 0 {
+        summary.counts = Some(format!(
+            "{} passed, {} failed, {} ignored",
+            passed, failed, ignored
+        ));
+    }
+    summary
+}
+
+fn summarize_pytest(output: &str) -> Summary {
+    let mut summary = Summary::default();
+    // Section of the report being read (FAILURES, ERRORS, ...)
+    let mut section = String::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+
+    for line in output.lines() {
+        if let Some(counts) = PYTEST_SUMMARY.captures(line) {
+            summary.counts = Some(counts[1].trim_end_matches(", ").to_string());
+            continue;
+        }
+        if let Some(name) = PYTEST_SECTION.captures(line) {
+            if let Some((name, lines)) = current.take() {
+                push_pytest_block(&mut summary, &section, name, lines);
+            }
+            section = name[1].to_string();
+            continue;
+        }
+        if section != "FAILURES" && section != "ERRORS" {
+            continue;
+        }
+        if let Some(name) = PYTEST_TEST.captures(line) {
+            if let Some((name, lines)) = current.take() {
+                push_pytest_block(&mut summary, &section, name, lines);
+            }
+            current = Some((name[1].to_string(), Vec::new()));
+        } else if let Some((_, ref mut lines)) = current {
+            lines.push(line);
+        }
+    }
+    if let Some((name, lines)) = current {
+        push_pytest_block(&mut summary, &section, name, lines);
+    }
+    summary
+}
+
+fn push_pytest_block(summary: &mut Summary, section: &str, name: String, lines: Vec<&str>) {
+    let text = lines.join("\n");
+    if section == "ERRORS" {
+        summary.errors.push(format!("{}\n{}", name, text));
+    } else {
+        summary.failures.push((name, text));
+    }
+}
+
+fn format_report(
+    command: &str,
+    location: &str,
+    exit_code: Option<i32>,
+    summary: &Summary,
+    output: &str,
+    max_failures: usize,
+) -> String {
+    let status = match exit_code {
+        Some(0) => "passed".to_string(),
+        Some(code) => format!("failed (exit code {})", code),
+        None => "was killed".to_string(),
+    };
+    let mut report = format!("`{}` in {} {}", command, location, status);
+    if let Some(ref counts) = summary.counts {
+        report.push_str(&format!("\nResults: {}", counts));
+    }
+    if exit_code == Some(0) {
+        return report;
+    }
+
+    if !summary.errors.is_empty() {
+        report.push_str("\n\n## Errors");
+        for error in summary.errors.iter().take(max_failures) {
+            report.push_str("\n\n");
+            report.push_str(&clip(error));
+        }
+        if summary.errors.len() > max_failures {
+            report.push_str(&format!(
+                "\n\n({} more errors not shown)",
+                summary.errors.len() - max_failures
+            ));
+        }
+    }
+    if !summary.failures.is_empty() {
+        report.push_str("\n\n## Failing tests");
+        for (name, details) in summary.failures.iter().take(max_failures) {
+            report.push_str(&format!("\n\n### {}\n{}", name, clip(details)));
+        }
+        let rest: Vec<&str> = summary
+            .failures
+            .iter()
+            .skip(max_failures)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !rest.is_empty() {
+            report.push_str(&format!("\n\nAlso failing: {}", rest.join(", ")));
+        }
+    }
+    if summary.errors.is_empty() && summary.failures.is_empty() {
+        let lines: Vec<&str> = output.trim_end().lines().collect();
+        let tail = &lines[lines.len().saturating_sub(TAIL_LINES)..];
+        report.push_str("\n\n## Output (last lines)\n");
+        report.push_str(&tail.join("\n"));
+    }
+    report
+}
+
+/// At most `MAX_DETAIL_LINES` of `text`, without surrounding blank lines
+fn clip(text: &str) -> String {
+    let lines: Vec<&str> = text.trim_matches('\n').lines().collect();
+    if lines.len() <= MAX_DETAIL_LINES {
+        return lines.join("\n");
+    }
+    format!(
+        "{}\n[{} more lines]",
+        lines[..MAX_DETAIL_LINES].join("\n"),
+        lines.len() - MAX_DETAIL_LINES
+    )
+}
+
+/// Quote `s` as a single bash word
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=,+@".contains(c))
+    {
+        return s.to_string();
+    }
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_summarize() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = RunTestsTool::new(dir.path().to_path_buf(), &RunTestsConfig::default());
+        std::fs::create_dir_all(dir.path().join("app/tests")).unwrap();
+        std::fs::write(dir.path().join("app/pyproject.toml"), "[project]\n").unwrap();
+        std::fs::write(dir.path().join("app/tests/test_x.py"), "").unwrap();
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"scripts": {"test": "echo \"Error: no test specified\" && exit 1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tool.find_project(&dir.path().join("app/tests/test_x.py"), None),
+            Some((dir.path().join("app"), Framework::Pytest))
+        );
+        // npm init's placeholder script isn't a test suite
+        assert_eq!(tool.find_project(dir.path(), None), None);
+        assert_eq!(
+            tool.command(Framework::Pytest, &dir.path().join("app"), Some("it's")),
+            r"python3 -m pytest -q -rfE --tb=short --color=no -k 'it'\''s'"
+        );
+
+        let cargo = "\
+running 3 tests
+test a::ok ... ok
+test a::bad ... FAILED
+test a::worse ... FAILED
+
+failures:
+
+---- a::bad stdout ----
+thread 'a::bad' panicked at src/a.rs:10:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- a::worse stdout ----
+thread 'a::worse' panicked at src/a.rs:20:9:
+boom
+
+
+failures:
+    a::bad
+    a::worse
+
+test result: FAILED. 1 passed; 2 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+
+error: test failed, to rerun pass `--lib`";
+        let summary = summarize(Framework::Cargo, cargo);
+        assert_eq!(
+            format_report("cargo test", "the workspace", Some(101), &summary, cargo, 1),
+            "`cargo test` in the workspace failed (exit code 101)\n\
+             Results: 1 passed, 2 failed, 0 ignored\n\n\
+             ## Failing tests\n\n\
+             ### a::bad\n\
+             thread 'a::bad' panicked at src/a.rs:10:9:\n\
+             assertion `left == right` failed\n  left: 1\n right: 2\n\n\
+             Also failing: a::worse"
+        );
+
+        let build = "\
+   Compiling demo v0.1.0
+error[E0308]: mismatched types
+ --> src/lib.rs:2:5
+  |
+2 |     \"x\"
+  |     ^^^ expected `u32`, found `&str`
+
+error: could not compile `demo` (lib test) due to 1 previous error";
+        let summary = summarize(Framework::Cargo, build);
+        assert_eq!(summary.counts, None);
+        assert_eq!(summary.errors.len(), 1);
+        assert!(summary.errors[0].ends_with("expected `u32`, found `&str`"));
+
+        let pytest = "\
+.F                                                                       [100%]
+=================================== FAILURES ===================================
+__________________________________ test_add ___________________________________
+tests/test_x.py:4: in test_add
+    assert add(1, 1) == 3
+E   assert 2 == 3
+=========================== short test summary info ============================
+FAILED tests/test_x.py::test_add - assert 2 == 3
+1 failed, 1 passed in 0.03s";
+        let summary = summarize(Framework::Pytest, pytest);
+        assert_eq!(summary.counts.as_deref(), Some("1 failed, 1 passed"));
+        assert_eq!(
+            summary.failures,
+            vec![(
+                "test_add".to_string(),
+                "tests/test_x.py:4: in test_add\n    assert add(1, 1) == 3\nE   assert 2 == 3"
+                    .to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Token counting for context accounting
+//!
+//! OpenAI models are counted exactly with their tiktoken encoding (o200k for
+//! GPT-4o and later, cl100k for GPT-4 and GPT-3.5). Claude's tokenizer isn't
+//! public, so Claude models use a denser character estimate, and everything
+//! else keeps the ~4 characters per token rule of thumb. The counts drive the
+//! context meter, `/context`, `/stats`, and when the session is compacted.
+
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+use super::providers::{resolve_provider, Message, ToolSchema};
+use crate::config::Config;
+
+/// Rough cost of one image; providers charge by size, which isn't known here
+const IMAGE_TOKENS: usize = 1_000;
+
+/// How tokens are counted for a model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenCounter {
+    /// ~4 characters per token
+    #[default]
+    Estimate,
+    /// ~3.5 characters per token, closer to Claude's tokenizer
+    Claude,
+    /// tiktoken `o200k_base` (GPT-4o, GPT-4.1, GPT-5, o-series)
+    O200k,
+    /// tiktoken `cl100k_base` (GPT-4, GPT-3.5)
+    Cl100k,
+}
+
+impl TokenCounter {
+    /// The counter for `model` (alias, `provider/model`, or bare name)
+    pub fn for_model(model: &str, config: &Config) -> Self {
+        let (provider, model_id) = resolve_provider(model, config);
+        match provider.as_str() {
+            "openai" if model_id == "gpt-4" || model_id.starts_with("gpt-4-") => Self::Cl100k,
+            "openai" if model_id.starts_with("gpt-3.5") => Self::Cl100k,
+            "openai" => Self::O200k,
+            "anthropic" | "claude-cli" => Self::Claude,
+            _ => Self::Estimate,
+        }
+    }
+
+    /// Shown next to counts, e.g. in `/context`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Estimate => "estimate",
+            Self::Claude => "Claude estimate",
+            Self::O200k => "o200k_base",
+            Self::Cl100k => "cl100k_base",
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Estimate => text.len().div_ceil(4),
+            Self::Claude => (text.len() * 2).div_ceil(7),
+            Self::O200k => bpe(Self::O200k).encode_ordinary(text).len(),
+            Self::Cl100k => bpe(Self::Cl100k).encode_ordinary(text).len(),
+        }
+    }
+
+    /// Content, tool calls and images of one message
+    pub fn message(&self, message: &Message) -> usize {
+        let calls: usize = message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| self.count(&call.name) + self.count(&call.arguments))
+            .sum();
+        self.count(&message.content) + calls + message.images.len() * IMAGE_TOKENS
+    }
+
+    /// Tool definitions sent alongside the messages
+    pub fn tools(&self, tools: &[ToolSchema]) -> usize {
+        tools
+            .iter()
+            .map(|t| {
+                self.count(&t.name)
+                    + self.count(&t.description)
+                    + self.count(&t.parameters.to_string())
+            })
+            .sum()
+    }
+}
+
+/// Context window of `model`: its `[models.context_windows]` entry, else
+/// `agent.context_window` if set, else the provider's published limit, else
+/// `fallback`
+pub fn context_window(model: &str, config: &Config, fallback: usize) -> usize {
+    let windows = &config.models.context_windows;
+    let (provider, model_id) = resolve_provider(model, config);
+    let configured = windows
+        .get(model)
+        .or_else(|| windows.get(config.models.resolve(model)))
+        .or_else(|| windows.get(&format!("{}/{}", provider, model_id)));
+    match configured {
+        Some(&window) => window,
+        None => config
+            .agent
+            .context_window
+            .or_else(|| known_context_window(&provider, &model_id))
+            .unwrap_or(fallback),
+    }
+}
+
+fn known_context_window(provider: &str, model_id: &str) -> Option<usize> {
+    let id = model_id.to_lowercase();
+    match provider {
+        "anthropic" | "claude-cli" => Some(200_000),
+        "gemini" | "google" => Some(1_048_576),
+        "deepseek" => Some(128_000),
+        "openai" if id.starts_with("gpt-4.1") => Some(1_047_576),
+        "openai" if id.starts_with("gpt-5") => Some(400_000),
+        "openai" if ["o1", "o3", "o4"].iter().any(|p| id.starts_with(p)) => Some(200_000),
+        "openai" if id.starts_with("gpt-4o") || id.starts_with("gpt-4-turbo") => Some(128_000),
+        "openai" if id == "gpt-4" || id.starts_with("gpt-4-0") => Some(8_192),
+        "openai" if id.starts_with("gpt-3.5") => Some(16_385),
+        _ => None,
+    }
+}
+
+/// Encodings are built once, on first use
+fn bpe(counter: TokenCounter) -> &'static CoreBPE {
+    static O200K: OnceLock<CoreBPE> = OnceLock::new();
+    static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+    match counter {
+        TokenCounter::Cl100k => {
+            CL100K.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base encoding"))
+        }
+        _ => O200K.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k_base encoding")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_for_model() {
+        let config = Config::default();
+        let counter = |model| TokenCounter::for_model(model, &config);
+        assert_eq!(counter("openai/gpt-4o"), TokenCounter::O200k);
+        assert_eq!(counter("gpt-4o-mini"), TokenCounter::O200k);
+        assert_eq!(counter("openai/gpt-4-turbo"), TokenCounter::Cl100k);
+        assert_eq!(counter("anthropic/claude-sonnet-4-5"), TokenCounter::Claude);
+        assert_eq!(counter("ollama/llama3.2"), TokenCounter::Estimate);
+
+        let text = "The quick brown fox jumps over the lazy dog.";
+        assert_eq!(TokenCounter::O200k.count(text), 10);
+        assert_eq!(TokenCounter::Cl100k.count(text), 10);
+        assert_eq!(TokenCounter::Estimate.count(text), 11);
+        assert_eq!(TokenCounter::Claude.count(text), 13);
+        assert_eq!(TokenCounter::O200k.count(""), 0);
+    }
+
+    #[test]
+    fn test_context_window() {
+        let mut config = Config::default();
+        config
+            .models
+            .aliases
+            .insert("local".into(), "ollama/llama3.2".into());
+        config
+            .models
+            .context_windows
+            .insert("ollama/llama3.2".into(), 8192);
+        assert_eq!(context_window("local", &config, 32_000), 8192);
+        assert_eq!(context_window("ollama/qwen3", &config, 32_000), 32_000);
+        assert_eq!(
+            context_window("anthropic/claude-opus-4-5", &config, 32_000),
+            200_000
+        );
+        assert_eq!(context_window("gpt-4o", &config, 32_000), 128_000);
+        assert_eq!(context_window("openai/gpt-4", &config, 32_000), 8_192);
+
+        // agent.context_window caps known models too, but not per-model entries
+        config.agent.context_window = Some(100_000);
+        assert_eq!(context_window("claude-cli/opus", &config, 32_000), 100_000);
+        assert_eq!(context_window("local", &config, 32_000), 8192);
+    }
+}
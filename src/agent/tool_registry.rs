@@ -0,0 +1,409 @@
+//! Registry of the tools agents are given
+//!
+//! Each tool is registered under its name with a factory that builds it from
+//! the config (or returns None when it isn't configured, like the browser
+//! without `tools.browser_allowed_domains`). The built-in tools are
+//! registered here; applications embedding LocalGPT add their own with
+//! `register_tool` before creating an agent, and `tools.disabled` leaves
+//! any of them out.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+use super::approval_policy::ApprovalStore;
+use super::calendar_tools::{CalendarTool, ReminderTool};
+use super::clipboard::ClipboardTool;
+use super::code_interpreter::CodeInterpreterTool;
+use super::code_search::{FindFilesTool, GrepWorkspaceTool};
+use super::document_search::DocumentSearchTool;
+use super::email::{EmailAccounts, EmailReadTool, EmailSearchTool, EmailSendTool};
+use super::fetch_url::FetchUrlTool;
+use super::messaging::MessageAgentTool;
+use super::notify::NotifyTool;
+use super::ocr::Ocr;
+use super::sandbox::PathSandbox;
+use super::sql::SqlTool;
+use super::subagent::SpawnSubagentTool;
+use super::test_runner::RunTestsTool;
+use super::tools::*;
+use super::web_search::WebSearchTool;
+use crate::config::Config;
+use crate::memory::MemoryManager;
+
+/// What a factory gets to build its tool from
+pub struct ToolContext<'a> {
+    pub config: &'a Config,
+    pub agent_id: &'a str,
+    pub memory: Option<Arc<MemoryManager>>,
+    /// The agent's approval policies, for tools that act on its behalf
+    pub approvals: ApprovalStore,
+    /// Live output of shell commands, for the desktop terminal panel
+    pub shell_events: broadcast::Sender<ShellEvent>,
+}
+
+/// Builds a tool, or returns None if the config leaves it off
+pub type ToolFactory = Arc<dyn Fn(&ToolContext) -> Result<Option<Box<dyn Tool>>> + Send + Sync>;
+
+/// Tools by name, in the order they are offered to the model
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    factories: Vec<(String, ToolFactory)>,
+}
+
+impl ToolRegistry {
+    /// The tools that ship with LocalGPT
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register("bash", |ctx| {
+            Ok(Some(Box::new(
+                BashTool::new(ctx.config.tools.bash_timeout_ms)
+                    .with_events(ctx.shell_events.clone()),
+            )))
+        });
+        registry.register("read_file", |ctx| {
+            let ocr = Ocr::from_config(ctx.config)?.map(Arc::new);
+            Ok(Some(Box::new(
+                ReadFileTool::new(PathSandbox::from_config(ctx.config)).with_ocr(ocr),
+            )))
+        });
+        registry.register("write_file", |ctx| {
+            Ok(Some(Box::new(WriteFileTool::new(
+                PathSandbox::from_config(ctx.config),
+            ))))
+        });
+        registry.register("edit_file", |ctx| {
+            Ok(Some(Box::new(EditFileTool::new(PathSandbox::from_config(
+                ctx.config,
+            )))))
+        });
+        registry.register("grep_workspace", |ctx| {
+            Ok(Some(Box::new(GrepWorkspaceTool::new(
+                PathSandbox::from_config(ctx.config),
+            ))))
+        });
+        registry.register("find_files", |ctx| {
+            Ok(Some(Box::new(FindFilesTool::new(
+                PathSandbox::from_config(ctx.config),
+            ))))
+        });
+        registry.register("apply_patch", |ctx| {
+            Ok(Some(Box::new(ApplyPatchTool::new(
+                PathSandbox::from_config(ctx.config),
+            ))))
+        });
+        // Indexed search when the agent has a memory manager, grep otherwise
+        registry.register("memory_search", |ctx| {
+            Ok(Some(match ctx.memory {
+                Some(ref memory) => Box::new(MemorySearchToolWithIndex::new(Arc::clone(memory))),
+                None => Box::new(MemorySearchTool::new(ctx.config.workspace_path())),
+            }))
+        });
+        registry.register("memory_get", |ctx| {
+            Ok(Some(Box::new(MemoryGetTool::new(
+                PathSandbox::from_config(ctx.config),
+            ))))
+        });
+        registry.register("memory_save", |ctx| {
+            Ok(ctx
+                .memory
+                .as_ref()
+                .map(|memory| Box::new(MemorySaveTool::new(Arc::clone(memory))) as Box<dyn Tool>))
+        });
+        registry.register("remember", |ctx| {
+            Ok(ctx
+                .memory
+                .as_ref()
+                .map(|memory| Box::new(RememberTool::new(Arc::clone(memory))) as Box<dyn Tool>))
+        });
+        // Only once `localgpt index` has added documents, or with watched
+        // document directories
+        registry.register("document_search", |ctx| {
+            Ok(ctx
+                .memory
+                .as_ref()
+                .filter(|memory| {
+                    !memory.document_dirs().is_empty() || memory.document_count().unwrap_or(0) > 0
+                })
+                .map(|memory| {
+                    Box::new(DocumentSearchTool::new(Arc::clone(memory))) as Box<dyn Tool>
+                }))
+        });
+        registry.register("web_fetch", |ctx| {
+            Ok(Some(Box::new(WebFetchTool::new(
+                ctx.config.tools.web_fetch_max_bytes,
+            ))))
+        });
+        registry.register("fetch_url", |ctx| {
+            Ok(Some(Box::new(FetchUrlTool::from_config(ctx.config)?)))
+        });
+        registry.register("web_search", |ctx| {
+            if ctx.config.tools.web_search.backend.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(WebSearchTool::from_config(ctx.config)?)))
+        });
+        registry.register("run_code", |ctx| {
+            let interpreter = &ctx.config.tools.code_interpreter;
+            Ok(interpreter
+                .enabled
+                .then(|| Box::new(CodeInterpreterTool::new(interpreter)) as Box<dyn Tool>))
+        });
+        registry.register("run_tests", |ctx| {
+            Ok(Some(Box::new(
+                RunTestsTool::new(ctx.config.workspace_path(), &ctx.config.tools.run_tests)
+                    .with_events(ctx.shell_events.clone()),
+            )))
+        });
+        registry.register("sql", |ctx| {
+            if ctx.config.tools.databases.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(SqlTool::from_config(ctx.config)?)))
+        });
+        registry.register("email_search", |ctx| {
+            if ctx.config.tools.email.is_empty() {
+                return Ok(None);
+            }
+            let accounts = EmailAccounts::from_config(ctx.config)?;
+            Ok(Some(Box::new(EmailSearchTool::new(accounts))))
+        });
+        registry.register("email_read", |ctx| {
+            if ctx.config.tools.email.is_empty() {
+                return Ok(None);
+            }
+            let accounts = EmailAccounts::from_config(ctx.config)?;
+            Ok(Some(Box::new(EmailReadTool::new(accounts))))
+        });
+        registry.register("email_send", |ctx| {
+            if ctx.config.tools.email.is_empty() {
+                return Ok(None);
+            }
+            let accounts = EmailAccounts::from_config(ctx.config)?;
+            Ok(Some(Box::new(EmailSendTool::new(accounts))))
+        });
+        registry.register("calendar", |ctx| {
+            if !ctx.config.calendar.enabled {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(CalendarTool::from_config(ctx.config)?)))
+        });
+        registry.register("reminder", |ctx| {
+            if !ctx.config.calendar.enabled {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(ReminderTool::new()?)))
+        });
+        registry.register("capture_screen", |ctx| {
+            Ok(ctx
+                .config
+                .tools
+                .screen_capture
+                .then(|| Box::new(ScreenCaptureTool::screen()) as Box<dyn Tool>))
+        });
+        registry.register("capture_window", |ctx| {
+            Ok(ctx
+                .config
+                .tools
+                .screen_capture
+                .then(|| Box::new(ScreenCaptureTool::window()) as Box<dyn Tool>))
+        });
+        registry.register("clipboard", |ctx| {
+            Ok(ctx
+                .config
+                .tools
+                .clipboard
+                .then(|| Box::new(ClipboardTool) as Box<dyn Tool>))
+        });
+        registry.register("notify", |ctx| {
+            Ok(ctx
+                .config
+                .tools
+                .notifications
+                .then(|| Box::new(NotifyTool) as Box<dyn Tool>))
+        });
+        registry.register("generate_image", |ctx| {
+            if ctx.config.tools.image_generation.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(ImageGenerationTool::from_config(
+                ctx.config,
+            )?)))
+        });
+        registry.register("browser", |ctx| {
+            let tools = &ctx.config.tools;
+            if tools.browser_allowed_domains.is_empty() {
+                return Ok(None);
+            }
+            #[cfg(feature = "browser")]
+            let tool: Option<Box<dyn Tool>> = Some(Box::new(BrowserTool::new(
+                tools.browser_allowed_domains.clone(),
+                tools.browser_executable.clone(),
+                tools.web_fetch_max_bytes,
+            )));
+            #[cfg(not(feature = "browser"))]
+            let tool = {
+                tracing::warn!("tools.browser_allowed_domains is set but the 'browser' feature is not enabled. Build with --features browser.");
+                None
+            };
+            Ok(tool)
+        });
+        registry.register("message_agent", |ctx| {
+            if ctx.config.tools.agent_messaging.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(MessageAgentTool::new(
+                ctx.config,
+                ctx.agent_id,
+            ))))
+        });
+        registry.register("spawn_subagent", |ctx| {
+            Ok(ctx.config.tools.subagent.enabled.then(|| {
+                Box::new(SpawnSubagentTool::new(
+                    ctx.config,
+                    ctx.agent_id,
+                    ctx.memory.clone(),
+                    ctx.approvals.clone(),
+                )) as Box<dyn Tool>
+            }))
+        });
+        registry
+    }
+
+    /// Add a tool, replacing any registered under the same name
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&ToolContext) -> Result<Option<Box<dyn Tool>>> + Send + Sync + 'static,
+    {
+        let factory: ToolFactory = Arc::new(factory);
+        match self.factories.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = factory,
+            None => self.factories.push((name.to_string(), factory)),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.factories
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Build every tool the config enables, skipping `tools.disabled` (and
+    /// any not in `tools.allowed`, when set)
+    pub fn build(&self, ctx: &ToolContext) -> Result<Vec<Box<dyn Tool>>> {
+        let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+        for (name, factory) in &self.factories {
+            if !ctx.config.tools.offers(name) {
+                continue;
+            }
+            let Some(tool) = factory(ctx)? else {
+                continue;
+            };
+            if tools.iter().any(|t| t.name() == tool.name()) {
+                anyhow::bail!("Two tools are named '{}'", tool.name());
+            }
+            tools.push(tool);
+        }
+        Ok(tools)
+    }
+}
+
+fn registered() -> &'static Mutex<ToolRegistry> {
+    static REGISTRY: OnceLock<Mutex<ToolRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(ToolRegistry::builtin()))
+}
+
+/// Give every agent created from now on this tool (or replace the built-in
+/// one with the same name)
+pub fn register_tool<F>(name: &str, factory: F)
+where
+    F: Fn(&ToolContext) -> Result<Option<Box<dyn Tool>>> + Send + Sync + 'static,
+{
+    registered()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .register(name, factory);
+}
+
+/// The built-in tools plus those added with `register_tool`
+pub fn tool_registry() -> ToolRegistry {
+    registered()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    use crate::agent::providers::ToolSchema;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema {
+                name: "echo".to_string(),
+                description: "Repeat the arguments".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn execute(&self, arguments: &str) -> Result<String> {
+            Ok(arguments.to_string())
+        }
+    }
+
+    #[test]
+    fn test_registry_build() {
+        let mut config = Config::default();
+        config.tools.disabled = vec!["bash".to_string()];
+        let (shell_events, _) = broadcast::channel(1);
+        let ctx = ToolContext {
+            config: &config,
+            agent_id: "main",
+            memory: None,
+            approvals: ApprovalStore::default(),
+            shell_events,
+        };
+
+        let mut registry = ToolRegistry::builtin();
+        registry.register("echo", |_| Ok(Some(Box::new(EchoTool))));
+        let tools = registry.build(&ctx).unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert!(!names.contains(&"bash"));
+        // Not configured
+        assert!(!names.contains(&"capture_screen"));
+        assert_eq!(names.first(), Some(&"read_file"));
+        assert_eq!(names.last(), Some(&"echo"));
+
+        // A factory whose tool takes a name already in use
+        registry.register("echo2", |_| Ok(Some(Box::new(EchoTool))));
+        assert!(registry.build(&ctx).is_err());
+
+        // Only the allowed tools
+        let mut config = Config::default();
+        config.tools.allowed = vec!["echo".to_string(), "read_file".to_string()];
+        let (shell_events, _) = broadcast::channel(1);
+        let ctx = ToolContext {
+            config: &config,
+            agent_id: "main",
+            memory: None,
+            approvals: ApprovalStore::default(),
+            shell_events,
+        };
+        let mut registry = ToolRegistry::builtin();
+        registry.register("echo", |_| Ok(Some(Box::new(EchoTool))));
+        let tools = registry.build(&ctx).unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert_eq!(names, vec!["read_file", "echo"]);
+    }
+}
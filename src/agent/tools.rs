@@ -2,13 +2,19 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::broadcast;
 use tracing::debug;
 
-use super::providers::ToolSchema;
+use super::diff::unified_diff;
+use super::diff::{apply_hunks, parse_patch, FilePatch, Patched};
+use super::ocr::Ocr;
+use super::providers::{ImageAttachment, ToolSchema};
+use super::sandbox::PathSandbox;
 use crate::config::Config;
-use crate::memory::MemoryManager;
+use crate::memory::{document_text, needs_extraction, MemoryManager};
 
 #[derive(Debug, Clone)]
 pub struct ToolResult {
@@ -21,40 +27,203 @@ pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
     fn schema(&self) -> ToolSchema;
     async fn execute(&self, arguments: &str) -> Result<String>;
+
+    /// Execute and return any images produced (e.g. screenshots for vision models).
+    /// Tools that only produce text can rely on the default.
+    async fn execute_with_images(&self, arguments: &str) -> Result<(String, Vec<ImageAttachment>)> {
+        Ok((self.execute(arguments).await?, Vec::new()))
+    }
+
+    /// Whether the output comes from outside the user's control and is
+    /// wrapped as untrusted external content
+    fn untrusted_output(&self) -> bool {
+        UNTRUSTED_OUTPUT_TOOLS.contains(&self.name())
+    }
+
+    /// What the call would change (a unified diff for file edits), shown
+    /// when the user is asked to approve it
+    fn preview(&self, _arguments: &str) -> Option<String> {
+        None
+    }
 }
 
-pub fn create_default_tools(
-    config: &Config,
-    memory: Option<Arc<MemoryManager>>,
-) -> Result<Vec<Box<dyn Tool>>> {
-    let workspace = config.workspace_path();
+/// Tools that must be approved by the user on every call, regardless of
+/// `tools.require_approval`. They are refused in non-interactive contexts.
+pub const ALWAYS_REQUIRE_APPROVAL: &[&str] = &["capture_screen", "capture_window", "email_send"];
+
+/// Tools known to have no side effects, whose calls in one turn may run
+/// together. Any other tool, including MCP and embedder tools, runs alone.
+pub const PARALLEL_SAFE_TOOLS: &[&str] = &[
+    "read_file",
+    "grep_workspace",
+    "find_files",
+    "memory_search",
+    "memory_get",
+    "document_search",
+    "web_fetch",
+    "fetch_url",
+    "web_search",
+    "email_search",
+    "email_read",
+];
+
+/// Tools whose output comes from outside the user's control (web pages, file
+/// contents, other agents). Their output is wrapped as untrusted external content.
+pub const UNTRUSTED_OUTPUT_TOOLS: &[&str] = &[
+    "web_fetch",
+    "web_search",
+    "fetch_url",
+    "browser",
+    "read_file",
+    "grep_workspace",
+    "message_agent",
+    "spawn_subagent",
+    "clipboard",
+    "email_search",
+    "email_read",
+    "document_search",
+    "calendar",
+    "sql",
+];
+
+/// Whether `host` is one of `domains` or a subdomain of one (a leading
+/// `*.` on a domain is ignored)
+pub(crate) fn host_in_domains(host: &str, domains: &[String]) -> bool {
+    let host = host.to_lowercase();
+    domains.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
 
-    // Use indexed memory search if MemoryManager is provided, otherwise fallback to grep-based
-    let memory_search_tool: Box<dyn Tool> = if let Some(ref mem) = memory {
-        Box::new(MemorySearchToolWithIndex::new(Arc::clone(mem)))
-    } else {
-        Box::new(MemorySearchTool::new(workspace.clone()))
-    };
+/// Resolve a file tool's path inside the workspace (see `PathSandbox`)
+pub fn workspace_path(workspace: &Path, path: &str) -> Result<PathBuf> {
+    PathSandbox::new(workspace.to_path_buf()).resolve(path)
+}
+
+/// Drop `.` and resolve `..` components without touching the filesystem
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
 
-    Ok(vec![
-        Box::new(BashTool::new(config.tools.bash_timeout_ms)),
-        Box::new(ReadFileTool::new()),
-        Box::new(WriteFileTool::new()),
-        Box::new(EditFileTool::new()),
-        memory_search_tool,
-        Box::new(MemoryGetTool::new(workspace)),
-        Box::new(WebFetchTool::new(config.tools.web_fetch_max_bytes)),
-    ])
+/// Live shell activity from the bash tool, for terminal-style UIs
+#[derive(Debug, Clone)]
+pub enum ShellEvent {
+    Started { command: String },
+    Output { text: String, stderr: bool },
+    Finished { exit_code: Option<i32> },
 }
 
 // Bash Tool
 pub struct BashTool {
     default_timeout_ms: u64,
+    events: Option<broadcast::Sender<ShellEvent>>,
 }
 
 impl BashTool {
     pub fn new(default_timeout_ms: u64) -> Self {
-        Self { default_timeout_ms }
+        Self {
+            default_timeout_ms,
+            events: None,
+        }
+    }
+
+    /// Mirror live stdout/stderr to subscribers while commands run
+    pub fn with_events(mut self, events: broadcast::Sender<ShellEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn emit(&self, event: ShellEvent) {
+        if let Some(ref events) = self.events {
+            // No subscribers is fine
+            let _ = events.send(event);
+        }
+    }
+
+    /// Read a pipe to completion, forwarding each line as it arrives
+    async fn read_pipe<R: AsyncRead + Unpin>(&self, pipe: Option<R>, stderr: bool) -> Vec<u8> {
+        let mut collected = Vec::new();
+        let Some(pipe) = pipe else {
+            return collected;
+        };
+
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    self.emit(ShellEvent::Output {
+                        text: String::from_utf8_lossy(&line).to_string(),
+                        stderr,
+                    });
+                    collected.extend_from_slice(&line);
+                }
+            }
+        }
+        collected
+    }
+
+    /// Run `command` in `dir` (default: the current directory), mirroring
+    /// its output to subscribers. Returns stdout, stderr and the exit code
+    /// (None when killed by a signal).
+    pub(crate) async fn run(
+        &self,
+        command: &str,
+        dir: Option<&Path>,
+        timeout_ms: u64,
+    ) -> Result<(Vec<u8>, Vec<u8>, Option<i32>)> {
+        self.emit(ShellEvent::Started {
+            command: command.to_string(),
+        });
+
+        let mut process = tokio::process::Command::new("bash");
+        process.arg("-c").arg(command);
+        if let Some(dir) = dir {
+            process.current_dir(dir);
+        }
+        let mut child = process
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout_pipe = child.stdout.take();
+        let stderr_pipe = child.stderr.take();
+
+        // Run command with timeout (the child is killed on drop if it expires)
+        let timeout_duration = std::time::Duration::from_millis(timeout_ms);
+        let run = async {
+            tokio::join!(
+                self.read_pipe(stdout_pipe, false),
+                self.read_pipe(stderr_pipe, true),
+                child.wait()
+            )
+        };
+        let (stdout, stderr, status) = match tokio::time::timeout(timeout_duration, run).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.emit(ShellEvent::Finished { exit_code: None });
+                anyhow::bail!("Command timed out after {}ms", timeout_ms);
+            }
+        };
+        let status = status?;
+        self.emit(ShellEvent::Finished {
+            exit_code: status.code(),
+        });
+        Ok((stdout, stderr, status.code()))
     }
 }
 
@@ -100,20 +269,9 @@ impl Tool for BashTool {
             timeout_ms, command
         );
 
-        // Run command with timeout
-        let timeout_duration = std::time::Duration::from_millis(timeout_ms);
-        let output = tokio::time::timeout(
-            timeout_duration,
-            tokio::process::Command::new("bash")
-                .arg("-c")
-                .arg(command)
-                .output(),
-        )
-        .await
-        .map_err(|_| anyhow::anyhow!("Command timed out after {}ms", timeout_ms))??;
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let (stdout, stderr, exit_code) = self.run(command, None, timeout_ms).await?;
+        let stdout = String::from_utf8_lossy(&stdout);
+        let stderr = String::from_utf8_lossy(&stderr);
 
         let mut result = String::new();
 
@@ -131,7 +289,7 @@ impl Tool for BashTool {
         if result.is_empty() {
             result = format!(
                 "Command completed with exit code: {}",
-                output.status.code().unwrap_or(-1)
+                exit_code.unwrap_or(-1)
             );
         }
 
@@ -140,11 +298,23 @@ impl Tool for BashTool {
 }
 
 // Read File Tool
-pub struct ReadFileTool;
+pub struct ReadFileTool {
+    sandbox: PathSandbox,
+    ocr: Option<Arc<Ocr>>,
+}
 
 impl ReadFileTool {
-    pub fn new() -> Self {
-        Self
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+            ocr: None,
+        }
+    }
+
+    /// Read images and PDFs as OCR'd text
+    pub fn with_ocr(mut self, ocr: Option<Arc<Ocr>>) -> Self {
+        self.ocr = ocr;
+        self
     }
 }
 
@@ -157,13 +327,13 @@ impl Tool for ReadFileTool {
     fn schema(&self) -> ToolSchema {
         ToolSchema {
             name: "read_file".to_string(),
-            description: "Read the contents of a file".to_string(),
+            description: "Read the contents of a file in the workspace (PDFs and Word documents are returned as extracted text with page markers; images too when OCR is enabled)".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": {
                         "type": "string",
-                        "description": "The path to the file to read"
+                        "description": "The path to the file to read, relative to the workspace"
                     },
                     "offset": {
                         "type": "integer",
@@ -185,11 +355,15 @@ impl Tool for ReadFileTool {
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("Missing path"))?;
 
-        let path = shellexpand::tilde(path).to_string();
+        let path = self.sandbox.resolve(path)?;
 
-        debug!("Reading file: {}", path);
+        debug!("Reading file: {}", path.display());
 
-        let content = fs::read_to_string(&path)?;
+        let content = match self.ocr {
+            Some(ref ocr) if Ocr::supports(&path) => ocr.extract_and_save(&path).await?.0,
+            _ if needs_extraction(&path) => document_text(&path).await?,
+            _ => fs::read_to_string(&path)?,
+        };
 
         // Handle offset and limit
         let offset = args["offset"].as_u64().unwrap_or(0) as usize;
@@ -214,11 +388,15 @@ impl Tool for ReadFileTool {
 }
 
 // Write File Tool
-pub struct WriteFileTool;
+pub struct WriteFileTool {
+    sandbox: PathSandbox,
+}
 
 impl WriteFileTool {
-    pub fn new() -> Self {
-        Self
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+        }
     }
 }
 
@@ -231,13 +409,14 @@ impl Tool for WriteFileTool {
     fn schema(&self) -> ToolSchema {
         ToolSchema {
             name: "write_file".to_string(),
-            description: "Write content to a file (creates or overwrites)".to_string(),
+            description: "Write content to a file in the workspace (creates or overwrites)"
+                .to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": {
                         "type": "string",
-                        "description": "The path to the file to write"
+                        "description": "The path to the file to write, relative to the workspace"
                     },
                     "content": {
                         "type": "string",
@@ -258,8 +437,7 @@ impl Tool for WriteFileTool {
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("Missing content"))?;
 
-        let path = shellexpand::tilde(path).to_string();
-        let path = PathBuf::from(&path);
+        let path = self.sandbox.resolve(path)?;
 
         debug!("Writing file: {}", path.display());
 
@@ -276,14 +454,56 @@ impl Tool for WriteFileTool {
             path.display()
         ))
     }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        let label = args["path"].as_str()?;
+        let path = self.sandbox.resolve(label).ok()?;
+        // A new file is diffed against nothing
+        let old = fs::read_to_string(&path).unwrap_or_default();
+        Some(unified_diff(label, &old, args["content"].as_str()?))
+    }
 }
 
 // Edit File Tool
-pub struct EditFileTool;
+pub struct EditFileTool {
+    sandbox: PathSandbox,
+}
 
 impl EditFileTool {
-    pub fn new() -> Self {
-        Self
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+        }
+    }
+
+    /// The file's path, its contents after the edit, and how many
+    /// replacements were made
+    fn edited(&self, arguments: &str) -> Result<(PathBuf, String, usize)> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let path = args["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing path"))?;
+        let old_string = args["old_string"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing old_string"))?;
+        let new_string = args["new_string"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing new_string"))?;
+        let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+
+        let path = self.sandbox.resolve(path)?;
+        let content = fs::read_to_string(&path)?;
+
+        let (new_content, count) = if replace_all {
+            let count = content.matches(old_string).count();
+            (content.replace(old_string, new_string), count)
+        } else if content.contains(old_string) {
+            (content.replacen(old_string, new_string, 1), 1)
+        } else {
+            return Err(anyhow::anyhow!("old_string not found in file"));
+        };
+        Ok((path, new_content, count))
     }
 }
 
@@ -296,13 +516,14 @@ impl Tool for EditFileTool {
     fn schema(&self) -> ToolSchema {
         ToolSchema {
             name: "edit_file".to_string(),
-            description: "Edit a file by replacing old_string with new_string".to_string(),
+            description: "Edit a file in the workspace by replacing old_string with new_string"
+                .to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": {
                         "type": "string",
-                        "description": "The path to the file to edit"
+                        "description": "The path to the file to edit, relative to the workspace"
                     },
                     "old_string": {
                         "type": "string",
@@ -323,36 +544,231 @@ impl Tool for EditFileTool {
     }
 
     async fn execute(&self, arguments: &str) -> Result<String> {
+        let (path, new_content, count) = self.edited(arguments)?;
+
+        debug!("Editing file: {}", path.display());
+
+        fs::write(&path, &new_content)?;
+
+        Ok(format!(
+            "Replaced {} occurrence(s) in {}",
+            count,
+            path.display()
+        ))
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let (path, new_content, _) = self.edited(arguments).ok()?;
+        let old = fs::read_to_string(&path).ok()?;
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        Some(unified_diff(args["path"].as_str()?, &old, &new_content))
+    }
+}
+
+// Apply Patch Tool
+pub struct ApplyPatchTool {
+    sandbox: PathSandbox,
+}
+
+/// What a patch would do to one file
+struct PatchedFile {
+    /// Path as written in the patch
+    label: String,
+    /// File the patch reads; None for a new file
+    source: Option<PathBuf>,
+    /// File the result goes to; None when the patch deletes the file
+    target: Option<PathBuf>,
+    old: String,
+    patched: Patched,
+    hunks: usize,
+    /// Why the file can't be patched at all (missing, already exists)
+    error: Option<String>,
+}
+
+impl ApplyPatchTool {
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
+        }
+    }
+
+    /// Each file of the patch with its contents after patching
+    fn plan(&self, arguments: &str) -> Result<Vec<PatchedFile>> {
         let args: Value = serde_json::from_str(arguments)?;
-        let path = args["path"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Missing path"))?;
-        let old_string = args["old_string"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Missing old_string"))?;
-        let new_string = args["new_string"]
+        let patch = args["patch"]
             .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Missing new_string"))?;
-        let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+            .ok_or_else(|| anyhow::anyhow!("Missing patch"))?;
+        parse_patch(patch)?
+            .into_iter()
+            .map(|file| self.plan_file(file))
+            .collect()
+    }
 
-        let path = shellexpand::tilde(path).to_string();
+    fn plan_file(&self, file: FilePatch) -> Result<PatchedFile> {
+        let label = file
+            .new_path
+            .clone()
+            .or_else(|| file.old_path.clone())
+            .ok_or_else(|| anyhow::anyhow!("A file in the patch is /dev/null on both sides"))?;
+        let resolve =
+            |path: &Option<String>| path.as_deref().map(|p| self.sandbox.resolve(p)).transpose();
+        let source = resolve(&file.old_path)?;
+        let target = resolve(&file.new_path)?;
+
+        let mut error = None;
+        let old = match source {
+            Some(ref path) => fs::read_to_string(path).unwrap_or_else(|e| {
+                error = Some(format!("cannot read it ({})", e));
+                String::new()
+            }),
+            None => {
+                if target.as_ref().is_some_and(|t| t.exists()) {
+                    error = Some("the patch creates it, but it already exists".to_string());
+                }
+                String::new()
+            }
+        };
+        Ok(PatchedFile {
+            label,
+            source,
+            target,
+            patched: apply_hunks(&old, &file.hunks),
+            old,
+            hunks: file.hunks.len(),
+            error,
+        })
+    }
 
-        debug!("Editing file: {}", path);
+    /// Why a file was left unchanged, with what the model should fix
+    fn refusal(file: &PatchedFile) -> Option<String> {
+        if let Some(ref error) = file.error {
+            return Some(format!("Not applied to {}: {}", file.label, error));
+        }
+        let mut out = String::new();
+        for rejection in &file.patched.rejected {
+            out.push_str(&format!(
+                "Not applied to {}: hunk {} of {} ({}) does not match the file",
+                file.label, rejection.hunk, file.hunks, rejection.header
+            ));
+            if rejection.near_line > 0 {
+                out.push_str(&format!(" near line {}", rejection.near_line));
+            }
+            out.push_str(". It expects:\n");
+            for line in rejection.expected.iter().take(12) {
+                out.push_str(&format!("    {}\n", line));
+            }
+            if rejection.expected.len() > 12 {
+                out.push_str("    ...\n");
+            }
+        }
+        if out.is_empty() {
+            return None;
+        }
+        out.push_str(&format!(
+            "{} was left unchanged; read it again and send a corrected patch for it.",
+            file.label
+        ));
+        Some(out)
+    }
+}
 
-        let content = fs::read_to_string(&path)?;
+#[async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
 
-        let (new_content, count) = if replace_all {
-            let count = content.matches(old_string).count();
-            (content.replace(old_string, new_string), count)
-        } else if content.contains(old_string) {
-            (content.replacen(old_string, new_string, 1), 1)
-        } else {
-            return Err(anyhow::anyhow!("old_string not found in file"));
-        };
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "apply_patch".to_string(),
+            description: "Change workspace files with a unified diff (--- a/path, +++ b/path, @@ hunks with 3 lines of context). Cheaper than rewriting whole files; can create (--- /dev/null), delete (+++ /dev/null) and rename files. Hunks are placed even if line numbers are off; a file with a hunk that doesn't match is left unchanged and reported.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "patch": {
+                        "type": "string",
+                        "description": "The unified diff, paths relative to the workspace"
+                    }
+                },
+                "required": ["patch"]
+            }),
+        }
+    }
 
-        fs::write(&path, &new_content)?;
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let files = self.plan(arguments)?;
+        let mut report = Vec::new();
+        let mut applied = 0;
+
+        for file in &files {
+            if let Some(refusal) = Self::refusal(file) {
+                report.push(refusal);
+                continue;
+            }
+            debug!("Patching file: {}", file.label);
+
+            let summary = match (&file.source, &file.target) {
+                (_, Some(target)) => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(target, &file.patched.content)?;
+                    match file.source {
+                        None => format!("Created {}", file.label),
+                        Some(ref source) if source != target => {
+                            fs::remove_file(source)?;
+                            format!("Renamed {} to {}", source.display(), file.label)
+                        }
+                        Some(_) => {
+                            let mut summary = format!(
+                                "Patched {} ({} hunk{}",
+                                file.label,
+                                file.hunks,
+                                if file.hunks == 1 { "" } else { "s" }
+                            );
+                            if file.patched.fuzzy > 0 {
+                                summary.push_str(&format!(
+                                    ", {} placed ignoring whitespace or outer context",
+                                    file.patched.fuzzy
+                                ));
+                            }
+                            summary.push(')');
+                            summary
+                        }
+                    }
+                }
+                (Some(source), None) => {
+                    fs::remove_file(source)?;
+                    format!("Deleted {}", file.label)
+                }
+                (None, None) => unreachable!("checked in plan_file"),
+            };
+            report.push(summary);
+            applied += 1;
+        }
 
-        Ok(format!("Replaced {} occurrence(s) in {}", count, path))
+        if applied == 0 {
+            anyhow::bail!("{}", report.join("\n\n"));
+        }
+        Ok(report.join("\n\n"))
+    }
+
+    fn preview(&self, arguments: &str) -> Option<String> {
+        let files = self.plan(arguments).ok()?;
+        let parts: Vec<String> = files
+            .iter()
+            .map(|file| {
+                Self::refusal(file).unwrap_or_else(|| {
+                    let new = if file.target.is_some() {
+                        file.patched.content.as_str()
+                    } else {
+                        ""
+                    };
+                    unified_diff(&file.label, &file.old, new)
+                })
+            })
+            .collect();
+        Some(parts.join("\n"))
     }
 }
 
@@ -522,7 +938,7 @@ impl Tool for MemorySearchToolWithIndex {
             search_type, query, limit
         );
 
-        let results = self.memory.search(query, limit)?;
+        let results = self.memory.search(query, limit).await?;
 
         if results.is_empty() {
             return Ok("No results found".to_string());
@@ -554,20 +970,13 @@ impl Tool for MemorySearchToolWithIndex {
 
 // Memory Get Tool - efficient snippet fetching after memory_search
 pub struct MemoryGetTool {
-    workspace: PathBuf,
+    sandbox: PathSandbox,
 }
 
 impl MemoryGetTool {
-    pub fn new(workspace: PathBuf) -> Self {
-        Self { workspace }
-    }
-
-    fn resolve_path(&self, path: &str) -> PathBuf {
-        // Handle paths relative to workspace
-        if path.starts_with("memory/") || path == "MEMORY.md" || path == "HEARTBEAT.md" {
-            self.workspace.join(path)
-        } else {
-            PathBuf::from(shellexpand::tilde(path).to_string())
+    pub fn new(workspace: impl Into<PathSandbox>) -> Self {
+        Self {
+            sandbox: workspace.into(),
         }
     }
 }
@@ -612,7 +1021,7 @@ impl Tool for MemoryGetTool {
         let from = args["from"].as_u64().unwrap_or(1).max(1) as usize;
         let lines_count = args["lines"].as_u64().unwrap_or(50) as usize;
 
-        let resolved_path = self.resolve_path(path);
+        let resolved_path = self.sandbox.resolve(path)?;
 
         debug!(
             "Memory get: {} (from: {}, lines: {})",
@@ -657,6 +1066,111 @@ impl Tool for MemoryGetTool {
     }
 }
 
+// Memory Save Tool - appends to MEMORY.md and embeds it for memory_search
+pub struct MemorySaveTool {
+    memory: Arc<MemoryManager>,
+}
+
+impl MemorySaveTool {
+    pub fn new(memory: Arc<MemoryManager>) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemorySaveTool {
+    fn name(&self) -> &str {
+        "memory_save"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "memory_save".to_string(),
+            description: "Save a fact, note or decision to long-term memory (MEMORY.md). It is indexed right away, so memory_search finds it in later sessions. One self-contained statement per call. For facts about the user themselves, use remember.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "What to save, e.g. 'The Atlas project uses PostgreSQL 16'"
+                    }
+                },
+                "required": ["text"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let text = args["text"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing text"))?;
+
+        debug!("Memory save: {}", text);
+
+        Ok(match self.memory.remember(text).await? {
+            (line, Some(replaced)) => format!(
+                "Updated MEMORY.md:{} (replaced the similar \"{}\")",
+                line, replaced
+            ),
+            (line, None) => format!("Saved to MEMORY.md:{}", line),
+        })
+    }
+}
+
+// Remember Tool - durable facts about the user, kept in USER.md
+pub struct RememberTool {
+    memory: Arc<MemoryManager>,
+}
+
+impl RememberTool {
+    pub fn new(memory: Arc<MemoryManager>) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for RememberTool {
+    fn name(&self) -> &str {
+        "remember"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "remember".to_string(),
+            description: "Remember a durable fact about the user: who they are, their preferences, projects or habits. Saved to USER.md, which is part of every future conversation. Use it when the user tells you something about themselves worth knowing next time; one short standalone sentence per call.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "fact": {
+                        "type": "string",
+                        "description": "The fact, e.g. 'Prefers metric units'"
+                    }
+                },
+                "required": ["fact"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let fact = args["fact"]
+            .as_str()
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing fact"))?;
+
+        debug!("Remember: {}", fact);
+
+        let added = self.memory.add_user_facts(&[fact.to_string()]).await?;
+        Ok(if added.is_empty() {
+            format!("Already known: {}", fact)
+        } else {
+            format!("Remembered in USER.md: {}", added.join("; "))
+        })
+    }
+}
+
 // Web Fetch Tool
 pub struct WebFetchTool {
     client: reqwest::Client,
@@ -728,32 +1242,1053 @@ impl Tool for WebFetchTool {
     }
 }
 
-/// Extract relevant detail from tool arguments for display.
-/// Returns a human-readable summary of the key argument (file path, command, query, URL).
-pub fn extract_tool_detail(tool_name: &str, arguments: &str) -> Option<String> {
-    let args: Value = serde_json::from_str(arguments).ok()?;
+// Screen Capture Tool
+pub struct ScreenCaptureTool {
+    window: bool,
+}
 
-    match tool_name {
-        "edit_file" | "write_file" | "read_file" => args
-            .get("path")
-            .or_else(|| args.get("file_path"))
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        "bash" => args.get("command").and_then(|v| v.as_str()).map(|s| {
-            if s.len() > 60 {
-                format!("{}...", &s[..57])
+impl ScreenCaptureTool {
+    /// Capture the entire screen
+    pub fn screen() -> Self {
+        Self { window: false }
+    }
+
+    /// Capture the currently focused window
+    pub fn window() -> Self {
+        Self { window: true }
+    }
+
+    /// Platform capture commands to try in order (program, args).
+    /// The output path is appended as the last argument.
+    fn capture_commands(&self) -> Vec<(&'static str, Vec<&'static str>)> {
+        if cfg!(target_os = "macos") {
+            if self.window {
+                // -w: window selection mode, -o: no window shadow
+                vec![("screencapture", vec!["-x", "-o", "-w", "-t", "png"])]
+            } else {
+                vec![("screencapture", vec!["-x", "-t", "png"])]
+            }
+        } else if cfg!(target_os = "linux") {
+            if self.window {
+                vec![
+                    ("gnome-screenshot", vec!["-w", "-f"]),
+                    ("spectacle", vec!["-a", "-b", "-n", "-o"]),
+                    ("scrot", vec!["-u", "-o"]),
+                ]
+            } else {
+                vec![
+                    ("grim", vec![]),
+                    ("gnome-screenshot", vec!["-f"]),
+                    ("spectacle", vec!["-f", "-b", "-n", "-o"]),
+                    ("scrot", vec!["-o"]),
+                    ("import", vec!["-window", "root"]),
+                ]
+            }
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ScreenCaptureTool {
+    fn name(&self) -> &str {
+        if self.window {
+            "capture_window"
+        } else {
+            "capture_screen"
+        }
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let description = if self.window {
+            "Take a screenshot of the focused window and attach it for visual inspection. Requires user approval each time."
+        } else {
+            "Take a screenshot of the entire screen and attach it for visual inspection. Requires user approval each time."
+        };
+
+        ToolSchema {
+            name: self.name().to_string(),
+            description: description.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "reason": {
+                        "type": "string",
+                        "description": "Why the screenshot is needed (shown to the user when asking for approval)"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let (text, _) = self.execute_with_images(arguments).await?;
+        Ok(text)
+    }
+
+    async fn execute_with_images(
+        &self,
+        _arguments: &str,
+    ) -> Result<(String, Vec<ImageAttachment>)> {
+        let commands = self.capture_commands();
+        if commands.is_empty() {
+            anyhow::bail!("Screen capture is not supported on this platform");
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("localgpt-capture-{}.png", uuid::Uuid::new_v4()));
+        let path_str = path.to_string_lossy().to_string();
+
+        let mut errors = Vec::new();
+        for (program, args) in commands {
+            debug!("Capturing screen with {} -> {}", program, path_str);
+
+            let result = tokio::process::Command::new(program)
+                .args(&args)
+                .arg(&path_str)
+                .output()
+                .await;
+
+            match result {
+                Ok(output) if output.status.success() && path.exists() => {
+                    errors.clear();
+                    break;
+                }
+                Ok(output) => errors.push(format!(
+                    "{}: {}",
+                    program,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    errors.push(format!("{}: not installed", program))
+                }
+                Err(e) => errors.push(format!("{}: {}", program, e)),
+            }
+        }
+
+        if !errors.is_empty() {
+            let _ = fs::remove_file(&path);
+            anyhow::bail!("Screen capture failed ({})", errors.join("; "));
+        }
+
+        let bytes = fs::read(&path)?;
+        let _ = fs::remove_file(&path);
+
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        let image = ImageAttachment {
+            data: STANDARD.encode(&bytes),
+            media_type: "image/png".to_string(),
+        };
+
+        let target = if self.window { "window" } else { "screen" };
+        Ok((
+            format!(
+                "Captured {} ({} bytes). The screenshot is attached to the next message.",
+                target,
+                bytes.len()
+            ),
+            vec![image],
+        ))
+    }
+}
+
+// Image Generation Tool
+/// Image generation backend selected by `tools.image_generation`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageBackend {
+    /// AUTOMATIC1111 / Forge WebUI (`/sdapi/v1/txt2img`)
+    Automatic1111,
+    /// ComfyUI (`/prompt` + `/history` + `/view`)
+    ComfyUi,
+    /// OpenAI Images API (uses `providers.openai`)
+    OpenAi,
+}
+
+impl ImageBackend {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "automatic1111" | "a1111" => Ok(Self::Automatic1111),
+            "comfyui" => Ok(Self::ComfyUi),
+            "openai" => Ok(Self::OpenAi),
+            other => anyhow::bail!(
+                "Unknown image generation backend '{}'. Use automatic1111, comfyui, or openai.",
+                other
+            ),
+        }
+    }
+
+    fn default_url(&self) -> &'static str {
+        match self {
+            Self::Automatic1111 => "http://127.0.0.1:7860",
+            Self::ComfyUi => "http://127.0.0.1:8188",
+            Self::OpenAi => "https://api.openai.com/v1",
+        }
+    }
+}
+
+pub struct ImageGenerationTool {
+    client: reqwest::Client,
+    backend: ImageBackend,
+    base_url: String,
+    api_key: Option<String>,
+    model: Option<String>,
+    output_dir: PathBuf,
+}
+
+impl ImageGenerationTool {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let backend = ImageBackend::parse(&config.tools.image_generation)?;
+        let openai = config.providers.openai.as_ref();
+
+        let api_key = match backend {
+            ImageBackend::OpenAi => Some(
+                openai
+                    .map(|o| o.api_key.clone())
+                    .ok_or_else(|| anyhow::anyhow!("OpenAI image generation requires [providers.openai] in ~/.localgpt/config.toml"))?,
+            ),
+            _ => None,
+        };
+
+        let base_url = config
+            .tools
+            .image_generation_url
+            .clone()
+            .or_else(|| match backend {
+                ImageBackend::OpenAi => openai.map(|o| o.base_url.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| backend.default_url().to_string());
+
+        if backend == ImageBackend::ComfyUi && config.tools.image_generation_model.is_none() {
+            anyhow::bail!(
+                "ComfyUI image generation requires tools.image_generation_model (checkpoint file name)"
+            );
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            backend,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: config.tools.image_generation_model.clone(),
+            output_dir: config.workspace_path().join("images"),
+        })
+    }
+
+    async fn generate_automatic1111(
+        &self,
+        prompt: &str,
+        negative: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>> {
+        let mut body = json!({
+            "prompt": prompt,
+            "negative_prompt": negative,
+            "width": width,
+            "height": height,
+            "steps": 25,
+        });
+        if let Some(ref model) = self.model {
+            body["override_settings"] = json!({ "sd_model_checkpoint": model });
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/sdapi/v1/txt2img", self.base_url))
+            .json(&body)
+            .send()
+            .await?;
+        let result = check_image_response(response, "AUTOMATIC1111").await?;
+
+        let image = result["images"][0]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("AUTOMATIC1111 returned no image"))?;
+        decode_base64_image(image)
+    }
+
+    async fn generate_comfyui(
+        &self,
+        prompt: &str,
+        negative: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>> {
+        let checkpoint = self.model.as_deref().unwrap_or_default();
+        let seed = uuid::Uuid::new_v4().as_u128() as u32;
+
+        // Minimal text-to-image workflow (API format)
+        let workflow = json!({
+            "1": { "class_type": "CheckpointLoaderSimple", "inputs": { "ckpt_name": checkpoint } },
+            "2": { "class_type": "CLIPTextEncode", "inputs": { "text": prompt, "clip": ["1", 1] } },
+            "3": { "class_type": "CLIPTextEncode", "inputs": { "text": negative, "clip": ["1", 1] } },
+            "4": { "class_type": "EmptyLatentImage", "inputs": { "width": width, "height": height, "batch_size": 1 } },
+            "5": { "class_type": "KSampler", "inputs": {
+                "model": ["1", 0], "positive": ["2", 0], "negative": ["3", 0], "latent_image": ["4", 0],
+                "seed": seed, "steps": 25, "cfg": 7.0, "sampler_name": "euler", "scheduler": "normal", "denoise": 1.0
+            } },
+            "6": { "class_type": "VAEDecode", "inputs": { "samples": ["5", 0], "vae": ["1", 2] } },
+            "7": { "class_type": "SaveImage", "inputs": { "images": ["6", 0], "filename_prefix": "localgpt" } }
+        });
+
+        let response = self
+            .client
+            .post(format!("{}/prompt", self.base_url))
+            .json(&json!({ "prompt": workflow }))
+            .send()
+            .await?;
+        let queued = check_image_response(response, "ComfyUI").await?;
+        let prompt_id = queued["prompt_id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("ComfyUI did not return a prompt_id"))?
+            .to_string();
+
+        // Poll until the workflow finishes
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(600);
+        let image = loop {
+            if std::time::Instant::now() > deadline {
+                anyhow::bail!("ComfyUI image generation timed out");
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+            let history: Value = self
+                .client
+                .get(format!("{}/history/{}", self.base_url, prompt_id))
+                .send()
+                .await?
+                .json()
+                .await?;
+            if let Some(image) = history[&prompt_id]["outputs"]["7"]["images"].get(0) {
+                break image.clone();
+            }
+        };
+
+        let bytes = self
+            .client
+            .get(format!("{}/view", self.base_url))
+            .query(&[
+                ("filename", image["filename"].as_str().unwrap_or_default()),
+                ("subfolder", image["subfolder"].as_str().unwrap_or_default()),
+                ("type", image["type"].as_str().unwrap_or("output")),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn generate_openai(&self, prompt: &str, width: u32, height: u32) -> Result<Vec<u8>> {
+        let model = self.model.as_deref().unwrap_or("gpt-image-1");
+        let mut body = json!({
+            "model": model,
+            "prompt": prompt,
+            "size": format!("{}x{}", width, height),
+            "n": 1,
+        });
+        // gpt-image models always return base64; DALL-E needs asking
+        if model.starts_with("dall-e") {
+            body["response_format"] = json!("b64_json");
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/images/generations", self.base_url))
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.api_key.as_deref().unwrap_or_default()),
+            )
+            .json(&body)
+            .send()
+            .await?;
+        let result = check_image_response(response, "OpenAI Images").await?;
+
+        let image = result["data"][0]["b64_json"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("OpenAI Images returned no image"))?;
+        decode_base64_image(image)
+    }
+
+    /// Save under workspace/images/ with a timestamped, prompt-derived name
+    fn save(&self, prompt: &str, bytes: &[u8]) -> Result<PathBuf> {
+        fs::create_dir_all(&self.output_dir)?;
+
+        let slug: String = prompt
+            .split_whitespace()
+            .take(6)
+            .collect::<Vec<_>>()
+            .join("-")
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>()
+            .to_lowercase();
+        let name = format!(
+            "{}-{}.png",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            if slug.is_empty() { "image" } else { &slug }
+        );
+
+        let path = self.output_dir.join(name);
+        fs::write(&path, bytes)?;
+        Ok(path)
+    }
+}
+
+async fn check_image_response(response: reqwest::Response, backend: &str) -> Result<Value> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} error {}: {}", backend, status, body);
+    }
+    Ok(response.json().await?)
+}
+
+fn decode_base64_image(data: &str) -> Result<Vec<u8>> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    // Some backends prefix a data URL header
+    let data = data.split_once(',').map(|(_, d)| d).unwrap_or(data);
+    Ok(STANDARD.decode(data)?)
+}
+
+/// Parse "WIDTHxHEIGHT"
+fn parse_image_size(size: &str) -> Option<(u32, u32)> {
+    let (w, h) = size.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+#[async_trait]
+impl Tool for ImageGenerationTool {
+    fn name(&self) -> &str {
+        "generate_image"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "generate_image".to_string(),
+            description: "Generate an image from a text prompt. The image is saved under the workspace images/ folder and shown to the user.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "prompt": {
+                        "type": "string",
+                        "description": "Detailed description of the image"
+                    },
+                    "negative_prompt": {
+                        "type": "string",
+                        "description": "What to avoid (Stable Diffusion backends only)"
+                    },
+                    "size": {
+                        "type": "string",
+                        "description": "WIDTHxHEIGHT, e.g. 1024x1024 (default)"
+                    }
+                },
+                "required": ["prompt"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let prompt = args["prompt"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing prompt"))?;
+        let negative = args["negative_prompt"].as_str().unwrap_or("");
+        let (width, height) = args["size"]
+            .as_str()
+            .and_then(parse_image_size)
+            .unwrap_or((1024, 1024));
+
+        debug!(
+            "Generating {}x{} image via {:?}: {}",
+            width, height, self.backend, prompt
+        );
+
+        let bytes = match self.backend {
+            ImageBackend::Automatic1111 => {
+                self.generate_automatic1111(prompt, negative, width, height)
+                    .await?
+            }
+            ImageBackend::ComfyUi => {
+                self.generate_comfyui(prompt, negative, width, height)
+                    .await?
+            }
+            ImageBackend::OpenAi => self.generate_openai(prompt, width, height).await?,
+        };
+
+        let path = self.save(prompt, &bytes)?;
+
+        // The markdown image line is rendered inline by the desktop chat
+        Ok(format!(
+            "Image saved to {}\n\n![{}]({})",
+            path.display(),
+            prompt.replace(['[', ']'], ""),
+            path.display()
+        ))
+    }
+}
+
+// Browser Tool (headless Chrome via DevTools Protocol)
+#[cfg(feature = "browser")]
+pub struct BrowserTool {
+    allowed_domains: Vec<String>,
+    executable: Option<String>,
+    max_bytes: usize,
+    session: tokio::sync::Mutex<Option<BrowserSession>>,
+}
+
+#[cfg(feature = "browser")]
+struct BrowserSession {
+    // Kept alive for the page; dropping it kills the browser process
+    _browser: chromiumoxide::Browser,
+    page: chromiumoxide::Page,
+    _handler: tokio::task::JoinHandle<()>,
+    /// Fails requests to hosts outside the allowlist
+    _interceptor: tokio::task::JoinHandle<()>,
+}
+
+/// Whether the browser may request `url`: http(s) to an allowed host, or
+/// content that never leaves the browser (`data:`, `blob:`, `about:`)
+#[cfg(feature = "browser")]
+fn browser_url_allowed(url: &str, domains: &[String]) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    match parsed.scheme() {
+        "data" | "blob" | "about" => true,
+        "http" | "https" => parsed
+            .host_str()
+            .is_some_and(|host| host_in_domains(host, domains)),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "browser")]
+impl BrowserTool {
+    pub fn new(allowed_domains: Vec<String>, executable: Option<String>, max_bytes: usize) -> Self {
+        Self {
+            allowed_domains,
+            executable,
+            max_bytes,
+            session: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Check a URL to navigate to against the domain allowlist (exact host
+    /// or subdomain, http(s) only)
+    fn is_allowed(&self, url: &str) -> bool {
+        url.starts_with("http") && browser_url_allowed(url, &self.allowed_domains)
+    }
+
+    async fn launch(&self) -> Result<BrowserSession> {
+        use chromiumoxide::{Browser, BrowserConfig};
+        use futures::StreamExt;
+
+        let mut builder = BrowserConfig::builder();
+        if let Some(ref path) = self.executable {
+            builder = builder.chrome_executable(shellexpand::tilde(path).to_string());
+        }
+        let config = builder
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid browser config: {}", e))?;
+
+        let (browser, mut handler) = Browser::launch(config).await?;
+        let handler = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+        let page = browser.new_page("about:blank").await?;
+        let interceptor = self.intercept_requests(&page).await?;
+
+        Ok(BrowserSession {
+            _browser: browser,
+            page,
+            _handler: handler,
+            _interceptor: interceptor,
+        })
+    }
+
+    /// Pause every request the page makes (navigations, subresources,
+    /// scripts' fetches) and fail those to hosts outside the allowlist
+    /// before they are sent
+    async fn intercept_requests(
+        &self,
+        page: &chromiumoxide::Page,
+    ) -> Result<tokio::task::JoinHandle<()>> {
+        use chromiumoxide::cdp::browser_protocol::fetch::{
+            ContinueRequestParams, EnableParams, EventRequestPaused, FailRequestParams,
+            RequestPattern,
+        };
+        use chromiumoxide::cdp::browser_protocol::network::ErrorReason;
+        use futures::StreamExt;
+
+        let mut paused = page.event_listener::<EventRequestPaused>().await?;
+        page.execute(
+            EnableParams::builder()
+                .pattern(RequestPattern::builder().url_pattern("*").build())
+                .build(),
+        )
+        .await?;
+
+        let page = page.clone();
+        let domains = self.allowed_domains.clone();
+        Ok(tokio::spawn(async move {
+            while let Some(event) = paused.next().await {
+                let id = event.request_id.clone();
+                let result = if browser_url_allowed(&event.request.url, &domains) {
+                    page.execute(ContinueRequestParams::new(id))
+                        .await
+                        .map(|_| ())
+                } else {
+                    debug!("Browser request blocked: {}", event.request.url);
+                    page.execute(FailRequestParams::new(id, ErrorReason::BlockedByClient))
+                        .await
+                        .map(|_| ())
+                };
+                if let Err(e) = result {
+                    debug!("Browser request interception failed: {}", e);
+                }
+            }
+        }))
+    }
+
+    /// Leave the page if it still ended up outside the allowlist (requests
+    /// there are already failed by `intercept_requests`)
+    async fn ensure_allowed_page(&self, page: &chromiumoxide::Page) -> Result<String> {
+        let current = page.url().await?.unwrap_or_default();
+        if !self.is_allowed(&current) {
+            let _ = page.goto("about:blank").await;
+            anyhow::bail!("Navigation to {} blocked: domain not in allowlist", current);
+        }
+        Ok(current)
+    }
+
+    fn truncate(&self, text: String) -> String {
+        if text.len() > self.max_bytes {
+            let mut end = self.max_bytes;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            format!(
+                "{}...\n\n[Truncated, {} bytes total]",
+                &text[..end],
+                text.len()
+            )
+        } else {
+            text
+        }
+    }
+}
+
+#[cfg(feature = "browser")]
+#[async_trait]
+impl Tool for BrowserTool {
+    fn name(&self) -> &str {
+        "browser"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "browser".to_string(),
+            description: format!(
+                "Control a headless browser for JavaScript-heavy pages that web_fetch can't read. \
+                 Actions: navigate (url), click (selector), extract (optional selector), screenshot. \
+                 Only these domains are allowed: {}",
+                self.allowed_domains.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["navigate", "click", "extract", "screenshot"],
+                        "description": "The browser action to perform"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "URL to open (navigate)"
+                    },
+                    "selector": {
+                        "type": "string",
+                        "description": "CSS selector of the element (click, extract)"
+                    }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let (text, _) = self.execute_with_images(arguments).await?;
+        Ok(text)
+    }
+
+    async fn execute_with_images(&self, arguments: &str) -> Result<(String, Vec<ImageAttachment>)> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let action = args["action"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing action"))?;
+
+        let mut session = self.session.lock().await;
+        if session.is_none() {
+            debug!("Launching headless browser");
+            *session = Some(self.launch().await?);
+        }
+        let page = &session.as_ref().unwrap().page;
+
+        match action {
+            "navigate" => {
+                let url = args["url"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing url"))?;
+                if !self.is_allowed(url) {
+                    anyhow::bail!("Domain not in browser allowlist: {}", url);
+                }
+
+                debug!("Browser navigating to: {}", url);
+                page.goto(url).await?;
+                let current = self.ensure_allowed_page(page).await?;
+                let title = page.get_title().await?.unwrap_or_default();
+                Ok((format!("Loaded: {}\nTitle: {}", current, title), Vec::new()))
+            }
+            "click" => {
+                let selector = args["selector"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing selector"))?;
+
+                debug!("Browser clicking: {}", selector);
+                page.find_element(selector).await?.click().await?;
+                // Give client-side navigation a moment to settle
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                let current = self.ensure_allowed_page(page).await?;
+                Ok((
+                    format!("Clicked {}\nNow at: {}", selector, current),
+                    Vec::new(),
+                ))
+            }
+            "extract" => {
+                self.ensure_allowed_page(page).await?;
+                let text = match args["selector"].as_str() {
+                    Some(selector) => page
+                        .find_element(selector)
+                        .await?
+                        .inner_text()
+                        .await?
+                        .unwrap_or_default(),
+                    None => page
+                        .evaluate("document.body ? document.body.innerText : ''")
+                        .await?
+                        .into_value::<String>()?,
+                };
+                Ok((self.truncate(text), Vec::new()))
+            }
+            "screenshot" => {
+                let current = self.ensure_allowed_page(page).await?;
+                let bytes = page
+                    .screenshot(chromiumoxide::page::ScreenshotParams::builder().build())
+                    .await?;
+
+                use base64::{engine::general_purpose::STANDARD, Engine as _};
+                let image = ImageAttachment {
+                    data: STANDARD.encode(&bytes),
+                    media_type: "image/png".to_string(),
+                };
+                Ok((
+                    format!(
+                        "Captured screenshot of {} ({} bytes). The screenshot is attached to the next message.",
+                        current,
+                        bytes.len()
+                    ),
+                    vec![image],
+                ))
+            }
+            other => anyhow::bail!("Unknown browser action: {}", other),
+        }
+    }
+}
+
+/// Extract relevant detail from tool arguments for display.
+/// Returns a human-readable summary of the key argument (file path, command, query, URL).
+pub fn extract_tool_detail(tool_name: &str, arguments: &str) -> Option<String> {
+    let args: Value = serde_json::from_str(arguments).ok()?;
+
+    match tool_name {
+        "edit_file" | "write_file" | "read_file" => args
+            .get("path")
+            .or_else(|| args.get("file_path"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        "bash" => args.get("command").and_then(|v| v.as_str()).map(|s| {
+            if s.len() > 60 {
+                format!("{}...", &s[..57])
             } else {
                 s.to_string()
             }
         }),
-        "memory_search" => args
+        "apply_patch" => {
+            let patch = args.get("patch").and_then(|v| v.as_str())?;
+            let paths: Vec<String> = parse_patch(patch)
+                .ok()?
+                .into_iter()
+                .filter_map(|f| f.new_path.or(f.old_path))
+                .collect();
+            Some(paths.join(", "))
+        }
+        "grep_workspace" | "find_files" => {
+            let pattern = args.get("pattern").and_then(|v| v.as_str())?;
+            Some(match args.get("path").and_then(|v| v.as_str()) {
+                Some(path) => format!("\"{}\" in {}", pattern, path),
+                None => format!("\"{}\"", pattern),
+            })
+        }
+        "email_search" => Some(
+            [
+                args.get("query")
+                    .and_then(|v| v.as_str())
+                    .map(|q| format!("\"{}\"", q)),
+                args.get("from")
+                    .and_then(|v| v.as_str())
+                    .map(|f| format!("from {}", f)),
+                args.get("folder")
+                    .and_then(|v| v.as_str())
+                    .map(|f| format!("in {}", f)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" "),
+        )
+        .filter(|s| !s.is_empty()),
+        "email_read" => args
+            .get("uid")
+            .and_then(|v| v.as_u64())
+            .map(|uid| format!("uid {}", uid)),
+        "email_send" => args
+            .get("subject")
+            .and_then(|v| v.as_str())
+            .map(|s| format!("\"{}\"", s)),
+        "calendar" | "reminder" => args.get("action").and_then(|v| v.as_str()).map(|action| {
+            match args
+                .get("title")
+                .or_else(|| args.get("text"))
+                .and_then(|v| v.as_str())
+            {
+                Some(title) => format!("{} \"{}\"", action, title),
+                None => action.to_string(),
+            }
+        }),
+        "notify" => args
+            .get("title")
+            .and_then(|v| v.as_str())
+            .map(|s| format!("\"{}\"", s)),
+        "clipboard" => args
+            .get("action")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        "memory_search" | "web_search" | "document_search" => args
             .get("query")
             .and_then(|v| v.as_str())
             .map(|s| format!("\"{}\"", s)),
-        "web_fetch" => args
+        "memory_save" => args
+            .get("text")
+            .and_then(|v| v.as_str())
+            .map(|s| format!("\"{}\"", s)),
+        "remember" => args
+            .get("fact")
+            .and_then(|v| v.as_str())
+            .map(|s| format!("\"{}\"", s)),
+        "run_tests" => {
+            let path = args.get("path").and_then(|v| v.as_str());
+            let filter = args.get("filter").and_then(|v| v.as_str());
+            match (path, filter) {
+                (Some(path), Some(filter)) => Some(format!("\"{}\" in {}", filter, path)),
+                (Some(path), None) => Some(path.to_string()),
+                (None, Some(filter)) => Some(format!("\"{}\"", filter)),
+                (None, None) => None,
+            }
+        }
+        "web_fetch" | "fetch_url" => args
             .get("url")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
+        "browser" => {
+            let action = args.get("action").and_then(|v| v.as_str())?;
+            let target = args
+                .get("url")
+                .or_else(|| args.get("selector"))
+                .and_then(|v| v.as_str());
+            Some(match target {
+                Some(t) => format!("{} {}", action, t),
+                None => action.to_string(),
+            })
+        }
+        "sql" => args.get("query").and_then(|v| v.as_str()).map(|s| {
+            let mut s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+            if s.chars().count() > 60 {
+                s = format!("{}...", s.chars().take(57).collect::<String>());
+            }
+            match args.get("database").and_then(|v| v.as_str()) {
+                Some(db) => format!("{}: {}", db, s),
+                None => s,
+            }
+        }),
+        "message_agent" => args
+            .get("agent")
+            .and_then(|v| v.as_str())
+            .map(|s| format!("→ {}", s)),
+        "spawn_subagent" => args.get("task").and_then(|v| v.as_str()).map(|s| {
+            if s.len() > 60 {
+                format!("{}...", s.chars().take(57).collect::<String>())
+            } else {
+                s.to_string()
+            }
+        }),
+        "generate_image" => args.get("prompt").and_then(|v| v.as_str()).map(|s| {
+            if s.len() > 60 {
+                format!("{}...", s.chars().take(57).collect::<String>())
+            } else {
+                s.to_string()
+            }
+        }),
+        "capture_screen" | "capture_window" => args
+            .get("reason")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "browser")]
+    #[test]
+    fn test_browser_requests_follow_allowlist() {
+        let domains = vec!["example.com".to_string()];
+        assert!(browser_url_allowed(
+            "https://docs.example.com/a.js",
+            &domains
+        ));
+        assert!(browser_url_allowed("data:image/png;base64,AAAA", &domains));
+        assert!(!browser_url_allowed(
+            "https://tracker.net/pixel.gif",
+            &domains
+        ));
+        assert!(!browser_url_allowed(
+            "https://example.com.evil.net/",
+            &domains
+        ));
+        assert!(!browser_url_allowed("file:///etc/passwd", &domains));
+    }
+
+    #[tokio::test]
+    async fn test_bash_streams_shell_events() {
+        let (events, mut rx) = broadcast::channel(16);
+        let tool = BashTool::new(5000).with_events(events);
+
+        let output = tool
+            .execute(r#"{"command": "echo out; echo err >&2"}"#)
+            .await
+            .unwrap();
+        assert_eq!(output, "out\n\n\nSTDERR:\nerr\n");
+
+        assert!(matches!(rx.recv().await, Ok(ShellEvent::Started { .. })));
+        let mut lines = Vec::new();
+        loop {
+            match rx.recv().await.unwrap() {
+                ShellEvent::Output { text, stderr } => lines.push((text, stderr)),
+                ShellEvent::Finished { exit_code } => {
+                    assert_eq!(exit_code, Some(0));
+                    break;
+                }
+                ShellEvent::Started { .. } => panic!("unexpected start"),
+            }
+        }
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![("err\n".to_string(), true), ("out\n".to_string(), false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_tools_stay_in_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().to_path_buf();
+        fs::write(workspace.join("notes.md"), "one\ntwo\n").unwrap();
+
+        let edit = EditFileTool::new(workspace.clone());
+        let args = r#"{"path": "notes.md", "old_string": "two", "new_string": "2"}"#;
+        assert_eq!(
+            edit.preview(args).unwrap(),
+            "--- a/notes.md\n+++ b/notes.md\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n"
+        );
+        // Previewing changes nothing
+        assert_eq!(
+            fs::read_to_string(workspace.join("notes.md")).unwrap(),
+            "one\ntwo\n"
+        );
+        edit.execute(args).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(workspace.join("notes.md")).unwrap(),
+            "one\n2\n"
+        );
+
+        let write = WriteFileTool::new(workspace.clone());
+        assert!(write
+            .execute(r#"{"path": "../outside.md", "content": "x"}"#)
+            .await
+            .is_err());
+        assert!(!dir.path().parent().unwrap().join("outside.md").exists());
+        let read = ReadFileTool::new(workspace.clone());
+        assert!(read.execute(r#"{"path": "/etc/hostname"}"#).await.is_err());
+        assert!(read.execute(r#"{"path": "sub/../notes.md"}"#).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().to_path_buf();
+        fs::write(workspace.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(workspace.join("b.txt"), "alpha\nbeta\n").unwrap();
+        fs::write(workspace.join("old.txt"), "gone\n").unwrap();
+
+        let tool = ApplyPatchTool::new(workspace.clone());
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n\
+                     --- a/b.txt\n+++ b/b.txt\n@@ -1,2 +1,2 @@\n alpha\n-gamma\n+delta\n\
+                     --- /dev/null\n+++ b/new/c.txt\n@@ -0,0 +1 @@\n+created\n\
+                     --- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\n";
+        let args = json!({ "patch": patch }).to_string();
+        let out = tool.execute(&args).await.unwrap();
+        assert_eq!(
+            out,
+            "Patched a.txt (1 hunk)\n\n\
+             Not applied to b.txt: hunk 1 of 1 (@@ -1,2 +1,2 @@) does not match the file near line 1. It expects:\n    alpha\n    gamma\n\
+             b.txt was left unchanged; read it again and send a corrected patch for it.\n\n\
+             Created new/c.txt\n\nDeleted old.txt"
+        );
+        assert_eq!(
+            fs::read_to_string(workspace.join("a.txt")).unwrap(),
+            "one\n2\nthree\n"
+        );
+        assert_eq!(
+            fs::read_to_string(workspace.join("b.txt")).unwrap(),
+            "alpha\nbeta\n"
+        );
+        assert_eq!(
+            fs::read_to_string(workspace.join("new/c.txt")).unwrap(),
+            "created\n"
+        );
+        assert!(!workspace.join("old.txt").exists());
+
+        // Nothing applied is an error
+        let args = json!({ "patch": "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-missing\n+x\n" });
+        assert!(tool.execute(&args.to_string()).await.is_err());
+    }
+}
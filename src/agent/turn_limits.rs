@@ -0,0 +1,164 @@
+//! Limits on one turn's tool loop
+//!
+//! A turn keeps calling the model as long as it asks for tools. These limits
+//! (`agent.max_tool_iterations`, `max_failed_tool_calls`, `max_turn_secs`)
+//! are checked after each round of tool calls; when one is hit the turn ends
+//! with a reply saying which, instead of an error, so the results so far stay
+//! in the session and the user can tell the agent to continue.
+
+use std::time::{Duration, Instant};
+
+use super::approval::DENIED_OUTPUT;
+use crate::config::AgentConfig;
+
+/// Why a turn was stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LimitHit {
+    Iterations(usize),
+    FailedCalls(usize),
+    WallClock(Duration),
+}
+
+impl LimitHit {
+    /// The reply that ends the turn
+    pub(crate) fn notice(&self) -> String {
+        let reason = match self {
+            LimitHit::Iterations(n) => format!(
+                "I stopped after {} rounds of tool calls, the limit for one turn \
+                 (agent.max_tool_iterations)",
+                n
+            ),
+            LimitHit::FailedCalls(n) => format!(
+                "I stopped after {} failed tool calls in a row \
+                 (agent.max_failed_tool_calls), rather than keep retrying",
+                n
+            ),
+            LimitHit::WallClock(elapsed) => format!(
+                "I stopped after {} seconds, the time limit for one turn \
+                 (agent.max_turn_secs)",
+                elapsed.as_secs()
+            ),
+        };
+        format!(
+            "[{}. The tool results so far are kept; say \"continue\" to pick up where I left off.]",
+            reason
+        )
+    }
+}
+
+pub(crate) struct TurnLimits {
+    max_iterations: usize,
+    max_failed: usize,
+    max_duration: Option<Duration>,
+    started: Instant,
+    iterations: usize,
+    failed_in_row: usize,
+}
+
+impl TurnLimits {
+    /// Limits for a turn starting now
+    pub(crate) fn new(config: &AgentConfig) -> Self {
+        Self {
+            max_iterations: config.max_tool_iterations,
+            max_failed: config.max_failed_tool_calls,
+            max_duration: (config.max_turn_secs > 0)
+                .then(|| Duration::from_secs(config.max_turn_secs)),
+            started: Instant::now(),
+            iterations: 0,
+            failed_in_row: 0,
+        }
+    }
+
+    /// Count a finished round of tool calls by their outputs, in call order
+    /// ("Error: ..." outputs are failures; denied calls are neither failures
+    /// nor successes). Returns the limit that ends the turn, if any.
+    pub(crate) fn after_tool_round<'a>(
+        &mut self,
+        outputs: impl IntoIterator<Item = &'a str>,
+    ) -> Option<LimitHit> {
+        self.iterations += 1;
+        for output in outputs {
+            if output == DENIED_OUTPUT {
+                continue;
+            }
+            if output.starts_with("Error:") {
+                self.failed_in_row += 1;
+            } else {
+                self.failed_in_row = 0;
+            }
+        }
+
+        if self.max_failed > 0 && self.failed_in_row >= self.max_failed {
+            return Some(LimitHit::FailedCalls(self.failed_in_row));
+        }
+        if self.max_iterations > 0 && self.iterations >= self.max_iterations {
+            return Some(LimitHit::Iterations(self.iterations));
+        }
+        let elapsed = self.started.elapsed();
+        match self.max_duration {
+            Some(max) if elapsed >= max => Some(LimitHit::WallClock(elapsed)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits_with(iterations: usize, failed: usize) -> TurnLimits {
+        let config = AgentConfig {
+            max_tool_iterations: iterations,
+            max_failed_tool_calls: failed,
+            ..AgentConfig::default()
+        };
+        TurnLimits::new(&config)
+    }
+
+    #[test]
+    fn test_iteration_limit() {
+        let mut limits = limits_with(3, 0);
+        assert_eq!(limits.after_tool_round(["ok"]), None);
+        assert_eq!(limits.after_tool_round(["ok"]), None);
+        assert_eq!(
+            limits.after_tool_round(["ok"]),
+            Some(LimitHit::Iterations(3))
+        );
+
+        let mut unlimited = limits_with(0, 0);
+        for _ in 0..50 {
+            assert_eq!(unlimited.after_tool_round(["Error: nope"]), None);
+        }
+    }
+
+    #[test]
+    fn test_consecutive_failures() {
+        let mut limits = limits_with(0, 3);
+        assert_eq!(limits.after_tool_round(["Error: a", "Error: b"]), None);
+        // A success in between resets the count; denials are ignored
+        assert_eq!(limits.after_tool_round(["fine", "Error: c"]), None);
+        assert_eq!(limits.after_tool_round([DENIED_OUTPUT, "Error: d"]), None);
+        assert_eq!(
+            limits.after_tool_round(["Error: e"]),
+            Some(LimitHit::FailedCalls(3))
+        );
+        assert!(LimitHit::FailedCalls(3)
+            .notice()
+            .contains("agent.max_failed_tool_calls"));
+    }
+
+    #[test]
+    fn test_wall_clock_limit() {
+        let config = AgentConfig {
+            max_turn_secs: 1,
+            ..AgentConfig::default()
+        };
+        let mut limits = TurnLimits::new(&config);
+        assert_eq!(limits.after_tool_round(["ok"]), None);
+        limits.started -= Duration::from_secs(2);
+        assert!(matches!(
+            limits.after_tool_round(["ok"]),
+            Some(LimitHit::WallClock(_))
+        ));
+    }
+}
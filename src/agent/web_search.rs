@@ -0,0 +1,363 @@
+//! `web_search` tool (`[tools.web_search]`)
+//!
+//! Queries a SearxNG instance (JSON API), the Brave Search API, or
+//! DuckDuckGo's HTML results page, and returns numbered results (title,
+//! URL, snippet) the model can cite as [1], [2], ...
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::providers::{http_client, ToolSchema};
+use super::tools::Tool;
+use crate::config::{expand_env, Config};
+
+/// Most results a single search may ask for
+const MAX_RESULTS_LIMIT: usize = 20;
+
+/// Browser-like agent; DuckDuckGo refuses requests without one
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; LocalGPT/0.1)";
+
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+static DDG_TITLE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?s)<a[^>]*class="result__a"[^>]*href="([^"]*)"[^>]*>(.*?)</a>"#).unwrap()
+});
+static DDG_SNIPPET: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?s)<a[^>]*class="result__snippet"[^>]*>(.*?)</a>"#).unwrap());
+
+/// Search engine selected by `tools.web_search.backend`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchBackend {
+    /// Self-hosted SearxNG (`/search?format=json`; JSON output must be
+    /// enabled in its settings.yml)
+    Searxng,
+    /// Brave Search API (needs `api_key`)
+    Brave,
+    /// DuckDuckGo's HTML results page (no key)
+    DuckDuckGo,
+}
+
+impl SearchBackend {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "searxng" | "searx" => Ok(Self::Searxng),
+            "brave" => Ok(Self::Brave),
+            "duckduckgo" | "ddg" => Ok(Self::DuckDuckGo),
+            other => anyhow::bail!(
+                "Unknown web search backend '{}'. Use searxng, brave, or duckduckgo.",
+                other
+            ),
+        }
+    }
+
+    fn default_url(&self) -> &'static str {
+        match self {
+            Self::Searxng => "http://127.0.0.1:8888",
+            Self::Brave => "https://api.search.brave.com/res/v1",
+            Self::DuckDuckGo => "https://html.duckduckgo.com/html",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Searxng => "SearxNG",
+            Self::Brave => "Brave",
+            Self::DuckDuckGo => "DuckDuckGo",
+        }
+    }
+}
+
+/// One search result, in the engine's ranking order
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+pub struct WebSearchTool {
+    client: reqwest::Client,
+    backend: SearchBackend,
+    base_url: String,
+    api_key: Option<String>,
+    max_results: usize,
+}
+
+impl WebSearchTool {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let search = &config.tools.web_search;
+        let backend = SearchBackend::parse(&search.backend)?;
+        let api_key = match search.api_key {
+            Some(ref key) => Some(expand_env(key).map_err(|var| {
+                anyhow::anyhow!(
+                    "tools.web_search.api_key uses ${{{}}}, which is not set",
+                    var
+                )
+            })?),
+            None => None,
+        };
+        if backend == SearchBackend::Brave && api_key.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!("Brave web search requires tools.web_search.api_key");
+        }
+        let base_url = search
+            .url
+            .clone()
+            .unwrap_or_else(|| backend.default_url().to_string());
+
+        Ok(Self {
+            client: http_client(&search.timeouts, &search.network)?,
+            backend,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            max_results: search.max_results.clamp(1, MAX_RESULTS_LIMIT),
+        })
+    }
+
+    async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchResult>> {
+        let request = match self.backend {
+            SearchBackend::Searxng => self
+                .client
+                .get(format!("{}/search", self.base_url))
+                .query(&[("q", query), ("format", "json")]),
+            SearchBackend::Brave => self
+                .client
+                .get(format!("{}/web/search", self.base_url))
+                .query(&[("q", query), ("count", &count.to_string())])
+                .header("Accept", "application/json")
+                .header(
+                    "X-Subscription-Token",
+                    self.api_key.as_deref().unwrap_or(""),
+                ),
+            SearchBackend::DuckDuckGo => self
+                .client
+                .get(format!("{}/", self.base_url))
+                .query(&[("q", query)])
+                .header("User-Agent", USER_AGENT),
+        };
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("{} search failed", self.backend.name()))?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            let hint = match (self.backend, status.as_u16()) {
+                (SearchBackend::Searxng, 403) => {
+                    " (enable the json format in SearxNG's settings.yml)"
+                }
+                (SearchBackend::Brave, 401 | 422) => " (check tools.web_search.api_key)",
+                _ => "",
+            };
+            anyhow::bail!("{} search returned {}{}", self.backend.name(), status, hint);
+        }
+
+        let mut results = match self.backend {
+            SearchBackend::Searxng => parse_searxng(&serde_json::from_str(&body)?),
+            SearchBackend::Brave => parse_brave(&serde_json::from_str(&body)?),
+            SearchBackend::DuckDuckGo => parse_duckduckgo(&body),
+        };
+        results.truncate(count);
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: "web_search".to_string(),
+            description: "Search the web. Returns numbered results with title, URL and snippet; cite them as [1], [2], ... and use web_fetch to read a page in full.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!("Number of results (default: {}, at most {})", self.max_results, MAX_RESULTS_LIMIT)
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<String> {
+        let args: Value = serde_json::from_str(arguments)?;
+        let query = args["query"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing query"))?;
+        let count = args["max_results"]
+            .as_u64()
+            .map_or(self.max_results, |n| n as usize)
+            .clamp(1, MAX_RESULTS_LIMIT);
+
+        debug!("Searching {} for: {}", self.backend.name(), query);
+
+        let results = self.search(query, count).await?;
+        Ok(format_results(query, &results))
+    }
+}
+
+/// Numbered results for the model
+fn format_results(query: &str, results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return format!("No results for \"{}\"", query);
+    }
+    let mut out = format!("Results for \"{}\":\n", query);
+    for (i, result) in results.iter().enumerate() {
+        out.push_str(&format!("\n[{}] {}\n{}\n", i + 1, result.title, result.url));
+        if !result.snippet.is_empty() {
+            out.push_str(&result.snippet);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn parse_searxng(body: &Value) -> Vec<SearchResult> {
+    body["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| {
+            Some(SearchResult {
+                title: html_to_text(r["title"].as_str()?),
+                url: r["url"].as_str()?.to_string(),
+                snippet: html_to_text(r["content"].as_str().unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+fn parse_brave(body: &Value) -> Vec<SearchResult> {
+    body["web"]["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| {
+            Some(SearchResult {
+                title: html_to_text(r["title"].as_str()?),
+                url: r["url"].as_str()?.to_string(),
+                // Matches come wrapped in <strong>
+                snippet: html_to_text(r["description"].as_str().unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+/// Organic results of DuckDuckGo's HTML page (ads are skipped)
+fn parse_duckduckgo(html: &str) -> Vec<SearchResult> {
+    html.split("<div class=\"result ")
+        .skip(1)
+        .filter(|block| !block.contains("result--ad"))
+        .filter_map(|block| {
+            let title = DDG_TITLE.captures(block)?;
+            let url = ddg_target(&html_to_text(&title[1]))?;
+            Some(SearchResult {
+                title: html_to_text(&title[2]),
+                url,
+                snippet: DDG_SNIPPET
+                    .captures(block)
+                    .map(|s| html_to_text(&s[1]))
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// The page a DuckDuckGo result link redirects to (`/l/?uddg=<url>`)
+fn ddg_target(href: &str) -> Option<String> {
+    let absolute = match href {
+        h if h.starts_with("//") => format!("https:{}", h),
+        h if h.starts_with('/') => format!("https://duckduckgo.com{}", h),
+        h => h.to_string(),
+    };
+    let url = reqwest::Url::parse(&absolute).ok()?;
+    if url.path() != "/l/" {
+        return Some(absolute);
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "uddg")
+        .map(|(_, target)| target.into_owned())
+}
+
+/// Tags removed, common entities decoded, whitespace collapsed
+fn html_to_text(html: &str) -> String {
+    let text = TAG
+        .replace_all(html, "")
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_results() {
+        let searxng = json!({
+            "results": [
+                { "title": "Rust", "url": "https://www.rust-lang.org/", "content": "A language &amp; more" },
+                { "title": "No URL" },
+            ]
+        });
+        assert_eq!(
+            parse_searxng(&searxng),
+            vec![SearchResult {
+                title: "Rust".to_string(),
+                url: "https://www.rust-lang.org/".to_string(),
+                snippet: "A language & more".to_string(),
+            }]
+        );
+
+        let brave = json!({
+            "web": { "results": [
+                { "title": "Tokio", "url": "https://tokio.rs/", "description": "An <strong>async</strong> runtime" },
+            ] }
+        });
+        assert_eq!(parse_brave(&brave)[0].snippet, "An async runtime");
+
+        let ddg = r#"
+<div class="result results_links results_links_deep result--ad ">
+  <a rel="nofollow" class="result__a" href="https://ads.example.com/">Ad</a>
+</div>
+<div class="result results_links results_links_deep web-result ">
+  <h2 class="result__title">
+    <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdocs.rs%2Fserde&amp;rut=abc">serde - <b>Rust</b></a>
+  </h2>
+  <a class="result__snippet" href="//duckduckgo.com/l/?uddg=x">A <b>serialization</b> framework</a>
+</div>
+"#;
+        assert_eq!(
+            parse_duckduckgo(ddg),
+            vec![SearchResult {
+                title: "serde - Rust".to_string(),
+                url: "https://docs.rs/serde".to_string(),
+                snippet: "A serialization framework".to_string(),
+            }]
+        );
+
+        let text = format_results("serde", &parse_duckduckgo(ddg));
+        assert!(text.contains("[1] serde - Rust\nhttps://docs.rs/serde\n"));
+        assert_eq!(format_results("x", &[]), "No results for \"x\"");
+    }
+}
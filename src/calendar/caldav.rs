@@ -0,0 +1,185 @@
+//! A CalDAV (RFC 4791) calendar collection: time-range queries with
+//! server-side recurrence expansion, PUT to add, DELETE to remove
+
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, Method, StatusCode, Url};
+use std::time::Duration;
+
+use super::ics::{self, Event};
+
+static RESPONSE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:[\w-]+:)?response\b[^>]*>(.*?)</(?:[\w-]+:)?response>").unwrap()
+});
+static HREF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<(?:[\w-]+:)?href\b[^>]*>(.*?)</(?:[\w-]+:)?href>").unwrap());
+static CALENDAR_DATA: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:[\w-]+:)?calendar-data\b[^>]*>(.*?)</(?:[\w-]+:)?calendar-data>").unwrap()
+});
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct CalDav {
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    client: Client,
+}
+
+impl CalDav {
+    pub fn new(url: &str, username: Option<String>, password: Option<String>) -> Result<Self> {
+        // Resources are addressed relative to the collection, which needs
+        // its trailing slash for that
+        let url = if url.ends_with('/') {
+            url.to_string()
+        } else {
+            format!("{}/", url)
+        };
+        Ok(Self {
+            url: Url::parse(&url).map_err(|e| anyhow::anyhow!("Invalid caldav_url: {}", e))?,
+            username,
+            password,
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    fn request(&self, method: Method, url: Url) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        match self.username {
+            Some(ref username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+
+    /// Event occurrences overlapping `[from, to)`
+    pub async fn events(&self, from: DateTime<Local>, to: DateTime<Local>) -> Result<Vec<Event>> {
+        let (start, end) = (utc(from), utc(to));
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data><C:expand start="{start}" end="{end}"/></C:calendar-data></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR"><C:comp-filter name="VEVENT">
+    <C:time-range start="{start}" end="{end}"/>
+  </C:comp-filter></C:comp-filter></C:filter>
+</C:calendar-query>"#
+        );
+        // Servers that don't expand send the whole series; expand it here
+        Ok(self
+            .query(&body)
+            .await?
+            .into_iter()
+            .flat_map(|(_, events)| events)
+            .flat_map(|event| event.occurrences(from, to))
+            .collect())
+    }
+
+    pub async fn add(&self, event: &Event) -> Result<()> {
+        let url = self.url.join(&format!("{}.ics", event.uid))?;
+        let response = self
+            .request(Method::PUT, url)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("If-None-Match", "*")
+            .body(ics::calendar(&ics::vevent(event)))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!("CalDAV server refused the event: {}", response.status());
+        }
+        Ok(())
+    }
+
+    /// Remove the event `uid`; None when the calendar has no such event
+    pub async fn delete(&self, uid: &str) -> Result<Option<Event>> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data/></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR"><C:comp-filter name="VEVENT">
+    <C:prop-filter name="UID"><C:text-match collation="i;octet">{}</C:text-match></C:prop-filter>
+  </C:comp-filter></C:comp-filter></C:filter>
+</C:calendar-query>"#,
+            xml_escape(uid)
+        );
+        let found = self
+            .query(&body)
+            .await?
+            .into_iter()
+            .find_map(|(href, events)| {
+                let event = events.into_iter().find(|e| e.uid == uid)?;
+                Some((href, event))
+            });
+        let Some((href, event)) = found else {
+            return Ok(None);
+        };
+        let response = self
+            .request(Method::DELETE, self.url.join(&href)?)
+            .send()
+            .await?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            anyhow::bail!("CalDAV server refused to delete: {}", response.status());
+        }
+        Ok(Some(event))
+    }
+
+    /// Run a calendar-query REPORT; the href and events of each resource
+    async fn query(&self, body: &str) -> Result<Vec<(String, Vec<Event>)>> {
+        let response = self
+            .request(Method::from_bytes(b"REPORT")?, self.url.clone())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body.to_string())
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("CalDAV query failed: {}", status);
+        }
+        Ok(parse_multistatus(&response.text().await?))
+    }
+}
+
+fn utc(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn parse_multistatus(xml: &str) -> Vec<(String, Vec<Event>)> {
+    RESPONSE
+        .captures_iter(xml)
+        .filter_map(|response| {
+            let href = xml_unescape(HREF.captures(&response[1])?[1].trim());
+            let data = CALENDAR_DATA.captures(&response[1])?;
+            Some((href, ics::parse_events(&xml_unescape(&data[1]))))
+        })
+        .collect()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    let trimmed = text.trim();
+    if let Some(inner) = trimmed
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+    {
+        return inner.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
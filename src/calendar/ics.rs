@@ -0,0 +1,540 @@
+//! Reading and writing iCalendar (RFC 5545) events
+//!
+//! Only VEVENTs are read. TZID times are taken as local time, and
+//! recurrences are expanded for simple RRULEs (FREQ, INTERVAL, COUNT,
+//! UNTIL); BYDAY and the other BY* parts are ignored.
+
+use chrono::{DateTime, Days, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Occurrences of one recurring event looked at before giving up
+const MAX_OCCURRENCES: usize = 5000;
+
+/// When an event starts or ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventTime {
+    /// An all-day date
+    Date(NaiveDate),
+    DateTime(DateTime<Local>),
+}
+
+impl EventTime {
+    /// The moment this time begins (midnight for a date)
+    pub fn instant(&self) -> DateTime<Local> {
+        match self {
+            Self::Date(date) => local(date.and_hms_opt(0, 0, 0).expect("midnight")),
+            Self::DateTime(dt) => *dt,
+        }
+    }
+
+    /// For tool output: `2026-10-17` or `2026-10-17 09:30`
+    pub fn display(&self) -> String {
+        match self {
+            Self::Date(date) => date.format("%Y-%m-%d").to_string(),
+            Self::DateTime(dt) => dt.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+
+    fn to_property(self, name: &str) -> String {
+        match self {
+            Self::Date(date) => format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d")),
+            Self::DateTime(dt) => format!(
+                "{}:{}",
+                name,
+                dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ")
+            ),
+        }
+    }
+
+    /// This time moved by `months` and `days`, keeping the wall-clock time
+    fn shifted(self, months: u32, days: u64) -> Option<Self> {
+        match self {
+            Self::Date(date) => Some(Self::Date(
+                date.checked_add_months(Months::new(months))?
+                    .checked_add_days(Days::new(days))?,
+            )),
+            Self::DateTime(dt) => {
+                let naive = dt
+                    .naive_local()
+                    .checked_add_months(Months::new(months))?
+                    .checked_add_days(Days::new(days))?;
+                Some(Self::DateTime(local(naive)))
+            }
+        }
+    }
+}
+
+/// A naive local time; in a DST gap, the first valid time after it
+fn local(naive: NaiveDateTime) -> DateTime<Local> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&naive))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub start: EventTime,
+    pub end: Option<EventTime>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+}
+
+impl Event {
+    /// When the event ends: DTEND, or a day after an all-day start, or the
+    /// start itself
+    pub fn end_instant(&self) -> DateTime<Local> {
+        match (self.end, self.start) {
+            (Some(end), _) => end.instant(),
+            (None, EventTime::Date(date)) => EventTime::Date(date + Days::new(1)).instant(),
+            (None, start) => start.instant(),
+        }
+    }
+
+    /// Whether any part of the event falls in `[from, to)`
+    fn overlaps(&self, from: DateTime<Local>, to: DateTime<Local>) -> bool {
+        let start = self.start.instant();
+        let end = self.end_instant();
+        start < to && (end > from || (end == start && start >= from))
+    }
+
+    /// The event's occurrences that overlap `[from, to)`
+    pub fn occurrences(&self, from: DateTime<Local>, to: DateTime<Local>) -> Vec<Event> {
+        let Some(rule) = self.rrule.as_deref().and_then(Recurrence::parse) else {
+            return if self.overlaps(from, to) {
+                vec![self.clone()]
+            } else {
+                Vec::new()
+            };
+        };
+
+        let mut out = Vec::new();
+        for n in 0..MAX_OCCURRENCES.min(rule.count.unwrap_or(usize::MAX)) {
+            let (months, days) = rule.step(n as u32);
+            let (Some(start), end) = (
+                self.start.shifted(months, days),
+                self.end.map(|e| e.shifted(months, days)),
+            ) else {
+                break;
+            };
+            if start.instant() >= to || rule.until.is_some_and(|u| start.instant() > u) {
+                break;
+            }
+            let occurrence = Event {
+                start,
+                end: end.flatten(),
+                ..self.clone()
+            };
+            if occurrence.overlaps(from, to) {
+                out.push(occurrence);
+            }
+        }
+        out
+    }
+}
+
+/// A simple RRULE
+struct Recurrence {
+    freq: Freq,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Local>>,
+}
+
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Recurrence {
+    fn parse(rule: &str) -> Option<Self> {
+        let mut freq = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+        for part in rule.split(';') {
+            let (key, value) = part.split_once('=')?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        "MONTHLY" => Freq::Monthly,
+                        "YEARLY" => Freq::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => interval = value.parse().ok().filter(|n| *n > 0)?,
+                "COUNT" => count = value.parse().ok(),
+                "UNTIL" => until = parse_time(&[], value).map(|t| t.instant()),
+                _ => {}
+            }
+        }
+        Some(Self {
+            freq: freq?,
+            interval,
+            count,
+            until,
+        })
+    }
+
+    /// Months and days from the first occurrence to the `n`th
+    fn step(&self, n: u32) -> (u32, u64) {
+        let k = n * self.interval;
+        match self.freq {
+            Freq::Daily => (0, k as u64),
+            Freq::Weekly => (0, 7 * k as u64),
+            Freq::Monthly => (k, 0),
+            Freq::Yearly => (12 * k, 0),
+        }
+    }
+}
+
+/// Content lines with folding undone
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// One content line, split up
+struct Property {
+    /// Uppercased
+    name: String,
+    /// (uppercased key, unquoted value) pairs
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+/// Name, parameters and value of a content line
+fn property(line: &str) -> Option<Property> {
+    // The value starts at the first ':' outside a quoted parameter
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let mut head = line[..colon].split(';');
+    let name = head.next()?.to_ascii_uppercase();
+    let params = head
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+fn parse_time(params: &[(String, String)], value: &str) -> Option<EventTime> {
+    let is_date = params
+        .iter()
+        .any(|(k, v)| k == "VALUE" && v.eq_ignore_ascii_case("DATE"))
+        || value.len() == 8;
+    if is_date {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(EventTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(EventTime::DateTime(
+            Utc.from_utc_datetime(&naive).with_timezone(&Local),
+        ));
+    }
+    // Floating or TZID times: read as local time
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(EventTime::DateTime(local(naive)))
+}
+
+/// An ISO 8601 duration like `PT1H30M` or `P1D`, as (days, seconds)
+fn parse_duration(value: &str) -> Option<(u64, u64)> {
+    let value = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+    let (mut days, mut seconds) = (0u64, 0u64);
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            _ => {
+                let n: u64 = number.parse().ok()?;
+                number.clear();
+                match (c, in_time) {
+                    ('W', false) => days += 7 * n,
+                    ('D', false) => days += n,
+                    ('H', true) => seconds += 3600 * n,
+                    ('M', true) => seconds += 60 * n,
+                    ('S', true) => seconds += n,
+                    _ => return None,
+                }
+            }
+        }
+    }
+    Some((days, seconds))
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// The VEVENTs in an iCalendar document
+pub fn parse_events(ics: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut current: Option<Vec<String>> = None;
+    // Nested components (VALARM) inside an event are skipped
+    let mut nested = 0;
+    for line in unfold(ics) {
+        let upper = line.to_ascii_uppercase();
+        match (upper.as_str(), current.as_mut()) {
+            ("BEGIN:VEVENT", None) => current = Some(Vec::new()),
+            ("END:VEVENT", Some(_)) if nested == 0 => {
+                if let Some(event) = current.take().and_then(|lines| event_from(&lines)) {
+                    events.push(event);
+                }
+            }
+            (u, Some(_)) if u.starts_with("BEGIN:") => nested += 1,
+            (u, Some(_)) if u.starts_with("END:") => nested -= 1,
+            (_, Some(lines)) if nested == 0 => lines.push(line),
+            _ => {}
+        }
+    }
+    events
+}
+
+fn event_from(lines: &[String]) -> Option<Event> {
+    let mut uid = None;
+    let mut summary = String::new();
+    let mut start = None;
+    let mut end = None;
+    let mut duration = None;
+    let mut location = None;
+    let mut description = None;
+    let mut rrule = None;
+    for line in lines {
+        let Some(Property {
+            name,
+            params,
+            value,
+        }) = property(line)
+        else {
+            continue;
+        };
+        match name.as_str() {
+            "UID" => uid = Some(value),
+            "SUMMARY" => summary = unescape(&value),
+            "DTSTART" => start = parse_time(&params, &value),
+            "DTEND" => end = parse_time(&params, &value),
+            "DURATION" => duration = parse_duration(&value),
+            "LOCATION" => location = Some(unescape(&value)).filter(|s| !s.is_empty()),
+            "DESCRIPTION" => description = Some(unescape(&value)).filter(|s| !s.is_empty()),
+            "RRULE" => rrule = Some(value),
+            _ => {}
+        }
+    }
+    let start = start?;
+    let end = end.or_else(|| {
+        let (days, seconds) = duration?;
+        Some(match start {
+            EventTime::Date(date) => EventTime::Date(date + Days::new(days)),
+            EventTime::DateTime(dt) => EventTime::DateTime(
+                dt + chrono::Duration::days(days as i64)
+                    + chrono::Duration::seconds(seconds as i64),
+            ),
+        })
+    });
+    Some(Event {
+        uid: uid.unwrap_or_default(),
+        summary,
+        start,
+        end,
+        location,
+        description,
+        rrule,
+    })
+}
+
+/// A content line folded at 75 octets, with CRLF
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// The event as a VEVENT component
+pub fn vevent(event: &Event) -> String {
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", event.uid),
+        format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+        event.start.to_property("DTSTART"),
+    ];
+    if let Some(end) = event.end {
+        lines.push(end.to_property("DTEND"));
+    }
+    lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+    if let Some(ref location) = event.location {
+        lines.push(format!("LOCATION:{}", escape(location)));
+    }
+    if let Some(ref description) = event.description {
+        lines.push(format!("DESCRIPTION:{}", escape(description)));
+    }
+    if let Some(ref rrule) = event.rrule {
+        lines.push(format!("RRULE:{}", rrule));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+/// A calendar holding these components
+pub fn calendar(components: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//LocalGPT//Calendar//EN\r\n{}END:VCALENDAR\r\n",
+        components
+    )
+}
+
+/// The document with `event` added before its END:VCALENDAR
+pub fn insert_event(ics: &str, event: &Event) -> String {
+    match ics.to_ascii_uppercase().rfind("END:VCALENDAR") {
+        Some(end) => format!("{}{}{}", &ics[..end], vevent(event), &ics[end..]),
+        None => calendar(&vevent(event)),
+    }
+}
+
+/// The document without the event `uid`, and that event; None when the
+/// document has no such event. Everything else is left as it was.
+pub fn remove_event(ics: &str, uid: &str) -> Option<(String, Event)> {
+    let mut out = String::with_capacity(ics.len());
+    let mut block: Option<String> = None;
+    let mut removed = None;
+    for line in ics.split_inclusive('\n') {
+        let trimmed = line.trim_end().to_ascii_uppercase();
+        if let Some(ref mut text) = block {
+            text.push_str(line);
+            if trimmed == "END:VEVENT" {
+                let text = block.take().unwrap_or_default();
+                match parse_events(&text).into_iter().next() {
+                    Some(event) if event.uid == uid && removed.is_none() => removed = Some(event),
+                    _ => out.push_str(&text),
+                }
+            }
+        } else if trimmed == "BEGIN:VEVENT" {
+            block = Some(line.to_string());
+        } else {
+            out.push_str(line);
+        }
+    }
+    if let Some(text) = block {
+        out.push_str(&text);
+    }
+    removed.map(|event| (out, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_round_trip_and_recur() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:standup\r\n\
+DTSTART;TZID=Europe/Paris:20261012T093000\r\n\
+DURATION:PT15M\r\n\
+SUMMARY:Standup\\, team\r\n\
+RRULE:FREQ=WEEKLY;COUNT=4\r\n\
+BEGIN:VALARM\r\n\
+TRIGGER:-PT5M\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:trip\r\n\
+DTSTART;VALUE=DATE:20261020\r\n\
+DTEND;VALUE=DATE:20261022\r\n\
+SUMMARY:Trip to a place with a very long name that needs folding over\r\n\
+\x20two lines\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+        let events = parse_events(ics);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].summary, "Standup, team");
+        assert_eq!(
+            events[0].end_instant() - events[0].start.instant(),
+            chrono::Duration::minutes(15)
+        );
+        assert!(events[1].summary.ends_with("folding overtwo lines"));
+
+        // Weekly on the 12th, 19th, 26th and 2nd; the window holds two
+        let from = EventTime::Date(NaiveDate::from_ymd_opt(2026, 10, 15).unwrap()).instant();
+        let to = EventTime::Date(NaiveDate::from_ymd_opt(2026, 10, 30).unwrap()).instant();
+        let starts: Vec<String> = events[0]
+            .occurrences(from, to)
+            .iter()
+            .map(|e| e.start.display())
+            .collect();
+        assert_eq!(starts, vec!["2026-10-19 09:30", "2026-10-26 09:30"]);
+        assert_eq!(events[1].occurrences(from, to).len(), 1);
+
+        // Adding and removing leave the rest of the file as it was
+        let added = insert_event(
+            ics,
+            &Event {
+                uid: "new".into(),
+                ..events[1].clone()
+            },
+        );
+        assert_eq!(parse_events(&added).len(), 3);
+        let (removed, event) = remove_event(&added, "new").unwrap();
+        assert_eq!(removed, ics);
+        assert_eq!(event.start, events[1].start);
+        assert!(remove_event(ics, "missing").is_none());
+    }
+}
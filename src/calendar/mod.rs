@@ -0,0 +1,150 @@
+//! Calendar events and reminders (`[calendar]`)
+//!
+//! Events live in a local iCalendar file or on a CalDAV server; the agent
+//! reaches them through the `calendar` tool. Reminders are kept in
+//! `~/.localgpt/reminders.json` and fired by `ReminderScheduler`, which the
+//! daemon and the desktop app run.
+
+mod caldav;
+mod ics;
+mod reminders;
+
+pub use ics::{Event, EventTime};
+pub use reminders::{Delivery, Reminder, ReminderScheduler, ReminderStore};
+
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::agent::get_state_dir;
+use crate::config::{expand_env, Config};
+use caldav::CalDav;
+
+enum Backend {
+    Local(PathBuf),
+    CalDav(CalDav),
+}
+
+/// The configured calendar
+pub struct Calendar {
+    backend: Backend,
+}
+
+impl Calendar {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let calendar = &config.calendar;
+        let backend = match calendar.caldav_url {
+            Some(ref url) => {
+                let password = calendar
+                    .password
+                    .as_deref()
+                    .map(expand_env)
+                    .transpose()
+                    .map_err(|var| {
+                        anyhow::anyhow!("calendar.password uses ${{{}}}, which is not set", var)
+                    })?;
+                Backend::CalDav(CalDav::new(url, calendar.username.clone(), password)?)
+            }
+            None => Backend::Local(match calendar.ics_path {
+                Some(ref path) => PathBuf::from(shellexpand::tilde(path).as_ref()),
+                None => get_state_dir()?.join("calendar.ics"),
+            }),
+        };
+        Ok(Self { backend })
+    }
+
+    /// Where the events are, for tool descriptions
+    pub fn describe(&self) -> String {
+        match self.backend {
+            Backend::Local(ref path) => format!("local file {}", path.display()),
+            Backend::CalDav(ref caldav) => format!("CalDAV {}", caldav.url()),
+        }
+    }
+
+    /// Event occurrences overlapping `[from, to)`, soonest first
+    pub async fn events(&self, from: DateTime<Local>, to: DateTime<Local>) -> Result<Vec<Event>> {
+        let mut events = match self.backend {
+            Backend::Local(ref path) => ics::parse_events(&read_local(path)?)
+                .iter()
+                .flat_map(|event| event.occurrences(from, to))
+                .collect(),
+            Backend::CalDav(ref caldav) => caldav.events(from, to).await?,
+        };
+        events.sort_by_key(|e| e.start.instant());
+        Ok(events)
+    }
+
+    pub async fn add(&self, event: &Event) -> Result<()> {
+        match self.backend {
+            Backend::Local(ref path) => {
+                write_local(path, &ics::insert_event(&read_local(path)?, event))
+            }
+            Backend::CalDav(ref caldav) => caldav.add(event).await,
+        }
+    }
+
+    /// Remove the event `uid`, returning it; errors when there is none
+    pub async fn delete(&self, uid: &str) -> Result<Event> {
+        let removed = match self.backend {
+            Backend::Local(ref path) => match ics::remove_event(&read_local(path)?, uid) {
+                Some((rest, event)) => {
+                    write_local(path, &rest)?;
+                    Some(event)
+                }
+                None => None,
+            },
+            Backend::CalDav(ref caldav) => caldav.delete(uid).await?,
+        };
+        removed.ok_or_else(|| anyhow::anyhow!("No event with uid '{}'", uid))
+    }
+}
+
+/// The local calendar file (empty when it doesn't exist yet)
+fn read_local(path: &PathBuf) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_local(path: &PathBuf, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("ics.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A time as the model writes it: `2026-10-17` (a date), `2026-10-17 14:30`,
+/// `2026-10-17T14:30` or RFC 3339 (local time unless an offset is given)
+pub fn parse_when(s: &str) -> Result<EventTime> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(EventTime::Date(date));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(EventTime::DateTime(dt.with_timezone(&Local)));
+    }
+    for format in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            let time = naive
+                .and_local_timezone(Local)
+                .earliest()
+                .ok_or_else(|| anyhow::anyhow!("{} does not exist in the local time zone", s))?;
+            return Ok(EventTime::DateTime(time));
+        }
+    }
+    anyhow::bail!(
+        "Could not read the time '{}'. Use YYYY-MM-DD or YYYY-MM-DD HH:MM",
+        s
+    )
+}
@@ -0,0 +1,233 @@
+//! Reminders kept in `~/.localgpt/reminders.json`, and the scheduler that
+//! fires them
+//!
+//! The daemon and the desktop app each run a scheduler. Taking due
+//! reminders out of the file happens under a file lock, so a reminder
+//! fires once even when both are running.
+
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::agent::{get_state_dir, send_notification, Urgency};
+
+/// Longest sleep between looks at the file, which other processes (the
+/// reminder tool in a CLI chat) may have changed
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reminders this late say when they were due
+const LATE_AFTER_MINUTES: i64 = 5;
+
+/// How a reminder reaches the user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    /// A desktop notification
+    #[default]
+    Notification,
+    /// A message in the desktop chat (a notification where there is none)
+    Chat,
+}
+
+impl Delivery {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "notification" => Some(Self::Notification),
+            "chat" => Some(Self::Chat),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    pub text: String,
+    pub due: DateTime<Utc>,
+    #[serde(default)]
+    pub via: Delivery,
+}
+
+impl Reminder {
+    /// The reminder as shown when it fires
+    pub fn message(&self, now: DateTime<Utc>) -> String {
+        if (now - self.due).num_minutes() >= LATE_AFTER_MINUTES {
+            format!(
+                "{} (was due {})",
+                self.text,
+                self.due.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            )
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+pub struct ReminderStore {
+    path: PathBuf,
+}
+
+impl ReminderStore {
+    pub fn new() -> Result<Self> {
+        Ok(Self::at(get_state_dir()?.join("reminders.json")))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Pending reminders, soonest first
+    pub fn list(&self) -> Result<Vec<Reminder>> {
+        self.update(|reminders| reminders.clone())
+    }
+
+    pub fn add(&self, text: &str, due: DateTime<Utc>, via: Delivery) -> Result<Reminder> {
+        let reminder = Reminder {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            text: text.to_string(),
+            due,
+            via,
+        };
+        self.update(|reminders| reminders.push(reminder.clone()))?;
+        Ok(reminder)
+    }
+
+    /// Remove a pending reminder; None when there is no such reminder
+    pub fn cancel(&self, id: &str) -> Result<Option<Reminder>> {
+        self.update(|reminders| {
+            let i = reminders.iter().position(|r| r.id == id)?;
+            Some(reminders.remove(i))
+        })
+    }
+
+    /// Take out the reminders due at `now`
+    pub fn take_due(&self, now: DateTime<Utc>) -> Result<Vec<Reminder>> {
+        self.update(|reminders| {
+            let (due, pending) = reminders.drain(..).partition(|r| r.due <= now);
+            *reminders = pending;
+            due
+        })
+    }
+
+    /// Read, change and write the file under its lock (sorted by due time)
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Reminder>) -> T) -> Result<T> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock = File::create(self.path.with_extension("lock"))?;
+        lock.lock_exclusive()?;
+
+        let mut reminders: Vec<Reminder> = match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let before = reminders.clone();
+        let result = change(&mut reminders);
+        reminders.sort_by_key(|r| r.due);
+        if reminders != before {
+            let tmp_path = self.path.with_extension("json.tmp");
+            fs::write(&tmp_path, serde_json::to_string_pretty(&reminders)?)?;
+            fs::rename(&tmp_path, &self.path)?;
+        }
+        let _ = lock.unlock();
+        Ok(result)
+    }
+}
+
+/// Fires due reminders
+pub struct ReminderScheduler {
+    store: ReminderStore,
+}
+
+impl ReminderScheduler {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            store: ReminderStore::new()?,
+        })
+    }
+
+    /// Fire reminders as they come due, until the task is dropped.
+    /// Chat reminders go to `chat`; without one they become notifications.
+    pub async fn run(&self, chat: Option<mpsc::UnboundedSender<Reminder>>) {
+        loop {
+            let now = Utc::now();
+            match self.store.take_due(now) {
+                Ok(due) => {
+                    for reminder in due {
+                        self.fire(reminder, now, chat.as_ref()).await;
+                    }
+                }
+                Err(e) => warn!("Failed to read reminders: {}", e),
+            }
+
+            let wait = self
+                .store
+                .list()
+                .ok()
+                .and_then(|r| r.first().map(|r| r.due))
+                .and_then(|due| (due - Utc::now()).to_std().ok())
+                .map_or(POLL_INTERVAL, |d| d.min(POLL_INTERVAL));
+            tokio::time::sleep(wait.max(Duration::from_millis(200))).await;
+        }
+    }
+
+    async fn fire(
+        &self,
+        reminder: Reminder,
+        now: DateTime<Utc>,
+        chat: Option<&mpsc::UnboundedSender<Reminder>>,
+    ) {
+        debug!("Firing reminder {}: {}", reminder.id, reminder.text);
+        let reminder = match (reminder.via, chat) {
+            (Delivery::Chat, Some(chat)) => match chat.send(reminder) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(reminder)) => reminder,
+            },
+            _ => reminder,
+        };
+        if let Err(e) = send_notification("Reminder", &reminder.message(now), Urgency::Normal).await
+        {
+            warn!("Reminder '{}' could not be shown: {}", reminder.text, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ReminderStore::at(dir.path().join("reminders.json"));
+        let now = Utc::now();
+        let later = store
+            .add("stretch", now + chrono::Duration::hours(1), Delivery::Chat)
+            .unwrap();
+        let soon = store
+            .add(
+                "tea",
+                now - chrono::Duration::minutes(10),
+                Delivery::Notification,
+            )
+            .unwrap();
+        let cancelled = store.add("x", now, Delivery::Notification).unwrap();
+
+        assert_eq!(store.list().unwrap()[0], soon);
+        assert_eq!(store.cancel(&cancelled.id).unwrap(), Some(cancelled));
+        assert_eq!(store.cancel("missing").unwrap(), None);
+
+        let due = store.take_due(now).unwrap();
+        assert_eq!(due, vec![soon]);
+        assert!(due[0].message(now).starts_with("tea (was due "));
+        assert_eq!(store.list().unwrap(), vec![later]);
+        assert!(store.take_due(now).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+//! Named agents and their settings (`agents/<id>/agent.toml`)
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use localgpt::agent::{ApprovalPolicies, ApprovalPolicy};
+use localgpt::config::{list_agents, AgentProfile, Config};
+
+#[derive(Args)]
+pub struct AgentsArgs {
+    #[command(subcommand)]
+    pub command: AgentsCommands,
+}
+
+#[derive(Subcommand)]
+pub enum AgentsCommands {
+    /// List agents
+    List,
+
+    /// Show an agent's settings
+    Show {
+        /// Agent ID
+        id: String,
+    },
+
+    /// Create an agent, or change settings of an existing one
+    Create {
+        /// Agent ID (letters, digits, '-' or '_')
+        id: String,
+
+        /// Shown in agent lists
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Model (default: agent.default_model)
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Workspace directory (default: the shared workspace)
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// Instructions added to the system prompt
+        #[arg(long)]
+        system_prompt: Option<String>,
+
+        /// Only these tools (repeat, or comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        tools: Vec<String>,
+    },
+
+    /// Set whether an agent's calls of a tool run without asking
+    Policy {
+        /// Agent ID
+        id: String,
+
+        /// Tool name
+        tool: String,
+
+        /// allow, ask, never, or default to remove the tool's policy
+        policy: String,
+    },
+}
+
+pub async fn run(args: AgentsArgs) -> Result<()> {
+    match args.command {
+        AgentsCommands::List => list(),
+        AgentsCommands::Show { id } => show(&id),
+        AgentsCommands::Create {
+            id,
+            description,
+            model,
+            workspace,
+            system_prompt,
+            tools,
+        } => {
+            let mut profile = AgentProfile::load(&id)?.unwrap_or_default();
+            if description.is_some() {
+                profile.description = description;
+            }
+            if model.is_some() {
+                profile.model = model;
+            }
+            if workspace.is_some() {
+                profile.workspace = workspace;
+            }
+            if system_prompt.is_some() {
+                profile.system_prompt = system_prompt;
+            }
+            if !tools.is_empty() {
+                profile.tools = tools;
+            }
+            profile.save(&id)?;
+            println!("Saved {}", AgentProfile::path(&id)?.display());
+            println!("Use it with: localgpt --agent {} chat", id);
+            Ok(())
+        }
+        AgentsCommands::Policy { id, tool, policy } => {
+            let policy = match policy.as_str() {
+                "default" => None,
+                other => Some(other.parse::<ApprovalPolicy>()?),
+            };
+            let mut policies = ApprovalPolicies::load(&id)?;
+            policies.set_tool(&tool, policy);
+            policies.save(&id)?;
+            match policy {
+                Some(policy) => println!("{}: {}", tool, policy.as_str()),
+                None => println!("{}: default", tool),
+            }
+            println!("Saved {}", ApprovalPolicies::path(&id)?.display());
+            Ok(())
+        }
+    }
+}
+
+fn list() -> Result<()> {
+    for id in list_agents()? {
+        let profile = AgentProfile::load(&id)?.unwrap_or_default();
+        match profile.description {
+            Some(description) => println!("{}  {}", id, description),
+            None => println!("{}", id),
+        }
+    }
+    Ok(())
+}
+
+fn show(id: &str) -> Result<()> {
+    let path = AgentProfile::path(id)?;
+    let profile = AgentProfile::load(id)?;
+    let config = Config::load_for_agent(id)?;
+
+    println!("Agent: {}", id);
+    match profile {
+        Some(ref profile) => {
+            println!("Settings: {}", path.display());
+            if let Some(ref description) = profile.description {
+                println!("Description: {}", description);
+            }
+        }
+        None => println!("Settings: none (uses config.toml)"),
+    }
+    println!("Model: {}", config.agent.default_model);
+    println!("Workspace: {}", config.workspace_path().display());
+    if !config.tools.allowed.is_empty() {
+        println!("Tools: {}", config.tools.allowed.join(", "));
+    }
+    let policies = ApprovalPolicies::load(id)?;
+    if !policies.is_empty() {
+        println!(
+            "Approval policies: {}",
+            ApprovalPolicies::path(id)?.display()
+        );
+        for (tool, policy) in &policies.tools {
+            println!("  {}: {}", tool, policy.as_str());
+        }
+        if !policies.rules.is_empty() {
+            println!("  plus {} argument rules", policies.rules.len());
+        }
+    }
+    if let Some(ref prompt) = config.agent.system_prompt {
+        println!("System prompt:\n{}", prompt);
+    }
+    Ok(())
+}
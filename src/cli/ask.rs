@@ -21,12 +21,12 @@ pub struct AskArgs {
 }
 
 pub async fn run(args: AskArgs, agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
     let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
 
     let agent_config = AgentConfig {
         model: args.model.unwrap_or(config.agent.default_model.clone()),
-        context_window: config.agent.context_window,
+        context_window: config.agent.context_window_or_default(),
         reserve_tokens: config.agent.reserve_tokens,
     };
 
@@ -42,7 +42,7 @@ pub async fn run(args: AskArgs, agent_id: &str) -> Result<()> {
             let output = serde_json::json!({
                 "question": args.question,
                 "response": response,
-                "model": agent.model(),
+                "model": agent.active_model(),
             });
             println!("{}", serde_json::to_string_pretty(&output)?);
         }
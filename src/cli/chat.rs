@@ -6,13 +6,16 @@ use rustyline::DefaultEditor;
 use std::io::{self, Write};
 
 use localgpt::agent::{
-    extract_tool_detail, get_last_session_id_for_agent, get_skills_summary,
-    list_sessions_for_agent, load_skills, parse_skill_command, search_sessions_for_agent, Agent,
-    AgentConfig, ImageAttachment, Skill,
+    clear_response_cache, default_share_path, extract_tool_detail, get_last_session_id_for_agent,
+    get_skills_summary, list_sessions_for_agent, load_skills, parse_skill_command, paste_message,
+    prepare_local_model, read_clipboard, response_cache_len, search_sessions_for_agent,
+    write_clipboard, Agent, AgentConfig, BudgetExceededError, ImageAttachment, Ocr, Plan, PlanStep,
+    ProviderAuthError, ProviderTimeoutError, ProviderToolEvent, Rating, ShareOptions, Skill,
+    StepStatus, MAX_PLAN_STEPS,
 };
 use localgpt::concurrency::WorkspaceLock;
-use localgpt::config::Config;
-use localgpt::memory::MemoryManager;
+use localgpt::config::{compaction_strategy_names, CompactionStrategy, Config};
+use localgpt::memory::{document_text, needs_extraction, MemoryManager};
 
 /// Adjust a byte index to the nearest valid UTF-8 char boundary (searching forward).
 fn floor_char_boundary(s: &str, index: usize) -> usize {
@@ -115,19 +118,35 @@ pub struct ChatArgs {
 }
 
 pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
     // Embedding provider is automatically created based on config.memory.embedding_provider
     let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
 
     let agent_config = AgentConfig {
         model: args.model.unwrap_or(config.agent.default_model.clone()),
-        context_window: config.agent.context_window,
+        context_window: config.agent.context_window_or_default(),
         reserve_tokens: config.agent.reserve_tokens,
     };
 
     let mut agent = Agent::new(agent_config, &config, memory).await?;
+    agent.enable_turn_checkpoints();
     let workspace_lock = WorkspaceLock::new()?;
 
+    // Load a local model while the user types the first message
+    let local_config = config.clone();
+    let local_model = agent.model().to_string();
+    tokio::spawn(async move {
+        prepare_local_model(&local_config, &local_model, |status| {
+            tracing::debug!(
+                "Local model {} on {}: {:?}",
+                status.model,
+                status.host,
+                status.state
+            );
+        })
+        .await;
+    });
+
     // Determine session to use
     let session_id = if let Some(id) = args.session {
         Some(id)
@@ -196,7 +215,19 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
     }
     let mut pending_attachments: Vec<Attachment> = Vec::new();
 
+    // OCR makes scanned PDFs and screenshots usable as text (and searchable in memory)
+    let ocr = Ocr::from_config(&config)?;
+
     loop {
+        // A background compaction is swapped in as soon as it is ready
+        match agent.poll_compaction() {
+            Some(Ok((before, after))) => {
+                println!("Session compacted. Token count: {} → {}\n", before, after)
+            }
+            Some(Err(e)) => eprintln!("Compaction failed: {}\n", e),
+            None => {}
+        }
+
         let readline = rl.readline("You: ");
 
         let input = match readline {
@@ -272,10 +303,60 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
                                 data: ImageAttachment { data, media_type },
                             });
                             println!("Attached image: {} ({} bytes)", filename, size);
-                            println!("Type your message to send with attachment(s), or /attachments to list.\n");
                         }
                         Err(e) => {
                             eprintln!("Failed to read image file: {}", e);
+                            continue;
+                        }
+                    }
+                }
+
+                // Add OCR text alongside images so non-vision models can read them too
+                let is_pdf = ext.as_deref() == Some("pdf");
+                if let Some(ocr) = ocr.as_ref().filter(|_| is_image || is_pdf) {
+                    match ocr.extract_and_save(path).await {
+                        Ok((text, saved)) if !text.trim().is_empty() => {
+                            println!(
+                                "Extracted {} characters of text from {}",
+                                text.len(),
+                                filename
+                            );
+                            if let Some(saved) = saved {
+                                println!("Saved to {} for memory search", saved.display());
+                            }
+                            pending_attachments.push(Attachment::Text {
+                                name: format!("{} (OCR)", filename),
+                                content: text,
+                            });
+                        }
+                        Ok(_) => println!("No text found in {}", filename),
+                        Err(e) => eprintln!("OCR failed for {}: {}", filename, e),
+                    }
+                    println!(
+                        "Type your message to send with attachment(s), or /attachments to list.\n"
+                    );
+                    continue;
+                } else if is_image {
+                    println!(
+                        "Type your message to send with attachment(s), or /attachments to list.\n"
+                    );
+                } else if needs_extraction(path) {
+                    // PDFs and Word documents, with page markers for citation
+                    match document_text(path).await {
+                        Ok(content) => {
+                            println!(
+                                "Attached: {} ({} characters of text)",
+                                filename,
+                                content.len()
+                            );
+                            pending_attachments.push(Attachment::Text {
+                                name: filename.clone(),
+                                content,
+                            });
+                            println!("Type your message to send with attachment(s), or /attachments to list.\n");
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to read {}: {}", filename, e);
                         }
                     }
                 } else {
@@ -354,6 +435,14 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
                     }
                     continue;
                 }
+                CommandResult::Plan(task) => {
+                    let _lock_guard = workspace_lock.acquire()?;
+                    if let Err(e) = run_plan(&mut agent, &task).await {
+                        eprintln!("Error: {}\n", e);
+                        print_error_hint(&e);
+                    }
+                    continue;
+                }
                 CommandResult::Error(e) => {
                     eprintln!("Error: {}", e);
                     continue;
@@ -361,6 +450,10 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
             }
         }
 
+        if !confirm_budget(&mut agent, input)? {
+            continue;
+        }
+
         // Build message with attachments
         let mut message = input.to_string();
         let mut images: Vec<ImageAttachment> = Vec::new();
@@ -402,6 +495,22 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
                 while let Some(result) = stream.next().await {
                     match result {
                         Ok(chunk) => {
+                            // Tools the provider runs itself (Claude CLI)
+                            for event in &chunk.provider_tools {
+                                match event {
+                                    ProviderToolEvent::Started { name, detail, .. } => match detail
+                                    {
+                                        Some(d) => print!("\n[{}: {}]", name, d),
+                                        None => print!("\n[{}]", name),
+                                    },
+                                    ProviderToolEvent::Finished { is_error, .. } => {
+                                        println!(
+                                            " [{}]",
+                                            if *is_error { "failed" } else { "done" }
+                                        );
+                                    }
+                                }
+                            }
                             print!("{}", chunk.delta);
                             stdout.flush()?;
                             full_response.push_str(&chunk.delta);
@@ -413,6 +522,7 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
                         }
                         Err(e) => {
                             eprintln!("\nStream error: {}", e);
+                            print_error_hint(&e);
                             break;
                         }
                     }
@@ -432,8 +542,13 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
                             println!("\n[{}]", tc.name);
                         }
 
-                        if agent.requires_approval(&tc.name) {
-                            // Prompt for approval
+                        if agent.requires_approval(&tc) {
+                            // Show what a file change would do, then prompt
+                            match agent.tool_preview(&tc) {
+                                Some(diff) if diff.is_empty() => println!("(no changes)"),
+                                Some(diff) => print!("{}", diff),
+                                None => {}
+                            }
                             print!("Execute {}? [y/N]: ", tc.name);
                             stdout.flush()?;
 
@@ -483,21 +598,272 @@ pub async fn run(args: ChatArgs, agent_id: &str) -> Result<()> {
             }
             Err(e) => {
                 eprintln!("Error: {}\n", e);
+                print_error_hint(&e);
             }
         }
     }
 
+    remember_session_facts(&agent).await;
     println!("Goodbye!");
     Ok(())
 }
 
+/// Add what the session taught about the user to USER.md, when
+/// `memory.extract_facts` is on
+async fn remember_session_facts(agent: &Agent) {
+    match agent.extract_session_facts().await {
+        Ok(facts) if !facts.is_empty() => {
+            println!("\nRemembered in USER.md:");
+            for fact in facts {
+                println!("  - {}", fact);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to extract facts from session: {}", e),
+    }
+}
+
+/// `/plan`: have the model break the task into steps, let the user review
+/// them, then run one turn per step
+async fn run_plan(agent: &mut Agent, task: &str) -> Result<()> {
+    println!("\nPlanning...");
+    let mut plan = agent.make_plan(task).await?;
+    if !review_plan(&mut plan)? {
+        println!("Plan cancelled.\n");
+        return Ok(());
+    }
+
+    let total = plan.steps.len();
+    while let Some(index) = plan.next_pending() {
+        plan.steps[index].status = StepStatus::Running;
+        println!(
+            "\n[Step {}/{}] {}",
+            index + 1,
+            total,
+            plan.steps[index].description
+        );
+        print!("\nLocalGPT: ");
+        io::stdout().flush()?;
+        match agent.chat(&plan.step_message(index)).await {
+            Ok(reply) => {
+                println!("{}", reply);
+                plan.steps[index].status = StepStatus::Done;
+            }
+            Err(e) => {
+                eprintln!("\nError: {}", e);
+                print_error_hint(&e);
+                plan.steps[index].status = StepStatus::Failed;
+                plan.skip_remaining();
+            }
+        }
+        if let Err(e) = agent.auto_save_session() {
+            eprintln!("Warning: Failed to auto-save session: {}", e);
+        }
+    }
+
+    println!(
+        "\nPlan finished: {} of {} steps done\n{}\n",
+        plan.done_count(),
+        total,
+        plan.checklist()
+    );
+    Ok(())
+}
+
+/// Show the plan and apply edits until the user runs or cancels it.
+/// Returns whether to run it.
+fn review_plan(plan: &mut Plan) -> Result<bool> {
+    println!("\nPlan for: {}", plan.task);
+    loop {
+        println!("\n{}\n", plan.checklist());
+        print!("[r]un, [e]dit N <text>, [d]elete N, [a]dd [N] <text>, [c]ancel: ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim();
+        let (choice, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let rest = rest.trim();
+        // "N text" -> (N as an index, text)
+        let len = plan.steps.len();
+        let numbered = |rest: &str| -> Option<(usize, String)> {
+            let (n, text) = rest.split_once(' ').unwrap_or((rest, ""));
+            let n: usize = n.parse().ok()?;
+            (1..=len + 1)
+                .contains(&n)
+                .then(|| (n - 1, text.trim().to_string()))
+        };
+
+        match choice.to_lowercase().as_str() {
+            "r" | "run" | "y" | "yes" => return Ok(true),
+            "c" | "cancel" | "n" | "no" => return Ok(false),
+            "e" | "edit" => match numbered(rest) {
+                Some((i, text)) if i < plan.steps.len() && !text.is_empty() => {
+                    plan.steps[i].description = text
+                }
+                _ => println!("Usage: e <step number> <new text>"),
+            },
+            "d" | "delete" => match numbered(rest) {
+                Some((i, _)) if i < plan.steps.len() => {
+                    plan.steps.remove(i);
+                }
+                _ => println!("Usage: d <step number>"),
+            },
+            "a" | "add" => {
+                let (index, text) = numbered(rest)
+                    .filter(|(_, text)| !text.is_empty())
+                    .unwrap_or((plan.steps.len(), rest.to_string()));
+                if text.is_empty() || plan.steps.len() >= MAX_PLAN_STEPS {
+                    println!(
+                        "Usage: a [position] <text> (at most {} steps)",
+                        MAX_PLAN_STEPS
+                    );
+                } else {
+                    plan.steps.insert(
+                        index,
+                        PlanStep {
+                            description: text,
+                            status: StepStatus::Pending,
+                        },
+                    );
+                }
+            }
+            _ => println!("Choose r, e, d, a or c"),
+        }
+        if plan.steps.is_empty() {
+            println!("No steps left.");
+            return Ok(false);
+        }
+    }
+}
+
+/// Check the budget of the provider `message` would go to. If it ran out,
+/// ask whether to send anyway. Returns whether to send the message.
+fn confirm_budget(agent: &mut Agent, message: &str) -> Result<bool> {
+    let result = agent.check_budget(message);
+    if let Some(warning) = agent.take_budget_warning() {
+        println!("\nWarning: {}", warning);
+    }
+    let Err(e) = result else {
+        return Ok(true);
+    };
+    let Some(budget) = e.downcast_ref::<BudgetExceededError>() else {
+        return Err(e);
+    };
+    print!("\n{}. Send anyway? [y/N]: ", budget);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        agent.override_budget(&budget.provider);
+        Ok(true)
+    } else {
+        println!("Not sent. Limits are under [budget] in config.toml.\n");
+        Ok(false)
+    }
+}
+
+/// First line of a pinned message, shortened for listing
+fn pin_preview(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    let end = floor_char_boundary(line, 80);
+    if end < line.len() || line.len() < content.trim_end().len() {
+        format!("{}...", &line[..end])
+    } else {
+        line.to_string()
+    }
+}
+
+/// Explain how to replace a rejected API key or raise a timeout
+fn print_error_hint(error: &anyhow::Error) {
+    if let Some(timeout) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ProviderTimeoutError>())
+    {
+        let key = if timeout.connect {
+            "connect_timeout_secs"
+        } else {
+            "timeout_secs"
+        };
+        let section = match timeout.provider.as_str() {
+            "openai" | "anthropic" | "gemini" | "deepseek" | "ollama" => {
+                format!("[providers.{}]", timeout.provider)
+            }
+            name => format!("the [[providers.custom]] entry \"{}\"", name),
+        };
+        eprintln!(
+            "Check that the server is up, or raise {} under {}.\n",
+            key, section
+        );
+    }
+    if let Some(auth) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ProviderAuthError>())
+    {
+        eprintln!(
+            "Update api_key under [providers.{}] in {}, or enter a new key in the \
+            desktop app to save it to the OS keyring.\n",
+            auth.provider,
+            Config::config_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|_| "config.toml".to_string())
+        );
+    }
+}
+
 enum CommandResult {
     Continue,
     Quit,
     SendMessage(String),
+    /// Plan the task, then run the reviewed steps
+    Plan(String),
     Error(String),
 }
 
+/// List the models configured providers offer and read a choice.
+/// Returns None to keep the current model.
+async fn pick_model(agent: &Agent) -> Result<Option<String>> {
+    println!("\nCurrent model: {}", agent.model());
+    println!("Fetching available models...");
+    let listings = agent.available_models().await;
+
+    let mut choices = Vec::new();
+    for listing in &listings {
+        println!("\n{}:", listing.provider);
+        if let Some(ref error) = listing.error {
+            println!("  (unavailable: {})", error);
+        } else if listing.models.is_empty() {
+            println!("  (no models listed)");
+        }
+        for model in &listing.models {
+            choices.push(model.clone());
+            let current = if *model == agent.model() { " *" } else { "" };
+            println!("  {:>3}) {}{}", choices.len(), model, current);
+        }
+    }
+    if choices.is_empty() {
+        println!("\nNo models found. Use /model <name> to switch.\n");
+        return Ok(None);
+    }
+
+    print!("\nModel number or name [keep current]: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    if input.is_empty() {
+        println!();
+        return Ok(None);
+    }
+    match input.parse::<usize>() {
+        Ok(n) => choices
+            .get(n.wrapping_sub(1))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("No model numbered {}", n)),
+        Err(_) => Ok(Some(input.to_string())),
+    }
+}
+
 async fn handle_command(
     input: &str,
     agent: &mut Agent,
@@ -519,18 +885,53 @@ async fn handle_command(
             println!("  /sessions         - List available sessions");
             println!("  /search <query>   - Search across all sessions");
             println!("  /resume <id>      - Resume a specific session");
-            println!("  /model [name]     - Show or switch model (e.g., /model gpt-4o)");
+            println!(
+                "  /model [name]     - Switch model (e.g., /model gpt-4o), or pick from available models"
+            );
+            println!(
+                "  /ask <model> <msg> - Send one message to another model (same as @model msg)"
+            );
             println!("  /models           - List available model prefixes");
             println!("  /context          - Show context window usage");
+            println!("  /context view     - Show the exact messages the next turn will send");
+            println!(
+                "  /set key=value    - Set temperature or max_tokens for this session (value \"default\" resets)"
+            );
+            println!("  /system [text]    - Show or set extra system instructions (/system clear)");
+            println!("  /lang [language]  - Show or set the reply language (/lang off)");
             println!("  /export [file]    - Export session as markdown");
+            println!(
+                "  /share [file] [--no-tools] - Export a redacted, self-contained HTML copy to share"
+            );
             println!("  /attach <file>    - Attach file to next message");
             println!("  /attachments      - List pending attachments");
-            println!("  /compact          - Compact session history");
+            println!(
+                "  /plan <task>      - Plan the task as steps to review and edit, then run them"
+            );
+            println!("  /compact          - Compact session history in the background");
+            println!(
+                "  /compact strategy [name|default] - Show or set how this session is compacted"
+            );
             println!("  /clear            - Clear session history (keeps context)");
+            println!("  /undo             - Revert file changes from the agent's last turn");
+            println!(
+                "  /rate up|down [note] - Rate the last reply (/rate clear removes the rating)"
+            );
+            println!("  /pin [text]       - Keep the last reply (or a message with this text) through compaction");
+            println!("  /unpin [text]     - Unpin it again; /pins lists pinned messages");
+            println!(
+                "  /paste [instruction] - Send the clipboard text, optionally with an instruction"
+            );
+            println!("  /copy             - Copy the last reply to the clipboard");
             println!("  /memory <query>   - Search memory");
             println!("  /reindex          - Rebuild memory index");
+            println!("  /cache [clear]    - Show or clear cached replies ([providers.cache])");
+            println!("  /doctor           - Check each provider's connection, key and latency");
             println!("  /save             - Save current session");
             println!("  /status           - Show session status and API token usage");
+            println!(
+                "  /stats            - Show message, tool call, and per-turn token statistics"
+            );
 
             // Show skill commands if any
             let invocable: Vec<&Skill> = skills.iter().filter(|s| s.can_invoke()).collect();
@@ -662,12 +1063,15 @@ async fn handle_command(
         }
 
         "/model" => {
-            if parts.len() < 2 {
-                println!("\nCurrent model: {}\n", agent.model());
-                return CommandResult::Continue;
-            }
-            let model = parts[1];
-            match agent.set_model(model) {
+            let model = match parts.get(1) {
+                Some(model) => model.to_string(),
+                None => match pick_model(agent).await {
+                    Ok(Some(model)) => model,
+                    Ok(None) => return CommandResult::Continue,
+                    Err(e) => return CommandResult::Error(format!("Failed to pick model: {}", e)),
+                },
+            };
+            match agent.set_model(&model) {
                 Ok(()) => {
                     println!("\nSwitched to model: {}\n", model);
                     CommandResult::Continue
@@ -676,13 +1080,66 @@ async fn handle_command(
             }
         }
 
-        "/compact" => match agent.compact_session().await {
-            Ok((before, after)) => {
-                println!("\nSession compacted. Token count: {} → {}\n", before, after);
-                CommandResult::Continue
+        "/ask" => {
+            if parts.len() < 3 {
+                return CommandResult::Error("Usage: /ask <model> <message>".into());
             }
-            Err(e) => CommandResult::Error(format!("Failed to compact: {}", e)),
-        },
+            // Only the next turn goes to the model; the session keeps its default
+            let prompt = input
+                .trim_start()
+                .splitn(3, char::is_whitespace)
+                .nth(2)
+                .unwrap_or("")
+                .trim();
+            match agent.set_turn_model(parts[1]).await {
+                Ok(()) => CommandResult::SendMessage(prompt.to_string()),
+                Err(e) => CommandResult::Error(e.to_string()),
+            }
+        }
+
+        "/plan" => {
+            let task = input.trim_start()[cmd.len()..].trim();
+            if task.is_empty() {
+                return CommandResult::Error("Usage: /plan <task>".into());
+            }
+            CommandResult::Plan(task.to_string())
+        }
+
+        "/compact" if parts.get(1) == Some(&"strategy") => {
+            match parts.get(2).copied() {
+                None => println!(
+                    "\nCompaction strategy: {} (one of {})\n",
+                    agent.compaction_strategy(),
+                    compaction_strategy_names()
+                ),
+                Some("default") => {
+                    agent.set_compaction_strategy(None);
+                    println!(
+                        "\nCompaction strategy reset to {}.\n",
+                        agent.compaction_strategy()
+                    );
+                }
+                Some(name) => match name.parse::<CompactionStrategy>() {
+                    Ok(strategy) => {
+                        agent.set_compaction_strategy(Some(strategy));
+                        println!("\nThis session will be compacted with {}.\n", strategy);
+                    }
+                    Err(e) => return CommandResult::Error(e.to_string()),
+                },
+            }
+            CommandResult::Continue
+        }
+
+        "/compact" => {
+            if agent.compaction_running() {
+                println!("\nAlready compacting.\n");
+            } else if agent.start_compaction() {
+                println!("\nCompacting in the background; keep chatting.\n");
+            } else {
+                println!("\nNothing to compact.\n");
+            }
+            CommandResult::Continue
+        }
 
         "/clear" => {
             agent.clear_session();
@@ -691,6 +1148,8 @@ async fn handle_command(
         }
 
         "/new" => {
+            remember_session_facts(agent).await;
+
             // Save current session to memory before starting new one
             match agent.save_session_to_memory().await {
                 Ok(Some(path)) => {
@@ -724,7 +1183,12 @@ async fn handle_command(
                             query
                         );
                     } else {
-                        println!("\nMemory search results for '{}':", query);
+                        let kind = if agent.has_embeddings() {
+                            "Semantic memory"
+                        } else {
+                            "Memory"
+                        };
+                        println!("\n{} search results for '{}':", kind, query);
                         for (i, result) in results.iter().enumerate() {
                             let snippet = extract_snippet(&result.content, &query, 120);
                             println!(
@@ -761,6 +1225,31 @@ async fn handle_command(
             Err(e) => CommandResult::Error(format!("Failed to reindex: {}", e)),
         },
 
+        "/cache" => match parts.get(1).copied() {
+            None => match response_cache_len() {
+                Ok(count) => {
+                    println!("\nCached replies: {}\n", count);
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(format!("Failed to read cache: {}", e)),
+            },
+            Some("clear") => match clear_response_cache() {
+                Ok(count) => {
+                    println!("\nCleared {} cached replies.\n", count);
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(format!("Failed to clear cache: {}", e)),
+            },
+            Some(_) => CommandResult::Error("Usage: /cache [clear]".into()),
+        },
+
+        "/doctor" => {
+            println!("\nChecking providers...\n");
+            super::doctor::print_report(&agent.health_checks().await);
+            println!();
+            CommandResult::Continue
+        }
+
         "/save" => match agent.save_session().await {
             Ok(path) => {
                 println!("\nSession saved to: {}\n", path.display());
@@ -777,6 +1266,15 @@ async fn handle_command(
             println!("  Messages: {}", status.message_count);
             println!("  Context tokens: ~{}", status.token_count);
             println!("  Compactions: {}", status.compaction_count);
+            if let Some(t) = status.generation.temperature {
+                println!("  Temperature: {}", t);
+            }
+            if let Some(n) = status.generation.max_tokens {
+                println!("  Max tokens: {}", n);
+            }
+            if let Some(ref prompt) = status.system_prompt {
+                println!("  System instructions: {}", prompt);
+            }
 
             println!("\nMemory:");
             println!("  Chunks: {}", agent.memory_chunk_count());
@@ -788,6 +1286,9 @@ async fn handle_command(
                 println!("\nAPI Usage:");
                 println!("  Input tokens: {}", status.api_input_tokens);
                 println!("  Output tokens: {}", status.api_output_tokens);
+                if status.api_reasoning_tokens > 0 {
+                    println!("  Reasoning tokens: {}", status.api_reasoning_tokens);
+                }
                 println!(
                     "  Total tokens: {}",
                     status.api_input_tokens + status.api_output_tokens
@@ -802,15 +1303,87 @@ async fn handle_command(
             println!(
                 "  claude-cli/*    - Use Claude CLI (e.g., claude-cli/opus, claude-cli/sonnet)"
             );
+            println!("  codex-cli/*     - Use OpenAI Codex CLI (e.g., codex-cli/gpt-5-codex)");
             println!("  gpt-*           - OpenAI (requires API key)");
             println!("  claude-*        - Anthropic API (requires API key)");
+            println!("  gemini-*        - Google Gemini API (requires API key)");
+            println!("  deepseek-*      - DeepSeek API (requires API key)");
+            println!("  <custom>/*      - [[providers.custom]] OpenAI-compatible server");
             println!("  ollama/*        - Ollama local (e.g., ollama/llama3)");
+            println!("  gguf/*          - In-process llama.cpp (--features gguf)");
             println!("  <other>         - Defaults to Ollama");
             println!("\nCurrent model: {}", agent.model());
             println!("Use /model <name> to switch.\n");
             CommandResult::Continue
         }
 
+        "/set" => {
+            let settings = &parts[1..];
+            if settings.is_empty() {
+                return CommandResult::Error(
+                    "Usage: /set temperature=0.2 [max_tokens=2000]".into(),
+                );
+            }
+            for setting in settings {
+                let Some((key, value)) = setting.split_once('=') else {
+                    return CommandResult::Error(format!("Expected key=value, got '{}'", setting));
+                };
+                if let Err(e) = agent.set_generation_param(key, value) {
+                    return CommandResult::Error(e.to_string());
+                }
+            }
+            println!("\nUpdated: {}\n", settings.join(" "));
+            CommandResult::Continue
+        }
+
+        "/system" => {
+            let text = input[cmd.len()..].trim();
+            match text {
+                "" => match agent.system_prompt() {
+                    Some(prompt) => println!("\nSystem instructions: {}\n", prompt),
+                    None => println!("\nNo session system instructions. Use /system <text>.\n"),
+                },
+                "clear" => {
+                    agent.set_system_prompt(None);
+                    println!("\nSystem instructions cleared.\n");
+                }
+                _ => {
+                    agent.set_system_prompt(Some(text.to_string()));
+                    println!("\nSystem instructions set for this session.\n");
+                }
+            }
+            CommandResult::Continue
+        }
+
+        "/lang" => {
+            let language = input[cmd.len()..].trim();
+            match language {
+                "" => match agent.language() {
+                    Some(language) => println!("\nReply language: {}\n", language),
+                    None => println!("\nNo reply language set. Use /lang <language>.\n"),
+                },
+                "off" | "clear" => {
+                    agent.set_language(None);
+                    println!("\nReply language cleared.\n");
+                }
+                _ => {
+                    agent.set_language(Some(language.to_string()));
+                    println!("\nReplies will be in {} for this session.\n", language);
+                }
+            }
+            CommandResult::Continue
+        }
+
+        "/stats" => {
+            println!("\n{}", agent.session_stats().to_text());
+            CommandResult::Continue
+        }
+
+        "/context" if parts.get(1) == Some(&"view") => {
+            println!("\n{}", agent.inspect_context().to_text());
+            CommandResult::Continue
+        }
+
         "/context" => {
             let (used, usable, total) = agent.context_usage();
             let pct = (used as f64 / usable as f64 * 100.0).min(100.0);
@@ -846,6 +1419,111 @@ async fn handle_command(
             }
         }
 
+        "/share" => {
+            let include_tool_calls = !parts.contains(&"--no-tools");
+            let path_arg: Vec<&str> = parts[1..]
+                .iter()
+                .copied()
+                .filter(|p| *p != "--no-tools")
+                .collect();
+            let path = if path_arg.is_empty() {
+                default_share_path(&agent.session_status().id)
+            } else {
+                shellexpand::tilde(&path_arg.join(" ")).to_string().into()
+            };
+
+            let html = agent.export_html(&ShareOptions {
+                include_tool_calls,
+                ..Default::default()
+            });
+            match std::fs::write(&path, html) {
+                Ok(()) => {
+                    println!("\nShared copy written to: {}", path.display());
+                    println!("Secrets were redacted; review it before sending.\n");
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(format!("Failed to write share file: {}", e)),
+            }
+        }
+
+        "/rate" => {
+            let rating = match parts.get(1).copied() {
+                Some("clear") => None,
+                Some(arg) => match Rating::parse(arg) {
+                    Some(rating) => Some(rating),
+                    None => return CommandResult::Error("Usage: /rate up|down [note]".into()),
+                },
+                None => return CommandResult::Error("Usage: /rate up|down [note]".into()),
+            };
+            let note = parts.get(2..).map(|words| words.join(" "));
+            match agent.rate_reply(None, rating, note.as_deref()).await {
+                Ok(()) => {
+                    println!("\nRating saved.\n");
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(format!("Failed to rate: {}", e)),
+            }
+        }
+
+        "/pins" => {
+            let pinned = agent.pinned_messages();
+            if pinned.is_empty() {
+                println!("\nNo pinned messages. /pin [text] pins one.\n");
+            } else {
+                println!("\nPinned messages (kept verbatim by compaction):");
+                for content in pinned {
+                    println!("  📌 {}", pin_preview(&content));
+                }
+                println!();
+            }
+            CommandResult::Continue
+        }
+
+        "/pin" | "/unpin" => {
+            let pinned = cmd == "/pin";
+            let text = (parts.len() > 1).then(|| parts[1..].join(" "));
+            match agent.set_pinned(text.as_deref(), pinned) {
+                Ok(content) => {
+                    let verb = if pinned { "Pinned" } else { "Unpinned" };
+                    println!("\n{}: {}\n", verb, pin_preview(&content));
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(e.to_string()),
+            }
+        }
+
+        "/paste" => {
+            let instruction = input[cmd.len()..].trim();
+            match read_clipboard() {
+                Ok(text) if text.trim().is_empty() => {
+                    CommandResult::Error("The clipboard has no text".to_string())
+                }
+                Ok(text) => CommandResult::SendMessage(paste_message(instruction, &text)),
+                Err(e) => CommandResult::Error(e.to_string()),
+            }
+        }
+
+        "/copy" => {
+            let Some(reply) = agent.last_reply() else {
+                return CommandResult::Error("No reply to copy yet".to_string());
+            };
+            match write_clipboard(&reply) {
+                Ok(()) => {
+                    println!("\nCopied the last reply to the clipboard\n");
+                    CommandResult::Continue
+                }
+                Err(e) => CommandResult::Error(e.to_string()),
+            }
+        }
+
+        "/undo" => match agent.undo_agent_changes() {
+            Ok(summary) => {
+                println!("\n{}\n", summary);
+                CommandResult::Continue
+            }
+            Err(e) => CommandResult::Error(format!("Undo failed: {}", e)),
+        },
+
         _ => {
             // Check if it's a skill command
             if let Some(invocation) = parse_skill_command(input, skills) {
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
 
-use localgpt::config::Config;
+use localgpt::config::{store_api_key, Config};
 
 #[derive(Args)]
 pub struct ConfigArgs {
@@ -33,6 +33,13 @@ pub enum ConfigCommands {
         value: String,
     },
 
+    /// Save an API key in the OS keyring (use with api_key = "keyring"
+    /// or "keyring:<entry>")
+    SetKey {
+        /// Provider (openai, anthropic, ...) or keyring entry name
+        entry: String,
+    },
+
     /// Show config file path
     Path,
 
@@ -49,6 +56,7 @@ pub async fn run(args: ConfigArgs) -> Result<()> {
         ConfigCommands::Show { format } => show_config(&format),
         ConfigCommands::Get { key } => get_config(&key),
         ConfigCommands::Set { key, value } => set_config(&key, &value),
+        ConfigCommands::SetKey { entry } => set_key(&entry),
         ConfigCommands::Path => show_path(),
         ConfigCommands::Init { force } => init_config(force),
     }
@@ -59,11 +67,11 @@ fn show_config(format: &str) -> Result<()> {
 
     match format {
         "json" => {
-            let json = serde_json::to_string_pretty(&config)?;
+            let json = serde_json::to_string_pretty(&config.as_written())?;
             println!("{}", json);
         }
         _ => {
-            let toml = toml::to_string_pretty(&config)?;
+            let toml = toml::to_string_pretty(&config.as_written())?;
             println!("{}", toml);
         }
     }
@@ -86,6 +94,17 @@ fn set_config(key: &str, value: &str) -> Result<()> {
     Ok(())
 }
 
+fn set_key(entry: &str) -> Result<()> {
+    let key = rpassword::prompt_password(format!("API key for '{}': ", entry))?;
+    let key = key.trim();
+    if key.is_empty() {
+        anyhow::bail!("No key entered");
+    }
+    store_api_key(entry, key)?;
+    println!("Saved '{}' in the OS keyring", entry);
+    Ok(())
+}
+
 fn show_path() -> Result<()> {
     let path = Config::config_path()?;
     println!("{}", path.display());
@@ -108,7 +127,10 @@ fn init_config(force: bool) -> Result<()> {
     }
 
     // Write template config with comments
-    std::fs::write(&path, DEFAULT_CONFIG_TEMPLATE)?;
+    std::fs::write(
+        &path,
+        localgpt::config::profile::localize_template(DEFAULT_CONFIG_TEMPLATE)?,
+    )?;
 
     println!("Created config file at {}", path.display());
     Ok(())
@@ -124,16 +146,28 @@ reserve_tokens = 8000
 
 # Anthropic API (for anthropic/* models)
 # [providers.anthropic]
-# api_key = "${ANTHROPIC_API_KEY}"
+# api_key = "${ANTHROPIC_API_KEY}"   # or "keyring" (localgpt config set-key anthropic)
 
 # OpenAI API (for openai/* models)
 # [providers.openai]
 # api_key = "${OPENAI_API_KEY}"
 
+# Google Gemini API (for gemini/* models)
+# [providers.gemini]
+# api_key = "${GEMINI_API_KEY}"
+
+# DeepSeek API (for deepseek/* models)
+# [providers.deepseek]
+# api_key = "${DEEPSEEK_API_KEY}"
+
 # Claude CLI (for claude-cli/* models, requires claude CLI installed)
 [providers.claude_cli]
 command = "claude"
 
+# OpenAI Codex CLI (for codex-cli/* models, requires codex CLI installed)
+# [providers.codex_cli]
+# command = "codex"
+
 [heartbeat]
 enabled = true
 interval = "30m"
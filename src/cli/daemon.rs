@@ -2,10 +2,13 @@ use anyhow::Result;
 use clap::{Args, Subcommand};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg(unix)]
 use daemonize::Daemonize;
 
+use localgpt::calendar::ReminderScheduler;
 use localgpt::concurrency::TurnGate;
 use localgpt::config::Config;
 use localgpt::heartbeat::HeartbeatRunner;
@@ -66,7 +69,7 @@ pub fn stop_sync() -> Result<()> {
 /// This avoids the macOS fork-safety issue with ObjC/Swift runtime.
 #[cfg(unix)]
 pub fn daemonize_and_run(agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
 
     // Check if already running
     let pid_file = get_pid_file()?;
@@ -131,12 +134,16 @@ async fn run_daemon_server(config: Config, agent_id: &str) -> Result<()> {
         .with_ansi(false)
         .init();
 
-    let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
+    let memory = Arc::new(MemoryManager::new_with_full_config(
+        &config.memory,
+        Some(&config),
+        agent_id,
+    )?);
     let _watcher = memory.start_watcher()?;
 
     println!("Daemon started successfully");
 
-    run_daemon_services(&config, agent_id).await?;
+    run_daemon_services(&config, agent_id, &memory).await?;
 
     println!("\nShutting down...");
     let pid_file = get_pid_file()?;
@@ -146,7 +153,11 @@ async fn run_daemon_server(config: Config, agent_id: &str) -> Result<()> {
 }
 
 /// Run daemon services (server and/or heartbeat)
-async fn run_daemon_services(config: &Config, agent_id: &str) -> Result<()> {
+async fn run_daemon_services(
+    config: &Config,
+    agent_id: &str,
+    memory: &Arc<MemoryManager>,
+) -> Result<()> {
     // Create shared turn gate for heartbeat + HTTP concurrency control
     let turn_gate = TurnGate::new();
 
@@ -179,6 +190,54 @@ async fn run_daemon_services(config: &Config, agent_id: &str) -> Result<()> {
         None
     };
 
+    // Fire due reminders as desktop notifications
+    let reminder_handle = if config.calendar.enabled {
+        println!("  Reminders: enabled");
+        match ReminderScheduler::new() {
+            Ok(scheduler) => Some(tokio::spawn(async move { scheduler.run(None).await })),
+            Err(e) => {
+                tracing::error!("Failed to create reminder scheduler: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Keep the document directories indexed
+    let documents_handle = match memory.start_document_watcher() {
+        Ok(Some(watcher)) => {
+            println!(
+                "  Document directories: watching {}",
+                config.memory.document_dirs.join(", ")
+            );
+            Some(tokio::spawn(watcher.run()))
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::error!("Failed to watch document directories: {}", e);
+            None
+        }
+    };
+
+    // Archive stale notes now and then daily
+    let archive_handle = (config.memory.archive_after_days > 0).then(|| {
+        let memory = Arc::clone(memory);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+            loop {
+                interval.tick().await;
+                match memory.archive_stale() {
+                    Ok(archived) if !archived.is_empty() => {
+                        tracing::info!("Archived {} stale memory notes", archived.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("Failed to archive memory notes: {}", e),
+                }
+            }
+        })
+    });
+
     // Run server or wait for shutdown
     if config.server.enabled {
         println!(
@@ -187,8 +246,9 @@ async fn run_daemon_services(config: &Config, agent_id: &str) -> Result<()> {
         );
         let server = Server::new_with_gate(config, turn_gate)?;
         server.run().await?;
-    } else if heartbeat_handle.is_some() {
-        // Server not enabled but heartbeat is - wait for Ctrl+C
+    } else if heartbeat_handle.is_some() || reminder_handle.is_some() || documents_handle.is_some()
+    {
+        // Server not enabled but background tasks are - wait for Ctrl+C
         println!("  Server: disabled");
         tokio::signal::ctrl_c().await?;
     } else {
@@ -196,10 +256,19 @@ async fn run_daemon_services(config: &Config, agent_id: &str) -> Result<()> {
         tokio::signal::ctrl_c().await?;
     }
 
-    // Abort heartbeat task on shutdown
+    // Abort background tasks on shutdown
     if let Some(handle) = heartbeat_handle {
         handle.abort();
     }
+    if let Some(handle) = reminder_handle {
+        handle.abort();
+    }
+    if let Some(handle) = documents_handle {
+        handle.abort();
+    }
+    if let Some(handle) = archive_handle {
+        handle.abort();
+    }
 
     Ok(())
 }
@@ -247,7 +316,7 @@ pub async fn run(args: DaemonArgs, agent_id: &str) -> Result<()> {
 }
 
 async fn start_daemon(foreground: bool, agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
 
     // Check if already running
     let pid_file = get_pid_file()?;
@@ -283,12 +352,16 @@ async fn start_daemon(foreground: bool, agent_id: &str) -> Result<()> {
     fs::write(&pid_file, std::process::id().to_string())?;
 
     // Initialize components
-    let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
+    let memory = Arc::new(MemoryManager::new_with_full_config(
+        &config.memory,
+        Some(&config),
+        agent_id,
+    )?);
     let _watcher = memory.start_watcher()?;
 
     println!("Daemon started successfully");
 
-    run_daemon_services(&config, agent_id).await?;
+    run_daemon_services(&config, agent_id, &memory).await?;
 
     println!("\nShutting down...");
     fs::remove_file(&pid_file).ok();
@@ -418,7 +491,7 @@ async fn show_status() -> Result<()> {
 }
 
 async fn run_heartbeat_once(agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
     let runner = HeartbeatRunner::new_with_agent(&config, agent_id)?;
 
     println!("Running heartbeat (agent: {})...", agent_id);
@@ -9,8 +9,29 @@ pub struct DesktopArgs {
     // Could add --theme, --size, etc. in future
 }
 
-pub fn run(_args: DesktopArgs, agent_id: &str) -> Result<()> {
-    use localgpt::desktop::DesktopApp;
+pub fn run(_args: DesktopArgs, agent_id: &str, user: Option<&str>) -> Result<()> {
+    use localgpt::config::profile::{self, DEFAULT_PROFILE, USER_PASSPHRASE_ENV};
+    use localgpt::desktop::{DesktopApp, ProfilePicker};
+
+    // Activate the requested profile directly when no passphrase is needed;
+    // otherwise let the startup picker ask (pre-selecting the request)
+    let profile_picker = match user {
+        Some(DEFAULT_PROFILE) => None,
+        Some(name) => {
+            let p = profile::load_profile(name)?;
+            let unlocked = !p.has_passphrase()
+                || std::env::var(USER_PASSPHRASE_ENV)
+                    .map(|pass| p.verify_passphrase(&pass))
+                    .unwrap_or(false);
+            if unlocked {
+                profile::set_active_profile(name)?;
+                None
+            } else {
+                ProfilePicker::new(Some(name))
+            }
+        }
+        None => ProfilePicker::new(None),
+    };
 
     let native_options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
@@ -25,7 +46,13 @@ pub fn run(_args: DesktopArgs, agent_id: &str) -> Result<()> {
     eframe::run_native(
         "LocalGPT",
         native_options,
-        Box::new(move |cc| Ok(Box::new(DesktopApp::new(cc, Some(agent_id.clone()))))),
+        Box::new(move |cc| {
+            Ok(Box::new(DesktopApp::new(
+                cc,
+                Some(agent_id.clone()),
+                profile_picker,
+            )))
+        }),
     )
     .map_err(|e| anyhow::anyhow!("Failed to run desktop app: {}", e))
 }
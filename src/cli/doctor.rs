@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::Args;
+
+use localgpt::agent::{run_health_checks, ProviderHealth};
+use localgpt::config::Config;
+
+#[derive(Args)]
+pub struct DoctorArgs {}
+
+pub async fn run(_args: DoctorArgs) -> Result<()> {
+    let config = Config::load()?;
+    println!("Checking providers...\n");
+    let results = run_health_checks(&config).await;
+    print_report(&results);
+
+    let failed = results.iter().filter(|r| !r.ok).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, results.len());
+    }
+    Ok(())
+}
+
+/// One line per provider: status, latency and what it reported
+pub fn print_report(results: &[ProviderHealth]) {
+    if results.is_empty() {
+        println!("No providers configured. Add one to ~/.localgpt/config.toml");
+        return;
+    }
+    for result in results {
+        let latency = result
+            .latency_ms
+            .map(|ms| format!("{} ms", ms))
+            .unwrap_or_default();
+        println!(
+            "  {} {:<20} {:>8}  {}",
+            if result.ok { "ok  " } else { "FAIL" },
+            result.provider,
+            latency,
+            result.detail
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use std::path::{Path, PathBuf};
+
+use localgpt::agent::{
+    collect_rated_pairs, export_rated_pairs, load_feedback_summary, summarize_feedback, Rating,
+};
+use localgpt::config::Config;
+
+#[derive(Args)]
+pub struct FeedbackArgs {
+    #[command(subcommand)]
+    pub command: FeedbackCommands,
+}
+
+#[derive(Subcommand)]
+pub enum FeedbackCommands {
+    /// Export rated prompt/reply pairs as JSONL
+    Export {
+        /// File to write
+        file: PathBuf,
+
+        /// Only export pairs with this rating (up or down)
+        #[arg(long)]
+        rating: Option<String>,
+    },
+
+    /// Rebuild the preference summary from all rated replies
+    Summarize,
+
+    /// Show the current preference summary
+    Show,
+}
+
+pub async fn run(args: FeedbackArgs) -> Result<()> {
+    match args.command {
+        FeedbackCommands::Export { file, rating } => export(&file, rating.as_deref()),
+        FeedbackCommands::Summarize => summarize().await,
+        FeedbackCommands::Show => {
+            match load_feedback_summary() {
+                Some(summary) => println!("{}", summary),
+                None => println!("No feedback summary yet. Run: localgpt feedback summarize"),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn export(file: &Path, rating: Option<&str>) -> Result<()> {
+    let rating = match rating {
+        Some(r) => {
+            Some(Rating::parse(r).ok_or_else(|| anyhow::anyhow!("Rating must be 'up' or 'down'"))?)
+        }
+        None => None,
+    };
+    let pairs: Vec<_> = collect_rated_pairs()?
+        .into_iter()
+        .filter(|p| rating.is_none_or(|r| p.rating == r))
+        .collect();
+    export_rated_pairs(&pairs, file)?;
+    println!(
+        "Exported {} rated replies to {}",
+        pairs.len(),
+        file.display()
+    );
+    Ok(())
+}
+
+async fn summarize() -> Result<()> {
+    let config = Config::load()?;
+    match summarize_feedback(&config).await? {
+        Some(summary) => {
+            println!("{}", summary);
+            if !config.agent.feedback_summary {
+                println!(
+                    "\nTo add this to new sessions: localgpt config set agent.feedback_summary true"
+                );
+            }
+        }
+        None => println!("No rated replies yet. Rate replies with /rate up|down in chat."),
+    }
+    Ok(())
+}
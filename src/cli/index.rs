@@ -0,0 +1,48 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use localgpt::config::Config;
+use localgpt::memory::{MemoryManager, DOCUMENT_EXTENSIONS};
+
+#[derive(Args)]
+pub struct IndexArgs {
+    /// File or directory of documents (Markdown, text, PDF, .docx)
+    pub path: PathBuf,
+
+    /// Re-read files even when they haven't changed
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+pub async fn run(args: IndexArgs, agent_id: &str) -> Result<()> {
+    let config = Config::load_for_agent(agent_id)?;
+    let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
+
+    println!("Indexing {}...", args.path.display());
+    let stats = memory.index_documents(&args.path, args.force).await?;
+
+    println!("  Indexed: {}", stats.indexed);
+    println!("  Unchanged: {}", stats.unchanged);
+    if stats.removed > 0 {
+        println!("  Removed (deleted files): {}", stats.removed);
+    }
+    if !stats.skipped.is_empty() {
+        println!("  Skipped: {}", stats.skipped.len());
+        for (path, reason) in &stats.skipped {
+            println!("    {}: {}", path.display(), reason);
+        }
+    }
+    if stats.indexed + stats.unchanged == 0 && stats.skipped.is_empty() {
+        println!(
+            "No documents found (looked for .{})",
+            DOCUMENT_EXTENSIONS.join(", .")
+        );
+    }
+    println!(
+        "{} documents available to document_search",
+        memory.document_count()?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Wake word voice mode: transcribes speech locally and answers like `ask`
+
+use anyhow::Result;
+use clap::Args;
+use std::time::{Duration, Instant};
+
+use localgpt::agent::{Agent, AgentConfig};
+use localgpt::concurrency::WorkspaceLock;
+use localgpt::config::Config;
+use localgpt::memory::MemoryManager;
+use localgpt::voice::{Microphone, SpeechSegmenter, Transcriber, WakeWord};
+
+/// How long to wait for a request after a bare wake word
+const REQUEST_WINDOW: Duration = Duration::from_secs(8);
+
+#[derive(Args)]
+pub struct ListenArgs {
+    /// Model to use (overrides config)
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Wake phrase (overrides voice.wake_word)
+    #[arg(short, long)]
+    pub wake_word: Option<String>,
+}
+
+pub async fn run(args: ListenArgs, agent_id: &str) -> Result<()> {
+    let config = Config::load_for_agent(agent_id)?;
+    let voice = &config.voice;
+    if voice.whisper_model.is_empty() {
+        anyhow::bail!(
+            "Voice mode needs a local whisper model. Set voice.whisper_model in \
+             ~/.localgpt/config.toml (e.g. ~/.localgpt/models/ggml-base.en.bin)"
+        );
+    }
+
+    let transcriber = Transcriber::new(&voice.whisper_model, &voice.language)?;
+    let wake_word = WakeWord::new(args.wake_word.as_deref().unwrap_or(&voice.wake_word));
+    let mut segmenter = SpeechSegmenter::new(
+        voice.energy_threshold,
+        voice.silence_ms,
+        voice.max_utterance_secs,
+    );
+
+    let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
+    let agent_config = AgentConfig {
+        model: args.model.unwrap_or(config.agent.default_model.clone()),
+        context_window: config.agent.context_window_or_default(),
+        reserve_tokens: config.agent.reserve_tokens,
+    };
+    let mut agent = Agent::new(agent_config, &config, memory).await?;
+    let workspace_lock = WorkspaceLock::new()?;
+
+    let mut microphone = Microphone::open()?;
+    println!(
+        "Listening for \"{}\" (Ctrl+C to stop)",
+        args.wake_word.as_deref().unwrap_or(&voice.wake_word)
+    );
+
+    // Set when the wake word was heard on its own and the request should follow
+    let mut awaiting_request: Option<Instant> = None;
+
+    while let Some(chunk) = microphone.next_chunk().await {
+        if awaiting_request.is_some_and(|since| since.elapsed() > REQUEST_WINDOW) {
+            awaiting_request = None;
+            println!("(No request heard)");
+        }
+
+        for utterance in segmenter.push(&chunk) {
+            // Transcription is CPU-bound; keep it off the async workers
+            let text = tokio::task::block_in_place(|| transcriber.transcribe(&utterance))?;
+            if text.is_empty() {
+                continue;
+            }
+
+            let request = if awaiting_request.take().is_some() {
+                text
+            } else {
+                match wake_word.strip(&text) {
+                    Some("") => {
+                        println!("Yes?");
+                        awaiting_request = Some(Instant::now());
+                        continue;
+                    }
+                    Some(rest) => rest.to_string(),
+                    None => continue,
+                }
+            };
+
+            println!("\nYou: {}", request);
+            agent.new_session().await?;
+            let _lock_guard = workspace_lock.acquire()?;
+            match agent.chat(&request).await {
+                Ok(response) => println!("\nLocalGPT: {}\n", response),
+                Err(e) => eprintln!("Error: {}\n", e),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use localgpt::config::Config;
-use localgpt::memory::MemoryManager;
+use localgpt::memory::{MemoryExport, MemoryManager};
 
 #[derive(Args)]
 pub struct MemoryArgs {
@@ -38,10 +40,29 @@ pub enum MemoryCommands {
         #[arg(short, long, default_value = "10")]
         count: usize,
     },
+
+    /// Move stale daily notes out of the workspace (memory.archive_after_days)
+    Archive,
+
+    /// Export memory files and the indexed document list to a JSON file
+    Export {
+        /// File to write
+        path: PathBuf,
+    },
+
+    /// Import a JSON file written by `memory export`
+    Import {
+        /// File to read
+        path: PathBuf,
+
+        /// Replace workspace files that differ from the export
+        #[arg(long)]
+        overwrite: bool,
+    },
 }
 
 pub async fn run(args: MemoryArgs, agent_id: &str) -> Result<()> {
-    let config = Config::load()?;
+    let config = Config::load_for_agent(agent_id)?;
     let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), agent_id)?;
 
     match args.command {
@@ -49,11 +70,16 @@ pub async fn run(args: MemoryArgs, agent_id: &str) -> Result<()> {
         MemoryCommands::Reindex { force } => reindex_memory(&memory, force).await,
         MemoryCommands::Stats => show_stats(&memory).await,
         MemoryCommands::Recent { count } => show_recent(&memory, count).await,
+        MemoryCommands::Archive => archive_memory(&memory, config.memory.archive_after_days),
+        MemoryCommands::Export { path } => export_memory(&memory, &path),
+        MemoryCommands::Import { path, overwrite } => {
+            import_memory(&memory, &path, overwrite).await
+        }
     }
 }
 
 async fn search_memory(memory: &MemoryManager, query: &str, limit: usize) -> Result<()> {
-    let results = memory.search(query, limit)?;
+    let results = memory.search(query, limit).await?;
 
     if results.is_empty() {
         println!("No results found for '{}'", query);
@@ -118,6 +144,71 @@ async fn reindex_memory(memory: &MemoryManager, force: bool) -> Result<()> {
     Ok(())
 }
 
+fn archive_memory(memory: &MemoryManager, archive_after_days: u32) -> Result<()> {
+    if archive_after_days == 0 {
+        println!("Archiving is off. Set memory.archive_after_days in config.toml to enable it.");
+        return Ok(());
+    }
+
+    let archived = memory.archive_stale()?;
+    if archived.is_empty() {
+        println!(
+            "No notes older than {} days without recent use.",
+            archive_after_days
+        );
+    } else {
+        println!("Archived {} notes:", archived.len());
+        for path in &archived {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn export_memory(memory: &MemoryManager, path: &Path) -> Result<()> {
+    let export = memory.export()?;
+    fs::write(path, serde_json::to_string_pretty(&export)?)?;
+    println!(
+        "Exported {} files and {} document paths to {}",
+        export.files.len(),
+        export.documents.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+async fn import_memory(memory: &MemoryManager, path: &Path, overwrite: bool) -> Result<()> {
+    let export: MemoryExport = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("{} is not a memory export: {}", path.display(), e))?;
+    println!(
+        "Importing memory of agent '{}' exported {}...",
+        export.agent_id, export.exported_at
+    );
+
+    let stats = memory.import(&export, overwrite).await?;
+    println!("  Files written: {}", stats.written);
+    println!("  Files already up to date: {}", stats.unchanged);
+    if !stats.kept.is_empty() {
+        println!(
+            "  Kept {} local files that differ (use --overwrite to replace them):",
+            stats.kept.len()
+        );
+        for file in &stats.kept {
+            println!("    {}", file);
+        }
+    }
+    if !export.documents.is_empty() {
+        println!("  Documents indexed: {}", stats.documents_indexed);
+    }
+    if !stats.documents_missing.is_empty() {
+        println!("  Documents not found on this machine:");
+        for document in &stats.documents_missing {
+            println!("    {}", document);
+        }
+    }
+    Ok(())
+}
+
 async fn show_stats(memory: &MemoryManager) -> Result<()> {
     let stats = memory.stats()?;
 
@@ -1,10 +1,18 @@
+pub mod agents;
 pub mod ask;
 pub mod chat;
 pub mod config;
 pub mod daemon;
 #[cfg(feature = "desktop")]
 pub mod desktop;
+pub mod doctor;
+pub mod feedback;
+pub mod index;
+#[cfg(feature = "voice")]
+pub mod listen;
 pub mod memory;
+pub mod ollama;
+pub mod profile;
 
 use clap::{Parser, Subcommand};
 
@@ -24,7 +32,8 @@ pub struct Cli {
     #[arg(short, long, global = true, env = "LOCALGPT_CONFIG")]
     pub config: Option<String>,
 
-    /// Agent ID to use (default: "main", OpenClaw-compatible)
+    /// Agent ID to use, with its own sessions and `agents/<id>/agent.toml`
+    /// settings (default: "main", OpenClaw-compatible)
     #[arg(
         short,
         long,
@@ -33,6 +42,10 @@ pub struct Cli {
         env = "LOCALGPT_AGENT"
     )]
     pub agent: String,
+
+    /// User profile to use (separate config, keys, sessions, and memory)
+    #[arg(long, global = true, env = "LOCALGPT_USER_PROFILE")]
+    pub user: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -50,9 +63,31 @@ pub enum Commands {
     /// Manage the daemon
     Daemon(daemon::DaemonArgs),
 
+    /// Always-listening voice mode: say the wake word, then ask a question
+    #[cfg(feature = "voice")]
+    Listen(listen::ListenArgs),
+
     /// Memory operations
     Memory(memory::MemoryArgs),
 
+    /// Index documents for the agent to search and cite
+    Index(index::IndexArgs),
+
     /// Configuration management
     Config(config::ConfigArgs),
+
+    /// Manage user profiles
+    Profile(profile::ProfileArgs),
+
+    /// List and configure named agents (selected with --agent)
+    Agents(agents::AgentsArgs),
+
+    /// List, pull, and delete Ollama models
+    Ollama(ollama::OllamaArgs),
+
+    /// Export rated replies and manage the preference summary
+    Feedback(feedback::FeedbackArgs),
+
+    /// Check that each configured provider answers and accepts its key
+    Doctor(doctor::DoctorArgs),
 }
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use std::io::{self, Write};
+
+use localgpt::agent::{
+    delete_ollama_model, find_host, list_ollama_models, ollama_model_host, pull_ollama_model,
+};
+use localgpt::config::{Config, OllamaConfig, DEFAULT_OLLAMA_HOST};
+
+#[derive(Args)]
+pub struct OllamaArgs {
+    #[command(subcommand)]
+    pub command: OllamaCommands,
+}
+
+#[derive(Subcommand)]
+pub enum OllamaCommands {
+    /// List models installed on an Ollama host
+    List {
+        /// Host name from [[providers.ollama.hosts]] (default: providers.ollama.endpoint)
+        #[arg(long, default_value = DEFAULT_OLLAMA_HOST)]
+        host: String,
+    },
+
+    /// Download a model (use model@host for another host)
+    Pull {
+        /// Model name, e.g. llama3.2:3b or qwen2.5:7b@gpu
+        model: String,
+    },
+
+    /// Delete a model (use model@host for another host)
+    Rm {
+        /// Model name, e.g. llama3.2:3b or qwen2.5:7b@gpu
+        model: String,
+    },
+}
+
+pub async fn run(args: OllamaArgs) -> Result<()> {
+    let config = Config::load()?;
+    let ollama = config.providers.ollama.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Ollama provider not configured.\n\
+            Add to ~/.localgpt/config.toml:\n\n\
+            [providers.ollama]\n\
+            endpoint = \"http://localhost:11434\""
+        )
+    })?;
+
+    match args.command {
+        OllamaCommands::List { host } => list(ollama, &host).await,
+        OllamaCommands::Pull { model } => pull(ollama, &model).await,
+        OllamaCommands::Rm { model } => {
+            let (host, model) = ollama_model_host(ollama, &model)?;
+            delete_ollama_model(&host, model).await?;
+            println!("Deleted {} from {}", model, host.name);
+            Ok(())
+        }
+    }
+}
+
+async fn list(ollama: &OllamaConfig, host: &str) -> Result<()> {
+    let host = find_host(ollama, host)?;
+    let models = list_ollama_models(&host).await?;
+    if models.is_empty() {
+        println!("No models on {} ({})", host.name, host.endpoint);
+        return Ok(());
+    }
+
+    println!("Models on {} ({}):\n", host.name, host.endpoint);
+    for model in models {
+        let details: Vec<&str> = [
+            model.parameter_size.as_deref(),
+            model.quantization.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        println!(
+            "  {:<32} {:>8.1} GB  {}",
+            model.name,
+            model.size as f64 / 1e9,
+            details.join(" ")
+        );
+    }
+    Ok(())
+}
+
+async fn pull(ollama: &OllamaConfig, model: &str) -> Result<()> {
+    let (host, model) = ollama_model_host(ollama, model)?;
+    println!("Pulling {} on {}...", model, host.name);
+
+    let mut last_status = String::new();
+    pull_ollama_model(&host, model, |progress| {
+        // One line per step, updated in place while it downloads
+        if progress.status != last_status {
+            if !last_status.is_empty() {
+                println!();
+            }
+            last_status = progress.status.clone();
+        }
+        match progress.fraction() {
+            Some(fraction) => print!("\r  {} {:>3.0}%", progress.status, fraction * 100.0),
+            None => print!("\r  {}", progress.status),
+        }
+        let _ = io::stdout().flush();
+    })
+    .await?;
+    println!("\nPulled {}", model);
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! User profile management and startup selection
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use localgpt::config::profile::{
+    create_profile, list_profiles, load_profile, save_profile, set_active_profile, DEFAULT_PROFILE,
+    USER_PASSPHRASE_ENV,
+};
+use localgpt::config::{export_profile, import_profile};
+
+#[derive(Args)]
+pub struct ProfileArgs {
+    #[command(subcommand)]
+    pub command: ProfileCommands,
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// List user profiles
+    List,
+
+    /// Create a user profile (separate config, keys, sessions, and memory)
+    Create {
+        /// Profile name (lowercase letters, digits, '-' or '_')
+        name: String,
+
+        /// Protect the profile with a passphrase (prompted)
+        #[arg(long)]
+        passphrase: bool,
+    },
+
+    /// Set or clear a profile's passphrase
+    Passphrase {
+        /// Profile name
+        name: String,
+
+        /// Remove the passphrase
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Export config, skills, memory, and sessions to a .tar.gz archive
+    Export {
+        /// Archive to write
+        file: PathBuf,
+
+        /// Profile to export (default: the shared profile)
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Include API keys and tokens, encrypted with a passphrase (prompted)
+        #[arg(long)]
+        include_secrets: bool,
+    },
+
+    /// Restore an exported archive (e.g. on a new machine)
+    Import {
+        /// Archive to read
+        file: PathBuf,
+
+        /// Profile to import into (create it first; default: the shared profile)
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Overwrite an existing config and files
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+pub async fn run(args: ProfileArgs) -> Result<()> {
+    match args.command {
+        ProfileCommands::List => list(),
+        ProfileCommands::Create { name, passphrase } => create(&name, passphrase),
+        ProfileCommands::Passphrase { name, clear } => change_passphrase(&name, clear),
+        ProfileCommands::Export {
+            file,
+            profile,
+            include_secrets,
+        } => export(&file, profile.as_deref(), include_secrets),
+        ProfileCommands::Import {
+            file,
+            profile,
+            force,
+        } => import(&file, profile.as_deref(), force),
+    }
+}
+
+fn export(file: &Path, profile: Option<&str>, include_secrets: bool) -> Result<()> {
+    select(profile, false)?;
+    let passphrase = if include_secrets {
+        println!("Choose a passphrase to encrypt API keys and tokens in the archive.");
+        Some(prompt_new_passphrase()?)
+    } else {
+        None
+    };
+
+    let summary = export_profile(file, passphrase.as_deref())?;
+    println!("Exported {} files to {}", summary.files, file.display());
+    if summary.encrypted_secrets {
+        println!("Secrets are encrypted; the passphrase is needed to import them.");
+    } else if !summary.removed_secrets.is_empty() {
+        println!("Left out (use --include-secrets to keep them):");
+        for name in &summary.removed_secrets {
+            println!("  {}", name);
+        }
+    }
+    Ok(())
+}
+
+fn import(file: &Path, profile: Option<&str>, force: bool) -> Result<()> {
+    select(profile, false)?;
+    let summary = import_profile(file, force, || {
+        let passphrase = rpassword::prompt_password(
+            "Archive passphrase (empty to skip the encrypted secrets): ",
+        )?;
+        Ok((!passphrase.is_empty()).then_some(passphrase))
+    })?;
+
+    println!("Imported {} files", summary.files);
+    println!("Workspace: {}", summary.workspace.display());
+    if summary.secrets_restored > 0 {
+        println!("Restored {} secrets", summary.secrets_restored);
+    }
+    if !summary.missing_secrets.is_empty() {
+        println!("Not in the archive, set these again:");
+        for name in &summary.missing_secrets {
+            println!("  {}", name);
+        }
+    }
+    println!("Rebuild the memory index with: localgpt memory reindex");
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    println!("{}  (shared ~/.localgpt)", DEFAULT_PROFILE);
+    for p in list_profiles()? {
+        println!(
+            "{}{}  (created {})",
+            p.name,
+            if p.has_passphrase() { " [locked]" } else { "" },
+            p.created_at.format("%Y-%m-%d")
+        );
+    }
+    Ok(())
+}
+
+fn create(name: &str, with_passphrase: bool) -> Result<()> {
+    let passphrase = if with_passphrase {
+        Some(prompt_new_passphrase()?)
+    } else {
+        None
+    };
+    create_profile(name, passphrase.as_deref())?;
+
+    println!("Created profile '{}'", name);
+    println!("Use it with: localgpt --user {} chat", name);
+    Ok(())
+}
+
+fn change_passphrase(name: &str, clear: bool) -> Result<()> {
+    let mut p = load_profile(name)?;
+    if p.has_passphrase() {
+        let current = rpassword::prompt_password(format!("Current passphrase for '{}': ", name))?;
+        if !p.verify_passphrase(&current) {
+            anyhow::bail!("Incorrect passphrase");
+        }
+    }
+
+    if clear {
+        p.set_passphrase(None)?;
+        println!("Removed passphrase from '{}'", name);
+    } else {
+        p.set_passphrase(Some(&prompt_new_passphrase()?))?;
+        println!("Updated passphrase for '{}'", name);
+    }
+    save_profile(&p)
+}
+
+fn prompt_new_passphrase() -> Result<String> {
+    let first = rpassword::prompt_password("New passphrase: ")?;
+    if first.is_empty() {
+        anyhow::bail!("Passphrase cannot be empty");
+    }
+    let second = rpassword::prompt_password("Repeat passphrase: ")?;
+    if first != second {
+        anyhow::bail!("Passphrases do not match");
+    }
+    Ok(first)
+}
+
+/// Activate the requested profile (checking its passphrase), or let the user
+/// pick one when none was requested, profiles exist, and `prompt_if_unset` is set.
+pub fn select(requested: Option<&str>, prompt_if_unset: bool) -> Result<()> {
+    let name = match requested {
+        Some(name) => name.to_string(),
+        None if prompt_if_unset && io::stdin().is_terminal() => match prompt_choice()? {
+            Some(name) => name,
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+
+    if name == DEFAULT_PROFILE {
+        return Ok(());
+    }
+
+    let p = load_profile(&name)?;
+    if p.has_passphrase() {
+        let passphrase = match std::env::var(USER_PASSPHRASE_ENV) {
+            Ok(passphrase) => passphrase,
+            Err(_) => rpassword::prompt_password(format!("Passphrase for '{}': ", name))?,
+        };
+        if !p.verify_passphrase(&passphrase) {
+            anyhow::bail!("Incorrect passphrase for profile '{}'", name);
+        }
+    }
+
+    set_active_profile(&name)
+}
+
+/// Startup profile switcher. Returns None for the shared default profile.
+fn prompt_choice() -> Result<Option<String>> {
+    let profiles = list_profiles()?;
+    if profiles.is_empty() {
+        return Ok(None);
+    }
+
+    println!("Select a profile:");
+    println!("  0) {}", DEFAULT_PROFILE);
+    for (i, p) in profiles.iter().enumerate() {
+        println!("  {}) {}", i + 1, p.name);
+    }
+    print!("Profile [0]: ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+
+    if input.is_empty() || input == "0" || input == DEFAULT_PROFILE {
+        return Ok(None);
+    }
+    if let Ok(n) = input.parse::<usize>() {
+        return profiles
+            .get(n.wrapping_sub(1))
+            .map(|p| Some(p.name.clone()))
+            .ok_or_else(|| anyhow::anyhow!("No profile numbered {}", n));
+    }
+    Ok(Some(input.to_string()))
+}
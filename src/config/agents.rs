@@ -0,0 +1,208 @@
+//! Named agents with their own settings
+//!
+//! Every agent ID (`--agent`, default "main") keeps its sessions under
+//! `~/.localgpt/agents/<id>/`. An `agent.toml` there overrides config.toml
+//! for that agent only:
+//!
+//! ```toml
+//! description = "Research assistant"
+//! model = "anthropic/claude-sonnet-4-5"
+//! workspace = "~/research"
+//! system_prompt = "Cite a source for every claim."
+//! tools = ["web_search", "fetch_url", "read_file", "memory_search"]
+//! ```
+//!
+//! Unset fields fall back to config.toml. Agents without a workspace share
+//! the default one (and so its memory).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{localgpt_home, Config};
+
+/// Per-agent settings file, in the agent's directory
+pub const AGENT_FILE: &str = "agent.toml";
+
+/// ID used when `--agent` isn't given
+const MAIN_AGENT: &str = "main";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentProfile {
+    /// Shown next to the ID in agent lists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Replaces `agent.default_model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Replaces `memory.workspace`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+
+    /// Added to the system prompt of every session (`agent.system_prompt`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+
+    /// The only tools the agent gets (`tools.allowed`; empty for those
+    /// config.toml enables)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+}
+
+/// `~/.localgpt/agents` (per user profile)
+pub fn agents_dir() -> Result<PathBuf> {
+    Ok(localgpt_home()?.join("agents"))
+}
+
+/// `~/.localgpt/agents/<id>`, where the agent's sessions and settings live
+pub fn agent_dir(agent_id: &str) -> Result<PathBuf> {
+    validate_agent_id(agent_id)?;
+    Ok(agents_dir()?.join(agent_id))
+}
+
+pub fn validate_agent_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "Invalid agent ID '{}'. Use up to 64 letters, digits, '-' or '_'.",
+            id
+        );
+    }
+    Ok(())
+}
+
+impl AgentProfile {
+    /// `agents/<id>/agent.toml`
+    pub fn path(agent_id: &str) -> Result<PathBuf> {
+        Ok(agent_dir(agent_id)?.join(AGENT_FILE))
+    }
+
+    /// The agent's settings, or None if it has no agent.toml
+    pub fn load(agent_id: &str) -> Result<Option<Self>> {
+        Self::load_from(&Self::path(agent_id)?)
+    }
+
+    fn load_from(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(path)?;
+        let profile =
+            toml::from_str(&text).with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(Some(profile))
+    }
+
+    pub fn save(&self, agent_id: &str) -> Result<()> {
+        let path = Self::path(agent_id)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Override `config` with the fields that are set
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(ref model) = self.model {
+            config.agent.default_model = model.clone();
+        }
+        if let Some(ref workspace) = self.workspace {
+            config.memory.workspace = workspace.clone();
+        }
+        if let Some(ref prompt) = self.system_prompt {
+            config.agent.system_prompt = Some(prompt.clone());
+        }
+        if !self.tools.is_empty() {
+            config.tools.allowed = self.tools.clone();
+        }
+    }
+}
+
+/// "main" plus every agent with a directory under `agents/`, sorted
+pub fn list_agents() -> Result<Vec<String>> {
+    list_agents_in(&agents_dir()?)
+}
+
+fn list_agents_in(dir: &Path) -> Result<Vec<String>> {
+    let mut agents = vec![MAIN_AGENT.to_string()];
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let Some(id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if entry.path().is_dir() && validate_agent_id(&id).is_ok() && !agents.contains(&id) {
+                agents.push(id);
+            }
+        }
+    }
+    agents.sort();
+    Ok(agents)
+}
+
+impl Config {
+    /// config.toml with the agent's agent.toml applied
+    pub fn load_for_agent(agent_id: &str) -> Result<Self> {
+        Self::load()?.for_agent(agent_id)
+    }
+
+    /// A copy of this config with the agent's agent.toml applied. Don't
+    /// `save()` the result: the overrides would end up in config.toml.
+    pub fn for_agent(&self, agent_id: &str) -> Result<Self> {
+        let mut config = self.clone();
+        if let Some(profile) = AgentProfile::load(agent_id)? {
+            profile.apply(&mut config);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_profile_overrides_config() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let path = temp.path().join("researcher").join(AGENT_FILE);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(
+            &path,
+            "model = \"ollama/qwen3\"\nsystem_prompt = \"Cite sources.\"\ntools = [\"web_search\"]\n",
+        )?;
+
+        let profile = AgentProfile::load_from(&path)?.unwrap();
+        let mut config = Config::default();
+        let workspace = config.memory.workspace.clone();
+        profile.apply(&mut config);
+        assert_eq!(config.agent.default_model, "ollama/qwen3");
+        assert_eq!(config.agent.system_prompt.as_deref(), Some("Cite sources."));
+        assert_eq!(config.tools.allowed, vec!["web_search"]);
+        assert_eq!(config.memory.workspace, workspace);
+
+        fs::create_dir_all(temp.path().join("coder"))?;
+        fs::create_dir_all(temp.path().join("main/sessions"))?;
+        assert_eq!(
+            list_agents_in(temp.path())?,
+            vec!["coder", "main", "researcher"]
+        );
+        assert!(AgentProfile::load_from(&temp.path().join("coder").join(AGENT_FILE))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_agent_id() {
+        assert!(validate_agent_id("researcher").is_ok());
+        assert!(validate_agent_id("code-review_2").is_ok());
+        assert!(validate_agent_id("").is_err());
+        assert!(validate_agent_id("../main").is_err());
+        assert!(validate_agent_id("a b").is_err());
+    }
+}
@@ -0,0 +1,592 @@
+//! Profile export and import
+//!
+//! `localgpt profile export` packs the active profile's home (config, agents
+//! and sessions, skills) and its workspace (memory files, SOUL.md and other
+//! prompts) into one `.tar.gz`. Memory indexes, snapshots, logs and lock
+//! files are left out; the index is rebuilt on first use after import.
+//! Import puts the workspace in the target profile's home, wherever the
+//! archived config kept it, and points the imported config there.
+//!
+//! API keys, tokens and passwords are removed from the exported config.
+//! With a passphrase they travel in `secrets.enc` instead, encrypted with
+//! ChaCha20-Poly1305 under an Argon2-derived key, together with any keys
+//! saved in the OS keyring.
+
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use super::{active_profile, localgpt_home, store_api_key, stored_api_key, Config};
+
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const CONFIG: &str = "config.toml";
+const SECRETS: &str = "secrets.enc";
+const HOME_PREFIX: &str = "home";
+const WORKSPACE_PREFIX: &str = "workspace";
+/// Where an imported workspace goes, under the target home
+const WORKSPACE_DIR: &str = "workspace";
+
+/// Top-level home entries that are machine-specific or rebuilt on demand
+const SKIPPED_HOME_ENTRIES: &[&str] = &[CONFIG, "profiles", "snapshots", "logs", "memory"];
+
+/// File suffixes never archived (indexes, locks, temp files)
+const SKIPPED_SUFFIXES: &[&str] = &[
+    ".sqlite",
+    ".sqlite-wal",
+    ".sqlite-shm",
+    ".lock",
+    ".pid",
+    ".tmp",
+];
+
+/// Providers whose keys may be saved in the OS keyring
+const KEYRING_PROVIDERS: &[&str] = &["openai", "anthropic", "gemini", "deepseek"];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: u32,
+    created_at: DateTime<Utc>,
+    localgpt_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    /// Dotted config paths whose values were removed
+    removed_secrets: Vec<String>,
+    encrypted_secrets: bool,
+}
+
+/// A config value removed from the exported config.toml
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SecretValue {
+    path: Vec<String>,
+    value: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Secrets {
+    config: Vec<SecretValue>,
+    /// Provider → API key from the OS keyring
+    keyring: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedSecrets {
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+#[derive(Debug)]
+pub struct ExportSummary {
+    pub files: usize,
+    /// Secret values left out of the archive (not encrypted into it)
+    pub removed_secrets: Vec<String>,
+    pub encrypted_secrets: bool,
+}
+
+#[derive(Debug)]
+pub struct ImportSummary {
+    pub files: usize,
+    pub workspace: PathBuf,
+    pub secrets_restored: usize,
+    /// Secret values the archive did not carry; set them again
+    pub missing_secrets: Vec<String>,
+}
+
+/// Write the active profile to `dest`. With a passphrase, secrets are
+/// encrypted into the archive; without one they are left out.
+pub fn export_profile(dest: &Path, passphrase: Option<&str>) -> Result<ExportSummary> {
+    let home = localgpt_home()?;
+    let config = Config::load()?;
+    let workspace = config.workspace_path();
+    let custom = config.providers.custom.iter().map(|c| c.name.clone());
+    let mut entries: Vec<String> = KEYRING_PROVIDERS
+        .iter()
+        .map(|p| p.to_string())
+        .chain(custom)
+        .chain(config.keyring_entries())
+        .collect();
+    entries.sort();
+    entries.dedup();
+    let keyring = entries
+        .into_iter()
+        .filter_map(|entry| Some((entry.clone(), stored_api_key(&entry)?)))
+        .collect();
+    export_archive(&home, &workspace, keyring, dest, passphrase)
+}
+
+/// Unpack an archive into the active profile. `passphrase` is asked for
+/// only when the archive carries encrypted secrets.
+pub fn import_profile(
+    archive: &Path,
+    force: bool,
+    passphrase: impl FnOnce() -> Result<Option<String>>,
+) -> Result<ImportSummary> {
+    let (summary, keyring) = import_archive(archive, &localgpt_home()?, force, passphrase)?;
+    for (provider, key) in &keyring {
+        store_api_key(provider, key)?;
+    }
+    Ok(summary)
+}
+
+fn export_archive(
+    home: &Path,
+    workspace: &Path,
+    keyring: Vec<(String, String)>,
+    dest: &Path,
+    passphrase: Option<&str>,
+) -> Result<ExportSummary> {
+    let mut secrets = Secrets {
+        config: Vec::new(),
+        keyring,
+    };
+    let config = match fs::read_to_string(home.join(CONFIG)) {
+        Ok(text) => {
+            let mut value: toml::Value = toml::from_str(&text).context("Invalid config.toml")?;
+            strip_secrets(&mut value, &mut Vec::new(), &mut secrets.config);
+            Some(toml::to_string_pretty(&value)?)
+        }
+        Err(_) => None,
+    };
+
+    let mut removed_secrets: Vec<String> =
+        secrets.config.iter().map(|s| s.path.join(".")).collect();
+    removed_secrets.extend(
+        secrets
+            .keyring
+            .iter()
+            .map(|(provider, _)| format!("keyring:{}", provider)),
+    );
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        created_at: Utc::now(),
+        localgpt_version: env!("CARGO_PKG_VERSION").to_string(),
+        profile: active_profile().map(str::to_string),
+        removed_secrets: removed_secrets.clone(),
+        encrypted_secrets: passphrase.is_some(),
+    };
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file =
+        File::create(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    append_bytes(&mut tar, MANIFEST, &serde_json::to_vec_pretty(&manifest)?)?;
+    if let Some(ref config) = config {
+        append_bytes(&mut tar, CONFIG, config.as_bytes())?;
+    }
+    if let Some(passphrase) = passphrase {
+        let sealed = encrypt_secrets(&secrets, passphrase)?;
+        append_bytes(&mut tar, SECRETS, &serde_json::to_vec_pretty(&sealed)?)?;
+    }
+
+    let mut files = 0;
+    // The workspace usually lives inside the home; archive it once, on its own
+    let skip_home = |path: &Path| {
+        path == workspace
+            || path.parent() == Some(home)
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| SKIPPED_HOME_ENTRIES.contains(&n))
+    };
+    append_dir(
+        &mut tar,
+        home,
+        Path::new(HOME_PREFIX),
+        &skip_home,
+        &mut files,
+    )?;
+    if workspace.is_dir() {
+        append_dir(
+            &mut tar,
+            workspace,
+            Path::new(WORKSPACE_PREFIX),
+            &|_| false,
+            &mut files,
+        )?;
+    }
+    tar.into_inner()?.finish()?;
+
+    Ok(ExportSummary {
+        files,
+        removed_secrets: if passphrase.is_some() {
+            Vec::new()
+        } else {
+            removed_secrets
+        },
+        encrypted_secrets: passphrase.is_some(),
+    })
+}
+
+fn append_bytes<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Add the regular files under `dir`, skipping paths `skip` rejects
+fn append_dir<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    dir: &Path,
+    prefix: &Path,
+    skip: &dyn Fn(&Path) -> bool,
+    files: &mut usize,
+) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        if skip(&path)
+            || SKIPPED_SUFFIXES
+                .iter()
+                .any(|suffix| name.to_string_lossy().ends_with(suffix))
+        {
+            continue;
+        }
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let archived = prefix.join(&name);
+        if meta.is_dir() {
+            append_dir(tar, &path, &archived, skip, files)?;
+        } else if meta.is_file() {
+            tar.append_path_with_name(&path, &archived)
+                .with_context(|| format!("Failed to archive {}", path.display()))?;
+            *files += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Restore into `home`. Returns the summary and keyring keys to save.
+fn import_archive(
+    archive: &Path,
+    home: &Path,
+    force: bool,
+    passphrase: impl FnOnce() -> Result<Option<String>>,
+) -> Result<(ImportSummary, Vec<(String, String)>)> {
+    if home.join(CONFIG).exists() && !force {
+        anyhow::bail!(
+            "{} already has a config. Import into a new profile \
+            (localgpt profile create <name>), or pass --force to overwrite.",
+            home.display()
+        );
+    }
+
+    // First pass: manifest, config and secrets
+    let mut manifest: Option<Manifest> = None;
+    let mut config: Option<String> = None;
+    let mut sealed: Option<EncryptedSecrets> = None;
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut content = String::new();
+        match path.as_str() {
+            MANIFEST => {
+                entry.read_to_string(&mut content)?;
+                manifest = Some(serde_json::from_str(&content)?);
+            }
+            CONFIG => {
+                entry.read_to_string(&mut content)?;
+                config = Some(content);
+            }
+            SECRETS => {
+                entry.read_to_string(&mut content)?;
+                sealed = Some(serde_json::from_str(&content)?);
+            }
+            _ => {}
+        }
+    }
+    let manifest = manifest.ok_or_else(|| anyhow::anyhow!("Not a LocalGPT profile archive"))?;
+    if manifest.format > FORMAT_VERSION {
+        anyhow::bail!(
+            "Archive was made by a newer LocalGPT ({}); upgrade to import it",
+            manifest.localgpt_version
+        );
+    }
+
+    let secrets = match sealed {
+        Some(sealed) => match passphrase()? {
+            Some(passphrase) => decrypt_secrets(&sealed, &passphrase)?,
+            None => Secrets::default(),
+        },
+        None => Secrets::default(),
+    };
+
+    let mut missing_secrets: Vec<String> = manifest.removed_secrets.clone();
+    missing_secrets.retain(|name| {
+        !secrets.config.iter().any(|s| &s.path.join(".") == name)
+            && !secrets
+                .keyring
+                .iter()
+                .any(|(provider, _)| name == &format!("keyring:{}", provider))
+    });
+
+    // The archive's own memory.workspace could point anywhere on this machine
+    let workspace = home.join(WORKSPACE_DIR);
+    if let Some(ref text) = config {
+        let mut value: toml::Value = toml::from_str(text)?;
+        restore_secrets(&mut value, &secrets.config);
+        set_workspace(&mut value, &workspace)?;
+        let text = toml::to_string_pretty(&value)?;
+        toml::from_str::<Config>(&text)?;
+        fs::create_dir_all(home)?;
+        fs::write(home.join(CONFIG), &text)?;
+    }
+
+    // Second pass: files
+    let mut files = 0;
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let (root, rel) = if let Ok(rel) = path.strip_prefix(HOME_PREFIX) {
+            (home, rel.to_path_buf())
+        } else if let Ok(rel) = path.strip_prefix(WORKSPACE_PREFIX) {
+            (workspace.as_path(), rel.to_path_buf())
+        } else {
+            continue;
+        };
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            anyhow::bail!("Archive contains an unsafe path: {}", path.display());
+        }
+        let target = root.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+        files += 1;
+    }
+
+    Ok((
+        ImportSummary {
+            files,
+            workspace,
+            secrets_restored: secrets.config.len() + secrets.keyring.len(),
+            missing_secrets,
+        },
+        secrets.keyring,
+    ))
+}
+
+/// Point a config's `memory.workspace` at `workspace`
+fn set_workspace(config: &mut toml::Value, workspace: &Path) -> Result<()> {
+    let memory = config
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("Invalid config.toml"))?
+        .entry("memory")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let memory = memory
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("Invalid config.toml: [memory] is not a table"))?;
+    memory.insert(
+        "workspace".to_string(),
+        toml::Value::String(workspace.to_string_lossy().to_string()),
+    );
+    Ok(())
+}
+
+fn open_archive(path: &Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// Config keys holding credentials
+fn is_secret_key(key: &str) -> bool {
+    key == "api_key"
+        || key.ends_with("_api_key")
+        || key.ends_with("token")
+        || key.ends_with("password")
+        || key.ends_with("secret")
+}
+
+/// Blank out credentials, collecting them in `out`. `${VAR}` references
+/// hold no secret and are kept.
+fn strip_secrets(value: &mut toml::Value, path: &mut Vec<String>, out: &mut Vec<SecretValue>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                path.push(key.clone());
+                match value {
+                    toml::Value::String(s)
+                        if is_secret_key(key) && !s.is_empty() && !s.starts_with('$') =>
+                    {
+                        out.push(SecretValue {
+                            path: path.clone(),
+                            value: std::mem::take(s),
+                        });
+                    }
+                    _ => strip_secrets(value, path, out),
+                }
+                path.pop();
+            }
+        }
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                strip_secrets(item, path, out);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn restore_secrets(value: &mut toml::Value, secrets: &[SecretValue]) {
+    for secret in secrets {
+        if let Some(toml::Value::String(s)) = lookup_mut(value, &secret.path) {
+            *s = secret.value.clone();
+        }
+    }
+}
+
+fn lookup_mut<'a>(value: &'a mut toml::Value, path: &[String]) -> Option<&'a mut toml::Value> {
+    let Some((part, rest)) = path.split_first() else {
+        return Some(value);
+    };
+    let next = match value {
+        toml::Value::Table(table) => table.get_mut(part)?,
+        toml::Value::Array(items) => items.get_mut(part.parse::<usize>().ok()?)?,
+        _ => return None,
+    };
+    lookup_mut(next, rest)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive key: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt_secrets(secrets: &Secrets, passphrase: &str) -> Result<EncryptedSecrets> {
+    let salt = uuid::Uuid::new_v4();
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt.as_bytes())?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let data = cipher
+        .encrypt(&nonce, serde_json::to_vec(secrets)?.as_slice())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt secrets"))?;
+    Ok(EncryptedSecrets {
+        salt: BASE64.encode(salt.as_bytes()),
+        nonce: BASE64.encode(nonce),
+        data: BASE64.encode(data),
+    })
+}
+
+fn decrypt_secrets(sealed: &EncryptedSecrets, passphrase: &str) -> Result<Secrets> {
+    let salt = BASE64.decode(&sealed.salt)?;
+    let nonce = BASE64.decode(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        anyhow::bail!("Corrupt secrets in archive");
+    }
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let data = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            BASE64.decode(&sealed.data)?.as_slice(),
+        )
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase for the archive's secrets"))?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path().join("old");
+        let workspace = home.join("workspace");
+        write(
+            &home.join(CONFIG),
+            &format!(
+                "[memory]\nworkspace = \"{}\"\n\n[providers.openai]\napi_key = \"sk-secret\"\n\n\
+                [providers.anthropic]\napi_key = \"${{ANTHROPIC_API_KEY}}\"\n",
+                tmp.path().join("new-workspace").display()
+            ),
+        );
+        write(&home.join("agents/main/sessions/s1.jsonl"), "{}");
+        write(&home.join("skills/review/SKILL.md"), "# Review");
+        write(&home.join("memory/main.sqlite"), "index");
+        write(&home.join("workspace.lock"), "");
+        write(&workspace.join("MEMORY.md"), "- likes tea");
+
+        // Without a passphrase the key is dropped
+        let plain = tmp.path().join("plain.tar.gz");
+        let summary = export_archive(&home, &workspace, Vec::new(), &plain, None).unwrap();
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.removed_secrets, vec!["providers.openai.api_key"]);
+
+        let target = tmp.path().join("plain-home");
+        let (imported, _) = import_archive(&plain, &target, false, || Ok(None)).unwrap();
+        let config = fs::read_to_string(target.join(CONFIG)).unwrap();
+        assert!(!config.contains("sk-secret"));
+        assert!(config.contains("${ANTHROPIC_API_KEY}"));
+        assert_eq!(imported.missing_secrets, vec!["providers.openai.api_key"]);
+        assert!(target.join("skills/review/SKILL.md").exists());
+        assert!(!target.join("memory/main.sqlite").exists());
+        // The workspace lands in the new home, not where the archive's config said
+        assert_eq!(imported.workspace, target.join("workspace"));
+        assert!(!tmp.path().join("new-workspace").exists());
+        assert_eq!(
+            toml::from_str::<Config>(&config).unwrap().memory.workspace,
+            target.join("workspace").to_string_lossy()
+        );
+        assert_eq!(
+            fs::read_to_string(imported.workspace.join("MEMORY.md")).unwrap(),
+            "- likes tea"
+        );
+
+        // Existing config is not overwritten without --force
+        assert!(import_archive(&plain, &target, false, || Ok(None)).is_err());
+
+        // With a passphrase the key and keyring entries come back
+        let sealed = tmp.path().join("sealed.tar.gz");
+        let keyring = vec![("gemini".to_string(), "g-key".to_string())];
+        export_archive(&home, &workspace, keyring, &sealed, Some("pw")).unwrap();
+        let target = tmp.path().join("sealed-home");
+        assert!(import_archive(&sealed, &target, true, || Ok(Some("bad".into()))).is_err());
+        let (imported, keyring) =
+            import_archive(&sealed, &target, true, || Ok(Some("pw".into()))).unwrap();
+        assert!(fs::read_to_string(target.join(CONFIG))
+            .unwrap()
+            .contains("sk-secret"));
+        assert_eq!(imported.secrets_restored, 2);
+        assert!(imported.missing_secrets.is_empty());
+        assert_eq!(keyring, vec![("gemini".to_string(), "g-key".to_string())]);
+    }
+}
@@ -8,7 +8,7 @@ use std::fs;
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
 
-use super::{AnthropicConfig, ClaudeCliConfig, Config, OpenAIConfig};
+use super::{AnthropicConfig, ClaudeCliConfig, Config, NetworkConfig, OpenAIConfig, TimeoutConfig};
 
 /// OpenClaw config structure (partial - only fields we can migrate)
 #[derive(Debug, Deserialize)]
@@ -124,7 +124,7 @@ fn convert_openclaw_config(oc: OpenClawConfig) -> Config {
             }
 
             if let Some(context_window) = defaults.context_window {
-                config.agent.context_window = context_window;
+                config.agent.context_window = Some(context_window);
             }
         }
     }
@@ -136,6 +136,9 @@ fn convert_openclaw_config(oc: OpenClawConfig) -> Config {
                 config.providers.openai = Some(OpenAIConfig {
                     api_key,
                     base_url: "https://api.openai.com/v1".to_string(),
+                    reasoning_effort: None,
+                    timeouts: TimeoutConfig::default(),
+                    network: NetworkConfig::default(),
                 });
             }
         }
@@ -145,6 +148,9 @@ fn convert_openclaw_config(oc: OpenClawConfig) -> Config {
                 config.providers.anthropic = Some(AnthropicConfig {
                     api_key,
                     base_url: "https://api.anthropic.com".to_string(),
+                    thinking_budget_tokens: 0,
+                    timeouts: TimeoutConfig::default(),
+                    network: NetworkConfig::default(),
                 });
             }
         }
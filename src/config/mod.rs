@@ -1,13 +1,23 @@
+pub mod agents;
+mod archive;
 mod migrate;
+pub mod profile;
 mod schema;
+mod secrets;
 
+pub use agents::{list_agents, AgentProfile};
+pub use archive::{export_profile, import_profile, ExportSummary, ImportSummary};
 pub use migrate::{has_openclaw_workspace, openclaw_config_path, try_migrate_openclaw_config};
+pub use profile::{active_profile, localgpt_home};
 pub use schema::*;
+pub use secrets::{keyring_entry, store_api_key, stored_api_key};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
@@ -17,6 +27,12 @@ pub struct Config {
     #[serde(default)]
     pub providers: ProvidersConfig,
 
+    #[serde(default)]
+    pub models: ModelsConfig,
+
+    #[serde(default)]
+    pub budget: BudgetConfig,
+
     #[serde(default)]
     pub heartbeat: HeartbeatConfig,
 
@@ -31,6 +47,29 @@ pub struct Config {
 
     #[serde(default)]
     pub tools: ToolsConfig,
+
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+
+    #[serde(default)]
+    pub voice: VoiceConfig,
+
+    #[serde(default)]
+    pub ocr: OcrConfig,
+
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+
+    /// API keys as written in config.toml (`${VAR}`, `keyring`, ...), by
+    /// provider, where loading resolved them to another value. Written back
+    /// on save so resolved secrets don't end up in the file.
+    #[serde(skip)]
+    api_key_refs: HashMap<String, String>,
+
+    /// Why a provider's API key couldn't be resolved (unset environment
+    /// variable, missing keyring entry), reported when the provider is used
+    #[serde(skip)]
+    api_key_errors: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,8 +77,11 @@ pub struct AgentConfig {
     #[serde(default = "default_model")]
     pub default_model: String,
 
-    #[serde(default = "default_context_window")]
-    pub context_window: usize,
+    /// Context window for every model, ahead of the providers' published
+    /// limits (`[models.context_windows]` still wins). Unset uses those
+    /// limits, and 128000 for models without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<usize>,
 
     #[serde(default = "default_reserve_tokens")]
     pub reserve_tokens: usize,
@@ -47,6 +89,86 @@ pub struct AgentConfig {
     /// Maximum tokens for LLM response
     #[serde(default = "default_max_tokens")]
     pub max_tokens: usize,
+
+    /// Seconds between saves of an in-progress turn (streamed text, tool calls)
+    /// so a crash mid-turn loses at most this much (0 disables)
+    #[serde(default = "default_checkpoint_interval_secs")]
+    pub checkpoint_interval_secs: u64,
+
+    /// Rounds of tool calls in one turn before the agent stops and says so
+    /// (0 = unlimited)
+    #[serde(default = "default_max_tool_iterations")]
+    pub max_tool_iterations: usize,
+
+    /// Failed tool calls in a row before the turn stops (0 = unlimited)
+    #[serde(default = "default_max_failed_tool_calls")]
+    pub max_failed_tool_calls: usize,
+
+    /// Seconds a turn may run before it stops at the next round of tool
+    /// calls (0 = unlimited)
+    #[serde(default)]
+    pub max_turn_secs: u64,
+
+    /// Instructions added to the system prompt of every session (usually
+    /// set per agent, in `agents/<id>/agent.toml`)
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+
+    /// Instruction used when compacting history into a summary
+    /// (default: keep key information and context)
+    #[serde(default)]
+    pub compaction_prompt: Option<String>,
+
+    /// How history is compacted when the context fills up; `/compact
+    /// strategy <name>` changes it for one session
+    #[serde(default)]
+    pub compaction_strategy: CompactionStrategy,
+
+    /// Latest messages kept as they are by the keep-recent and
+    /// rolling-summary strategies
+    #[serde(default = "default_compaction_keep_recent")]
+    pub compaction_keep_recent: usize,
+
+    /// Summarize on a background thread once the context is nearly full,
+    /// swapping the summary in when it is ready, instead of making the next
+    /// turn wait for it. `/compact` runs in the background either way.
+    #[serde(default = "default_true")]
+    pub background_compaction: bool,
+
+    /// Have the model title sessions saved to memory (otherwise the title
+    /// comes from the first message's words)
+    #[serde(default)]
+    pub llm_session_titles: bool,
+
+    /// Instruction used for session titles
+    #[serde(default)]
+    pub title_prompt: Option<String>,
+
+    /// Small/cheap model for background work: session titles, compaction
+    /// and tool output summaries (default: the main model)
+    #[serde(default)]
+    pub auxiliary_model: Option<String>,
+
+    /// Model for compaction summaries and session titles (default: the
+    /// `summarizer` model alias, then `auxiliary_model`)
+    #[serde(default)]
+    pub summarize_model: Option<String>,
+
+    /// Show model reasoning ("thinking") from providers that stream it, in a
+    /// collapsible panel; false discards it as it arrives
+    #[serde(default = "default_true")]
+    pub keep_reasoning: bool,
+
+    /// Summarize thumbs-up/down ratings into a few preferences ("prefers
+    /// concise answers with code first") and add them to the system prompt
+    #[serde(default)]
+    pub feedback_summary: bool,
+
+    /// Token budget for the repository map (source files and the symbols
+    /// they define) added to the system prompt when the workspace contains
+    /// code; it is refreshed as files change (0 disables)
+    #[serde(default = "default_repo_map_tokens")]
+    pub repo_map_tokens: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +181,21 @@ pub struct ToolsConfig {
     #[serde(default = "default_web_fetch_max_bytes")]
     pub web_fetch_max_bytes: usize,
 
+    /// Tools left out of every agent, built-in or registered by an
+    /// embedding application, e.g. ["bash", "web_fetch"]
+    #[serde(default)]
+    pub disabled: Vec<String>,
+
+    /// When set, the only tools agents get (MCP tools included); usually set
+    /// per agent, in `agents/<id>/agent.toml`
+    #[serde(default)]
+    pub allowed: Vec<String>,
+
+    /// Directories besides the workspace that file tools (read_file,
+    /// write_file, grep_workspace, ...) may use
+    #[serde(default)]
+    pub allowed_dirs: Vec<String>,
+
     /// Tools that require user approval before execution
     /// e.g., ["bash", "write_file", "edit_file"]
     #[serde(default)]
@@ -75,6 +212,128 @@ pub struct ToolsConfig {
     /// Wrap tool outputs and memory content with XML-style delimiters
     #[serde(default = "default_true")]
     pub use_content_delimiters: bool,
+
+    /// Require approval for any tool call made right after untrusted content
+    /// (web pages, file contents) entered the context
+    #[serde(default)]
+    pub approve_after_untrusted: bool,
+
+    /// Enable capture_screen / capture_window tools (always require per-use approval)
+    #[serde(default)]
+    pub screen_capture: bool,
+
+    /// Enable the clipboard tool (read the clipboard, copy text to it)
+    #[serde(default)]
+    pub clipboard: bool,
+
+    /// Enable the notify tool (native desktop notifications)
+    #[serde(default)]
+    pub notifications: bool,
+
+    /// Domains the headless browser tool may visit (subdomains included).
+    /// The browser tool is disabled while this is empty. Requires the `browser` feature.
+    #[serde(default)]
+    pub browser_allowed_domains: Vec<String>,
+
+    /// Path to the Chrome/Chromium executable (auto-detected if unset)
+    #[serde(default)]
+    pub browser_executable: Option<String>,
+
+    /// Image generation backend for the generate_image tool:
+    /// "automatic1111", "comfyui", or "openai" (empty disables the tool)
+    #[serde(default)]
+    pub image_generation: String,
+
+    /// Backend base URL (defaults: 127.0.0.1:7860 for AUTOMATIC1111,
+    /// 127.0.0.1:8188 for ComfyUI, providers.openai.base_url for OpenAI)
+    #[serde(default)]
+    pub image_generation_url: Option<String>,
+
+    /// Checkpoint (Stable Diffusion, required for ComfyUI) or model (OpenAI, default gpt-image-1)
+    #[serde(default)]
+    pub image_generation_model: Option<String>,
+
+    /// Snapshot files before write_file, edit_file, and bash calls so the
+    /// agent's last turn of changes can be undone (`/undo`)
+    #[serde(default = "default_true")]
+    pub workspace_snapshots: bool,
+
+    /// Agent IDs the message_agent tool may send messages to (empty disables it)
+    #[serde(default)]
+    pub agent_messaging: Vec<String>,
+
+    /// Ask before each cross-agent message
+    #[serde(default = "default_true")]
+    pub agent_messaging_approval: bool,
+
+    /// Child agents the spawn_subagent tool delegates tasks to
+    /// (`[tools.subagent]`)
+    #[serde(default)]
+    pub subagent: SubagentConfig,
+
+    /// Tool outputs longer than this many characters get a one-paragraph
+    /// summary shown above the raw output (0 disables)
+    #[serde(default = "default_tool_output_summary_chars")]
+    pub tool_output_summary_chars: usize,
+
+    /// Model for tool output summaries (default: `agent.auxiliary_model`,
+    /// else the session's model). A small, fast model works well here.
+    #[serde(default)]
+    pub tool_output_summary_model: Option<String>,
+
+    /// Tool outputs longer than this many tokens are shortened before they
+    /// go into the context; the full output is still shown (0 disables)
+    #[serde(default = "default_tool_output_context_tokens")]
+    pub tool_output_context_tokens: usize,
+
+    /// How outputs over `tool_output_context_tokens` are shortened
+    #[serde(default)]
+    pub tool_output_overflow: ToolOutputOverflow,
+
+    /// Model Context Protocol servers whose tools the agent is given
+    /// (`[[tools.mcp_servers]]`)
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServerConfig>,
+
+    /// Databases the sql tool can query (`[[tools.databases]]`)
+    #[serde(default)]
+    pub databases: Vec<DatabaseConfig>,
+
+    /// Mail accounts for the email tools (`[[tools.email]]`)
+    #[serde(default)]
+    pub email: Vec<EmailAccountConfig>,
+
+    /// Search engine for the web_search tool (`[tools.web_search]`)
+    #[serde(default)]
+    pub web_search: WebSearchConfig,
+
+    /// Python/JavaScript snippets for the run_code tool
+    /// (`[tools.code_interpreter]`)
+    #[serde(default)]
+    pub code_interpreter: CodeInterpreterConfig,
+
+    /// Readable page fetching for the fetch_url tool (`[tools.fetch_url]`)
+    #[serde(default)]
+    pub fetch_url: FetchUrlConfig,
+
+    /// Project test suites for the run_tests tool (`[tools.run_tests]`)
+    #[serde(default)]
+    pub run_tests: RunTestsConfig,
+
+    /// Tool calls from one reply that may run at the same time (1 runs
+    /// them one by one). Only read-only tools (`PARALLEL_SAFE_TOOLS`) run
+    /// together; any other call runs on its own.
+    #[serde(default = "default_max_parallel_tools")]
+    pub max_parallel_tools: usize,
+
+    /// Seconds a tool call may run before it fails with a timeout error
+    /// (0 = no limit). bash and run_tests also stop at their own timeouts.
+    #[serde(default = "default_tool_timeout_secs")]
+    pub tool_timeout_secs: u64,
+
+    /// Per-tool overrides of `tool_timeout_secs`, e.g. { spawn_subagent = 900 }
+    #[serde(default)]
+    pub tool_timeouts: HashMap<String, u64>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -88,42 +347,727 @@ pub struct ProvidersConfig {
     #[serde(default)]
     pub ollama: Option<OllamaConfig>,
 
-    #[serde(default)]
-    pub claude_cli: Option<ClaudeCliConfig>,
-}
+    #[serde(default)]
+    pub claude_cli: Option<ClaudeCliConfig>,
+
+    #[serde(default)]
+    pub codex_cli: Option<CodexCliConfig>,
+
+    #[serde(default)]
+    pub gemini: Option<GeminiConfig>,
+
+    #[serde(default)]
+    pub deepseek: Option<DeepSeekConfig>,
+
+    /// In-process llama.cpp inference (`gguf/*` models, `--features gguf`)
+    #[serde(default)]
+    pub gguf: Option<GgufConfig>,
+
+    /// OpenAI-compatible servers (LM Studio, vLLM, LiteLLM, llama.cpp server)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom: Vec<CustomProviderConfig>,
+
+    /// Retries for rate limits, server errors and dropped connections
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// On-disk cache of non-streaming replies
+    #[serde(default)]
+    pub cache: ResponseCacheConfig,
+
+    /// JSONL log of every request and response
+    #[serde(default)]
+    pub audit: AuditLogConfig,
+
+    /// Record replies to, or replay them from, a fixture file
+    #[serde(default)]
+    pub replay: ReplayConfig,
+}
+
+impl ProvidersConfig {
+    /// Every configured API key, for redaction
+    pub fn api_keys(&self) -> Vec<String> {
+        [
+            self.openai.as_ref().map(|p| p.api_key.clone()),
+            self.anthropic.as_ref().map(|p| p.api_key.clone()),
+            self.gemini.as_ref().map(|p| p.api_key.clone()),
+            self.deepseek.as_ref().map(|p| p.api_key.clone()),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.custom.iter().map(|c| c.api_key.clone()))
+        .filter(|key| !key.is_empty())
+        .collect()
+    }
+
+    /// The `[[providers.custom]]` entry called `name`
+    pub fn find_custom(&self, name: &str) -> Option<&CustomProviderConfig> {
+        self.custom
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// An MCP server: a child process speaking JSON-RPC on its stdin and stdout
+/// (`command`), or a remote server over streamable HTTP or HTTP+SSE (`url`).
+/// Its tools are offered as `<name>__<tool>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    pub name: String,
+
+    /// Program to run, e.g. `npx`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Extra environment variables; values may reference `${VAR}`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// Endpoint of a remote server, e.g. `https://mcp.example.com/mcp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// Headers sent to a remote server, e.g. `Authorization`; values may
+    /// reference `${VAR}`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// Agents given this server's tools (empty: every agent)
+    #[serde(default)]
+    pub agents: Vec<String>,
+
+    /// Seconds to wait for the server to answer a request
+    #[serde(default = "default_mcp_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Proxy and custom CA for a remote server
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+/// A database for the sql tool, named in its `database` argument
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    pub name: String,
+
+    /// `postgres://...`, `mysql://...`, or a SQLite file (`sqlite:` prefix
+    /// optional; relative paths are taken from the workspace). May
+    /// reference `${VAR}`, e.g. for a password.
+    pub url: String,
+
+    /// Only run statements that read. Set to false to allow changes; the
+    /// sql tool then asks for approval before each call.
+    #[serde(default = "default_true")]
+    pub read_only: bool,
+
+    /// Most rows returned per query
+    #[serde(default = "default_sql_max_rows")]
+    pub max_rows: usize,
+
+    /// Seconds a query may run
+    #[serde(default = "default_sql_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// A mail account for the email tools, named in their `account` argument
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailAccountConfig {
+    pub name: String,
+
+    /// Address mail is sent from
+    pub address: String,
+
+    /// Login name (defaults to `address`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Password or app password; may reference `${VAR}`
+    pub password: String,
+
+    pub imap_host: String,
+
+    /// 993 uses TLS from the start; other ports upgrade with STARTTLS
+    #[serde(default = "default_imap_port")]
+    pub imap_port: u16,
+
+    /// Outgoing server (email_send is unavailable for the account without it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_host: Option<String>,
+
+    /// 465 uses TLS from the start; other ports upgrade with STARTTLS
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+
+    /// IMAP folder drafts are saved to
+    #[serde(default = "default_drafts_folder")]
+    pub drafts_folder: String,
+
+    /// Seconds a mail server may take per tool call
+    #[serde(default = "default_email_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// Search engine for the web_search tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchConfig {
+    /// "searxng", "brave" or "duckduckgo" (empty disables the tool)
+    #[serde(default)]
+    pub backend: String,
+
+    /// SearxNG instance (default http://127.0.0.1:8888), or another
+    /// endpoint for the backend's API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// Brave Search API subscription token; may reference `${VAR}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+
+    /// Results returned per search
+    #[serde(default = "default_web_search_max_results")]
+    pub max_results: usize,
+
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA for search requests
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: String::new(),
+            url: None,
+            api_key: None,
+            max_results: default_web_search_max_results(),
+            timeouts: TimeoutConfig::default(),
+            network: NetworkConfig::default(),
+        }
+    }
+}
+
+/// Snippet runner for the run_code tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeInterpreterConfig {
+    /// Offer the run_code tool
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Python interpreter
+    #[serde(default = "default_python")]
+    pub python: String,
+
+    /// Node.js, for JavaScript snippets
+    #[serde(default = "default_node")]
+    pub node: String,
+
+    /// Seconds a snippet may run (also its CPU time limit)
+    #[serde(default = "default_code_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Memory limit per run in MB (0 for none)
+    #[serde(default = "default_code_memory_mb")]
+    pub memory_mb: u64,
+}
+
+impl Default for CodeInterpreterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            python: default_python(),
+            node: default_node(),
+            timeout_secs: default_code_timeout_secs(),
+            memory_mb: default_code_memory_mb(),
+        }
+    }
+}
+
+/// Child agents for the spawn_subagent tool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubagentConfig {
+    /// Offer the spawn_subagent tool
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Model children use unless the call picks another (default:
+    /// `agent.default_model`)
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Other models a call may pick for its child
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    /// Tools children may be given (empty: the parent's tools, except
+    /// spawn_subagent and message_agent)
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+/// Test suites for the run_tests tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunTestsConfig {
+    /// Seconds a test run may take
+    #[serde(default = "default_run_tests_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Python interpreter for pytest when the project has no `.venv`
+    #[serde(default = "default_python")]
+    pub python: String,
+
+    /// Failing tests reported in detail; the rest are only named
+    #[serde(default = "default_run_tests_max_failures")]
+    pub max_failures: usize,
+}
+
+impl Default for RunTestsConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_run_tests_timeout_secs(),
+            python: default_python(),
+            max_failures: default_run_tests_max_failures(),
+        }
+    }
+}
+
+/// Readable page fetching for the fetch_url tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchUrlConfig {
+    /// Most tokens of page text returned; longer pages are cut at a
+    /// paragraph boundary
+    #[serde(default = "default_fetch_url_max_tokens")]
+    pub max_tokens: usize,
+
+    /// Most bytes of a page downloaded; the rest is never read
+    #[serde(default = "default_fetch_url_max_bytes")]
+    pub max_bytes: usize,
+
+    /// Domains (and their subdomains) that may be fetched; empty allows
+    /// any domain not denied
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+
+    /// Domains (and their subdomains) that are never fetched
+    #[serde(default)]
+    pub denied_domains: Vec<String>,
+
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA for page requests
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+impl Default for FetchUrlConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: default_fetch_url_max_tokens(),
+            max_bytes: default_fetch_url_max_bytes(),
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            timeouts: TimeoutConfig::default(),
+            network: NetworkConfig::default(),
+        }
+    }
+}
+
+/// An OpenAI-compatible server, addressed as `<name>/<model>` or by any
+/// model listed in `models`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomProviderConfig {
+    pub name: String,
+
+    /// API root including the version, e.g. `http://localhost:1234/v1`
+    pub base_url: String,
+
+    /// Sent as a Bearer token; empty for servers without auth
+    #[serde(default)]
+    pub api_key: String,
+
+    /// Models routed here without the `<name>/` prefix
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIConfig {
+    pub api_key: String,
+
+    #[serde(default = "default_openai_base_url")]
+    pub base_url: String,
+
+    /// Effort for reasoning models (o1, o3, o4-mini): "low", "medium" or
+    /// "high". Unset uses OpenAI's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiConfig {
+    pub api_key: String,
+
+    #[serde(default = "default_gemini_base_url")]
+    pub base_url: String,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeepSeekConfig {
+    pub api_key: String,
+
+    #[serde(default = "default_deepseek_base_url")]
+    pub base_url: String,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicConfig {
+    pub api_key: String,
+
+    #[serde(default = "default_anthropic_base_url")]
+    pub base_url: String,
+
+    /// Token budget for extended thinking, shown as the reply's reasoning
+    /// (0 = off, otherwise at least 1024). Added on top of agent.max_tokens.
+    #[serde(default)]
+    pub thinking_budget_tokens: usize,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaConfig {
+    #[serde(default = "default_ollama_endpoint")]
+    pub endpoint: String,
+
+    #[serde(default = "default_ollama_model")]
+    pub model: String,
+
+    /// Additional Ollama servers. Requests go to the fastest reachable host
+    /// that has the model, or to a named host with `model@host`.
+    #[serde(default)]
+    pub hosts: Vec<OllamaHostConfig>,
+
+    /// Load the default model into memory at startup so the first message
+    /// doesn't wait for it
+    #[serde(default)]
+    pub warm_up: bool,
+
+    /// How long Ollama keeps a warmed-up model loaded (e.g. "30m", "-1" for forever)
+    #[serde(default = "default_ollama_keep_alive")]
+    pub keep_alive: String,
+
+    /// Request and connect timeouts
+    #[serde(flatten)]
+    pub timeouts: TimeoutConfig,
+
+    /// Proxy and custom CA
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+}
+
+impl OllamaConfig {
+    /// All hosts, starting with `endpoint` under the name "default"
+    pub fn all_hosts(&self) -> Vec<OllamaHostConfig> {
+        let mut hosts = vec![OllamaHostConfig {
+            name: DEFAULT_OLLAMA_HOST.to_string(),
+            endpoint: self.endpoint.clone(),
+            models: Vec::new(),
+        }];
+        hosts.extend(self.hosts.iter().cloned());
+        hosts
+    }
+}
+
+/// Name of the host configured by `providers.ollama.endpoint`
+pub const DEFAULT_OLLAMA_HOST: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaHostConfig {
+    /// Name used in `model@host`
+    pub name: String,
+
+    pub endpoint: String,
+
+    /// Models to route to this host. Empty means whatever the host reports
+    /// having installed.
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+/// Settings for running GGUF models in-process with llama.cpp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufConfig {
+    /// Directory searched for `gguf/<name>` models that aren't a file path
+    #[serde(default = "default_gguf_model_dir")]
+    pub model_dir: String,
+
+    /// Context window in tokens (prompt plus reply)
+    #[serde(default = "default_gguf_context_size")]
+    pub context_size: u32,
+
+    /// CPU threads for generation; unset lets llama.cpp decide
+    #[serde(default)]
+    pub threads: Option<i32>,
+
+    /// Layers to offload to the GPU (0 = CPU only)
+    #[serde(default)]
+    pub gpu_layers: u32,
+}
+
+impl Default for GgufConfig {
+    fn default() -> Self {
+        Self {
+            model_dir: default_gguf_model_dir(),
+            context_size: default_gguf_context_size(),
+            threads: None,
+            gpu_layers: 0,
+        }
+    }
+}
+
+impl GgufConfig {
+    /// Model file for `gguf/<model>`: a path, or a file in `model_dir` with
+    /// or without the `.gguf` extension
+    pub fn resolve_model(&self, model: &str) -> Result<PathBuf> {
+        let direct = PathBuf::from(shellexpand::tilde(model).to_string());
+        let dir = PathBuf::from(shellexpand::tilde(&self.model_dir).to_string());
+        let mut candidates = vec![direct, dir.join(model)];
+        if !model.ends_with(".gguf") {
+            candidates.push(dir.join(format!("{}.gguf", model)));
+        }
+
+        if let Some(found) = candidates.iter().find(|p| p.is_file()) {
+            return Ok(found.clone());
+        }
+        anyhow::bail!(
+            "GGUF model '{}' not found. Looked for:\n  {}\n\n\
+             Download a GGUF file (e.g. from https://huggingface.co/models?library=gguf)\n\
+             into {} or use gguf/<path-to-file>.",
+            model,
+            candidates
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n  "),
+            self.model_dir
+        )
+    }
+}
+
+/// HTTP timeouts for one provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeoutConfig {
+    /// Longest wait for the server to send anything (the reply, or the
+    /// next piece of a stream)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Longest wait to establish a connection
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_timeout_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+        }
+    }
+}
+
+/// Proxy and TLS trust settings for one provider
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    /// Send this provider's requests through a proxy: `http://`, `https://`,
+    /// `socks5://` or `socks5h://` (DNS resolved by the proxy), optionally
+    /// with `user:password@`. Unset follows HTTP_PROXY / HTTPS_PROXY.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Comma-separated hosts that bypass `proxy` (e.g. "localhost,.internal")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    /// PEM file of extra CA certificates to trust, for endpoints whose TLS
+    /// certificate is self-signed or issued by a private CA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+}
+
+/// Retry policy shared by the HTTP providers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Attempts per request, including the first (1 = no retries)
+    #[serde(default = "default_retry_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Wait before the first retry; doubles on each further retry
+    #[serde(default = "default_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Longest wait between attempts. A `Retry-After` longer than this
+    /// fails the request instead of waiting.
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Randomize waits so parallel requests don't retry in lockstep
+    #[serde(default = "default_true")]
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_max_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+            jitter: true,
+        }
+    }
+}
+
+/// Cache of non-streaming chat replies (summaries, titles, tests), keyed
+/// on a hash of the model, messages and tools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How long a cached reply is reused; 0 keeps it until `/cache clear`
+    #[serde(default = "default_response_cache_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_response_cache_ttl_secs(),
+        }
+    }
+}
+
+/// Audit log of provider traffic, written to rotating JSONL files in
+/// `agents/<agent>/audit/` with secrets redacted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Size at which `audit.jsonl` is rotated to `audit.1.jsonl`
+    #[serde(default = "default_audit_max_file_mb")]
+    pub max_file_mb: u64,
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OpenAIConfig {
-    pub api_key: String,
+    /// Rotated files kept besides the current one
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
 
-    #[serde(default = "default_openai_base_url")]
-    pub base_url: String,
+    /// Agent whose directory the log goes in; set by `Agent::new`, the
+    /// default agent otherwise
+    #[serde(skip)]
+    pub agent_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AnthropicConfig {
-    pub api_key: String,
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_file_mb: default_audit_max_file_mb(),
+            max_files: default_audit_max_files(),
+            agent_id: None,
+        }
+    }
+}
 
-    #[serde(default = "default_anthropic_base_url")]
-    pub base_url: String,
+/// Record provider replies to a JSONL fixture, or answer every model from
+/// one without network access (tests and demos)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    /// Append every reply to this file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<String>,
+
+    /// Answer from this file instead of calling providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OllamaConfig {
-    #[serde(default = "default_ollama_endpoint")]
-    pub endpoint: String,
+pub struct ClaudeCliConfig {
+    #[serde(default = "default_claude_cli_command")]
+    pub command: String,
 
-    #[serde(default = "default_ollama_model")]
+    #[serde(default = "default_claude_cli_model")]
     pub model: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClaudeCliConfig {
-    #[serde(default = "default_claude_cli_command")]
+pub struct CodexCliConfig {
+    #[serde(default = "default_codex_cli_command")]
     pub command: String,
 
-    #[serde(default = "default_claude_cli_model")]
+    /// Used when the model name is just `codex-cli/`
+    #[serde(default = "default_codex_cli_model")]
     pub model: String,
+
+    /// `--sandbox` for commands Codex runs: read-only, workspace-write,
+    /// or danger-full-access
+    #[serde(default = "default_codex_cli_sandbox")]
+    pub sandbox: String,
+}
+
+impl Default for CodexCliConfig {
+    fn default() -> Self {
+        Self {
+            command: default_codex_cli_command(),
+            model: default_codex_cli_model(),
+            sandbox: default_codex_cli_sandbox(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,7 +1096,8 @@ pub struct MemoryConfig {
     #[serde(default = "default_workspace")]
     pub workspace: String,
 
-    /// Embedding provider: "local" (fastembed, default), "openai", or "none"
+    /// Embedding provider: "local" (fastembed, default), "openai", "ollama",
+    /// "gguf", or "none"
     #[serde(default = "default_embedding_provider")]
     pub embedding_provider: String,
 
@@ -176,6 +1121,12 @@ pub struct MemoryConfig {
     #[serde(default = "default_index_paths")]
     pub paths: Vec<MemoryIndexPath>,
 
+    /// Document directories kept indexed for `document_search` (as with
+    /// `localgpt index`); the daemon and desktop app watch them and
+    /// reindex changed files
+    #[serde(default)]
+    pub document_dirs: Vec<String>,
+
     /// Maximum messages to save in session memory files (0 = unlimited)
     /// Similar to OpenClaw's hooks.session-memory.messages (default: 15)
     #[serde(default = "default_session_max_messages")]
@@ -185,6 +1136,28 @@ pub struct MemoryConfig {
     /// Set to 0 to preserve full message content like OpenClaw
     #[serde(default)]
     pub session_max_chars: usize,
+
+    /// At the end of a session, have the model pick out durable facts
+    /// about the user (structured output) and add the new ones to USER.md
+    #[serde(default)]
+    pub extract_facts: bool,
+
+    /// A memory saved with memory_save, remember or fact extraction that is
+    /// at least this similar to an existing bullet (cosine similarity of
+    /// embeddings, word overlap without them) replaces it. 0 disables.
+    #[serde(default = "default_dedup_similarity")]
+    pub dedup_similarity: f32,
+
+    /// Search scores of dated notes (memory/YYYY-MM-DD*.md) halve every
+    /// this many days of age. 0 disables.
+    #[serde(default)]
+    pub decay_half_life_days: u32,
+
+    /// Move dated notes neither modified nor returned by a search for this
+    /// many days out of the workspace (to state_dir/memory/archive/). 0
+    /// disables.
+    #[serde(default)]
+    pub archive_after_days: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -206,6 +1179,280 @@ pub struct ServerConfig {
     pub bind: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Moderation provider: "openai" (moderation endpoint, uses providers.openai)
+    /// or "keywords" (local regex classifier using blocked_patterns)
+    #[serde(default = "default_moderation_provider")]
+    pub provider: String,
+
+    /// What to do with flagged content: "warn", "block", or "log"
+    #[serde(default = "default_moderation_action")]
+    pub action: String,
+
+    /// Check user messages before they reach the model
+    #[serde(default = "default_true")]
+    pub check_input: bool,
+
+    /// Check model responses before they are returned
+    #[serde(default = "default_true")]
+    pub check_output: bool,
+
+    /// Case-insensitive regex patterns for the "keywords" provider
+    #[serde(default)]
+    pub blocked_patterns: Vec<String>,
+}
+
+/// Voice mode (`localgpt listen`), desktop dictation and spoken replies.
+/// Microphone input requires the `voice` feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceConfig {
+    /// Phrase that starts listening for a request
+    #[serde(default = "default_wake_word")]
+    pub wake_word: String,
+
+    /// Path to a whisper.cpp ggml model (e.g. ggml-base.en.bin)
+    #[serde(default)]
+    pub whisper_model: String,
+
+    /// Transcription language code, or "auto"
+    #[serde(default = "default_voice_language")]
+    pub language: String,
+
+    /// RMS level (0.0-1.0) above which audio counts as speech
+    #[serde(default = "default_energy_threshold")]
+    pub energy_threshold: f32,
+
+    /// Silence that ends an utterance, in milliseconds
+    #[serde(default = "default_silence_ms")]
+    pub silence_ms: u64,
+
+    /// Longest utterance recorded, in seconds
+    #[serde(default = "default_max_utterance_secs")]
+    pub max_utterance_secs: u64,
+
+    /// Speech-to-text for dictation: "whisper" (local whisper.cpp) or
+    /// "openai" (audio transcription API, needs [providers.openai])
+    #[serde(default = "default_stt_engine")]
+    pub stt_engine: String,
+
+    /// Model for stt_engine = "openai"
+    #[serde(default = "default_transcription_model")]
+    pub transcription_model: String,
+
+    /// Text-to-speech for spoken replies: "system" (say, espeak-ng or
+    /// Windows speech) or "openai" (speech API, needs [providers.openai])
+    #[serde(default = "default_tts_engine")]
+    pub tts_engine: String,
+
+    /// Model for tts_engine = "openai"
+    #[serde(default = "default_tts_model")]
+    pub tts_model: String,
+
+    /// Voice for tts_engine = "openai" (e.g. "alloy", "nova")
+    #[serde(default = "default_tts_voice")]
+    pub tts_voice: String,
+
+    /// Read assistant replies aloud in the desktop app (toggle in the toolbar)
+    #[serde(default)]
+    pub speak_replies: bool,
+}
+
+/// Alias naming the model for compaction summaries (`[models.aliases]`)
+pub const SUMMARIZER_ALIAS: &str = "summarizer";
+
+/// How a tool output too long for the context is shortened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolOutputOverflow {
+    /// Keep the start and end, dropping the middle (no model call)
+    #[default]
+    Truncate,
+    /// Condense it with the summarizer model, truncating if that fails
+    Summarize,
+}
+
+/// How older history is compacted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompactionStrategy {
+    /// Summarize everything before the current turn
+    SummarizeAll,
+    /// Fold the messages since the last compaction into its summary,
+    /// keeping the latest `compaction_keep_recent`
+    RollingSummary,
+    /// Drop the oldest messages without a summary (no model call)
+    DropOldest,
+    /// Summarize all but the latest `compaction_keep_recent` messages
+    #[default]
+    KeepRecent,
+}
+
+impl CompactionStrategy {
+    pub const ALL: [CompactionStrategy; 4] = [
+        Self::SummarizeAll,
+        Self::RollingSummary,
+        Self::DropOldest,
+        Self::KeepRecent,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SummarizeAll => "summarize-all",
+            Self::RollingSummary => "rolling-summary",
+            Self::DropOldest => "drop-oldest",
+            Self::KeepRecent => "keep-recent",
+        }
+    }
+}
+
+/// "summarize-all, rolling-summary, ..." for help and errors
+pub fn compaction_strategy_names() -> String {
+    let names: Vec<&str> = CompactionStrategy::ALL.iter().map(|s| s.name()).collect();
+    names.join(", ")
+}
+
+impl std::fmt::Display for CompactionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for CompactionStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == s.trim())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown compaction strategy '{}' (expected {})",
+                    s,
+                    compaction_strategy_names()
+                )
+            })
+    }
+}
+
+/// `[models]`: user-defined model names
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelsConfig {
+    /// `fast = "gpt-4o-mini"` makes `fast` usable wherever a model name is.
+    /// Role names such as `summarizer` pick the model for that job.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+
+    /// Context window in tokens by model (`"ollama/llama3.2" = 8192`), for
+    /// models whose limit isn't built in or differs from it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context_windows: BTreeMap<String, usize>,
+}
+
+impl ModelsConfig {
+    /// Follow aliases to a model name. An alias may name another alias;
+    /// a loop stops after a few steps.
+    pub fn resolve<'a>(&'a self, model: &'a str) -> &'a str {
+        let mut model = model;
+        for _ in 0..8 {
+            match self.aliases.get(model) {
+                Some(target) if target != model => model = target,
+                _ => break,
+            }
+        }
+        model
+    }
+}
+
+/// `[budget]`: spending limits per provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// Share of a limit at which to warn (0.8 = 80%)
+    #[serde(default = "default_budget_warn_at")]
+    pub warn_at: f64,
+
+    /// Limits by provider section (`openai`, `anthropic`, a custom name, ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub providers: BTreeMap<String, ProviderBudget>,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            warn_at: default_budget_warn_at(),
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+/// Limits for one provider; days and months are local time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderBudget {
+    /// Estimated spend in USD (from the built-in price table)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_usd: Option<f64>,
+    /// Input plus output tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_tokens: Option<u64>,
+}
+
+/// OCR for attached images and scanned PDFs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrConfig {
+    /// Extract text from attached images/PDFs and from read_file on them
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// "tesseract" (tesseract + poppler CLIs) or "ollama" (local vision model)
+    #[serde(default = "default_ocr_engine")]
+    pub engine: String,
+
+    /// Tesseract language codes, e.g. "eng" or "eng+deu"
+    #[serde(default = "default_ocr_language")]
+    pub language: String,
+
+    /// Ollama vision model for the "ollama" engine
+    #[serde(default = "default_ocr_vision_model")]
+    pub vision_model: String,
+
+    /// Save extracted text under workspace/ocr/ so it is searchable in memory
+    #[serde(default = "default_true")]
+    pub save_to_memory: bool,
+}
+
+/// `[calendar]`: the calendar and reminder tools and the reminder scheduler
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CalendarConfig {
+    /// Give the agent the calendar and reminder tools, and fire due
+    /// reminders from the daemon and desktop app
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Local iCalendar file used when no CalDAV server is set
+    /// (default ~/.localgpt/calendar.ics)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ics_path: Option<String>,
+
+    /// CalDAV calendar collection, e.g.
+    /// `https://dav.example.com/calendars/me/personal/`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caldav_url: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// May reference `${VAR}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
@@ -230,9 +1477,84 @@ fn default_context_window() -> usize {
 fn default_reserve_tokens() -> usize {
     8000
 }
+fn default_compaction_keep_recent() -> usize {
+    4
+}
+fn default_repo_map_tokens() -> usize {
+    1024
+}
 fn default_max_tokens() -> usize {
     4096
 }
+fn default_checkpoint_interval_secs() -> u64 {
+    5
+}
+fn default_max_tool_iterations() -> usize {
+    10
+}
+fn default_max_failed_tool_calls() -> usize {
+    5
+}
+fn default_mcp_timeout_secs() -> u64 {
+    60
+}
+fn default_web_search_max_results() -> usize {
+    5
+}
+fn default_sql_max_rows() -> usize {
+    100
+}
+fn default_sql_timeout_secs() -> u64 {
+    30
+}
+fn default_imap_port() -> u16 {
+    993
+}
+fn default_smtp_port() -> u16 {
+    465
+}
+fn default_drafts_folder() -> String {
+    "Drafts".to_string()
+}
+fn default_email_timeout_secs() -> u64 {
+    30
+}
+fn default_python() -> String {
+    "python3".to_string()
+}
+fn default_node() -> String {
+    "node".to_string()
+}
+fn default_code_timeout_secs() -> u64 {
+    30
+}
+fn default_code_memory_mb() -> u64 {
+    512
+}
+fn default_run_tests_timeout_secs() -> u64 {
+    600
+}
+fn default_run_tests_max_failures() -> usize {
+    10
+}
+fn default_fetch_url_max_tokens() -> usize {
+    4000
+}
+fn default_fetch_url_max_bytes() -> usize {
+    2_000_000
+}
+fn default_max_parallel_tools() -> usize {
+    4
+}
+fn default_tool_timeout_secs() -> u64 {
+    300
+}
+fn default_tool_output_summary_chars() -> usize {
+    4000
+}
+fn default_tool_output_context_tokens() -> usize {
+    8000
+}
 fn default_bash_timeout() -> u64 {
     30000 // 30 seconds
 }
@@ -245,12 +1567,21 @@ fn default_tool_output_max_chars() -> usize {
 fn default_openai_base_url() -> String {
     "https://api.openai.com/v1".to_string()
 }
+fn default_gemini_base_url() -> String {
+    "https://generativelanguage.googleapis.com/v1beta".to_string()
+}
+fn default_deepseek_base_url() -> String {
+    "https://api.deepseek.com/v1".to_string()
+}
 fn default_anthropic_base_url() -> String {
     "https://api.anthropic.com".to_string()
 }
 fn default_ollama_endpoint() -> String {
     "http://localhost:11434".to_string()
 }
+fn default_ollama_keep_alive() -> String {
+    "30m".to_string()
+}
 fn default_ollama_model() -> String {
     "llama3".to_string()
 }
@@ -260,14 +1591,71 @@ fn default_claude_cli_command() -> String {
 fn default_claude_cli_model() -> String {
     "opus".to_string()
 }
+fn default_codex_cli_command() -> String {
+    "codex".to_string()
+}
+fn default_codex_cli_model() -> String {
+    "gpt-5-codex".to_string()
+}
+fn default_codex_cli_sandbox() -> String {
+    "workspace-write".to_string()
+}
 fn default_true() -> bool {
     true
 }
+fn default_moderation_provider() -> String {
+    "openai".to_string()
+}
+fn default_moderation_action() -> String {
+    "warn".to_string()
+}
+fn default_wake_word() -> String {
+    "hey localgpt".to_string()
+}
+fn default_voice_language() -> String {
+    "en".to_string()
+}
+fn default_energy_threshold() -> f32 {
+    0.015
+}
+fn default_silence_ms() -> u64 {
+    800
+}
+fn default_max_utterance_secs() -> u64 {
+    15
+}
+fn default_stt_engine() -> String {
+    "whisper".to_string()
+}
+fn default_transcription_model() -> String {
+    "whisper-1".to_string()
+}
+fn default_tts_engine() -> String {
+    "system".to_string()
+}
+fn default_tts_model() -> String {
+    "tts-1".to_string()
+}
+fn default_tts_voice() -> String {
+    "alloy".to_string()
+}
+fn default_budget_warn_at() -> f64 {
+    0.8
+}
+fn default_ocr_engine() -> String {
+    "tesseract".to_string()
+}
+fn default_ocr_language() -> String {
+    "eng".to_string()
+}
+fn default_ocr_vision_model() -> String {
+    "llava".to_string()
+}
 fn default_interval() -> String {
     "30m".to_string()
 }
 fn default_workspace() -> String {
-    "~/.localgpt/workspace".to_string()
+    home_relative("workspace")
 }
 fn default_embedding_provider() -> String {
     "local".to_string() // Local embeddings via fastembed (no API key needed)
@@ -278,6 +1666,36 @@ fn default_embedding_model() -> String {
 fn default_embedding_cache_dir() -> String {
     "~/.cache/localgpt/models".to_string()
 }
+fn default_gguf_model_dir() -> String {
+    "~/.cache/localgpt/models".to_string()
+}
+fn default_gguf_context_size() -> u32 {
+    4096
+}
+fn default_timeout_secs() -> u64 {
+    300
+}
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+fn default_response_cache_ttl_secs() -> u64 {
+    86400
+}
+fn default_audit_max_file_mb() -> u64 {
+    10
+}
+fn default_audit_max_files() -> usize {
+    5
+}
+fn default_retry_max_attempts() -> u32 {
+    3
+}
+fn default_retry_initial_backoff_ms() -> u64 {
+    1000
+}
+fn default_retry_max_backoff_ms() -> u64 {
+    30_000
+}
 fn default_chunk_size() -> usize {
     400
 }
@@ -293,6 +1711,9 @@ fn default_index_paths() -> Vec<MemoryIndexPath> {
 fn default_pattern() -> String {
     "**/*.md".to_string()
 }
+fn default_dedup_similarity() -> f32 {
+    0.92
+}
 fn default_session_max_messages() -> usize {
     15 // Match OpenClaw's default
 }
@@ -306,16 +1727,47 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 fn default_log_file() -> String {
-    "~/.localgpt/logs/agent.log".to_string()
+    home_relative("logs/agent.log")
+}
+
+/// Path under the active profile's home, in `~/.localgpt/...` form when no profile is active
+fn home_relative(path: &str) -> String {
+    match (active_profile(), localgpt_home()) {
+        (Some(_), Ok(home)) => home.join(path).to_string_lossy().to_string(),
+        _ => format!("~/.localgpt/{}", path),
+    }
+}
+
+impl AgentConfig {
+    /// `context_window`, else the window for models without a known limit
+    pub fn context_window_or_default(&self) -> usize {
+        self.context_window.unwrap_or_else(default_context_window)
+    }
 }
 
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
             default_model: default_model(),
-            context_window: default_context_window(),
+            context_window: None,
             reserve_tokens: default_reserve_tokens(),
             max_tokens: default_max_tokens(),
+            checkpoint_interval_secs: default_checkpoint_interval_secs(),
+            max_tool_iterations: default_max_tool_iterations(),
+            max_failed_tool_calls: default_max_failed_tool_calls(),
+            max_turn_secs: 0,
+            system_prompt: None,
+            compaction_prompt: None,
+            compaction_strategy: CompactionStrategy::default(),
+            compaction_keep_recent: default_compaction_keep_recent(),
+            background_compaction: true,
+            llm_session_titles: false,
+            title_prompt: None,
+            auxiliary_model: None,
+            summarize_model: None,
+            keep_reasoning: true,
+            feedback_summary: false,
+            repo_map_tokens: default_repo_map_tokens(),
         }
     }
 }
@@ -325,10 +1777,102 @@ impl Default for ToolsConfig {
         Self {
             bash_timeout_ms: default_bash_timeout(),
             web_fetch_max_bytes: default_web_fetch_max_bytes(),
+            disabled: Vec::new(),
+            allowed: Vec::new(),
+            allowed_dirs: Vec::new(),
             require_approval: Vec::new(),
             tool_output_max_chars: default_tool_output_max_chars(),
             log_injection_warnings: default_true(),
             use_content_delimiters: default_true(),
+            approve_after_untrusted: false,
+            screen_capture: false,
+            clipboard: false,
+            notifications: false,
+            browser_allowed_domains: Vec::new(),
+            browser_executable: None,
+            image_generation: String::new(),
+            image_generation_url: None,
+            image_generation_model: None,
+            workspace_snapshots: default_true(),
+            agent_messaging: Vec::new(),
+            agent_messaging_approval: default_true(),
+            subagent: SubagentConfig::default(),
+            tool_output_summary_chars: default_tool_output_summary_chars(),
+            tool_output_summary_model: None,
+            tool_output_context_tokens: default_tool_output_context_tokens(),
+            tool_output_overflow: ToolOutputOverflow::default(),
+            mcp_servers: Vec::new(),
+            databases: Vec::new(),
+            email: Vec::new(),
+            web_search: WebSearchConfig::default(),
+            code_interpreter: CodeInterpreterConfig::default(),
+            fetch_url: FetchUrlConfig::default(),
+            run_tests: RunTestsConfig::default(),
+            max_parallel_tools: default_max_parallel_tools(),
+            tool_timeout_secs: default_tool_timeout_secs(),
+            tool_timeouts: HashMap::new(),
+        }
+    }
+}
+
+impl ToolsConfig {
+    /// Timeout for calls of the tool named `name`, if any
+    pub fn tool_timeout(&self, name: &str) -> Option<Duration> {
+        let secs = self
+            .tool_timeouts
+            .get(name)
+            .copied()
+            .unwrap_or(self.tool_timeout_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Whether agents get the tool named `name` (if it is configured)
+    pub fn offers(&self, name: &str) -> bool {
+        !self.disabled.iter().any(|d| d == name)
+            && (self.allowed.is_empty() || self.allowed.iter().any(|a| a == name))
+    }
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: default_moderation_provider(),
+            action: default_moderation_action(),
+            check_input: default_true(),
+            check_output: default_true(),
+            blocked_patterns: Vec::new(),
+        }
+    }
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            wake_word: default_wake_word(),
+            whisper_model: String::new(),
+            language: default_voice_language(),
+            energy_threshold: default_energy_threshold(),
+            silence_ms: default_silence_ms(),
+            max_utterance_secs: default_max_utterance_secs(),
+            stt_engine: default_stt_engine(),
+            transcription_model: default_transcription_model(),
+            tts_engine: default_tts_engine(),
+            tts_model: default_tts_model(),
+            tts_voice: default_tts_voice(),
+            speak_replies: false,
+        }
+    }
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            engine: default_ocr_engine(),
+            language: default_ocr_language(),
+            vision_model: default_ocr_vision_model(),
+            save_to_memory: true,
         }
     }
 }
@@ -354,8 +1898,13 @@ impl Default for MemoryConfig {
             chunk_size: default_chunk_size(),
             chunk_overlap: default_chunk_overlap(),
             paths: default_index_paths(),
+            document_dirs: Vec::new(),
             session_max_messages: default_session_max_messages(),
             session_max_chars: 0, // 0 = unlimited (preserve full content like OpenClaw)
+            extract_facts: false,
+            dedup_similarity: default_dedup_similarity(),
+            decay_half_life_days: 0,
+            archive_after_days: 0,
         }
     }
 }
@@ -385,11 +1934,13 @@ impl Config {
         let path = Self::config_path()?;
 
         if !path.exists() {
-            // Try to migrate from OpenClaw config
-            if let Some(migrated) = try_migrate_openclaw_config() {
-                // Save migrated config to disk
-                migrated.save()?;
-                return Ok(migrated);
+            // Try to migrate from OpenClaw config (shared home only)
+            if active_profile().is_none() {
+                if let Some(migrated) = try_migrate_openclaw_config() {
+                    // Save migrated config to disk
+                    migrated.save()?;
+                    return Ok(migrated);
+                }
             }
             // Create default config file on first run
             let config = Config::default();
@@ -400,8 +1951,8 @@ impl Config {
         let content = fs::read_to_string(&path)?;
         let mut config: Config = toml::from_str(&content)?;
 
-        // Expand environment variables in API keys
-        config.expand_env_vars();
+        // Expand environment variables and keyring references in API keys
+        config.resolve_api_keys();
 
         Ok(config)
     }
@@ -414,7 +1965,7 @@ impl Config {
             fs::create_dir_all(parent)?;
         }
 
-        let content = toml::to_string_pretty(self)?;
+        let content = toml::to_string_pretty(&self.as_written())?;
         fs::write(&path, content)?;
 
         Ok(())
@@ -429,26 +1980,181 @@ impl Config {
             fs::create_dir_all(parent)?;
         }
 
-        fs::write(&path, DEFAULT_CONFIG_TEMPLATE)?;
+        fs::write(&path, profile::localize_template(DEFAULT_CONFIG_TEMPLATE)?)?;
         eprintln!("Created default config at {}", path.display());
 
         Ok(())
     }
 
+    /// Config file for the active user profile (`~/.localgpt/config.toml` by default)
     pub fn config_path() -> Result<PathBuf> {
-        let base = directories::BaseDirs::new()
-            .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+        Ok(localgpt_home()?.join("config.toml"))
+    }
+
+    /// Each configured provider's API key, by config section
+    fn api_keys_mut(&mut self) -> Vec<(String, &mut String)> {
+        let providers = &mut self.providers;
+        let mut keys = Vec::new();
+        if let Some(ref mut openai) = providers.openai {
+            keys.push(("openai".to_string(), &mut openai.api_key));
+        }
+        if let Some(ref mut anthropic) = providers.anthropic {
+            keys.push(("anthropic".to_string(), &mut anthropic.api_key));
+        }
+        if let Some(ref mut gemini) = providers.gemini {
+            keys.push(("gemini".to_string(), &mut gemini.api_key));
+        }
+        if let Some(ref mut deepseek) = providers.deepseek {
+            keys.push(("deepseek".to_string(), &mut deepseek.api_key));
+        }
+        for custom in &mut providers.custom {
+            keys.push((custom.name.clone(), &mut custom.api_key));
+        }
+        keys
+    }
+
+    /// Expand `${VAR}` keys, look up `keyring` / `keyring:<entry>` keys, and
+    /// otherwise prefer a key saved in the OS keyring over the configured one.
+    /// Keys that can't be resolved are left empty and their error recorded.
+    fn resolve_api_keys(&mut self) {
+        let mut refs = HashMap::new();
+        let mut errors = HashMap::new();
+        for (provider, key) in self.api_keys_mut() {
+            let written = key.clone();
+            let resolved = if let Some(entry) = keyring_entry(&written, &provider) {
+                stored_api_key(entry).ok_or_else(|| {
+                    format!(
+                        "{} is set to come from the OS keyring, but there is no '{}' entry.\n\
+                         Save the key with: localgpt config set-key {}",
+                        api_key_setting(&provider),
+                        entry,
+                        entry
+                    )
+                })
+            } else if let Some(stored) = stored_api_key(&provider) {
+                Ok(stored)
+            } else {
+                expand_env(&written).map_err(|var| {
+                    format!(
+                        "{} uses ${{{}}}, which is not set.\n\
+                         Set it (export {}=...) or save the key with: localgpt config set-key {}",
+                        api_key_setting(&provider),
+                        var,
+                        var,
+                        provider
+                    )
+                })
+            };
+            *key = resolved.unwrap_or_else(|error| {
+                errors.insert(provider.clone(), error);
+                String::new()
+            });
+            if *key != written {
+                refs.insert(provider, written);
+            }
+        }
+        self.api_key_refs = refs;
+        self.api_key_errors = errors;
+    }
 
-        Ok(base.home_dir().join(".localgpt").join("config.toml"))
+    /// Why `provider`'s API key couldn't be resolved at load, if it couldn't
+    pub fn api_key_error(&self, provider: &str) -> Option<&str> {
+        self.api_key_errors.get(provider).map(String::as_str)
     }
 
-    fn expand_env_vars(&mut self) {
-        if let Some(ref mut openai) = self.providers.openai {
-            openai.api_key = expand_env(&openai.api_key);
+    /// Copy with API keys as written in config.toml rather than resolved,
+    /// for saving or showing
+    pub fn as_written(&self) -> Config {
+        let mut config = self.clone();
+        let refs = std::mem::take(&mut config.api_key_refs);
+        for (provider, key) in config.api_keys_mut() {
+            if let Some(written) = refs.get(&provider) {
+                *key = written.clone();
+            }
         }
-        if let Some(ref mut anthropic) = self.providers.anthropic {
-            anthropic.api_key = expand_env(&anthropic.api_key);
+        config
+    }
+
+    /// Keyring entry holding `provider`'s key: the one its config refers
+    /// to, else the provider's own
+    pub fn api_key_entry(&self, provider: &str) -> String {
+        self.api_key_refs
+            .get(provider)
+            .and_then(|written| keyring_entry(written, provider))
+            .unwrap_or(provider)
+            .to_string()
+    }
+
+    /// Keyring entries that `keyring:<entry>` keys refer to
+    pub fn keyring_entries(&self) -> Vec<String> {
+        self.api_key_refs
+            .iter()
+            .filter_map(|(provider, written)| keyring_entry(written, provider))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Set the API key for a provider, adding its section if missing
+    pub fn set_api_key(&mut self, provider: &str, key: &str) -> Result<()> {
+        let key = key.to_string();
+        match provider {
+            "openai" => match self.providers.openai {
+                Some(ref mut openai) => openai.api_key = key,
+                None => {
+                    self.providers.openai = Some(OpenAIConfig {
+                        api_key: key,
+                        base_url: default_openai_base_url(),
+                        reasoning_effort: None,
+                        timeouts: TimeoutConfig::default(),
+                        network: NetworkConfig::default(),
+                    })
+                }
+            },
+            "anthropic" => match self.providers.anthropic {
+                Some(ref mut anthropic) => anthropic.api_key = key,
+                None => {
+                    self.providers.anthropic = Some(AnthropicConfig {
+                        api_key: key,
+                        base_url: default_anthropic_base_url(),
+                        thinking_budget_tokens: 0,
+                        timeouts: TimeoutConfig::default(),
+                        network: NetworkConfig::default(),
+                    })
+                }
+            },
+            "gemini" => match self.providers.gemini {
+                Some(ref mut gemini) => gemini.api_key = key,
+                None => {
+                    self.providers.gemini = Some(GeminiConfig {
+                        api_key: key,
+                        base_url: default_gemini_base_url(),
+                        timeouts: TimeoutConfig::default(),
+                        network: NetworkConfig::default(),
+                    })
+                }
+            },
+            "deepseek" => match self.providers.deepseek {
+                Some(ref mut deepseek) => deepseek.api_key = key,
+                None => {
+                    self.providers.deepseek = Some(DeepSeekConfig {
+                        api_key: key,
+                        base_url: default_deepseek_base_url(),
+                        timeouts: TimeoutConfig::default(),
+                        network: NetworkConfig::default(),
+                    })
+                }
+            },
+            name => match self
+                .providers
+                .custom
+                .iter_mut()
+                .find(|c| c.name.eq_ignore_ascii_case(name))
+            {
+                Some(custom) => custom.api_key = key,
+                None => anyhow::bail!("Provider '{}' does not use an API key", provider),
+            },
         }
+        Ok(())
     }
 
     pub fn get_value(&self, key: &str) -> Result<String> {
@@ -456,8 +2162,21 @@ impl Config {
 
         match parts.as_slice() {
             ["agent", "default_model"] => Ok(self.agent.default_model.clone()),
-            ["agent", "context_window"] => Ok(self.agent.context_window.to_string()),
+            ["agent", "auxiliary_model"] => {
+                Ok(self.agent.auxiliary_model.clone().unwrap_or_default())
+            }
+            ["agent", "summarize_model"] => {
+                Ok(self.agent.summarize_model.clone().unwrap_or_default())
+            }
+            ["agent", "context_window"] => Ok(self
+                .agent
+                .context_window
+                .map(|w| w.to_string())
+                .unwrap_or_default()),
             ["agent", "reserve_tokens"] => Ok(self.agent.reserve_tokens.to_string()),
+            ["agent", "feedback_summary"] => Ok(self.agent.feedback_summary.to_string()),
+            ["agent", "repo_map_tokens"] => Ok(self.agent.repo_map_tokens.to_string()),
+            ["agent", "compaction_strategy"] => Ok(self.agent.compaction_strategy.to_string()),
             ["heartbeat", "enabled"] => Ok(self.heartbeat.enabled.to_string()),
             ["heartbeat", "interval"] => Ok(self.heartbeat.interval.clone()),
             ["server", "enabled"] => Ok(self.server.enabled.to_string()),
@@ -474,8 +2193,17 @@ impl Config {
 
         match parts.as_slice() {
             ["agent", "default_model"] => self.agent.default_model = value.to_string(),
-            ["agent", "context_window"] => self.agent.context_window = value.parse()?,
+            ["agent", "auxiliary_model"] => {
+                self.agent.auxiliary_model = (!value.is_empty()).then(|| value.to_string())
+            }
+            ["agent", "summarize_model"] => {
+                self.agent.summarize_model = (!value.is_empty()).then(|| value.to_string())
+            }
+            ["agent", "context_window"] => self.agent.context_window = Some(value.parse()?),
             ["agent", "reserve_tokens"] => self.agent.reserve_tokens = value.parse()?,
+            ["agent", "feedback_summary"] => self.agent.feedback_summary = value.parse()?,
+            ["agent", "repo_map_tokens"] => self.agent.repo_map_tokens = value.parse()?,
+            ["agent", "compaction_strategy"] => self.agent.compaction_strategy = value.parse()?,
             ["heartbeat", "enabled"] => self.heartbeat.enabled = value.parse()?,
             ["heartbeat", "interval"] => self.heartbeat.interval = value.to_string(),
             ["server", "enabled"] => self.server.enabled = value.parse()?,
@@ -493,7 +2221,7 @@ impl Config {
     ///
     /// Resolution order (like OpenClaw):
     /// 1. LOCALGPT_WORKSPACE env var (absolute path override)
-    /// 2. LOCALGPT_PROFILE env var (creates ~/.localgpt/workspace-{profile})
+    /// 2. LOCALGPT_PROFILE env var (creates workspace-{profile} in the LocalGPT home)
     /// 3. memory.workspace from config file
     /// 4. Default: ~/.localgpt/workspace
     pub fn workspace_path(&self) -> PathBuf {
@@ -510,12 +2238,8 @@ impl Config {
         if let Ok(profile) = std::env::var("LOCALGPT_PROFILE") {
             let trimmed = profile.trim().to_lowercase();
             if !trimmed.is_empty() && trimmed != "default" {
-                let base = directories::BaseDirs::new()
-                    .map(|b| b.home_dir().to_path_buf())
-                    .unwrap_or_else(|| PathBuf::from("~"));
-                return base
-                    .join(".localgpt")
-                    .join(format!("workspace-{}", trimmed));
+                let home = localgpt_home().unwrap_or_else(|_| PathBuf::from("~/.localgpt"));
+                return home.join(format!("workspace-{}", trimmed));
             }
         }
 
@@ -525,13 +2249,47 @@ impl Config {
     }
 }
 
-fn expand_env(s: &str) -> String {
-    if let Some(var_name) = s.strip_prefix("${").and_then(|s| s.strip_suffix('}')) {
-        std::env::var(var_name).unwrap_or_else(|_| s.to_string())
-    } else if let Some(var_name) = s.strip_prefix('$') {
-        std::env::var(var_name).unwrap_or_else(|_| s.to_string())
-    } else {
-        s.to_string()
+/// Replace each `${VAR}` in `s` (or all of `s` when it is `$VAR`) with the
+/// variable's value. Returns the name of the first unset variable as the error.
+pub(crate) fn expand_env(s: &str) -> std::result::Result<String, String> {
+    let lookup = |name: &str| std::env::var(name).map_err(|_| name.to_string());
+
+    if let Some(name) = s.strip_prefix('$').filter(|name| is_env_var_name(name)) {
+        return lookup(name);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        out.push_str(&rest[..start]);
+        if is_env_var_name(name) {
+            out.push_str(&lookup(name)?);
+        } else {
+            out.push_str(&rest[start..start + 3 + len]);
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// How a provider's key setting is named in messages
+fn api_key_setting(provider: &str) -> String {
+    match provider {
+        "openai" | "anthropic" | "gemini" | "deepseek" => {
+            format!("providers.{}.api_key", provider)
+        }
+        custom => format!("The api_key of [[providers.custom]] '{}'", custom),
     }
 }
 
@@ -542,21 +2300,34 @@ const DEFAULT_CONFIG_TEMPLATE: &str = r#"# LocalGPT Configuration
 [agent]
 # Default model: claude-cli/opus, anthropic/claude-sonnet-4-5, openai/gpt-4o, etc.
 default_model = "claude-cli/opus"
-context_window = 128000
+# context_window = 128000   # every model; unset uses each provider's published limit
 reserve_tokens = 8000
 
 # Anthropic API (for anthropic/* models)
 # [providers.anthropic]
-# api_key = "${ANTHROPIC_API_KEY}"
+# api_key = "${ANTHROPIC_API_KEY}"   # or "keyring" (localgpt config set-key anthropic)
 
 # OpenAI API (for openai/* models)
 # [providers.openai]
 # api_key = "${OPENAI_API_KEY}"
 
+# Google Gemini API (for gemini/* models)
+# [providers.gemini]
+# api_key = "${GEMINI_API_KEY}"
+
+# DeepSeek API (for deepseek/* models)
+# [providers.deepseek]
+# api_key = "${DEEPSEEK_API_KEY}"
+
 # Claude CLI (for claude-cli/* models, requires claude CLI installed)
 [providers.claude_cli]
 command = "claude"
 
+# OpenAI Codex CLI (for codex-cli/* models, requires codex CLI installed)
+# [providers.codex_cli]
+# command = "codex"
+# sandbox = "workspace-write"   # or "read-only", "danger-full-access"
+
 [heartbeat]
 enabled = true
 interval = "30m"
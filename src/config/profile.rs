@@ -0,0 +1,257 @@
+//! User profiles for shared machines
+//!
+//! Each profile is a complete, separate LocalGPT home under
+//! `~/.localgpt/profiles/<name>/` with its own config (and API keys), agents
+//! and sessions, workspace, memory index, and skills. Without a profile the
+//! home is `~/.localgpt` itself, so single-user installs are unchanged.
+//!
+//! A profile passphrase gates selecting the profile in LocalGPT. It does not
+//! encrypt the profile's files; use OS accounts for that level of separation.
+
+use anyhow::Result;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Env var selecting a user profile (same as the `--user` flag)
+pub const USER_PROFILE_ENV: &str = "LOCALGPT_USER_PROFILE";
+
+/// Env var supplying the profile passphrase for non-interactive use
+pub const USER_PASSPHRASE_ENV: &str = "LOCALGPT_USER_PASSPHRASE";
+
+/// Name that refers to the shared `~/.localgpt` home
+pub const DEFAULT_PROFILE: &str = "default";
+
+static ACTIVE_PROFILE: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Argon2 PHC string, if the profile is passphrase-protected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    passphrase_hash: Option<String>,
+}
+
+impl Profile {
+    pub fn has_passphrase(&self) -> bool {
+        self.passphrase_hash.is_some()
+    }
+
+    /// Check a passphrase (always true for unprotected profiles)
+    pub fn verify_passphrase(&self, passphrase: &str) -> bool {
+        let Some(ref hash) = self.passphrase_hash else {
+            return true;
+        };
+        PasswordHash::new(hash)
+            .map(|parsed| {
+                Argon2::default()
+                    .verify_password(passphrase.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Set or clear (empty) the passphrase
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<()> {
+        self.passphrase_hash = match passphrase.filter(|p| !p.is_empty()) {
+            Some(passphrase) => {
+                let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())
+                    .map_err(|e| anyhow::anyhow!("Failed to generate salt: {}", e))?;
+                let hash = Argon2::default()
+                    .hash_password(passphrase.as_bytes(), &salt)
+                    .map_err(|e| anyhow::anyhow!("Failed to hash passphrase: {}", e))?;
+                Some(hash.to_string())
+            }
+            None => None,
+        };
+        Ok(())
+    }
+}
+
+/// Shared LocalGPT home (`~/.localgpt`)
+fn base_home() -> Result<PathBuf> {
+    let base = directories::BaseDirs::new()
+        .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+    Ok(base.home_dir().join(".localgpt"))
+}
+
+fn profiles_dir_in(base: &Path) -> PathBuf {
+    base.join("profiles")
+}
+
+/// Home directory for the active profile (`~/.localgpt` when none is active)
+pub fn localgpt_home() -> Result<PathBuf> {
+    let base = base_home()?;
+    Ok(match active_profile() {
+        Some(name) => profiles_dir_in(&base).join(name),
+        None => base,
+    })
+}
+
+/// The profile selected for this process, if any
+pub fn active_profile() -> Option<&'static str> {
+    ACTIVE_PROFILE.get().map(String::as_str)
+}
+
+/// Select the profile for this process. Must happen before config or
+/// session paths are first used; the profile cannot change afterwards.
+pub fn set_active_profile(name: &str) -> Result<()> {
+    if name == DEFAULT_PROFILE {
+        return Ok(());
+    }
+    load_profile(name)?;
+    ACTIVE_PROFILE
+        .set(name.to_string())
+        .map_err(|_| anyhow::anyhow!("A user profile is already active"))
+}
+
+/// Rewrite `~/.localgpt/` paths in a config template to the active profile's home
+pub fn localize_template(template: &str) -> Result<String> {
+    if active_profile().is_none() {
+        return Ok(template.to_string());
+    }
+    let home = localgpt_home()?;
+    Ok(template.replace("~/.localgpt/", &format!("{}/", home.display())))
+}
+
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "Invalid profile name '{}'. Use up to 32 lowercase letters, digits, '-' or '_'.",
+            name
+        );
+    }
+    if name == DEFAULT_PROFILE {
+        anyhow::bail!("'{}' is reserved for the shared profile", DEFAULT_PROFILE);
+    }
+    Ok(())
+}
+
+/// List profiles, sorted by name
+pub fn list_profiles() -> Result<Vec<Profile>> {
+    Ok(list_profiles_in(&base_home()?))
+}
+
+fn list_profiles_in(base: &Path) -> Vec<Profile> {
+    let Ok(entries) = fs::read_dir(profiles_dir_in(base)) else {
+        return Vec::new();
+    };
+
+    let mut profiles: Vec<Profile> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| read_profile(&e.path()).ok())
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles
+}
+
+pub fn load_profile(name: &str) -> Result<Profile> {
+    load_profile_in(&base_home()?, name)
+}
+
+fn load_profile_in(base: &Path, name: &str) -> Result<Profile> {
+    validate_profile_name(name)?;
+    read_profile(&profiles_dir_in(base).join(name)).map_err(|_| {
+        anyhow::anyhow!(
+            "Unknown profile '{}'. Create it with: localgpt profile create {}",
+            name,
+            name
+        )
+    })
+}
+
+fn read_profile(dir: &Path) -> Result<Profile> {
+    let content = fs::read_to_string(dir.join("profile.json"))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Create a new profile. Its config is written on first use.
+pub fn create_profile(name: &str, passphrase: Option<&str>) -> Result<Profile> {
+    create_profile_in(&base_home()?, name, passphrase)
+}
+
+fn create_profile_in(base: &Path, name: &str, passphrase: Option<&str>) -> Result<Profile> {
+    validate_profile_name(name)?;
+    let dir = profiles_dir_in(base).join(name);
+    if dir.join("profile.json").exists() {
+        anyhow::bail!("Profile '{}' already exists", name);
+    }
+
+    let mut profile = Profile {
+        name: name.to_string(),
+        created_at: Utc::now(),
+        passphrase_hash: None,
+    };
+    profile.set_passphrase(passphrase)?;
+    save_profile_in(base, &profile)?;
+    Ok(profile)
+}
+
+pub fn save_profile(profile: &Profile) -> Result<()> {
+    save_profile_in(&base_home()?, profile)
+}
+
+fn save_profile_in(base: &Path, profile: &Profile) -> Result<()> {
+    let dir = profiles_dir_in(base).join(&profile.name);
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join("profile.json"),
+        serde_json::to_string_pretty(profile)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_and_list_profiles() {
+        let tmp = TempDir::new().unwrap();
+        create_profile_in(tmp.path(), "kids", None).unwrap();
+        create_profile_in(tmp.path(), "alex", Some("hunter2")).unwrap();
+
+        let profiles = list_profiles_in(tmp.path());
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["alex", "kids"]);
+        assert!(profiles[0].has_passphrase());
+        assert!(!profiles[1].has_passphrase());
+
+        assert!(create_profile_in(tmp.path(), "kids", None).is_err());
+        assert!(load_profile_in(tmp.path(), "missing").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_verification() {
+        let tmp = TempDir::new().unwrap();
+        create_profile_in(tmp.path(), "alex", Some("correct horse")).unwrap();
+
+        let mut profile = load_profile_in(tmp.path(), "alex").unwrap();
+        assert!(profile.verify_passphrase("correct horse"));
+        assert!(!profile.verify_passphrase("wrong"));
+
+        profile.set_passphrase(Some("")).unwrap();
+        assert!(!profile.has_passphrase());
+        assert!(profile.verify_passphrase("anything"));
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile_name("work_2").is_ok());
+        assert!(validate_profile_name("default").is_err());
+        assert!(validate_profile_name("../etc").is_err());
+        assert!(validate_profile_name("Alex").is_err());
+    }
+}
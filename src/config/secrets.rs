@@ -0,0 +1,128 @@
+//! API keys saved in the OS secret store
+//!
+//! Keys entered through the desktop recovery dialog are kept in the platform
+//! keyring (macOS Keychain, Windows Credential Manager, Linux keyutils) under
+//! the `localgpt` service, one entry per provider (and user profile). A
+//! saved key takes precedence over the one in config.toml.
+//!
+//! config.toml can also point at the keyring instead of holding a key:
+//! `api_key = "keyring"` uses the provider's own entry, and
+//! `api_key = "keyring:<entry>"` a named one (saved with
+//! `localgpt config set-key <entry>`).
+
+use anyhow::{Context, Result};
+
+use super::active_profile;
+
+const SERVICE: &str = "localgpt";
+
+fn entry(provider: &str) -> Result<keyring::Entry> {
+    // Profiles keep separate keys, like everything else under their home
+    let account = match active_profile() {
+        Some(profile) => format!("{}@{}", provider, profile),
+        None => provider.to_string(),
+    };
+    keyring::Entry::new(SERVICE, &account).context("Failed to open OS keyring")
+}
+
+/// Save `key` as the API key for `provider`
+pub fn store_api_key(provider: &str, key: &str) -> Result<()> {
+    entry(provider)?
+        .set_password(key)
+        .with_context(|| format!("Failed to store {} API key in OS keyring", provider))
+}
+
+/// Key previously saved for `provider`, if the keyring has one
+pub fn stored_api_key(provider: &str) -> Option<String> {
+    entry(provider).ok()?.get_password().ok()
+}
+
+/// Keyring entry a configured `api_key` refers to (`keyring` or
+/// `keyring:<entry>`), if it is a reference rather than a key
+pub fn keyring_entry<'a>(api_key: &'a str, provider: &'a str) -> Option<&'a str> {
+    match api_key.trim() {
+        "keyring" => Some(provider),
+        value => value
+            .strip_prefix("keyring:")
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{expand_env, Config, OpenAIConfig};
+
+    #[test]
+    fn test_keyring_references() {
+        assert_eq!(keyring_entry("keyring", "openai"), Some("openai"));
+        assert_eq!(
+            keyring_entry("keyring:work-openai", "openai"),
+            Some("work-openai")
+        );
+        assert_eq!(keyring_entry("keyring:", "openai"), None);
+        assert_eq!(keyring_entry("sk-keyring", "openai"), None);
+        assert_eq!(keyring_entry("${OPENAI_API_KEY}", "openai"), None);
+
+        // Resolved keys are saved as they were written
+        let mut config = Config::default();
+        config.providers.openai = Some(OpenAIConfig {
+            api_key: "sk-resolved".to_string(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            reasoning_effort: None,
+            timeouts: Default::default(),
+            network: Default::default(),
+        });
+        config
+            .api_key_refs
+            .insert("openai".to_string(), "keyring:work-openai".to_string());
+        let written = config.as_written();
+        assert_eq!(
+            written.providers.openai.unwrap().api_key,
+            "keyring:work-openai"
+        );
+        assert_eq!(
+            config.providers.openai.as_ref().unwrap().api_key,
+            "sk-resolved"
+        );
+        assert_eq!(config.api_key_entry("openai"), "work-openai");
+        assert_eq!(config.api_key_entry("anthropic"), "anthropic");
+        assert_eq!(config.keyring_entries(), vec!["work-openai"]);
+    }
+
+    #[test]
+    fn test_env_var_api_keys() {
+        std::env::set_var("LOCALGPT_TEST_KEY", "sk-from-env");
+        std::env::remove_var("LOCALGPT_TEST_UNSET_KEY");
+
+        // Names no keyring has an entry for
+        let mut config: Config = toml::from_str(
+            "[[providers.custom]]\nname = \"localgpt-test-a\"\n\
+             base_url = \"http://localhost:1234/v1\"\napi_key = \"${LOCALGPT_TEST_KEY}\"\n\
+             [[providers.custom]]\nname = \"localgpt-test-b\"\n\
+             base_url = \"http://localhost:1235/v1\"\napi_key = \"${LOCALGPT_TEST_UNSET_KEY}\"\n",
+        )
+        .unwrap();
+        config.resolve_api_keys();
+
+        assert_eq!(config.providers.custom[0].api_key, "sk-from-env");
+        assert_eq!(config.api_key_error("localgpt-test-a"), None);
+        assert_eq!(config.providers.custom[1].api_key, "");
+        let error = config.api_key_error("localgpt-test-b").unwrap();
+        assert!(error.contains("${LOCALGPT_TEST_UNSET_KEY}, which is not set"));
+        // The reference, not the resolved key, is what gets saved
+        let written = config.as_written();
+        assert_eq!(written.providers.custom[0].api_key, "${LOCALGPT_TEST_KEY}");
+
+        assert_eq!(
+            expand_env("pre-${LOCALGPT_TEST_KEY}-${not a var}"),
+            Ok("pre-sk-from-env-${not a var}".to_string())
+        );
+        assert_eq!(
+            expand_env("$LOCALGPT_TEST_KEY"),
+            Ok("sk-from-env".to_string())
+        );
+        assert_eq!(expand_env("sk-plain$"), Ok("sk-plain$".to_string()));
+    }
+}
@@ -2,29 +2,81 @@
 
 use eframe::egui;
 
-use super::state::{Panel, UiState};
-use super::views::{chat::show_toolbar, ChatView, SessionsView, StatusView};
+use super::drafts::DraftStore;
+use super::profiles::ProfilePicker;
+use super::state::{Panel, UiMessage, UiState};
+use super::views::{
+    chat::show_toolbar, ApiKeyDialog, ChatView, ContextView, FilesView, SessionsView, StatusView,
+    TerminalView,
+};
 use super::worker::WorkerHandle;
+use crate::config::list_agents;
 
 /// The main desktop application
 pub struct DesktopApp {
     state: UiState,
-    worker: WorkerHandle,
+    /// Started once a user profile is chosen
+    worker: Option<WorkerHandle>,
+    agent_id: Option<String>,
+    /// Shown at startup when user profiles exist
+    profile_picker: Option<ProfilePicker>,
+    /// Autosaved unsent input, per session
+    drafts: Option<DraftStore>,
+    /// Session the input box currently belongs to
+    draft_session: Option<String>,
 }
 
 impl DesktopApp {
-    /// Create a new desktop app
-    pub fn new(cc: &eframe::CreationContext<'_>, agent_id: Option<String>) -> Self {
+    /// Create a new desktop app. With a profile picker, the agent starts
+    /// only after a profile has been chosen.
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        agent_id: Option<String>,
+        profile_picker: Option<ProfilePicker>,
+    ) -> Self {
         // Configure fonts and visuals
         Self::configure_style(&cc.egui_ctx);
 
-        // Start the background worker
-        let worker = WorkerHandle::start(agent_id).expect("Failed to start worker");
-
-        Self {
+        let mut app = Self {
             state: UiState::new(),
-            worker,
+            worker: None,
+            agent_id,
+            profile_picker,
+            drafts: None,
+            draft_session: None,
+        };
+        if app.profile_picker.is_none() {
+            app.start();
+        }
+        app
+    }
+
+    /// Start the background worker for the active profile
+    fn start(&mut self) {
+        let agent_id = self
+            .agent_id
+            .as_deref()
+            .unwrap_or(crate::agent::DEFAULT_AGENT_ID);
+        self.drafts = DraftStore::load_for_agent(agent_id).ok();
+        self.state.agent_id = agent_id.to_string();
+        self.state.agents = list_agents().unwrap_or_default();
+
+        self.worker =
+            Some(WorkerHandle::start(self.agent_id.clone()).expect("Failed to start worker"));
+    }
+
+    /// Restart the worker for another agent, with its own settings and sessions
+    fn switch_agent(&mut self, agent_id: String) {
+        self.sync_draft();
+        if let Some(drafts) = self.drafts.as_mut() {
+            drafts.flush();
         }
+        // Dropping the handle disconnects the old worker, which then exits
+        self.worker = None;
+        self.state = UiState::new();
+        self.draft_session = None;
+        self.agent_id = Some(agent_id);
+        self.start();
     }
 
     fn configure_style(ctx: &egui::Context) {
@@ -56,45 +108,126 @@ impl DesktopApp {
 
     /// Process all pending worker messages
     fn process_worker_messages(&mut self) {
-        while let Some(msg) = self.worker.try_recv() {
+        let Some(ref worker) = self.worker else {
+            return;
+        };
+        while let Some(msg) = worker.try_recv() {
             self.state.handle_worker_message(msg);
         }
     }
+
+    /// Restore the draft when the active session changes, otherwise record edits
+    fn sync_draft(&mut self) {
+        let Some(drafts) = self.drafts.as_mut() else {
+            return;
+        };
+        let Some(session_id) = self.state.session_id().map(str::to_string) else {
+            return;
+        };
+
+        if self.draft_session.as_deref() != Some(session_id.as_str()) {
+            let restored = match self.draft_session {
+                // First session after launch: pick up where the last run left off
+                None => drafts
+                    .get(&session_id)
+                    .map(str::to_string)
+                    .or_else(|| drafts.take_latest()),
+                Some(_) => drafts.get(&session_id).map(str::to_string),
+            };
+            self.state.input = restored.unwrap_or_default();
+            self.draft_session = Some(session_id.clone());
+        }
+
+        drafts.set(&session_id, &self.state.input);
+        drafts.autosave();
+    }
 }
 
 impl eframe::App for DesktopApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let frame_start = std::time::Instant::now();
+        let _span = tracing::trace_span!("frame").entered();
+
+        if let Some(ref mut picker) = self.profile_picker {
+            let chosen = egui::CentralPanel::default()
+                .show(ctx, |ui| picker.show(ui))
+                .inner;
+            if chosen {
+                self.profile_picker = None;
+                self.start();
+            }
+            return;
+        }
+
         // Process worker messages
         self.process_worker_messages();
+        self.sync_draft();
 
         // Request repaint while loading or streaming
-        if self.state.is_loading || !self.state.streaming_content.is_empty() {
+        if self.state.is_loading
+            || self.state.shell_running
+            || self
+                .state
+                .api_key_prompt
+                .as_ref()
+                .is_some_and(|prompt| prompt.testing)
+            || !self.state.streaming_content.is_empty()
+        {
             ctx.request_repaint();
         }
 
         // Top panel with toolbar
-        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
-            show_toolbar(ui, &mut self.state);
-        });
+        let toolbar_msg = egui::TopBottomPanel::top("toolbar")
+            .show(ctx, |ui| show_toolbar(ui, &mut self.state))
+            .inner;
 
         // Main content
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let msg = match self.state.active_panel {
-                Panel::Chat => ChatView::show(ui, &mut self.state),
-                Panel::Sessions => SessionsView::show(ui, &mut self.state),
-                Panel::Status => StatusView::show(ui, &mut self.state),
-            };
+        let panel_msg = egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                toolbar_msg.or(match self.state.active_panel {
+                    Panel::Chat => ChatView::show(ui, &mut self.state),
+                    Panel::Sessions => SessionsView::show(ui, &mut self.state),
+                    Panel::Files => FilesView::show(ui, &mut self.state),
+                    Panel::Terminal => TerminalView::show(ui, &mut self.state),
+                    Panel::Context => ContextView::show(ui, &mut self.state),
+                    Panel::Status => StatusView::show(ui, &mut self.state),
+                })
+            })
+            .inner;
+
+        if let Some(agent_id) = self.state.switch_agent.take() {
+            self.switch_agent(agent_id);
+            ctx.request_repaint();
+            return;
+        }
+
+        // Replacement key after an auth error
+        let dialog_msg = ApiKeyDialog::show(ctx, &mut self.state);
+        let msg = panel_msg
+            .or(dialog_msg)
+            .or_else(|| self.state.pending_speech.take().map(UiMessage::Speak));
 
-            // Send any UI messages to worker
-            if let Some(msg) = msg {
-                if let Err(e) = self.worker.send(msg) {
-                    self.state.error = Some(format!("Failed to send to worker: {}", e));
-                }
+        // Send any UI messages to worker
+        if let (Some(msg), Some(worker)) = (msg, self.worker.as_ref()) {
+            if let Err(e) = worker.send(msg) {
+                self.state.error = Some(format!("Failed to send to worker: {}", e));
             }
-        });
+        }
+
+        self.state.frame_stats.record(frame_start.elapsed());
     }
 
     fn save(&mut self, _storage: &mut dyn eframe::Storage) {
         // Could save window position, etc.
+        if let Some(drafts) = self.drafts.as_mut() {
+            drafts.flush();
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.sync_draft();
+        if let Some(drafts) = self.drafts.as_mut() {
+            drafts.flush();
+        }
     }
 }
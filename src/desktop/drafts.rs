@@ -0,0 +1,168 @@
+//! Draft autosave for the chat input box
+//!
+//! Unsent input is persisted per session to
+//! `~/.localgpt/agents/<agentId>/sessions/drafts.json` so a crash or
+//! accidental close doesn't lose a long prompt being composed.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::agent::get_sessions_dir_for_agent;
+
+/// How often changed drafts are written to disk
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Draft {
+    text: String,
+    updated_at: DateTime<Utc>,
+}
+
+pub struct DraftStore {
+    path: PathBuf,
+    drafts: HashMap<String, Draft>,
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl DraftStore {
+    /// Load drafts for an agent (missing or unreadable file starts empty)
+    pub fn load_for_agent(agent_id: &str) -> Result<Self> {
+        let path = get_sessions_dir_for_agent(agent_id)?.join("drafts.json");
+        Ok(Self::load(path))
+    }
+
+    fn load(path: PathBuf) -> Self {
+        let drafts = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            drafts,
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Get the saved draft for a session
+    pub fn get(&self, session_id: &str) -> Option<&str> {
+        self.drafts.get(session_id).map(|d| d.text.as_str())
+    }
+
+    /// Remove and return the most recently edited draft from any session.
+    /// Used on startup, since the app opens a fresh session each launch.
+    pub fn take_latest(&mut self) -> Option<String> {
+        let id = self
+            .drafts
+            .iter()
+            .max_by_key(|(_, d)| d.updated_at)
+            .map(|(id, _)| id.clone())?;
+        self.dirty = true;
+        self.drafts.remove(&id).map(|d| d.text)
+    }
+
+    /// Record the current input for a session (empty input clears the draft)
+    pub fn set(&mut self, session_id: &str, text: &str) {
+        let changed = if text.trim().is_empty() {
+            self.drafts.remove(session_id).is_some()
+        } else if self.get(session_id) != Some(text) {
+            self.drafts.insert(
+                session_id.to_string(),
+                Draft {
+                    text: text.to_string(),
+                    updated_at: Utc::now(),
+                },
+            );
+            true
+        } else {
+            false
+        };
+        self.dirty |= changed;
+    }
+
+    /// Save if there are changes and the autosave interval has elapsed
+    pub fn autosave(&mut self) {
+        if self.dirty && self.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+            self.flush();
+        }
+    }
+
+    /// Save pending changes immediately
+    pub fn flush(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.last_save = Instant::now();
+        match self.save() {
+            Ok(()) => self.dirty = false,
+            Err(e) => debug!("Failed to save drafts: {}", e),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temp file then atomically rename
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.drafts)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_drafts_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("drafts.json");
+
+        let mut store = DraftStore::load(path.clone());
+        store.set("session-1", "a long prompt in progress");
+        store.set("session-2", "   ");
+        store.flush();
+
+        let store = DraftStore::load(path);
+        assert_eq!(store.get("session-1"), Some("a long prompt in progress"));
+        assert_eq!(store.get("session-2"), None);
+    }
+
+    #[test]
+    fn test_empty_input_clears_draft() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = DraftStore::load(tmp.path().join("drafts.json"));
+
+        store.set("s", "draft");
+        store.flush();
+        store.set("s", "");
+        assert!(store.dirty);
+        assert_eq!(store.get("s"), None);
+    }
+
+    #[test]
+    fn test_take_latest() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = DraftStore::load(tmp.path().join("drafts.json"));
+
+        store.set("old", "older draft");
+        store.set("new", "newer draft");
+        store.drafts.get_mut("old").unwrap().updated_at -= chrono::Duration::seconds(60);
+        assert_eq!(store.take_latest().as_deref(), Some("newer draft"));
+        assert_eq!(store.get("new"), None);
+        assert_eq!(store.get("old"), Some("older draft"));
+    }
+}
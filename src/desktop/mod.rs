@@ -5,9 +5,12 @@
 //! and communicates with the UI via channels.
 
 mod app;
+mod drafts;
+mod profiles;
 mod state;
 mod views;
 mod worker;
 
 pub use app::DesktopApp;
+pub use profiles::ProfilePicker;
 pub use worker::WorkerHandle;
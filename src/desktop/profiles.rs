@@ -0,0 +1,95 @@
+//! Startup profile switcher for shared machines
+
+use eframe::egui::{self, Color32, RichText, TextEdit, Ui};
+
+use crate::config::profile::{list_profiles, set_active_profile, Profile, DEFAULT_PROFILE};
+
+pub struct ProfilePicker {
+    profiles: Vec<Profile>,
+    selected: String,
+    passphrase: String,
+    error: Option<String>,
+}
+
+impl ProfilePicker {
+    /// Picker over all user profiles, or None when there are none to choose from
+    pub fn new(preselect: Option<&str>) -> Option<Self> {
+        let profiles = list_profiles().ok().filter(|p| !p.is_empty())?;
+        Some(Self {
+            selected: preselect.unwrap_or(DEFAULT_PROFILE).to_string(),
+            profiles,
+            passphrase: String::new(),
+            error: None,
+        })
+    }
+
+    fn selected_profile(&self) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == self.selected)
+    }
+
+    /// Show the picker. Returns true once a profile has been unlocked and activated.
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let mut activated = false;
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(40.0);
+            ui.heading("Who's using LocalGPT?");
+            ui.add_space(20.0);
+
+            ui.radio_value(
+                &mut self.selected,
+                DEFAULT_PROFILE.to_string(),
+                DEFAULT_PROFILE,
+            );
+            for p in &self.profiles {
+                let label = if p.has_passphrase() {
+                    format!("{} 🔒", p.name)
+                } else {
+                    p.name.clone()
+                };
+                ui.radio_value(&mut self.selected, p.name.clone(), label);
+            }
+
+            ui.add_space(10.0);
+
+            let needs_passphrase = self
+                .selected_profile()
+                .map(|p| p.has_passphrase())
+                .unwrap_or(false);
+            let mut submitted = false;
+            if needs_passphrase {
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.passphrase)
+                        .password(true)
+                        .hint_text("Passphrase")
+                        .desired_width(200.0),
+                );
+                submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            }
+
+            if ui.button("Continue").clicked() || submitted {
+                match self.activate() {
+                    Ok(()) => activated = true,
+                    Err(e) => self.error = Some(e),
+                }
+                self.passphrase.clear();
+            }
+
+            if let Some(ref error) = self.error {
+                ui.add_space(5.0);
+                ui.label(RichText::new(error).color(Color32::from_rgb(220, 80, 80)));
+            }
+        });
+
+        activated
+    }
+
+    fn activate(&self) -> Result<(), String> {
+        if let Some(p) = self.selected_profile() {
+            if !p.verify_passphrase(&self.passphrase) {
+                return Err("Incorrect passphrase".to_string());
+            }
+        }
+        set_active_profile(&self.selected).map_err(|e| e.to_string())
+    }
+}
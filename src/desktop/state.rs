@@ -1,36 +1,161 @@
 //! Application state shared between UI and worker
 
-use crate::agent::{SessionInfo, SessionStatus, ToolCall};
+use std::path::PathBuf;
+
+use super::views::chat::TranscriptCache;
+use super::views::files::{FileNode, FilePreview};
+use crate::agent::{
+    parse_sources, ApprovalScope, ContextReport, LocalModelStatus, ModelSuggestion,
+    ModelUsageReport, OllamaHostStatus, OllamaPullProgress, PendingApproval, Plan, ProviderHealth,
+    ProviderModels, Rating, RegenerateStyle, SessionInfo, SessionStats, SessionStatus, ShellEvent,
+    StepStatus, ALWAYS_REQUIRE_APPROVAL,
+};
+
+/// Maximum lines kept in the terminal panel
+const MAX_TERMINAL_LINES: usize = 5000;
+
+/// Frame budget for 60 fps
+const FRAME_BUDGET_MS: f32 = 16.0;
 
 /// Message from UI to worker
 #[derive(Debug, Clone)]
 pub enum UiMessage {
     /// Send a chat message
     Chat(String),
+    /// Send a chat message to another model for this turn only (`/ask`)
+    Ask { model: String, message: String },
+    /// Stop the reply being generated, keeping what has streamed so far
+    StopGeneration,
+    /// Cancel one running tool call (by call ID); the turn goes on
+    CancelTool(String),
     /// Create a new session
     NewSession,
     /// Resume a session by ID
     ResumeSession(String),
-    /// Approve pending tool calls
-    ApproveTools(Vec<ToolCall>),
-    /// Deny pending tool calls
-    DenyTools,
     /// Request session list refresh
     RefreshSessions,
     /// Request status update
     RefreshStatus,
+    /// Check reachability and models of the Ollama hosts
+    ProbeOllamaHosts,
+    /// Check each configured provider's connection and key (`/doctor`)
+    RunDoctor,
+    /// Download an Ollama model (`model` or `model@host`)
+    PullOllamaModel(String),
+    /// Delete an Ollama model (`model` or `model@host`)
+    DeleteOllamaModel(String),
     /// Set model
     SetModel(String),
+    /// Ask the configured providers which models they offer
+    ListModels,
+    /// Override generation parameters for this session (`/set key=value ...`)
+    SetParams(Vec<(String, String)>),
+    /// Set or clear extra system instructions for this session
+    SetSystemPrompt(Option<String>),
+    /// Set or clear the reply language for this session
+    SetLanguage(Option<String>),
+    /// Save a suggested model change to config.toml and use it now
+    ApplyModelSuggestion(ModelSuggestion),
     /// Compact current session
     Compact,
+    /// Show (None), set, or reset ("default") the session's compaction
+    /// strategy
+    CompactionStrategy(Option<String>),
+    /// Show (false) or delete (true) the cached replies
+    ResponseCache(bool),
+    /// Show the exact context the next turn will send
+    InspectContext,
     /// Search memory
     SearchMemory(String),
     /// Save session to disk
     Save,
+    /// Restore files changed by the agent's last turn
+    UndoChanges,
+    /// Export a redacted, self-contained HTML copy of the session
+    Share { include_tool_calls: bool },
     /// Show help text
     ShowHelp,
     /// Show status info
     ShowStatus,
+    /// Pin a workspace file into context
+    PinFile(PathBuf),
+    /// Remove a file from context
+    UnpinFile(PathBuf),
+    /// Run a command typed into the terminal panel
+    RunShell(String),
+    /// Run the last turn again, replacing its reply
+    Regenerate(RegenerateStyle),
+    /// Use another version of the last reply as context from now on
+    UseReplyVariant(String),
+    /// Rate the assistant reply with this text (None clears the rating)
+    RateReply {
+        content: String,
+        rating: Option<Rating>,
+    },
+    /// Pin (or unpin) the message with this text so compaction keeps it
+    /// (None: the last reply)
+    PinMessage {
+        content: Option<String>,
+        pinned: bool,
+    },
+    /// List pinned messages
+    ListPins,
+    /// Test and save a replacement API key, then retry the failed turn
+    UpdateApiKey {
+        provider: String,
+        model: String,
+        key: String,
+    },
+    /// Send a message that timed out again
+    RetryFailedMessage,
+    /// Keep using a provider past its budget and send the refused message
+    OverrideBudget(String),
+    /// Start recording dictation from the microphone
+    StartRecording,
+    /// Stop recording and transcribe it into the chat input
+    StopRecording,
+    /// Read a reply aloud
+    Speak(String),
+    /// Have the model plan a task (`/plan`), without running anything
+    MakePlan(String),
+    /// Run the reviewed plan, one turn per step
+    RunPlan(Plan),
+}
+
+/// Tool calls the worker is waiting on the user to approve
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub calls: Vec<PendingApproval>,
+    pub reply: tokio::sync::mpsc::UnboundedSender<ApprovalAnswer>,
+}
+
+/// The user's decision on every call of an approval request
+#[derive(Debug, Clone, Copy)]
+pub struct ApprovalAnswer {
+    pub approved: bool,
+    /// Keep making this decision for these tools, without asking
+    pub remember: Option<ApprovalScope>,
+}
+
+impl ApprovalRequest {
+    /// Answer every call the same way
+    pub fn answer(&self, approved: bool, remember: Option<ApprovalScope>) {
+        // The turn may have been stopped meanwhile
+        let _ = self.reply.send(ApprovalAnswer { approved, remember });
+    }
+
+    /// Names of the tools asked about whose decision can be remembered
+    /// (per-use tools such as screen capture are asked about every time)
+    pub fn rememberable_tools(&self) -> Vec<String> {
+        let mut tools: Vec<String> = Vec::new();
+        for pending in &self.calls {
+            let name = &pending.call.name;
+            if !ALWAYS_REQUIRE_APPROVAL.contains(&name.as_str()) && !tools.contains(name) {
+                tools.push(name.clone());
+            }
+        }
+        tools
+    }
 }
 
 /// Message from worker to UI
@@ -41,9 +166,14 @@ pub enum WorkerMessage {
         model: String,
         memory_chunks: usize,
         has_embeddings: bool,
+        workspace: PathBuf,
+        /// Initial state of the spoken replies toggle (`voice.speak_replies`)
+        speak_replies: bool,
     },
     /// Streaming content chunk
     ContentChunk(String),
+    /// Streaming reasoning ("thinking") chunk
+    ReasoningChunk(String),
     /// Tool call started
     ToolCallStart {
         name: String,
@@ -55,13 +185,32 @@ pub enum WorkerMessage {
         name: String,
         id: String,
         output: String,
+        /// Summary of a long output, shown above it
+        summary: Option<String>,
+        /// The tool reported an error (only known for tools the provider ran)
+        failed: bool,
     },
-    /// Tool calls pending approval
-    ToolsPendingApproval(Vec<ToolCall>),
+    /// Tool calls waiting for approval; the turn continues once the
+    /// decisions (one per call) are sent back
+    ToolsPendingApproval(ApprovalRequest),
     /// Response complete
     Done,
     /// Error occurred
     Error(String),
+    /// The provider didn't connect or respond in time; the turn can be retried
+    TimedOut(String),
+    /// A provider's `[budget]` ran out; the turn can be sent anyway
+    BudgetExceeded { provider: String, message: String },
+    /// A provider rejected its API key
+    AuthError {
+        provider: String,
+        model: String,
+        message: String,
+    },
+    /// Replacement API key works and is in use
+    ApiKeyAccepted { provider: String, retrying: bool },
+    /// Replacement API key failed its test request
+    ApiKeyRejected(String),
     /// Session status update
     Status(SessionStatus),
     /// Session list update
@@ -70,6 +219,47 @@ pub enum WorkerMessage {
     SessionChanged { id: String, message_count: usize },
     /// System message for display (command output, help text, etc.)
     SystemMessage(String),
+    /// A message was pinned or unpinned
+    MessagePinned { content: String, pinned: bool },
+    /// Current list of files pinned into context
+    PinnedFiles(Vec<PathBuf>),
+    /// Live bash tool activity
+    Shell(ShellEvent),
+    /// Context the next turn will send
+    Context(ContextReport),
+    /// Session statistics for the Status panel
+    Stats(SessionStats),
+    /// Per-model usage and suggestions for the Status panel
+    ModelUsage(ModelUsageReport),
+    /// Ollama host probe results
+    OllamaHosts(Vec<OllamaHostStatus>),
+    /// Provider health check results (`/doctor`)
+    Diagnostics(Vec<ProviderHealth>),
+    /// Startup check or warm-up progress of a local default model
+    LocalModel(LocalModelStatus),
+    /// Download progress of an Ollama model
+    OllamaPull(OllamaPullProgress),
+    /// An Ollama model download ended
+    OllamaPullFinished {
+        model: String,
+        error: Option<String>,
+    },
+    /// Models offered by each configured provider
+    Models(Vec<ProviderModels>),
+    /// The session switched to this model
+    ModelChanged(String),
+    /// Dictation recording started (true) or stopped (false)
+    Recording(bool),
+    /// Dictated text for the chat input
+    Transcribed(String),
+    /// Recording, transcription or speech failed
+    VoiceError(String),
+    /// A plan to review before it runs
+    PlanProposed(Plan),
+    /// A plan step started or finished
+    PlanStep { index: usize, status: StepStatus },
+    /// The plan's run ended (all steps done, or one failed or was stopped)
+    PlanFinished,
 }
 
 /// A chat message for display
@@ -78,6 +268,42 @@ pub struct ChatMessage {
     pub role: MessageRole,
     pub content: String,
     pub tool_info: Option<ToolInfo>,
+    /// Model reasoning behind an assistant message, shown collapsed
+    pub reasoning: Option<String>,
+    /// Every version of a regenerated reply
+    pub variants: Option<ReplyVariants>,
+    /// Thumbs up/down given to an assistant reply
+    pub rating: Option<Rating>,
+    /// Kept verbatim when the session is compacted
+    pub pinned: bool,
+}
+
+impl ChatMessage {
+    /// Display version `index` of a regenerated reply
+    pub fn show_variant(&mut self, index: usize) {
+        if let Some(ref mut variants) = self.variants {
+            if let Some(variant) = variants.items.get(index) {
+                self.content = variant.content.clone();
+                self.reasoning = variant.reasoning.clone();
+                variants.current = index;
+                self.rating = None;
+            }
+        }
+    }
+}
+
+/// Versions of a reply, paged through on the message
+#[derive(Debug, Clone)]
+pub struct ReplyVariants {
+    pub items: Vec<ReplyVariant>,
+    /// Version currently shown
+    pub current: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplyVariant {
+    pub content: String,
+    pub reasoning: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -90,13 +316,49 @@ pub enum MessageRole {
 
 #[derive(Debug, Clone)]
 pub struct ToolInfo {
+    /// Tool call ID, which tells apart calls running at the same time
+    pub id: String,
     pub name: String,
     pub detail: Option<String>,
     pub status: ToolStatus,
+    /// Full output, kept for long outputs that were summarized
+    pub output: Option<String>,
+    pub summary: Option<String>,
+    /// Cancel was clicked and the call hasn't ended yet
+    pub cancelling: bool,
+}
+
+/// Dialog asking for a new API key after the provider rejected the old one
+#[derive(Debug, Clone)]
+pub struct ApiKeyPrompt {
+    pub provider: String,
+    /// Model used to test the new key
+    pub model: String,
+    /// The provider's error response
+    pub message: String,
+    pub key: String,
+    /// Whether the key is being tested
+    pub testing: bool,
+    /// Why the last key tried was rejected
+    pub error: Option<String>,
+}
+
+/// A line of terminal panel output
+#[derive(Debug, Clone)]
+pub struct TerminalLine {
+    pub text: String,
+    pub kind: TerminalLineKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerminalLineKind {
+    Command,
+    Stdout,
+    Stderr,
+    Exit,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[allow(dead_code)]
 pub enum ToolStatus {
     Running,
     Completed(String), // output preview
@@ -112,20 +374,39 @@ pub struct UiState {
     pub input: String,
     /// Whether the agent is processing
     pub is_loading: bool,
+    /// Whether the response being streamed replaces the last reply
+    pub regenerating: bool,
+    /// Whether Stop was clicked for the reply in progress
+    pub stopping: bool,
     /// Current streaming response (being built)
     pub streaming_content: String,
+    /// Reasoning streamed for the current response
+    pub streaming_reasoning: String,
+    /// Numbered sources `document_search` returned during the current
+    /// response, listed under it when the reply cites them
+    pub turn_sources: Vec<(usize, String)>,
     /// Active tool calls
     pub active_tools: Vec<ToolInfo>,
     /// Tool calls pending approval
-    pub pending_approval: Option<Vec<ToolCall>>,
+    pub pending_approval: Option<ApprovalRequest>,
+    /// "Remember this choice" scope picked in the approval dialog
+    pub approval_remember: Option<ApprovalScope>,
     /// Error message to display
     pub error: Option<String>,
+    /// Whether `error` is a provider timeout, offering a retry
+    pub error_timed_out: bool,
+    /// Provider whose budget refused the last message, offering to continue
+    pub budget_exceeded: Option<String>,
+    /// Open API key recovery dialog
+    pub api_key_prompt: Option<ApiKeyPrompt>,
     /// Available sessions
     pub sessions: Vec<SessionInfo>,
     /// Current session info
     pub current_session: Option<SessionInfo>,
     /// Model name
     pub model: String,
+    /// Readiness of the model when it runs locally, shown in the toolbar
+    pub local_model: Option<LocalModelStatus>,
     /// Memory chunk count
     pub memory_chunks: usize,
     /// Whether embeddings are enabled
@@ -136,6 +417,98 @@ pub struct UiState {
     pub active_panel: Panel,
     /// Scroll to bottom on next frame
     pub scroll_to_bottom: bool,
+    /// Agent workspace directory
+    pub workspace: PathBuf,
+    /// Workspace file tree (loaded when the Files panel is first shown)
+    pub file_tree: Option<Vec<FileNode>>,
+    /// Files pinned into context
+    pub pinned_files: Vec<PathBuf>,
+    /// File open in the preview pane
+    pub preview: Option<FilePreview>,
+    /// Shell output shown in the terminal panel
+    pub terminal_lines: Vec<TerminalLine>,
+    /// Command being typed into the terminal panel
+    pub terminal_input: String,
+    /// Whether a shell command is currently running
+    pub shell_running: bool,
+    /// Laid-out transcript messages
+    pub transcript: TranscriptCache,
+    /// UI frame timings
+    pub frame_stats: FrameStats,
+    /// Last context inspection, shown in the Context panel
+    pub context: Option<ContextReport>,
+    /// Whether a context inspection has been requested
+    pub context_requested: bool,
+    /// Reply language being edited in the Status panel
+    pub language_input: String,
+    /// Session statistics, shown in the Status panel
+    pub stats: Option<SessionStats>,
+    /// Whether statistics have been requested
+    pub stats_requested: bool,
+    /// Per-model usage and suggestions, shown in the Status panel
+    pub model_usage: Option<ModelUsageReport>,
+    /// Ollama hosts, shown in the Status panel
+    pub ollama_hosts: Option<Vec<OllamaHostStatus>>,
+    /// Whether an Ollama host probe is running
+    pub ollama_hosts_requested: bool,
+    /// Provider health checks, shown in the Status panel
+    pub diagnostics: Option<Vec<ProviderHealth>>,
+    /// Whether health checks are running
+    pub diagnostics_requested: bool,
+    /// Model name typed into the Status panel's pull field
+    pub ollama_pull_input: String,
+    /// Download in progress (model and latest update)
+    pub ollama_pull: Option<(String, Option<OllamaPullProgress>)>,
+    /// Installed model (`model@host`) waiting for a second click to delete
+    pub ollama_delete_confirm: Option<String>,
+    /// Models the configured providers offer, for the model picker
+    pub available_models: Option<Vec<ProviderModels>>,
+    /// Whether a model list request is in flight
+    pub models_requested: bool,
+    /// Whether dictation is being recorded
+    pub recording: bool,
+    /// Whether recorded dictation is being transcribed
+    pub transcribing: bool,
+    /// Read finished replies aloud
+    pub speak_replies: bool,
+    /// Finished reply waiting to be sent to the worker to speak
+    pub pending_speech: Option<String>,
+    /// Agent the worker runs (`--agent`, or picked in the toolbar)
+    pub agent_id: String,
+    /// Agents to switch between, offered in the toolbar when there are several
+    pub agents: Vec<String>,
+    /// Agent picked in the toolbar; the app restarts the worker for it
+    pub switch_agent: Option<String>,
+    /// Plan being reviewed or run (`/plan`)
+    pub plan: Option<Plan>,
+    /// Whether the plan's steps are running
+    pub plan_running: bool,
+}
+
+/// Rolling UI frame timings, for spotting slow repaints
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    pub last_ms: f32,
+    /// Exponential moving average
+    pub avg_ms: f32,
+    /// Frames that went over the 16 ms budget
+    pub slow_frames: u64,
+}
+
+impl FrameStats {
+    pub fn record(&mut self, elapsed: std::time::Duration) {
+        let ms = elapsed.as_secs_f32() * 1000.0;
+        self.last_ms = ms;
+        self.avg_ms = if self.avg_ms == 0.0 {
+            ms
+        } else {
+            self.avg_ms * 0.95 + ms * 0.05
+        };
+        if ms > FRAME_BUDGET_MS {
+            self.slow_frames += 1;
+            tracing::debug!("Slow UI frame: {:.1} ms", ms);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -143,6 +516,9 @@ pub enum Panel {
     #[default]
     Chat,
     Sessions,
+    Files,
+    Terminal,
+    Context,
     Status,
 }
 
@@ -158,65 +534,209 @@ impl UiState {
                 model,
                 memory_chunks,
                 has_embeddings,
+                workspace,
+                speak_replies,
             } => {
                 self.model = model;
                 self.memory_chunks = memory_chunks;
                 self.has_embeddings = has_embeddings;
+                self.workspace = workspace;
+                self.speak_replies = speak_replies;
                 self.is_loading = false;
             }
             WorkerMessage::ContentChunk(content) => {
                 self.streaming_content.push_str(&content);
                 self.scroll_to_bottom = true;
             }
-            WorkerMessage::ToolCallStart {
-                name,
-                id: _,
-                detail,
-            } => {
+            WorkerMessage::ReasoningChunk(text) => {
+                self.streaming_reasoning.push_str(&text);
+            }
+            WorkerMessage::ToolCallStart { name, id, detail } => {
                 self.active_tools.push(ToolInfo {
+                    id,
                     name,
                     detail,
                     status: ToolStatus::Running,
+                    output: None,
+                    summary: None,
+                    cancelling: false,
                 });
             }
             WorkerMessage::ToolCallEnd {
                 name,
                 output,
-                id: _,
+                id,
+                summary,
+                failed,
             } => {
+                // Show generated images inline in the response
+                if name == "generate_image" {
+                    for line in output.lines().filter(|l| l.starts_with("![")) {
+                        self.streaming_content
+                            .push_str(&format!("\n\n{}\n\n", line));
+                    }
+                }
+
+                // A later search's numbering replaces an earlier one's
+                if name == "document_search" && !failed {
+                    for (n, source) in parse_sources(&output) {
+                        self.turn_sources.retain(|(m, _)| *m != n);
+                        self.turn_sources.push((n, source));
+                    }
+                }
+
                 // Update tool status
-                if let Some(tool) = self.active_tools.iter_mut().find(|t| t.name == name) {
-                    let preview = if output.len() > 100 {
-                        format!("{}...", &output[..100])
+                let tool = self.active_tools.iter_mut().find(|t| {
+                    if id.is_empty() {
+                        t.name == name && matches!(t.status, ToolStatus::Running)
+                    } else {
+                        t.id == id
+                    }
+                });
+                if let Some(tool) = tool {
+                    let preview = if output.chars().count() > 100 {
+                        format!("{}...", output.chars().take(100).collect::<String>())
+                    } else {
+                        output.clone()
+                    };
+                    tool.status = if failed {
+                        ToolStatus::Error(preview)
                     } else {
-                        output
+                        ToolStatus::Completed(preview)
                     };
-                    tool.status = ToolStatus::Completed(preview);
+                    if summary.is_some() {
+                        tool.output = Some(output);
+                        tool.summary = summary;
+                    }
                 }
             }
-            WorkerMessage::ToolsPendingApproval(calls) => {
-                self.pending_approval = Some(calls);
-                self.is_loading = false;
+            WorkerMessage::ToolsPendingApproval(request) => {
+                self.pending_approval = Some(request);
+                self.approval_remember = None;
+                self.scroll_to_bottom = true;
             }
             WorkerMessage::Done => {
+                // Stopped while asking
+                self.pending_approval = None;
+                // Keep summaries of long tool outputs in the transcript
+                for tool in self.active_tools.drain(..) {
+                    if let Some(ref summary) = tool.summary {
+                        self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: summary.clone(),
+                            tool_info: Some(tool),
+                            reasoning: None,
+                            variants: None,
+                            rating: None,
+                            pinned: false,
+                        });
+                    }
+                }
+
                 // Finalize streaming content as assistant message
-                if !self.streaming_content.is_empty() {
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::Assistant,
+                let reasoning = std::mem::take(&mut self.streaming_reasoning);
+                let sources = std::mem::take(&mut self.turn_sources);
+                if !self.streaming_content.is_empty() || !reasoning.is_empty() {
+                    if self.speak_replies && !self.streaming_content.is_empty() {
+                        self.pending_speech = Some(self.streaming_content.clone());
+                    }
+                    if let Some(footer) = cited_sources(&self.streaming_content, &sources) {
+                        self.streaming_content.push_str(&footer);
+                    }
+                    let reply = ReplyVariant {
                         content: std::mem::take(&mut self.streaming_content),
+                        reasoning: (!reasoning.is_empty()).then_some(reasoning),
+                    };
+                    self.finish_reply(reply);
+                }
+                if std::mem::take(&mut self.stopping) {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Stopped.".to_string(),
                         tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
                     });
                 }
+                self.regenerating = false;
                 self.active_tools.clear();
                 self.is_loading = false;
                 self.scroll_to_bottom = true;
             }
             WorkerMessage::Error(err) => {
                 self.error = Some(err);
+                self.error_timed_out = false;
+                self.is_loading = false;
+                self.regenerating = false;
+                self.stopping = false;
+                self.streaming_content.clear();
+                self.streaming_reasoning.clear();
+                self.turn_sources.clear();
+            }
+            WorkerMessage::TimedOut(err) => {
+                self.error = Some(err);
+                self.error_timed_out = true;
+                self.is_loading = false;
+                self.regenerating = false;
+                self.stopping = false;
+                self.streaming_content.clear();
+                self.streaming_reasoning.clear();
+                self.turn_sources.clear();
+            }
+            WorkerMessage::BudgetExceeded { provider, message } => {
+                self.error = Some(message);
+                self.error_timed_out = false;
+                self.budget_exceeded = Some(provider);
+                self.is_loading = false;
+                self.regenerating = false;
+                self.stopping = false;
+            }
+            WorkerMessage::AuthError {
+                provider,
+                model,
+                message,
+            } => {
                 self.is_loading = false;
                 self.streaming_content.clear();
+                self.streaming_reasoning.clear();
+                self.active_tools.clear();
+                self.api_key_prompt = Some(ApiKeyPrompt {
+                    provider,
+                    model,
+                    message,
+                    key: String::new(),
+                    testing: false,
+                    error: None,
+                });
+            }
+            WorkerMessage::ApiKeyAccepted { provider, retrying } => {
+                self.api_key_prompt = None;
+                let mut text = format!("Now using the new {} API key.", provider);
+                if retrying {
+                    text.push_str(" Retrying your last message...");
+                }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: text,
+                    tool_info: None,
+                    reasoning: None,
+                    variants: None,
+                    rating: None,
+                    pinned: false,
+                });
+                self.is_loading = retrying;
+                self.scroll_to_bottom = true;
+            }
+            WorkerMessage::ApiKeyRejected(err) => {
+                if let Some(ref mut prompt) = self.api_key_prompt {
+                    prompt.testing = false;
+                    prompt.error = Some(err);
+                }
             }
             WorkerMessage::Status(status) => {
+                self.language_input = status.language.clone().unwrap_or_default();
                 self.status = Some(status);
             }
             WorkerMessage::Sessions(sessions) => {
@@ -232,30 +752,258 @@ impl UiState {
                 // Clear chat on session change
                 self.messages.clear();
                 self.streaming_content.clear();
+                self.streaming_reasoning.clear();
+            }
+            WorkerMessage::PinnedFiles(files) => {
+                self.pinned_files = files;
+            }
+            WorkerMessage::Shell(event) => self.handle_shell_event(event),
+            WorkerMessage::Context(report) => {
+                self.context = Some(report);
+                self.context_requested = false;
+            }
+            WorkerMessage::Stats(stats) => {
+                self.stats = Some(stats);
+                self.stats_requested = false;
+            }
+            WorkerMessage::ModelUsage(report) => {
+                self.model_usage = Some(report);
+            }
+            WorkerMessage::LocalModel(status) => {
+                self.local_model = Some(status);
+            }
+            WorkerMessage::Models(models) => {
+                self.available_models = Some(models);
+                self.models_requested = false;
+            }
+            WorkerMessage::ModelChanged(model) => {
+                self.model = model;
+            }
+            WorkerMessage::OllamaHosts(hosts) => {
+                self.ollama_hosts = Some(hosts);
+                self.ollama_hosts_requested = false;
+            }
+            WorkerMessage::Diagnostics(results) => {
+                self.diagnostics = Some(results);
+                self.diagnostics_requested = false;
+            }
+            WorkerMessage::OllamaPull(progress) => {
+                self.ollama_pull = Some((progress.model.clone(), Some(progress)));
+            }
+            WorkerMessage::Recording(recording) => {
+                self.recording = recording;
+                self.transcribing = !recording;
+            }
+            WorkerMessage::Transcribed(text) => {
+                self.transcribing = false;
+                if !text.is_empty() {
+                    if !self.input.is_empty() && !self.input.ends_with(char::is_whitespace) {
+                        self.input.push(' ');
+                    }
+                    self.input.push_str(&text);
+                }
+            }
+            WorkerMessage::VoiceError(err) => {
+                self.recording = false;
+                self.transcribing = false;
+                self.error = Some(err);
+                self.error_timed_out = false;
+            }
+            WorkerMessage::OllamaPullFinished { model, error } => {
+                self.ollama_pull = None;
+                let content = match error {
+                    Some(error) => format!("Failed to pull {}: {}", model, error),
+                    None => format!("Pulled Ollama model {}", model),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    tool_info: None,
+                    reasoning: None,
+                    variants: None,
+                    rating: None,
+                    pinned: false,
+                });
+            }
+            WorkerMessage::MessagePinned { content, pinned } => {
+                if let Some(msg) =
+                    self.messages.iter_mut().rev().find(|m| {
+                        m.role != MessageRole::System && m.content.trim() == content.trim()
+                    })
+                {
+                    msg.pinned = pinned;
+                }
+            }
+            WorkerMessage::PlanProposed(plan) => {
+                self.plan = Some(plan);
+                self.plan_running = false;
+                self.is_loading = false;
+                self.scroll_to_bottom = true;
+            }
+            WorkerMessage::PlanStep { index, status } => {
+                let Some(ref mut plan) = self.plan else {
+                    return;
+                };
+                let total = plan.steps.len();
+                let Some(step) = plan.steps.get_mut(index) else {
+                    return;
+                };
+                step.status = status;
+                if status == StepStatus::Running {
+                    let content = format!("Step {}/{}: {}", index + 1, total, step.description);
+                    self.add_user_message(content);
+                    self.is_loading = true;
+                }
+            }
+            WorkerMessage::PlanFinished => {
+                self.plan_running = false;
+                self.is_loading = false;
+                if let Some(ref mut plan) = self.plan {
+                    plan.skip_remaining();
+                    let summary = format!(
+                        "Plan finished: {} of {} steps done",
+                        plan.done_count(),
+                        plan.steps.len()
+                    );
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: summary,
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    self.scroll_to_bottom = true;
+                }
             }
             WorkerMessage::SystemMessage(text) => {
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
                     content: text,
                     tool_info: None,
+                    reasoning: None,
+                    variants: None,
+                    rating: None,
+                    pinned: false,
                 });
                 self.scroll_to_bottom = true;
             }
         }
     }
 
+    /// Add a finished reply, or another version of the last one when regenerating
+    fn finish_reply(&mut self, reply: ReplyVariant) {
+        let last_reply = self
+            .messages
+            .iter()
+            .rposition(|m| m.role == MessageRole::Assistant);
+        if let (true, Some(index)) = (self.regenerating, last_reply) {
+            let msg = &mut self.messages[index];
+            let variants = msg.variants.get_or_insert_with(|| ReplyVariants {
+                items: vec![ReplyVariant {
+                    content: msg.content.clone(),
+                    reasoning: msg.reasoning.clone(),
+                }],
+                current: 0,
+            });
+            variants.items.push(reply);
+            let newest = variants.items.len() - 1;
+            msg.show_variant(newest);
+            return;
+        }
+
+        self.messages.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: reply.content,
+            tool_info: None,
+            reasoning: reply.reasoning,
+            variants: None,
+            rating: None,
+            pinned: false,
+        });
+    }
+
+    fn handle_shell_event(&mut self, event: ShellEvent) {
+        let (text, kind) = match event {
+            ShellEvent::Started { command } => {
+                self.shell_running = true;
+                (format!("$ {}", command), TerminalLineKind::Command)
+            }
+            ShellEvent::Output { text, stderr } => {
+                let kind = if stderr {
+                    TerminalLineKind::Stderr
+                } else {
+                    TerminalLineKind::Stdout
+                };
+                (text.trim_end_matches(['\r', '\n']).to_string(), kind)
+            }
+            ShellEvent::Finished { exit_code } => {
+                self.shell_running = false;
+                let text = match exit_code {
+                    Some(code) => format!("[exit {}]", code),
+                    None => "[terminated]".to_string(),
+                };
+                (text, TerminalLineKind::Exit)
+            }
+        };
+
+        self.terminal_lines.push(TerminalLine { text, kind });
+        if self.terminal_lines.len() > MAX_TERMINAL_LINES {
+            let excess = self.terminal_lines.len() - MAX_TERMINAL_LINES;
+            self.terminal_lines.drain(..excess);
+        }
+    }
+
     /// Add a user message
     pub fn add_user_message(&mut self, content: String) {
         self.messages.push(ChatMessage {
             role: MessageRole::User,
             content,
             tool_info: None,
+            reasoning: None,
+            variants: None,
+            rating: None,
+            pinned: false,
         });
         self.scroll_to_bottom = true;
     }
 
+    /// Whether the worker has reported the workspace directory yet
+    pub fn has_workspace(&self) -> bool {
+        !self.workspace.as_os_str().is_empty()
+    }
+
+    /// ID of the active session, if known yet
+    pub fn session_id(&self) -> Option<&str> {
+        self.current_session
+            .as_ref()
+            .map(|s| s.id.as_str())
+            .or_else(|| self.status.as_ref().map(|s| s.id.as_str()))
+    }
+
     /// Clear error
     pub fn clear_error(&mut self) {
         self.error = None;
+        self.error_timed_out = false;
+        self.budget_exceeded = None;
+    }
+}
+
+/// Markdown list of the sources a reply cites as [n], None when it cites
+/// none of them
+fn cited_sources(reply: &str, sources: &[(usize, String)]) -> Option<String> {
+    let mut cited: Vec<&(usize, String)> = sources
+        .iter()
+        .filter(|(n, _)| reply.contains(&format!("[{}]", n)))
+        .collect();
+    if cited.is_empty() {
+        return None;
+    }
+    cited.sort_by_key(|(n, _)| *n);
+    let mut footer = "\n\n**Sources**\n".to_string();
+    for (n, source) in cited {
+        footer.push_str(&format!("\n- [{}] {}", n, source));
     }
+    Some(footer)
 }
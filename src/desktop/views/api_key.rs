@@ -0,0 +1,85 @@
+//! API key dialog - replace a key the provider rejected
+
+use eframe::egui::{self, Color32, RichText, TextEdit};
+
+use crate::desktop::state::{UiMessage, UiState};
+
+/// Longest part of the provider's error shown in the dialog
+const MAX_ERROR_CHARS: usize = 300;
+
+pub struct ApiKeyDialog;
+
+impl ApiKeyDialog {
+    pub fn show(ctx: &egui::Context, state: &mut UiState) -> Option<UiMessage> {
+        let prompt = state.api_key_prompt.as_mut()?;
+        let mut message_to_send = None;
+        let mut cancelled = false;
+
+        egui::Window::new("API key rejected")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} did not accept the configured API key. It may be invalid, \
+                    expired, or revoked.",
+                    prompt.provider
+                ));
+                let error: String = prompt.message.chars().take(MAX_ERROR_CHARS).collect();
+                ui.label(RichText::new(error).small().color(Color32::GRAY));
+                ui.add_space(8.0);
+
+                ui.label("Paste a new key:");
+                let response = ui.add_enabled(
+                    !prompt.testing,
+                    TextEdit::singleline(&mut prompt.key)
+                        .password(true)
+                        .desired_width(360.0),
+                );
+                if let Some(ref error) = prompt.error {
+                    ui.colored_label(Color32::from_rgb(231, 76, 60), error);
+                }
+                ui.label(
+                    RichText::new("The key is tested, then saved to the OS keyring.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    let key = prompt.key.trim();
+                    let can_submit = !prompt.testing && !key.is_empty();
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui
+                        .add_enabled(can_submit, egui::Button::new("Test and save"))
+                        .clicked()
+                        || (can_submit && submitted)
+                    {
+                        message_to_send = Some(UiMessage::UpdateApiKey {
+                            provider: prompt.provider.clone(),
+                            model: prompt.model.clone(),
+                            key: key.to_string(),
+                        });
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                    if prompt.testing {
+                        ui.spinner();
+                        ui.label("Testing...");
+                    }
+                });
+            });
+
+        if message_to_send.is_some() {
+            prompt.testing = true;
+            prompt.error = None;
+        }
+        if cancelled {
+            state.api_key_prompt = None;
+            state.error = Some("Request failed: the API key was rejected".to_string());
+        }
+        message_to_send
+    }
+}
@@ -1,13 +1,143 @@
 //! Chat view - message display and input
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use eframe::egui::{self, Color32, RichText, ScrollArea, TextEdit, Ui};
 
+use super::markdown::{render_markdown, LaidOutMarkdown};
+use crate::agent::{
+    paste_message, read_clipboard, write_clipboard, ApprovalScope, LocalModelState,
+    LocalModelStatus, PlanStep, Rating, RegenerateStyle, StepStatus, MAX_PLAN_STEPS,
+};
 use crate::desktop::state::{ChatMessage, MessageRole, Panel, ToolStatus, UiMessage, UiState};
 
+/// Space between transcript messages
+const MESSAGE_SPACING: f32 = 8.0;
+
+/// Per-message layout cache for the transcript. Finished messages are laid
+/// out once per width and style; only the streaming tail is laid out every
+/// frame. Messages scrolled out of view are skipped using their last height.
+#[derive(Default)]
+pub struct TranscriptCache {
+    /// Width and style the entries were laid out for
+    layout_key: u64,
+    entries: Vec<CachedMessage>,
+}
+
+struct CachedMessage {
+    content_hash: u64,
+    body: LaidOutMarkdown,
+    /// Height of the whole message block when last drawn
+    height: Option<f32>,
+}
+
+impl TranscriptCache {
+    /// Number of messages with a cached layout
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drop stale entries and lay out new messages
+    fn sync(&mut self, ui: &Ui, messages: &[ChatMessage]) {
+        let width = ui.available_width();
+        let mut hasher = DefaultHasher::new();
+        width.to_bits().hash(&mut hasher);
+        ui.visuals().dark_mode.hash(&mut hasher);
+        ui.style()
+            .text_styles
+            .get(&egui::TextStyle::Body)
+            .map(|f| f.size.to_bits())
+            .hash(&mut hasher);
+        let layout_key = hasher.finish();
+
+        if layout_key != self.layout_key {
+            self.entries.clear();
+            self.layout_key = layout_key;
+        }
+
+        self.entries.truncate(messages.len());
+        if let Some(changed) = self
+            .entries
+            .iter()
+            .zip(messages)
+            .position(|(entry, msg)| entry.content_hash != message_hash(msg))
+        {
+            self.entries.truncate(changed);
+        }
+
+        for msg in &messages[self.entries.len()..] {
+            let _span = tracing::trace_span!("layout_message", len = msg.content.len()).entered();
+            // Assistant replies are markdown; user/system text is shown as typed
+            let body = if msg.role == MessageRole::Assistant {
+                LaidOutMarkdown::layout(ui, &msg.content, width)
+            } else {
+                LaidOutMarkdown::plain(ui, &msg.content, width)
+            };
+            self.entries.push(CachedMessage {
+                content_hash: message_hash(msg),
+                body,
+                height: None,
+            });
+        }
+    }
+
+    /// Draw the transcript. `last_reply` is the message that gets the
+    /// regenerate actions (None while a response is in progress).
+    fn show(
+        &mut self,
+        ui: &mut Ui,
+        messages: &[ChatMessage],
+        last_reply: Option<usize>,
+    ) -> Option<(usize, MessageAction)> {
+        self.sync(ui, messages);
+        let mut action = None;
+
+        for (i, (msg, entry)) in messages.iter().zip(self.entries.iter_mut()).enumerate() {
+            let width = ui.available_width();
+            if let Some(height) = entry.height {
+                let rect = egui::Rect::from_min_size(ui.cursor().min, egui::vec2(width, height));
+                if !ui.is_rect_visible(rect) {
+                    ui.allocate_space(egui::vec2(width, height));
+                    ui.add_space(MESSAGE_SPACING);
+                    continue;
+                }
+            }
+
+            let response = ui.vertical(|ui| {
+                ChatView::render_message(ui, i, msg, &entry.body, last_reply == Some(i))
+            });
+            if let Some(clicked) = response.inner {
+                action = Some((i, clicked));
+            }
+            entry.height = Some(response.response.rect.height());
+            ui.add_space(MESSAGE_SPACING);
+        }
+        action
+    }
+}
+
+/// Something clicked on a message in the transcript
+enum MessageAction {
+    ShowVariant(usize),
+    Regenerate(RegenerateStyle),
+    Rate(Option<Rating>),
+    Pin(bool),
+}
+
+fn message_hash(msg: &ChatMessage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    std::mem::discriminant(&msg.role).hash(&mut hasher);
+    msg.content.hash(&mut hasher);
+    msg.tool_info.as_ref().map(|t| &t.name).hash(&mut hasher);
+    hasher.finish()
+}
+
 pub struct ChatView;
 
 impl ChatView {
     pub fn show(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+        let _span = tracing::trace_span!("chat_view").entered();
         let mut message_to_send = None;
 
         // Main chat area
@@ -22,13 +152,55 @@ impl ChatView {
             .show(ui, |ui| {
                 ui.set_min_width(ui.available_width());
 
-                // Show messages
-                for msg in &state.messages {
-                    Self::render_message(ui, msg);
-                    ui.add_space(8.0);
+                // Show messages (cached layout)
+                let last_reply = if state.is_loading {
+                    None
+                } else {
+                    state
+                        .messages
+                        .iter()
+                        .rposition(|m| m.role == MessageRole::Assistant)
+                };
+                match state.transcript.show(ui, &state.messages, last_reply) {
+                    Some((index, MessageAction::ShowVariant(variant))) => {
+                        let msg = &mut state.messages[index];
+                        msg.show_variant(variant);
+                        // Later turns should build on the version being read
+                        if last_reply == Some(index) {
+                            message_to_send = Some(UiMessage::UseReplyVariant(msg.content.clone()));
+                        }
+                    }
+                    Some((_, MessageAction::Regenerate(style))) => {
+                        state.regenerating = true;
+                        state.is_loading = true;
+                        message_to_send = Some(UiMessage::Regenerate(style));
+                    }
+                    Some((index, MessageAction::Pin(pinned))) => {
+                        message_to_send = Some(UiMessage::PinMessage {
+                            content: Some(state.messages[index].content.clone()),
+                            pinned,
+                        });
+                    }
+                    Some((index, MessageAction::Rate(rating))) => {
+                        let msg = &mut state.messages[index];
+                        msg.rating = rating;
+                        message_to_send = Some(UiMessage::RateReply {
+                            content: msg.content.clone(),
+                            rating,
+                        });
+                    }
+                    None => {}
                 }
 
-                // Show streaming content if any
+                // Show streaming reasoning and content if any
+                if !state.streaming_reasoning.is_empty() {
+                    reasoning_panel(
+                        ui,
+                        "streaming_reasoning",
+                        &state.streaming_reasoning,
+                        "Thinking...",
+                    );
+                }
                 if !state.streaming_content.is_empty() {
                     ui.horizontal(|ui| {
                         ui.label(
@@ -37,12 +209,12 @@ impl ChatView {
                                 .color(Color32::from_rgb(100, 149, 237)),
                         );
                     });
-                    ui.label(&state.streaming_content);
-                    ui.add_space(8.0);
+                    render_markdown(ui, &state.streaming_content);
+                    ui.add_space(MESSAGE_SPACING);
                 }
 
                 // Show active tools
-                for tool in &state.active_tools {
+                for tool in &mut state.active_tools {
                     ui.horizontal(|ui| match &tool.status {
                         ToolStatus::Running => {
                             ui.spinner();
@@ -51,10 +223,20 @@ impl ChatView {
                             } else {
                                 ui.label(format!("Running: {}", tool.name));
                             }
+                            let cancel = ui
+                                .add_enabled(!tool.cancelling, egui::Button::new("Cancel").small());
+                            if cancel
+                                .on_hover_text("Stop this tool call; the reply goes on without it")
+                                .clicked()
+                            {
+                                tool.cancelling = true;
+                                message_to_send = Some(UiMessage::CancelTool(tool.id.clone()));
+                            }
                         }
                         ToolStatus::Completed(preview) => {
                             ui.label(RichText::new("Done").color(Color32::from_rgb(46, 204, 113)));
-                            ui.label(format!("{}: {}", tool.name, preview));
+                            let text = tool.summary.as_ref().unwrap_or(preview);
+                            ui.label(format!("{}: {}", tool.name, text));
                         }
                         ToolStatus::Error(err) => {
                             ui.label(RichText::new("Error").color(Color32::from_rgb(231, 76, 60)));
@@ -65,26 +247,57 @@ impl ChatView {
 
                 // Show pending approval dialog
                 if state.pending_approval.is_some() {
-                    let tools = state.pending_approval.clone().unwrap();
+                    let request = state.pending_approval.clone().unwrap();
                     ui.add_space(10.0);
                     ui.group(|ui| {
                         ui.label(RichText::new("Tools pending approval:").strong());
-                        for tool in &tools {
-                            ui.label(format!("  - {}", tool.name));
+                        for (index, pending) in request.calls.iter().enumerate() {
+                            match pending.detail {
+                                Some(ref detail) => {
+                                    ui.label(format!("  - {}: {}", pending.call.name, detail))
+                                }
+                                None => ui.label(format!("  - {}", pending.call.name)),
+                            };
+                            if let Some(ref preview) = pending.preview {
+                                diff_preview(ui, ("approval_preview", index), preview);
+                            }
+                        }
+                        let tools = request.rememberable_tools();
+                        if !tools.is_empty() {
+                            let tools = tools.join(", ");
+                            for (scope, label) in [
+                                (ApprovalScope::Session, "this session"),
+                                (ApprovalScope::Agent, "this agent"),
+                            ] {
+                                let mut checked = state.approval_remember == Some(scope);
+                                let text =
+                                    format!("Remember this choice for {} in {}", tools, label);
+                                if ui.checkbox(&mut checked, text).changed() {
+                                    state.approval_remember = checked.then_some(scope);
+                                }
+                            }
                         }
                         ui.horizontal(|ui| {
                             if ui.button("Approve").clicked() {
-                                message_to_send = Some(UiMessage::ApproveTools(tools.clone()));
+                                request.answer(true, state.approval_remember.take());
                                 state.pending_approval = None;
                             }
                             if ui.button("Deny").clicked() {
-                                message_to_send = Some(UiMessage::DenyTools);
+                                request.answer(false, state.approval_remember.take());
                                 state.pending_approval = None;
                             }
                         });
                     });
                 }
 
+                // Show the plan under review or running
+                if state.plan.is_some() {
+                    ui.add_space(10.0);
+                    if let Some(msg) = plan_panel(ui, state) {
+                        message_to_send = Some(msg);
+                    }
+                }
+
                 // Scroll to bottom if requested
                 if state.scroll_to_bottom {
                     ui.scroll_to_cursor(Some(egui::Align::BOTTOM));
@@ -97,8 +310,31 @@ impl ChatView {
             let error = state.error.clone().unwrap();
             ui.add_space(5.0);
             ui.horizontal(|ui| {
-                ui.label(RichText::new("Error: ").color(Color32::from_rgb(231, 76, 60)));
+                if state.error_timed_out {
+                    ui.label(RichText::new("Timed out: ").color(Color32::from_rgb(230, 126, 34)));
+                } else {
+                    ui.label(RichText::new("Error: ").color(Color32::from_rgb(231, 76, 60)));
+                }
                 ui.label(&error);
+                if state.error_timed_out && ui.small_button("Retry").clicked() {
+                    state.clear_error();
+                    state.is_loading = true;
+                    message_to_send = Some(UiMessage::RetryFailedMessage);
+                }
+                if let Some(provider) = state.budget_exceeded.clone() {
+                    if ui
+                        .small_button("Continue anyway")
+                        .on_hover_text(format!(
+                            "Ignore the {} budget until LocalGPT exits",
+                            provider
+                        ))
+                        .clicked()
+                    {
+                        state.clear_error();
+                        state.is_loading = true;
+                        message_to_send = Some(UiMessage::OverrideBudget(provider));
+                    }
+                }
                 if ui.small_button("Dismiss").clicked() {
                     state.clear_error();
                 }
@@ -116,10 +352,19 @@ impl ChatView {
                     .frame(true),
             );
 
-            let can_send = !state.input.trim().is_empty() && !state.is_loading;
-            let send_clicked = ui
-                .add_enabled(can_send, egui::Button::new("Send"))
-                .clicked();
+            let can_send =
+                !state.input.trim().is_empty() && !state.is_loading && !state.plan_running;
+            let send_clicked = if state.is_loading {
+                let stop = ui.add_enabled(!state.stopping, egui::Button::new("Stop"));
+                if stop.on_hover_text("Stop generating").clicked() {
+                    state.stopping = true;
+                    message_to_send = Some(UiMessage::StopGeneration);
+                }
+                false
+            } else {
+                ui.add_enabled(can_send, egui::Button::new("Send"))
+                    .clicked()
+            };
 
             // Send on Enter or button click
             let enter_pressed =
@@ -168,8 +413,16 @@ impl ChatView {
                     // Show current model
                     state.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: format!("Current model: {}", state.model),
+                        content: format!(
+                            "Current model: {}. Pick another from the model menu in the \
+                             toolbar, or use /model <name>.",
+                            state.model
+                        ),
                         tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
                     });
                     state.scroll_to_bottom = true;
                     None // No message to send to worker
@@ -177,13 +430,226 @@ impl ChatView {
                     Some(UiMessage::SetModel(arg.to_string()))
                 }
             }
-            "/compact" => Some(UiMessage::Compact),
+            "/ask" => match arg.split_once(char::is_whitespace) {
+                Some((model, prompt)) if !prompt.trim().is_empty() => {
+                    state.add_user_message(format!("@{} {}", model, prompt.trim()));
+                    state.is_loading = true;
+                    Some(UiMessage::Ask {
+                        model: model.to_string(),
+                        message: prompt.trim().to_string(),
+                    })
+                }
+                _ => {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Usage: /ask <model> <message>".to_string(),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+            },
+            "/plan" => {
+                if arg.is_empty() {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Usage: /plan <task>".to_string(),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                } else {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("Planning: {}", arg),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.plan = None;
+                    state.is_loading = true;
+                    state.scroll_to_bottom = true;
+                    Some(UiMessage::MakePlan(arg.to_string()))
+                }
+            }
+            "/set" => {
+                let settings: Option<Vec<(String, String)>> = arg
+                    .split_whitespace()
+                    .map(|s| {
+                        s.split_once('=')
+                            .map(|(k, v)| (k.to_string(), v.to_string()))
+                    })
+                    .collect();
+                match settings {
+                    Some(settings) if !settings.is_empty() => Some(UiMessage::SetParams(settings)),
+                    _ => {
+                        state.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: "Usage: /set temperature=0.2 [max_tokens=2000]".to_string(),
+                            tool_info: None,
+                            reasoning: None,
+                            variants: None,
+                            rating: None,
+                            pinned: false,
+                        });
+                        state.scroll_to_bottom = true;
+                        None
+                    }
+                }
+            }
+            "/system" => match arg {
+                "" => {
+                    let current = state
+                        .status
+                        .as_ref()
+                        .and_then(|s| s.system_prompt.clone())
+                        .unwrap_or_else(|| "none".to_string());
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("System instructions: {}", current),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+                "clear" => Some(UiMessage::SetSystemPrompt(None)),
+                text => Some(UiMessage::SetSystemPrompt(Some(text.to_string()))),
+            },
+            "/lang" => match arg {
+                "" => {
+                    let current = state
+                        .status
+                        .as_ref()
+                        .and_then(|s| s.language.clone())
+                        .unwrap_or_else(|| "not set".to_string());
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("Reply language: {}", current),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+                "off" | "clear" => Some(UiMessage::SetLanguage(None)),
+                language => Some(UiMessage::SetLanguage(Some(language.to_string()))),
+            },
+            "/compact" => match arg.strip_prefix("strategy") {
+                Some(name) => Some(UiMessage::CompactionStrategy(
+                    Some(name.trim())
+                        .filter(|n| !n.is_empty())
+                        .map(str::to_string),
+                )),
+                None => Some(UiMessage::Compact),
+            },
+            "/pin" | "/unpin" => Some(UiMessage::PinMessage {
+                content: Some(arg).filter(|a| !a.is_empty()).map(str::to_string),
+                pinned: cmd == "/pin",
+            }),
+            "/pins" => Some(UiMessage::ListPins),
+            "/cache" => match arg {
+                "" => Some(UiMessage::ResponseCache(false)),
+                "clear" => Some(UiMessage::ResponseCache(true)),
+                _ => {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Usage: /cache [clear]".to_string(),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+            },
+            "/paste" => match read_clipboard() {
+                Ok(text) if !text.trim().is_empty() => {
+                    let content = paste_message(arg, &text);
+                    state.add_user_message(content.clone());
+                    state.is_loading = true;
+                    Some(UiMessage::Chat(content))
+                }
+                result => {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: match result {
+                            Err(e) => e.to_string(),
+                            Ok(_) => "The clipboard has no text".to_string(),
+                        },
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+            },
+            "/copy" => {
+                let reply = state
+                    .messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == MessageRole::Assistant && !m.content.is_empty())
+                    .map(|m| m.content.clone());
+                let content = match reply {
+                    Some(reply) => match write_clipboard(&reply) {
+                        Ok(()) => "Copied the last reply to the clipboard".to_string(),
+                        Err(e) => e.to_string(),
+                    },
+                    None => "No reply to copy yet".to_string(),
+                };
+                state.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    tool_info: None,
+                    reasoning: None,
+                    variants: None,
+                    rating: None,
+                    pinned: false,
+                });
+                state.scroll_to_bottom = true;
+                None
+            }
+            "/context" => {
+                state.active_panel = Panel::Context;
+                Some(UiMessage::InspectContext)
+            }
+            "/undo" => {
+                // Files may change on disk; reload the tree when next shown
+                state.file_tree = None;
+                Some(UiMessage::UndoChanges)
+            }
             "/memory" => {
                 if arg.is_empty() {
                     state.messages.push(ChatMessage {
                         role: MessageRole::System,
                         content: "Usage: /memory <query>".to_string(),
                         tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
                     });
                     state.scroll_to_bottom = true;
                     None
@@ -194,12 +660,25 @@ impl ChatView {
             "/save" => Some(UiMessage::Save),
             "/help" => Some(UiMessage::ShowHelp),
             "/status" => Some(UiMessage::ShowStatus),
+            "/stats" => {
+                state.active_panel = Panel::Status;
+                Some(UiMessage::RefreshStatus)
+            }
+            "/doctor" => {
+                state.active_panel = Panel::Status;
+                state.diagnostics_requested = true;
+                Some(UiMessage::RunDoctor)
+            }
             "/resume" => {
                 if arg.is_empty() {
                     state.messages.push(ChatMessage {
                         role: MessageRole::System,
                         content: "Usage: /resume <session-id>".to_string(),
                         tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
                     });
                     state.scroll_to_bottom = true;
                     None
@@ -207,6 +686,35 @@ impl ChatView {
                     Some(UiMessage::ResumeSession(arg.to_string()))
                 }
             }
+            "/regenerate" => match RegenerateStyle::parse(arg) {
+                Some(style)
+                    if state
+                        .messages
+                        .iter()
+                        .any(|m| m.role == MessageRole::Assistant) =>
+                {
+                    state.regenerating = true;
+                    state.is_loading = true;
+                    Some(UiMessage::Regenerate(style))
+                }
+                Some(_) => {
+                    state.error = Some("Nothing to regenerate yet".to_string());
+                    None
+                }
+                None => {
+                    state.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Usage: /regenerate [creative|precise]".to_string(),
+                        tool_info: None,
+                        reasoning: None,
+                        variants: None,
+                        rating: None,
+                        pinned: false,
+                    });
+                    state.scroll_to_bottom = true;
+                    None
+                }
+            },
             "/sessions" => {
                 state.active_panel = Panel::Sessions;
                 Some(UiMessage::RefreshSessions)
@@ -219,6 +727,10 @@ impl ChatView {
                         cmd
                     ),
                     tool_info: None,
+                    reasoning: None,
+                    variants: None,
+                    rating: None,
+                    pinned: false,
                 });
                 state.scroll_to_bottom = true;
                 None
@@ -226,7 +738,14 @@ impl ChatView {
         }
     }
 
-    fn render_message(ui: &mut Ui, msg: &ChatMessage) {
+    fn render_message(
+        ui: &mut Ui,
+        index: usize,
+        msg: &ChatMessage,
+        body: &LaidOutMarkdown,
+        is_last_reply: bool,
+    ) -> Option<MessageAction> {
+        let mut action = None;
         let (label, color) = match msg.role {
             MessageRole::User => ("You", Color32::from_rgb(52, 152, 219)),
             MessageRole::Assistant => ("Assistant", Color32::from_rgb(100, 149, 237)),
@@ -234,11 +753,102 @@ impl ChatView {
         };
 
         ui.horizontal(|ui| {
-            ui.label(RichText::new(label).strong().color(color));
+            let header = ui
+                .label(RichText::new(label).strong().color(color))
+                .interact(egui::Sense::click());
+            if msg.pinned {
+                ui.label("📌")
+                    .on_hover_text("Pinned: kept verbatim when the session is compacted");
+            }
+            // Right-click to pin; tool calls stay with their results instead
+            if msg.role != MessageRole::System && msg.tool_info.is_none() {
+                header.context_menu(|ui| {
+                    let text = if msg.pinned { "Unpin" } else { "Pin" };
+                    if ui
+                        .button(text)
+                        .on_hover_text("Pinned messages are kept verbatim by compaction")
+                        .clicked()
+                    {
+                        action = Some(MessageAction::Pin(!msg.pinned));
+                        ui.close_menu();
+                    }
+                });
+            }
         });
 
-        // Render content with basic markdown-like formatting
-        ui.label(&msg.content);
+        if let Some(ref reasoning) = msg.reasoning {
+            reasoning_panel(ui, ("reasoning", index), reasoning, "Thinking");
+        }
+
+        body.show(ui);
+
+        let rateable = msg.role == MessageRole::Assistant && msg.tool_info.is_none();
+        if msg.variants.is_some() || is_last_reply || rateable {
+            ui.horizontal(|ui| {
+                if rateable {
+                    for (label, hint, rating) in [
+                        ("👍", "Good reply", Rating::Up),
+                        ("👎", "Bad reply", Rating::Down),
+                    ] {
+                        let selected = msg.rating == Some(rating);
+                        if ui
+                            .add(egui::Button::new(label).small().selected(selected))
+                            .on_hover_text(hint)
+                            .clicked()
+                        {
+                            // Clicking the current rating again clears it
+                            action = Some(MessageAction::Rate((!selected).then_some(rating)));
+                        }
+                    }
+                }
+                if let Some(ref variants) = msg.variants {
+                    let current = variants.current;
+                    if ui
+                        .add_enabled(current > 0, egui::Button::new("<").small())
+                        .clicked()
+                    {
+                        action = Some(MessageAction::ShowVariant(current - 1));
+                    }
+                    ui.label(
+                        RichText::new(format!("{}/{}", current + 1, variants.items.len()))
+                            .small()
+                            .color(Color32::GRAY),
+                    );
+                    if ui
+                        .add_enabled(
+                            current + 1 < variants.items.len(),
+                            egui::Button::new(">").small(),
+                        )
+                        .clicked()
+                    {
+                        action = Some(MessageAction::ShowVariant(current + 1));
+                    }
+                }
+                if is_last_reply {
+                    for (label, hint, style) in [
+                        (
+                            "Regenerate",
+                            "Run the last message again",
+                            RegenerateStyle::Same,
+                        ),
+                        (
+                            "More creative",
+                            "Regenerate with a higher temperature",
+                            RegenerateStyle::Creative,
+                        ),
+                        (
+                            "More precise",
+                            "Regenerate with a low temperature",
+                            RegenerateStyle::Precise,
+                        ),
+                    ] {
+                        if ui.small_button(label).on_hover_text(hint).clicked() {
+                            action = Some(MessageAction::Regenerate(style));
+                        }
+                    }
+                }
+            });
+        }
 
         // Show tool info if any
         if let Some(ref tool_info) = msg.tool_info {
@@ -249,22 +859,403 @@ impl ChatView {
                         .color(Color32::GRAY),
                 );
             });
+
+            // Raw output of a summarized tool call, collapsed by default
+            if let Some(ref output) = tool_info.output {
+                egui::CollapsingHeader::new(format!(
+                    "Raw output ({} chars)",
+                    output.chars().count()
+                ))
+                .id_salt(("tool_output", index))
+                .show(ui, |ui| {
+                    ScrollArea::vertical()
+                        .id_salt(("tool_output_scroll", index))
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(output).monospace().small());
+                        });
+                });
+            }
+        }
+
+        action
+    }
+}
+
+/// The `/plan` steps: editable while under review, with live statuses
+/// while running
+fn plan_panel(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+    let running = state.plan_running;
+    let plan = state.plan.as_mut()?;
+    let reviewing = !running && plan.steps.iter().all(|s| s.status == StepStatus::Pending);
+    let mut message = None;
+    let mut remove = None;
+    let mut close = false;
+
+    ui.group(|ui| {
+        ui.label(RichText::new(format!("Plan: {}", plan.task)).strong());
+        for (index, step) in plan.steps.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                match step.status {
+                    StepStatus::Running => {
+                        ui.spinner();
+                    }
+                    StepStatus::Done => {
+                        ui.label(RichText::new("✓").color(Color32::from_rgb(46, 204, 113)));
+                    }
+                    StepStatus::Failed => {
+                        ui.label(RichText::new("✗").color(Color32::from_rgb(231, 76, 60)));
+                    }
+                    StepStatus::Skipped => {
+                        ui.label(RichText::new("–").color(Color32::GRAY));
+                    }
+                    StepStatus::Pending => {
+                        ui.label(format!("{}.", index + 1));
+                    }
+                }
+                if reviewing {
+                    ui.add(
+                        TextEdit::singleline(&mut step.description)
+                            .desired_width(ui.available_width() - 30.0),
+                    );
+                    if ui.small_button("✕").on_hover_text("Remove step").clicked() {
+                        remove = Some(index);
+                    }
+                } else {
+                    ui.label(&step.description);
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            if reviewing {
+                let can_add = plan.steps.len() < MAX_PLAN_STEPS;
+                if ui
+                    .add_enabled(can_add, egui::Button::new("Add step"))
+                    .clicked()
+                {
+                    plan.steps.push(PlanStep {
+                        description: String::new(),
+                        status: StepStatus::Pending,
+                    });
+                }
+                let can_run = plan.steps.iter().any(|s| !s.description.trim().is_empty());
+                if ui
+                    .add_enabled(can_run, egui::Button::new("Run plan"))
+                    .clicked()
+                {
+                    plan.remove_blank_steps();
+                    message = Some(UiMessage::RunPlan(plan.clone()));
+                }
+                if ui.button("Discard").clicked() {
+                    close = true;
+                }
+            } else if !running && ui.button("Close").clicked() {
+                close = true;
+            }
+        });
+    });
+
+    if let Some(index) = remove {
+        plan.steps.remove(index);
+    }
+    if close {
+        state.plan = None;
+    } else if message.is_some() {
+        state.plan_running = true;
+        state.is_loading = true;
+        state.scroll_to_bottom = true;
+    }
+    message
+}
+
+/// Collapsed trace of the model's reasoning, kept apart from the answer
+fn reasoning_panel(ui: &mut Ui, id_salt: impl std::hash::Hash, text: &str, title: &str) {
+    egui::CollapsingHeader::new(
+        RichText::new(format!("{} ({} chars)", title, text.chars().count()))
+            .small()
+            .color(Color32::GRAY),
+    )
+    .id_salt(id_salt)
+    .show(ui, |ui| {
+        ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            ui.label(RichText::new(text).small().italics().color(Color32::GRAY));
+        });
+    });
+}
+
+/// A unified diff with added lines in green and removed ones in red
+fn diff_preview(ui: &mut Ui, id_salt: impl std::hash::Hash, diff: &str) {
+    if diff.is_empty() {
+        ui.label(RichText::new("No changes").small().color(Color32::GRAY));
+        return;
+    }
+    ScrollArea::vertical()
+        .id_salt(id_salt)
+        .max_height(300.0)
+        .show(ui, |ui| {
+            for line in diff.lines() {
+                let color = if line.starts_with("+++") || line.starts_with("---") {
+                    Color32::GRAY
+                } else if line.starts_with('+') {
+                    Color32::from_rgb(46, 204, 113)
+                } else if line.starts_with('-') {
+                    Color32::from_rgb(231, 76, 60)
+                } else if line.starts_with("@@") {
+                    Color32::from_rgb(52, 152, 219)
+                } else {
+                    ui.visuals().text_color()
+                };
+                ui.label(RichText::new(line).monospace().small().color(color));
+            }
+        });
+}
+
+/// Readiness of a local model next to the model name
+fn local_model_badge(ui: &mut Ui, status: &LocalModelStatus) {
+    let (text, color, detail) = match status.state {
+        LocalModelState::Ready { loaded: true } => (
+            "loaded",
+            Color32::from_rgb(46, 204, 113),
+            "Model is in memory".to_string(),
+        ),
+        LocalModelState::Ready { loaded: false } => (
+            "ready",
+            Color32::from_rgb(46, 204, 113),
+            "Model is installed; the first message may take a moment to load it".to_string(),
+        ),
+        LocalModelState::Warming => (
+            "warming up",
+            Color32::from_rgb(241, 196, 15),
+            "Loading the model into memory".to_string(),
+        ),
+        LocalModelState::NotInstalled => (
+            "not installed",
+            Color32::from_rgb(231, 76, 60),
+            format!("Run: ollama pull {}", status.model),
+        ),
+        LocalModelState::Unreachable(ref error) | LocalModelState::Failed(ref error) => {
+            ("unavailable", Color32::from_rgb(231, 76, 60), error.clone())
+        }
+    };
+    if status.state == LocalModelState::Warming {
+        ui.spinner();
+    }
+    ui.label(RichText::new(format!("● {}", text)).small().color(color))
+        .on_hover_text(format!(
+            "{} on {} ({})\n{}",
+            status.model, status.host, status.endpoint, detail
+        ));
+}
+
+/// Current model as a menu of the models the configured providers offer
+/// Switch agents; the app restarts the worker with the chosen one
+fn agent_picker(ui: &mut Ui, state: &mut UiState) {
+    let label = RichText::new(format!("Agent: {} ▾", state.agent_id))
+        .small()
+        .color(Color32::GRAY);
+    ui.add_enabled_ui(!state.is_loading, |ui| {
+        ui.menu_button(label, |ui| {
+            for agent in &state.agents {
+                if ui
+                    .selectable_label(*agent == state.agent_id, agent)
+                    .clicked()
+                {
+                    if *agent != state.agent_id {
+                        state.switch_agent = Some(agent.clone());
+                    }
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Switch agent (its own model, tools, workspace and sessions)");
+    });
+}
+
+fn model_picker(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+    let mut message = None;
+    let label = RichText::new(format!("{} ▾", state.model))
+        .small()
+        .color(Color32::GRAY);
+    let response = ui.menu_button(label, |ui| {
+        let Some(ref listings) = state.available_models else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Fetching models...");
+            });
+            return;
+        };
+        ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            for listing in listings {
+                ui.label(RichText::new(&listing.provider).strong());
+                if let Some(ref error) = listing.error {
+                    ui.label(RichText::new(error).small().color(Color32::GRAY));
+                }
+                for model in &listing.models {
+                    if ui.selectable_label(*model == state.model, model).clicked() {
+                        message = Some(UiMessage::SetModel(model.clone()));
+                        ui.close_menu();
+                    }
+                }
+                ui.add_space(4.0);
+            }
+        });
+        ui.separator();
+        if ui.button("Refresh").clicked() {
+            message = Some(UiMessage::ListModels);
         }
+    });
+    let response = response.response.on_hover_text("Switch model");
+
+    // Fetch the list the first time the menu opens
+    if response.clicked() && state.available_models.is_none() && !state.models_requested {
+        message = Some(UiMessage::ListModels);
+    }
+    if matches!(message, Some(UiMessage::ListModels)) {
+        state.models_requested = true;
+        state.available_models = None;
     }
+    message
+}
+
+/// Microphone button: click to start dictating, click again to transcribe
+/// into the chat input
+fn dictation_button(ui: &mut Ui, state: &UiState) -> Option<UiMessage> {
+    if state.transcribing {
+        ui.spinner().on_hover_text("Transcribing");
+        return None;
+    }
+    let button = if state.recording {
+        egui::Button::new(RichText::new("🎤 Stop").color(Color32::from_rgb(231, 76, 60)))
+    } else {
+        egui::Button::new("🎤")
+    };
+    let available = cfg!(feature = "voice");
+    let response = ui.add_enabled(available && (state.recording || !state.is_loading), button);
+    let response = if available {
+        response.on_hover_text("Dictate a message")
+    } else {
+        response.on_disabled_hover_text("Voice input needs LocalGPT built with --features voice")
+    };
+    if !response.clicked() {
+        return None;
+    }
+    Some(if state.recording {
+        UiMessage::StopRecording
+    } else {
+        UiMessage::StartRecording
+    })
 }
 
 /// Top toolbar with panel tabs
-pub fn show_toolbar(ui: &mut Ui, state: &mut UiState) {
+pub fn show_toolbar(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+    let mut message = None;
+
     ui.horizontal(|ui| {
         ui.selectable_value(&mut state.active_panel, Panel::Chat, "Chat");
         ui.selectable_value(&mut state.active_panel, Panel::Sessions, "Sessions");
+        ui.selectable_value(&mut state.active_panel, Panel::Files, "Files");
+        ui.selectable_value(&mut state.active_panel, Panel::Terminal, "Terminal");
+        if ui
+            .selectable_value(&mut state.active_panel, Panel::Context, "Context")
+            .clicked()
+        {
+            message = Some(UiMessage::InspectContext);
+        }
         ui.selectable_value(&mut state.active_panel, Panel::Status, "Status");
 
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.menu_button("Share", |ui| {
+                if ui.button("Export conversation as HTML").clicked() {
+                    message = Some(UiMessage::Share {
+                        include_tool_calls: true,
+                    });
+                    ui.close_menu();
+                }
+                if ui.button("Export without tool details").clicked() {
+                    message = Some(UiMessage::Share {
+                        include_tool_calls: false,
+                    });
+                    ui.close_menu();
+                }
+            });
+            ui.toggle_value(&mut state.speak_replies, "🔊")
+                .on_hover_text("Read replies aloud");
+            if let Some(msg) = dictation_button(ui, state) {
+                message = Some(msg);
+            }
+            if ui
+                .add_enabled(!state.is_loading, egui::Button::new("Undo agent changes"))
+                .on_hover_text("Restore files changed by the agent's last turn")
+                .clicked()
+            {
+                state.file_tree = None;
+                message = Some(UiMessage::UndoChanges);
+            }
             if !state.model.is_empty() {
-                ui.label(RichText::new(&state.model).small().color(Color32::GRAY));
+                if let Some(msg) = model_picker(ui, state) {
+                    message = Some(msg);
+                }
+            }
+            if state.agents.len() > 1 {
+                agent_picker(ui, state);
+            }
+            if let Some(ref local) = state.local_model {
+                local_model_badge(ui, local);
             }
         });
     });
     ui.separator();
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            tool_info: None,
+            reasoning: None,
+            variants: None,
+            rating: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_transcript_cache_only_lays_out_changes() {
+        let ctx = egui::Context::default();
+        let mut cache = TranscriptCache::default();
+        let mut messages = vec![
+            message(MessageRole::User, "hello"),
+            message(MessageRole::Assistant, "**hi** there"),
+        ];
+
+        let frame = |cache: &mut TranscriptCache, messages: &[ChatMessage]| {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| cache.sync(ui, messages));
+            });
+        };
+
+        frame(&mut cache, &messages);
+        assert_eq!(cache.len(), 2);
+        let first_hash = cache.entries[0].content_hash;
+
+        // Appending keeps earlier layouts
+        messages.push(message(MessageRole::System, "saved"));
+        frame(&mut cache, &messages);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.entries[0].content_hash, first_hash);
+
+        // A replaced transcript (new session) is laid out again
+        messages = vec![message(MessageRole::User, "different")];
+        frame(&mut cache, &messages);
+        assert_eq!(cache.len(), 1);
+        assert_ne!(cache.entries[0].content_hash, first_hash);
+    }
 }
@@ -0,0 +1,83 @@
+//! Context view - the exact messages the next turn will send
+
+use eframe::egui::{self, Color32, ProgressBar, RichText, ScrollArea, Ui};
+
+use crate::agent::Role;
+use crate::desktop::state::{UiMessage, UiState};
+
+pub struct ContextView;
+
+impl ContextView {
+    pub fn show(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+        let mut message_to_send = None;
+
+        ui.horizontal(|ui| {
+            ui.heading("Context");
+            if ui.button("Refresh").clicked() {
+                message_to_send = Some(UiMessage::InspectContext);
+            }
+        });
+        // Fetch when first shown
+        if state.context.is_none() && !state.context_requested {
+            message_to_send = Some(UiMessage::InspectContext);
+        }
+        if message_to_send.is_some() {
+            state.context_requested = true;
+        }
+        ui.label(
+            RichText::new(
+                "Exactly what the next turn sends, after compaction and memory injection",
+            )
+            .small()
+            .color(Color32::GRAY),
+        );
+        ui.add_space(8.0);
+
+        let Some(ref report) = state.context else {
+            ui.label("Loading...");
+            return message_to_send;
+        };
+
+        let total = report.total_tokens();
+        let usable = report.usable_tokens.max(1);
+        ui.add(
+            ProgressBar::new((total as f32 / usable as f32).min(1.0)).text(format!(
+                "~{} of {} usable tokens · {} messages · {} tools (~{} tokens)",
+                total,
+                report.usable_tokens,
+                report.segments.len(),
+                report.tool_count,
+                report.tool_tokens
+            )),
+        );
+        ui.add_space(8.0);
+
+        ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (i, segment) in report.segments.iter().enumerate() {
+                    let color = match segment.role {
+                        Role::System => Color32::from_rgb(155, 89, 182),
+                        Role::User => Color32::from_rgb(52, 152, 219),
+                        Role::Assistant => Color32::from_rgb(46, 204, 113),
+                        Role::Tool => Color32::from_rgb(230, 126, 34),
+                    };
+                    let header = RichText::new(format!(
+                        "[{}] {}  ~{} tokens",
+                        i + 1,
+                        segment.label,
+                        segment.tokens
+                    ))
+                    .color(color);
+
+                    egui::CollapsingHeader::new(header)
+                        .id_salt(("context_segment", i))
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(&segment.content).monospace().small());
+                        });
+                }
+            });
+
+        message_to_send
+    }
+}
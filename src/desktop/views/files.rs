@@ -0,0 +1,273 @@
+//! Files view - workspace file tree with context pinning and preview
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use eframe::egui::{self, Color32, RichText, ScrollArea, Ui};
+
+use crate::desktop::state::{UiMessage, UiState};
+
+/// Maximum directory depth shown in the tree
+const MAX_DEPTH: usize = 6;
+/// Maximum entries listed per directory
+const MAX_ENTRIES: usize = 500;
+/// Maximum bytes shown in the preview pane
+const MAX_PREVIEW_BYTES: usize = 100_000;
+
+/// A file or directory in the workspace tree
+#[derive(Debug, Clone)]
+pub struct FileNode {
+    pub name: String,
+    pub path: PathBuf,
+    /// Some for directories
+    pub children: Option<Vec<FileNode>>,
+}
+
+/// File shown in the preview pane
+#[derive(Debug, Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub content: String,
+    pub modified: Option<SystemTime>,
+}
+
+impl FilePreview {
+    fn load(path: &Path) -> Self {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let content = match fs::read(path) {
+            Ok(bytes) => {
+                let truncated = bytes.len() > MAX_PREVIEW_BYTES;
+                let mut text =
+                    String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_PREVIEW_BYTES)])
+                        .to_string();
+                if truncated {
+                    text.push_str(&format!("\n\n[Truncated, {} bytes total]", bytes.len()));
+                }
+                text
+            }
+            Err(e) => format!("Could not read file: {}", e),
+        };
+
+        Self {
+            path: path.to_path_buf(),
+            content,
+            modified,
+        }
+    }
+
+    /// Reload if the file changed on disk
+    fn refresh(&mut self) {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified != self.modified {
+            *self = Self::load(&self.path);
+        }
+    }
+}
+
+/// Read the directory tree under `root` (hidden entries skipped, dirs first)
+pub fn load_tree(root: &Path) -> Vec<FileNode> {
+    load_dir(root, 0)
+}
+
+fn load_dir(dir: &Path, depth: usize) -> Vec<FileNode> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<FileNode> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .take(MAX_ENTRIES)
+        .map(|e| {
+            let path = e.path();
+            let children = if path.is_dir() {
+                Some(if depth < MAX_DEPTH {
+                    load_dir(&path, depth + 1)
+                } else {
+                    Vec::new()
+                })
+            } else {
+                None
+            };
+            FileNode {
+                name: e.file_name().to_string_lossy().to_string(),
+                path,
+                children,
+            }
+        })
+        .collect();
+
+    nodes.sort_by(|a, b| {
+        b.children
+            .is_some()
+            .cmp(&a.children.is_some())
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    nodes
+}
+
+pub struct FilesView;
+
+impl FilesView {
+    pub fn show(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+        let mut message_to_send = None;
+
+        ui.heading("Workspace");
+        ui.label(
+            RichText::new(state.workspace.display().to_string())
+                .small()
+                .color(Color32::GRAY),
+        );
+        ui.add_space(5.0);
+
+        if ui.button("Refresh").clicked() || (state.file_tree.is_none() && state.has_workspace()) {
+            state.file_tree = Some(load_tree(&state.workspace));
+        }
+
+        ui.add_space(10.0);
+
+        // Pinned files
+        ui.group(|ui| {
+            ui.label(RichText::new("Pinned into context").strong());
+            if state.pinned_files.is_empty() {
+                ui.label(
+                    RichText::new(
+                        "Nothing pinned. Pin files below to include them in every message.",
+                    )
+                    .small()
+                    .color(Color32::GRAY),
+                );
+            }
+            for path in &state.pinned_files {
+                ui.horizontal(|ui| {
+                    ui.label(display_path(path, &state.workspace));
+                    if ui.small_button("Unpin").clicked() {
+                        message_to_send = Some(UiMessage::UnpinFile(path.clone()));
+                    }
+                });
+            }
+        });
+
+        ui.add_space(10.0);
+
+        let preview_height = if state.preview.is_some() {
+            ui.available_height() * 0.45
+        } else {
+            0.0
+        };
+
+        // File tree
+        ScrollArea::vertical()
+            .id_salt("file_tree")
+            .max_height(ui.available_height() - preview_height)
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                if let Some(ref tree) = state.file_tree {
+                    if tree.is_empty() {
+                        ui.label(RichText::new("Workspace is empty").color(Color32::GRAY));
+                    }
+                    for node in tree {
+                        if let Some(msg) =
+                            Self::show_node(ui, node, &state.pinned_files, &mut state.preview)
+                        {
+                            message_to_send = Some(msg);
+                        }
+                    }
+                }
+            });
+
+        // Preview
+        let mut close_preview = false;
+        if let Some(ref mut preview) = state.preview {
+            preview.refresh();
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(display_path(&preview.path, &state.workspace)).strong());
+                if ui.small_button("Close").clicked() {
+                    close_preview = true;
+                }
+            });
+            ScrollArea::vertical()
+                .id_salt("file_preview")
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    ui.label(RichText::new(&preview.content).monospace());
+                });
+        }
+        if close_preview {
+            state.preview = None;
+        }
+
+        message_to_send
+    }
+
+    fn show_node(
+        ui: &mut Ui,
+        node: &FileNode,
+        pinned: &[PathBuf],
+        preview: &mut Option<FilePreview>,
+    ) -> Option<UiMessage> {
+        let mut message = None;
+
+        match node.children {
+            Some(ref children) => {
+                egui::CollapsingHeader::new(&node.name)
+                    .id_salt(&node.path)
+                    .show(ui, |ui| {
+                        for child in children {
+                            if let Some(msg) = Self::show_node(ui, child, pinned, preview) {
+                                message = Some(msg);
+                            }
+                        }
+                    });
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    let is_pinned = pinned.contains(&node.path);
+                    let selected = preview.as_ref().map(|p| p.path == node.path) == Some(true);
+
+                    if ui.selectable_label(selected, &node.name).clicked() {
+                        *preview = Some(FilePreview::load(&node.path));
+                    }
+                    if is_pinned {
+                        if ui.small_button("Unpin").clicked() {
+                            message = Some(UiMessage::UnpinFile(node.path.clone()));
+                        }
+                    } else if ui.small_button("Pin").clicked() {
+                        message = Some(UiMessage::PinFile(node.path.clone()));
+                    }
+                });
+            }
+        }
+
+        message
+    }
+}
+
+fn display_path(path: &Path, workspace: &Path) -> String {
+    path.strip_prefix(workspace)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_tree_dirs_first_and_hidden_skipped() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("b.md"), "b").unwrap();
+        fs::write(tmp.path().join(".hidden"), "h").unwrap();
+        fs::create_dir(tmp.path().join("memory")).unwrap();
+        fs::write(tmp.path().join("memory").join("a.md"), "a").unwrap();
+
+        let tree = load_tree(tmp.path());
+        let names: Vec<&str> = tree.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["memory", "b.md"]);
+        assert_eq!(tree[0].children.as_ref().unwrap()[0].name, "a.md");
+    }
+}
@@ -0,0 +1,585 @@
+//! Lightweight markdown renderer that tolerates partial (streaming) content
+//!
+//! Unterminated code fences render as code up to the end of the text, and
+//! unmatched emphasis/backtick markers render literally, so a response looks
+//! the same while it is still being generated as it does when complete.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, text::LayoutJob, Color32, FontId, Galley, RichText, TextFormat, Ui};
+
+/// A block-level markdown element
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block<'a> {
+    Heading(usize, &'a str),
+    /// Fenced code block; `closed` is false while the fence is still open
+    Code {
+        lang: &'a str,
+        code: String,
+        closed: bool,
+    },
+    ListItem {
+        indent: usize,
+        marker: &'a str,
+        text: &'a str,
+    },
+    Quote(&'a str),
+    /// Image on a line of its own: `![alt](path)`
+    Image {
+        alt: &'a str,
+        path: &'a str,
+    },
+    Paragraph(String),
+    Rule,
+}
+
+/// An inline markdown span
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span<'a> {
+    Text(&'a str),
+    Bold(&'a str),
+    Italic(&'a str),
+    Code(&'a str),
+}
+
+/// Split markdown into blocks
+pub fn parse_blocks(text: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = text.lines();
+
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+    };
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            flush(&mut paragraph, &mut blocks);
+            let mut code = Vec::new();
+            let mut closed = false;
+            for code_line in lines.by_ref() {
+                if code_line.trim_start().starts_with("```") {
+                    closed = true;
+                    break;
+                }
+                code.push(code_line);
+            }
+            blocks.push(Block::Code {
+                lang: lang.trim(),
+                code: code.join("\n"),
+                closed,
+            });
+        } else if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+        } else if let Some(level) = heading_level(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading(level, trimmed[level..].trim()));
+        } else if is_rule(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Rule);
+        } else if let Some((marker, rest)) = list_marker(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::ListItem {
+                indent,
+                marker,
+                text: rest,
+            });
+        } else if let Some((alt, path)) = image_link(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Image { alt, path });
+        } else if let Some(rest) = trimmed.strip_prefix('>') {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Quote(rest.trim_start()));
+        } else {
+            paragraph.push(trimmed);
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+
+    blocks
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() >= 3
+        && (line.chars().all(|c| c == '-')
+            || line.chars().all(|c| c == '*')
+            || line.chars().all(|c| c == '_'))
+}
+
+fn list_marker(line: &str) -> Option<(&str, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some((&line[..1], rest));
+        }
+    }
+
+    // Ordered list: "1. item"
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some((&line[..digits + 1], &line[digits + 2..]));
+    }
+
+    None
+}
+
+fn image_link(line: &str) -> Option<(&str, &str)> {
+    let (alt, rest) = line.trim_end().strip_prefix("![")?.split_once("](")?;
+    let path = rest.strip_suffix(')')?;
+    (!path.is_empty()).then_some((alt, path))
+}
+
+/// Split a line into inline spans. Unmatched markers are kept as literal text.
+pub fn parse_inline(text: &str) -> Vec<Span<'_>> {
+    let mut spans = Vec::new();
+    let mut plain_start = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        // `_` inside a word (snake_case) is never emphasis (CommonMark)
+        if rest.starts_with('_')
+            && text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric)
+        {
+            i += rest.len() - rest.trim_start_matches('_').len();
+            continue;
+        }
+        let parsed = if rest.starts_with('`') {
+            closing(rest, "`").map(|end| (Span::Code(&rest[1..end]), end + 1))
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            closing(rest, &rest[..2]).map(|end| (Span::Bold(&rest[2..end]), end + 2))
+        } else if rest.starts_with('*') || rest.starts_with('_') {
+            closing(rest, &rest[..1]).map(|end| (Span::Italic(&rest[1..end]), end + 1))
+        } else {
+            None
+        };
+
+        match parsed {
+            Some((span, len)) => {
+                if plain_start < i {
+                    spans.push(Span::Text(&text[plain_start..i]));
+                }
+                spans.push(span);
+                i += len;
+                plain_start = i;
+            }
+            None => {
+                i += rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+            }
+        }
+    }
+
+    if plain_start < text.len() {
+        spans.push(Span::Text(&text[plain_start..]));
+    }
+
+    spans
+}
+
+/// Find the closing delimiter for a span opened at the start of `text`.
+/// Returns the byte offset of the closing delimiter, requiring non-empty content.
+/// An `_` delimiter closes only at the end of a word.
+fn closing(text: &str, delim: &str) -> Option<usize> {
+    let open = delim.len();
+    let mut from = open;
+    let end = loop {
+        let end = text[from..].find(delim)? + from;
+        let next = text[end + delim.len()..].chars().next();
+        if delim.starts_with('_') && next.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            from = end + 1;
+            continue;
+        }
+        break end;
+    };
+    if end == open || text[open..end].starts_with(' ') {
+        None
+    } else {
+        Some(end)
+    }
+}
+
+/// A laid-out markdown element, ready to draw
+enum LaidOut {
+    /// Wrapped text, optionally led by a list bullet or quote bar
+    Text {
+        indent: f32,
+        marker: Option<Arc<Galley>>,
+        galley: Arc<Galley>,
+    },
+    Code {
+        lang: Option<Arc<Galley>>,
+        code: Arc<Galley>,
+        closed: bool,
+    },
+    Image {
+        alt: String,
+        path: String,
+    },
+    Rule,
+}
+
+/// Markdown laid out for a fixed width and style. Drawing it is cheap; lay it
+/// out again only when the text, available width, or visuals change.
+pub struct LaidOutMarkdown {
+    items: Vec<LaidOut>,
+}
+
+impl LaidOutMarkdown {
+    /// Parse and lay out markdown (complete or partial)
+    pub fn layout(ui: &Ui, text: &str, width: f32) -> Self {
+        let body_size = body_size(ui);
+        let color = ui.visuals().text_color();
+        let strong = ui.visuals().strong_text_color();
+
+        let items = parse_blocks(text)
+            .into_iter()
+            .map(|block| match block {
+                Block::Heading(level, text) => {
+                    let size = match level {
+                        1 => body_size + 6.0,
+                        2 => body_size + 4.0,
+                        3 => body_size + 2.0,
+                        _ => body_size,
+                    };
+                    let job = LayoutJob::simple(
+                        text.to_string(),
+                        FontId::proportional(size),
+                        strong,
+                        width,
+                    );
+                    LaidOut::Text {
+                        indent: 0.0,
+                        marker: None,
+                        galley: ui.fonts(|f| f.layout_job(job)),
+                    }
+                }
+                Block::Code { lang, code, closed } => {
+                    let code_job = LayoutJob::simple(
+                        code,
+                        FontId::monospace(body_size - 1.0),
+                        color,
+                        width - 12.0,
+                    );
+                    let lang = (!lang.is_empty()).then(|| {
+                        let job = LayoutJob::simple_singleline(
+                            lang.to_string(),
+                            FontId::proportional(body_size - 3.0),
+                            Color32::GRAY,
+                        );
+                        ui.fonts(|f| f.layout_job(job))
+                    });
+                    LaidOut::Code {
+                        lang,
+                        code: ui.fonts(|f| f.layout_job(code_job)),
+                        closed,
+                    }
+                }
+                Block::ListItem {
+                    indent,
+                    marker,
+                    text,
+                } => {
+                    let indent = 8.0 + indent as f32 * 6.0;
+                    let bullet = if marker.ends_with('.') { marker } else { "•" };
+                    Self::marked(ui, indent, bullet, color, text, width, body_size)
+                }
+                Block::Quote(text) => {
+                    Self::marked(ui, 0.0, "▎", Color32::GRAY, text, width, body_size)
+                }
+                Block::Image { alt, path } => LaidOut::Image {
+                    alt: alt.to_string(),
+                    path: path.to_string(),
+                },
+                Block::Paragraph(text) => LaidOut::Text {
+                    indent: 0.0,
+                    marker: None,
+                    galley: ui.fonts(|f| f.layout_job(inline_job(ui, &text, body_size, width))),
+                },
+                Block::Rule => LaidOut::Rule,
+            })
+            .collect();
+
+        Self { items }
+    }
+
+    /// Lay out text as-is, without markdown
+    pub fn plain(ui: &Ui, text: &str, width: f32) -> Self {
+        let job = LayoutJob::simple(
+            text.to_string(),
+            FontId::proportional(body_size(ui)),
+            ui.visuals().text_color(),
+            width,
+        );
+        Self {
+            items: vec![LaidOut::Text {
+                indent: 0.0,
+                marker: None,
+                galley: ui.fonts(|f| f.layout_job(job)),
+            }],
+        }
+    }
+
+    fn marked(
+        ui: &Ui,
+        indent: f32,
+        marker: &str,
+        marker_color: Color32,
+        text: &str,
+        width: f32,
+        body_size: f32,
+    ) -> LaidOut {
+        let marker_job = LayoutJob::simple_singleline(
+            marker.to_string(),
+            FontId::proportional(body_size),
+            marker_color,
+        );
+        let marker = ui.fonts(|f| f.layout_job(marker_job));
+        let text_width = width - indent - marker.size().x - ui.spacing().item_spacing.x;
+        LaidOut::Text {
+            indent,
+            marker: Some(marker),
+            galley: ui.fonts(|f| f.layout_job(inline_job(ui, text, body_size, text_width))),
+        }
+    }
+
+    /// Draw the laid-out markdown
+    pub fn show(&self, ui: &mut Ui) {
+        for item in &self.items {
+            match item {
+                LaidOut::Text {
+                    indent,
+                    marker: None,
+                    galley,
+                } if *indent == 0.0 => {
+                    ui.label(galley.clone());
+                }
+                LaidOut::Text {
+                    indent,
+                    marker,
+                    galley,
+                } => {
+                    ui.horizontal(|ui| {
+                        ui.add_space(*indent);
+                        if let Some(marker) = marker {
+                            ui.label(marker.clone());
+                        }
+                        ui.label(galley.clone());
+                    });
+                }
+                LaidOut::Code { lang, code, closed } => {
+                    egui::Frame::none()
+                        .fill(ui.visuals().extreme_bg_color)
+                        .rounding(4.0)
+                        .inner_margin(6.0)
+                        .show(ui, |ui| {
+                            ui.set_min_width(ui.available_width());
+                            if let Some(lang) = lang {
+                                ui.label(lang.clone());
+                            }
+                            ui.label(code.clone());
+                            if !closed {
+                                ui.label(RichText::new("...").small().color(Color32::GRAY));
+                            }
+                        });
+                }
+                LaidOut::Image { alt, path } => match load_image(ui.ctx(), path) {
+                    Some(texture) => {
+                        let size = texture.size_vec2();
+                        let scale = (ui.available_width() / size.x).min(1.0);
+                        ui.add(egui::Image::new(&texture).fit_to_exact_size(size * scale))
+                            .on_hover_text(format!("{}\n{}", alt, path));
+                    }
+                    None => {
+                        ui.label(RichText::new(format!("[image: {}]", path)).color(Color32::GRAY));
+                    }
+                },
+                LaidOut::Rule => {
+                    ui.separator();
+                }
+            }
+        }
+    }
+}
+
+/// Render markdown (complete or partial) into the UI, laying it out this frame
+pub fn render_markdown(ui: &mut Ui, text: &str) {
+    LaidOutMarkdown::layout(ui, text, ui.available_width()).show(ui);
+}
+
+fn body_size(ui: &Ui) -> f32 {
+    ui.style()
+        .text_styles
+        .get(&egui::TextStyle::Body)
+        .map(|f| f.size)
+        .unwrap_or(14.0)
+}
+
+/// How long an image that failed to load waits before it is tried again;
+/// it may still be being written
+const IMAGE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+enum CachedImage {
+    Loaded(egui::TextureHandle),
+    Failed(Instant),
+}
+
+/// Decode a local image into a texture, cached per path for the app's
+/// lifetime once it loads
+fn load_image(ctx: &egui::Context, path: &str) -> Option<egui::TextureHandle> {
+    let id = egui::Id::new(("markdown_image", path));
+    match ctx.data(|d| d.get_temp::<CachedImage>(id)) {
+        Some(CachedImage::Loaded(texture)) => return Some(texture),
+        Some(CachedImage::Failed(at)) if at.elapsed() < IMAGE_RETRY_DELAY => return None,
+        _ => {}
+    }
+
+    let texture = image::open(path).ok().map(|img| {
+        let rgba = img.to_rgba8();
+        let size = [rgba.width() as usize, rgba.height() as usize];
+        let color = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+        ctx.load_texture(path, color, egui::TextureOptions::LINEAR)
+    });
+    let cached = match texture {
+        Some(ref texture) => CachedImage::Loaded(texture.clone()),
+        None => {
+            ctx.request_repaint_after(IMAGE_RETRY_DELAY);
+            CachedImage::Failed(Instant::now())
+        }
+    };
+    ctx.data_mut(|d| d.insert_temp(id, cached));
+    texture
+}
+
+fn inline_job(ui: &Ui, text: &str, size: f32, width: f32) -> LayoutJob {
+    let color = ui.visuals().text_color();
+    let strong = ui.visuals().strong_text_color();
+    let mut job = LayoutJob::default();
+    job.wrap.max_width = width;
+
+    for span in parse_inline(text) {
+        let (text, format) = match span {
+            Span::Text(t) => (t, TextFormat::simple(FontId::proportional(size), color)),
+            Span::Bold(t) => (t, TextFormat::simple(FontId::proportional(size), strong)),
+            Span::Italic(t) => (
+                t,
+                TextFormat {
+                    italics: true,
+                    ..TextFormat::simple(FontId::proportional(size), color)
+                },
+            ),
+            Span::Code(t) => (
+                t,
+                TextFormat {
+                    background: ui.visuals().extreme_bg_color,
+                    ..TextFormat::simple(FontId::monospace(size - 1.0), color)
+                },
+            ),
+        };
+        job.append(text, 0.0, format);
+    }
+
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unterminated_fence_is_code() {
+        let blocks = parse_blocks("Here:\n```rust\nfn main() {\n    println!(\"hi\");");
+        assert_eq!(blocks[0], Block::Paragraph("Here:".to_string()));
+        assert_eq!(
+            blocks[1],
+            Block::Code {
+                lang: "rust",
+                code: "fn main() {\n    println!(\"hi\");".to_string(),
+                closed: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = parse_blocks("# Title\n\n- one\n2. two\n> quoted\n---\n```\nx\n```");
+        assert_eq!(blocks[0], Block::Heading(1, "Title"));
+        assert!(matches!(blocks[1], Block::ListItem { marker: "-", .. }));
+        assert!(matches!(blocks[2], Block::ListItem { marker: "2.", .. }));
+        assert_eq!(blocks[3], Block::Quote("quoted"));
+        assert_eq!(blocks[4], Block::Rule);
+        assert!(matches!(blocks[5], Block::Code { closed: true, .. }));
+    }
+
+    #[test]
+    fn test_image_line() {
+        let blocks = parse_blocks("Done:\n![a red fox](/ws/images/fox.png)\n![broken](");
+        assert_eq!(
+            blocks[1],
+            Block::Image {
+                alt: "a red fox",
+                path: "/ws/images/fox.png"
+            }
+        );
+        assert_eq!(blocks[2], Block::Paragraph("![broken](".to_string()));
+    }
+
+    #[test]
+    fn test_inline_spans() {
+        assert_eq!(
+            parse_inline("a **b** *c* `d`"),
+            vec![
+                Span::Text("a "),
+                Span::Bold("b"),
+                Span::Text(" "),
+                Span::Italic("c"),
+                Span::Text(" "),
+                Span::Code("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_inline_is_literal() {
+        assert_eq!(
+            parse_inline("partial **bold and `code"),
+            vec![Span::Text("partial **bold and `code")]
+        );
+        assert_eq!(parse_inline("2 * 3 = 6"), vec![Span::Text("2 * 3 = 6")]);
+    }
+
+    #[test]
+    fn test_underscores_inside_words() {
+        assert_eq!(
+            parse_inline("call snake_case_name and my__dunder__var"),
+            vec![Span::Text("call snake_case_name and my__dunder__var")]
+        );
+        assert_eq!(
+            parse_inline("_foo_bar_ and __bold__"),
+            vec![
+                Span::Italic("foo_bar"),
+                Span::Text(" and "),
+                Span::Bold("bold"),
+            ]
+        );
+    }
+}
@@ -1,9 +1,18 @@
 //! UI views
 
+mod api_key;
 pub mod chat;
+mod context;
+pub mod files;
+mod markdown;
 mod sessions;
 mod status;
+mod terminal;
 
+pub use api_key::ApiKeyDialog;
 pub use chat::ChatView;
+pub use context::ContextView;
+pub use files::FilesView;
 pub use sessions::SessionsView;
 pub use status::StatusView;
+pub use terminal::TerminalView;
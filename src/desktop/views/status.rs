@@ -1,6 +1,6 @@
-//! Status view - show model, memory, and session stats
+//! Status view - show model, memory, session stats, model usage and provider health
 
-use eframe::egui::{Color32, ProgressBar, RichText, Ui};
+use eframe::egui::{self, Color32, ProgressBar, RichText, Sense, Stroke, Ui};
 
 use crate::desktop::state::{UiMessage, UiState};
 
@@ -51,15 +51,42 @@ impl StatusView {
                 ui.label(format!("ID: {}...", &status.id[..8.min(status.id.len())]));
                 ui.label(format!("Messages: {}", status.message_count));
                 ui.label(format!("Compactions: {}", status.compaction_count));
+                if let Some(t) = status.generation.temperature {
+                    ui.label(format!("Temperature: {}", t));
+                }
+                if let Some(n) = status.generation.max_tokens {
+                    ui.label(format!("Max tokens: {}", n));
+                }
+                if let Some(ref prompt) = status.system_prompt {
+                    ui.label("System instructions:");
+                    ui.label(RichText::new(prompt).small().italics());
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Reply language:");
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut state.language_input)
+                            .hint_text("model default")
+                            .desired_width(140.0),
+                    );
+                    let language = state.language_input.trim();
+                    let changed = language != status.language.as_deref().unwrap_or("");
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if changed && (ui.small_button("Apply").clicked() || submitted) {
+                        message_to_send = Some(UiMessage::SetLanguage(
+                            (!language.is_empty()).then(|| language.to_string()),
+                        ));
+                    }
+                });
 
                 // Token usage bar
                 ui.add_space(5.0);
                 ui.label("Context usage:");
-                let token_pct = status.token_count as f32 / 128000.0; // Assume 128k context
-                ui.add(
-                    ProgressBar::new(token_pct.min(1.0))
-                        .text(format!("~{} tokens", status.token_count)),
-                );
+                let token_pct = status.token_count as f32 / status.usable_tokens.max(1) as f32;
+                ui.add(ProgressBar::new(token_pct.min(1.0)).text(format!(
+                    "{} / {} tokens",
+                    status.token_count, status.usable_tokens
+                )));
 
                 if token_pct > 0.8 {
                     ui.label(
@@ -78,6 +105,12 @@ impl StatusView {
                     ui.label(RichText::new("API Usage (Session)").strong());
                     ui.label(format!("Input: {} tokens", status.api_input_tokens));
                     ui.label(format!("Output: {} tokens", status.api_output_tokens));
+                    if status.api_reasoning_tokens > 0 {
+                        ui.label(format!(
+                            "  of which reasoning: {} tokens",
+                            status.api_reasoning_tokens
+                        ));
+                    }
                     ui.label(format!(
                         "Total: {} tokens",
                         status.api_input_tokens + status.api_output_tokens
@@ -86,6 +119,315 @@ impl StatusView {
             }
         }
 
+        ui.add_space(10.0);
+
+        // Conversation statistics
+        if let Some(ref stats) = state.stats {
+            ui.group(|ui| {
+                ui.label(RichText::new("Statistics").strong());
+                ui.label(format!(
+                    "Messages: {} user, {} assistant, {} tool results",
+                    stats.user_messages, stats.assistant_messages, stats.tool_results
+                ));
+                ui.label(format!("Tool calls: {}", stats.total_tool_calls()));
+                for (name, count) in &stats.tool_calls {
+                    ui.label(RichText::new(format!("  {}: {}", name, count)).small());
+                }
+                if !stats.web_searches.is_empty() {
+                    ui.add_space(5.0);
+                    ui.label(format!("Web searches: {}", stats.web_searches.len()));
+                    for query in &stats.web_searches {
+                        ui.label(RichText::new(format!("  🔍 {}", query)).small());
+                    }
+                }
+                if !stats.turn_tokens.is_empty() {
+                    ui.add_space(5.0);
+                    ui.label(format!(
+                        "Tokens per turn (max ~{}):",
+                        stats.turn_tokens.iter().max().copied().unwrap_or(0)
+                    ));
+                    token_sparkline(ui, &stats.turn_tokens);
+                }
+                for event in &stats.compactions {
+                    ui.label(
+                        RichText::new(format!(
+                            "Compacted {}: ~{} -> ~{} tokens",
+                            event.at.with_timezone(&chrono::Local).format("%H:%M"),
+                            event.tokens_before,
+                            event.tokens_after
+                        ))
+                        .small(),
+                    );
+                }
+            });
+
+            ui.add_space(10.0);
+        } else if !state.stats_requested {
+            // Fetch when first shown
+            state.stats_requested = true;
+            message_to_send = Some(UiMessage::RefreshStatus);
+        }
+
+        // Model usage across sessions
+        if let Some(ref report) = state.model_usage {
+            if !report.models.is_empty() {
+                ui.group(|ui| {
+                    ui.label(RichText::new("Model Usage").strong());
+                    egui::Grid::new("model_usage")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in ["Model", "Requests", "Failed", "Avg latency", "Cost"] {
+                                ui.label(RichText::new(header).small().strong());
+                            }
+                            ui.end_row();
+                            for summary in &report.models {
+                                let usage = &summary.usage;
+                                ui.label(&summary.model);
+                                ui.label(usage.requests.to_string());
+                                ui.label(format!("{:.0}%", usage.failure_rate() * 100.0));
+                                ui.label(format!("{:.1}s", usage.avg_latency_ms() as f64 / 1000.0));
+                                ui.label(match summary.cost {
+                                    Some(cost) => format!("~${:.2}", cost),
+                                    None => "-".to_string(),
+                                });
+                                ui.end_row();
+                            }
+                        });
+                    for suggestion in &report.suggestions {
+                        ui.add_space(5.0);
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(
+                                RichText::new(&suggestion.message)
+                                    .color(Color32::from_rgb(241, 196, 15)),
+                            );
+                            if ui
+                                .small_button("Apply")
+                                .on_hover_text(format!(
+                                    "Set {} = {} in config.toml",
+                                    suggestion.key, suggestion.value
+                                ))
+                                .clicked()
+                            {
+                                message_to_send =
+                                    Some(UiMessage::ApplyModelSuggestion(suggestion.clone()));
+                            }
+                        });
+                    }
+                });
+                ui.add_space(10.0);
+            }
+        }
+
+        // Provider health checks (`/doctor`)
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Providers").strong());
+                if state.diagnostics_requested {
+                    ui.spinner();
+                } else if ui.small_button("Check").clicked() {
+                    state.diagnostics_requested = true;
+                    message_to_send = Some(UiMessage::RunDoctor);
+                }
+            });
+            match state.diagnostics {
+                Some(ref results) if results.is_empty() => {
+                    ui.label(RichText::new("No providers configured").color(Color32::GRAY));
+                }
+                Some(ref results) => {
+                    egui::Grid::new("provider_health")
+                        .num_columns(3)
+                        .spacing([12.0, 4.0])
+                        .show(ui, |ui| {
+                            for result in results {
+                                ui.label(&result.provider);
+                                match result.latency_ms {
+                                    Some(ms) if result.ok => ui.label(
+                                        RichText::new(format!("{} ms", ms))
+                                            .color(Color32::from_rgb(46, 204, 113)),
+                                    ),
+                                    _ => ui.label(
+                                        RichText::new("failed")
+                                            .color(Color32::from_rgb(231, 76, 60)),
+                                    ),
+                                };
+                                ui.label(RichText::new(&result.detail).small());
+                                ui.end_row();
+                            }
+                        });
+                }
+                None => {
+                    ui.label(
+                        RichText::new("Check each provider's connection and key (/doctor)")
+                            .small()
+                            .color(Color32::GRAY),
+                    );
+                }
+            }
+        });
+        ui.add_space(10.0);
+
+        // Ollama hosts
+        match state.ollama_hosts {
+            Some(ref hosts) if !hosts.is_empty() => {
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Ollama Hosts").strong());
+                        if state.ollama_hosts_requested {
+                            ui.spinner();
+                        } else if ui.small_button("Probe").clicked() {
+                            message_to_send = Some(UiMessage::ProbeOllamaHosts);
+                        }
+                    });
+                    for host in hosts {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{} ({})", host.name, host.endpoint));
+                            match host.latency_ms {
+                                Some(ms) => ui.label(
+                                    RichText::new(format!("{} ms", ms))
+                                        .color(Color32::from_rgb(46, 204, 113)),
+                                ),
+                                None => ui
+                                    .label(
+                                        RichText::new("unreachable")
+                                            .color(Color32::from_rgb(231, 76, 60)),
+                                    )
+                                    .on_hover_text(host.error.as_deref().unwrap_or_default()),
+                            };
+                        });
+                        if !host.models.is_empty() {
+                            ui.label(
+                                RichText::new(format!("  {}", host.models.join(", ")))
+                                    .small()
+                                    .color(Color32::GRAY),
+                            );
+                        }
+                        if !host.installed.is_empty() {
+                            egui::CollapsingHeader::new(format!(
+                                "Installed ({})",
+                                host.installed.len()
+                            ))
+                            .id_salt(("ollama_installed", &host.name))
+                            .show(ui, |ui| {
+                                for model in &host.installed {
+                                    let target = format!("{}@{}", model, host.name);
+                                    ui.horizontal(|ui| {
+                                        ui.label(RichText::new(model).small());
+                                        // Deleting takes a second click
+                                        if state.ollama_delete_confirm.as_ref() == Some(&target) {
+                                            if ui.small_button("Confirm delete").clicked() {
+                                                state.ollama_delete_confirm = None;
+                                                message_to_send =
+                                                    Some(UiMessage::DeleteOllamaModel(target));
+                                            } else if ui.small_button("Cancel").clicked() {
+                                                state.ollama_delete_confirm = None;
+                                            }
+                                        } else if ui.small_button("Delete").clicked() {
+                                            state.ollama_delete_confirm = Some(target);
+                                        }
+                                    });
+                                }
+                            });
+                        }
+                    }
+
+                    ui.add_space(4.0);
+                    match state.ollama_pull {
+                        Some((ref model, ref progress)) => {
+                            ui.label(format!("Pulling {}", model));
+                            let status = progress
+                                .as_ref()
+                                .map(|p| p.status.as_str())
+                                .unwrap_or("starting");
+                            match progress.as_ref().and_then(|p| p.fraction()) {
+                                Some(fraction) => ui.add(ProgressBar::new(fraction).text(format!(
+                                    "{} {:.0}%",
+                                    status,
+                                    fraction * 100.0
+                                ))),
+                                None => ui.add(ProgressBar::new(0.0).animate(true).text(status)),
+                            };
+                        }
+                        None => {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut state.ollama_pull_input)
+                                        .hint_text("model or model@host")
+                                        .desired_width(180.0),
+                                );
+                                let model = state.ollama_pull_input.trim().to_string();
+                                if ui
+                                    .add_enabled(!model.is_empty(), egui::Button::new("Pull"))
+                                    .clicked()
+                                {
+                                    state.ollama_pull = Some((model.clone(), None));
+                                    state.ollama_pull_input.clear();
+                                    message_to_send = Some(UiMessage::PullOllamaModel(model));
+                                }
+                            });
+                        }
+                    }
+                    ui.label(
+                        RichText::new("Use model@host to pick a host explicitly")
+                            .small()
+                            .color(Color32::GRAY),
+                    );
+                });
+                ui.add_space(10.0);
+            }
+            Some(_) => {}
+            None if !state.ollama_hosts_requested => {
+                message_to_send = message_to_send.or(Some(UiMessage::ProbeOllamaHosts));
+            }
+            None => {}
+        }
+        if matches!(message_to_send, Some(UiMessage::ProbeOllamaHosts)) {
+            state.ollama_hosts_requested = true;
+        }
+
+        // Rendering performance
+        ui.group(|ui| {
+            ui.label(RichText::new("Rendering").strong());
+            let stats = &state.frame_stats;
+            ui.label(format!(
+                "Frame time: {:.1} ms (avg {:.1} ms)",
+                stats.last_ms, stats.avg_ms
+            ));
+            ui.label(format!("Frames over 16 ms: {}", stats.slow_frames));
+            ui.label(format!("Cached messages: {}", state.transcript.len()));
+        });
+
         message_to_send
     }
 }
+
+/// Line chart of per-turn token counts
+fn token_sparkline(ui: &mut Ui, values: &[usize]) {
+    let size = egui::vec2(ui.available_width().min(300.0), 32.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let max = values.iter().max().copied().unwrap_or(0).max(1) as f32;
+    let step = rect.width() / (values.len().max(2) - 1) as f32;
+    let points: Vec<egui::Pos2> = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            egui::pos2(
+                rect.left() + i as f32 * step,
+                rect.bottom() - v as f32 / max * rect.height(),
+            )
+        })
+        .collect();
+
+    let color = Color32::from_rgb(52, 152, 219);
+    let painter = ui.painter_at(rect);
+    if points.len() == 1 {
+        painter.circle_filled(points[0], 2.0, color);
+    } else {
+        painter.add(egui::Shape::line(points, Stroke::new(1.5, color)));
+    }
+
+    if let Some(pos) = response.hover_pos() {
+        let i = (((pos.x - rect.left()) / step).round() as usize).min(values.len() - 1);
+        response.on_hover_text(format!("Turn {}: ~{} tokens", i + 1, values[i]));
+    }
+}
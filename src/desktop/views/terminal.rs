@@ -0,0 +1,87 @@
+//! Terminal view - live output of shell tool executions
+//!
+//! Shows stdout/stderr of the agent's bash tool as it runs, and lets the user
+//! type follow-up commands that run through the same tool.
+
+use eframe::egui::{self, Color32, RichText, ScrollArea, TextEdit, Ui};
+
+use crate::desktop::state::{TerminalLineKind, UiMessage, UiState};
+
+pub struct TerminalView;
+
+impl TerminalView {
+    pub fn show(ui: &mut Ui, state: &mut UiState) -> Option<UiMessage> {
+        let mut message_to_send = None;
+
+        ui.horizontal(|ui| {
+            ui.heading("Terminal");
+            if state.shell_running {
+                ui.spinner();
+                ui.label(RichText::new("Running").small().color(Color32::GRAY));
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Clear").clicked() {
+                    state.terminal_lines.clear();
+                }
+            });
+        });
+        ui.separator();
+
+        // Leave room for the input row
+        let output_height = ui.available_height() - 36.0;
+
+        egui::Frame::none()
+            .fill(ui.visuals().extreme_bg_color)
+            .inner_margin(6.0)
+            .show(ui, |ui| {
+                ScrollArea::vertical()
+                    .max_height(output_height)
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        if state.terminal_lines.is_empty() {
+                            ui.label(
+                                RichText::new("Shell commands run by the agent will appear here.")
+                                    .monospace()
+                                    .color(Color32::GRAY),
+                            );
+                        }
+                        for line in &state.terminal_lines {
+                            let color = match line.kind {
+                                TerminalLineKind::Command => Color32::from_rgb(100, 180, 255),
+                                TerminalLineKind::Stdout => ui.visuals().text_color(),
+                                TerminalLineKind::Stderr => Color32::from_rgb(230, 120, 100),
+                                TerminalLineKind::Exit => Color32::GRAY,
+                            };
+                            ui.label(RichText::new(&line.text).monospace().color(color));
+                        }
+                    });
+            });
+
+        ui.add_space(4.0);
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("$").monospace());
+            let response = ui.add(
+                TextEdit::singleline(&mut state.terminal_input)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(ui.available_width() - 60.0)
+                    .hint_text("Run a command in the agent workspace shell"),
+            );
+
+            let enter_pressed =
+                response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let can_run = !state.shell_running && !state.terminal_input.trim().is_empty();
+
+            if (ui.add_enabled(can_run, egui::Button::new("Run")).clicked() || enter_pressed)
+                && can_run
+            {
+                let command = std::mem::take(&mut state.terminal_input);
+                message_to_send = Some(UiMessage::RunShell(command.trim().to_string()));
+                response.request_focus();
+            }
+        });
+
+        message_to_send
+    }
+}
@@ -3,21 +3,35 @@
 //! The worker runs in a separate thread with its own tokio runtime.
 //! It receives commands from the UI and sends back status updates.
 
+use std::future::Future;
 use std::pin::pin;
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use anyhow::Result;
 use futures::StreamExt;
 
 use crate::agent::{
-    extract_tool_detail, list_sessions_for_agent, Agent, AgentConfig, StreamEvent, ToolCall,
-    DEFAULT_AGENT_ID,
+    clear_response_cache, default_share_path, delete_ollama_model, extract_tool_detail,
+    list_available_models, list_sessions_for_agent, ollama_model_host, prepare_local_model,
+    probe_hosts, pull_ollama_model, response_cache_len, run_health_checks, AbortHandle, Agent,
+    AgentConfig, ApprovalPolicy, ApprovalStore, BudgetExceededError, ModelSuggestion,
+    ProviderAuthError, ProviderTimeoutError, ProviderToolEvent, ShareOptions, ShellEvent,
+    StepStatus, StreamEvent, ToolApprover, DEFAULT_AGENT_ID,
+};
+use crate::calendar::{Reminder, ReminderScheduler};
+use crate::config::{
+    compaction_strategy_names, store_api_key, CompactionStrategy, Config, OllamaConfig,
 };
-use crate::config::Config;
 use crate::memory::MemoryManager;
+use crate::voice::{speak, transcribe, Recording};
+
+use super::state::{ApprovalRequest, UiMessage, WorkerMessage};
 
-use super::state::{UiMessage, WorkerMessage};
+/// Longest dictation recorded before the rest is dropped
+const MAX_DICTATION_SECS: u64 = 300;
 
 /// Handle to the background worker
 pub struct WorkerHandle {
@@ -25,6 +39,8 @@ pub struct WorkerHandle {
     pub tx: Sender<UiMessage>,
     /// Receive updates from the worker
     pub rx: Receiver<WorkerMessage>,
+    /// Stops the agent's turn in progress
+    abort: AbortHandle,
     /// Thread handle
     _thread: JoinHandle<()>,
 }
@@ -36,30 +52,41 @@ impl WorkerHandle {
         let (worker_tx, worker_rx) = mpsc::channel::<WorkerMessage>();
 
         let agent_id = agent_id.unwrap_or_else(|| DEFAULT_AGENT_ID.to_string());
+        let abort = AbortHandle::default();
+        let worker_abort = abort.clone();
 
-        let thread = thread::spawn(move || {
-            // Create tokio runtime for this thread
-            let rt = tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .expect("Failed to create tokio runtime");
-
-            rt.block_on(async {
-                if let Err(e) = worker_loop(agent_id, ui_rx, worker_tx).await {
-                    eprintln!("Worker error: {}", e);
-                }
-            });
+        let thread = spawn_blocking_runtime(move || async move {
+            let error_tx = worker_tx.clone();
+            if let Err(e) = worker_loop(agent_id, worker_abort, ui_rx, worker_tx).await {
+                eprintln!("Worker error: {}", e);
+                // e.g. an invalid approvals.toml; show it in the error bar
+                let _ = error_tx.send(WorkerMessage::Error(format!("{:#}", e)));
+            }
         });
 
         Ok(Self {
             tx: ui_tx,
             rx: worker_rx,
+            abort,
             _thread: thread,
         })
     }
 
     /// Send a message to the worker
     pub fn send(&self, msg: UiMessage) -> Result<()> {
+        // The worker only reads messages between turns, so stopping one
+        // (or one of its tool calls) goes straight to the agent
+        match msg {
+            UiMessage::StopGeneration => {
+                self.abort.abort();
+                return Ok(());
+            }
+            UiMessage::CancelTool(ref id) => {
+                self.abort.cancel_tool(id);
+                return Ok(());
+            }
+            _ => {}
+        }
         self.tx.send(msg)?;
         Ok(())
     }
@@ -72,20 +99,24 @@ impl WorkerHandle {
 
 async fn worker_loop(
     agent_id: String,
+    abort: AbortHandle,
     rx: Receiver<UiMessage>,
     tx: Sender<WorkerMessage>,
 ) -> Result<()> {
     // Initialize agent
-    let config = Config::load()?;
+    let config = Config::load_for_agent(&agent_id)?;
     let memory = MemoryManager::new_with_full_config(&config.memory, Some(&config), &agent_id)?;
 
     let agent_config = AgentConfig {
         model: config.agent.default_model.clone(),
-        context_window: config.agent.context_window,
+        context_window: config.agent.context_window_or_default(),
         reserve_tokens: config.agent.reserve_tokens,
     };
 
     let mut agent = Agent::new(agent_config, &config, memory).await?;
+    agent.set_abort_handle(abort.clone());
+    agent.set_tool_approver(Some(approver(tx.clone(), agent.approval_store())));
+    agent.enable_turn_checkpoints();
     agent.new_session().await?;
 
     // Send ready message
@@ -93,6 +124,20 @@ async fn worker_loop(
         model: agent.model().to_string(),
         memory_chunks: agent.memory_chunk_count(),
         has_embeddings: agent.has_embeddings(),
+        workspace: agent.workspace().to_path_buf(),
+        speak_replies: config.voice.speak_replies,
+    });
+
+    // Check (and optionally warm up) a local default model in the
+    // background, so a slow model load doesn't hold up the UI
+    let local_config = config.clone();
+    let local_model = agent.model().to_string();
+    let local_tx = tx.clone();
+    spawn_blocking_runtime(move || async move {
+        prepare_local_model(&local_config, &local_model, |status| {
+            let _ = local_tx.send(WorkerMessage::LocalModel(status));
+        })
+        .await
     });
 
     // Send initial session list
@@ -103,93 +148,214 @@ async fn worker_loop(
     // Send initial status
     let _ = tx.send(WorkerMessage::Status(agent.session_status()));
 
-    // Track tools requiring approval
-    let approval_tools: Vec<String> = agent.approval_required_tools().to_vec();
+    // Mirror bash tool output to the terminal panel. The worker's runtime is
+    // blocked on UI messages between requests, so forward from a plain thread.
+    let mut shell_events = agent.subscribe_shell_events();
+    let shell_tx = tx.clone();
+    thread::spawn(move || loop {
+        match shell_events.blocking_recv() {
+            Ok(event) => {
+                if shell_tx.send(WorkerMessage::Shell(event)).is_err() {
+                    break;
+                }
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+        }
+    });
+
+    // Fire due reminders; chat reminders show up in the conversation
+    if config.calendar.enabled {
+        match ReminderScheduler::new() {
+            Ok(scheduler) => {
+                let reminder_tx = tx.clone();
+                spawn_blocking_runtime(move || async move {
+                    let (chat_tx, mut chat_rx) = tokio::sync::mpsc::unbounded_channel::<Reminder>();
+                    let forward = async {
+                        while let Some(reminder) = chat_rx.recv().await {
+                            let message =
+                                format!("Reminder: {}", reminder.message(chrono::Utc::now()));
+                            if reminder_tx
+                                .send(WorkerMessage::SystemMessage(message))
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                    };
+                    // Stops once the UI is gone
+                    tokio::select! {
+                        _ = scheduler.run(Some(chat_tx)) => {}
+                        _ = forward => {}
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to start reminders: {}", e),
+        }
+    }
+
+    // Keep the document directories indexed
+    match agent.memory().start_document_watcher() {
+        Ok(Some(watcher)) => {
+            spawn_blocking_runtime(move || watcher.run());
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to watch document directories: {}", e),
+    }
+
+    // Chat message whose turn failed on a rejected API key, resent once
+    // a working key is entered
+    let mut failed_chat: Option<ChatTurn> = None;
+
+    // Chat message refused by a provider's budget, sent if the user overrides it
+    let mut over_budget: Option<ChatTurn> = None;
+
+    // Dictation being recorded
+    let mut recording: Option<Recording> = None;
 
     // Main loop
-    while let Ok(msg) = rx.recv() {
+    loop {
+        // While a compaction runs in the background, wake up now and then
+        // to swap it in
+        let msg = if agent.compaction_running() {
+            match rx.recv_timeout(Duration::from_millis(250)) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => {
+                    report_compaction(&mut agent, &tx);
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match rx.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            }
+        };
         let mut should_auto_save = false;
 
         match msg {
             UiMessage::Chat(message) => {
-                // Stream response with tool support
-                match agent.chat_stream_with_tools(&message).await {
-                    Ok(stream) => {
-                        let mut stream = pin!(stream);
-                        let mut pending_tools: Vec<ToolCall> = Vec::new();
-
-                        while let Some(result) = stream.next().await {
-                            match result {
-                                Ok(event) => match event {
-                                    StreamEvent::Content(text) => {
-                                        let _ = tx.send(WorkerMessage::ContentChunk(text));
-                                    }
-                                    StreamEvent::ToolCallStart {
-                                        name,
-                                        id,
-                                        arguments,
-                                    } => {
-                                        // Check if this tool requires approval
-                                        if approval_tools.contains(&name) {
-                                            // Collect for approval
-                                            pending_tools.push(ToolCall {
-                                                id,
-                                                name,
-                                                arguments: String::new(),
-                                            });
-                                        } else {
-                                            let detail = extract_tool_detail(&name, &arguments);
-                                            let _ = tx.send(WorkerMessage::ToolCallStart {
-                                                name,
-                                                id,
-                                                detail,
-                                            });
-                                        }
-                                    }
-                                    StreamEvent::ToolCallEnd { name, id, output } => {
-                                        let _ = tx.send(WorkerMessage::ToolCallEnd {
-                                            name,
-                                            id,
-                                            output,
-                                        });
-                                    }
-                                    StreamEvent::Done => {
-                                        if !pending_tools.is_empty() {
-                                            let _ = tx.send(WorkerMessage::ToolsPendingApproval(
-                                                pending_tools.clone(),
-                                            ));
-                                            pending_tools.clear();
-                                        } else {
-                                            let _ = tx.send(WorkerMessage::Done);
-                                        }
-                                        should_auto_save = true;
-                                    }
-                                },
-                                Err(e) => {
-                                    let _ = tx.send(WorkerMessage::Error(e.to_string()));
-                                    break;
-                                }
-                            }
+                let turn = ChatTurn {
+                    message,
+                    model: None,
+                };
+                should_auto_save =
+                    send_turn(&mut agent, turn, &tx, &mut failed_chat, &mut over_budget).await;
+            }
+            UiMessage::Ask { model, message } => {
+                let turn = ChatTurn {
+                    message,
+                    model: Some(model),
+                };
+                should_auto_save =
+                    send_turn(&mut agent, turn, &tx, &mut failed_chat, &mut over_budget).await;
+            }
+            UiMessage::OverrideBudget(provider) => {
+                agent.override_budget(&provider);
+                match over_budget.take() {
+                    Some(turn) => match stream_turn(&mut agent, &turn, &tx).await {
+                        Ok(done) => should_auto_save = done,
+                        Err(e) => {
+                            failed_chat = can_retry(&e).then_some(turn);
+                            let _ = tx.send(error_message(&e));
                         }
-                    }
-                    Err(e) => {
-                        let _ = tx.send(WorkerMessage::Error(e.to_string()));
+                    },
+                    None => {
+                        let _ = tx.send(WorkerMessage::SystemMessage(format!(
+                            "Ignoring the {} budget until LocalGPT exits.",
+                            provider
+                        )));
+                        let _ = tx.send(WorkerMessage::Done);
                     }
                 }
             }
-            UiMessage::NewSession => match agent.new_session().await {
+            // Handled by WorkerHandle::send; arrives here only between turns
+            UiMessage::StopGeneration | UiMessage::CancelTool(_) => {}
+            UiMessage::UpdateApiKey {
+                provider,
+                model,
+                key,
+            } => match agent.replace_api_key(&provider, &model, &key).await {
                 Ok(()) => {
-                    let status = agent.session_status();
-                    let _ = tx.send(WorkerMessage::SessionChanged {
-                        id: status.id.clone(),
-                        message_count: status.message_count,
+                    if let Err(e) = store_api_key(&config.api_key_entry(&provider), &key) {
+                        let _ = tx.send(WorkerMessage::SystemMessage(format!(
+                            "The new {} key works but could not be saved ({}). \
+                            It will be used until LocalGPT exits.",
+                            provider, e
+                        )));
+                    }
+                    // Only retry if the failed turn is still the last thing in the session
+                    let retry = failed_chat
+                        .take()
+                        .filter(|_| agent.take_failed_turn().is_some());
+                    let _ = tx.send(WorkerMessage::ApiKeyAccepted {
+                        provider,
+                        retrying: retry.is_some(),
                     });
-                    let _ = tx.send(WorkerMessage::Status(status));
+                    if let Some(turn) = retry {
+                        match stream_turn(&mut agent, &turn, &tx).await {
+                            Ok(done) => should_auto_save = done,
+                            Err(e) => {
+                                if can_retry(&e) {
+                                    failed_chat = Some(turn);
+                                }
+                                let _ = tx.send(error_message(&e));
+                            }
+                        }
+                    }
                 }
                 Err(e) => {
-                    let _ = tx.send(WorkerMessage::Error(e.to_string()));
+                    let _ = tx.send(WorkerMessage::ApiKeyRejected(e.to_string()));
                 }
             },
+            UiMessage::RetryFailedMessage => {
+                // Only retry if the failed turn is still the last thing in the session
+                let retry = failed_chat
+                    .take()
+                    .filter(|_| agent.take_failed_turn().is_some());
+                match retry {
+                    Some(turn) => match stream_turn(&mut agent, &turn, &tx).await {
+                        Ok(done) => should_auto_save = done,
+                        Err(e) => {
+                            if can_retry(&e) {
+                                failed_chat = Some(turn);
+                            }
+                            let _ = tx.send(error_message(&e));
+                        }
+                    },
+                    None => {
+                        let _ = tx.send(WorkerMessage::Error(
+                            "Nothing to retry: the last message was already answered".to_string(),
+                        ));
+                    }
+                }
+            }
+            UiMessage::NewSession => {
+                let facts = agent.extract_session_facts().await.unwrap_or_else(|e| {
+                    eprintln!("Failed to extract facts from session: {}", e);
+                    Vec::new()
+                });
+                match agent.new_session().await {
+                    Ok(()) => {
+                        let status = agent.session_status();
+                        let _ = tx.send(WorkerMessage::SessionChanged {
+                            id: status.id.clone(),
+                            message_count: status.message_count,
+                        });
+                        let _ = tx.send(WorkerMessage::Status(status));
+                        if !facts.is_empty() {
+                            let _ = tx.send(WorkerMessage::SystemMessage(format!(
+                                "Remembered in USER.md: {}",
+                                facts.join("; ")
+                            )));
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(WorkerMessage::Error(e.to_string()));
+                    }
+                }
+            }
             UiMessage::ResumeSession(session_id) => match agent.resume_session(&session_id).await {
                 Ok(()) => {
                     let status = agent.session_status();
@@ -203,14 +369,6 @@ async fn worker_loop(
                     let _ = tx.send(WorkerMessage::Error(e.to_string()));
                 }
             },
-            UiMessage::ApproveTools(_tools) => {
-                // Tool approval is handled in chat loop
-                // For now, just send done
-                let _ = tx.send(WorkerMessage::Done);
-            }
-            UiMessage::DenyTools => {
-                let _ = tx.send(WorkerMessage::Done);
-            }
             UiMessage::RefreshSessions => {
                 if let Ok(sessions) = list_sessions_for_agent(&agent_id) {
                     let _ = tx.send(WorkerMessage::Sessions(sessions));
@@ -218,9 +376,104 @@ async fn worker_loop(
             }
             UiMessage::RefreshStatus => {
                 let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+                let _ = tx.send(WorkerMessage::Stats(agent.session_stats()));
+                let _ = tx.send(WorkerMessage::ModelUsage(agent.model_usage_report()));
+            }
+            UiMessage::ApplyModelSuggestion(suggestion) => {
+                let text = match apply_model_suggestion(&mut agent, &suggestion) {
+                    Ok(()) => format!("Set {} = {}", suggestion.key, suggestion.value),
+                    Err(e) => format!("Failed to apply suggestion: {}", e),
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+                let _ = tx.send(WorkerMessage::ModelChanged(agent.model().to_string()));
+                let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+                let _ = tx.send(WorkerMessage::ModelUsage(agent.model_usage_report()));
+            }
+            UiMessage::Regenerate(style) => {
+                let result = async {
+                    let stream = agent.regenerate_stream(style).await?;
+                    forward_stream(stream, &tx).await
+                }
+                .await;
+                match result {
+                    Ok(done) => should_auto_save = done,
+                    Err(e) => {
+                        let _ = tx.send(error_message(&e));
+                    }
+                }
+            }
+            UiMessage::UseReplyVariant(content) => {
+                if agent.use_reply_variant(&content) {
+                    should_auto_save = true;
+                }
+            }
+            UiMessage::RateReply { content, rating } => {
+                if let Err(e) = agent.rate_reply(Some(&content), rating, None).await {
+                    let _ = tx.send(error_message(&e));
+                }
+            }
+            UiMessage::PinMessage { content, pinned } => {
+                match agent.set_pinned(content.as_deref(), pinned) {
+                    Ok(content) => {
+                        let _ = tx.send(WorkerMessage::MessagePinned { content, pinned });
+                    }
+                    Err(e) => {
+                        let _ = tx.send(WorkerMessage::SystemMessage(e.to_string()));
+                    }
+                }
+            }
+            UiMessage::ListPins => {
+                let pinned = agent.pinned_messages();
+                let text = if pinned.is_empty() {
+                    "No pinned messages.".to_string()
+                } else {
+                    let list: Vec<String> = pinned
+                        .iter()
+                        .map(|content| format!("📌 {}", content.lines().next().unwrap_or_default()))
+                        .collect();
+                    format!("Pinned messages:\n{}", list.join("\n"))
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+            }
+            UiMessage::ListModels => spawn_model_listing(config.clone(), tx.clone()),
+            UiMessage::ProbeOllamaHosts => {
+                let _ = tx.send(WorkerMessage::OllamaHosts(agent.ollama_hosts().await));
+            }
+            UiMessage::RunDoctor => spawn_health_checks(config.clone(), tx.clone()),
+            UiMessage::StartRecording => {
+                if recording.is_none() {
+                    match Recording::start(MAX_DICTATION_SECS) {
+                        Ok(started) => {
+                            recording = Some(started);
+                            let _ = tx.send(WorkerMessage::Recording(true));
+                        }
+                        Err(e) => {
+                            let _ = tx.send(WorkerMessage::VoiceError(e.to_string()));
+                        }
+                    }
+                }
+            }
+            UiMessage::StopRecording => {
+                if let Some(finished) = recording.take() {
+                    let _ = tx.send(WorkerMessage::Recording(false));
+                    spawn_transcription(finished, config.clone(), tx.clone());
+                }
+            }
+            UiMessage::Speak(text) => spawn_speech(text, config.clone(), tx.clone()),
+            UiMessage::PullOllamaModel(model) => {
+                spawn_ollama_pull(model, config.clone(), tx.clone())
+            }
+            UiMessage::DeleteOllamaModel(model) => {
+                let text = match delete_model(&config, &model).await {
+                    Ok(()) => format!("Deleted Ollama model {}", model),
+                    Err(e) => format!("Failed to delete {}: {}", model, e),
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+                let _ = tx.send(WorkerMessage::OllamaHosts(agent.ollama_hosts().await));
             }
             UiMessage::SetModel(name) => match agent.set_model(&name) {
                 Ok(()) => {
+                    let _ = tx.send(WorkerMessage::ModelChanged(agent.model().to_string()));
                     let _ = tx.send(WorkerMessage::SystemMessage(format!(
                         "Model set to: {}",
                         agent.model()
@@ -233,21 +486,81 @@ async fn worker_loop(
                     )));
                 }
             },
-            UiMessage::Compact => match agent.compact_session().await {
-                Ok((before, after)) => {
-                    let _ = tx.send(WorkerMessage::SystemMessage(format!(
-                        "Session compacted: {} -> {} tokens",
-                        before, after
-                    )));
-                    let _ = tx.send(WorkerMessage::Status(agent.session_status()));
-                }
-                Err(e) => {
-                    let _ = tx.send(WorkerMessage::SystemMessage(format!(
-                        "Compact failed: {}",
-                        e
-                    )));
-                }
-            },
+            UiMessage::SetParams(settings) => {
+                let result = settings
+                    .iter()
+                    .try_for_each(|(key, value)| agent.set_generation_param(key, value));
+                let text = match result {
+                    Ok(()) => format!(
+                        "Updated: {}",
+                        settings
+                            .iter()
+                            .map(|(k, v)| format!("{}={}", k, v))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                    Err(e) => format!("Failed to update settings: {}", e),
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+                let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+            }
+            UiMessage::SetSystemPrompt(prompt) => {
+                let text = if prompt.is_some() {
+                    "System instructions set for this session."
+                } else {
+                    "System instructions cleared."
+                };
+                agent.set_system_prompt(prompt);
+                let _ = tx.send(WorkerMessage::SystemMessage(text.to_string()));
+                let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+            }
+            UiMessage::SetLanguage(language) => {
+                let text = match language {
+                    Some(ref language) => {
+                        format!("Replies will be in {} for this session.", language)
+                    }
+                    None => "Reply language cleared.".to_string(),
+                };
+                agent.set_language(language);
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+                let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+            }
+            UiMessage::CompactionStrategy(name) => {
+                let text = match name.as_deref() {
+                    None => format!(
+                        "Compaction strategy: {} (one of {})",
+                        agent.compaction_strategy(),
+                        compaction_strategy_names()
+                    ),
+                    Some("default") => {
+                        agent.set_compaction_strategy(None);
+                        format!(
+                            "Compaction strategy reset to {}.",
+                            agent.compaction_strategy()
+                        )
+                    }
+                    Some(name) => match name.parse::<CompactionStrategy>() {
+                        Ok(strategy) => {
+                            agent.set_compaction_strategy(Some(strategy));
+                            format!("This session will be compacted with {}.", strategy)
+                        }
+                        Err(e) => e.to_string(),
+                    },
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+            }
+            UiMessage::Compact => {
+                let text = if agent.compaction_running() {
+                    "Already compacting."
+                } else if agent.start_compaction() {
+                    "Compacting in the background..."
+                } else {
+                    "Nothing to compact."
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text.to_string()));
+                // Drop-oldest has no summary to wait for
+                report_compaction(&mut agent, &tx);
+            }
             UiMessage::SearchMemory(query) => match agent.search_memory(&query).await {
                 Ok(results) => {
                     if results.is_empty() {
@@ -273,9 +586,14 @@ async fn worker_loop(
                             })
                             .collect::<Vec<_>>()
                             .join("\n\n");
+                        let kind = if agent.has_embeddings() {
+                            "Semantic memory"
+                        } else {
+                            "Memory"
+                        };
                         let _ = tx.send(WorkerMessage::SystemMessage(format!(
-                            "Memory search results for \"{}\":\n{}",
-                            query, text
+                            "{} search results for \"{}\":\n{}",
+                            kind, query, text
                         )));
                     }
                 }
@@ -297,20 +615,132 @@ async fn worker_loop(
                     let _ = tx.send(WorkerMessage::SystemMessage(format!("Save failed: {}", e)));
                 }
             },
+            UiMessage::Share { include_tool_calls } => {
+                let path = default_share_path(&agent.session_status().id);
+                let html = agent.export_html(&ShareOptions {
+                    include_tool_calls,
+                    ..Default::default()
+                });
+                let text = match std::fs::write(&path, html) {
+                    Ok(()) => format!(
+                        "Shared copy written to {} (secrets redacted; review before sending)",
+                        path.display()
+                    ),
+                    Err(e) => format!("Share failed: {}", e),
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+            }
+            UiMessage::InspectContext => {
+                let _ = tx.send(WorkerMessage::Context(agent.inspect_context()));
+            }
+            UiMessage::UndoChanges => {
+                let text = match agent.undo_agent_changes() {
+                    Ok(summary) => summary.to_string(),
+                    Err(e) => format!("Undo failed: {}", e),
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+            }
+            UiMessage::ResponseCache(clear) => {
+                let text = if clear {
+                    match clear_response_cache() {
+                        Ok(count) => format!("Cleared {} cached replies", count),
+                        Err(e) => format!("Failed to clear cache: {}", e),
+                    }
+                } else {
+                    match response_cache_len() {
+                        Ok(count) => format!("Cached replies: {}", count),
+                        Err(e) => format!("Failed to read cache: {}", e),
+                    }
+                };
+                let _ = tx.send(WorkerMessage::SystemMessage(text));
+            }
             UiMessage::ShowHelp => {
                 let help_text = "\
 Available commands:
   /new              Start a new session
-  /model [name]     Show or set the current model
-  /compact          Compact session history
+  /model [name]     Show or set the current model (or pick from the toolbar menu)
+  /ask <model> <msg> Send one message to another model (or @model msg)
+  /set key=value    Set temperature or max_tokens (\"default\" resets)
+  /system [text]    Show or set session system instructions (/system clear)
+  /lang [language]  Show or set the reply language (/lang off)
+  /compact          Compact session history in the background
+  /pin [text]       Keep the last reply (or a message with this text) through compaction
+  /unpin [text]     Unpin it again; /pins lists pinned messages
+  /paste [instruction]  Send the clipboard text, optionally with an instruction
+  /copy             Copy the last reply to the clipboard
+  /compact strategy [name|default]  Show or set how this session is compacted
+  /cache [clear]    Show or clear cached replies ([providers.cache])
+  /doctor           Check each provider's connection, key and latency
+  /context          Show the exact messages the next turn will send
+  /undo             Revert file changes from the agent's last turn
+  /plan <task>      Plan a task as reviewable steps, then run them one by one
+  /regenerate [creative|precise]  Rerun the last message, keeping old replies
   /memory <query>   Search memory files
   /save             Save current session to disk
   /status           Show session status
+  /stats            Show message, tool call, and token statistics
   /sessions         Show saved sessions
   /resume <id>      Resume a session by ID
   /help             Show this help text";
                 let _ = tx.send(WorkerMessage::SystemMessage(help_text.to_string()));
             }
+            UiMessage::PinFile(path) => {
+                if let Err(e) = agent.pin_file(&path) {
+                    let _ = tx.send(WorkerMessage::Error(format!("Failed to pin file: {}", e)));
+                }
+                let _ = tx.send(WorkerMessage::PinnedFiles(agent.pinned_files().to_vec()));
+            }
+            UiMessage::UnpinFile(path) => {
+                agent.unpin_file(&path);
+                let _ = tx.send(WorkerMessage::PinnedFiles(agent.pinned_files().to_vec()));
+            }
+            UiMessage::RunShell(command) => {
+                // Output arrives through the shell event forwarder; only
+                // failures (e.g. timeouts) need reporting here
+                if let Err(e) = agent.run_shell_command(&command).await {
+                    let _ = tx.send(WorkerMessage::Shell(ShellEvent::Output {
+                        text: e.to_string(),
+                        stderr: true,
+                    }));
+                }
+            }
+            UiMessage::MakePlan(task) => match agent.make_plan(&task).await {
+                Ok(plan) => {
+                    let _ = tx.send(WorkerMessage::PlanProposed(plan));
+                }
+                Err(e) => {
+                    let _ = tx.send(error_message(&e));
+                }
+            },
+            UiMessage::RunPlan(mut plan) => {
+                plan.remove_blank_steps();
+                while let Some(index) = plan.next_pending() {
+                    plan.steps[index].status = StepStatus::Running;
+                    let _ = tx.send(WorkerMessage::PlanStep {
+                        index,
+                        status: StepStatus::Running,
+                    });
+                    let status = match stream_chat(&mut agent, &plan.step_message(index), &tx).await
+                    {
+                        Ok(true) if !abort.is_aborted() => StepStatus::Done,
+                        Ok(_) => StepStatus::Failed,
+                        Err(e) => {
+                            let _ = tx.send(error_message(&e));
+                            StepStatus::Failed
+                        }
+                    };
+                    plan.steps[index].status = status;
+                    let _ = tx.send(WorkerMessage::PlanStep { index, status });
+                    if let Err(e) = agent.auto_save_session() {
+                        eprintln!("Warning: Failed to auto-save session: {}", e);
+                    }
+                    if status == StepStatus::Failed {
+                        plan.skip_remaining();
+                        break;
+                    }
+                }
+                let _ = tx.send(WorkerMessage::PlanFinished);
+            }
             UiMessage::ShowStatus => {
                 let status = agent.session_status();
                 let text = format!(
@@ -327,6 +757,10 @@ Available commands:
             }
         }
 
+        if let Some(warning) = agent.take_budget_warning() {
+            let _ = tx.send(WorkerMessage::SystemMessage(warning));
+        }
+
         // Auto-save session after chat completes
         if should_auto_save {
             if let Err(e) = agent.auto_save_session() {
@@ -337,3 +771,328 @@ Available commands:
 
     Ok(())
 }
+
+/// Stream one chat turn to the UI. Returns whether the turn finished.
+async fn stream_chat(agent: &mut Agent, message: &str, tx: &Sender<WorkerMessage>) -> Result<bool> {
+    let stream = agent.chat_stream_with_tools(message).await?;
+    forward_stream(stream, tx).await
+}
+
+/// A chat message, and the model `/ask` sent it to, kept to send again
+#[derive(Clone)]
+struct ChatTurn {
+    message: String,
+    model: Option<String>,
+}
+
+async fn stream_turn(
+    agent: &mut Agent,
+    turn: &ChatTurn,
+    tx: &Sender<WorkerMessage>,
+) -> Result<bool> {
+    if let Some(ref model) = turn.model {
+        agent.set_turn_model(model).await?;
+    }
+    stream_chat(agent, &turn.message, tx).await
+}
+
+/// Stream a turn, keeping it to send again if it failed in a way the user
+/// can fix (a rejected key, a timeout, a budget). Returns whether it finished.
+async fn send_turn(
+    agent: &mut Agent,
+    turn: ChatTurn,
+    tx: &Sender<WorkerMessage>,
+    failed_chat: &mut Option<ChatTurn>,
+    over_budget: &mut Option<ChatTurn>,
+) -> bool {
+    match stream_turn(agent, &turn, tx).await {
+        Ok(done) => done,
+        Err(e) => {
+            *over_budget = budget_error(&e).is_some().then(|| turn.clone());
+            *failed_chat = can_retry(&e).then_some(turn);
+            let _ = tx.send(error_message(&e));
+            false
+        }
+    }
+}
+
+/// Ask the user about tool calls in the approval dialog, waiting for
+/// their answer. Choices they ask to remember go into the agent's
+/// approval store, so they apply from the next call on.
+fn approver(tx: Sender<WorkerMessage>, approvals: ApprovalStore) -> ToolApprover {
+    let tx = Arc::new(std::sync::Mutex::new(tx));
+    Arc::new(move |calls| {
+        let count = calls.len();
+        let (reply, mut answers) = tokio::sync::mpsc::unbounded_channel();
+        let request = ApprovalRequest { calls, reply };
+        let tools = request.rememberable_tools();
+        let sent = tx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .send(WorkerMessage::ToolsPendingApproval(request));
+        let tx = Arc::clone(&tx);
+        let approvals = approvals.clone();
+        Box::pin(async move {
+            if sent.is_err() {
+                return vec![false; count];
+            }
+            let Some(answer) = answers.recv().await else {
+                return vec![false; count];
+            };
+            if let Some(scope) = answer.remember {
+                let policy = if answer.approved {
+                    ApprovalPolicy::Allow
+                } else {
+                    ApprovalPolicy::Never
+                };
+                for tool in &tools {
+                    if let Err(e) = approvals.remember(tool, Some(policy), scope) {
+                        let _ = tx.lock().unwrap_or_else(|e| e.into_inner()).send(
+                            WorkerMessage::Error(format!(
+                                "Could not save the approval policy for {}: {}",
+                                tool, e
+                            )),
+                        );
+                    }
+                }
+            }
+            vec![answer.approved; count]
+        })
+    })
+}
+
+/// Send a turn's events to the UI. Returns whether the turn finished.
+async fn forward_stream(
+    stream: impl futures::Stream<Item = Result<StreamEvent>>,
+    tx: &Sender<WorkerMessage>,
+) -> Result<bool> {
+    let mut stream = pin!(stream);
+
+    while let Some(event) = stream.next().await {
+        match event? {
+            StreamEvent::Content(text) => {
+                let _ = tx.send(WorkerMessage::ContentChunk(text));
+            }
+            StreamEvent::Reasoning(text) => {
+                let _ = tx.send(WorkerMessage::ReasoningChunk(text));
+            }
+            StreamEvent::ToolCallStart {
+                name,
+                id,
+                arguments,
+            } => {
+                let detail = extract_tool_detail(&name, &arguments);
+                let _ = tx.send(WorkerMessage::ToolCallStart { name, id, detail });
+            }
+            StreamEvent::ToolCallEnd {
+                name,
+                id,
+                output,
+                summary,
+            } => {
+                let _ = tx.send(WorkerMessage::ToolCallEnd {
+                    name,
+                    id,
+                    output,
+                    summary,
+                    failed: false,
+                });
+            }
+            // Already run by the provider, so never held for approval
+            StreamEvent::ProviderTool(ProviderToolEvent::Started { id, name, detail }) => {
+                let _ = tx.send(WorkerMessage::ToolCallStart { name, id, detail });
+            }
+            StreamEvent::ProviderTool(ProviderToolEvent::Finished {
+                id,
+                name,
+                output,
+                is_error,
+            }) => {
+                let _ = tx.send(WorkerMessage::ToolCallEnd {
+                    name,
+                    id,
+                    output,
+                    summary: None,
+                    failed: is_error,
+                });
+            }
+            StreamEvent::Done => {
+                let _ = tx.send(WorkerMessage::Done);
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Write a suggested model change to config.toml and switch to it now
+fn apply_model_suggestion(agent: &mut Agent, suggestion: &ModelSuggestion) -> Result<()> {
+    match suggestion.key.as_str() {
+        "agent.default_model" => agent.set_model(&suggestion.value)?,
+        "agent.auxiliary_model" => agent.set_auxiliary_model(Some(&suggestion.value))?,
+        key => anyhow::bail!("Unsupported config key: {}", key),
+    }
+    let mut config = Config::load()?;
+    config.set_value(&suggestion.key, &suggestion.value)?;
+    config.save()
+}
+
+/// The provider's rejection of its API key, if that is what `error` is
+fn auth_error(error: &anyhow::Error) -> Option<&ProviderAuthError> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ProviderAuthError>())
+}
+
+/// A provider's budget running out, if that is what `error` is
+fn budget_error(error: &anyhow::Error) -> Option<&BudgetExceededError> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BudgetExceededError>())
+}
+
+/// Ollama settings from the agent's config
+fn ollama_config(config: &Config) -> Result<OllamaConfig> {
+    config
+        .providers
+        .ollama
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Ollama provider not configured"))
+}
+
+async fn delete_model(config: &Config, model: &str) -> Result<()> {
+    let ollama = ollama_config(config)?;
+    let (host, model) = ollama_model_host(&ollama, model)?;
+    delete_ollama_model(&host, model).await
+}
+
+/// Ask the providers for their models on a separate thread, so a slow or
+/// unreachable provider doesn't hold up chatting
+fn spawn_model_listing(config: Config, tx: Sender<WorkerMessage>) {
+    spawn_blocking_runtime(move || async move {
+        let models = list_available_models(&config).await;
+        let _ = tx.send(WorkerMessage::Models(models));
+    });
+}
+
+/// Check the configured providers on their own thread so chat isn't blocked
+fn spawn_health_checks(config: Config, tx: Sender<WorkerMessage>) {
+    spawn_blocking_runtime(move || async move {
+        let results = run_health_checks(&config).await;
+        let _ = tx.send(WorkerMessage::Diagnostics(results));
+    });
+}
+
+/// Stop a recording and transcribe it on its own thread
+fn spawn_transcription(recording: Recording, config: Config, tx: Sender<WorkerMessage>) {
+    spawn_blocking_runtime(move || async move {
+        let result = match recording.finish() {
+            Ok(audio) => transcribe(&config, audio).await,
+            Err(e) => Err(e),
+        };
+        let _ = tx.send(match result {
+            Ok(text) => WorkerMessage::Transcribed(text),
+            Err(e) => WorkerMessage::VoiceError(format!("Transcription failed: {}", e)),
+        });
+    });
+}
+
+/// Read a reply aloud on its own thread, so chatting continues meanwhile
+fn spawn_speech(text: String, config: Config, tx: Sender<WorkerMessage>) {
+    spawn_blocking_runtime(move || async move {
+        if let Err(e) = speak(&config, &text).await {
+            let _ = tx.send(WorkerMessage::VoiceError(format!(
+                "Could not speak the reply: {}",
+                e
+            )));
+        }
+    });
+}
+
+/// Pull a model on its own thread, streaming progress to the Status panel
+/// and re-probing the hosts when done
+fn spawn_ollama_pull(model: String, config: Config, tx: Sender<WorkerMessage>) {
+    spawn_blocking_runtime(move || async move {
+        let result = async {
+            let ollama = ollama_config(&config)?;
+            let (host, name) = ollama_model_host(&ollama, &model)?;
+            pull_ollama_model(&host, name, |progress| {
+                let _ = tx.send(WorkerMessage::OllamaPull(progress));
+            })
+            .await?;
+            Ok::<_, anyhow::Error>(ollama)
+        }
+        .await;
+
+        let error = match result {
+            Ok(ollama) => {
+                let _ = tx.send(WorkerMessage::OllamaHosts(probe_hosts(&ollama).await));
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        let _ = tx.send(WorkerMessage::OllamaPullFinished { model, error });
+    });
+}
+
+/// Run the future `f` makes on a new thread with its own runtime, so slow
+/// work doesn't hold up the worker loop
+fn spawn_blocking_runtime<F, Fut>(f: F) -> JoinHandle<Fut::Output>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future,
+    Fut::Output: Send + 'static,
+{
+    thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create tokio runtime")
+            .block_on(f())
+    })
+}
+
+/// The provider timing out, if that is what `error` is
+fn timeout_error(error: &anyhow::Error) -> Option<&ProviderTimeoutError> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ProviderTimeoutError>())
+}
+
+/// Swap in a finished background compaction and tell the UI
+fn report_compaction(agent: &mut Agent, tx: &Sender<WorkerMessage>) {
+    let text = match agent.poll_compaction() {
+        Some(Ok((before, after))) => format!("Session compacted: {} -> {} tokens", before, after),
+        Some(Err(e)) => format!("Compact failed: {}", e),
+        None => return,
+    };
+    let _ = tx.send(WorkerMessage::SystemMessage(text));
+    let _ = tx.send(WorkerMessage::Status(agent.session_status()));
+}
+
+/// Whether a failed message can be sent again once the problem is fixed
+fn can_retry(error: &anyhow::Error) -> bool {
+    auth_error(error).is_some() || timeout_error(error).is_some()
+}
+
+/// Auth errors open the API key dialog, timeouts offer a retry, budget
+/// errors offer to continue; anything else is shown as text
+fn error_message(error: &anyhow::Error) -> WorkerMessage {
+    if let Some(auth) = auth_error(error) {
+        return WorkerMessage::AuthError {
+            provider: auth.provider.clone(),
+            model: auth.model.clone(),
+            message: auth.message.clone(),
+        };
+    }
+    if let Some(budget) = budget_error(error) {
+        return WorkerMessage::BudgetExceeded {
+            provider: budget.provider.clone(),
+            message: budget.to_string(),
+        };
+    }
+    match timeout_error(error) {
+        Some(timeout) => WorkerMessage::TimedOut(timeout.to_string()),
+        None => WorkerMessage::Error(error.to_string()),
+    }
+}
@@ -236,7 +236,7 @@ impl HeartbeatRunner {
         // Create agent for heartbeat (clone the cached MemoryManager to share the embedding provider)
         let agent_config = AgentConfig {
             model: self.config.agent.default_model.clone(),
-            context_window: self.config.agent.context_window,
+            context_window: self.config.agent.context_window_or_default(),
             reserve_tokens: self.config.agent.reserve_tokens,
         };
 
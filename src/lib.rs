@@ -6,8 +6,10 @@
 //! - Heartbeat runner for continuous operation
 //! - HTTP server for UI integration
 //! - Desktop GUI (egui-based)
+//! - Wake word voice mode (local speech recognition)
 
 pub mod agent;
+pub mod calendar;
 pub mod concurrency;
 pub mod config;
 #[cfg(feature = "desktop")]
@@ -15,5 +17,6 @@ pub mod desktop;
 pub mod heartbeat;
 pub mod memory;
 pub mod server;
+pub mod voice;
 
 pub use config::Config;
@@ -2,7 +2,6 @@ use anyhow::Result;
 use clap::Parser;
 use tracing::debug;
 
-
 mod cli;
 
 use cli::{Cli, Commands};
@@ -11,6 +10,16 @@ fn main() -> Result<()> {
     debug!("main() begin");
     let cli = Cli::parse();
 
+    // Select the user profile before any config or state paths are resolved.
+    // Chat offers a switcher when profiles exist; desktop shows its own.
+    match cli.command {
+        #[cfg(feature = "desktop")]
+        Commands::Desktop(_) => {}
+        Commands::Profile(_) => {}
+        Commands::Chat(_) => cli::profile::select(cli.user.as_deref(), true)?,
+        _ => cli::profile::select(cli.user.as_deref(), false)?,
+    }
+
     // Handle daemon start/restart specially - must fork BEFORE starting Tokio runtime
     #[cfg(unix)]
     if let Commands::Daemon(ref args) = cli.command {
@@ -49,9 +58,17 @@ async fn async_main(cli: Cli) -> Result<()> {
         Commands::Chat(args) => cli::chat::run(args, &cli.agent).await,
         Commands::Ask(args) => cli::ask::run(args, &cli.agent).await,
         #[cfg(feature = "desktop")]
-        Commands::Desktop(args) => cli::desktop::run(args, &cli.agent),
+        Commands::Desktop(args) => cli::desktop::run(args, &cli.agent, cli.user.as_deref()),
         Commands::Daemon(args) => cli::daemon::run(args, &cli.agent).await,
+        #[cfg(feature = "voice")]
+        Commands::Listen(args) => cli::listen::run(args, &cli.agent).await,
         Commands::Memory(args) => cli::memory::run(args, &cli.agent).await,
+        Commands::Index(args) => cli::index::run(args, &cli.agent).await,
         Commands::Config(args) => cli::config::run(args).await,
+        Commands::Profile(args) => cli::profile::run(args).await,
+        Commands::Agents(args) => cli::agents::run(args).await,
+        Commands::Ollama(args) => cli::ollama::run(args).await,
+        Commands::Feedback(args) => cli::feedback::run(args).await,
+        Commands::Doctor(args) => cli::doctor::run(args).await,
     }
 }
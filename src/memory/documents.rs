@@ -0,0 +1,215 @@
+//! Documents indexed with `localgpt index <path>` for the `document_search`
+//! tool
+//!
+//! Markdown and text files are read as they are, PDFs through poppler's
+//! `pdftotext` (one section per page, so passages can be cited by page),
+//! and .docx files by pulling the paragraphs out of `word/document.xml`.
+//! Scanned PDFs without a text layer have nothing to index.
+//!
+//! `document_text` gives the same text with page markers for chat
+//! attachments and `read_file`.
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Extensions `localgpt index` picks up
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "text", "rst", "pdf", "docx"];
+
+/// Larger files are skipped
+const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+static XML_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// What `MemoryManager::index_documents` did
+#[derive(Debug, Default)]
+pub struct DocumentIndexStats {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Files that could not be read, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Whether `localgpt index` reads this file
+pub fn is_document(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| DOCUMENT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Supported files at `path` (the file itself, or everything under a
+/// directory that isn't gitignored or hidden)
+pub fn find_documents(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let mut files: Vec<PathBuf> = ignore::WalkBuilder::new(path)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(|e| e.into_path())
+        .filter(|p| is_document(p))
+        .filter(|p| fs::metadata(p).is_ok_and(|m| m.len() <= MAX_DOCUMENT_BYTES))
+        .collect();
+    files.sort();
+    files
+}
+
+/// The document's text as (chunk path, text) sections: one per non-empty
+/// page for PDFs (`<path>#page=<n>`), else one for the whole file
+pub async fn extract_sections(path: &Path) -> Result<Vec<(String, String)>> {
+    let key = path.to_string_lossy().to_string();
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let sections = match ext.as_str() {
+        "pdf" => pdf_pages(&pdf_text(path).await?)
+            .into_iter()
+            .map(|(page, text)| (format!("{}#page={}", key, page), text))
+            .collect(),
+        "docx" => vec![(key, docx_text(path)?)],
+        _ => vec![(key, String::from_utf8_lossy(&fs::read(path)?).to_string())],
+    };
+    let sections: Vec<_> = sections
+        .into_iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+    if sections.is_empty() {
+        anyhow::bail!("no text found");
+    }
+    Ok(sections)
+}
+
+/// Whether the file is a PDF or .docx, which has to be extracted rather
+/// than read as text
+pub fn needs_extraction(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| ext == "pdf" || ext == "docx")
+}
+
+/// A PDF's or .docx's text, with a `--- Page n ---` marker before each PDF
+/// page so answers can cite pages
+pub async fn document_text(path: &Path) -> Result<String> {
+    let text = match path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("pdf") => paged_text(&pdf_text(path).await?),
+        Some("docx") => docx_text(path)?,
+        _ => String::from_utf8_lossy(&fs::read(path)?).to_string(),
+    };
+    if text.trim().is_empty() {
+        anyhow::bail!("no text found");
+    }
+    Ok(text)
+}
+
+/// `pdftotext` output with a `--- Page n ---` marker before each non-empty
+/// page
+pub fn paged_text(pdftotext_output: &str) -> String {
+    pdf_pages(pdftotext_output)
+        .into_iter()
+        .map(|(page, text)| format!("--- Page {} ---\n{}\n", page, text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn pdf_text(path: &Path) -> Result<String> {
+    let output = Command::new("pdftotext")
+        .args(["-enc", "UTF-8"])
+        .arg(path)
+        .arg("-")
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("pdftotext not available ({}); install poppler", e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "pdftotext failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Split `pdftotext` output at its form feeds into numbered pages
+fn pdf_pages(text: &str) -> Vec<(usize, String)> {
+    text.split('\u{c}')
+        .enumerate()
+        .map(|(i, page)| (i + 1, page.trim_end().to_string()))
+        .filter(|(_, page)| !page.trim().is_empty())
+        .collect()
+}
+
+fn docx_text(path: &Path) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|_| anyhow::anyhow!("not a Word document"))?
+        .read_to_string(&mut xml)?;
+    Ok(docx_xml_text(&xml))
+}
+
+/// Paragraph text of a `word/document.xml`
+fn docx_xml_text(xml: &str) -> String {
+    let xml = xml
+        .replace("</w:p>", "\n")
+        .replace("<w:tab/>", "\t")
+        .replace("<w:br/>", "\n");
+    let text = XML_TAG.replace_all(&xml, "");
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// How a chunk path reads in a citation: `report.pdf, page 3` or
+/// `notes.md, lines 10-24`
+pub fn citation(chunk_path: &str, line_start: i32, line_end: i32) -> String {
+    match chunk_path.rsplit_once("#page=") {
+        Some((path, page)) => format!("{}, page {}", path, page),
+        None => format!("{}, lines {}-{}", chunk_path, line_start, line_end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_pages_and_citations() {
+        let pages = pdf_pages("Intro\n\u{c}\n\u{c}Results\nTable 1\n\u{c}");
+        assert_eq!(
+            pages,
+            vec![
+                (1, "Intro".to_string()),
+                (3, "Results\nTable 1".to_string())
+            ]
+        );
+        assert_eq!(citation("/d/r.pdf#page=3", 1, 2), "/d/r.pdf, page 3");
+        assert_eq!(citation("/d/notes.md", 10, 24), "/d/notes.md, lines 10-24");
+        assert_eq!(
+            paged_text("Intro\n\u{c}\n\u{c}Results\n\u{c}"),
+            "--- Page 1 ---\nIntro\n\n--- Page 3 ---\nResults\n"
+        );
+    }
+
+    #[test]
+    fn test_docx_xml_text() {
+        let xml = r#"<w:body><w:p><w:r><w:t>Terms &amp; conditions</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">Pay in </w:t><w:tab/><w:t>30 days</w:t></w:r></w:p></w:body>"#;
+        assert_eq!(docx_xml_text(xml), "Terms & conditions\nPay in \t30 days\n");
+        assert!(is_document(Path::new("Contract.DOCX")));
+        assert!(!is_document(Path::new("photo.png")));
+        assert!(needs_extraction(Path::new("scan.PDF")));
+        assert!(!needs_extraction(Path::new("notes.md")));
+    }
+}
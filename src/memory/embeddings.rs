@@ -1,7 +1,8 @@
 //! Embedding providers for semantic search
 //!
-//! Supports OpenAI embeddings API, local embeddings via fastembed (ONNX),
-//! and optional GGUF embeddings via llama.cpp (requires `gguf` feature).
+//! Supports OpenAI embeddings API, Ollama embedding models, local embeddings
+//! via fastembed (ONNX), and optional GGUF embeddings via llama.cpp (requires
+//! `gguf` feature).
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -137,6 +138,102 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
     }
 }
 
+// ============================================================================
+// Ollama Embedding Provider - embedding models served by a local Ollama
+// ============================================================================
+
+/// Default Ollama embedding model
+pub const DEFAULT_OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// Ollama embedding provider (`/api/embeddings`)
+pub struct OllamaEmbeddingProvider {
+    client: Client,
+    endpoint: String,
+    model: String,
+    dimensions: usize,
+}
+
+impl OllamaEmbeddingProvider {
+    pub fn new(endpoint: &str, model: &str) -> Result<Self> {
+        let dimensions = match model.split(':').next().unwrap_or(model) {
+            "all-minilm" => 384,
+            "mxbai-embed-large" | "snowflake-arctic-embed" | "bge-m3" | "bge-large" => 1024,
+            _ => 768, // nomic-embed-text and most others
+        };
+
+        Ok(Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            dimensions,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct OllamaEmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddingProvider {
+    fn id(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let response = self
+            .client
+            .post(format!("{}/api/embeddings", self.endpoint))
+            .json(&OllamaEmbeddingRequest {
+                model: &self.model,
+                prompt: text,
+            })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error {}: {}", status, body);
+        }
+
+        let response: OllamaEmbeddingResponse = response.json().await?;
+        if response.embedding.is_empty() {
+            anyhow::bail!(
+                "Ollama returned no embedding; is {} an embedding model?",
+                self.model
+            );
+        }
+        Ok(normalize_embedding(response.embedding))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        debug!("Embedding {} texts with Ollama {}", texts.len(), self.model);
+
+        // /api/embeddings takes one prompt per request
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
 /// Normalize embedding to unit vector
 pub fn normalize_embedding(mut vec: Vec<f32>) -> Vec<f32> {
     let magnitude: f32 = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -527,6 +624,49 @@ mod tests {
         assert!(cosine_similarity(&a, &c).abs() < 1e-6);
     }
 
+    #[tokio::test]
+    async fn test_ollama_embeddings() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers every request with the same unnormalized vector
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0u8; 4096];
+                let n = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..n]).to_string();
+                assert!(request.starts_with("POST /api/embeddings"));
+                assert!(request.contains(r#""model":"nomic-embed-text""#));
+                let body = r#"{"embedding":[3.0,4.0]}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let provider = OllamaEmbeddingProvider::new(
+            &format!("http://{}/", addr),
+            DEFAULT_OLLAMA_EMBEDDING_MODEL,
+        )
+        .unwrap();
+        assert_eq!(provider.dimensions(), 768);
+        let embeddings = provider
+            .embed_batch(&["first".to_string(), "second".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![0.6, 0.8], vec![0.6, 0.8]]);
+
+        let large =
+            OllamaEmbeddingProvider::new("http://localhost:11434", "mxbai-embed-large:335m");
+        assert_eq!(large.unwrap().dimensions(), 1024);
+    }
+
     #[test]
     fn test_serialize_deserialize() {
         let embedding = vec![0.1, 0.2, 0.3];
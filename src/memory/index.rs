@@ -106,6 +106,8 @@ impl MemoryIndex {
         // Ensure source column exists on older tables
         Self::ensure_column(&conn, "files", "source", "TEXT NOT NULL DEFAULT 'memory'")?;
         Self::ensure_column(&conn, "chunks", "source", "TEXT NOT NULL DEFAULT 'memory'")?;
+        // When a search last returned the file (`memory.archive_after_days`)
+        Self::ensure_column(&conn, "files", "last_hit", "INTEGER")?;
 
         // Try to load sqlite-vec extension for fast vector search
         let has_vec_extension = Self::try_load_sqlite_vec(&conn);
@@ -238,8 +240,21 @@ impl MemoryIndex {
         // Delete existing chunks and their FTS entries
         Self::delete_chunks_for_path(&conn, &relative_path)?;
 
-        // Create new chunks (OpenClaw-compatible)
-        let chunks = chunk_text(&content, self.chunk_size, self.chunk_overlap);
+        self.insert_chunks(&conn, &relative_path, "memory", &content, now)?;
+
+        Ok(true)
+    }
+
+    /// Chunk `content` and store it under `path` (OpenClaw-compatible)
+    fn insert_chunks(
+        &self,
+        conn: &Connection,
+        path: &str,
+        source: &str,
+        content: &str,
+        now: i64,
+    ) -> Result<()> {
+        let chunks = chunk_text(content, self.chunk_size, self.chunk_overlap);
 
         for chunk in chunks.iter() {
             let chunk_id = Uuid::new_v4().to_string();
@@ -247,16 +262,16 @@ impl MemoryIndex {
 
             conn.execute(
                 r#"INSERT INTO chunks (id, path, source, start_line, end_line, hash, model, text, embedding, updated_at)
-                   VALUES (?1, ?2, 'memory', ?3, ?4, ?5, '', ?6, '', ?7)"#,
-                params![&chunk_id, &relative_path, chunk.line_start, chunk.line_end, &chunk_hash, &chunk.content, now],
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, '', ?7, '', ?8)"#,
+                params![&chunk_id, path, source, chunk.line_start, chunk.line_end, &chunk_hash, &chunk.content, now],
             )?;
 
             // Insert into FTS
             Self::insert_fts(
-                &conn,
+                conn,
                 &chunk_id,
-                &relative_path,
-                "memory",
+                path,
+                source,
                 "",
                 chunk.line_start,
                 chunk.line_end,
@@ -264,7 +279,79 @@ impl MemoryIndex {
             )?;
         }
 
-        Ok(true)
+        Ok(())
+    }
+
+    /// Hash recorded for an indexed file, None when it isn't indexed
+    pub fn indexed_hash(&self, path: &str) -> Result<Option<String>> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+        Ok(conn
+            .query_row(
+                "SELECT hash FROM files WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .ok())
+    }
+
+    /// Replace a document's chunks with `sections`: (chunk path, text)
+    /// pairs, one per page of a PDF (`<path>#page=<n>`) or just `path`
+    pub fn index_sections(
+        &self,
+        path: &str,
+        hash: &str,
+        mtime: i64,
+        size: i64,
+        sections: &[(String, String)],
+    ) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+
+        Self::delete_document_chunks(&conn, path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO files (path, source, hash, mtime, size) VALUES (?1, 'document', ?2, ?3, ?4)",
+            params![path, hash, mtime, size],
+        )?;
+        for (section, text) in sections {
+            self.insert_chunks(&conn, section, "document", text, now)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a document indexed with `index_sections`
+    pub fn remove_document(&self, path: &str) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+
+        Self::delete_document_chunks(&conn, path)?;
+        conn.execute("DELETE FROM files WHERE path = ?1", params![path])?;
+        Ok(())
+    }
+
+    /// Delete the chunks of `path` and of its `#page=` sections
+    fn delete_document_chunks(conn: &Connection, path: &str) -> Result<()> {
+        let pages = format!("{}#page=", path);
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT path FROM chunks WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+        )?;
+        let sections: Vec<String> = stmt
+            .query_map(params![path, &pages], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        for section in sections {
+            Self::delete_chunks_for_path(conn, &section)?;
+        }
+        Ok(())
     }
 
     /// Delete chunks for a path and their FTS entries
@@ -299,6 +386,66 @@ impl MemoryIndex {
         Ok(())
     }
 
+    /// (path, hash, mtime, size) of every indexed file
+    pub fn file_metadata(&self) -> Result<Vec<(String, String, i64, i64)>> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+
+        let mut stmt = conn.prepare("SELECT path, hash, mtime, size FROM files ORDER BY path")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+
+        let mut files = Vec::new();
+        for row in rows {
+            files.push(row?);
+        }
+        Ok(files)
+    }
+
+    /// Note that a search just returned chunks of these files
+    pub fn record_hits(&self, paths: &[&str]) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        for path in paths {
+            conn.execute(
+                "UPDATE files SET last_hit = ?1 WHERE path = ?2",
+                params![now, path],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Indexed files under `prefix` that were neither modified nor returned
+    /// by a search since `cutoff` (seconds since the epoch)
+    pub fn stale_files(&self, prefix: &str, cutoff: i64) -> Result<Vec<String>> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow!("Lock poisoned: {}", e))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT path FROM files
+             WHERE substr(path, 1, length(?1)) = ?1
+               AND MAX(mtime, COALESCE(last_hit, 0)) < ?2
+             ORDER BY path",
+        )?;
+        let rows = stmt.query_map(params![prefix, cutoff], |row| row.get(0))?;
+
+        let mut paths = Vec::new();
+        for row in rows {
+            paths.push(row?);
+        }
+        Ok(paths)
+    }
+
     /// Get all indexed file paths
     pub fn indexed_files(&self) -> Result<Vec<String>> {
         let conn = self
@@ -1064,4 +1211,51 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_document_sections() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let index = MemoryIndex::new(temp_dir.path())?;
+        let sections = vec![
+            (
+                "/docs/a.pdf#page=1".to_string(),
+                "Quarterly revenue".to_string(),
+            ),
+            ("/docs/a.pdf#page=2".to_string(), "Hiring plan".to_string()),
+        ];
+        index.index_sections("/docs/a.pdf", "h1", 0, 10, &sections)?;
+        let backup = vec![("/docs/a.pdf.bak".to_string(), "Old revenue".to_string())];
+        index.index_sections("/docs/a.pdf.bak", "h2", 0, 10, &backup)?;
+
+        assert_eq!(index.indexed_hash("/docs/a.pdf")?.as_deref(), Some("h1"));
+        let results = index.search("hiring", 10)?;
+        assert_eq!(results[0].file, "/docs/a.pdf#page=2");
+
+        index.remove_document("/docs/a.pdf")?;
+        assert_eq!(index.indexed_hash("/docs/a.pdf")?, None);
+        assert!(index.search("hiring", 10)?.is_empty());
+        // Only that document's pages are gone
+        assert_eq!(index.chunk_count()?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let index = MemoryIndex::new(temp_dir.path())?;
+        for path in ["memory/2020-01-01.md", "memory/2020-01-02.md", "MEMORY.md"] {
+            let sections = vec![(path.to_string(), "Old notes".to_string())];
+            index.index_sections(path, "h", 100, 10, &sections)?;
+        }
+
+        index.record_hits(&["memory/2020-01-02.md"])?;
+        assert_eq!(
+            index.stale_files("memory/", 1000)?,
+            vec!["memory/2020-01-01.md".to_string()]
+        );
+        assert!(index.stale_files("memory/", 50)?.is_empty());
+
+        Ok(())
+    }
 }
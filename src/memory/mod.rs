@@ -1,33 +1,52 @@
+mod documents;
 mod embeddings;
 mod index;
+mod policy;
 mod search;
+mod transfer;
 mod watcher;
 mod workspace;
 
+pub use documents::{
+    citation, document_text, is_document, needs_extraction, paged_text, DocumentIndexStats,
+    DOCUMENT_EXTENSIONS,
+};
 #[cfg(feature = "gguf")]
 pub use embeddings::LlamaCppProvider;
-pub use embeddings::{hash_text, EmbeddingProvider, FastEmbedProvider, OpenAIEmbeddingProvider};
+pub use embeddings::{
+    hash_text, EmbeddingProvider, FastEmbedProvider, OllamaEmbeddingProvider,
+    OpenAIEmbeddingProvider,
+};
 pub use index::{MemoryIndex, ReindexStats};
 pub use search::MemoryChunk;
-pub use watcher::MemoryWatcher;
+pub use transfer::{ExportedDocument, ExportedFile, ImportStats, MemoryExport, EXPORT_VERSION};
+pub use watcher::{DocumentWatcher, MemoryWatcher};
 pub use workspace::{init_state_dir, init_workspace};
 
 use anyhow::Result;
 use chrono::Local;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::runtime::Handle;
 use tracing::{debug, info, warn};
 
 use crate::config::{Config, MemoryConfig};
+use embeddings::cosine_similarity;
+
+/// Chunks sent to the embedding provider per request
+const EMBEDDING_BATCH_SIZE: usize = 50;
 
 #[derive(Clone)]
 pub struct MemoryManager {
     workspace: PathBuf,
+    /// Agent this memory belongs to (names the index database)
+    agent_id: String,
     db_path: PathBuf,
     index: MemoryIndex,
+    /// Files added with `localgpt index`, searched by `document_search`
+    documents: MemoryIndex,
     config: MemoryConfig,
     /// Optional embedding provider for semantic search
     embedding_provider: Option<Arc<dyn EmbeddingProvider>>,
@@ -91,6 +110,11 @@ impl MemoryManager {
 
         let index = MemoryIndex::new_with_db_path(&workspace, &db_path)?
             .with_chunk_config(memory_config.chunk_size, memory_config.chunk_overlap);
+        let documents = MemoryIndex::new_with_db_path(
+            &workspace,
+            &memory_dir.join(format!("{}.documents.sqlite", agent_id)),
+        )?
+        .with_chunk_config(memory_config.chunk_size, memory_config.chunk_overlap);
 
         // Create embedding provider based on config
         let embedding_provider: Option<Arc<dyn EmbeddingProvider>> = match memory_config
@@ -148,6 +172,29 @@ impl MemoryManager {
                     None
                 }
             }
+            "ollama" => {
+                let endpoint = app_config
+                    .and_then(|c| c.providers.ollama.as_ref())
+                    .map(|o| o.endpoint.as_str())
+                    .unwrap_or("http://localhost:11434");
+                let model = if memory_config.embedding_model.is_empty()
+                    || memory_config.embedding_model == "all-MiniLM-L6-v2"
+                {
+                    embeddings::DEFAULT_OLLAMA_EMBEDDING_MODEL
+                } else {
+                    memory_config.embedding_model.as_str()
+                };
+                match OllamaEmbeddingProvider::new(endpoint, model) {
+                    Ok(provider) => {
+                        info!("Using Ollama embedding provider: {}", provider.model());
+                        Some(Arc::new(provider))
+                    }
+                    Err(e) => {
+                        warn!("Failed to initialize Ollama embeddings: {}. Falling back to FTS-only search.", e);
+                        None
+                    }
+                }
+            }
             #[cfg(feature = "gguf")]
             "gguf" => {
                 let cache_dir = if memory_config.embedding_cache_dir.is_empty() {
@@ -186,15 +233,17 @@ impl MemoryManager {
 
         Ok(Self {
             workspace,
+            agent_id: agent_id.to_string(),
             db_path,
             index,
+            documents,
             config: memory_config.clone(),
             embedding_provider,
             is_brand_new,
         })
     }
 
-    /// Set embedding provider for semantic search (requires OpenAI API key)
+    /// Replace the configured embedding provider
     pub fn with_embedding_provider(mut self, provider: Arc<dyn EmbeddingProvider>) -> Self {
         self.embedding_provider = Some(provider);
         self
@@ -209,6 +258,10 @@ impl MemoryManager {
         &self.workspace
     }
 
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
     /// Read the main MEMORY.md file
     pub fn read_memory_file(&self) -> Result<String> {
         let path = self.workspace.join("MEMORY.md");
@@ -313,39 +366,424 @@ impl MemoryManager {
         Ok(content)
     }
 
-    /// Search memory using hybrid search (FTS + semantic if available)
-    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryChunk>> {
-        // If we have an embedding provider, try hybrid search
+    /// Search memory using hybrid search (FTS + semantic if available).
+    /// Chunks indexed since the last embedding pass are embedded first, so
+    /// newly written memory is found by meaning too.
+    /// With `memory.decay_half_life_days`, older dated notes rank lower.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryChunk>> {
+        let half_life = self.config.decay_half_life_days;
+        let mut results = if half_life > 0 {
+            // Extra candidates, since decay can reorder them
+            let mut results = self.search_index(&self.index, query, limit * 2).await?;
+            let today = Local::now().date_naive();
+            for chunk in &mut results {
+                chunk.score *= policy::decay_factor(&chunk.file, today, half_life);
+            }
+            results.sort_by(|a, b| b.score.total_cmp(&a.score));
+            results
+        } else {
+            self.search_index(&self.index, query, limit).await?
+        };
+        results.truncate(limit);
+
+        let mut files: Vec<&str> = results.iter().map(|c| c.file.as_str()).collect();
+        files.sort_unstable();
+        files.dedup();
+        if let Err(e) = self.index.record_hits(&files) {
+            debug!("Failed to record memory hits: {}", e);
+        }
+        Ok(results)
+    }
+
+    /// Search the documents added with `index_documents`, like `search`.
+    /// PDF passages come from `<path>#page=<n>`; see `citation`.
+    pub async fn search_documents(&self, query: &str, limit: usize) -> Result<Vec<MemoryChunk>> {
+        self.search_index(&self.documents, query, limit).await
+    }
+
+    async fn search_index(
+        &self,
+        index: &MemoryIndex,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryChunk>> {
         if let Some(ref provider) = self.embedding_provider {
-            // Try to get query embedding (may fail if no API key, rate limited, etc.)
-            if let Ok(handle) = Handle::try_current() {
-                let provider = provider.clone();
-                let query_string = query.to_string();
-                let model = provider.model().to_string();
-
-                // Run embedding in blocking context
-                let embedding_result = std::thread::spawn(move || {
-                    handle.block_on(async { provider.embed(&query_string).await })
-                })
-                .join()
-                .map_err(|_| anyhow::anyhow!("Thread panicked"))?;
-
-                if let Ok(embedding) = embedding_result {
+            if let Err(e) = self.embed_pending(index, EMBEDDING_BATCH_SIZE).await {
+                warn!("Failed to embed new chunks: {}", e);
+            }
+            // May fail without an API key, when rate limited, etc.
+            match provider.embed(query).await {
+                Ok(embedding) => {
                     debug!("Using hybrid search with {} dimensions", embedding.len());
-                    return self.index.search_hybrid(
+                    return index.search_hybrid(
                         query,
                         Some(&embedding),
-                        &model,
+                        provider.model(),
                         limit,
                         0.3, // FTS weight
                         0.7, // Vector weight
                     );
                 }
+                Err(e) => warn!("Query embedding failed, using keyword search: {}", e),
             }
         }
 
         // Fallback to FTS-only search
-        self.index.search(query, limit)
+        index.search(query, limit)
+    }
+
+    /// Index the documents at `path` (a file or a directory) for
+    /// `search_documents`. Unchanged files are skipped unless `force`;
+    /// indexed files under a directory that are gone are removed.
+    pub async fn index_documents(&self, path: &Path, force: bool) -> Result<DocumentIndexStats> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        let mut stats = DocumentIndexStats::default();
+
+        for file in documents::find_documents(&path) {
+            let key = file.to_string_lossy().to_string();
+            let bytes = match fs::read(&file) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    stats.skipped.push((file, e.to_string()));
+                    continue;
+                }
+            };
+            let hash = format!("{:x}", Sha256::digest(&bytes));
+            if !force && self.documents.indexed_hash(&key)?.as_deref() == Some(hash.as_str()) {
+                stats.unchanged += 1;
+                continue;
+            }
+            match documents::extract_sections(&file).await {
+                Ok(sections) => {
+                    let mtime = fs::metadata(&file)?
+                        .modified()?
+                        .duration_since(std::time::UNIX_EPOCH)?
+                        .as_secs() as i64;
+                    self.documents.index_sections(
+                        &key,
+                        &hash,
+                        mtime,
+                        bytes.len() as i64,
+                        &sections,
+                    )?;
+                    stats.indexed += 1;
+                }
+                Err(e) => stats.skipped.push((file, e.to_string())),
+            }
+        }
+
+        if path.is_dir() {
+            for key in self.documents.indexed_files()? {
+                if Path::new(&key).starts_with(&path) && !Path::new(&key).exists() {
+                    self.documents.remove_document(&key)?;
+                    stats.removed += 1;
+                }
+            }
+        }
+
+        self.embed_pending(&self.documents, EMBEDDING_BATCH_SIZE)
+            .await?;
+        Ok(stats)
+    }
+
+    /// Drop the indexed documents at or under `path`, e.g. after it was
+    /// deleted. Returns how many were removed.
+    pub fn remove_documents(&self, path: &Path) -> Result<usize> {
+        let mut removed = 0;
+        for key in self.documents.indexed_files()? {
+            if Path::new(&key).starts_with(path) {
+                self.documents.remove_document(&key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The `[memory] document_dirs`, with `~` expanded and relative paths
+    /// under the workspace
+    pub fn document_dirs(&self) -> Vec<PathBuf> {
+        self.config
+            .document_dirs
+            .iter()
+            .map(|dir| {
+                let dir = PathBuf::from(shellexpand::tilde(dir).to_string());
+                if dir.is_absolute() {
+                    dir
+                } else {
+                    self.workspace.join(dir)
+                }
+            })
+            .collect()
+    }
+
+    /// Number of documents added with `index_documents`
+    pub fn document_count(&self) -> Result<usize> {
+        Ok(self.documents.indexed_files()?.len())
+    }
+
+    /// Append `text` to MEMORY.md as a bullet and index and embed it right
+    /// away. A bullet it nearly repeats (`memory.dedup_similarity`) is
+    /// replaced instead. Returns the line it was written on and the text
+    /// it replaced.
+    pub async fn remember(&self, text: &str) -> Result<(usize, Option<String>)> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            anyhow::bail!("Nothing to remember");
+        }
+
+        let path = self.workspace.join("MEMORY.md");
+        let mut content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let (line, replaced) = match self.near_duplicate(&text, &content).await {
+            Some((index, old)) => {
+                content = policy::replace_line(&content, index, &format!("- {}", text));
+                (index + 1, Some(old))
+            }
+            None => {
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push_str(&format!("- {}\n", text));
+                (content.lines().count(), None)
+            }
+        };
+        fs::write(&path, &content)?;
+
+        self.index.index_file(&path, false)?;
+        if let Err(e) = self.generate_embeddings(EMBEDDING_BATCH_SIZE).await {
+            warn!("Failed to embed saved memory: {}", e);
+        }
+        Ok((line, replaced))
+    }
+
+    /// Add facts about the user to USER.md (part of every system prompt),
+    /// under a "Remembered facts" heading, skipping ones it already has. A
+    /// fact that nearly repeats a bullet (`memory.dedup_similarity`)
+    /// replaces it. Returns the facts that were written.
+    pub async fn add_user_facts(&self, facts: &[String]) -> Result<Vec<String>> {
+        let path = self.workspace.join("USER.md");
+        let mut content = self.read_user_file()?;
+        let mut added = Vec::new();
+        for fact in facts {
+            let (merged, new) = merge_user_facts(&content, std::slice::from_ref(fact));
+            let Some(fact) = new.into_iter().next() else {
+                continue;
+            };
+            content = match self.near_duplicate(&fact, &content).await {
+                Some((index, _)) => policy::replace_line(&content, index, &format!("- {}", fact)),
+                None => merged,
+            };
+            added.push(fact);
+        }
+        if !added.is_empty() {
+            fs::write(&path, content)?;
+            self.index.index_file(&path, false)?;
+        }
+        Ok(added)
+    }
+
+    /// The bullet in `content` that `text` nearly repeats, as (line index,
+    /// text): the most similar one at or above `memory.dedup_similarity`,
+    /// by embeddings, or by word overlap without an embedding provider
+    async fn near_duplicate(&self, text: &str, content: &str) -> Option<(usize, String)> {
+        let threshold = self.config.dedup_similarity;
+        let bullets = policy::bullet_lines(content);
+        if threshold <= 0.0 || bullets.is_empty() {
+            return None;
+        }
+
+        let mut texts = vec![text.to_string()];
+        texts.extend(bullets.iter().map(|(_, bullet)| bullet.clone()));
+        let scores: Vec<f32> = match self.embed_cached(&texts).await {
+            Ok(Some(embeddings)) => embeddings[1..]
+                .iter()
+                .map(|e| cosine_similarity(&embeddings[0], e))
+                .collect(),
+            other => {
+                if let Err(e) = other {
+                    debug!("Embedding for deduplication failed: {}", e);
+                }
+                bullets
+                    .iter()
+                    .map(|(_, bullet)| policy::word_similarity(text, bullet))
+                    .collect()
+            }
+        };
+
+        scores
+            .into_iter()
+            .zip(bullets)
+            .filter(|(score, _)| *score >= threshold)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, bullet)| bullet)
+    }
+
+    /// Embeddings for `texts`, from the embedding cache where it has them;
+    /// None without an embedding provider
+    async fn embed_cached(&self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        let Some(ref provider) = self.embedding_provider else {
+            return Ok(None);
+        };
+        let (provider_id, model) = (provider.id(), provider.model());
+
+        let mut embeddings: Vec<Option<Vec<f32>>> = texts
+            .iter()
+            .map(|text| {
+                self.index
+                    .get_cached_embedding(provider_id, model, &hash_text(text))
+                    .ok()
+                    .flatten()
+            })
+            .collect();
+        let missing: Vec<usize> = (0..texts.len())
+            .filter(|&i| embeddings[i].is_none())
+            .collect();
+        if !missing.is_empty() {
+            let batch: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+            for (i, embedding) in missing.into_iter().zip(provider.embed_batch(&batch).await?) {
+                let hash = hash_text(&texts[i]);
+                if let Err(e) =
+                    self.index
+                        .cache_embedding(provider_id, model, "", &hash, &embedding)
+                {
+                    debug!("Failed to cache embedding: {}", e);
+                }
+                embeddings[i] = Some(embedding);
+            }
+        }
+        Ok(Some(
+            embeddings
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect(),
+        ))
+    }
+
+    /// Move dated notes (`memory/YYYY-MM-DD*.md`) that were neither modified
+    /// nor returned by a search in `memory.archive_after_days` out of the
+    /// workspace, to `<state dir>/memory/archive/<agent>/`. Returns where
+    /// they went.
+    pub fn archive_stale(&self) -> Result<Vec<PathBuf>> {
+        let days = self.config.archive_after_days;
+        if days == 0 {
+            return Ok(Vec::new());
+        }
+        let cutoff = chrono::Utc::now().timestamp() - i64::from(days) * 86_400;
+        let archive_dir = self
+            .db_path
+            .parent()
+            .unwrap_or(&self.workspace)
+            .join("archive")
+            .join(&self.agent_id);
+
+        let mut archived = Vec::new();
+        for relative in self.index.stale_files("memory/", cutoff)? {
+            if policy::note_date(&relative).is_none() {
+                continue;
+            }
+            let source = self.workspace.join(&relative);
+            let target = archive_dir.join(relative.trim_start_matches("memory/"));
+            if target.exists() {
+                warn!("Not archiving {}: {} exists", relative, target.display());
+                continue;
+            }
+            if source.exists() {
+                fs::create_dir_all(&archive_dir)?;
+                if fs::rename(&source, &target).is_err() {
+                    // Across filesystems
+                    fs::copy(&source, &target)?;
+                    fs::remove_file(&source)?;
+                }
+            }
+            self.index.remove_file(&relative)?;
+            info!("Archived {} to {}", relative, target.display());
+            archived.push(target);
+        }
+        Ok(archived)
+    }
+
+    /// Everything needed to rebuild this memory on another machine: the
+    /// workspace's Markdown files and the indexed documents' paths
+    pub fn export(&self) -> Result<MemoryExport> {
+        let documents = self
+            .documents
+            .file_metadata()?
+            .into_iter()
+            .map(|(path, hash, mtime, size)| ExportedDocument {
+                path,
+                hash,
+                mtime,
+                size,
+            })
+            .collect();
+        Ok(MemoryExport {
+            version: EXPORT_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            agent_id: self.agent_id.clone(),
+            files: transfer::workspace_files(&self.workspace)?,
+            documents,
+        })
+    }
+
+    /// Write an export's files into the workspace, then reindex and embed
+    /// them and index its documents that exist here. Files that differ from
+    /// the export are kept unless `overwrite` (or the workspace was only
+    /// just created, so they are templates).
+    pub async fn import(&self, export: &MemoryExport, overwrite: bool) -> Result<ImportStats> {
+        if export.version > EXPORT_VERSION {
+            anyhow::bail!(
+                "Export version {} is newer than this LocalGPT supports ({})",
+                export.version,
+                EXPORT_VERSION
+            );
+        }
+        // Check every path before writing anything
+        let targets = export
+            .files
+            .iter()
+            .map(|file| transfer::import_path(&self.workspace, &file.path))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stats = ImportStats::default();
+        for (file, target) in export.files.iter().zip(targets) {
+            match fs::read_to_string(&target) {
+                Ok(existing) if existing == file.content => stats.unchanged += 1,
+                Ok(_) if !overwrite && !self.is_brand_new => stats.kept.push(file.path.clone()),
+                _ => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&target, &file.content)?;
+                    stats.written += 1;
+                }
+            }
+        }
+
+        self.reindex(false)?;
+        if let Err(e) = self.generate_embeddings(EMBEDDING_BATCH_SIZE).await {
+            warn!("Failed to embed imported memory: {}", e);
+        }
+
+        for document in &export.documents {
+            let path = Path::new(&document.path);
+            if !path.exists() {
+                stats.documents_missing.push(document.path.clone());
+                continue;
+            }
+            match self.index_documents(path, false).await {
+                Ok(indexed) => stats.documents_indexed += indexed.indexed + indexed.unchanged,
+                Err(e) => {
+                    warn!("Failed to index {}: {}", document.path, e);
+                    stats.documents_missing.push(document.path.clone());
+                }
+            }
+        }
+        Ok(stats)
     }
 
     /// Search memory using FTS only (faster, no API calls)
@@ -589,10 +1027,28 @@ impl MemoryManager {
         )
     }
 
+    /// Start keeping the `[memory] document_dirs` indexed; None when there
+    /// are none. The caller runs `DocumentWatcher::run` on its runtime.
+    pub fn start_document_watcher(self: &Arc<Self>) -> Result<Option<DocumentWatcher>> {
+        let dirs = self.document_dirs();
+        if dirs.is_empty() {
+            return Ok(None);
+        }
+        DocumentWatcher::new(Arc::clone(self), dirs).map(Some)
+    }
+
     /// Generate embeddings for chunks that don't have them
     /// Returns (chunks_processed, chunks_embedded)
     /// Uses embedding cache to avoid regenerating identical content
     pub async fn generate_embeddings(&self, batch_size: usize) -> Result<(usize, usize)> {
+        self.embed_pending(&self.index, batch_size).await
+    }
+
+    async fn embed_pending(
+        &self,
+        index: &MemoryIndex,
+        batch_size: usize,
+    ) -> Result<(usize, usize)> {
         let provider = match &self.embedding_provider {
             Some(p) => p,
             None => {
@@ -609,7 +1065,7 @@ impl MemoryManager {
 
         loop {
             // Get chunks without embeddings
-            let chunks = self.index.chunks_without_embeddings(batch_size)?;
+            let chunks = index.chunks_without_embeddings(batch_size)?;
             if chunks.is_empty() {
                 break;
             }
@@ -625,8 +1081,7 @@ impl MemoryManager {
 
                 // Check cache first
                 if let Ok(Some(cached)) =
-                    self.index
-                        .get_cached_embedding(&provider_id, &model, &text_hash)
+                    index.get_cached_embedding(&provider_id, &model, &text_hash)
                 {
                     from_cache.push((chunk_id.clone(), cached));
                     cache_hits += 1;
@@ -637,7 +1092,7 @@ impl MemoryManager {
 
             // Store cached embeddings
             for (chunk_id, embedding) in from_cache {
-                if let Err(e) = self.index.store_embedding(&chunk_id, &embedding, &model) {
+                if let Err(e) = index.store_embedding(&chunk_id, &embedding, &model) {
                     warn!(
                         "Failed to store cached embedding for chunk {}: {}",
                         chunk_id, e
@@ -657,15 +1112,14 @@ impl MemoryManager {
                             to_embed.iter().zip(embeddings.iter())
                         {
                             // Store in chunk
-                            if let Err(e) = self.index.store_embedding(chunk_id, embedding, &model)
-                            {
+                            if let Err(e) = index.store_embedding(chunk_id, embedding, &model) {
                                 warn!("Failed to store embedding for chunk {}: {}", chunk_id, e);
                             } else {
                                 total_embedded += 1;
                             }
 
                             // Store in cache for future reuse
-                            if let Err(e) = self.index.cache_embedding(
+                            if let Err(e) = index.cache_embedding(
                                 &provider_id,
                                 &model,
                                 "", // provider_key (API key identifier, can be empty)
@@ -712,3 +1166,85 @@ impl MemoryManager {
         self.index.embedded_chunk_count(&model)
     }
 }
+
+const USER_FACTS_HEADING: &str = "## Remembered facts";
+
+/// USER.md with the new `facts` appended as bullets under
+/// `USER_FACTS_HEADING`, and the facts that were new
+fn merge_user_facts(content: &str, facts: &[String]) -> (String, Vec<String>) {
+    let normalize = |s: &str| {
+        s.trim_start_matches("- ")
+            .trim()
+            .trim_end_matches('.')
+            .to_lowercase()
+    };
+    let mut known: Vec<String> = content.lines().map(normalize).collect();
+    let mut added = Vec::new();
+    for fact in facts {
+        let fact = fact.split_whitespace().collect::<Vec<_>>().join(" ");
+        let key = normalize(&fact);
+        if key.is_empty() || known.contains(&key) {
+            continue;
+        }
+        known.push(key);
+        added.push(fact);
+    }
+    if added.is_empty() {
+        return (content.to_string(), added);
+    }
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let bullets = added.iter().map(|fact| format!("- {}", fact));
+    match lines.iter().position(|l| l.trim() == USER_FACTS_HEADING) {
+        Some(heading) => {
+            // After the section's last bullet
+            let mut end = heading + 1;
+            while end < lines.len() && !lines[end].starts_with('#') {
+                end += 1;
+            }
+            while end > heading + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            for (i, bullet) in bullets.enumerate() {
+                lines.insert(end + i, bullet);
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(USER_FACTS_HEADING.to_string());
+            lines.push(String::new());
+            lines.extend(bullets);
+        }
+    }
+    (format!("{}\n", lines.join("\n")), added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_user_facts() {
+        let facts = vec![
+            "Prefers metric units".to_string(),
+            "Works on   the Atlas project.".to_string(),
+        ];
+        let (content, added) = merge_user_facts("# User\n\nName: Sam\n", &facts);
+        assert_eq!(added.len(), 2);
+        assert_eq!(
+            content,
+            "# User\n\nName: Sam\n\n## Remembered facts\n\n- Prefers metric units\n- Works on the Atlas project.\n"
+        );
+
+        // Known facts are skipped; new ones go under the existing heading
+        let facts = vec![
+            "prefers metric units.".to_string(),
+            "Uses Neovim".to_string(),
+        ];
+        let (content, added) = merge_user_facts(&format!("{}\n## Other\n", content), &facts);
+        assert_eq!(added, vec!["Uses Neovim".to_string()]);
+        assert!(content.contains("- Works on the Atlas project.\n- Uses Neovim\n\n## Other"));
+    }
+}
@@ -0,0 +1,108 @@
+//! Policies that keep memory useful as it grows (`[memory]` settings)
+//!
+//! - `dedup_similarity`: a saved memory close enough to an existing bullet
+//!   replaces it instead of piling up next to it
+//! - `decay_half_life_days`: dated notes (`memory/YYYY-MM-DD*.md`) rank
+//!   lower in search as they age; MEMORY.md, USER.md and other files don't
+//! - `archive_after_days`: dated notes nobody has modified or found in a
+//!   search for that long move out of the workspace
+
+use chrono::NaiveDate;
+use std::collections::HashSet;
+
+/// Date of a dated note: a file directly under `memory/` whose name starts
+/// with `YYYY-MM-DD` (daily logs and saved sessions)
+pub fn note_date(path: &str) -> Option<NaiveDate> {
+    let name = path.strip_prefix("memory/")?;
+    if name.contains('/') {
+        return None;
+    }
+    NaiveDate::parse_from_str(name.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Search score multiplier for a chunk of `path`: halves every
+/// `half_life_days` of a dated note's age, 1.0 for everything else
+pub fn decay_factor(path: &str, today: NaiveDate, half_life_days: u32) -> f64 {
+    match note_date(path) {
+        Some(date) if half_life_days > 0 => {
+            let age = (today - date).num_days().max(0) as f64;
+            0.5f64.powf(age / half_life_days as f64)
+        }
+        _ => 1.0,
+    }
+}
+
+/// Overlap of the two texts' words (Jaccard), for near-duplicate checks
+/// without an embedding provider
+pub fn word_similarity(a: &str, b: &str) -> f32 {
+    let words = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f32 / a.union(&b).count() as f32
+}
+
+/// The `- ` bullets in a memory file, as (line index, text)
+pub fn bullet_lines(content: &str) -> Vec<(usize, String)> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let text = line.trim_start().strip_prefix("- ")?.trim();
+            (!text.is_empty()).then(|| (i, text.to_string()))
+        })
+        .collect()
+}
+
+/// `content` with line `index` replaced by `line`
+pub fn replace_line(content: &str, index: usize, line: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    if let Some(old) = lines.get_mut(index) {
+        *old = line;
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decay_only_applies_to_dated_notes() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 31).unwrap();
+        assert_eq!(
+            note_date("memory/2025-03-01-trip-plans.md"),
+            NaiveDate::from_ymd_opt(2025, 3, 1)
+        );
+        assert_eq!(note_date("MEMORY.md"), None);
+        assert_eq!(note_date("memory/notes/2025-03-01.md"), None);
+
+        assert!((decay_factor("memory/2025-03-01.md", today, 30) - 0.5).abs() < 1e-9);
+        assert_eq!(decay_factor("memory/2025-03-01.md", today, 0), 1.0);
+        assert_eq!(decay_factor("MEMORY.md", today, 30), 1.0);
+    }
+
+    #[test]
+    fn test_bullets_and_similarity() {
+        let content = "# Memory\n\n- Prefers metric units\n-   \n  - Uses Neovim\n";
+        assert_eq!(
+            bullet_lines(content),
+            vec![
+                (2, "Prefers metric units".to_string()),
+                (4, "Uses Neovim".to_string())
+            ]
+        );
+        assert_eq!(
+            replace_line(content, 2, "- Prefers metric units, except for cooking"),
+            "# Memory\n\n- Prefers metric units, except for cooking\n-   \n  - Uses Neovim\n"
+        );
+        assert_eq!(word_similarity("Uses Neovim.", "uses neovim"), 1.0);
+        assert!(word_similarity("Prefers metric units", "Uses Neovim") < 0.1);
+    }
+}
@@ -0,0 +1,125 @@
+//! Portable JSON export of an agent's memory (`localgpt memory export` /
+//! `import`), for backups and moving to another machine
+//!
+//! An export holds every Markdown file in the workspace and the list of
+//! documents added with `localgpt index` (paths and hashes, not their
+//! contents). Indexes and embeddings are rebuilt on import, so the other
+//! machine may use a different embedding model.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Format version written by `MemoryManager::export`
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryExport {
+    pub version: u32,
+    /// RFC 3339
+    pub exported_at: String,
+    pub agent_id: String,
+    /// Markdown files, relative to the workspace
+    pub files: Vec<ExportedFile>,
+    #[serde(default)]
+    pub documents: Vec<ExportedDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// An indexed document; its text is read again from `path` on import
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedDocument {
+    pub path: String,
+    /// SHA-256 of the file
+    pub hash: String,
+    pub mtime: i64,
+    pub size: i64,
+}
+
+/// What `MemoryManager::import` did
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    pub written: usize,
+    pub unchanged: usize,
+    /// Existing files that differ from the export, left alone
+    pub kept: Vec<String>,
+    pub documents_indexed: usize,
+    /// Documents not found at their exported path
+    pub documents_missing: Vec<String>,
+}
+
+/// The Markdown files under `workspace`, with paths relative to it
+pub fn workspace_files(workspace: &Path) -> Result<Vec<ExportedFile>> {
+    let pattern = format!("{}/**/*.md", workspace.display());
+    let mut files = Vec::new();
+    for path in glob::glob(&pattern)?.filter_map(|r| r.ok()) {
+        if !path.is_file() {
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(workspace) else {
+            continue;
+        };
+        files.push(ExportedFile {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            content: fs::read_to_string(&path)?,
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Where an exported file goes in `workspace`. Absolute paths and `..` are
+/// refused, so an export can't write outside the workspace.
+pub fn import_path(workspace: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        anyhow::bail!(
+            "Refusing to import {}: not a path inside the workspace",
+            path
+        );
+    }
+    Ok(workspace.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_path_stays_in_workspace() {
+        let workspace = Path::new("/ws");
+        assert_eq!(
+            import_path(workspace, "memory/2025-01-02.md").unwrap(),
+            PathBuf::from("/ws/memory/2025-01-02.md")
+        );
+        assert!(import_path(workspace, "../.bashrc").is_err());
+        assert!(import_path(workspace, "/etc/passwd").is_err());
+        assert!(import_path(workspace, "memory/../../x.md").is_err());
+        assert!(import_path(workspace, "").is_err());
+    }
+
+    #[test]
+    fn test_workspace_files() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        fs::create_dir_all(temp.path().join("memory"))?;
+        fs::write(temp.path().join("MEMORY.md"), "- Uses Rust\n")?;
+        fs::write(temp.path().join("memory/2025-01-02.md"), "Notes")?;
+        fs::write(temp.path().join("image.png"), [0u8, 1])?;
+
+        let files = workspace_files(temp.path())?;
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["MEMORY.md", "memory/2025-01-02.md"]);
+        assert_eq!(files[0].content, "- Uses Rust\n");
+        Ok(())
+    }
+}
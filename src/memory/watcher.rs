@@ -1,15 +1,26 @@
-//! File system watcher for automatic memory reindexing
+//! File system watchers for automatic memory reindexing
+//!
+//! `MemoryWatcher` keeps the workspace's Markdown index current;
+//! `DocumentWatcher` does the same for the `[memory] document_dirs` behind
+//! `document_search`.
 
 use anyhow::Result;
+use notify::event::ModifyKind;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
-use super::MemoryIndex;
+use super::documents::is_document;
+use super::{MemoryIndex, MemoryManager};
 use crate::config::MemoryConfig;
 
+/// How long document changes have to settle before they are reindexed
+const DOCUMENT_DEBOUNCE: Duration = Duration::from_secs(2);
+
 pub struct MemoryWatcher {
     #[allow(dead_code)]
     watcher: RecommendedWatcher,
@@ -136,3 +147,138 @@ impl MemoryWatcher {
         })
     }
 }
+
+/// Keeps the document index in step with the configured document
+/// directories: changed files are re-extracted and re-embedded (unchanged
+/// content is skipped by hash), deleted ones dropped.
+pub struct DocumentWatcher {
+    #[allow(dead_code)]
+    watcher: RecommendedWatcher,
+    rx: tokio::sync::mpsc::UnboundedReceiver<PathBuf>,
+    memory: Arc<MemoryManager>,
+    dirs: Vec<PathBuf>,
+}
+
+impl DocumentWatcher {
+    pub fn new(memory: Arc<MemoryManager>, dirs: Vec<PathBuf>) -> Result<Self> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+            match res {
+                Ok(event) => {
+                    // Directories only matter when they appear, move or go away
+                    let dirs_too = matches!(
+                        event.kind,
+                        EventKind::Create(_)
+                            | EventKind::Modify(ModifyKind::Name(_))
+                            | EventKind::Remove(_)
+                    );
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) {
+                        for path in event.paths {
+                            if (dirs_too && !path.is_file()) || is_document(&path) {
+                                let _ = tx.send(path);
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Watch error: {:?}", e),
+            }
+        })?;
+
+        // Index keys are canonical paths, so watch the canonical directories
+        let mut watched = Vec::new();
+        for dir in dirs {
+            match dir.canonicalize() {
+                Ok(dir) => {
+                    watcher.watch(&dir, RecursiveMode::Recursive)?;
+                    info!("Watching documents in: {}", dir.display());
+                    watched.push(dir);
+                }
+                Err(e) => warn!("Skipping document directory {}: {}", dir.display(), e),
+            }
+        }
+
+        Ok(Self {
+            watcher,
+            rx,
+            memory,
+            dirs: watched,
+        })
+    }
+
+    /// Catch up with changes made while nothing was watching, then reindex
+    /// changed paths as they settle. Runs until its task is dropped.
+    pub async fn run(mut self) {
+        for dir in self.dirs.clone() {
+            self.sync(&dir).await;
+        }
+
+        while let Some(path) = self.rx.recv().await {
+            let mut changed = BTreeSet::from([path]);
+            loop {
+                match tokio::time::timeout(DOCUMENT_DEBOUNCE, self.rx.recv()).await {
+                    Ok(Some(path)) => {
+                        changed.insert(path);
+                    }
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+            for path in changed {
+                self.sync(&path).await;
+            }
+        }
+    }
+
+    async fn sync(&self, path: &Path) {
+        if is_scratch_file(path) {
+            return;
+        }
+        if !path.exists() {
+            match self.memory.remove_documents(path) {
+                Ok(0) => {}
+                Ok(n) => info!("Removed {} document(s) at {}", n, path.display()),
+                Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+            }
+            return;
+        }
+        match self.memory.index_documents(path, false).await {
+            Ok(stats) => {
+                if stats.indexed > 0 || stats.removed > 0 {
+                    info!(
+                        "Reindexed documents at {}: {} indexed, {} removed",
+                        path.display(),
+                        stats.indexed,
+                        stats.removed
+                    );
+                }
+                for (file, reason) in stats.skipped {
+                    debug!("Skipped {}: {}", file.display(), reason);
+                }
+            }
+            Err(e) => warn!("Failed to index {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Hidden files and editor lock/temp files (e.g. Word's `~$report.docx`)
+fn is_scratch_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy())
+        .is_some_and(|n| n.starts_with('.') || n.starts_with("~$"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_scratch_file() {
+        assert!(is_scratch_file(Path::new("/docs/~$report.docx")));
+        assert!(is_scratch_file(Path::new("/docs/.notes.md.swp")));
+        assert!(!is_scratch_file(Path::new("/docs/report.docx")));
+    }
+}
@@ -30,7 +30,7 @@ use tokio::sync::Mutex;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{debug, info};
 
-use crate::agent::{extract_tool_detail, Agent, AgentConfig, StreamEvent};
+use crate::agent::{extract_tool_detail, Agent, AgentConfig, ProviderToolEvent, StreamEvent};
 use crate::concurrency::{TurnGate, WorkspaceLock};
 use crate::config::Config;
 use crate::heartbeat::{get_last_heartbeat_event, HeartbeatStatus};
@@ -217,7 +217,7 @@ async fn load_persisted_sessions(state: &Arc<AppState>) -> Result<(), anyhow::Er
     for session_info in sessions_list.into_iter().take(MAX_SESSIONS) {
         let agent_config = AgentConfig {
             model: state.config.agent.default_model.clone(),
-            context_window: state.config.agent.context_window,
+            context_window: state.config.agent.context_window_or_default(),
             reserve_tokens: state.config.agent.reserve_tokens,
         };
 
@@ -302,7 +302,7 @@ async fn get_or_create_session(
 
     let agent_config = AgentConfig {
         model: state.config.agent.default_model.clone(),
-        context_window: state.config.agent.context_window,
+        context_window: state.config.agent.context_window_or_default(),
         reserve_tokens: state.config.agent.reserve_tokens,
     };
 
@@ -456,6 +456,7 @@ struct SessionStatusResponse {
     idle_seconds: u64,
     api_input_tokens: u64,
     api_output_tokens: u64,
+    api_reasoning_tokens: u64,
 }
 
 async fn get_session_status(
@@ -475,6 +476,7 @@ async fn get_session_status(
                 idle_seconds: entry.last_accessed.elapsed().as_secs(),
                 api_input_tokens: status.api_input_tokens,
                 api_output_tokens: status.api_output_tokens,
+                api_reasoning_tokens: status.api_reasoning_tokens,
             })
             .into_response()
         }
@@ -707,7 +709,7 @@ async fn chat(State(state): State<Arc<AppState>>, Json(request): Json<ChatReques
             Json(ChatResponse {
                 response,
                 session_id,
-                model: entry.agent.model().to_string(),
+                model: entry.agent.active_model().to_string(),
             })
             .into_response()
         }
@@ -772,6 +774,10 @@ async fn chat_stream(
 
                 while let Some(event) = pinned_stream.next().await {
                     match event {
+                        Ok(StreamEvent::Reasoning(text)) => {
+                            let data = json!({"type": "reasoning", "delta": text});
+                            yield Ok(Event::default().data(data.to_string()));
+                        }
                         Ok(StreamEvent::Content(content)) => {
                             let data = json!({"type": "content", "delta": content});
                             yield Ok(Event::default().data(data.to_string()));
@@ -781,12 +787,27 @@ async fn chat_stream(
                             let data = json!({"type": "tool_start", "name": name, "id": id, "detail": detail});
                             yield Ok(Event::default().data(data.to_string()));
                         }
-                        Ok(StreamEvent::ToolCallEnd { name, id, output }) => {
+                        Ok(StreamEvent::ToolCallEnd { name, id, output, summary }) => {
+                            let data = json!({
+                                "type": "tool_end",
+                                "name": name,
+                                "id": id,
+                                "output": output.chars().take(500).collect::<String>(),
+                                "summary": summary
+                            });
+                            yield Ok(Event::default().data(data.to_string()));
+                        }
+                        Ok(StreamEvent::ProviderTool(ProviderToolEvent::Started { id, name, detail })) => {
+                            let data = json!({"type": "tool_start", "name": name, "id": id, "detail": detail});
+                            yield Ok(Event::default().data(data.to_string()));
+                        }
+                        Ok(StreamEvent::ProviderTool(ProviderToolEvent::Finished { id, name, output, is_error })) => {
                             let data = json!({
                                 "type": "tool_end",
                                 "name": name,
                                 "id": id,
-                                "output": output.chars().take(500).collect::<String>()
+                                "output": output.chars().take(500).collect::<String>(),
+                                "is_error": is_error
                             });
                             yield Ok(Event::default().data(data.to_string()));
                         }
@@ -838,19 +859,19 @@ async fn memory_search(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchQuery>,
 ) -> Response {
-    match memory_search_inner(&state.memory, &query.q, query.limit) {
+    match memory_search_inner(&state.memory, &query.q, query.limit).await {
         Ok(response) => Json(response).into_response(),
         Err(e) => AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
-fn memory_search_inner(
+async fn memory_search_inner(
     memory: &MemoryManager,
     query: &str,
     limit: Option<usize>,
 ) -> Result<SearchResponse, anyhow::Error> {
     let limit = limit.unwrap_or(10);
-    let results = memory.search(query, limit)?;
+    let results = memory.search(query, limit).await?;
 
     let results: Vec<SearchResult> = results
         .into_iter()
@@ -984,7 +1005,7 @@ async fn get_config(State(state): State<Arc<AppState>>) -> Json<ConfigResponse>
     Json(ConfigResponse {
         agent: AgentConfigInfo {
             default_model: state.config.agent.default_model.clone(),
-            context_window: state.config.agent.context_window,
+            context_window: state.config.agent.context_window_or_default(),
             reserve_tokens: state.config.agent.reserve_tokens,
         },
         server: ServerConfigInfo {
@@ -1178,7 +1199,7 @@ async fn get_saved_session(Path(session_id): Path<String>) -> Response {
                 };
 
                 // Extract tool calls
-                let tool_calls = msg["toolCalls"].as_array().map(|arr| arr.clone());
+                let tool_calls = msg["toolCalls"].as_array().cloned();
 
                 // Extract tool result ID
                 let tool_call_id = msg["toolCallId"].as_str().map(String::from);
@@ -1265,7 +1286,7 @@ async fn get_daemon_logs(Query(query): Query<LogsQuery>) -> Response {
     };
 
     let reader = BufReader::new(file);
-    let all_lines: Vec<String> = reader.lines().filter_map(|l| l.ok()).collect();
+    let all_lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
     let total_lines = all_lines.len();
 
     // Get last N lines
@@ -0,0 +1,77 @@
+//! Microphone capture via cpal
+
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::to_mono_16k;
+
+/// Default input device, delivering 16 kHz mono chunks while alive
+pub struct Microphone {
+    _stream: cpal::Stream,
+    rx: mpsc::UnboundedReceiver<Vec<f32>>,
+}
+
+impl Microphone {
+    pub fn open() -> Result<Self> {
+        let host = cpal::default_host();
+        let device = host
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No microphone found"))?;
+        let supported = device.default_input_config()?;
+        let channels = supported.channels();
+        let sample_rate = supported.sample_rate().0;
+        let config: cpal::StreamConfig = supported.config();
+
+        info!(
+            "Listening on {} ({} Hz, {} ch)",
+            device
+                .name()
+                .unwrap_or_else(|_| "default input".to_string()),
+            sample_rate,
+            channels
+        );
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let on_error = |e: cpal::StreamError| warn!("Microphone error: {}", e);
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(to_mono_16k(data, channels, sample_rate));
+                },
+                on_error,
+                None,
+            )?,
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let samples: Vec<f32> =
+                        data.iter().map(|s| *s as f32 / i16::MAX as f32).collect();
+                    let _ = tx.send(to_mono_16k(&samples, channels, sample_rate));
+                },
+                on_error,
+                None,
+            )?,
+            other => anyhow::bail!("Unsupported microphone sample format: {:?}", other),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            rx,
+        })
+    }
+
+    /// Next chunk of 16 kHz mono audio
+    pub async fn next_chunk(&mut self) -> Option<Vec<f32>> {
+        self.rx.recv().await
+    }
+
+    /// A chunk already captured, without waiting
+    pub fn try_chunk(&mut self) -> Option<Vec<f32>> {
+        self.rx.try_recv().ok()
+    }
+}
@@ -0,0 +1,368 @@
+//! Wake word voice mode, dictation and spoken replies
+//!
+//! Microphone audio is converted to 16 kHz mono, split into utterances by an
+//! energy-based speech segmenter, and transcribed locally with whisper.cpp.
+//! An utterance that starts with the wake word (or the one following it) is
+//! submitted as a quick question. No audio leaves the machine.
+//!
+//! The desktop app also records dictation into the chat input, transcribed
+//! locally or with OpenAI (`voice.stt_engine`), and can read replies aloud
+//! (`voice.tts_engine`).
+//!
+//! Capture and local transcription require the `voice` feature; the
+//! segmentation, wake word matching and speech output are plain Rust.
+
+#[cfg(feature = "voice")]
+mod capture;
+mod speech;
+#[cfg(feature = "voice")]
+mod transcribe;
+
+#[cfg(feature = "voice")]
+pub use capture::Microphone;
+pub use speech::{encode_wav, speak, speakable_text, transcribe_openai};
+#[cfg(feature = "voice")]
+pub use transcribe::Transcriber;
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+use crate::config::Config;
+
+/// Sample rate expected by whisper
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Analysis frame length (30 ms)
+const FRAME_SAMPLES: usize = SAMPLE_RATE as usize * 30 / 1000;
+/// Audio kept from before speech starts, so first syllables aren't clipped
+const PRE_ROLL_FRAMES: usize = 10;
+/// Shorter bursts (clicks, taps) are discarded
+const MIN_SPEECH_FRAMES: usize = 8;
+
+/// Downmix interleaved samples to mono and resample to 16 kHz
+pub fn to_mono_16k(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    if sample_rate == SAMPLE_RATE || mono.is_empty() {
+        return mono;
+    }
+
+    // Linear interpolation is plenty for speech recognition
+    let ratio = sample_rate as f64 / SAMPLE_RATE as f64;
+    let out_len = (mono.len() as f64 / ratio) as usize;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = mono[idx];
+            let b = mono.get(idx + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// Microphone recording for dictation, captured on its own thread until
+/// `finish` or the length limit
+pub struct Recording {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Result<Vec<f32>>>,
+}
+
+impl Recording {
+    /// Open the microphone and start recording, up to `max_secs`
+    pub fn start(max_secs: u64) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (opened_tx, opened_rx) = mpsc::channel();
+        let handle = std::thread::spawn({
+            let stop = stop.clone();
+            move || record(&stop, max_secs, opened_tx)
+        });
+        opened_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Recording thread panicked"))??;
+        Ok(Self { stop, handle })
+    }
+
+    /// Stop recording and return the 16 kHz mono audio
+    pub fn finish(self) -> Result<Vec<f32>> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("Recording thread panicked"))?
+    }
+}
+
+#[cfg(feature = "voice")]
+fn record(stop: &AtomicBool, max_secs: u64, opened: mpsc::Sender<Result<()>>) -> Result<Vec<f32>> {
+    let max_samples = max_secs as usize * SAMPLE_RATE as usize;
+    let mut microphone = match Microphone::open() {
+        Ok(microphone) => {
+            let _ = opened.send(Ok(()));
+            microphone
+        }
+        Err(e) => {
+            let _ = opened.send(Err(e));
+            return Ok(Vec::new());
+        }
+    };
+
+    let mut audio = Vec::new();
+    while !stop.load(Ordering::Relaxed) && audio.len() < max_samples {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        while let Some(chunk) = microphone.try_chunk() {
+            audio.extend(chunk);
+        }
+    }
+    audio.truncate(max_samples);
+    Ok(audio)
+}
+
+#[cfg(not(feature = "voice"))]
+fn record(
+    _stop: &AtomicBool,
+    _max_secs: u64,
+    opened: mpsc::Sender<Result<()>>,
+) -> Result<Vec<f32>> {
+    let _ = opened.send(Err(anyhow::anyhow!(
+        "Voice input needs LocalGPT built with the `voice` feature"
+    )));
+    Ok(Vec::new())
+}
+
+/// Transcribe 16 kHz mono audio with `voice.stt_engine`
+pub async fn transcribe(config: &Config, audio: Vec<f32>) -> Result<String> {
+    match config.voice.stt_engine.as_str() {
+        "whisper" => transcribe_local(config, audio).await,
+        "openai" => transcribe_openai(config, &audio).await,
+        other => anyhow::bail!(
+            "Unknown voice.stt_engine '{}' (use \"whisper\" or \"openai\")",
+            other
+        ),
+    }
+}
+
+#[cfg(feature = "voice")]
+async fn transcribe_local(config: &Config, audio: Vec<f32>) -> Result<String> {
+    let voice = config.voice.clone();
+    if voice.whisper_model.is_empty() {
+        anyhow::bail!(
+            "Local transcription needs voice.whisper_model in ~/.localgpt/config.toml \
+             (or set voice.stt_engine = \"openai\")"
+        );
+    }
+    // Transcription is CPU-bound; keep it off the async workers
+    tokio::task::spawn_blocking(move || {
+        Transcriber::new(&voice.whisper_model, &voice.language)?.transcribe(&audio)
+    })
+    .await?
+}
+
+#[cfg(not(feature = "voice"))]
+async fn transcribe_local(_config: &Config, _audio: Vec<f32>) -> Result<String> {
+    anyhow::bail!(
+        "Local transcription needs LocalGPT built with the `voice` feature \
+         (or set voice.stt_engine = \"openai\")"
+    )
+}
+
+/// Splits a 16 kHz audio stream into utterances separated by silence
+pub struct SpeechSegmenter {
+    threshold: f32,
+    silence_frames: usize,
+    max_frames: usize,
+    pending: Vec<f32>,
+    pre_roll: VecDeque<Vec<f32>>,
+    current: Vec<f32>,
+    speech_frames: usize,
+    trailing_silence: usize,
+    in_speech: bool,
+}
+
+impl SpeechSegmenter {
+    pub fn new(threshold: f32, silence_ms: u64, max_utterance_secs: u64) -> Self {
+        Self {
+            threshold,
+            silence_frames: (silence_ms as usize / 30).max(1),
+            max_frames: (max_utterance_secs as usize * 1000 / 30).max(1),
+            pending: Vec::new(),
+            pre_roll: VecDeque::new(),
+            current: Vec::new(),
+            speech_frames: 0,
+            trailing_silence: 0,
+            in_speech: false,
+        }
+    }
+
+    /// Feed audio; returns any utterances completed by it
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let mut completed = Vec::new();
+
+        while self.pending.len() >= FRAME_SAMPLES {
+            let frame: Vec<f32> = self.pending.drain(..FRAME_SAMPLES).collect();
+            if let Some(utterance) = self.push_frame(frame) {
+                completed.push(utterance);
+            }
+        }
+
+        completed
+    }
+
+    fn push_frame(&mut self, frame: Vec<f32>) -> Option<Vec<f32>> {
+        let loud = rms(&frame) >= self.threshold;
+
+        if !self.in_speech {
+            if loud {
+                self.in_speech = true;
+                self.current = self.pre_roll.drain(..).flatten().collect();
+                self.current.extend_from_slice(&frame);
+                self.speech_frames = 1;
+                self.trailing_silence = 0;
+            } else {
+                self.pre_roll.push_back(frame);
+                if self.pre_roll.len() > PRE_ROLL_FRAMES {
+                    self.pre_roll.pop_front();
+                }
+            }
+            return None;
+        }
+
+        self.current.extend_from_slice(&frame);
+        if loud {
+            self.speech_frames += 1;
+            self.trailing_silence = 0;
+        } else {
+            self.trailing_silence += 1;
+        }
+
+        let total_frames = self.current.len() / FRAME_SAMPLES;
+        if self.trailing_silence < self.silence_frames && total_frames < self.max_frames {
+            return None;
+        }
+
+        self.in_speech = false;
+        let utterance = std::mem::take(&mut self.current);
+        (self.speech_frames >= MIN_SPEECH_FRAMES).then_some(utterance)
+    }
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+}
+
+/// Matches a wake phrase at the start of a transcript, tolerant of case,
+/// punctuation, and spacing ("Hey, Local GPT!" matches "hey localgpt")
+pub struct WakeWord {
+    phrase: String,
+}
+
+impl WakeWord {
+    pub fn new(phrase: &str) -> Self {
+        Self {
+            phrase: squash(phrase),
+        }
+    }
+
+    /// If the transcript starts with the wake word, return whatever follows it
+    pub fn strip<'a>(&self, transcript: &'a str) -> Option<&'a str> {
+        if self.phrase.is_empty() {
+            return None;
+        }
+
+        let mut heard = String::new();
+        for (offset, word) in word_spans(transcript) {
+            heard.push_str(&squash(word));
+            if heard == self.phrase {
+                let rest = &transcript[offset + word.len()..];
+                return Some(rest.trim_start_matches(|c: char| !c.is_alphanumeric()));
+            }
+            if !self.phrase.starts_with(&heard) {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Lowercase alphanumerics only
+fn squash(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whitespace-separated words with their byte offsets
+fn word_spans(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_whitespace()
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wake_word_match() {
+        let wake = WakeWord::new("hey localgpt");
+        assert_eq!(
+            wake.strip("Hey, Local GPT! What's the weather?"),
+            Some("What's the weather?")
+        );
+        assert_eq!(wake.strip("hey localgpt."), Some(""));
+        assert_eq!(wake.strip("hey there localgpt"), None);
+        assert_eq!(wake.strip("Hey"), None);
+    }
+
+    #[test]
+    fn test_segmenter_splits_on_silence() {
+        let mut segmenter = SpeechSegmenter::new(0.1, 300, 15);
+        let speech = vec![0.5f32; FRAME_SAMPLES * 20];
+        let silence = vec![0.0f32; FRAME_SAMPLES * 15];
+
+        assert!(segmenter.push(&silence).is_empty());
+        assert!(segmenter.push(&speech).is_empty());
+        let utterances = segmenter.push(&silence);
+        assert_eq!(utterances.len(), 1);
+        // Pre-roll + speech + trailing silence
+        assert_eq!(utterances[0].len(), FRAME_SAMPLES * (10 + 20 + 10));
+
+        // A short click is not an utterance
+        assert!(segmenter.push(&vec![0.5f32; FRAME_SAMPLES * 2]).is_empty());
+        assert!(segmenter.push(&silence).is_empty());
+    }
+
+    #[test]
+    fn test_to_mono_16k() {
+        let stereo_48k: Vec<f32> = (0..4800).flat_map(|_| [0.2f32, 0.4]).collect();
+        let out = to_mono_16k(&stereo_48k, 2, 48_000);
+        assert_eq!(out.len(), 1600);
+        assert!((out[0] - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_speech_output_helpers() {
+        let reply = "## Steps\n\nRun **cargo build**, see [the docs](https://example.com).\n\n\
+                     ```sh\ncargo build --release\n```\n- Then `cargo test`";
+        assert_eq!(
+            speakable_text(reply),
+            "Steps\nRun cargo build, see the docs.\nThen cargo test"
+        );
+
+        let wav = encode_wav(&[0.0, 1.0, -1.0]);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 6);
+        assert_eq!(&wav[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80]);
+    }
+}
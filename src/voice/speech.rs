@@ -0,0 +1,250 @@
+//! Dictation via OpenAI's transcription API and spoken replies
+//!
+//! Replies are spoken with the platform's speech command (`say` on macOS,
+//! `espeak-ng`/`espeak` on Linux, System.Speech on Windows) or synthesized
+//! by OpenAI's speech API and played with the platform's audio player.
+
+use anyhow::Result;
+use reqwest::multipart::{Form, Part};
+use serde_json::{json, Value};
+use std::io::ErrorKind;
+use std::path::Path;
+use tokio::process::Command;
+
+use super::SAMPLE_RATE;
+use crate::config::{Config, OpenAIConfig};
+
+/// OpenAI's speech API limit on input length
+const MAX_OPENAI_SPEECH_CHARS: usize = 4096;
+
+/// Encode 16 kHz mono samples as a 16-bit PCM WAV file
+pub fn encode_wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+/// Transcribe 16 kHz mono audio with OpenAI's transcription API
+pub async fn transcribe_openai(config: &Config, audio: &[f32]) -> Result<String> {
+    let openai = openai_config(config, "transcription")?;
+    let file = Part::bytes(encode_wav(audio))
+        .file_name("speech.wav")
+        .mime_str("audio/wav")?;
+    let mut form = Form::new()
+        .text("model", config.voice.transcription_model.clone())
+        .part("file", file);
+    if config.voice.language != "auto" {
+        form = form.text("language", config.voice.language.clone());
+    }
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/audio/transcriptions",
+            openai.base_url.trim_end_matches('/')
+        ))
+        .bearer_auth(&openai.api_key)
+        .multipart(form)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        anyhow::bail!(
+            "OpenAI transcription failed ({}): {}",
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+    let body: Value = response.json().await?;
+    Ok(body["text"].as_str().unwrap_or_default().trim().to_string())
+}
+
+/// Read text aloud with `voice.tts_engine`, returning when it finishes
+pub async fn speak(config: &Config, text: &str) -> Result<()> {
+    let text = speakable_text(text);
+    if text.is_empty() {
+        return Ok(());
+    }
+    match config.voice.tts_engine.as_str() {
+        "system" => speak_system(&text).await,
+        "openai" => speak_openai(config, &text).await,
+        other => anyhow::bail!(
+            "Unknown voice.tts_engine '{}' (use \"system\" or \"openai\")",
+            other
+        ),
+    }
+}
+
+/// Markdown reduced to the prose worth reading aloud: code blocks, link
+/// targets and formatting characters are dropped
+pub fn speakable_text(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || trimmed.is_empty() {
+            continue;
+        }
+        let line = trimmed
+            .trim_start_matches(['#', '>', '-', '*', '+', ' '])
+            .trim_start();
+        lines.push(strip_inline_markup(line));
+    }
+    lines.join("\n")
+}
+
+/// Drop emphasis and code markers, and keep only the text of links
+fn strip_inline_markup(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '_' | '`' | '~' => {}
+            ']' if chars.peek() == Some(&'(') => {
+                for c in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            '[' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+async fn speak_system(text: &str) -> Result<()> {
+    if cfg!(target_os = "macos") {
+        return run("say", &[text]).await;
+    }
+    if cfg!(windows) {
+        let script = "Add-Type -AssemblyName System.Speech; \
+             (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())";
+        return run_with_stdin("powershell", &["-NoProfile", "-Command", script], text).await;
+    }
+    match run("espeak-ng", &[text]).await {
+        Err(e) if is_not_found(&e) => run("espeak", &[text]).await.map_err(|e| {
+            if is_not_found(&e) {
+                anyhow::anyhow!(
+                    "No speech command found. Install espeak-ng, or set \
+                     voice.tts_engine = \"openai\""
+                )
+            } else {
+                e
+            }
+        }),
+        result => result,
+    }
+}
+
+async fn speak_openai(config: &Config, text: &str) -> Result<()> {
+    let openai = openai_config(config, "speech")?;
+    let input: String = text.chars().take(MAX_OPENAI_SPEECH_CHARS).collect();
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/audio/speech",
+            openai.base_url.trim_end_matches('/')
+        ))
+        .bearer_auth(&openai.api_key)
+        .json(&json!({
+            "model": config.voice.tts_model,
+            "voice": config.voice.tts_voice,
+            "input": input,
+            "response_format": "wav",
+        }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        anyhow::bail!(
+            "OpenAI speech failed ({}): {}",
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+    let audio = response.bytes().await?;
+
+    let path = std::env::temp_dir().join(format!("localgpt-speech-{}.wav", std::process::id()));
+    tokio::fs::write(&path, &audio).await?;
+    let result = play_wav(&path).await;
+    let _ = tokio::fs::remove_file(&path).await;
+    result
+}
+
+async fn play_wav(path: &Path) -> Result<()> {
+    let file = path.to_string_lossy();
+    if cfg!(target_os = "macos") {
+        return run("afplay", &[&file]).await;
+    }
+    if cfg!(windows) {
+        let script = format!(
+            "(New-Object Media.SoundPlayer '{}').PlaySync()",
+            file.replace('\'', "''")
+        );
+        return run("powershell", &["-NoProfile", "-Command", &script]).await;
+    }
+    match run("paplay", &[&file]).await {
+        Err(e) if is_not_found(&e) => run("aplay", &["-q", &file]).await,
+        result => result,
+    }
+}
+
+fn openai_config<'a>(config: &'a Config, feature: &str) -> Result<&'a OpenAIConfig> {
+    config.providers.openai.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "OpenAI {} requires [providers.openai] in ~/.localgpt/config.toml",
+            feature
+        )
+    })
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program).args(args).status().await?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+async fn run_with_stdin(program: &str, args: &[&str], input: &str) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).await?;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::NotFound)
+}
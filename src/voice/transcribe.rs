@@ -0,0 +1,52 @@
+//! Local speech-to-text via whisper.cpp
+
+use anyhow::Result;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+pub struct Transcriber {
+    ctx: WhisperContext,
+    language: String,
+}
+
+impl Transcriber {
+    /// Load a ggml whisper model (e.g. ggml-base.en.bin)
+    pub fn new(model_path: &str, language: &str) -> Result<Self> {
+        let model_path = shellexpand::tilde(model_path).to_string();
+        if !std::path::Path::new(&model_path).exists() {
+            anyhow::bail!(
+                "Whisper model not found at {}. Download one from \
+                 https://huggingface.co/ggerganov/whisper.cpp and set voice.whisper_model",
+                model_path
+            );
+        }
+
+        let ctx = WhisperContext::new_with_params(&model_path, WhisperContextParameters::default())
+            .map_err(|e| anyhow::anyhow!("Failed to load whisper model: {}", e))?;
+
+        Ok(Self {
+            ctx,
+            language: language.to_string(),
+        })
+    }
+
+    /// Transcribe 16 kHz mono audio
+    pub fn transcribe(&self, audio: &[f32]) -> Result<String> {
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(self.language.as_str()));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+        params.set_no_context(true);
+        params.set_suppress_blank(true);
+
+        let mut state = self.ctx.create_state()?;
+        state.full(params, audio)?;
+
+        let mut text = String::new();
+        for i in 0..state.full_n_segments()? {
+            text.push_str(&state.full_get_segment_text_lossy(i)?);
+        }
+        Ok(text.trim().to_string())
+    }
+}